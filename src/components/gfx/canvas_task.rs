@@ -0,0 +1,367 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The task that draws what script does with a `<canvas>`'s 2D context. Each canvas that has a
+//! context gets a task of its own, holding a draw target the size of the canvas. Script sends it
+//! drawing commands as they're made, and layout asks it for what's been drawn so far, to show as
+//! the canvas's image.
+
+use color::{Color, rgb};
+use font::{FontStyle, FontWeight400};
+use font_context::FontContext;
+use geometry::Au;
+use opts::{GrayscaleTextAntialiasing, LightTextHinting};
+use path;
+use path::PathBuilder;
+use text::TextRun;
+
+use azure::AzFloat;
+use azure::azure_hl::{B8G8R8A8, ColorPattern, DrawOptions, DrawSurfaceOptions, DrawTarget};
+use azure::azure_hl::{Linear, SkiaBackend, SourceSurfaceMethods};
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use servo_net::image::base::Image;
+use servo_net::image_cache_task::{AnimatedImageReady, Decode, ImageCacheTask, ImageReady};
+use servo_net::image_cache_task::{Prefetch, WaitForImage};
+use servo_util::range::Range;
+use servo_util::time::ProfilerChan;
+use extra::arc::Arc;
+use extra::url::Url;
+
+use std::cell::Cell;
+use std::comm::{Chan, Port, SharedChan, stream};
+use std::libc::types::common::c99::uint16_t;
+use std::uint;
+use std::vec;
+
+pub enum CanvasMsg {
+    /// Fills a rectangle with the fill style.
+    FillRect(Rect<f32>),
+    /// Clears a rectangle to transparent black.
+    ClearRect(Rect<f32>),
+    /// Outlines a rectangle with the stroke style.
+    StrokeRect(Rect<f32>),
+    /// Throws the current path away and starts an empty one.
+    BeginPath,
+    /// Closes the current subpath, with a line back to where it started.
+    ClosePath,
+    MoveTo(Point2D<f32>),
+    LineTo(Point2D<f32>),
+    /// Adds a closed subpath going round a rectangle to the current path.
+    RectPath(Rect<f32>),
+    /// Fills the current path with the fill style, by the nonzero winding rule.
+    Fill,
+    /// Outlines the current path with the stroke style.
+    Stroke,
+    /// Draws text, filled with the fill style, with its baseline starting at the given point.
+    FillText(~str, Point2D<f32>),
+    /// Draws text in the stroke style, with its baseline starting at the given point.
+    StrokeText(~str, Point2D<f32>),
+    /// Draws the image at a URL with its top left corner at the given point, scaled to the given
+    /// size if there is one. If there's a source rect, only that part of the image is drawn. The
+    /// image is loaded if it hasn't been; nothing is drawn if it can't be.
+    DrawImage(Url, Point2D<f32>, Option<Size2D<f32>>, Option<Rect<f32>>),
+    SetFillStyle(Color),
+    SetStrokeStyle(Color),
+    SetLineWidth(f32),
+    SetFont(FontStyle),
+    /// Throws away everything drawn and starts over at a new size, as setting the canvas's width
+    /// or height does.
+    Recreate(Size2D<i32>),
+    /// Sends back a copy of what's been drawn so far.
+    SendImage(Chan<Arc<~Image>>),
+    /// Sends back the pixels of a rect of the canvas as rows of straight (not premultiplied)
+    /// RGBA, as `ImageData` holds them. Pixels outside the canvas are transparent black.
+    GetImageData(Rect<i32>, Chan<~[u8]>),
+    /// Replaces the pixels of a rect of the canvas with rows of straight RGBA, the size of the
+    /// rect.
+    PutImageData(~[u8], Rect<i32>),
+    Close,
+}
+
+pub type CanvasTask = SharedChan<CanvasMsg>;
+
+/// Starts the task for a canvas of the given size.
+pub fn CanvasTask(size: Size2D<i32>,
+                  image_cache_task: ImageCacheTask,
+                  profiler_chan: ProfilerChan)
+                  -> CanvasTask {
+    let (port, chan) = stream();
+    let port = Cell::new(port);
+    do spawn {
+        let mut renderer = CanvasRenderer::new(port.take(),
+                                               size,
+                                               image_cache_task.clone(),
+                                               profiler_chan.clone());
+        renderer.start();
+    }
+    SharedChan::new(chan)
+}
+
+struct CanvasRenderer {
+    port: Port<CanvasMsg>,
+    /// Drawn in software, so that the pixels can be read back.
+    draw_target: DrawTarget,
+    size: Size2D<i32>,
+    image_cache_task: ImageCacheTask,
+    font_ctx: @mut FontContext,
+    path: PathBuilder,
+    fill_color: Color,
+    stroke_color: Color,
+    line_width: f32,
+    font: FontStyle,
+}
+
+impl CanvasRenderer {
+    fn new(port: Port<CanvasMsg>,
+           size: Size2D<i32>,
+           image_cache_task: ImageCacheTask,
+           profiler_chan: ProfilerChan)
+           -> CanvasRenderer {
+        let renderer = CanvasRenderer {
+            port: port,
+            draw_target: DrawTarget::new(SkiaBackend, size, B8G8R8A8),
+            size: size,
+            image_cache_task: image_cache_task,
+            font_ctx: @mut FontContext::new(SkiaBackend, true, profiler_chan),
+            path: PathBuilder::new(),
+            fill_color: rgb(0, 0, 0),
+            stroke_color: rgb(0, 0, 0),
+            line_width: 1.0,
+            // The initial value of `font` is "10px sans-serif".
+            font: FontStyle {
+                pt_size: 10f,
+                weight: FontWeight400,
+                italic: false,
+                oblique: false,
+                families: ~"sans-serif",
+            },
+        };
+        renderer.clear();
+        renderer
+    }
+
+    fn start(&mut self) {
+        loop {
+            match self.port.recv() {
+                FillRect(rect) => self.fill_rect(&rect),
+                ClearRect(rect) => {
+                    self.draw_target.clear_rect(&to_azure_rect(&rect));
+                }
+                StrokeRect(rect) => {
+                    let mut outline = PathBuilder::new();
+                    outline.rect(&rect);
+                    self.stroke(outline.subpaths);
+                }
+                BeginPath => self.path = PathBuilder::new(),
+                ClosePath => self.path.close(),
+                MoveTo(point) => self.path.move_to(point),
+                LineTo(point) => self.path.line_to(point),
+                RectPath(rect) => self.path.rect(&rect),
+                Fill => {
+                    path::fill(&self.draw_target, self.path.subpaths, Point2D(0.0, 0.0),
+                               self.fill_color)
+                }
+                Stroke => self.stroke(self.path.subpaths),
+                FillText(text, origin) => self.draw_text(text, origin, self.fill_color),
+                StrokeText(text, origin) => {
+                    // FIXME: The glyphs should be outlined, but there's no way to get their
+                    // outlines; fill them in the stroke style instead.
+                    self.draw_text(text, origin, self.stroke_color)
+                }
+                DrawImage(url, origin, size, source) => {
+                    self.draw_image(url, origin, size, source)
+                }
+                SetFillStyle(color) => self.fill_color = color,
+                SetStrokeStyle(color) => self.stroke_color = color,
+                SetLineWidth(width) => self.line_width = width,
+                SetFont(style) => self.font = style,
+                Recreate(size) => {
+                    self.draw_target = DrawTarget::new(SkiaBackend, size, B8G8R8A8);
+                    self.size = size;
+                    self.path = PathBuilder::new();
+                    self.clear();
+                }
+                SendImage(response) => response.send(self.snapshot()),
+                GetImageData(rect, response) => response.send(self.get_image_data(&rect)),
+                PutImageData(data, rect) => self.put_image_data(data, &rect),
+                Close => break,
+            }
+        }
+    }
+
+    /// Clears the whole canvas to transparent black.
+    fn clear(&self) {
+        let rect = Rect(Point2D(0.0, 0.0),
+                        Size2D(self.size.width as f32, self.size.height as f32));
+        self.draw_target.clear_rect(&to_azure_rect(&rect));
+    }
+
+    fn fill_rect(&self, rect: &Rect<f32>) {
+        self.draw_target.fill_rect(&to_azure_rect(rect), &ColorPattern(self.fill_color));
+    }
+
+    fn stroke(&self, subpaths: &[path::Subpath]) {
+        path::stroke(&self.draw_target, subpaths, Point2D(0.0, 0.0), self.stroke_color,
+                     self.line_width);
+    }
+
+    fn draw_text(&self, text: ~str, origin: Point2D<f32>, color: Color) {
+        if text.is_empty() {
+            return;
+        }
+        let font = self.font_ctx.get_resolved_font_for_style(&self.font).fonts[0];
+        let run = TextRun::new(font, text, false);
+        let range = Range::new(0, run.char_len());
+        let baseline_origin = Point2D(Au::from_frac_px(origin.x as float),
+                                      Au::from_frac_px(origin.y as float));
+        // Canvases can be transparent, which subpixel antialiasing needs them not to be.
+        font.draw_text_into_target(&self.draw_target, &run, &range, baseline_origin, color, 1.0,
+                                   GrayscaleTextAntialiasing, LightTextHinting);
+    }
+
+    fn draw_image(&self,
+                  url: Url,
+                  origin: Point2D<f32>,
+                  size: Option<Size2D<f32>>,
+                  source: Option<Rect<f32>>) {
+        let image = match self.load_image(url) {
+            Some(image) => image,
+            None => return,
+        };
+        let image = image.get();
+        let image_size = Size2D(image.width as i32, image.height as i32);
+        let stride = image.width * 4;
+        let surface = self.draw_target.create_source_surface_from_data(image.data, image_size,
+                                                                       stride as i32, B8G8R8A8);
+        let source = source.unwrap_or_default(Rect(Point2D(0.0, 0.0),
+                                                   Size2D(image.width as f32,
+                                                          image.height as f32)));
+        let dest = Rect(origin, size.unwrap_or_default(source.size));
+        self.draw_target.draw_surface(surface,
+                                      to_azure_rect(&dest),
+                                      to_azure_rect(&source),
+                                      DrawSurfaceOptions(Linear, true),
+                                      DrawOptions(1.0 as AzFloat, 0 as uint16_t));
+    }
+
+    /// Gets the image at `url` from the image cache, waiting for it to load if it hasn't. An
+    /// animated image gives its first frame.
+    fn load_image(&self, url: Url) -> Option<Arc<~Image>> {
+        self.image_cache_task.send(Prefetch(url.clone()));
+        self.image_cache_task.send(Decode(url.clone()));
+        let (response_port, response_chan) = stream();
+        self.image_cache_task.send(WaitForImage(url, response_chan));
+        match response_port.recv() {
+            ImageReady(image) => Some(image),
+            AnimatedImageReady(animation) => Some(animation.get().frames[0].image.clone()),
+            _ => None,
+        }
+    }
+
+    /// Copies what's been drawn so far into an image.
+    fn snapshot(&self) -> Arc<~Image> {
+        let width = self.size.width as uint;
+        let height = self.size.height as uint;
+        let mut data = vec::with_capacity(width * height * 4);
+        let surface = self.draw_target.snapshot().get_data_surface();
+        let stride = surface.stride() as uint;
+        do surface.with_data |pixels| {
+            for row in range(0, height) {
+                data.push_all(pixels.slice(row * stride, row * stride + width * 4));
+            }
+        }
+        Arc::new(~Image(width, height, 4, data))
+    }
+
+    fn get_image_data(&self, rect: &Rect<i32>) -> ~[u8] {
+        let width = rect.size.width as uint;
+        let height = rect.size.height as uint;
+        let mut data = vec::from_elem(width * height * 4, 0u8);
+        let surface = self.draw_target.snapshot().get_data_surface();
+        let stride = surface.stride() as uint;
+        do surface.with_data |pixels| {
+            for y in range(0, height) {
+                let canvas_y = rect.origin.y + y as i32;
+                if canvas_y < 0 || canvas_y >= self.size.height {
+                    loop
+                }
+                for x in range(0, width) {
+                    let canvas_x = rect.origin.x + x as i32;
+                    if canvas_x < 0 || canvas_x >= self.size.width {
+                        loop
+                    }
+                    let from = canvas_y as uint * stride + canvas_x as uint * 4;
+                    let to = (y * width + x) * 4;
+                    bgra_to_rgba(pixels.slice(from, from + 4), data.mut_slice(to, to + 4));
+                }
+            }
+        }
+        data
+    }
+
+    fn put_image_data(&self, data: &[u8], rect: &Rect<i32>) {
+        let mut pixels = vec::from_elem(data.len(), 0u8);
+        for i in range(0, data.len() / 4) {
+            rgba_to_bgra(data.slice(i * 4, i * 4 + 4), pixels.mut_slice(i * 4, i * 4 + 4));
+        }
+        let surface = self.draw_target.create_source_surface_from_data(pixels, rect.size,
+                                                                       rect.size.width * 4,
+                                                                       B8G8R8A8);
+        let dest = Rect(Point2D(rect.origin.x as f32, rect.origin.y as f32),
+                        Size2D(rect.size.width as f32, rect.size.height as f32));
+        let source = Rect(Point2D(0.0, 0.0), dest.size);
+        // The pixels replace what was there, rather than being drawn over it.
+        self.draw_target.clear_rect(&to_azure_rect(&dest));
+        self.draw_target.draw_surface(surface,
+                                      to_azure_rect(&dest),
+                                      to_azure_rect(&source),
+                                      DrawSurfaceOptions(Linear, true),
+                                      DrawOptions(1.0 as AzFloat, 0 as uint16_t));
+    }
+}
+
+/// Converts a pixel of premultiplied BGRA, as the draw target keeps them, to straight RGBA.
+/// Colors of transparent pixels are lost.
+pub fn bgra_to_rgba(bgra: &[u8], rgba: &mut [u8]) {
+    let alpha = bgra[3] as uint;
+    for i in range(0u, 3) {
+        rgba[i] = if alpha == 0 {
+            0
+        } else {
+            uint::min((bgra[2 - i] as uint * 255 + alpha / 2) / alpha, 255) as u8
+        };
+    }
+    rgba[3] = bgra[3];
+}
+
+/// Converts a pixel of straight RGBA to premultiplied BGRA.
+pub fn rgba_to_bgra(rgba: &[u8], bgra: &mut [u8]) {
+    let alpha = rgba[3] as uint;
+    for i in range(0u, 3) {
+        bgra[2 - i] = ((rgba[i] as uint * alpha + 127) / 255) as u8;
+    }
+    bgra[3] = rgba[3];
+}
+
+fn to_azure_rect(rect: &Rect<f32>) -> Rect<AzFloat> {
+    Rect(Point2D(rect.origin.x as AzFloat, rect.origin.y as AzFloat),
+         Size2D(rect.size.width as AzFloat, rect.size.height as AzFloat))
+}
+
+#[test]
+fn test_pixel_conversions() {
+    let mut rgba = [0u8, ..4];
+    bgra_to_rgba([0x40, 0x20, 0x80, 0x80], rgba);
+    assert!(rgba == [0xff, 0x40, 0x80, 0x80]);
+    bgra_to_rgba([0x10, 0x10, 0x10, 0x00], rgba);
+    assert!(rgba == [0, 0, 0, 0]);
+
+    let mut bgra = [0u8, ..4];
+    rgba_to_bgra([0xff, 0x40, 0x80, 0x80], bgra);
+    assert!(bgra == [0x40, 0x20, 0x80, 0x80]);
+    rgba_to_bgra([0x12, 0x34, 0x56, 0xff], bgra);
+    assert!(bgra == [0x56, 0x34, 0x12, 0xff]);
+}
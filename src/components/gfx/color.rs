@@ -5,6 +5,11 @@
 use azure::AzFloat;
 use AzColor = azure::azure_hl::Color;
 
+use std::ascii::StrAsciiExt;
+use std::float;
+use std::num::{Orderable, Round};
+use std::u8;
+
 pub type Color = AzColor;
 
 pub fn rgb(r: u8, g: u8, b: u8) -> AzColor {
@@ -20,3 +25,382 @@ pub fn rgba(r: u8, g: u8, b: u8, a: float) -> AzColor {
     }
 }
 
+/// A color in sRGB, with alpha between 0 and 1.
+#[deriving(Clone, Eq)]
+pub struct RGBA {
+    red: u8,
+    green: u8,
+    blue: u8,
+    alpha: float,
+}
+
+impl RGBA {
+    pub fn new(red: u8, green: u8, blue: u8, alpha: float) -> RGBA {
+        RGBA {
+            red: red,
+            green: green,
+            blue: blue,
+            alpha: alpha,
+        }
+    }
+
+    pub fn to_gfx_color(&self) -> Color {
+        rgba(self.red, self.green, self.blue, self.alpha)
+    }
+
+    /// Parses a color value. Returns `None` for invalid values, and for `currentColor`, which
+    /// has to be resolved against the `color` property.
+    pub fn parse(value: &str) -> Option<RGBA> {
+        let value = value.trim().to_ascii_lower();
+        if value.starts_with("#") {
+            return parse_hex(value.slice_from(1));
+        }
+        match value.find('(') {
+            Some(open) if value.ends_with(")") => {
+                let name = value.slice_to(open).trim();
+                let arguments: ~[&str] = value.slice(open + 1, value.len() - 1)
+                                              .split_iter(',')
+                                              .map(|argument| argument.trim())
+                                              .collect();
+                return match name {
+                    "rgb" | "rgba" => parse_rgb(arguments),
+                    "hsl" | "hsla" => parse_hsl(arguments),
+                    _ => None,
+                };
+            }
+            Some(_) => return None,
+            None => {}
+        }
+        if value.as_slice() == "transparent" {
+            return Some(RGBA::new(0, 0, 0, 0.0));
+        }
+        for &(name, red, green, blue) in NAMED_COLORS.iter() {
+            if value.as_slice() == name {
+                return Some(RGBA::new(red, green, blue, 1.0));
+            }
+        }
+        None
+    }
+}
+
+/// Parses the digits of `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa`.
+fn parse_hex(digits: &str) -> Option<RGBA> {
+    let mut values = ~[];
+    for c in digits.iter() {
+        match c.to_digit(16) {
+            Some(value) => values.push(value as u8),
+            None => return None,
+        }
+    }
+    let channels: ~[u8] = match values.len() {
+        3 | 4 => values.iter().map(|&value| value * 17).collect(),
+        6 | 8 => range(0, values.len() / 2).map(|i| values[i * 2] * 16 + values[i * 2 + 1])
+                                           .collect(),
+        _ => return None,
+    };
+    let alpha = if channels.len() == 4 { channels[3] as float / 255.0 } else { 1.0 };
+    Some(RGBA::new(channels[0], channels[1], channels[2], alpha))
+}
+
+/// Parses the arguments of `rgb()` and `rgba()`: three numbers or three percentages, and an
+/// optional alpha.
+fn parse_rgb(arguments: &[&str]) -> Option<RGBA> {
+    if arguments.len() != 3 && arguments.len() != 4 {
+        return None;
+    }
+    let percentages = arguments[0].ends_with("%");
+    let mut channels = [0u8, 0, 0];
+    for i in range(0, 3) {
+        if arguments[i].ends_with("%") != percentages {
+            return None;
+        }
+        let value = if percentages {
+            match parse_percentage(arguments[i]) {
+                Some(value) => value * 255.0,
+                None => return None,
+            }
+        } else {
+            match float::from_str(arguments[i]) {
+                Some(value) => value,
+                None => return None,
+            }
+        };
+        channels[i] = clamp_to_u8(value);
+    }
+    let alpha = match parse_alpha(arguments) {
+        Some(alpha) => alpha,
+        None => return None,
+    };
+    Some(RGBA::new(channels[0], channels[1], channels[2], alpha))
+}
+
+/// Parses the arguments of `hsl()` and `hsla()`: a hue in degrees, a saturation and a lightness,
+/// and an optional alpha.
+fn parse_hsl(arguments: &[&str]) -> Option<RGBA> {
+    if arguments.len() != 3 && arguments.len() != 4 {
+        return None;
+    }
+    let hue = match float::from_str(arguments[0]) {
+        Some(hue) => hue,
+        None => return None,
+    };
+    let (saturation, lightness) = match (parse_percentage(arguments[1]),
+                                         parse_percentage(arguments[2])) {
+        (Some(saturation), Some(lightness)) => (clamp_unit(saturation), clamp_unit(lightness)),
+        _ => return None,
+    };
+    let alpha = match parse_alpha(arguments) {
+        Some(alpha) => alpha,
+        None => return None,
+    };
+
+    // The algorithm from CSS Color Level 3 § 4.2.4.
+    let hue = (((hue % 360.0) + 360.0) % 360.0) / 360.0;
+    let m2 = if lightness <= 0.5 {
+        lightness * (saturation + 1.0)
+    } else {
+        lightness + saturation - lightness * saturation
+    };
+    let m1 = lightness * 2.0 - m2;
+    let red = hue_to_rgb(m1, m2, hue + 1.0 / 3.0);
+    let green = hue_to_rgb(m1, m2, hue);
+    let blue = hue_to_rgb(m1, m2, hue - 1.0 / 3.0);
+    Some(RGBA::new(clamp_to_u8(red * 255.0),
+                   clamp_to_u8(green * 255.0),
+                   clamp_to_u8(blue * 255.0),
+                   alpha))
+}
+
+fn hue_to_rgb(m1: float, m2: float, hue: float) -> float {
+    let hue = if hue < 0.0 { hue + 1.0 } else if hue > 1.0 { hue - 1.0 } else { hue };
+    if hue * 6.0 < 1.0 {
+        m1 + (m2 - m1) * hue * 6.0
+    } else if hue * 2.0 < 1.0 {
+        m2
+    } else if hue * 3.0 < 2.0 {
+        m1 + (m2 - m1) * (2.0 / 3.0 - hue) * 6.0
+    } else {
+        m1
+    }
+}
+
+/// Parses the optional fourth argument of a color function. It defaults to opaque.
+fn parse_alpha(arguments: &[&str]) -> Option<float> {
+    if arguments.len() < 4 {
+        return Some(1.0);
+    }
+    let alpha = if arguments[3].ends_with("%") {
+        parse_percentage(arguments[3])
+    } else {
+        float::from_str(arguments[3])
+    };
+    alpha.map_move(clamp_unit)
+}
+
+/// Parses a percentage into a fraction, so that `50%` is 0.5.
+fn parse_percentage(value: &str) -> Option<float> {
+    if !value.ends_with("%") {
+        return None;
+    }
+    float::from_str(value.slice_to(value.len() - 1).trim()).map_move(|value| value / 100.0)
+}
+
+fn clamp_unit(value: float) -> float {
+    value.max(&0.0).min(&1.0)
+}
+
+fn clamp_to_u8(value: float) -> u8 {
+    value.round().max(&0.0).min(&(u8::max_value as float)) as u8
+}
+
+/// The color keywords of CSS Color Level 3 § 4.3, plus `rebeccapurple` from Level 4.
+static NAMED_COLORS: &'static [(&'static str, u8, u8, u8)] = &[
+    ("aliceblue", 240, 248, 255),
+    ("antiquewhite", 250, 235, 215),
+    ("aqua", 0, 255, 255),
+    ("aquamarine", 127, 255, 212),
+    ("azure", 240, 255, 255),
+    ("beige", 245, 245, 220),
+    ("bisque", 255, 228, 196),
+    ("black", 0, 0, 0),
+    ("blanchedalmond", 255, 235, 205),
+    ("blue", 0, 0, 255),
+    ("blueviolet", 138, 43, 226),
+    ("brown", 165, 42, 42),
+    ("burlywood", 222, 184, 135),
+    ("cadetblue", 95, 158, 160),
+    ("chartreuse", 127, 255, 0),
+    ("chocolate", 210, 105, 30),
+    ("coral", 255, 127, 80),
+    ("cornflowerblue", 100, 149, 237),
+    ("cornsilk", 255, 248, 220),
+    ("crimson", 220, 20, 60),
+    ("cyan", 0, 255, 255),
+    ("darkblue", 0, 0, 139),
+    ("darkcyan", 0, 139, 139),
+    ("darkgoldenrod", 184, 134, 11),
+    ("darkgray", 169, 169, 169),
+    ("darkgreen", 0, 100, 0),
+    ("darkgrey", 169, 169, 169),
+    ("darkkhaki", 189, 183, 107),
+    ("darkmagenta", 139, 0, 139),
+    ("darkolivegreen", 85, 107, 47),
+    ("darkorange", 255, 140, 0),
+    ("darkorchid", 153, 50, 204),
+    ("darkred", 139, 0, 0),
+    ("darksalmon", 233, 150, 122),
+    ("darkseagreen", 143, 188, 143),
+    ("darkslateblue", 72, 61, 139),
+    ("darkslategray", 47, 79, 79),
+    ("darkslategrey", 47, 79, 79),
+    ("darkturquoise", 0, 206, 209),
+    ("darkviolet", 148, 0, 211),
+    ("deeppink", 255, 20, 147),
+    ("deepskyblue", 0, 191, 255),
+    ("dimgray", 105, 105, 105),
+    ("dimgrey", 105, 105, 105),
+    ("dodgerblue", 30, 144, 255),
+    ("firebrick", 178, 34, 34),
+    ("floralwhite", 255, 250, 240),
+    ("forestgreen", 34, 139, 34),
+    ("fuchsia", 255, 0, 255),
+    ("gainsboro", 220, 220, 220),
+    ("ghostwhite", 248, 248, 255),
+    ("gold", 255, 215, 0),
+    ("goldenrod", 218, 165, 32),
+    ("gray", 128, 128, 128),
+    ("green", 0, 128, 0),
+    ("greenyellow", 173, 255, 47),
+    ("grey", 128, 128, 128),
+    ("honeydew", 240, 255, 240),
+    ("hotpink", 255, 105, 180),
+    ("indianred", 205, 92, 92),
+    ("indigo", 75, 0, 130),
+    ("ivory", 255, 255, 240),
+    ("khaki", 240, 230, 140),
+    ("lavender", 230, 230, 250),
+    ("lavenderblush", 255, 240, 245),
+    ("lawngreen", 124, 252, 0),
+    ("lemonchiffon", 255, 250, 205),
+    ("lightblue", 173, 216, 230),
+    ("lightcoral", 240, 128, 128),
+    ("lightcyan", 224, 255, 255),
+    ("lightgoldenrodyellow", 250, 250, 210),
+    ("lightgray", 211, 211, 211),
+    ("lightgreen", 144, 238, 144),
+    ("lightgrey", 211, 211, 211),
+    ("lightpink", 255, 182, 193),
+    ("lightsalmon", 255, 160, 122),
+    ("lightseagreen", 32, 178, 170),
+    ("lightskyblue", 135, 206, 250),
+    ("lightslategray", 119, 136, 153),
+    ("lightslategrey", 119, 136, 153),
+    ("lightsteelblue", 176, 196, 222),
+    ("lightyellow", 255, 255, 224),
+    ("lime", 0, 255, 0),
+    ("limegreen", 50, 205, 50),
+    ("linen", 250, 240, 230),
+    ("magenta", 255, 0, 255),
+    ("maroon", 128, 0, 0),
+    ("mediumaquamarine", 102, 205, 170),
+    ("mediumblue", 0, 0, 205),
+    ("mediumorchid", 186, 85, 211),
+    ("mediumpurple", 147, 112, 219),
+    ("mediumseagreen", 60, 179, 113),
+    ("mediumslateblue", 123, 104, 238),
+    ("mediumspringgreen", 0, 250, 154),
+    ("mediumturquoise", 72, 209, 204),
+    ("mediumvioletred", 199, 21, 133),
+    ("midnightblue", 25, 25, 112),
+    ("mintcream", 245, 255, 250),
+    ("mistyrose", 255, 228, 225),
+    ("moccasin", 255, 228, 181),
+    ("navajowhite", 255, 222, 173),
+    ("navy", 0, 0, 128),
+    ("oldlace", 253, 245, 230),
+    ("olive", 128, 128, 0),
+    ("olivedrab", 107, 142, 35),
+    ("orange", 255, 165, 0),
+    ("orangered", 255, 69, 0),
+    ("orchid", 218, 112, 214),
+    ("palegoldenrod", 238, 232, 170),
+    ("palegreen", 152, 251, 152),
+    ("paleturquoise", 175, 238, 238),
+    ("palevioletred", 219, 112, 147),
+    ("papayawhip", 255, 239, 213),
+    ("peachpuff", 255, 218, 185),
+    ("peru", 205, 133, 63),
+    ("pink", 255, 192, 203),
+    ("plum", 221, 160, 221),
+    ("powderblue", 176, 224, 230),
+    ("purple", 128, 0, 128),
+    ("rebeccapurple", 102, 51, 153),
+    ("red", 255, 0, 0),
+    ("rosybrown", 188, 143, 143),
+    ("royalblue", 65, 105, 225),
+    ("saddlebrown", 139, 69, 19),
+    ("salmon", 250, 128, 114),
+    ("sandybrown", 244, 164, 96),
+    ("seagreen", 46, 139, 87),
+    ("seashell", 255, 245, 238),
+    ("sienna", 160, 82, 45),
+    ("silver", 192, 192, 192),
+    ("skyblue", 135, 206, 235),
+    ("slateblue", 106, 90, 205),
+    ("slategray", 112, 128, 144),
+    ("slategrey", 112, 128, 144),
+    ("snow", 255, 250, 250),
+    ("springgreen", 0, 255, 127),
+    ("steelblue", 70, 130, 180),
+    ("tan", 210, 180, 140),
+    ("teal", 0, 128, 128),
+    ("thistle", 216, 191, 216),
+    ("tomato", 255, 99, 71),
+    ("turquoise", 64, 224, 208),
+    ("violet", 238, 130, 238),
+    ("wheat", 245, 222, 179),
+    ("white", 255, 255, 255),
+    ("whitesmoke", 245, 245, 245),
+    ("yellow", 255, 255, 0),
+    ("yellowgreen", 154, 205, 50),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords() {
+        assert!(RGBA::parse("RebeccaPurple") == Some(RGBA::new(102, 51, 153, 1.0)));
+        assert!(RGBA::parse(" lightgoldenrodyellow ") == Some(RGBA::new(250, 250, 210, 1.0)));
+        assert!(RGBA::parse("transparent") == Some(RGBA::new(0, 0, 0, 0.0)));
+        assert!(RGBA::parse("currentcolor").is_none());
+        assert!(RGBA::parse("reddish").is_none());
+    }
+
+    #[test]
+    fn hex() {
+        assert!(RGBA::parse("#f80") == Some(RGBA::new(255, 136, 0, 1.0)));
+        assert!(RGBA::parse("#FF8800") == Some(RGBA::new(255, 136, 0, 1.0)));
+        assert!(RGBA::parse("#f800") == Some(RGBA::new(255, 136, 0, 0.0)));
+        assert!(RGBA::parse("#ff880000") == Some(RGBA::new(255, 136, 0, 0.0)));
+        assert!(RGBA::parse("#ff88").is_none());
+        assert!(RGBA::parse("#ggg").is_none());
+    }
+
+    #[test]
+    fn functions() {
+        assert!(RGBA::parse("rgb(255, 136, 0)") == Some(RGBA::new(255, 136, 0, 1.0)));
+        assert!(RGBA::parse("rgb(100%, 50%, 300)").is_none());
+        assert!(RGBA::parse("rgb(100%, 50%, 0%)") == Some(RGBA::new(255, 128, 0, 1.0)));
+        assert!(RGBA::parse("rgba(300, -5, 0, 0.5)") == Some(RGBA::new(255, 0, 0, 0.5)));
+        assert!(RGBA::parse("RGBA(0, 0, 0, 2)") == Some(RGBA::new(0, 0, 0, 1.0)));
+        assert!(RGBA::parse("rgb(0, 0)").is_none());
+
+        assert!(RGBA::parse("hsl(0, 100%, 50%)") == Some(RGBA::new(255, 0, 0, 1.0)));
+        assert!(RGBA::parse("hsl(120, 100%, 25%)") == Some(RGBA::new(0, 128, 0, 1.0)));
+        assert!(RGBA::parse("hsla(-120, 100%, 50%, 25%)") == Some(RGBA::new(0, 0, 255, 0.25)));
+        assert!(RGBA::parse("hsl(0, 0%, 100%)") == Some(RGBA::new(255, 255, 255, 1.0)));
+        assert!(RGBA::parse("hsl(0, 100, 50)").is_none());
+    }
+}
@@ -14,30 +14,71 @@
 /// They are therefore not exactly analogous to constructs like Skia pictures, which consist of
 /// low-level drawing primitives.
 
-use color::Color;
-use geometry::Au;
+use color::{Color, rgb};
+use geometry::{Au, to_frac_px};
+use path::PathShape;
 use render_context::RenderContext;
-use text::SendableTextRun;
+use text::GlyphRun;
 
 use std::cast::transmute_region;
 use geom::{Point2D, Rect, Size2D, SideOffsets2D};
+use servo_msg::compositor_msg::{Cursor, HitTestItem, VideoLayerGeometry, VideoLayerId};
+use servo_msg::constellation_msg::SubpageId;
 use servo_net::image::base::Image;
 use servo_util::range::Range;
 use extra::arc::Arc;
 
 /// A list of rendering operations to be performed.
 pub struct DisplayList<E> {
-    list: ~[DisplayItem<E>]
+    list: ~[DisplayItem<E>],
+    /// The rects, in absolute coordinates, that runs of items are clipped to: each entry gives
+    /// the index of the first item it applies to, and it applies up to the next entry. `None`
+    /// leaves items unclipped.
+    clips: ~[(uint, Option<Rect<Au>>)],
 }
 
 impl<E> DisplayList<E> {
     /// Creates a new display list.
     pub fn new() -> DisplayList<E> {
         DisplayList {
-            list: ~[]
+            list: ~[],
+            clips: ~[],
         }
     }
 
+    /// Clips the items appended from now on to `clip`.
+    pub fn set_clip(&mut self, clip: Option<Rect<Au>>) {
+        if self.current_clip() == clip {
+            return
+        }
+        let index = self.list.len();
+        match self.clips.last_opt() {
+            Some(&(last_index, _)) if last_index == index => {
+                self.clips[self.clips.len() - 1] = (index, clip);
+            }
+            _ => self.clips.push((index, clip)),
+        }
+    }
+
+    fn current_clip(&self) -> Option<Rect<Au>> {
+        match self.clips.last_opt() {
+            Some(&(_, clip)) => clip,
+            None => None,
+        }
+    }
+
+    /// The rect the item at `index` is clipped to, if any.
+    pub fn clip_at(&self, index: uint) -> Option<Rect<Au>> {
+        let mut clip = None;
+        for &(start, run_clip) in self.clips.iter() {
+            if start > index {
+                break
+            }
+            clip = run_clip;
+        }
+        clip
+    }
+
     /// Appends the given item to the display list.
     pub fn append_item(&mut self, item: DisplayItem<E>) {
         // FIXME(Issue #150): crashes
@@ -45,16 +86,112 @@ impl<E> DisplayList<E> {
         self.list.push(item)
     }
 
-    /// Draws the display list into the given render context.
+    /// Draws the display list into the given render context. Runs of text items in the same font
+    /// and color are drawn together, with a single fill.
     pub fn draw_into_context(&self, render_context: &RenderContext) {
         debug!("Beginning display list.");
-        for item in self.list.iter() {
+        let mut clip = None;
+        let mut i = 0;
+        while i < self.list.len() {
+            let item_clip = self.clip_at(i);
+            if item_clip != clip {
+                if clip.is_some() {
+                    render_context.pop_clip();
+                }
+                for rect in item_clip.iter() {
+                    render_context.push_clip(rect);
+                }
+                clip = item_clip;
+            }
             // FIXME(Issue #150): crashes
-            //debug!("drawing %?", *item);
-            item.draw_into_context(render_context)
+            //debug!("drawing %?", self.list[i]);
+            match self.list[i] {
+                TextDisplayItemClass(ref text) => {
+                    let mut batch = ~[&**text];
+                    i += 1;
+                    while i < self.list.len() && self.clip_at(i) == clip {
+                        match self.list[i] {
+                            TextDisplayItemClass(ref next) if next.draws_with(&**text) => {
+                                batch.push(&**next)
+                            }
+                            _ => break,
+                        }
+                        i += 1;
+                    }
+                    draw_text_items(render_context, batch);
+                }
+                ref item => {
+                    item.draw_into_context(render_context);
+                    i += 1;
+                }
+            }
+        }
+        if clip.is_some() {
+            render_context.pop_clip();
         }
         debug!("Ending display list.")
     }
+
+    /// Extracts the information the compositor needs to hit test this display list by itself.
+    /// Adjacent items belonging to the same node are coalesced into one.
+    pub fn hit_test_items(&self) -> ~[HitTestItem] {
+        let mut items: ~[HitTestItem] = ~[];
+        for (i, item) in self.list.iter().enumerate() {
+            let base = item.base();
+            // The part of an item its clip cuts off can't be hit.
+            let item_bounds = match self.clip_at(i) {
+                Some(clip) => {
+                    match base.bounds.intersection(&clip) {
+                        Some(bounds) => bounds,
+                        None => loop,
+                    }
+                }
+                None => base.bounds,
+            };
+            let bounds = Rect(Point2D(to_frac_px(item_bounds.origin.x) as f32,
+                                      to_frac_px(item_bounds.origin.y) as f32),
+                              Size2D(to_frac_px(item_bounds.size.width) as f32,
+                                     to_frac_px(item_bounds.size.height) as f32));
+            let metadata = base.metadata;
+            if !items.is_empty() {
+                let last = &mut items[items.len() - 1];
+                if last.node == metadata.node && last.cursor == metadata.cursor &&
+                        last.scroll_subpage == metadata.scroll_subpage {
+                    last.bounds = last.bounds.union(&bounds);
+                    loop;
+                }
+            }
+            items.push(HitTestItem {
+                bounds: bounds,
+                node: metadata.node,
+                cursor: metadata.cursor,
+                scroll_subpage: metadata.scroll_subpage,
+            });
+        }
+        items
+    }
+
+    /// Returns where the videos in this display list are, for the compositor to show their
+    /// frames over.
+    pub fn video_layers(&self) -> ~[VideoLayerGeometry] {
+        let mut videos = ~[];
+        for item in self.list.iter() {
+            match *item {
+                VideoDisplayItemClass(ref video) => {
+                    let bounds = video.base.bounds;
+                    videos.push(VideoLayerGeometry {
+                        id: video.layer,
+                        bounds: Rect(Point2D(to_frac_px(bounds.origin.x) as f32,
+                                             to_frac_px(bounds.origin.y) as f32),
+                                     Size2D(to_frac_px(bounds.size.width) as f32,
+                                            to_frac_px(bounds.size.height) as f32)),
+                    });
+                }
+                _ => {}
+            }
+        }
+        videos
+    }
 }
 
 /// One drawing command in the list.
@@ -63,6 +200,11 @@ pub enum DisplayItem<E> {
     TextDisplayItemClass(~TextDisplayItem<E>),
     ImageDisplayItemClass(~ImageDisplayItem<E>),
     BorderDisplayItemClass(~BorderDisplayItem<E>),
+    CaretDisplayItemClass(~CaretDisplayItem<E>),
+    ControlDisplayItemClass(~ControlDisplayItem<E>),
+    SelectionDisplayItemClass(~SelectionDisplayItem<E>),
+    PathDisplayItemClass(~PathDisplayItem<E>),
+    VideoDisplayItemClass(~VideoDisplayItem<E>),
 }
 
 /// Information common to all display items.
@@ -74,6 +216,20 @@ pub struct BaseDisplayItem<E> {
 
     /// Extra data: either the originating flow (for hit testing) or nothing (for rendering).
     extra: E,
+
+    /// What the compositor needs to know to hit test this item without asking layout.
+    metadata: DisplayItemMetadata,
+}
+
+/// Hit testing information about a display item.
+#[deriving(Clone)]
+pub struct DisplayItemMetadata {
+    /// An opaque identifier of the DOM node that the item was generated for.
+    node: uint,
+    /// The cursor to show while the mouse is over the item.
+    cursor: Cursor,
+    /// The subpage the mouse wheel scrolls over this item, if the item belongs to an iframe.
+    scroll_subpage: Option<SubpageId>,
 }
 
 /// Renders a solid color.
@@ -85,15 +241,60 @@ pub struct SolidColorDisplayItem<E> {
 /// Renders text.
 pub struct TextDisplayItem<E> {
     base: BaseDisplayItem<E>,
-    text_run: ~SendableTextRun,
+    /// The glyphs of the text, placed along its baseline.
+    glyph_run: ~GlyphRun,
+    /// The characters of the text run the text is, which the glyphs are of.
     range: Range,
     color: Color,
 }
 
+impl<E> TextDisplayItem<E> {
+    /// Whether this text can be drawn in the same fill as `other`: whether it's in the same font
+    /// and color.
+    fn draws_with(&self, other: &TextDisplayItem<E>) -> bool {
+        self.glyph_run.font == other.glyph_run.font && self.color.r == other.color.r &&
+            self.color.g == other.color.g && self.color.b == other.color.b &&
+            self.color.a == other.color.a
+    }
+}
+
+/// Draws text items in the same font and color with a single fill, then their underlines.
+fn draw_text_items<E>(render_context: &RenderContext, items: &[&TextDisplayItem<E>]) {
+    let first = items[0];
+    debug!("Drawing %u text items from %?.", items.len(), first.base.bounds);
+
+    let font = match render_context.font_ctx.get_font_by_descriptor(&first.glyph_run.font) {
+        Ok(font) => font,
+        Err(_) => fail!(fmt!("Font descriptor deserialization failed! desc=%?",
+                             first.glyph_run.font)),
+    };
+    let baseline_origin = |text: &TextDisplayItem<E>| {
+        let origin = text.base.bounds.origin;
+        Point2D(origin.x, origin.y + font.metrics.ascent)
+    };
+
+    let glyph_runs = do items.map |&text| {
+        (baseline_origin(text), text.glyph_run.glyphs.as_slice())
+    };
+    font.draw_glyphs_into_context(render_context, glyph_runs, first.color);
+
+    for &text in items.iter() {
+        if text.glyph_run.underline {
+            // TODO(eatkinson): Use the font metrics to properly position the underline bar.
+            let width = text.base.bounds.size.width;
+            let underline_size = font.metrics.underline_size;
+            let underline_bounds = Rect(baseline_origin(text), Size2D(width, underline_size));
+            render_context.draw_solid_color(&underline_bounds, text.color);
+        }
+    }
+}
+
 /// Renders an image.
 pub struct ImageDisplayItem<E> {
     base: BaseDisplayItem<E>,
     image: Arc<~Image>,
+    /// Whether `image` is the placeholder for an image that failed to load or decode.
+    broken: bool,
 }
 
 /// Renders a border.
@@ -107,6 +308,47 @@ pub struct BorderDisplayItem<E> {
     color: SideOffsets2D<Color>,
 }
 
+/// Renders the caret of a text field. The bounds are those of the caret itself.
+pub struct CaretDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    color: Color,
+}
+
+/// Renders the highlight behind selected text. The bounds are those of the selected part of the
+/// text.
+pub struct SelectionDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    color: Color,
+}
+
+/// Renders shapes, in order, with their coordinates relative to the origin of the bounds.
+pub struct PathDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    shapes: ~[PathShape],
+}
+
+/// Renders the box of a video. The box is painted black; the compositor shows the video's
+/// frames over it in a layer of their own.
+pub struct VideoDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    layer: VideoLayerId,
+}
+
+/// A form control that's painted natively rather than from CSS, and whether it's checked.
+#[deriving(Clone, Eq)]
+pub enum Control {
+    CheckboxControl(bool),
+    RadioControl(bool),
+    /// The button at the end of a closed select that opens its list of options.
+    DropdownControl,
+}
+
+/// Renders a checkbox, radio button or dropdown button filling the bounds.
+pub struct ControlDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    control: Control,
+}
+
 impl<E> DisplayItem<E> {
     /// Renders this display item into the given render context.
     fn draw_into_context(&self, render_context: &RenderContext) {
@@ -115,37 +357,14 @@ impl<E> DisplayItem<E> {
                 render_context.draw_solid_color(&solid_color.base.bounds, solid_color.color)
             }
 
-            TextDisplayItemClass(ref text) => {
-                debug!("Drawing text at %?.", text.base.bounds);
-
-                // FIXME(pcwalton): Allocating? Why?
-                let new_run = @text.text_run.deserialize(render_context.font_ctx);
-
-                let font = new_run.font;
-                let origin = text.base.bounds.origin;
-                let baseline_origin = Point2D(origin.x, origin.y + font.metrics.ascent);
-
-                font.draw_text_into_context(render_context,
-                                            new_run,
-                                            &text.range,
-                                            baseline_origin,
-                                            text.color);
-
-                if new_run.underline {
-                    // TODO(eatkinson): Use the font metrics to properly position the underline
-                    // bar.
-                    let width = text.base.bounds.size.width;
-                    let underline_size = font.metrics.underline_size;
-                    let underline_bounds = Rect(Point2D(baseline_origin.x, baseline_origin.y),
-                                                Size2D(width, underline_size));
-                    render_context.draw_solid_color(&underline_bounds, text.color);
-                }
-            }
+            TextDisplayItemClass(ref text) => draw_text_items(render_context, [&**text]),
 
             ImageDisplayItemClass(ref image_item) => {
                 debug!("Drawing image at %?.", image_item.base.bounds);
 
-                render_context.draw_image(image_item.base.bounds, image_item.image.clone())
+                render_context.draw_image(image_item.base.bounds,
+                                          image_item.image.clone(),
+                                          image_item.broken)
             }
 
             BorderDisplayItemClass(ref border) => {
@@ -153,6 +372,26 @@ impl<E> DisplayItem<E> {
                                            border.border,
                                            border.color)
             }
+
+            CaretDisplayItemClass(ref caret) => {
+                render_context.draw_solid_color(&caret.base.bounds, caret.color)
+            }
+
+            ControlDisplayItemClass(ref control) => {
+                render_context.draw_control(&control.base.bounds, control.control)
+            }
+
+            SelectionDisplayItemClass(ref selection) => {
+                render_context.draw_solid_color(&selection.base.bounds, selection.color)
+            }
+
+            PathDisplayItemClass(ref path_item) => {
+                render_context.draw_paths(&path_item.base.bounds, path_item.shapes)
+            }
+
+            VideoDisplayItemClass(ref video) => {
+                render_context.draw_solid_color(&video.base.bounds, rgb(0, 0, 0))
+            }
         }
     }
 
@@ -163,7 +402,12 @@ impl<E> DisplayItem<E> {
                 SolidColorDisplayItemClass(ref solid_color) => transmute_region(&solid_color.base),
                 TextDisplayItemClass(ref text) => transmute_region(&text.base),
                 ImageDisplayItemClass(ref image_item) => transmute_region(&image_item.base),
-                BorderDisplayItemClass(ref border) => transmute_region(&border.base)
+                BorderDisplayItemClass(ref border) => transmute_region(&border.base),
+                CaretDisplayItemClass(ref caret) => transmute_region(&caret.base),
+                ControlDisplayItemClass(ref control) => transmute_region(&control.base),
+                SelectionDisplayItemClass(ref selection) => transmute_region(&selection.base),
+                PathDisplayItemClass(ref path_item) => transmute_region(&path_item.base),
+                VideoDisplayItemClass(ref video) => transmute_region(&video.base),
             }
         }
     }
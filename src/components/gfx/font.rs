@@ -5,6 +5,9 @@
 use color::Color;
 use font_context::FontContext;
 use geometry::Au;
+use opts::{TextAntialiasing, NoTextAntialiasing, GrayscaleTextAntialiasing};
+use opts::{SubpixelTextAntialiasing, TextHinting, NoTextHinting, LightTextHinting};
+use opts::{NormalTextHinting, FullTextHinting};
 use platform::font_context::FontContextHandle;
 use platform::font::{FontHandle, FontTable};
 use render_context::RenderContext;
@@ -16,12 +19,13 @@ use std::vec;
 use servo_util::cache::{Cache, HashCache};
 use text::glyph::{GlyphStore, GlyphIndex};
 use text::shaping::ShaperMethods;
-use text::{Shaper, TextRun};
+use text::glyph_run::PositionedGlyph;
+use text::{GlyphRun, Shaper, TextRun};
 use extra::arc::Arc;
 
 use azure::{AzFloat, AzScaledFontRef};
 use azure::scaled_font::ScaledFont;
-use azure::azure_hl::{BackendType, ColorPattern};
+use azure::azure_hl::{BackendType, ColorPattern, DrawTarget};
 use geom::{Point2D, Rect, Size2D};
 
 use servo_util::time;
@@ -239,7 +243,35 @@ pub struct Font {
     metrics: FontMetrics,
     backend: BackendType,
     profiler_chan: ProfilerChan,
+    /// The words this font has shaped, each with its advance, so that the same words are neither
+    /// shaped nor measured twice.
     shape_cache: HashCache<~str, Arc<GlyphStore>>,
+    shape_cache_stats: WordCacheStats,
+}
+
+/// How well the caches of shaped and measured words are doing.
+#[deriving(Clone, Eq)]
+pub struct WordCacheStats {
+    /// How many words were found in the cache.
+    hits: uint,
+    /// How many words had to be shaped and measured, and were added to the cache.
+    misses: uint,
+}
+
+impl WordCacheStats {
+    pub fn new() -> WordCacheStats {
+        WordCacheStats {
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn add(&self, other: &WordCacheStats) -> WordCacheStats {
+        WordCacheStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+        }
+    }
 }
 
 impl Font {
@@ -268,6 +300,7 @@ impl Font {
             backend: backend,
             profiler_chan: profiler_chan,
             shape_cache: HashCache::new(),
+            shape_cache_stats: WordCacheStats::new(),
         });
     }
 
@@ -285,6 +318,7 @@ impl Font {
             backend: backend,
             profiler_chan: profiler_chan,
             shape_cache: HashCache::new(),
+            shape_cache_stats: WordCacheStats::new(),
         }
     }
 
@@ -369,43 +403,107 @@ impl Font {
                               range: &Range,
                               baseline_origin: Point2D<Au>,
                               color: Color) {
+        self.draw_text_into_target(rctx.get_draw_target(),
+                                   run,
+                                   range,
+                                   baseline_origin,
+                                   color,
+                                   rctx.device_pixels_per_px(),
+                                   rctx.text_antialiasing(),
+                                   rctx.opts.text_hinting)
+    }
+
+    /// Draws the glyphs of `range` of `run` straight onto a draw target, for drawing that isn't
+    /// done by the render task, like a canvas's. The glyphs are placed on whole device pixels,
+    /// of which there are `device_pixels_per_px` to a px, and rasterized with the given
+    /// antialiasing and hinting.
+    pub fn draw_text_into_target(&mut self,
+                                 target: &DrawTarget,
+                                 run: &TextRun,
+                                 range: &Range,
+                                 baseline_origin: Point2D<Au>,
+                                 color: Color,
+                                 device_pixels_per_px: f32,
+                                 antialiasing: TextAntialiasing,
+                                 hinting: TextHinting) {
+        let glyph_run = GlyphRun::new(run, range);
+        self.draw_glyphs_into_target(target,
+                                     [(baseline_origin, glyph_run.glyphs.as_slice())],
+                                     color,
+                                     device_pixels_per_px,
+                                     antialiasing,
+                                     hinting)
+    }
+
+    /// Draws runs of glyphs in this font, each placed from the start of a baseline, with a single
+    /// fill. Text split across display items is drawn a line or more at a time this way, rather
+    /// than an item at a time.
+    pub fn draw_glyphs_into_context(&mut self,
+                                    rctx: &RenderContext,
+                                    glyph_runs: &[(Point2D<Au>, &[PositionedGlyph])],
+                                    color: Color) {
+        self.draw_glyphs_into_target(rctx.get_draw_target(),
+                                     glyph_runs,
+                                     color,
+                                     rctx.device_pixels_per_px(),
+                                     rctx.text_antialiasing(),
+                                     rctx.opts.text_hinting)
+    }
+
+    fn draw_glyphs_into_target(&mut self,
+                               target: &DrawTarget,
+                               glyph_runs: &[(Point2D<Au>, &[PositionedGlyph])],
+                               color: Color,
+                               device_pixels_per_px: f32,
+                               antialiasing: TextAntialiasing,
+                               hinting: TextHinting) {
         use std::libc::types::common::c99::{uint16_t, uint32_t};
         use azure::{struct__AzDrawOptions,
                     struct__AzGlyph,
                     struct__AzGlyphBuffer,
                     struct__AzPoint};
-        use azure::azure::{AzDrawTargetFillGlyphs};
+        use azure::{AZ_AA_NONE, AZ_AA_GRAY, AZ_AA_SUBPIXEL};
+        use azure::{AZ_FONT_HINTING_NONE, AZ_FONT_HINTING_LIGHT, AZ_FONT_HINTING_NORMAL,
+                    AZ_FONT_HINTING_FULL};
+        use azure::azure::{AzCreateGlyphRenderingOptions, AzDrawTargetFillGlyphs};
+        use azure::azure::{AzReleaseGlyphRenderingOptions};
 
-        let target = rctx.get_draw_target();
         let azfontref = self.get_azure_font();
         let pattern = ColorPattern(color);
         let azure_pattern = pattern.azure_color_pattern;
         assert!(azure_pattern.is_not_null());
 
+        let antialias_mode = match antialiasing {
+            NoTextAntialiasing => AZ_AA_NONE,
+            GrayscaleTextAntialiasing => AZ_AA_GRAY,
+            SubpixelTextAntialiasing => AZ_AA_SUBPIXEL,
+        };
+        let font_hinting = match hinting {
+            NoTextHinting => AZ_FONT_HINTING_NONE,
+            LightTextHinting => AZ_FONT_HINTING_LIGHT,
+            NormalTextHinting => AZ_FONT_HINTING_NORMAL,
+            FullTextHinting => AZ_FONT_HINTING_FULL,
+        };
+
+        // The antialiasing mode sits above the composition operator, which is left as `OVER`.
         let options = struct__AzDrawOptions {
             mAlpha: 1f as AzFloat,
-            fields: 0x0200 as uint16_t
+            fields: (antialias_mode as uint16_t) << 8
         };
 
-        let mut origin = baseline_origin.clone();
-        let mut azglyphs = ~[];
-        azglyphs.reserve(range.length());
-
-        for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
-            for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
-                let glyph_advance = glyph.advance();
-                let glyph_offset = glyph.offset().unwrap_or_default(Au::zero_point());
-
-                let azglyph = struct__AzGlyph {
-                    mIndex: glyph.index() as uint32_t,
+        let glyph_count = glyph_runs.iter().fold(0, |count, &(_, glyphs)| count + glyphs.len());
+        let mut azglyphs = vec::with_capacity(glyph_count);
+        for &(baseline_origin, glyphs) in glyph_runs.iter() {
+            for glyph in glyphs.iter() {
+                let position = baseline_origin + glyph.position;
+                azglyphs.push(struct__AzGlyph {
+                    mIndex: glyph.index as uint32_t,
                     mPosition: struct__AzPoint {
-                        x: (origin.x + glyph_offset.x).to_nearest_px() as AzFloat,
-                        y: (origin.y + glyph_offset.y).to_nearest_px() as AzFloat
+                        x: position.x.to_nearest_device_px(device_pixels_per_px),
+                        y: position.y.to_nearest_device_px(device_pixels_per_px)
                     }
-                };
-                origin = Point2D(origin.x + glyph_advance, origin.y);
-                azglyphs.push(azglyph)
-            };
+                });
+            }
         }
 
         let azglyph_buf_len = azglyphs.len();
@@ -413,17 +511,19 @@ impl Font {
 
         let glyphbuf = struct__AzGlyphBuffer {
             mGlyphs: vec::raw::to_ptr(azglyphs),
-            mNumGlyphs: azglyph_buf_len as uint32_t            
+            mNumGlyphs: azglyph_buf_len as uint32_t
         };
 
         unsafe {
             // TODO(Issue #64): this call needs to move into azure_hl.rs
+            let rendering_options = AzCreateGlyphRenderingOptions(font_hinting, antialias_mode);
             AzDrawTargetFillGlyphs(target.azure_draw_target,
                                    azfontref,
                                    ptr::to_unsafe_ptr(&glyphbuf),
                                    azure_pattern,
                                    ptr::to_unsafe_ptr(&options),
-                                   ptr::null());
+                                   rendering_options);
+            AzReleaseGlyphRenderingOptions(rendering_options);
         }
     }
 
@@ -432,31 +532,41 @@ impl Font {
         // TODO(Issue #98): using inter-char and inter-word spacing settings  when measuring text
         let mut advance = Au(0);
         for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
-            for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
-                advance = advance + glyph.advance();
-            }
+            advance = advance + self.measure_text_for_slice(glyphs, &slice_range).advance_width;
         }
         RunMetrics::new(advance, self.metrics.ascent, self.metrics.descent)
     }
 
+    /// Measures `slice_range` of the glyphs of a word. Whole words were measured when they were
+    /// shaped; only parts of them have their glyphs' advances added up.
     pub fn measure_text_for_slice(&self,
                                   glyphs: &GlyphStore,
                                   slice_range: &Range)
                                   -> RunMetrics {
-        let mut advance = Au(0);
-        for (_i, glyph) in glyphs.iter_glyphs_for_char_range(slice_range) {
-            advance = advance + glyph.advance();
-        }
+        let advance = if slice_range.begin() == 0 && slice_range.length() == glyphs.char_len() {
+            glyphs.advance()
+        } else {
+            sum_advances(glyphs, slice_range)
+        };
         RunMetrics::new(advance, self.metrics.ascent, self.metrics.descent)
     }
 
     pub fn shape_text(@mut self, text: ~str, is_whitespace: bool) -> Arc<GlyphStore> {
         do profile(time::LayoutShapingCategory, self.profiler_chan.clone()) {
-            let shaper = self.get_shaper();
-            do self.shape_cache.find_or_create(&text) |txt| {
-                let mut glyphs = GlyphStore::new(text.char_len(), is_whitespace);
-                shaper.shape_text(*txt, &mut glyphs);
-                Arc::new(glyphs)
+            match self.shape_cache.find(&text) {
+                Some(glyphs) => {
+                    self.shape_cache_stats.hits += 1;
+                    glyphs
+                }
+                None => {
+                    self.shape_cache_stats.misses += 1;
+                    let mut glyphs = GlyphStore::new(text.char_len(), is_whitespace);
+                    self.get_shaper().shape_text(text.as_slice(), &mut glyphs);
+                    glyphs.advance = sum_advances(&glyphs, &Range::new(0, glyphs.char_len()));
+                    let glyphs = Arc::new(glyphs);
+                    self.shape_cache.insert(text, glyphs.clone());
+                    glyphs
+                }
             }
         }
     }
@@ -477,6 +587,15 @@ impl Font {
     }
 }
 
+/// Adds up the advances of the glyphs of `range` of a word.
+fn sum_advances(glyphs: &GlyphStore, range: &Range) -> Au {
+    let mut advance = Au(0);
+    for (_i, glyph) in glyphs.iter_glyphs_for_char_range(range) {
+        advance = advance + glyph.advance();
+    }
+    advance
+}
+
 /*fn should_destruct_on_fail_without_leaking() {
     #[test];
     #[should_fail];
@@ -546,3 +665,4 @@ fn should_be_able_to_create_instances_in_multiple_threads() {
 }
 
 */
+
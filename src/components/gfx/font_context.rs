@@ -4,7 +4,7 @@
 
 use font::{Font, FontDescriptor, FontGroup, FontHandleMethods, FontStyle,
            SelectorPlatformIdentifier};
-use font::{SpecifiedFontStyle, UsedFontStyle};
+use font::{SpecifiedFontStyle, UsedFontStyle, WordCacheStats};
 use font_list::FontList;
 use servo_util::cache::{Cache, LRUCache};
 use servo_util::time::ProfilerChan;
@@ -110,6 +110,21 @@ impl<'self> FontContext {
         }
     }
 
+    /// How well the word caches of the fonts in use are doing, all together.
+    pub fn word_cache_stats(&self) -> WordCacheStats {
+        do self.instance_cache.entries.iter().fold(WordCacheStats::new()) |stats, &(_, font)| {
+            stats.add(&font.shape_cache_stats)
+        }
+    }
+
+    /// How many fonts are cached, and how many shaped words they hold between them.
+    pub fn cache_sizes(&self) -> (uint, uint) {
+        let words = do self.instance_cache.entries.iter().fold(0) |words, &(_, font)| {
+            words + font.shape_cache.entries.len()
+        };
+        (self.instance_cache.entries.len(), words)
+    }
+
     fn transform_family(&self, family: &str) -> ~str {
         // FIXME: Need a find_like() in HashMap.
         let family = family.to_str();
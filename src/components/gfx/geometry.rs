@@ -93,6 +93,12 @@ impl Au {
         ((**self as float) / 60f).round() as int
     }
 
+    /// The length in px, rounded to a whole number of device pixels when there are
+    /// `device_pixels_per_px` of them to a px.
+    pub fn to_nearest_device_px(&self, device_pixels_per_px: f32) -> f32 {
+        ((**self as f32) / 60f32 * device_pixels_per_px).round() / device_pixels_per_px
+    }
+
     pub fn to_snapped(&self) -> Au {
         let res = **self % 60i32;
         return if res >= 30i32 { return Au(**self - res + 60i32) }
@@ -10,8 +10,37 @@ use azure::azure_hl::{CoreGraphicsAcceleratedBackend, Direct2DBackend, SkiaBacke
 
 use std::float;
 use std::result;
+use std::u16;
 use std::uint;
 
+/// The user agent string pages see in `navigator.userAgent`, unless another is given.
+#[cfg(target_os="macos")]
+pub static DEFAULT_USER_AGENT: &'static str = "Mozilla/5.0 (Macintosh; Intel Mac OS X) Servo/0.1";
+#[cfg(target_os="linux")]
+pub static DEFAULT_USER_AGENT: &'static str = "Mozilla/5.0 (X11; Linux) Servo/0.1";
+#[cfg(target_os="android")]
+pub static DEFAULT_USER_AGENT: &'static str = "Mozilla/5.0 (Android; Mobile) Servo/0.1";
+
+/// How the edges of glyphs are smoothed.
+#[deriving(Clone, Eq)]
+pub enum TextAntialiasing {
+    NoTextAntialiasing,
+    GrayscaleTextAntialiasing,
+    /// Smoothing each color channel of LCD pixels separately, as though each were a third of a
+    /// pixel wide. It only looks right over an opaque background.
+    SubpixelTextAntialiasing,
+}
+
+/// How far glyph outlines are fitted to the pixel grid, trading their shapes for sharpness.
+#[deriving(Clone, Eq)]
+pub enum TextHinting {
+    NoTextHinting,
+    /// Fitting vertically only, which keeps glyphs their designed widths.
+    LightTextHinting,
+    NormalTextHinting,
+    FullTextHinting,
+}
+
 #[deriving(Clone)]
 pub struct Opts {
     urls: ~[~str],
@@ -19,8 +48,55 @@ pub struct Opts {
     n_render_threads: uint,
     tile_size: uint,
     profiler_period: Option<float>,
+    /// How often, in seconds, to print how much memory each part of the engine is using, if at
+    /// all.
+    memory_profiler_period: Option<float>,
     exit_after_load: bool,
     output_file: Option<~str>,
+    /// A directory to keep the HTTP cache in between runs, if any.
+    cache_dir: Option<~str>,
+    /// How much memory, in megabytes, decoded images may take up, if not the default.
+    image_cache_size: Option<uint>,
+    /// Whether to check the invariants of the flow tree after each layout.
+    check_layout: bool,
+    /// How long to hold back each network response, in milliseconds, if throttling.
+    throttle_latency: Option<uint>,
+    /// How many bytes per second network responses may arrive at, if throttling.
+    throttle_bandwidth: Option<uint>,
+    /// How long the script task waits before handling each message, in milliseconds, if slowed
+    /// down.
+    script_delay: Option<uint>,
+    /// How many device pixels to render to a CSS px, if not what the platform says the display
+    /// has.
+    device_pixels_per_px: Option<f32>,
+    /// Whether presses and drags of the left mouse button stand in for touches.
+    emulate_touch: bool,
+    /// How the edges of text rendered into pages are smoothed.
+    text_antialiasing: TextAntialiasing,
+    /// How far text rendered into pages is fitted to the pixel grid.
+    text_hinting: TextHinting,
+    /// Whether to run without a window, for machines with no display. Pages are loaded and laid
+    /// out, but nothing is drawn.
+    headless: bool,
+    /// The path of a style sheet to use in place of the built-in user agent style sheet, if any.
+    user_agent_stylesheet: Option<~str>,
+    /// The paths of style sheets to apply to every page at the user origin, whose declarations
+    /// override those of pages only when they're `!important`.
+    user_stylesheets: ~[~str],
+    /// The user agent string pages see.
+    user_agent: ~str,
+    /// The port on the local machine to serve the developer tools protocol on, if any.
+    devtools_port: Option<u16>,
+    /// Whether layout writes the flow tree and display list of each reflow to a file, as
+    /// `--debug layout-trace` asks.
+    layout_trace: bool,
+    /// Whether the renderer outlines each tile, as `--debug tile-borders` asks.
+    show_tile_borders: bool,
+    /// Whether the renderer tints tiles as it renders them, in a different color each time, as
+    /// `--debug paint-flashing` asks.
+    paint_flashing: bool,
+    /// Whether the renderer outlines the bounds of each layer, as `--debug layer-borders` asks.
+    show_layer_borders: bool,
 }
 
 pub fn from_cmdline_args(args: &[~str]) -> Opts {
@@ -34,7 +110,24 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
         getopts::optopt("s"),  // size of tiles
         getopts::optopt("t"),  // threads to render with
         getopts::optflagopt("p"),  // profiler flag and output interval
+        getopts::optflagopt("m"),  // memory profiler flag and output interval
         getopts::optflag("x"), // exit after load flag
+        getopts::optopt("cache-dir"),  // directory for the HTTP cache
+        getopts::optopt("image-cache-size"),  // megabytes of decoded images to keep
+        getopts::optflag("check-layout"),  // check flow tree invariants after each layout
+        getopts::optopt("throttle-latency"),  // milliseconds to delay network responses by
+        getopts::optopt("throttle-bandwidth"),  // bytes per second to limit network responses to
+        getopts::optopt("script-delay"),  // milliseconds to delay each script task message by
+        getopts::optopt("device-pixel-ratio"),  // device pixels per CSS px, if not the display's
+        getopts::optflag("emulate-touch"),  // touch the page with the left mouse button
+        getopts::optopt("text-antialiasing"),  // none, grayscale or subpixel
+        getopts::optopt("text-hinting"),  // none, light, normal or full
+        getopts::optflag("headless"),  // run without a window
+        getopts::optopt("user-agent-stylesheet"),  // style sheet to replace the built-in one with
+        getopts::optmulti("user-stylesheet"),  // style sheet to apply at the user origin
+        getopts::optopt("user-agent"),  // user agent string to give pages
+        getopts::optopt("devtools"),  // port to serve the developer tools protocol on
+        getopts::optmulti("debug"),  // debugging aids to turn on, separated by commas
     ];
 
     let opt_match = match getopts::getopts(args, opts) {
@@ -81,18 +174,138 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
     let profiler_period = do getopts::opt_default(&opt_match, "p", "5").map |period| {
         float::from_str(*period).unwrap()
     };
+    let memory_profiler_period = do getopts::opt_default(&opt_match, "m", "5").map |period| {
+        float::from_str(*period).unwrap()
+    };
 
     let exit_after_load = getopts::opt_present(&opt_match, "x");
 
     let output_file = getopts::opt_maybe_str(&opt_match, "o");
 
+    let cache_dir = getopts::opt_maybe_str(&opt_match, "cache-dir");
+
+    let image_cache_size = do getopts::opt_maybe_str(&opt_match, "image-cache-size").map |size| {
+        uint::from_str(*size).unwrap()
+    };
+
+    let check_layout = getopts::opt_present(&opt_match, "check-layout");
+
+    let throttle_latency = do getopts::opt_maybe_str(&opt_match, "throttle-latency").map |ms| {
+        uint::from_str(*ms).unwrap()
+    };
+
+    let throttle_bandwidth = do getopts::opt_maybe_str(&opt_match, "throttle-bandwidth").map |n| {
+        uint::from_str(*n).unwrap()
+    };
+
+    let script_delay = do getopts::opt_maybe_str(&opt_match, "script-delay").map |ms| {
+        uint::from_str(*ms).unwrap()
+    };
+
+    let device_pixels_per_px = do getopts::opt_maybe_str(&opt_match, "device-pixel-ratio").map |r| {
+        let ratio = float::from_str(*r).unwrap() as f32;
+        if ratio <= 0.0 {
+            fail!(~"the device pixel ratio must be positive")
+        }
+        ratio
+    };
+
+    let emulate_touch = getopts::opt_present(&opt_match, "emulate-touch");
+
+    let text_antialiasing = match getopts::opt_maybe_str(&opt_match, "text-antialiasing") {
+        Some(mode) => {
+            if mode == ~"none" {
+                NoTextAntialiasing
+            } else if mode == ~"grayscale" {
+                GrayscaleTextAntialiasing
+            } else if mode == ~"subpixel" {
+                SubpixelTextAntialiasing
+            } else {
+                fail!(~"unknown text antialiasing mode")
+            }
+        }
+        None => SubpixelTextAntialiasing,
+    };
+
+    let text_hinting = match getopts::opt_maybe_str(&opt_match, "text-hinting") {
+        Some(mode) => {
+            if mode == ~"none" {
+                NoTextHinting
+            } else if mode == ~"light" {
+                LightTextHinting
+            } else if mode == ~"normal" {
+                NormalTextHinting
+            } else if mode == ~"full" {
+                FullTextHinting
+            } else {
+                fail!(~"unknown text hinting mode")
+            }
+        }
+        None => LightTextHinting,
+    };
+
+    let headless = getopts::opt_present(&opt_match, "headless");
+
+    let user_agent_stylesheet = getopts::opt_maybe_str(&opt_match, "user-agent-stylesheet");
+
+    let user_stylesheets = getopts::opt_strs(&opt_match, "user-stylesheet");
+
+    let user_agent = match getopts::opt_maybe_str(&opt_match, "user-agent") {
+        Some(user_agent) => user_agent,
+        None => DEFAULT_USER_AGENT.to_owned(),
+    };
+
+    let devtools_port = do getopts::opt_maybe_str(&opt_match, "devtools").map |port| {
+        u16::from_str(*port).unwrap()
+    };
+
+    let mut layout_trace = false;
+    let mut show_tile_borders = false;
+    let mut paint_flashing = false;
+    let mut show_layer_borders = false;
+    for options in getopts::opt_strs(&opt_match, "debug").iter() {
+        for option in options.split_iter(',') {
+            if option == "layout-trace" {
+                layout_trace = true
+            } else if option == "tile-borders" {
+                show_tile_borders = true
+            } else if option == "paint-flashing" {
+                paint_flashing = true
+            } else if option == "layer-borders" {
+                show_layer_borders = true
+            } else {
+                fail!(fmt!("unknown debug option %s", option))
+            }
+        }
+    }
+
     Opts {
         urls: urls,
         render_backend: render_backend,
         n_render_threads: n_render_threads,
         tile_size: tile_size,
         profiler_period: profiler_period,
+        memory_profiler_period: memory_profiler_period,
         exit_after_load: exit_after_load,
         output_file: output_file,
+        cache_dir: cache_dir,
+        image_cache_size: image_cache_size,
+        check_layout: check_layout,
+        throttle_latency: throttle_latency,
+        throttle_bandwidth: throttle_bandwidth,
+        script_delay: script_delay,
+        device_pixels_per_px: device_pixels_per_px,
+        emulate_touch: emulate_touch,
+        text_antialiasing: text_antialiasing,
+        text_hinting: text_hinting,
+        headless: headless,
+        user_agent_stylesheet: user_agent_stylesheet,
+        user_stylesheets: user_stylesheets,
+        user_agent: user_agent,
+        devtools_port: devtools_port,
+        layout_trace: layout_trace,
+        show_tile_borders: show_tile_borders,
+        paint_flashing: paint_flashing,
+        show_layer_borders: show_layer_borders,
     }
 }
@@ -0,0 +1,565 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Paths, as canvases and SVG draw them. Azure has no path API here, so paths are kept as lists
+//! of points, with curves flattened into lines: strokes are drawn a line at a time, and fills a
+//! row of pixels at a time.
+
+use color::Color;
+
+use azure::AzFloat;
+use azure::azure_hl::{ColorPattern, DrawOptions, DrawTarget, StrokeOptions};
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use extra::sort;
+
+use std::ascii::AsciiCast;
+use std::f32;
+use std::float;
+use std::libc::types::common::c99::uint16_t;
+use std::num::Orderable;
+use std::str;
+
+/// How many lines a Bézier curve is flattened into.
+static CURVE_SEGMENTS: uint = 16;
+/// How many lines an ellipse is flattened into.
+static ELLIPSE_SEGMENTS: uint = 64;
+
+/// A run of connected points of a path.
+#[deriving(Clone)]
+pub struct Subpath {
+    points: ~[Point2D<f32>],
+    closed: bool,
+}
+
+impl Subpath {
+    pub fn new(point: Point2D<f32>) -> Subpath {
+        Subpath {
+            points: ~[point],
+            closed: false,
+        }
+    }
+
+    /// The lines the subpath is made of. A subpath that's filled is closed whether or not it was
+    /// explicitly closed.
+    pub fn each_line(&self, close: bool, f: &fn(Point2D<f32>, Point2D<f32>)) {
+        for i in range(1, self.points.len()) {
+            f(self.points[i - 1], self.points[i]);
+        }
+        if (close || self.closed) && self.points.len() > 2 {
+            f(self.points[self.points.len() - 1], self.points[0]);
+        }
+    }
+}
+
+/// A path drawn filled, outlined or both, as an SVG shape is.
+#[deriving(Clone)]
+pub struct PathShape {
+    subpaths: ~[Subpath],
+    fill: Option<Color>,
+    stroke: Option<Color>,
+    stroke_width: f32,
+}
+
+/// Builds a path up out of lines and curves. There's always a subpath being added to once
+/// anything has been added.
+pub struct PathBuilder {
+    subpaths: ~[Subpath],
+}
+
+impl PathBuilder {
+    pub fn new() -> PathBuilder {
+        PathBuilder {
+            subpaths: ~[],
+        }
+    }
+
+    /// Where the subpath being added to ends, if there is one.
+    pub fn current_point(&self) -> Option<Point2D<f32>> {
+        self.subpaths.last_opt().map(|subpath| *subpath.points.last())
+    }
+
+    /// Starts a new subpath at `point`. A subpath that's just a point is moved rather than
+    /// kept.
+    pub fn move_to(&mut self, point: Point2D<f32>) {
+        let length = self.subpaths.len();
+        if length > 0 && self.subpaths[length - 1].points.len() == 1 &&
+                !self.subpaths[length - 1].closed {
+            self.subpaths[length - 1].points[0] = point;
+        } else {
+            self.subpaths.push(Subpath::new(point));
+        }
+    }
+
+    /// Adds a line to `point`, or starts a subpath there if there's none yet.
+    pub fn line_to(&mut self, point: Point2D<f32>) {
+        if self.subpaths.is_empty() {
+            self.move_to(point);
+        } else {
+            self.subpaths[self.subpaths.len() - 1].points.push(point);
+        }
+    }
+
+    pub fn quadratic_curve_to(&mut self, control: Point2D<f32>, end: Point2D<f32>) {
+        let start = self.current_point().unwrap_or_default(control);
+        for i in range(1, CURVE_SEGMENTS + 1) {
+            let t = i as f32 / CURVE_SEGMENTS as f32;
+            let u = 1.0 - t;
+            self.line_to(Point2D(u * u * start.x + 2.0 * u * t * control.x + t * t * end.x,
+                                 u * u * start.y + 2.0 * u * t * control.y + t * t * end.y));
+        }
+    }
+
+    pub fn bezier_curve_to(&mut self,
+                           control_1: Point2D<f32>,
+                           control_2: Point2D<f32>,
+                           end: Point2D<f32>) {
+        let start = self.current_point().unwrap_or_default(control_1);
+        for i in range(1, CURVE_SEGMENTS + 1) {
+            let t = i as f32 / CURVE_SEGMENTS as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            self.line_to(Point2D(a * start.x + b * control_1.x + c * control_2.x + d * end.x,
+                                 a * start.y + b * control_1.y + c * control_2.y + d * end.y));
+        }
+    }
+
+    /// Closes the subpath being added to, and starts a new one where it started.
+    pub fn close(&mut self) {
+        if self.subpaths.is_empty() {
+            return;
+        }
+        let start = {
+            let subpath = &mut self.subpaths[self.subpaths.len() - 1];
+            subpath.closed = true;
+            subpath.points[0]
+        };
+        self.move_to(start);
+    }
+
+    /// Adds a closed subpath going round a rect, clockwise from its origin.
+    pub fn rect(&mut self, rect: &Rect<f32>) {
+        let Rect { origin: origin, size: size } = *rect;
+        self.move_to(origin);
+        self.line_to(Point2D(origin.x + size.width, origin.y));
+        self.line_to(Point2D(origin.x + size.width, origin.y + size.height));
+        self.line_to(Point2D(origin.x, origin.y + size.height));
+        self.close();
+    }
+
+    /// Adds a closed subpath going round an ellipse, clockwise from its rightmost point.
+    pub fn ellipse(&mut self, center: Point2D<f32>, radius_x: f32, radius_y: f32) {
+        self.move_to(Point2D(center.x + radius_x, center.y));
+        for i in range(1, ELLIPSE_SEGMENTS) {
+            let angle = 2.0 * f32::consts::pi * i as f32 / ELLIPSE_SEGMENTS as f32;
+            self.line_to(Point2D(center.x + radius_x * angle.cos(),
+                                 center.y + radius_y * angle.sin()));
+        }
+        self.close();
+    }
+}
+
+/// The spans of the row of pixels whose centers are at `y` that the path covers, by the nonzero
+/// winding rule, from left to right.
+pub fn fill_spans(subpaths: &[Subpath], y: f32) -> ~[(f32, f32)] {
+    // Where the path's edges cross the row, and which way each goes.
+    let mut crossings = ~[];
+    for subpath in subpaths.iter() {
+        do subpath.each_line(true) |start, end| {
+            let (top, bottom, winding) = if start.y < end.y {
+                (start, end, 1)
+            } else {
+                (end, start, -1)
+            };
+            if top.y <= y && y < bottom.y {
+                let x = top.x + (y - top.y) * (bottom.x - top.x) / (bottom.y - top.y);
+                crossings.push((x, winding));
+            }
+        }
+    }
+    sort::quick_sort(crossings, |&(a, _), &(b, _)| a <= b);
+
+    let mut spans = ~[];
+    let mut winding = 0;
+    let mut span_start = 0.0f32;
+    for &(x, direction) in crossings.iter() {
+        let was_inside = winding != 0;
+        winding += direction;
+        if !was_inside && winding != 0 {
+            span_start = x;
+        } else if was_inside && winding == 0 && x > span_start {
+            spans.push((span_start, x));
+        }
+    }
+    spans
+}
+
+/// Fills a path, offset by `origin`, a row of pixels at a time. Edges aren't antialiased.
+pub fn fill(draw_target: &DrawTarget, subpaths: &[Subpath], origin: Point2D<f32>, color: Color) {
+    let mut top = f32::infinity;
+    let mut bottom = f32::neg_infinity;
+    for subpath in subpaths.iter() {
+        for point in subpath.points.iter() {
+            top = top.min(&point.y);
+            bottom = bottom.max(&point.y);
+        }
+    }
+    if top > bottom {
+        return;
+    }
+
+    let pattern = ColorPattern(color);
+    for row in range(top.floor() as int, bottom.ceil() as int) {
+        let y = row as f32 + 0.5;
+        for &(left, right) in fill_spans(subpaths, y).iter() {
+            let left = (left + origin.x).round();
+            let right = (right + origin.x).round();
+            if right <= left {
+                loop;
+            }
+            let span = Rect(Point2D(left as AzFloat, (row as f32 + origin.y) as AzFloat),
+                            Size2D((right - left) as AzFloat, 1.0 as AzFloat));
+            draw_target.fill_rect(&span, &pattern);
+        }
+    }
+}
+
+/// Strokes a path, offset by `origin`, a line at a time.
+pub fn stroke(draw_target: &DrawTarget,
+              subpaths: &[Subpath],
+              origin: Point2D<f32>,
+              color: Color,
+              width: f32) {
+    let draw_options = DrawOptions(1.0 as AzFloat, 0 as uint16_t);
+    // FIXME: Lines are drawn separately, so corners aren't joined.
+    let stroke_fields = 0; // CAP_BUTT
+    let stroke_options = StrokeOptions(width as AzFloat, 10.0 as AzFloat, stroke_fields);
+    let pattern = ColorPattern(color);
+    for subpath in subpaths.iter() {
+        do subpath.each_line(false) |start, end| {
+            draw_target.stroke_line(Point2D((start.x + origin.x) as AzFloat,
+                                            (start.y + origin.y) as AzFloat),
+                                    Point2D((end.x + origin.x) as AzFloat,
+                                            (end.y + origin.y) as AzFloat),
+                                    &pattern,
+                                    &stroke_options,
+                                    &draw_options);
+        }
+    }
+}
+
+/// Parses SVG path data, as in the `d` attribute of a `<path>`. If the data has an error in it,
+/// the path up to the error is returned, as SVG says to render it.
+///
+/// FIXME: Elliptical arcs are drawn as straight lines to their ends.
+pub fn parse_path_data(data: &str) -> ~[Subpath] {
+    let mut parser = PathDataParser {
+        chars: data.iter().collect(),
+        position: 0,
+    };
+    let mut path = PathBuilder::new();
+    let mut command = None;
+    // The second control point of the last curve, reflected for `S` and `T`.
+    let mut last_control = None;
+
+    loop {
+        parser.skip_separators();
+        if parser.at_end() {
+            break;
+        }
+        command = match parser.next_command() {
+            // Path data has to start with a move.
+            Some(next) if command.is_some() || next == 'M' || next == 'm' => Some(next),
+            Some(_) => break,
+            // Commands repeat until the next one, except that coordinates after a move are
+            // lines.
+            None => match command {
+                Some('M') => Some('L'),
+                Some('m') => Some('l'),
+                Some('Z') | Some('z') | None => break,
+                other => other,
+            },
+        };
+        let letter = command.unwrap();
+        let upper_letter = letter.to_ascii().to_upper().to_char();
+        let relative = letter.is_lowercase();
+        let current = path.current_point().unwrap_or_default(Point2D(0.0f32, 0.0f32));
+        let offset = if relative { current } else { Point2D(0.0f32, 0.0f32) };
+        let point = |parser: &mut PathDataParser| {
+            do parser.number_pair().map_move |(x, y)| {
+                Point2D(x + offset.x, y + offset.y)
+            }
+        };
+
+        let mut control = None;
+        let ok = match upper_letter {
+            'M' => match point(&mut parser) {
+                Some(p) => { path.move_to(p); true }
+                None => false,
+            },
+            'L' => match point(&mut parser) {
+                Some(p) => { path.line_to(p); true }
+                None => false,
+            },
+            'H' => match parser.number() {
+                Some(x) => { path.line_to(Point2D(x + offset.x, current.y)); true }
+                None => false,
+            },
+            'V' => match parser.number() {
+                Some(y) => { path.line_to(Point2D(current.x, y + offset.y)); true }
+                None => false,
+            },
+            'C' => match (point(&mut parser), point(&mut parser), point(&mut parser)) {
+                (Some(c1), Some(c2), Some(end)) => {
+                    path.bezier_curve_to(c1, c2, end);
+                    control = Some(c2);
+                    true
+                }
+                _ => false,
+            },
+            'S' => match (point(&mut parser), point(&mut parser)) {
+                (Some(c2), Some(end)) => {
+                    let c1 = reflect(last_control, current, ['C', 'S']);
+                    path.bezier_curve_to(c1, c2, end);
+                    control = Some(c2);
+                    true
+                }
+                _ => false,
+            },
+            'Q' => match (point(&mut parser), point(&mut parser)) {
+                (Some(c), Some(end)) => {
+                    path.quadratic_curve_to(c, end);
+                    control = Some(c);
+                    true
+                }
+                _ => false,
+            },
+            'T' => match point(&mut parser) {
+                Some(end) => {
+                    let c = reflect(last_control, current, ['Q', 'T']);
+                    path.quadratic_curve_to(c, end);
+                    control = Some(c);
+                    true
+                }
+                None => false,
+            },
+            'A' => {
+                // The radii, rotation and flags, then the end.
+                let parameters = [parser.number(), parser.number(), parser.number(),
+                                  parser.flag(), parser.flag()];
+                match point(&mut parser) {
+                    Some(end) if parameters.iter().all(|p| p.is_some()) => {
+                        path.line_to(end);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            'Z' => {
+                path.close();
+                true
+            }
+            _ => false,
+        };
+        if !ok {
+            break;
+        }
+        last_control = control.map_move(|c| (c, upper_letter));
+    }
+    path.subpaths
+}
+
+/// The first control point of a smooth curve: the reflection of the last curve's control point
+/// about the current point, if the last command was one of `commands`, the curves of the same
+/// kind, or the current point otherwise.
+fn reflect(last_control: Option<(Point2D<f32>, char)>,
+           current: Point2D<f32>,
+           commands: [char, ..2])
+           -> Point2D<f32> {
+    match last_control {
+        Some((control, command)) if commands.contains(&command) => {
+            Point2D(2.0 * current.x - control.x, 2.0 * current.y - control.y)
+        }
+        _ => current,
+    }
+}
+
+/// Reads the numbers, flags and command letters of path data.
+struct PathDataParser {
+    chars: ~[char],
+    position: uint,
+}
+
+impl PathDataParser {
+    fn at_end(&self) -> bool {
+        self.position >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        if self.at_end() { None } else { Some(self.chars[self.position]) }
+    }
+
+    /// Skips whitespace and at most one comma.
+    fn skip_separators(&mut self) {
+        let mut seen_comma = false;
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => self.position += 1,
+                Some(',') if !seen_comma => {
+                    seen_comma = true;
+                    self.position += 1;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn next_command(&mut self) -> Option<char> {
+        match self.peek() {
+            Some(c) if "MmLlHhVvCcSsQqTtAaZz".contains_char(c) => {
+                self.position += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads a number: an optional sign, digits with an optional decimal point, and an optional
+    /// exponent.
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let chars = self.chars.as_slice();
+        let start = self.position;
+        let mut end = start;
+        accept(chars, &mut end, |c| c == '+' || c == '-');
+        let mut digits = 0;
+        while accept(chars, &mut end, |c| c.is_digit()) {
+            digits += 1;
+        }
+        if accept(chars, &mut end, |c| c == '.') {
+            while accept(chars, &mut end, |c| c.is_digit()) {
+                digits += 1;
+            }
+        }
+        if digits == 0 {
+            return None;
+        }
+        let before_exponent = end;
+        if accept(chars, &mut end, |c| c == 'e' || c == 'E') {
+            accept(chars, &mut end, |c| c == '+' || c == '-');
+            let mut exponent_digits = 0;
+            while accept(chars, &mut end, |c| c.is_digit()) {
+                exponent_digits += 1;
+            }
+            if exponent_digits == 0 {
+                end = before_exponent;
+            }
+        }
+        let text = str::from_chars(chars.slice(start, end));
+        self.position = end;
+        float::from_str(text).map_move(|n| n as f32)
+    }
+
+    fn number_pair(&mut self) -> Option<(f32, f32)> {
+        match self.number() {
+            Some(x) => self.number().map_move(|y| (x, y)),
+            None => None,
+        }
+    }
+
+    /// Reads an arc flag, a single `0` or `1`.
+    fn flag(&mut self) -> Option<f32> {
+        self.skip_separators();
+        match self.peek() {
+            Some('0') => { self.position += 1; Some(0.0) }
+            Some('1') => { self.position += 1; Some(1.0) }
+            _ => None,
+        }
+    }
+}
+
+/// Moves `end` past the character at it if `f` accepts it.
+fn accept(chars: &[char], end: &mut uint, f: &fn(char) -> bool) -> bool {
+    if *end < chars.len() && f(chars[*end]) {
+        *end += 1;
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PathBuilder, Subpath, fill_spans, parse_path_data};
+    use geom::point::Point2D;
+    use geom::rect::Rect;
+    use geom::size::Size2D;
+
+    fn rect_path(x: f32, y: f32, width: f32, height: f32) -> ~[Subpath] {
+        let mut path = PathBuilder::new();
+        path.rect(&Rect(Point2D(x, y), Size2D(width, height)));
+        path.subpaths
+    }
+
+    #[test]
+    fn fill_spans_of_rect() {
+        let subpaths = rect_path(10.0, 10.0, 20.0, 5.0);
+        assert!(fill_spans(subpaths, 12.5) == ~[(10.0, 30.0)]);
+        assert!(fill_spans(subpaths, 9.5).is_empty());
+        assert!(fill_spans(subpaths, 15.5).is_empty());
+    }
+
+    #[test]
+    fn fill_spans_nonzero_winding() {
+        // Two overlapping rects going the same way fill as one span.
+        let mut subpaths = rect_path(0.0, 0.0, 10.0, 10.0);
+        subpaths.push_all(rect_path(5.0, 0.0, 10.0, 10.0));
+        assert!(fill_spans(subpaths, 5.5) == ~[(0.0, 15.0)]);
+    }
+
+    #[test]
+    fn fill_spans_of_triangle() {
+        let mut path = PathBuilder::new();
+        path.move_to(Point2D(0.0f32, 0.0f32));
+        path.line_to(Point2D(10.0f32, 10.0f32));
+        path.line_to(Point2D(0.0f32, 10.0f32));
+        assert!(fill_spans(path.subpaths, 5.0) == ~[(0.0, 5.0)]);
+    }
+
+    #[test]
+    fn path_data_lines() {
+        let subpaths = parse_path_data("M 10,10 L 20 10 h 5 v5 z m 1 1 2 2");
+        assert!(subpaths.len() == 2);
+        assert!(subpaths[0].points == ~[Point2D(10.0f32, 10.0f32), Point2D(20.0f32, 10.0f32),
+                                        Point2D(25.0f32, 10.0f32), Point2D(25.0f32, 15.0f32)]);
+        assert!(subpaths[0].closed);
+        // After closing, relative coordinates start from where the closed subpath started.
+        assert!(subpaths[1].points == ~[Point2D(11.0f32, 11.0f32), Point2D(13.0f32, 13.0f32)]);
+    }
+
+    #[test]
+    fn path_data_numbers() {
+        let subpaths = parse_path_data("M1e1-2.5L.5.5");
+        assert!(subpaths[0].points == ~[Point2D(10.0f32, -2.5f32), Point2D(0.5f32, 0.5f32)]);
+    }
+
+    #[test]
+    fn path_data_curves_end_where_they_should() {
+        let subpaths = parse_path_data("M0 0C0 10 10 10 10 0S20 -10 20 0Q25 5 30 0T40 0");
+        let points = &subpaths[0].points;
+        assert!(points.len() == 1 + 4 * super::CURVE_SEGMENTS);
+        assert!(points[super::CURVE_SEGMENTS] == Point2D(10.0f32, 0.0f32));
+        assert!(*points.last() == Point2D(40.0f32, 0.0f32));
+    }
+
+    #[test]
+    fn path_data_errors() {
+        // Everything up to the error is kept.
+        let subpaths = parse_path_data("M 0 0 L 10 10 L 20 X 30 30");
+        assert!(subpaths[0].points == ~[Point2D(0.0f32, 0.0f32), Point2D(10.0f32, 10.0f32)]);
+        assert!(parse_path_data("L 10 10").is_empty());
+        assert!(parse_path_data("").is_empty());
+    }
+}
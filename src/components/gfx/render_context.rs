@@ -3,9 +3,12 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use servo_msg::compositor_msg::LayerBuffer;
+use display_list::{CheckboxControl, Control, DropdownControl, RadioControl};
 use font_context::FontContext;
 use geometry::Au;
-use opts::Opts;
+use opts::{GrayscaleTextAntialiasing, Opts, SubpixelTextAntialiasing, TextAntialiasing};
+use path;
+use path::PathShape;
 
 use azure::azure_hl::{B8G8R8A8, Color, ColorPattern, DrawOptions};
 use azure::azure_hl::{DrawSurfaceOptions, DrawTarget, Linear, StrokeOptions};
@@ -29,54 +32,160 @@ impl<'self> RenderContext<'self>  {
         &self.canvas.draw_target
     }
 
+    /// How many device pixels there are to a px in the tile being rendered, counting both the
+    /// display's pixel density and any zoom. Edges are snapped to whole device pixels so that
+    /// they stay sharp.
+    pub fn device_pixels_per_px(&self) -> f32 {
+        self.canvas.resolution.get()
+    }
+
+    /// How the edges of text are smoothed. Subpixel antialiasing is left for grayscale once
+    /// there are enough device pixels to a px for it to make no visible difference.
+    pub fn text_antialiasing(&self) -> TextAntialiasing {
+        match self.opts.text_antialiasing {
+            SubpixelTextAntialiasing if self.device_pixels_per_px() >= 2.0 => {
+                GrayscaleTextAntialiasing
+            }
+            antialiasing => antialiasing,
+        }
+    }
+
     pub fn draw_solid_color(&self, bounds: &Rect<Au>, color: Color) {
         self.canvas.draw_target.make_current();
-        self.canvas.draw_target.fill_rect(&bounds.to_azure_rect(), &ColorPattern(color));
+        let rect = bounds.to_azure_rect(self.device_pixels_per_px());
+        self.canvas.draw_target.fill_rect(&rect, &ColorPattern(color));
     }
 
+    /// Draws a border inside `bounds`. Each side is filled as a rectangle that doesn't overlap
+    /// the others, so that the corners of semi-transparent borders aren't painted twice. The top
+    /// and bottom sides take the corners.
     pub fn draw_border(&self,
                        bounds: &Rect<Au>,
                        border: SideOffsets2D<Au>,
                        color: SideOffsets2D<Color>) {
+        let rect = bounds.to_azure_rect(self.device_pixels_per_px());
+        let border = border.to_float_px(self.device_pixels_per_px());
+        let left = rect.origin.x;
+        let top = rect.origin.y;
+        let right = rect.origin.x + rect.size.width;
+        let bottom = rect.origin.y + rect.size.height;
+        let middle_height = rect.size.height - border.top - border.bottom;
+
+        self.canvas.draw_target.make_current();
+        let fill = |origin: Point2D<AzFloat>, width: AzFloat, height: AzFloat, color: Color| {
+            if width > 0.0 && height > 0.0 && color.a > 0.0 {
+                self.canvas.draw_target.fill_rect(&Rect(origin, Size2D(width, height)),
+                                                  &ColorPattern(color));
+            }
+        };
+        fill(Point2D(left, top), rect.size.width, border.top, color.top);
+        fill(Point2D(right - border.right, top + border.top),
+             border.right,
+             middle_height,
+             color.right);
+        fill(Point2D(left, bottom - border.bottom), rect.size.width, border.bottom, color.bottom);
+        fill(Point2D(left, top + border.top), border.left, middle_height, color.left);
+    }
+
+    /// Draws a checkbox or radio button filling the given bounds. Azure has no paths here, so a
+    /// radio button is an octagon rather than a circle.
+    pub fn draw_control(&self, bounds: &Rect<Au>, control: Control) {
         let draw_opts = DrawOptions(1 as AzFloat, 0 as uint16_t);
-        let stroke_fields = 2; // CAP_SQUARE
-        let mut stroke_opts = StrokeOptions(0 as AzFloat, 10 as AzFloat, stroke_fields);
+        let stroke_fields = 1; // CAP_ROUND
+        let stroke_opts = StrokeOptions(1 as AzFloat, 10 as AzFloat, stroke_fields);
+        let white = ColorPattern(Color(1.0, 1.0, 1.0, 1.0));
+        let gray = ColorPattern(Color(0.5, 0.5, 0.5, 1.0));
+        let mark = ColorPattern(Color(0.1, 0.1, 0.1, 1.0));
 
-        let rect = bounds.to_azure_rect();
-        let border = border.to_float_px();
+        let rect = bounds.to_azure_rect(self.device_pixels_per_px());
+        let (x, y) = (rect.origin.x, rect.origin.y);
+        let (w, h) = (rect.size.width, rect.size.height);
 
         self.canvas.draw_target.make_current();
+        let draw_target = &self.canvas.draw_target;
 
-        // draw top border
-        stroke_opts.line_width = border.top;
-        let y = rect.origin.y + border.top * 0.5;
-        let start = Point2D(rect.origin.x, y);
-        let end = Point2D(rect.origin.x + rect.size.width, y);
-        self.canvas.draw_target.stroke_line(start, end, &ColorPattern(color.top), &stroke_opts, &draw_opts);
-
-        // draw right border
-        stroke_opts.line_width = border.right;
-        let x = rect.origin.x + rect.size.width - border.right * 0.5;
-        let start = Point2D(x, rect.origin.y);
-        let end = Point2D(x, rect.origin.y + rect.size.height);
-        self.canvas.draw_target.stroke_line(start, end, &ColorPattern(color.right), &stroke_opts, &draw_opts);
-
-        // draw bottom border
-        stroke_opts.line_width = border.bottom;
-        let y = rect.origin.y + rect.size.height - border.bottom * 0.5;
-        let start = Point2D(rect.origin.x, y);
-        let end = Point2D(rect.origin.x + rect.size.width, y);
-        self.canvas.draw_target.stroke_line(start, end, &ColorPattern(color.bottom), &stroke_opts, &draw_opts);
-
-        // draw left border
-        stroke_opts.line_width = border.left;
-        let x = rect.origin.x + border.left * 0.5;
-        let start = Point2D(x, rect.origin.y);
-        let end = Point2D(x, rect.origin.y + rect.size.height);
-        self.canvas.draw_target.stroke_line(start, end, &ColorPattern(color.left), &stroke_opts, &draw_opts);
-    }
-
-    pub fn draw_image(&self, bounds: Rect<Au>, image: Arc<~Image>) {
+        match control {
+            CheckboxControl(checked) => {
+                draw_target.fill_rect(&rect, &white);
+                self.draw_border(bounds,
+                                 SideOffsets2D::new_all_same(Au::from_px(1)),
+                                 SideOffsets2D::new_all_same(Color(0.5, 0.5, 0.5, 1.0)));
+                if checked {
+                    let mark_opts = StrokeOptions(w / 6.0, 10 as AzFloat, stroke_fields);
+                    let corner = Point2D(x + w * 0.4, y + h * 0.75);
+                    draw_target.stroke_line(Point2D(x + w * 0.2, y + h * 0.5), corner,
+                                            &mark, &mark_opts, &draw_opts);
+                    draw_target.stroke_line(corner, Point2D(x + w * 0.8, y + h * 0.25),
+                                            &mark, &mark_opts, &draw_opts);
+                }
+            }
+            RadioControl(checked) => {
+                // The octagon's corners, clockwise from the top left.
+                let (third_w, third_h) = (w / 3.0, h / 3.0);
+                let corners = [
+                    Point2D(x + third_w, y), Point2D(x + w - third_w, y),
+                    Point2D(x + w, y + third_h), Point2D(x + w, y + h - third_h),
+                    Point2D(x + w - third_w, y + h), Point2D(x + third_w, y + h),
+                    Point2D(x, y + h - third_h), Point2D(x, y + third_h),
+                ];
+                draw_target.fill_rect(&Rect(Point2D(x + third_w * 0.5, y + third_h * 0.5),
+                                            Size2D(w - third_w, h - third_h)),
+                                      &white);
+                for i in range(0, corners.len()) {
+                    let next = corners[(i + 1) % corners.len()];
+                    draw_target.stroke_line(corners[i], next, &gray, &stroke_opts, &draw_opts);
+                }
+                if checked {
+                    // A plus of two overlapping squares, which at this size reads as a dot.
+                    let (dot_w, dot_h) = (w * 0.4, h * 0.4);
+                    let (center_x, center_y) = (x + w * 0.5, y + h * 0.5);
+                    draw_target.fill_rect(&Rect(Point2D(center_x - dot_w * 0.5,
+                                                        center_y - dot_h * 0.35),
+                                                Size2D(dot_w, dot_h * 0.7)),
+                                          &mark);
+                    draw_target.fill_rect(&Rect(Point2D(center_x - dot_w * 0.35,
+                                                        center_y - dot_h * 0.5),
+                                                Size2D(dot_w * 0.7, dot_h)),
+                                          &mark);
+                }
+            }
+            DropdownControl => {
+                draw_target.fill_rect(&rect, &ColorPattern(Color(0.9, 0.9, 0.9, 1.0)));
+                self.draw_border(bounds,
+                                 SideOffsets2D::new_all_same(Au::from_px(1)),
+                                 SideOffsets2D::new_all_same(Color(0.5, 0.5, 0.5, 1.0)));
+                // A down arrow, as two strokes meeting at the bottom.
+                let arrow_opts = StrokeOptions(w / 8.0, 10 as AzFloat, stroke_fields);
+                let tip = Point2D(x + w * 0.5, y + h * 0.6);
+                draw_target.stroke_line(Point2D(x + w * 0.3, y + h * 0.4), tip,
+                                        &mark, &arrow_opts, &draw_opts);
+                draw_target.stroke_line(tip, Point2D(x + w * 0.7, y + h * 0.4),
+                                        &mark, &arrow_opts, &draw_opts);
+            }
+        }
+    }
+
+    /// Fills and strokes shapes whose coordinates are relative to the origin of the bounds.
+    pub fn draw_paths(&self, bounds: &Rect<Au>, shapes: &[PathShape]) {
+        let rect = bounds.to_azure_rect(self.device_pixels_per_px());
+        let origin = Point2D(rect.origin.x as f32, rect.origin.y as f32);
+
+        self.canvas.draw_target.make_current();
+        let draw_target = &self.canvas.draw_target;
+        for shape in shapes.iter() {
+            for &color in shape.fill.iter() {
+                path::fill(draw_target, shape.subpaths, origin, color);
+            }
+            for &color in shape.stroke.iter() {
+                path::stroke(draw_target, shape.subpaths, origin, color, shape.stroke_width);
+            }
+        }
+    }
+
+    /// Draws an image stretched to the given bounds. If `broken` is set, the image is the
+    /// placeholder for an image that failed to load or decode; the bounds get a frame and the
+    /// placeholder is drawn at its natural size in their top left corner, shrunk if it won't fit.
+    pub fn draw_image(&self, bounds: Rect<Au>, image: Arc<~Image>, broken: bool) {
         let image = image.get();
         let size = Size2D(image.width as i32, image.height as i32);
         let stride = image.width * 4;
@@ -87,7 +196,30 @@ impl<'self> RenderContext<'self>  {
                                                                             stride as i32, B8G8R8A8);
         let source_rect = Rect(Point2D(0 as AzFloat, 0 as AzFloat),
                                Size2D(image.width as AzFloat, image.height as AzFloat));
-        let dest_rect = bounds.to_azure_rect();
+        let dest_rect = if broken {
+            let gray = Color(0.5, 0.5, 0.5, 1.0);
+            self.draw_border(&bounds,
+                             SideOffsets2D::new_all_same(Au::from_px(1)),
+                             SideOffsets2D::new_all_same(gray));
+
+            let bounds = bounds.to_azure_rect(self.device_pixels_per_px());
+            let inset = 2.0 as AzFloat;
+            let fit = |natural: AzFloat, available: AzFloat| {
+                let available = available - inset * 2.0;
+                if available < 0.0 {
+                    0.0
+                } else if natural > available {
+                    available
+                } else {
+                    natural
+                }
+            };
+            Rect(Point2D(bounds.origin.x + inset, bounds.origin.y + inset),
+                 Size2D(fit(image.width as AzFloat, bounds.size.width),
+                        fit(image.height as AzFloat, bounds.size.height)))
+        } else {
+            bounds.to_azure_rect(self.device_pixels_per_px())
+        };
         let draw_surface_options = DrawSurfaceOptions(Linear, true);
         let draw_options = DrawOptions(1.0f as AzFloat, 0);
         draw_target_ref.draw_surface(azure_surface,
@@ -97,14 +229,64 @@ impl<'self> RenderContext<'self>  {
                                      draw_options);
     }
 
+    /// Clips everything drawn from now until the matching `pop_clip` to `bounds`.
+    pub fn push_clip(&self, bounds: &Rect<Au>) {
+        let rect = bounds.to_azure_rect(self.device_pixels_per_px());
+        self.canvas.draw_target.make_current();
+        self.canvas.draw_target.push_clip_rect(&rect);
+    }
+
+    pub fn pop_clip(&self) {
+        self.canvas.draw_target.pop_clip();
+    }
+
+    /// Clears the given rect, in page coordinates, to transparent.
+    pub fn punch_out(&self, bounds: &Rect<f32>) {
+        let rect = Rect(Point2D(bounds.origin.x as AzFloat, bounds.origin.y as AzFloat),
+                        Size2D(bounds.size.width as AzFloat, bounds.size.height as AzFloat));
+        self.canvas.draw_target.make_current();
+        self.canvas.draw_target.clear_rect(&rect);
+    }
+
     pub fn clear(&self) {
         let pattern = ColorPattern(Color(1.0, 1.0, 1.0, 1.0));
-        let rect = Rect(Point2D(self.canvas.rect.origin.x as AzFloat,
-                                self.canvas.rect.origin.y as AzFloat),
-                        Size2D(self.canvas.screen_pos.size.width as AzFloat,
-                               self.canvas.screen_pos.size.height as AzFloat));
         self.canvas.draw_target.make_current();
-        self.canvas.draw_target.fill_rect(&rect, &pattern);
+        self.canvas.draw_target.fill_rect(&self.tile_rect(), &pattern);
+    }
+
+    /// The part of the page the tile shows, in px.
+    pub fn tile_rect(&self) -> Rect<AzFloat> {
+        // The draw target is transformed to page coordinates, so the whole buffer is as big as
+        // its size in device pixels scaled back down.
+        let origin = self.canvas.rect.origin();
+        let screen_size = self.canvas.screen_pos.size().to_f32();
+        let size = self.canvas.resolution.inv().transform_size(&screen_size);
+        Rect(Point2D(origin.point.x as AzFloat, origin.point.y as AzFloat),
+             Size2D(size.size.width as AzFloat, size.size.height as AzFloat))
+    }
+
+    /// Fills the whole tile with `color` over what's been drawn, for debugging overlays.
+    pub fn tint(&self, color: Color) {
+        self.canvas.draw_target.make_current();
+        self.canvas.draw_target.fill_rect(&self.tile_rect(), &ColorPattern(color));
+    }
+
+    /// Draws lines the given number of device pixels wide along the inside of the edges of
+    /// `rect`, in px, for debugging overlays.
+    pub fn outline(&self, rect: &Rect<AzFloat>, device_pixels: AzFloat, color: Color) {
+        let width = device_pixels / self.device_pixels_per_px();
+        let pattern = ColorPattern(color);
+        let left = rect.origin.x;
+        let top = rect.origin.y;
+        let right = rect.origin.x + rect.size.width;
+        let bottom = rect.origin.y + rect.size.height;
+        self.canvas.draw_target.make_current();
+        for side in [Rect(Point2D(left, top), Size2D(rect.size.width, width)),
+                     Rect(Point2D(left, bottom - width), Size2D(rect.size.width, width)),
+                     Rect(Point2D(left, top), Size2D(width, rect.size.height)),
+                     Rect(Point2D(right - width, top), Size2D(width, rect.size.height))].iter() {
+            self.canvas.draw_target.fill_rect(side, &pattern);
+        }
     }
 }
 
@@ -119,27 +301,27 @@ impl to_float for u8 {
 }
 
 trait ToAzureRect {
-    fn to_azure_rect(&self) -> Rect<AzFloat>;
+    fn to_azure_rect(&self, device_pixels_per_px: f32) -> Rect<AzFloat>;
 }
 
 impl ToAzureRect for Rect<Au> {
-    fn to_azure_rect(&self) -> Rect<AzFloat> {
-        Rect(Point2D(self.origin.x.to_nearest_px() as AzFloat,
-                     self.origin.y.to_nearest_px() as AzFloat),
-             Size2D(self.size.width.to_nearest_px() as AzFloat,
-                    self.size.height.to_nearest_px() as AzFloat))
+    fn to_azure_rect(&self, device_pixels_per_px: f32) -> Rect<AzFloat> {
+        Rect(Point2D(self.origin.x.to_nearest_device_px(device_pixels_per_px),
+                     self.origin.y.to_nearest_device_px(device_pixels_per_px)),
+             Size2D(self.size.width.to_nearest_device_px(device_pixels_per_px),
+                    self.size.height.to_nearest_device_px(device_pixels_per_px)))
     }
 }
 
 trait ToSideOffsetsPx {
-    fn to_float_px(&self) -> SideOffsets2D<AzFloat>;
+    fn to_float_px(&self, device_pixels_per_px: f32) -> SideOffsets2D<AzFloat>;
 }
 
 impl ToSideOffsetsPx for SideOffsets2D<Au> {
-    fn to_float_px(&self) -> SideOffsets2D<AzFloat> {
-        SideOffsets2D::new(self.top.to_nearest_px() as AzFloat,
-                           self.right.to_nearest_px() as AzFloat,
-                           self.bottom.to_nearest_px() as AzFloat,
-                           self.left.to_nearest_px() as AzFloat)
+    fn to_float_px(&self, device_pixels_per_px: f32) -> SideOffsets2D<AzFloat> {
+        SideOffsets2D::new(self.top.to_nearest_device_px(device_pixels_per_px),
+                           self.right.to_nearest_device_px(device_pixels_per_px),
+                           self.bottom.to_nearest_device_px(device_pixels_per_px),
+                           self.left.to_nearest_device_px(device_pixels_per_px))
     }
 }
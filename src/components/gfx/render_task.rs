@@ -5,15 +5,16 @@
 // The task that handles all rendering/painting.
 
 use azure::{AzFloat, AzGLContext};
-use azure::azure_hl::{B8G8R8A8, DrawTarget};
+use azure::azure_hl::{B8G8R8A8, Color, DrawTarget};
 use display_list::DisplayList;
 use servo_msg::compositor_msg::{RenderListener, IdleRenderState, RenderingRenderState, LayerBuffer};
-use servo_msg::compositor_msg::{LayerBufferSet, Epoch};
+use servo_msg::compositor_msg::{LayerBufferSet, Epoch, NativeOverlayGeometry, PunchOutOverlay};
 use servo_msg::constellation_msg::PipelineId;
 use font_context::FontContext;
 use geom::matrix2d::Matrix2D;
-use geom::size::Size2D;
+use geom::point::Point2D;
 use geom::rect::Rect;
+use geom::size::Size2D;
 use opts::Opts;
 use render_context::RenderContext;
 
@@ -21,37 +22,57 @@ use std::cell::Cell;
 use std::comm::{Chan, Port, SharedChan};
 use extra::arc::Arc;
 
+use servo_util::geometry::{DevicePx, PagePx, ScaleFactor, TypedRect};
 use servo_util::time::{ProfilerChan, profile};
 use servo_util::time;
 
 use buffer_map::BufferMap;
 
 
+/// The colors tiles are tinted with in turn as they're rendered, with `--debug paint-flashing`, so
+/// that each render can be told from the one before.
+static PAINT_FLASH_COLORS: [(AzFloat, AzFloat, AzFloat), ..4] =
+    [(1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0), (1.0, 0.0, 1.0)];
+
 pub struct RenderLayer<T> {
     display_list: Arc<DisplayList<T>>,
-    size: Size2D<uint>
+    size: Size2D<uint>,
+    /// Where the elements of the embedder's native overlays are on this layer.
+    native_overlays: ~[NativeOverlayGeometry],
 }
 
 pub enum Msg<T> {
     RenderMsg(RenderLayer<T>),
-    ReRenderMsg(~[BufferRequest], f32, Epoch),
+    ReRenderMsg(RenderRequest),
     UnusedBufferMsg(~[~LayerBuffer]),
     PaintPermissionGranted,
     PaintPermissionRevoked,
     ExitMsg(Chan<()>),
 }
 
-/// A request from the compositor to the renderer for tiles that need to be (re)displayed.
+/// A request from the compositor to the renderer for the tiles of a layer it needs (re)displayed.
+pub struct RenderRequest {
+    /// The tiles, described by where they go on the screen and what part of the page they show.
+    buffer_requests: ~[BufferRequest],
+    /// How many device pixels there are to a page pixel.
+    scale: ScaleFactor<PagePx, DevicePx>,
+    /// The epoch of the layer the compositor asked with. The request is dropped if the layer has
+    /// been given a new display list since, as the tiles would be of the wrong size.
+    epoch: Epoch,
+}
+
+/// A tile the renderer is asked to render.
 #[deriving(Clone)]
 pub struct BufferRequest {
     // The rect in pixels that will be drawn to the screen
-    screen_rect: Rect<uint>,
+    screen_rect: TypedRect<DevicePx, uint>,
     
     // The rect in page coordinates that this tile represents
-    page_rect: Rect<f32>,
+    page_rect: TypedRect<PagePx, f32>,
 }
 
-pub fn BufferRequest(screen_rect: Rect<uint>, page_rect: Rect<f32>) -> BufferRequest {
+pub fn BufferRequest(screen_rect: TypedRect<DevicePx, uint>, page_rect: TypedRect<PagePx, f32>)
+                     -> BufferRequest {
     BufferRequest {
         screen_rect: screen_rect,
         page_rect: page_rect,
@@ -96,6 +117,8 @@ struct RenderTask<C,T> {
     epoch: Epoch,
     /// A data structure to store unused LayerBuffers
     buffer_map: BufferMap<~LayerBuffer>,
+    /// How many times tiles have been rendered, which picks the color of paint flashing.
+    render_count: uint,
 }
 
 impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
@@ -132,6 +155,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                 last_paint_msg: None,
                 epoch: Epoch(0),
                 buffer_map: BufferMap::new(10000000),
+                render_count: 0,
             };
 
             render_task.start();
@@ -147,15 +171,21 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                     if self.paint_permission {
                         self.epoch.next();
                         self.compositor.set_layer_page_size(self.id, render_layer.size, self.epoch);
+                        self.compositor.set_layer_hit_test_items(self.id,
+                                                                 render_layer.display_list.get().hit_test_items());
+                        self.compositor.set_layer_native_overlays(self.id,
+                                                                  render_layer.native_overlays.clone());
+                        self.compositor.set_layer_videos(self.id,
+                                                         render_layer.display_list.get().video_layers());
                     }
                     self.render_layer = Some(render_layer);
                     self.last_paint_msg = None;
                 }
-                ReRenderMsg(tiles, scale, epoch) => {
-                    if self.epoch == epoch {
-                        self.render(tiles, scale);
+                ReRenderMsg(request) => {
+                    if self.epoch == request.epoch {
+                        self.render(request.buffer_requests, request.scale);
                     } else {
-                        debug!("renderer epoch mismatch: %? != %?", self.epoch, epoch);
+                        debug!("renderer epoch mismatch: %? != %?", self.epoch, request.epoch);
                     }
                 }
                 UnusedBufferMsg(unused_buffers) => {
@@ -170,6 +200,12 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                         Some(ref render_layer) => {
                             self.epoch.next();
                             self.compositor.set_layer_page_size(self.id, render_layer.size, self.epoch);
+                            self.compositor.set_layer_hit_test_items(self.id,
+                                                                     render_layer.display_list.get().hit_test_items());
+                            self.compositor.set_layer_native_overlays(self.id,
+                                                                      render_layer.native_overlays.clone());
+                            self.compositor.set_layer_videos(self.id,
+                                                             render_layer.display_list.get().video_layers());
                         }
                         None => {}
                     }
@@ -195,7 +231,8 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
         }
     }
 
-    fn render(&mut self, tiles: ~[BufferRequest], scale: f32) {
+    fn render(&mut self, tiles: ~[BufferRequest], scale: ScaleFactor<PagePx, DevicePx>) {
+        self.render_count += 1;
         let render_layer;
         match self.render_layer {
             Some(ref r_layer) => {
@@ -213,10 +250,10 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
             // Divide up the layer into tiles.
             do time::profile(time::RenderingPrepBuffCategory, self.profiler_chan.clone()) {
                 for tile in tiles.iter() {
-                    let width = tile.screen_rect.size.width;
-                    let height = tile.screen_rect.size.height;
+                    let width = tile.screen_rect.rect.size.width;
+                    let height = tile.screen_rect.rect.size.height;
                     
-                    let buffer = match self.buffer_map.find(tile.screen_rect.size) {
+                    let buffer = match self.buffer_map.find(tile.screen_rect.rect.size) {
                         Some(buffer) => {
                             let mut buffer = buffer;
                             buffer.rect = tile.page_rect;
@@ -247,9 +284,9 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
 
                         // Apply the translation to render the tile we want.
                         let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
-                        let matrix = matrix.scale(scale as AzFloat, scale as AzFloat);
-                        let matrix = matrix.translate(-(buffer.rect.origin.x) as AzFloat,
-                                                      -(buffer.rect.origin.y) as AzFloat);
+                        let matrix = matrix.scale(scale.get() as AzFloat, scale.get() as AzFloat);
+                        let matrix = matrix.translate(-(buffer.rect.rect.origin.x) as AzFloat,
+                                                      -(buffer.rect.rect.origin.y) as AzFloat);
                         
                         ctx.canvas.draw_target.set_transform(&matrix);
                         
@@ -259,6 +296,15 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                         // Draw the display list.
                         do profile(time::RenderingDrawingCategory, self.profiler_chan.clone()) {
                             render_layer.display_list.get().draw_into_context(&ctx);
+
+                            // Let native views behind the page show through.
+                            for overlay in render_layer.native_overlays.iter() {
+                                if overlay.mode == PunchOutOverlay {
+                                    ctx.punch_out(&overlay.bounds);
+                                }
+                            }
+
+                            self.draw_debug_overlays(&ctx, render_layer.size);
                             ctx.canvas.draw_target.flush();
                         }
                     }
@@ -282,5 +328,21 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
             self.compositor.set_render_state(IdleRenderState);
         }
     }
+
+    /// Draws the overlays the `--debug` options ask for over a tile of a layer of the given size.
+    fn draw_debug_overlays(&self, ctx: &RenderContext, layer_size: Size2D<uint>) {
+        if self.opts.paint_flashing {
+            let (r, g, b) = PAINT_FLASH_COLORS[self.render_count % PAINT_FLASH_COLORS.len()];
+            ctx.tint(Color(r, g, b, 0.25));
+        }
+        if self.opts.show_tile_borders {
+            ctx.outline(&ctx.tile_rect(), 1.0, Color(0.0, 0.5, 1.0, 1.0));
+        }
+        if self.opts.show_layer_borders {
+            let layer_size = Size2D(layer_size.width as AzFloat, layer_size.height as AzFloat);
+            let layer_rect = Rect(Point2D(0.0, 0.0), layer_size);
+            ctx.outline(&layer_rect, 2.0, Color(1.0, 0.5, 0.0, 1.0));
+        }
+    }
 }
 
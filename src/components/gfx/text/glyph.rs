@@ -510,6 +510,9 @@ pub struct GlyphStore {
     entry_buffer: ~[GlyphEntry],
     detail_store: DetailedGlyphStore,
     is_whitespace: bool,
+    /// The advance of all the glyphs together, measured once when the text is shaped. Stores
+    /// hold a word each, and line breaking measures whole words over and over.
+    advance: Au,
 }
 
 impl<'self> GlyphStore {
@@ -522,6 +525,7 @@ impl<'self> GlyphStore {
             entry_buffer: vec::from_elem(length, GlyphEntry::initial()),
             detail_store: DetailedGlyphStore::new(),
             is_whitespace: is_whitespace,
+            advance: Au(0),
         }
     }
 
@@ -533,6 +537,11 @@ impl<'self> GlyphStore {
         self.is_whitespace
     }
 
+    /// The advance of all the glyphs together.
+    pub fn advance(&self) -> Au {
+        self.advance
+    }
+
     pub fn finalize_changes(&mut self) {
         self.detail_store.ensure_sorted();
     }
@@ -0,0 +1,61 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Glyph runs: the glyphs of part of a text run, placed along the baseline once when the display
+//! list is built, so that rendering each tile needn't walk the run's glyph stores again.
+
+use font::FontDescriptor;
+use geometry::Au;
+use text::glyph::GlyphIndex;
+use text::TextRun;
+use servo_util::range::Range;
+
+use geom::point::Point2D;
+use std::vec;
+
+/// A glyph, and where it goes relative to the start of the baseline.
+#[deriving(Clone, Eq)]
+pub struct PositionedGlyph {
+    index: GlyphIndex,
+    position: Point2D<Au>,
+}
+
+/// The glyphs of part of a text run, ready to draw in the run's font.
+#[deriving(Clone)]
+pub struct GlyphRun {
+    font: FontDescriptor,
+    underline: bool,
+    glyphs: ~[PositionedGlyph],
+    /// How far each character advances the pen, for finding the character at a point. Those a
+    /// ligature takes in after its first don't advance it at all.
+    char_advances: ~[Au],
+}
+
+impl GlyphRun {
+    /// Places the glyphs of `range` of `run`.
+    pub fn new(run: &TextRun, range: &Range) -> GlyphRun {
+        let mut glyphs = ~[];
+        let mut char_advances = vec::from_elem(range.length(), Au(0));
+        let mut pen = Au(0);
+        for (slice_glyphs, offset, slice_range) in run.iter_slices_for_range(range) {
+            for (i, glyph) in slice_glyphs.iter_glyphs_for_char_range(&slice_range) {
+                let glyph_offset = glyph.offset().unwrap_or_default(Au::zero_point());
+                glyphs.push(PositionedGlyph {
+                    index: glyph.index(),
+                    position: Point2D(pen + glyph_offset.x, glyph_offset.y),
+                });
+                let advance = glyph.advance();
+                let char_index = offset + i - range.begin();
+                char_advances[char_index] = char_advances[char_index] + advance;
+                pen = pen + advance;
+            }
+        }
+        GlyphRun {
+            font: run.font.get_descriptor(),
+            underline: run.underline,
+            glyphs: glyphs,
+            char_advances: char_advances,
+        }
+    }
+}
@@ -8,11 +8,12 @@
 Note that you still must define each of the files as a module in
 servo.rc. This is not ideal and may be changed in the future. */
 
+pub use text::glyph_run::GlyphRun;
 pub use text::shaping::Shaper;
-pub use text::text_run::SendableTextRun;
 pub use text::text_run::TextRun;
 
 pub mod glyph;
+pub mod glyph_run;
 #[path="shaping/mod.rs"] pub mod shaping;
 pub mod text_run;
 pub mod util;
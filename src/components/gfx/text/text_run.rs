@@ -4,10 +4,9 @@
 
 use std::vec::VecIterator;
 
-use font_context::FontContext;
 use geometry::Au;
 use text::glyph::GlyphStore;
-use font::{Font, FontDescriptor, RunMetrics};
+use font::{Font, RunMetrics};
 use servo_util::range::Range;
 use extra::arc::Arc;
 
@@ -19,30 +18,6 @@ pub struct TextRun {
     glyphs: ~[Arc<GlyphStore>],
 }
 
-/// This is a hack until TextRuns are normally sendable, or we instead use Arc<TextRun> everywhere.
-pub struct SendableTextRun {
-    text: ~str,
-    font: FontDescriptor,
-    underline: bool,
-    priv glyphs: ~[Arc<GlyphStore>],
-}
-
-impl SendableTextRun {
-    pub fn deserialize(&self, fctx: @mut FontContext) -> TextRun {
-        let font = match fctx.get_font_by_descriptor(&self.font) {
-            Ok(f) => f,
-            Err(_) => fail!(fmt!("Font descriptor deserialization failed! desc=%?", self.font))
-        };
-
-        TextRun {
-            text: self.text.clone(),
-            font: font,
-            underline: self.underline,
-            glyphs: self.glyphs.clone(),
-        }
-    }
-}
-
 pub struct SliceIterator<'self> {
     priv glyph_iter: VecIterator<'self, Arc<GlyphStore>>,
     priv range:      Range,
@@ -187,15 +162,6 @@ impl<'self> TextRun {
         glyphs
     }
 
-    pub fn serialize(&self) -> SendableTextRun {
-        SendableTextRun {
-            text: self.text.clone(),
-            font: self.font.get_descriptor(),
-            underline: self.underline,
-            glyphs: self.glyphs.clone(),
-        }
-    }
-
     pub fn char_len(&self) -> uint {
         do self.glyphs.iter().fold(0u) |len, slice_glyphs| {
             len + slice_glyphs.get().char_len()
@@ -212,7 +178,12 @@ impl<'self> TextRun {
     }
 
     pub fn metrics_for_range(&self, range: &Range) -> RunMetrics {
-        self.font.measure_text(self, range)
+        // TODO(Issue #199): alter advance direction for RTL
+        let mut advance = Au(0);
+        do self.each_slice_advance(range) |slice_advance| {
+            advance = advance + slice_advance;
+        }
+        RunMetrics::new(advance, self.font.metrics.ascent, self.font.metrics.descent)
     }
 
     pub fn metrics_for_slice(&self, glyphs: &GlyphStore, slice_range: &Range) -> RunMetrics {
@@ -222,14 +193,21 @@ impl<'self> TextRun {
     pub fn min_width_for_range(&self, range: &Range) -> Au {
         let mut max_piece_width = Au(0);
         debug!("iterating outer range %?", range);
-        for (glyphs, offset, slice_range) in self.iter_slices_for_range(range) {
-            debug!("iterated on %?[%?]", offset, slice_range);
-            let metrics = self.font.measure_text_for_slice(glyphs, &slice_range);
-            max_piece_width = Au::max(max_piece_width, metrics.advance_width);
+        do self.each_slice_advance(range) |slice_advance| {
+            max_piece_width = Au::max(max_piece_width, slice_advance);
         }
         max_piece_width
     }
 
+    /// Calls `f` with the advance width of the part of each slice that `range` covers. Slices
+    /// covered whole use the advances measured when they were shaped; only the slices at the
+    /// ends of the range, if it cuts them, are measured again.
+    fn each_slice_advance(&self, range: &Range, f: &fn(Au)) {
+        for (slice_glyphs, _offset, slice_range) in self.iter_slices_for_range(range) {
+            f(self.font.measure_text_for_slice(slice_glyphs, &slice_range).advance_width);
+        }
+    }
+
     pub fn iter_slices_for_range(&'self self, range: &Range) -> SliceIterator<'self> {
         SliceIterator {
             glyph_iter: self.glyphs.iter(),
@@ -95,6 +95,35 @@ pub fn transform_text(text: &str, mode: CompressionMode, incoming_whitespace: bo
     }
 }
 
+/// How many characters the first `offset` bytes of `text` become once their whitespace is
+/// compressed as `transform_text` does in `CompressWhitespaceNewline` mode. This maps a position
+/// in a text node's data to one in the text laid out for it (Issue #114).
+///
+/// FIXME: Like the text run scanner, this doesn't know whether the text before `text` ended in
+/// whitespace, and assumes it didn't.
+pub fn compressed_offset(text: &str, offset: uint) -> uint {
+    let (compressed, _) = transform_text(text.slice_to(offset), CompressWhitespaceNewline, false);
+    compressed.char_len()
+}
+
+/// The inverse of `compressed_offset`: the byte offset in `text` just past the characters that
+/// compress to its first `offset` characters.
+pub fn uncompressed_offset(text: &str, offset: uint) -> uint {
+    let mut in_whitespace = false;
+    let mut kept = 0;
+    for (i, ch) in text.char_offset_iter() {
+        if kept == offset {
+            return i
+        }
+        let next_in_whitespace = ch == ' ' || ch == '\t' || ch == '\n';
+        if !next_in_whitespace || !in_whitespace {
+            kept += 1;
+        }
+        in_whitespace = next_in_whitespace;
+    }
+    text.len()
+}
+
 pub fn float_to_fixed(before: int, f: float) -> i32 {
     (1i32 << before) * (f as i32)
 }
@@ -250,3 +279,15 @@ fn test_transform_compress_whitespace_newline() {
         assert!(trimmed_str == oracle_strs[i])
     }
 }
+
+#[test]
+fn test_compressed_offsets() {
+    let text = "foo  \n bar baz";
+    assert!(compressed_offset(text, 0) == 0);
+    assert!(compressed_offset(text, 3) == 3);
+    assert!(compressed_offset(text, 7) == 4);
+    assert!(compressed_offset(text, 10) == 7);
+    assert!(uncompressed_offset(text, 4) == 4);
+    assert!(uncompressed_offset(text, 6) == 9);
+    assert!(uncompressed_offset(text, 20) == text.len());
+}
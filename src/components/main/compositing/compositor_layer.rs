@@ -3,17 +3,29 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::cell::Cell;
+use std::util::replace;
 use geom::point::Point2D;
 use geom::size::Size2D;
 use geom::rect::Rect;
 use geom::matrix::identity;
-use gfx::render_task::{ReRenderMsg, UnusedBufferMsg};
+use gfx::render_task::{ReRenderMsg, RenderRequest, UnusedBufferMsg};
 use servo_msg::compositor_msg::{LayerBuffer, LayerBufferSet, Epoch};
+use servo_msg::compositor_msg::{Cursor, DefaultCursor, HitTestItem};
+use servo_msg::compositor_msg::{NativeOverlay, NativeOverlayGeometry, NativeOverlayId};
+use servo_msg::compositor_msg::{VideoFrame, VideoLayerGeometry, VideoLayerId};
 use servo_msg::constellation_msg::PipelineId;
-use script::dom::event::{ClickEvent, MouseDownEvent, MouseUpEvent};
-use script::script_task::SendEventMsg;
+use servo_util::geometry::{DevicePx, PagePx, ScaleFactor, TypedRect};
+use script::dom::event::{ClickEvent, MouseDownEvent, MouseUpEvent, ScrollEvent, Key, KeyEvent};
+use script::dom::event::{Event_, MouseMoveEvent, SelectOptionEvent, TouchStartEvent};
+use script::dom::event::{TouchMoveEvent, TouchEndEvent, TouchCancelEvent};
+use script::layout_interface::SetNativeOverlaysMsg;
+use script::script_task::{SendEventMsg, TickAnimationFrameMsg};
 use windowing::{MouseWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
+use windowing::{MouseWindowMoveEvent, TouchWindowEvent, TouchWindowStartEvent};
+use windowing::{TouchWindowMoveEvent, TouchWindowEndEvent, TouchWindowCancelEvent};
 use compositing::quadtree::{Quadtree, Normal, Invalid, Hidden};
+use compositing::video_layer::VideoLayer;
+use gfx::opts::Opts;
 use layers::layers::{ContainerLayerKind, ContainerLayer, TextureLayerKind, TextureLayer, TextureManager};
 use pipeline::Pipeline;
 use constellation::{SendableChildFrameTree, SendableFrameTree};
@@ -47,6 +59,13 @@ pub struct CompositorLayer {
     /// A monotonically increasing counter that keeps track of the current epoch.
     /// add_buffer() calls that don't match the current epoch will be ignored.
     epoch: Epoch,
+    /// What layout told us about the contents of this layer, topmost item last. Lets us pick
+    /// cursors and wheel targets without asking layout.
+    hit_test_items: ~[HitTestItem],
+    /// Where layout put the elements of the embedder's native overlays that are in this layer.
+    native_overlays: ~[NativeOverlayGeometry],
+    /// The videos in this layer, whose frames are shown above its tiles.
+    videos: ~[VideoLayer],
 }
 
 /// Helper struct for keeping CompositorLayer children organized.
@@ -85,6 +104,9 @@ impl CompositorLayer {
             root_layer: @mut ContainerLayer(),
             hidden: true,
             epoch: Epoch(0),
+            hit_test_items: ~[],
+            native_overlays: ~[],
+            videos: ~[],
         }
     }
     
@@ -126,6 +148,24 @@ impl CompositorLayer {
     // true; otherwise returns false, so a parent layer can scroll instead.
     pub fn scroll(&mut self, delta: Point2D<f32>, cursor: Point2D<f32>, window_size: Size2D<f32>) -> bool {
         let cursor = cursor - self.scroll_offset;
+
+        // If layout told us which iframe is under the cursor, offer the scroll to it first.
+        let target = do self.hit_test(cursor).chain |item| { item.scroll_subpage };
+        for subpage_id in target.iter() {
+            let target_subpage = Some(*subpage_id);
+            for child in self.children.mut_iter().filter(|x| !x.child.hidden) {
+                if child.child.pipeline.subpage_id != target_subpage {
+                    loop;
+                }
+                match child.container.scissor {
+                    Some(rect) if child.child.scroll(delta, cursor - rect.origin, rect.size) => {
+                        return true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
         for child in self.children.mut_iter().filter(|x| !x.child.hidden) {
             match child.container.scissor {
                 None => {
@@ -163,9 +203,226 @@ impl CompositorLayer {
         self.root_layer.common.set_transform(identity().translate(self.scroll_offset.x,
                                                                   self.scroll_offset.y,
                                                                   0.0));
+
+        // Let script know how far into the page we are. The scroll offset is the translation of
+        // the page, so it's negated.
+        let page_offset = Point2D(-self.scroll_offset.x, -self.scroll_offset.y);
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(),
+                                                    ScrollEvent(page_offset)));
         true
     }
 
+    // Scrolls the layer with the given pipeline id so that the given point in its page is at the
+    // top left of its window, as far as the page size allows. Returns true if it scrolled.
+    pub fn scroll_to(&mut self, pipeline_id: PipelineId, point: Point2D<f32>,
+                     window_size: Size2D<f32>) -> bool {
+        if self.pipeline.id == pipeline_id {
+            if self.page_size.is_none() {
+                return false;
+            }
+            let delta = Point2D(-point.x, -point.y) - self.scroll_offset;
+            // Use (-1, -1) as the cursor position to make sure the scroll isn't propagated
+            // downwards.
+            self.scroll(delta, Point2D(-1f32, -1f32), window_size)
+        } else {
+            for child in self.children.mut_iter() {
+                match child.container.scissor {
+                    Some(rect) if child.child.contains_pipeline(pipeline_id) => {
+                        return child.child.scroll_to(pipeline_id, point, rect.size);
+                    }
+                    _ => {}
+                }
+            }
+            false
+        }
+    }
+
+    // Returns the topmost hit test item under the given point, in page coordinates relative to
+    // this layer's scroll position.
+    fn hit_test<'a>(&'a self, point: Point2D<f32>) -> Option<&'a HitTestItem> {
+        self.hit_test_items.rev_iter().find(|item| {
+            let bounds = item.bounds;
+            point.x >= bounds.origin.x && point.x < bounds.origin.x + bounds.size.width &&
+                point.y >= bounds.origin.y && point.y < bounds.origin.y + bounds.size.height
+        })
+    }
+
+    // Returns the cursor to show at the given point in page coordinates, looking into child
+    // layers the same way mouse events do.
+    pub fn cursor_at(&self, cursor: Point2D<f32>) -> Cursor {
+        let cursor = cursor - self.scroll_offset;
+        for child in self.children.iter().filter(|&x| !x.child.hidden) {
+            for rect in child.container.scissor.iter() {
+                if cursor.x >= rect.origin.x && cursor.x < rect.origin.x + rect.size.width
+                    && cursor.y >= rect.origin.y && cursor.y < rect.origin.y + rect.size.height {
+                    return child.child.cursor_at(cursor - rect.origin);
+                }
+            }
+        }
+        match self.hit_test(cursor) {
+            Some(item) => item.cursor,
+            None => DefaultCursor,
+        }
+    }
+
+    // Replaces the hit test items of the layer with the given pipeline id.
+    // This method returns false if the specified layer is not found.
+    pub fn set_hit_test_items(&mut self, pipeline_id: PipelineId, items: ~[HitTestItem]) -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.hit_test_items = items;
+            true
+        } else {
+            let items = Cell::new(items);
+            for child in self.children.mut_iter() {
+                if child.child.contains_pipeline(pipeline_id) {
+                    return child.child.set_hit_test_items(pipeline_id, items.take());
+                }
+            }
+            false
+        }
+    }
+
+    // Replaces the native overlay geometry of the layer with the given pipeline id.
+    // This method returns false if the specified layer is not found.
+    pub fn set_native_overlays(&mut self,
+                               pipeline_id: PipelineId,
+                               overlays: ~[NativeOverlayGeometry])
+                               -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.native_overlays = overlays;
+            true
+        } else {
+            let overlays = Cell::new(overlays);
+            for child in self.children.mut_iter() {
+                if child.child.contains_pipeline(pipeline_id) {
+                    return child.child.set_native_overlays(pipeline_id, overlays.take());
+                }
+            }
+            false
+        }
+    }
+
+    // Replaces the videos of the layer with the given pipeline id, keeping the frames of the ones
+    // that were there before. This method returns false if the specified layer is not found.
+    pub fn set_videos(&mut self, pipeline_id: PipelineId, videos: ~[VideoLayerGeometry]) -> bool {
+        if self.pipeline.id == pipeline_id {
+            let mut old_videos = replace(&mut self.videos, ~[]);
+            for geometry in videos.iter() {
+                let video = match old_videos.iter().position(|video| video.id == geometry.id) {
+                    Some(index) => {
+                        let mut video = old_videos.swap_remove(index);
+                        video.set_bounds(geometry.bounds);
+                        video
+                    }
+                    None => VideoLayer::new(geometry),
+                };
+                self.videos.push(video);
+            }
+            self.rebuild_layer_tree();
+            true
+        } else {
+            let videos = Cell::new(videos);
+            for child in self.children.mut_iter() {
+                if child.child.contains_pipeline(pipeline_id) {
+                    return child.child.set_videos(pipeline_id, videos.take());
+                }
+            }
+            false
+        }
+    }
+
+    // Shows a new frame of the given video of the layer with the given pipeline id. This method
+    // returns false if the specified layer or video is not found.
+    pub fn show_video_frame(&mut self,
+                            pipeline_id: PipelineId,
+                            id: VideoLayerId,
+                            frame: ~VideoFrame,
+                            opts: &Opts)
+                            -> bool {
+        if self.pipeline.id == pipeline_id {
+            match self.videos.mut_iter().find(|video| video.id == id) {
+                Some(video) => video.show_frame(frame, opts),
+                None => return false,
+            }
+            self.rebuild_layer_tree();
+            true
+        } else {
+            let frame = Cell::new(frame);
+            for child in self.children.mut_iter() {
+                if child.child.contains_pipeline(pipeline_id) {
+                    return child.child.show_video_frame(pipeline_id, id, frame.take(), opts);
+                }
+            }
+            false
+        }
+    }
+
+    // Tells the layout task of this layer and of all its descendants which native overlays the
+    // embedder registered.
+    pub fn send_native_overlays(&self, overlays: &[NativeOverlay]) {
+        self.pipeline.layout_chan.send(SetNativeOverlaysMsg(overlays.to_owned()));
+        for child in self.children.iter() {
+            child.child.send_native_overlays(overlays);
+        }
+    }
+
+    // Returns where the element of the given native overlay is, in page coordinates relative to
+    // the parent of this layer, clipped to the layers it's in. Returns None if the element isn't
+    // in a visible layer.
+    pub fn native_overlay_bounds(&self, id: NativeOverlayId) -> Option<Rect<f32>> {
+        if self.hidden {
+            return None;
+        }
+        for overlay in self.native_overlays.iter() {
+            if overlay.id == id {
+                return Some(overlay.bounds.translate(&self.scroll_offset));
+            }
+        }
+        for child in self.children.iter().filter(|&x| !x.child.hidden) {
+            for rect in child.container.scissor.iter() {
+                match child.child.native_overlay_bounds(id) {
+                    Some(bounds) => {
+                        let bounds = rect.intersection(&bounds.translate(&rect.origin));
+                        return do bounds.map_move |bounds| {
+                            bounds.translate(&self.scroll_offset)
+                        };
+                    }
+                    None => {}
+                }
+            }
+        }
+        None
+    }
+
+    // Returns where a rect in the page of the given pipeline is, in this layer's page coordinates,
+    // or None if that pipeline's layer isn't showing.
+    pub fn rect_from_pipeline(&self, pipeline_id: PipelineId, rect: Rect<f32>)
+                              -> Option<Rect<f32>> {
+        if self.hidden {
+            return None;
+        }
+        if self.pipeline.id == pipeline_id {
+            return Some(rect.translate(&self.scroll_offset));
+        }
+        for child in self.children.iter().filter(|&x| !x.child.hidden) {
+            for scissor in child.container.scissor.iter() {
+                match child.child.rect_from_pipeline(pipeline_id, rect) {
+                    Some(rect) => {
+                        return Some(rect.translate(&scissor.origin).translate(&self.scroll_offset));
+                    }
+                    None => {}
+                }
+            }
+        }
+        None
+    }
+
+    // Returns true if this layer or one of its descendants belongs to the given pipeline.
+    fn contains_pipeline(&self, pipeline_id: PipelineId) -> bool {
+        self.pipeline.id == pipeline_id ||
+            self.children.iter().any(|x| x.child.contains_pipeline(pipeline_id))
+    }
+
     // Takes in a MouseWindowEvent, determines if it should be passed to children, and 
     // sends the event off to the appropriate pipeline. NB: the cursor position is in
     // page coordinates.
@@ -191,18 +448,89 @@ impl CompositorLayer {
             MouseWindowClickEvent(button, _) => ClickEvent(button, cursor),
             MouseWindowMouseDownEvent(button, _) => MouseDownEvent(button, cursor),
             MouseWindowMouseUpEvent(button, _) => MouseUpEvent(button, cursor),
+            MouseWindowMoveEvent(_) => MouseMoveEvent(cursor),
         };
         
         self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(), message));
     }
     
+    // Given the current window size, determine which child layer the touch falls on, and send it
+    // to that layer's pipeline. The point is in page coordinates.
+    // FIXME: Every event of a touch should go to the pipeline the touch started in, even once the
+    // finger moves out of its frame.
+    pub fn send_touch_event(&self, event: TouchWindowEvent, point: Point2D<f32>) {
+        let point = point - self.scroll_offset;
+        for child in self.children.iter().filter(|&x| !x.child.hidden) {
+            match child.container.scissor {
+                None => {
+                    error!("CompositorLayer: unable to perform touch hit test for layer");
+                }
+                Some(rect) => {
+                    if point.x >= rect.origin.x && point.x < rect.origin.x + rect.size.width
+                        && point.y >= rect.origin.y && point.y < rect.origin.y + rect.size.height {
+                        child.child.send_touch_event(event, point - rect.origin);
+                        return;
+                    }
+                }
+            }
+        }
+
+        let message = match event {
+            TouchWindowStartEvent(id, _) => TouchStartEvent(id, point),
+            TouchWindowMoveEvent(id, _) => TouchMoveEvent(id, point),
+            TouchWindowEndEvent(id, _) => TouchEndEvent(id, point),
+            TouchWindowCancelEvent(id, _) => TouchCancelEvent(id, point),
+        };
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(), message));
+    }
+
+    // Sends a key the user pressed to this layer's pipeline.
+    // FIXME: This should go to the pipeline of the frame that has focus.
+    pub fn send_key_event(&self, key: Key) {
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(), KeyEvent(key)));
+    }
+
+    // Sends a copy or paste the user asked for to this layer's pipeline.
+    // FIXME: Like keys, this should go to the pipeline of the frame that has focus.
+    pub fn send_clipboard_event(&self, event: Event_) {
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(), event));
+    }
+
+    // Sends an animation frame tick to the layer with the given pipeline id, including hidden
+    // layers. Returns false if the layer is not found.
+    pub fn tick_animation_frame(&self, pipeline_id: PipelineId) -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.pipeline.script_chan.send(TickAnimationFrameMsg(pipeline_id));
+            true
+        } else {
+            self.children.iter().any(|x| x.child.tick_animation_frame(pipeline_id))
+        }
+    }
+
+    // Tells the layer with the given pipeline id which option of its `<select>` popup the user
+    // picked, if any. Returns false if the layer is not found.
+    pub fn send_select_option(&self, pipeline_id: PipelineId, index: Option<uint>) -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.pipeline.script_chan.send(SendEventMsg(pipeline_id, SelectOptionEvent(index)));
+            true
+        } else {
+            self.children.iter().any(|x| x.child.send_select_option(pipeline_id, index))
+        }
+    }
+
     // Given the current window size, determine which tiles need to be (re)rendered
     // and sends them off the the appropriate renderer.
     // Returns a bool that is true if the scene should be repainted.
-    pub fn get_buffer_request(&mut self, window_rect: Rect<f32>, scale: f32) -> bool {
-        let rect = Rect(Point2D(-self.scroll_offset.x + window_rect.origin.x,
-                                -self.scroll_offset.y + window_rect.origin.y),
-                        window_rect.size);
+    pub fn get_buffer_request(&mut self,
+                              window_rect: TypedRect<PagePx, f32>,
+                              scale: ScaleFactor<PagePx, DevicePx>)
+                              -> bool {
+        // Tiles asked for ahead of a scroll can reach past the top or left of the page.
+        let x = -self.scroll_offset.x + window_rect.rect.origin.x;
+        let y = -self.scroll_offset.y + window_rect.rect.origin.y;
+        let rect = Rect(Point2D(x.max(&0.0), y.max(&0.0)),
+                        Size2D(window_rect.rect.size.width + x.min(&0.0),
+                               window_rect.rect.size.height + y.min(&0.0)));
         let mut redisplay: bool;
         { // block here to prevent double mutable borrow of self
             let quadtree = match self.quadtree {
@@ -210,13 +538,17 @@ impl CompositorLayer {
                                    no quadtree initialized", self.pipeline.id),
                 Tree(ref mut quadtree) => quadtree,
             };
-            let (request, unused) = quadtree.get_tile_rects_page(rect, scale);
+            let (request, unused) = quadtree.get_tile_rects_page(rect, scale.get());
             redisplay = !unused.is_empty(); // workaround to make redisplay visible outside block
             if redisplay { // send back unused tiles
                 self.pipeline.render_chan.send(UnusedBufferMsg(unused));
             }
             if !request.is_empty() { // ask for tiles
-                self.pipeline.render_chan.send(ReRenderMsg(request, scale, self.epoch));
+                self.pipeline.render_chan.send(ReRenderMsg(RenderRequest {
+                    buffer_requests: request,
+                    scale: scale,
+                    epoch: self.epoch,
+                }));
             }
         }
         if redisplay {
@@ -225,10 +557,10 @@ impl CompositorLayer {
         let transform = |x: &mut CompositorLayerChild| -> bool {
             match x.container.scissor {
                 Some(scissor) => {
-                    let new_rect = window_rect.intersection(&scissor);
+                    let new_rect = window_rect.rect.intersection(&scissor);
                     match new_rect {
                         Some(new_rect) => {
-                            x.child.get_buffer_request(new_rect, scale)
+                            x.child.get_buffer_request(TypedRect { rect: new_rect }, scale)
                         }
                         None => {
                             false //Layer is offscreen
@@ -392,7 +724,7 @@ impl CompositorLayer {
                 None => {
                     debug!("osmain: adding new texture layer");
                     texture_layer = @mut TextureLayer::new(@buffer.draw_target.clone() as @TextureManager,
-                                                           buffer.screen_pos.size);
+                                                           buffer.screen_pos.rect.size);
                     self.root_layer.add_child_end(TextureLayerKind(texture_layer));
                     None
                 }
@@ -409,13 +741,23 @@ impl CompositorLayer {
             };
             
 
-            let rect = buffer.rect;
+            let rect = buffer.rect.rect;
             // Set the layer's transform.
             let transform = identity().translate(rect.origin.x, rect.origin.y, 0.0);
             let transform = transform.scale(rect.size.width, rect.size.height, 1.0);
             texture_layer.common.set_transform(transform);
         }
 
+        // Add videos, above the tiles.
+        for video in self.videos.iter() {
+            for &texture_layer in video.texture_layer.iter() {
+                texture_layer.common.parent = None;
+                texture_layer.common.prev_sibling = None;
+                texture_layer.common.next_sibling = None;
+                self.root_layer.add_child_end(TextureLayerKind(texture_layer));
+            }
+        }
+
         // Add child layers.
         for child in self.children.mut_iter().filter(|x| !x.child.hidden) {
             current_layer_child = match current_layer_child {
@@ -435,6 +777,14 @@ impl CompositorLayer {
 
     }
     
+    // Rebuilds the layer tree if there are tiles to build it from yet.
+    fn rebuild_layer_tree(&mut self) {
+        match self.quadtree {
+            NoTree(*) => {}
+            Tree(_) => self.build_layer_tree(),
+        }
+    }
+
     // Add LayerBuffers to the specified layer. Returns false if the layer is not found.
     // If the epoch of the message does not match the layer's epoch, the message is ignored.
     pub fn add_buffers(&mut self, pipeline_id: PipelineId, new_buffers: ~LayerBufferSet, epoch: Epoch) -> bool {
@@ -454,9 +804,11 @@ impl CompositorLayer {
                 let mut unused_tiles = ~[];
                 // move_rev_iter is more efficient
                 for buffer in cell.take().buffers.move_rev_iter() {
-                    unused_tiles.push_all_move(quadtree.add_tile_pixel(buffer.screen_pos.origin.x,
-                                                                       buffer.screen_pos.origin.y,
-                                                                       buffer.resolution, buffer));
+                    let origin = buffer.screen_pos.rect.origin;
+                    unused_tiles.push_all_move(quadtree.add_tile_pixel(origin.x,
+                                                                       origin.y,
+                                                                       buffer.resolution.get(),
+                                                                       buffer));
                 }
                 if !unused_tiles.is_empty() { // send back unused buffers
                     self.pipeline.render_chan.send(UnusedBufferMsg(unused_tiles));
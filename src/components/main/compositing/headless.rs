@@ -0,0 +1,43 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A compositor for running without a window: it answers the questions the other tasks ask the
+//! compositor, but never grants permission to paint, so pages are loaded and laid out without
+//! anything being drawn.
+
+use compositing::{ChangeReadyState, Exit, GetGLContext, GetSize, GetWindowMetrics, Msg, SetIds};
+
+use geom::size::Size2D;
+use servo_msg::compositor_msg::{FinishedLoading, WindowMetrics};
+use std::comm::Port;
+use std::ptr;
+
+/// The size of the window pages are laid out in when there isn't one, the same as the size of a
+/// new window.
+pub static HEADLESS_WINDOW_SIZE: Size2D<uint> = Size2D { width: 800, height: 600 };
+
+/// Handles the messages on `port` as if the window had the given metrics, calling `loaded` each
+/// time a document has been laid out. Returns when the compositor is told to exit, when `loaded`
+/// returns true, or when nothing can send on `port` any more.
+pub fn run(port: &Port<Msg>, metrics: WindowMetrics, loaded: &fn() -> bool) {
+    loop {
+        match port.try_recv() {
+            None | Some(Exit) => break,
+            Some(GetSize(chan)) => {
+                chan.send(Size2D(metrics.window_size.width as int,
+                                 metrics.window_size.height as int))
+            }
+            Some(GetWindowMetrics(chan)) => chan.send(metrics.clone()),
+            // Nothing is rendered, so there's no GL context to share.
+            Some(GetGLContext(chan)) => chan.send(ptr::null()),
+            Some(SetIds(_, response_chan, _)) => response_chan.send(()),
+            Some(ChangeReadyState(FinishedLoading)) => {
+                if loaded() {
+                    break
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
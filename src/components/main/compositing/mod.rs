@@ -2,18 +2,28 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use platform::{Application, Window};
 
 pub use windowing;
-use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
+use windowing::{WindowEvent, WindowMethods};
 use windowing::{IdleWindowEvent, ResizeWindowEvent, LoadUrlWindowEvent, MouseWindowEventClass};
 use windowing::{ScrollWindowEvent, ZoomWindowEvent, NavigationWindowEvent, FinishedWindowEvent};
 use windowing::{QuitWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
+use windowing::{MouseWindowMoveEvent, MouseMoveWindowEvent, AddNativeOverlayWindowEvent};
+use windowing::{FlingWindowEvent, PageZoomWindowEvent, RemoveNativeOverlayWindowEvent};
+use windowing::{KeyWindowEvent, CopyWindowEvent, PasteWindowEvent, TouchWindowEventClass};
+use windowing::{TouchWindowStartEvent, TouchWindowMoveEvent, TouchWindowEndEvent};
+use windowing::{TouchWindowCancelEvent, PrintMemoryReportsWindowEvent};
+use windowing::{NewTabWindowEvent, CloseTabWindowEvent, SwitchTabWindowEvent};
 
 use servo_msg::compositor_msg::{RenderListener, LayerBufferSet, RenderState};
-use servo_msg::compositor_msg::{ReadyState, ScriptListener, Epoch};
+use servo_msg::compositor_msg::{ReadyState, ScriptListener, Epoch, SelectPopup, WindowMetrics};
+use servo_msg::compositor_msg::{DefaultCursor, HitTestItem, TouchEventResult};
+use servo_msg::compositor_msg::{NativeOverlay, NativeOverlayGeometry, NativeOverlayId};
+use servo_msg::compositor_msg::{VideoFrame, VideoFrameSink, VideoLayerGeometry, VideoLayerId};
 use servo_msg::constellation_msg::{ConstellationChan, NavigateMsg, PipelineId, ResizedWindowMsg, LoadUrlMsg};
+use servo_msg::constellation_msg::{CloseTabMsg, NewTabMsg, SwitchTabMsg, TabId};
 use servo_msg::constellation_msg;
+use gfx::font_context::FontContext;
 use gfx::opts::Opts;
 
 use azure::azure_hl::{DataSourceSurface, DrawTarget, SourceSurfaceMethods, current_gl_context};
@@ -21,6 +31,7 @@ use azure::azure::AzGLContext;
 use std::comm;
 use std::comm::{Chan, SharedChan, Port};
 use std::num::Orderable;
+use std::util::replace;
 use std::vec;
 use std::rt::rtio::RtioTimer;
 use std::rt::io::timer::Timer;
@@ -34,18 +45,44 @@ use layers::rendergl;
 use layers::scene::Scene;
 use opengles::gl2;
 use png;
+use servo_net::resource_task::LoadData;
 use servo_util::{time, url};
+use servo_util::geometry::{DevicePx, PagePx, ScaleFactor, TypedPoint2D, TypedRect, TypedSize2D};
 use servo_util::time::profile;
 use servo_util::time::ProfilerChan;
+use servo_util::memory;
+use servo_util::memory::MemoryProfilerChan;
+use servo_util::clipboard::Clipboard;
+use script::dom::event::{CopyEvent, PasteEvent, DownKey, EnterKey, UpKey};
 
 use extra::future::from_value;
+use extra::url::Url;
 use extra::time::precise_time_s;
 
 use constellation::SendableFrameTree;
 use compositing::compositor_layer::CompositorLayer;
+use compositing::headless::HEADLESS_WINDOW_SIZE;
+use compositing::scrollbars::ViewportScrollbars;
+use compositing::scrolling::{ScrollAnimation, tile_rect_ahead};
+use compositing::select_popup::SelectPopupLayer;
+use compositing::touch::{TouchHandler, TouchEmulator, NoAction, Scroll, Fling};
+
+pub mod headless;
 
 mod quadtree;
 mod compositor_layer;
+mod scrollbars;
+mod scrolling;
+mod select_popup;
+mod touch;
+mod video_layer;
+
+/// The interval, in seconds, between animation frame ticks sent to script.
+static ANIMATION_FRAME_INTERVAL: float = 1.0 / 60.0;
+
+/// How far pages can be zoomed out and in, as in other browsers.
+static MIN_PAGE_ZOOM: f32 = 0.3;
+static MAX_PAGE_ZOOM: f32 = 3.0;
 
 
 /// The implementation of the layers-based compositor.
@@ -63,10 +100,50 @@ impl ScriptListener for CompositorChan {
         self.chan.send(msg);
     }
 
+    fn set_title(&self, id: PipelineId, title: ~str) {
+        self.chan.send(ChangeTitle(id, title));
+    }
+
     fn invalidate_rect(&self, id: PipelineId, rect: Rect<uint>) {
         self.chan.send(InvalidateRect(id, rect));
     }
 
+    fn request_animation_frame(&self, id: PipelineId) {
+        self.chan.send(RequestAnimationFrame(id));
+    }
+
+    fn get_window_metrics(&self) -> WindowMetrics {
+        let (port, chan) = comm::stream();
+        self.chan.send(GetWindowMetrics(chan));
+        port.recv()
+    }
+
+    fn show_select_popup(&self, id: PipelineId, popup: SelectPopup) {
+        self.chan.send(ShowSelectPopup(id, popup));
+    }
+
+    fn set_clipboard_text(&self, text: ~str) {
+        self.chan.send(SetClipboardText(text));
+    }
+
+    fn scroll_fragment_point(&self, id: PipelineId, point: Point2D<f32>) {
+        self.chan.send(ScrollFragmentPoint(id, point));
+    }
+
+    fn touch_event_processed(&self, result: TouchEventResult) {
+        self.chan.send(TouchEventProcessed(result));
+    }
+
+    fn video_frame_sink(&self) -> ~VideoFrameSink:Send {
+        ~self.clone() as ~VideoFrameSink:Send
+    }
+
+}
+
+impl VideoFrameSink for CompositorChan {
+    fn show_video_frame(&self, id: PipelineId, video: VideoLayerId, frame: ~VideoFrame) {
+        self.chan.send(ShowVideoFrame(id, video, frame))
+    }
 }
 
 /// Implementation of the abstract `RenderListener` interface.
@@ -98,6 +175,18 @@ impl RenderListener for CompositorChan {
         self.chan.send(SetLayerClipRect(id, new_rect))
     }
 
+    fn set_layer_hit_test_items(&self, id: PipelineId, items: ~[HitTestItem]) {
+        self.chan.send(SetLayerHitTestItems(id, items))
+    }
+
+    fn set_layer_native_overlays(&self, id: PipelineId, overlays: ~[NativeOverlayGeometry]) {
+        self.chan.send(SetLayerNativeOverlays(id, overlays))
+    }
+
+    fn set_layer_videos(&self, id: PipelineId, videos: ~[VideoLayerGeometry]) {
+        self.chan.send(SetLayerVideos(id, videos))
+    }
+
     fn delete_layer(&self, id: PipelineId) {
         self.chan.send(DeleteLayer(id))
     }
@@ -132,6 +221,8 @@ pub enum Msg {
     Exit,
     /// Requests the window size
     GetSize(Chan<Size2D<int>>),
+    /// Requests the metrics of the window and screen, for the script task.
+    GetWindowMetrics(Chan<WindowMetrics>),
     /// Requests the compositors GL context.
     GetGLContext(Chan<AzGLContext>),
 
@@ -141,17 +232,42 @@ pub enum Msg {
     SetLayerPageSize(PipelineId, Size2D<f32>, Epoch),
     /// Alerts the compositor that the specified layer's clipping rect has changed.
     SetLayerClipRect(PipelineId, Rect<f32>),
+    /// Replaces the hit testing information for the specified layer.
+    SetLayerHitTestItems(PipelineId, ~[HitTestItem]),
+    /// Replaces the geometry of the native overlays whose elements are in the specified layer.
+    SetLayerNativeOverlays(PipelineId, ~[NativeOverlayGeometry]),
+    /// Replaces the geometry of the videos in the specified layer.
+    SetLayerVideos(PipelineId, ~[VideoLayerGeometry]),
+    /// Shows a new frame of a video in the specified layer.
+    ShowVideoFrame(PipelineId, VideoLayerId, ~VideoFrame),
     /// Alerts the compositor that the specified layer has been deleted.
     DeleteLayer(PipelineId),
     /// Invalidate a rect for a given layer
     InvalidateRect(PipelineId, Rect<uint>),
+    /// Requests an animation frame tick for the given pipeline on the next composite.
+    RequestAnimationFrame(PipelineId),
+    /// Shows the options of a `<select>` in the given pipeline in a popup list.
+    ShowSelectPopup(PipelineId, SelectPopup),
+    /// Puts plain text on the system clipboard.
+    SetClipboardText(~str),
+    /// Scrolls the specified layer so that the given point in its page is at the top left.
+    ScrollFragmentPoint(PipelineId, Point2D<f32>),
+    /// Says whether the page canceled the last touch event it was sent.
+    TouchEventProcessed(TouchEventResult),
 
     /// Requests that the compositor paint the given layer buffer set for the given page size.
     Paint(PipelineId, ~LayerBufferSet, Epoch),
     /// Alerts the compositor to the current status of page loading.
     ChangeReadyState(ReadyState),
+    /// Alerts the compositor to the URL of the current session history entry.
+    ChangeUrl(Url),
+    /// Alerts the compositor to the title of the given pipeline's document.
+    ChangeTitle(PipelineId, ~str),
     /// Alerts the compositor to the current status of rendering.
     ChangeRenderState(RenderState),
+    /// Alerts the compositor to the ids of the tabs, in the order they were opened, and that of
+    /// the one shown.
+    ChangeTabs(~[TabId], TabId),
     /// Sets the channel to the current layout and render tasks, along with their id
     SetIds(SendableFrameTree, Chan<()>, ConstellationChan),
 }
@@ -185,6 +301,7 @@ pub struct CompositorTask {
     opts: Opts,
     port: Port<Msg>,
     profiler_chan: ProfilerChan,
+    memory_profiler_chan: MemoryProfilerChan,
     shutdown_chan: SharedChan<()>,
 }
 
@@ -192,21 +309,37 @@ impl CompositorTask {
     pub fn new(opts: Opts,
                port: Port<Msg>,
                profiler_chan: ProfilerChan,
+               memory_profiler_chan: MemoryProfilerChan,
                shutdown_chan: Chan<()>)
                -> CompositorTask {
         CompositorTask {
             opts: opts,
             port: port,
             profiler_chan: profiler_chan,
+            memory_profiler_chan: memory_profiler_chan,
             shutdown_chan: SharedChan::new(shutdown_chan),
         }
     }
 
-    /// Starts the compositor, which listens for messages on the specified port. 
-    pub fn run(&self) {
-        let app: Application = ApplicationMethods::new();
-        let window: @mut Window = WindowMethods::new(&app);
+    /// Starts a compositor without a window, which lays pages out in one the size of a new window
+    /// but never draws them.
+    pub fn run_headless(&self) {
+        let metrics = WindowMetrics {
+            screen_size: HEADLESS_WINDOW_SIZE,
+            available_screen_size: HEADLESS_WINDOW_SIZE,
+            window_size: HEADLESS_WINDOW_SIZE,
+            device_pixel_ratio: self.opts.device_pixels_per_px.unwrap_or_default(1f32),
+            color_depth: 24,
+        };
+        do headless::run(&self.port, metrics) {
+            self.opts.exit_after_load
+        }
+        self.shutdown_chan.send(())
+    }
 
+    /// Starts the compositor in the given window, which it draws to and takes events from until
+    /// the window quits or the compositor is told to exit.
+    pub fn run_in_window<A, W: WindowMethods<A>>(&self, window: @mut W) {
         // Create an initial layer tree.
         //
         // TODO: There should be no initial layer tree until the renderer creates one from the display
@@ -215,27 +348,91 @@ impl CompositorTask {
         let root_layer = @mut ContainerLayer();
         let window_size = window.size();
         let mut scene = Scene(ContainerLayerKind(root_layer), window_size, identity());
-        let mut window_size = Size2D(window_size.width as uint, window_size.height as uint);
+        let mut window_size: TypedSize2D<DevicePx, uint> = TypedSize2D(window_size.width as uint,
+                                                                       window_size.height as uint);
         let mut done = false;
         let mut recomposite = false;
 
-        // Keeps track of the current zoom factor
-        let mut world_zoom = 1f32;
+        // How many device pixels the display has to a CSS px. Some platforms can't tell us.
+        let device_pixels_per_px = match self.opts.device_pixels_per_px {
+            Some(device_pixels_per_px) => device_pixels_per_px,
+            None => {
+                let hidpi = window.hidpi_factor();
+                if hidpi > 0f32 { hidpi } else { 1f32 }
+            }
+        };
+
+        // How far the user zoomed the page, which lays it out again with bigger CSS pixels, and
+        // how far they pinched into it, which only scales the tiles already rendered.
+        let mut page_zoom = 1f32;
+        let mut pinch_zoom = 1f32;
+
+        // The scale pages are rendered and composited at: the device pixels per px, times both
+        // zoom factors. Tiles are rendered at this scale, so they stay sharp on HiDPI displays.
+        let mut world_zoom: ScaleFactor<PagePx, DevicePx> = ScaleFactor(device_pixels_per_px);
+        root_layer.common.set_transform(identity().scale(device_pixels_per_px,
+                                                         device_pixels_per_px,
+                                                         1f32));
         let mut zoom_action = false;
         let mut zoom_time = 0f;
 
+        // The animation wheel scrolls and flings move the page with, where the cursor was when
+        // it started, and how far it last scrolled the page, which tiles are asked for ahead of.
+        let mut scroll_animation = ScrollAnimation::new();
+        let mut scroll_cursor = Point2D(0f32, 0f32);
+        let mut scroll_delta = Point2D(0f32, 0f32);
+
+        // The fingers on the screen, and whether they scroll the page. With touch emulation, the
+        // mouse stands in for a finger.
+        let mut touch_handler = TouchHandler::new();
+        let mut touch_emulator = if self.opts.emulate_touch {
+            Some(TouchEmulator::new())
+        } else {
+            None
+        };
+
+        // Pipelines waiting on a `requestAnimationFrame` tick, and when we last sent one.
+        let mut animation_frame_requests: ~[PipelineId] = ~[];
+        let mut last_animation_frame_time = 0f;
+
+        // The cursor we last asked the window to show, and where the mouse was then.
+        let mut cursor = DefaultCursor;
+        let mut mouse_position: Option<Point2D<f32>> = None;
+        // Whether a mouse button is held down, in which case the page hears about mouse moves.
+        let mut mouse_down = false;
+
+        // The native overlays the embedder registered, and where we last told it they are.
+        let mut native_overlays: ~[NativeOverlay] = ~[];
+        let mut native_overlay_rects: ~[(NativeOverlayId, Option<Rect<f32>>)] = ~[];
+
+        // The title of each page's document, so that the window can show the root page's.
+        let mut page_titles: ~[(PipelineId, ~str)] = ~[];
+
+        // The list of options of the `<select>` the user opened, if any, and the font context it
+        // shapes their labels with.
+        let mut select_popup: Option<SelectPopupLayer> = None;
+        let popup_font_ctx = @mut FontContext::new(self.opts.render_backend.clone(),
+                                                   false,
+                                                   self.profiler_chan.clone());
+
+        // The window's scrollbars, drawn over the page.
+        let mut viewport_scrollbars = ViewportScrollbars::new(root_layer,
+                                                              popup_font_ctx,
+                                                              &self.opts);
+
         // The root CompositorLayer
         let mut compositor_layer: Option<CompositorLayer> = None;
         let mut constellation_chan: Option<ConstellationChan> = None;
 
         // Get BufferRequests from each layer.
         let ask_for_tiles = || {
-            let window_size_page = Size2D(window_size.width as f32 / world_zoom,
-                                          window_size.height as f32 / world_zoom);
+            let window_size_page = world_zoom.inv().transform_size(&window_size.to_f32());
             for layer in compositor_layer.mut_iter() {
                 if !layer.hidden {
-                    recomposite = layer.get_buffer_request(Rect(Point2D(0f32, 0f32), window_size_page),
-                                                           world_zoom) || recomposite;
+                    let window_rect = TypedRect {
+                        rect: tile_rect_ahead(window_size_page.size, scroll_delta),
+                    };
+                    recomposite = layer.get_buffer_request(window_rect, world_zoom) || recomposite;
                 } else { 
                     debug!("Compositor: root layer is hidden!");
                 }
@@ -249,11 +446,25 @@ impl CompositorTask {
                     Exit => done = true,
 
                     ChangeReadyState(ready_state) => window.set_ready_state(ready_state),
+                    ChangeUrl(url) => window.set_url(url.to_str()),
+                    ChangeTitle(id, title) => {
+                        if compositor_layer.map_default(false, |layer| layer.pipeline.id == id) {
+                            window.set_page_title(title.as_slice());
+                        }
+                        page_titles.retain(|&(page_id, _)| page_id != id);
+                        page_titles.push((id, title));
+                    }
                     ChangeRenderState(render_state) => window.set_render_state(render_state),
+                    ChangeTabs(tabs, shown) => window.set_tabs(tabs, shown),
 
                     SetIds(frame_tree, response_chan, new_constellation_chan) => {
                         response_chan.send(());
 
+                        for popup in select_popup.take().iter() {
+                            popup.close(&compositor_layer, None);
+                        }
+                        scroll_animation.stop();
+
                         // This assumes there is at most one child, which should be the case.
                         match root_layer.first_child {
                             Some(old_layer) => root_layer.remove_child(old_layer),
@@ -264,7 +475,15 @@ impl CompositorTask {
                                                                      self.opts.tile_size,
                                                                      Some(10000000u));
                         root_layer.add_child_start(ContainerLayerKind(layer.root_layer));
+                        if !native_overlays.is_empty() {
+                            layer.send_native_overlays(native_overlays);
+                        }
+                        let root_id = layer.pipeline.id;
                         compositor_layer = Some(layer);
+                        match page_titles.iter().find(|&&(id, _)| id == root_id) {
+                            Some(&(_, ref title)) => window.set_page_title(title.as_slice()),
+                            None => window.set_page_title(""),
+                        }
 
                         constellation_chan = Some(new_constellation_chan);
                     }
@@ -274,6 +493,22 @@ impl CompositorTask {
                         chan.send(Size2D(size.width as int, size.height as int));
                     }
 
+                    GetWindowMetrics(chan) => {
+                        let size = window.size();
+                        let screen_size = window.screen_size();
+                        let screen_size = Size2D(screen_size.width as uint,
+                                                 screen_size.height as uint);
+                        chan.send(WindowMetrics {
+                            screen_size: screen_size,
+                            // FIXME: We have no way to ask the platform about docks and panels.
+                            available_screen_size: screen_size,
+                            window_size: Size2D(size.width as uint, size.height as uint),
+                            // Layout sees a zoomed page as one on a denser display.
+                            device_pixel_ratio: device_pixels_per_px * page_zoom,
+                            color_depth: 24,
+                        });
+                    }
+
                     GetGLContext(chan) => chan.send(current_gl_context()),
 
                     NewLayer(_id, new_size) => {
@@ -303,9 +538,8 @@ impl CompositorTask {
                     SetLayerPageSize(id, new_size, epoch) => {
                         match compositor_layer {
                             Some(ref mut layer) => {
-                                let page_window = Size2D(window_size.width as f32 / world_zoom,
-                                                         window_size.height as f32 / world_zoom);
-                                assert!(layer.resize(id, new_size, page_window, epoch));
+                                let page_window = world_zoom.inv().transform_size(&window_size.to_f32());
+                                assert!(layer.resize(id, new_size, page_window.size, epoch));
                                 ask_for_tiles();
                             }
                             None => {}
@@ -322,6 +556,46 @@ impl CompositorTask {
                         }
                     }
 
+                    SetLayerHitTestItems(id, items) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_hit_test_items(id, items));
+                            }
+                            None => {}
+                        }
+                    }
+
+                    SetLayerNativeOverlays(id, overlays) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_native_overlays(id, overlays));
+                            }
+                            None => {}
+                        }
+                    }
+
+                    SetLayerVideos(id, videos) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_videos(id, videos));
+                                recomposite = true;
+                            }
+                            None => {}
+                        }
+                    }
+
+                    ShowVideoFrame(id, video, frame) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                // The video may have gone away since the frame was sent.
+                                if layer.show_video_frame(id, video, frame, &self.opts) {
+                                    recomposite = true;
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+
                     DeleteLayer(id) => {
                         match compositor_layer {
                             Some(ref mut layer) => {
@@ -360,6 +634,56 @@ impl CompositorTask {
                             None => {} // Nothing to do
                         }
                     }
+
+                    RequestAnimationFrame(id) => {
+                        if !animation_frame_requests.contains(&id) {
+                            animation_frame_requests.push(id);
+                        }
+                    }
+
+                    ShowSelectPopup(id, popup) => {
+                        for old_popup in select_popup.take().iter() {
+                            old_popup.close(&compositor_layer, None);
+                        }
+                        let bounds = match compositor_layer {
+                            Some(ref layer) => layer.rect_from_pipeline(id, popup.bounds),
+                            None => None,
+                        };
+                        match bounds {
+                            Some(bounds) if !popup.options.is_empty() => {
+                                select_popup = Some(SelectPopupLayer::new(id,
+                                                                          popup,
+                                                                          bounds,
+                                                                          root_layer,
+                                                                          popup_font_ctx,
+                                                                          &self.opts,
+                                                                          world_zoom));
+                                recomposite = true;
+                            }
+                            _ => {
+                                // There's nothing to show, so the popup closes straight away.
+                                for layer in compositor_layer.iter() {
+                                    layer.send_select_option(id, None);
+                                }
+                            }
+                        }
+                    }
+
+                    SetClipboardText(text) => window.set_text(text.as_slice()),
+
+                    ScrollFragmentPoint(id, point) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                let page_window = world_zoom.inv().transform_size(&window_size.to_f32());
+                                recomposite = layer.scroll_to(id, point, page_window.size) ||
+                                    recomposite;
+                                ask_for_tiles();
+                            }
+                            None => {}
+                        }
+                    }
+
+                    TouchEventProcessed(result) => touch_handler.on_event_processed(result),
                 }
             }
         };
@@ -369,12 +693,12 @@ impl CompositorTask {
                 IdleWindowEvent => {}
 
                 ResizeWindowEvent(width, height) => {
-                    let new_size = Size2D(width, height);
+                    let new_size = TypedSize2D(width, height);
                     if window_size != new_size {
                         debug!("osmain: window resized to %ux%u", width, height);
                         window_size = new_size;
                         match constellation_chan {
-                            Some(ref chan) => chan.send(ResizedWindowMsg(new_size)),
+                            Some(ref chan) => chan.send(ResizedWindowMsg(new_size.size)),
                             None => error!("Compositor: Recieved resize event without initialized layout chan"),
                         }
                     } else {
@@ -389,60 +713,267 @@ impl CompositorTask {
                         None => fail!("Compositor: Received LoadUrlWindowEvent without initialized compositor layers"),
                     };
                     match constellation_chan {
-                        Some(ref chan) => chan.send(LoadUrlMsg(root_pipeline_id,
-                                                               url::make_url(url_string.to_str(), None),
-                                                               from_value(window_size))),
+                        Some(ref chan) => {
+                            let url = url::make_url(url_string.to_str(), None);
+                            chan.send(LoadUrlMsg(root_pipeline_id,
+                                                 LoadData::new(url),
+                                                 from_value(window_size.size)))
+                        }
                         None => error!("Compositor: Recieved loadurl event without initialized layout chan"),
                     }
                 }
                 
                 MouseWindowEventClass(mouse_window_event) => {
+                    // Pressing on the page catches it mid-fling.
+                    match mouse_window_event {
+                        MouseWindowMouseDownEvent(*) => scroll_animation.stop(),
+                        _ => {}
+                    }
                     let point = match mouse_window_event {
-                        MouseWindowClickEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
-                        MouseWindowMouseDownEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
-                        MouseWindowMouseUpEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
+                        MouseWindowClickEvent(_, p) |
+                        MouseWindowMouseDownEvent(_, p) |
+                        MouseWindowMouseUpEvent(_, p) |
+                        MouseWindowMoveEvent(p) => {
+                            world_zoom.inv().transform_point(&TypedPoint2D(p.x, p.y)).point
+                        }
                     };
-                    for layer in compositor_layer.iter() {
-                        layer.send_mouse_event(mouse_window_event, point);
+                    // Presses on the window's scrollbars, and the clicks and releases that follow
+                    // them, don't reach the page.
+                    let on_scrollbar = select_popup.is_none() && match mouse_window_event {
+                        MouseWindowMouseDownEvent(*) => {
+                            viewport_scrollbars.mouse_down(&mut compositor_layer, point)
+                        }
+                        MouseWindowClickEvent(*) => viewport_scrollbars.click(),
+                        MouseWindowMouseUpEvent(*) => viewport_scrollbars.mouse_up(),
+                        MouseWindowMoveEvent(*) => false,
+                    };
+                    match select_popup {
+                        // While a select popup is open, clicks pick an option from it or close it,
+                        // and don't reach the page.
+                        Some(ref popup) => {
+                            match mouse_window_event {
+                                MouseWindowClickEvent(*) => {
+                                    popup.close(&compositor_layer, popup.option_at(point));
+                                }
+                                _ => {}
+                            }
+                        }
+                        None if on_scrollbar => ask_for_tiles(),
+                        None => {
+                            for layer in compositor_layer.iter() {
+                                layer.send_mouse_event(mouse_window_event, point);
+                            }
+                        }
+                    }
+                    match mouse_window_event {
+                        MouseWindowClickEvent(*) if select_popup.is_some() => {
+                            select_popup = None;
+                            recomposite = true;
+                        }
+                        MouseWindowMouseDownEvent(*) => mouse_down = true,
+                        MouseWindowMouseUpEvent(*) => mouse_down = false,
+                        _ => {}
                     }
                 }
                 
+                TouchWindowEventClass(touch_window_event) => {
+                    for popup in select_popup.take().iter() {
+                        popup.close(&compositor_layer, None);
+                    }
+
+                    let now = precise_time_s();
+                    let (id, point) = match touch_window_event {
+                        TouchWindowStartEvent(id, p) |
+                        TouchWindowMoveEvent(id, p) |
+                        TouchWindowEndEvent(id, p) |
+                        TouchWindowCancelEvent(id, p) => (id, p),
+                    };
+                    let page_point = world_zoom.inv().transform_point(&TypedPoint2D(point.x,
+                                                                                    point.y));
+                    for layer in compositor_layer.iter() {
+                        layer.send_touch_event(touch_window_event, page_point.point);
+                    }
+
+                    let action = match touch_window_event {
+                        TouchWindowStartEvent(*) => {
+                            // Touching the page catches it mid-fling.
+                            scroll_animation.stop();
+                            touch_handler.on_touch_down(id, point, now);
+                            NoAction
+                        }
+                        TouchWindowMoveEvent(*) => touch_handler.on_touch_move(id, point, now),
+                        TouchWindowEndEvent(*) => touch_handler.on_touch_up(id),
+                        TouchWindowCancelEvent(*) => {
+                            touch_handler.on_touch_cancel(id);
+                            NoAction
+                        }
+                    };
+                    match action {
+                        NoAction => {}
+                        // The page follows the finger exactly, rather than easing after it.
+                        Scroll(delta) => {
+                            let device_to_page = world_zoom.inv();
+                            let page_delta = device_to_page.transform_point(
+                                &TypedPoint2D(delta.x, delta.y));
+                            let page_window = device_to_page.transform_size(&window_size.to_f32());
+                            for layer in compositor_layer.mut_iter() {
+                                recomposite = layer.scroll(page_delta.point,
+                                                           page_point.point,
+                                                           page_window.size) || recomposite;
+                            }
+                            ask_for_tiles();
+                        }
+                        Fling(velocity) => {
+                            scroll_animation.fling(velocity, now);
+                            scroll_cursor = point;
+                        }
+                    }
+                }
+
+                MouseMoveWindowEvent(point) => {
+                    mouse_position = Some(point);
+                    if mouse_down && select_popup.is_none() {
+                        let page_point = world_zoom.inv().transform_point(&TypedPoint2D(point.x,
+                                                                                        point.y));
+                        if viewport_scrollbars.mouse_move(&mut compositor_layer, page_point.point) {
+                            ask_for_tiles();
+                        } else {
+                            for layer in compositor_layer.iter() {
+                                layer.send_mouse_event(MouseWindowMoveEvent(point),
+                                                       page_point.point);
+                            }
+                        }
+                    }
+                    for popup in select_popup.mut_iter() {
+                        let page_point = world_zoom.inv().transform_point(&TypedPoint2D(point.x,
+                                                                                        point.y));
+                        let index = popup.option_at(page_point.point);
+                        if index.is_some() {
+                            recomposite = popup.highlight(index, world_zoom) || recomposite;
+                        }
+                    }
+                }
+
+                KeyWindowEvent(key) => {
+                    match select_popup {
+                        // While a select popup is open, the arrow keys move through its options
+                        // and Enter picks one.
+                        Some(ref mut popup) => {
+                            match key {
+                                UpKey => {
+                                    recomposite = popup.step_highlight(-1, world_zoom) ||
+                                        recomposite;
+                                }
+                                DownKey => {
+                                    recomposite = popup.step_highlight(1, world_zoom) ||
+                                        recomposite;
+                                }
+                                EnterKey => popup.close(&compositor_layer, popup.highlighted),
+                                _ => {}
+                            }
+                        }
+                        None => {
+                            for layer in compositor_layer.iter() {
+                                layer.send_key_event(key);
+                            }
+                        }
+                    }
+                    if key == EnterKey && select_popup.is_some() {
+                        select_popup = None;
+                        recomposite = true;
+                    }
+                }
+
+                CopyWindowEvent => {
+                    for layer in compositor_layer.iter() {
+                        layer.send_clipboard_event(CopyEvent);
+                    }
+                }
+
+                PasteWindowEvent => {
+                    for text in window.get_text().iter() {
+                        for layer in compositor_layer.iter() {
+                            layer.send_clipboard_event(PasteEvent(text.clone()));
+                        }
+                    }
+                }
+
                 ScrollWindowEvent(delta, cursor) => {
-                    // TODO: modify delta to snap scroll to pixels.
-                    let page_delta = Point2D(delta.x as f32 / world_zoom, delta.y as f32 / world_zoom);
-                    let page_cursor: Point2D<f32> = Point2D(cursor.x as f32 / world_zoom,
-                                                            cursor.y as f32 / world_zoom);
-                    let page_window = Size2D(window_size.width as f32 / world_zoom,
-                                             window_size.height as f32 / world_zoom);
-                    for layer in compositor_layer.mut_iter() {
-                        recomposite = layer.scroll(page_delta, page_cursor, page_window) || recomposite;
+                    // The popup would be left behind by the select it belongs to.
+                    for popup in select_popup.take().iter() {
+                        popup.close(&compositor_layer, None);
                     }
-                    ask_for_tiles();
+
+                    // The page eases there over the next few frames.
+                    scroll_animation.scroll_by(delta, precise_time_s());
+                    scroll_cursor = Point2D(cursor.x as f32, cursor.y as f32);
+                }
+
+                FlingWindowEvent(velocity, cursor) => {
+                    for popup in select_popup.take().iter() {
+                        popup.close(&compositor_layer, None);
+                    }
+
+                    scroll_animation.fling(velocity, precise_time_s());
+                    scroll_cursor = Point2D(cursor.x as f32, cursor.y as f32);
                 }
                 
                 ZoomWindowEvent(magnification) => {
+                    for popup in select_popup.take().iter() {
+                        popup.close(&compositor_layer, None);
+                    }
+
                     zoom_action = true;
                     zoom_time = precise_time_s();
                     let old_world_zoom = world_zoom;
 
-                    // Determine zoom amount
-                    world_zoom = (world_zoom * magnification).max(&1.0);            
-                    root_layer.common.set_transform(identity().scale(world_zoom, world_zoom, 1f32));
+                    // Determine zoom amount. Pinching can't zoom out past the page's laid out size.
+                    pinch_zoom = (pinch_zoom * magnification).max(&1.0);
+                    world_zoom = ScaleFactor(device_pixels_per_px * page_zoom * pinch_zoom);
+                    root_layer.common.set_transform(identity().scale(world_zoom.get(),
+                                                                     world_zoom.get(),
+                                                                     1f32));
                     
-                    // Scroll as needed
-                    let page_delta = Point2D(window_size.width as f32 * (1.0 / world_zoom - 1.0 / old_world_zoom) * 0.5,
-                                             window_size.height as f32 * (1.0 / world_zoom - 1.0 / old_world_zoom) * 0.5);
+                    // Scroll as needed, keeping the middle of the window in place.
+                    let old_page_window = old_world_zoom.inv().transform_size(&window_size.to_f32()).size;
+                    let page_window = world_zoom.inv().transform_size(&window_size.to_f32());
+                    let page_delta = Point2D((page_window.size.width - old_page_window.width) * 0.5,
+                                             (page_window.size.height - old_page_window.height) * 0.5);
                     // TODO: modify delta to snap scroll to pixels.
                     let page_cursor = Point2D(-1f32, -1f32); // Make sure this hits the base layer
-                    let page_window = Size2D(window_size.width as f32 / world_zoom,
-                                             window_size.height as f32 / world_zoom);
                     for layer in compositor_layer.mut_iter() {
-                        layer.scroll(page_delta, page_cursor, page_window);
+                        layer.scroll(page_delta, page_cursor, page_window.size);
                     }
 
                     recomposite = true;
                 }
 
+                PageZoomWindowEvent(magnification) => {
+                    for popup in select_popup.take().iter() {
+                        popup.close(&compositor_layer, None);
+                    }
+
+                    page_zoom = match magnification {
+                        Some(magnification) => {
+                            (page_zoom * magnification).max(&MIN_PAGE_ZOOM).min(&MAX_PAGE_ZOOM)
+                        }
+                        None => 1.0,
+                    };
+                    world_zoom = ScaleFactor(device_pixels_per_px * page_zoom * pinch_zoom);
+                    root_layer.common.set_transform(identity().scale(world_zoom.get(),
+                                                                     world_zoom.get(),
+                                                                     1f32));
+
+                    // The page has to be laid out again to fit the window at the new size, as
+                    // though the window had been resized.
+                    match constellation_chan {
+                        Some(ref chan) => chan.send(ResizedWindowMsg(window_size.size)),
+                        None => {}
+                    }
+                    ask_for_tiles();
+                    recomposite = true;
+                }
+
                 NavigationWindowEvent(direction) => {
                     let direction = match direction {
                         windowing::Forward => constellation_msg::Forward,
@@ -454,6 +985,50 @@ impl CompositorTask {
                     }
                 }
                 
+                AddNativeOverlayWindowEvent(overlay) => {
+                    native_overlays.retain(|old_overlay| old_overlay.id != overlay.id);
+                    native_overlays.push(overlay);
+                    for layer in compositor_layer.iter() {
+                        layer.send_native_overlays(native_overlays);
+                    }
+                }
+
+                RemoveNativeOverlayWindowEvent(id) => {
+                    native_overlays.retain(|overlay| overlay.id != id);
+                    native_overlay_rects.retain(|&(overlay_id, _)| overlay_id != id);
+                    for layer in compositor_layer.iter() {
+                        layer.send_native_overlays(native_overlays);
+                    }
+                }
+
+                PrintMemoryReportsWindowEvent => {
+                    self.memory_profiler_chan.send(memory::PrintMsg);
+                }
+
+                NewTabWindowEvent(url_string) => {
+                    debug!("osmain: opening a tab for URL `%s`", url_string);
+                    match constellation_chan {
+                        Some(ref chan) => {
+                            chan.send(NewTabMsg(url::make_url(url_string, None), None))
+                        }
+                        None => error!("Compositor: Received new tab event without initialized layout chan"),
+                    }
+                }
+
+                CloseTabWindowEvent(tab) => {
+                    match constellation_chan {
+                        Some(ref chan) => chan.send(CloseTabMsg(tab)),
+                        None => error!("Compositor: Received close tab event without initialized layout chan"),
+                    }
+                }
+
+                SwitchTabWindowEvent(tab) => {
+                    match constellation_chan {
+                        Some(ref chan) => chan.send(SwitchTabMsg(tab)),
+                        None => error!("Compositor: Received switch tab event without initialized layout chan"),
+                    }
+                }
+
                 FinishedWindowEvent => {
                     if self.opts.exit_after_load {
                         done = true;
@@ -483,7 +1058,7 @@ impl CompositorTask {
             // Render to PNG. We must read from the back buffer (ie, before
             // window.present()) as OpenGL ES 2 does not have glReadBuffer().
             if write_png {
-                let (width, height) = (window_size.width as uint, window_size.height as uint);
+                let (width, height) = (window_size.size.width, window_size.size.height);
                 let path = Path(*self.opts.output_file.get_ref());
                 let mut pixels = gl2::read_pixels(0, 0,
                                                   width as gl2::GLsizei,
@@ -523,13 +1098,103 @@ impl CompositorTask {
             check_for_messages(&self.port);
 
             // Check for messages coming from the windowing system.
-            check_for_window_messages(window.recv());
+            let event = window.recv();
+            let event = match touch_emulator {
+                Some(ref mut emulator) => emulator.filter(event),
+                None => event,
+            };
+            check_for_window_messages(event);
+
+            // Move the page along with any scroll animation. Tiles are asked for ahead of where
+            // it's going, and those past the window are let go once it stops.
+            if scroll_animation.is_running() {
+                // TODO: modify delta to snap scroll to pixels.
+                let delta = scroll_animation.tick(precise_time_s());
+                let device_to_page = world_zoom.inv();
+                let page_delta = device_to_page.transform_point(&TypedPoint2D(delta.x, delta.y));
+                let page_cursor = device_to_page.transform_point(&TypedPoint2D(scroll_cursor.x,
+                                                                               scroll_cursor.y));
+                let page_window = device_to_page.transform_size(&window_size.to_f32());
+                let mut scrolled = false;
+                for layer in compositor_layer.mut_iter() {
+                    scrolled = layer.scroll(page_delta.point,
+                                            page_cursor.point,
+                                            page_window.size) || scrolled;
+                }
+                // The page can't scroll past its edges.
+                if !scrolled && delta != Point2D(0f32, 0f32) {
+                    scroll_animation.stop();
+                }
+                scroll_delta = if scroll_animation.is_running() {
+                    page_delta.point
+                } else {
+                    Point2D(0f32, 0f32)
+                };
+                recomposite = scrolled || recomposite;
+                ask_for_tiles();
+            }
+
+            // Keep the cursor in sync with whatever is under the mouse, which can change because
+            // the mouse moved, the page scrolled, or layout sent new hit testing information.
+            for point in mouse_position.iter() {
+                let page_point = world_zoom.inv().transform_point(&TypedPoint2D(point.x, point.y)).point;
+                let new_cursor = match compositor_layer {
+                    Some(ref layer) => layer.cursor_at(page_point),
+                    None => DefaultCursor,
+                };
+                if new_cursor != cursor {
+                    cursor = new_cursor;
+                    window.set_cursor(cursor);
+                }
+            }
+
+            // Keep the window's scrollbars in step with the page.
+            let page_window = world_zoom.inv().transform_size(&window_size.to_f32());
+            recomposite = viewport_scrollbars.update(&compositor_layer,
+                                                     page_window.size,
+                                                     world_zoom,
+                                                     device_pixels_per_px) || recomposite;
 
             if recomposite {
                 recomposite = false;
                 composite();
             }
 
+            // Keep the embedder's native overlays over their elements, which move when layout
+            // changes, the page scrolls or the window zooms.
+            for overlay in native_overlays.iter() {
+                let page_rect = match compositor_layer {
+                    Some(ref layer) => layer.native_overlay_bounds(overlay.id),
+                    None => None,
+                };
+                let rect = do page_rect.map_move |page_rect| {
+                    let page_rect: TypedRect<PagePx, f32> = TypedRect { rect: page_rect };
+                    world_zoom.transform_rect(&page_rect).rect
+                };
+                let last_rect = native_overlay_rects.iter().find(|&&(id, _)| id == overlay.id)
+                                                           .map(|&&(_, rect)| rect);
+                if last_rect != Some(rect) {
+                    native_overlay_rects.retain(|&(id, _)| id != overlay.id);
+                    native_overlay_rects.push((overlay.id, rect));
+                    window.set_native_overlay_rect(overlay.id, rect);
+                }
+            }
+
+            // Tick any pipelines that asked for an animation frame. We don't have real vsync
+            // notifications yet, so ticks are paced to the display refresh rate.
+            if !animation_frame_requests.is_empty() &&
+                    precise_time_s() - last_animation_frame_time >= ANIMATION_FRAME_INTERVAL {
+                last_animation_frame_time = precise_time_s();
+                let requests = replace(&mut animation_frame_requests, ~[]);
+                for layer in compositor_layer.iter() {
+                    for &id in requests.iter() {
+                        if !layer.tick_animation_frame(id) {
+                            debug!("Compositor: animation frame for unknown pipeline %?", id);
+                        }
+                    }
+                }
+            }
+
             tm.sleep(10);
 
             // If a pinch-zoom happened recently, ask for tiles at the new resolution
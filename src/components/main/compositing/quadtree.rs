@@ -13,6 +13,7 @@ use std::vec::build_sized;
 use std::util::replace;
 use gfx::render_task::BufferRequest;
 use servo_msg::compositor_msg::Tile;
+use servo_util::geometry::{TypedPoint2D, TypedRect, TypedSize2D};
 
 static HEADER: &'static str = "<!DOCTYPE html><html>";
 
@@ -440,8 +441,10 @@ impl<T: Tile> QuadtreeNode<T> {
             let pix_width = (page_width * scale).ceil() as uint;
             let pix_height = (page_height * scale).ceil() as uint;
             self.status = Rendering;
-            return BufferRequest(Rect(Point2D(pix_x, pix_y), Size2D(pix_width, pix_height)),
-                                 Rect(Point2D(self.origin.x, self.origin.y), Size2D(page_width, page_height)));
+            return BufferRequest(TypedRect(TypedPoint2D(pix_x, pix_y),
+                                           TypedSize2D(pix_width, pix_height)),
+                                 TypedRect(TypedPoint2D(self.origin.x, self.origin.y),
+                                           TypedSize2D(page_width, page_height)));
         }
         
         let quad = self.get_quadrant(x,y);
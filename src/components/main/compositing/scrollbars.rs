@@ -0,0 +1,345 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The window's scrollbars. The compositor draws them over the page, into layers of their own
+//! above the page's, and drives them itself: dragging a thumb or clicking a track scrolls the
+//! page as the mouse wheel does, without involving script. Positions are in the root layer's
+//! page coordinates, relative to the window.
+
+use compositing::compositor_layer::CompositorLayer;
+
+use azure::AzFloat;
+use azure::azure_hl::{B8G8R8A8, Color, DrawTarget, current_gl_context};
+use geom::matrix::identity;
+use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::font_context::FontContext;
+use gfx::geometry::Au;
+use gfx::opts::Opts;
+use gfx::render_context::RenderContext;
+use layers::layers::{ContainerLayer, TextureLayer, TextureLayerKind, TextureManager};
+use servo_msg::compositor_msg::LayerBuffer;
+use servo_util::geometry::{DevicePx, PagePx, ScaleFactor, TypedPoint2D, TypedRect, TypedSize2D};
+use servo_util::scrollbar::{SCROLLBAR_WIDTH, Scrollbar, Thumb};
+use std::util::replace;
+
+/// What the scrollbars were drawn for: the size of the window and of the page, how far the page
+/// is scrolled, and how thick the scrollbars are.
+#[deriving(Eq)]
+struct ScrollbarState {
+    window_size: Size2D<f32>,
+    page_size: Size2D<f32>,
+    offset: Point2D<f32>,
+    thickness: f32,
+}
+
+impl ScrollbarState {
+    /// The vertical scrollbar, if the page is taller than the window. Its track runs down the
+    /// right edge of the window, stopping short of the horizontal scrollbar.
+    fn vertical(&self) -> Option<Scrollbar> {
+        let (vertical, horizontal) = self.needed();
+        if !vertical {
+            return None
+        }
+        let track = self.window_size.height - if horizontal { self.thickness } else { 0.0 };
+        Some(Scrollbar::new(track, self.window_size.height, self.page_size.height))
+    }
+
+    /// The horizontal scrollbar, if the page is wider than the window.
+    fn horizontal(&self) -> Option<Scrollbar> {
+        let (vertical, horizontal) = self.needed();
+        if !horizontal {
+            return None
+        }
+        let track = self.window_size.width - if vertical { self.thickness } else { 0.0 };
+        Some(Scrollbar::new(track, self.window_size.width, self.page_size.width))
+    }
+
+    /// Whether there's a vertical and a horizontal scrollbar.
+    fn needed(&self) -> (bool, bool) {
+        (self.page_size.height > self.window_size.height,
+         self.page_size.width > self.window_size.width)
+    }
+
+    /// The scrollbar at `point`, if there's one there: whether it's the vertical one, and how far
+    /// along its track the point is.
+    fn scrollbar_at(&self, point: Point2D<f32>) -> Option<(bool, f32)> {
+        let (vertical, horizontal) = self.needed();
+        let right = self.window_size.width - if vertical { self.thickness } else { 0.0 };
+        let bottom = self.window_size.height - if horizontal { self.thickness } else { 0.0 };
+        if vertical && point.x >= right && point.x < self.window_size.width && point.y < bottom {
+            Some((true, point.y))
+        } else if horizontal && point.y >= bottom && point.y < self.window_size.height &&
+                point.x < right {
+            Some((false, point.x))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct ViewportScrollbars {
+    /// What the scrollbars were last drawn for, if they've been drawn.
+    state: Option<ScrollbarState>,
+    /// Whether the mouse button was pressed on a scrollbar and hasn't been released yet.
+    pressed: bool,
+    /// The thumb being dragged, if any: whether it's the vertical one, and how far along it it
+    /// was grabbed.
+    drag: Option<(bool, f32)>,
+    /// The layers the scrollbars are drawn into, and the layer above the page's that holds them.
+    texture_layers: ~[@mut TextureLayer],
+    parent: @mut ContainerLayer,
+    font_ctx: @mut FontContext,
+    opts: Opts,
+}
+
+impl ViewportScrollbars {
+    pub fn new(parent: @mut ContainerLayer, font_ctx: @mut FontContext, opts: &Opts)
+               -> ViewportScrollbars {
+        ViewportScrollbars {
+            state: None,
+            pressed: false,
+            drag: None,
+            texture_layers: ~[],
+            parent: parent,
+            font_ctx: font_ctx,
+            opts: opts.clone(),
+        }
+    }
+
+    /// Draws the scrollbars again if the page has scrolled or changed size, or the window has,
+    /// since they were last drawn. `window_size` is in page px. Returns true if the window needs
+    /// compositing again.
+    pub fn update(&mut self,
+                  layer: &Option<CompositorLayer>,
+                  window_size: Size2D<f32>,
+                  scale: ScaleFactor<PagePx, DevicePx>,
+                  device_pixels_per_px: f32)
+                  -> bool {
+        let state = match *layer {
+            Some(ref layer) if !layer.hidden && layer.page_size.is_some() => {
+                Some(ScrollbarState {
+                    window_size: window_size,
+                    page_size: layer.page_size.unwrap(),
+                    offset: Point2D(-layer.scroll_offset.x, -layer.scroll_offset.y),
+                    // Scrollbars stay the same size on the screen whatever the zoom.
+                    thickness: SCROLLBAR_WIDTH * device_pixels_per_px / scale.get(),
+                })
+            }
+            _ => None,
+        };
+        if state == self.state {
+            return false
+        }
+        self.state = state;
+        self.render(scale);
+        true
+    }
+
+    /// Presses the mouse button at `point`, if it's on a scrollbar: pressing a thumb starts
+    /// dragging it, and pressing a track scrolls the page a window's length towards the point.
+    /// Returns whether the point was on a scrollbar, in which case the page doesn't hear about
+    /// the press.
+    pub fn mouse_down(&mut self,
+                      layer: &mut Option<CompositorLayer>,
+                      point: Point2D<f32>)
+                      -> bool {
+        let state = match self.state {
+            Some(state) => state,
+            None => return false,
+        };
+        let (vertical, position) = match state.scrollbar_at(point) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        self.pressed = true;
+        let scrollbar = self.scrollbar(vertical).unwrap();
+        let offset = if vertical { state.offset.y } else { state.offset.x };
+        match scrollbar.part_at(position, offset) {
+            Thumb => {
+                let (start, _) = scrollbar.thumb(offset);
+                self.drag = Some((vertical, position - start));
+            }
+            part => {
+                let new_offset = scrollbar.offset_for_track_click(part, offset);
+                self.scroll_page(layer, vertical, new_offset);
+            }
+        }
+        true
+    }
+
+    /// Moves the thumb being dragged, if any, to follow the mouse to `point`. Returns whether a
+    /// thumb is being dragged, in which case the page doesn't hear about the move.
+    pub fn mouse_move(&mut self,
+                      layer: &mut Option<CompositorLayer>,
+                      point: Point2D<f32>)
+                      -> bool {
+        let (vertical, grab) = match self.drag {
+            Some(drag) => drag,
+            None => return false,
+        };
+        let position = if vertical { point.y } else { point.x };
+        for scrollbar in self.scrollbar(vertical).iter() {
+            let new_offset = scrollbar.offset_for_thumb_at(position - grab);
+            self.scroll_page(layer, vertical, new_offset);
+        }
+        true
+    }
+
+    /// Releases the mouse button, letting go of the thumb being dragged, if any. Returns whether
+    /// the button was pressed on a scrollbar, in which case the page doesn't hear about the
+    /// release.
+    pub fn mouse_up(&mut self) -> bool {
+        self.drag = None;
+        replace(&mut self.pressed, false)
+    }
+
+    /// Whether a click, which comes before the release that makes it, was on a scrollbar, in
+    /// which case the page doesn't hear about it.
+    pub fn click(&self) -> bool {
+        self.pressed
+    }
+
+    fn scrollbar(&self, vertical: bool) -> Option<Scrollbar> {
+        do self.state.chain |state| {
+            if vertical { state.vertical() } else { state.horizontal() }
+        }
+    }
+
+    /// Scrolls the page along one axis to `offset`, leaving the other where it is.
+    fn scroll_page(&self, layer: &mut Option<CompositorLayer>, vertical: bool, offset: f32) {
+        let state = self.state.unwrap();
+        let point = if vertical {
+            Point2D(state.offset.x, offset)
+        } else {
+            Point2D(offset, state.offset.y)
+        };
+        for layer in layer.mut_iter() {
+            let id = layer.pipeline.id.clone();
+            layer.scroll_to(id, point, state.window_size);
+        }
+    }
+
+    /// Draws the scrollbars into new layers at the given scale, in place of the old ones.
+    fn render(&mut self, scale: ScaleFactor<PagePx, DevicePx>) {
+        for &texture_layer in self.texture_layers.iter() {
+            self.parent.remove_child(TextureLayerKind(texture_layer));
+        }
+        self.texture_layers = ~[];
+
+        let state = match self.state {
+            Some(state) => state,
+            None => return,
+        };
+        let thickness = state.thickness;
+        let window = state.window_size;
+        for scrollbar in state.vertical().iter() {
+            let (start, length) = scrollbar.thumb(state.offset.y);
+            let bounds = Rect(Point2D(window.width - thickness, 0f32),
+                              Size2D(thickness, scrollbar.track_length));
+            let thumb = Rect(Point2D(0f32, start), Size2D(thickness, length));
+            self.render_scrollbar(bounds, thumb, scale);
+        }
+        for scrollbar in state.horizontal().iter() {
+            let (start, length) = scrollbar.thumb(state.offset.x);
+            let bounds = Rect(Point2D(0f32, window.height - thickness),
+                              Size2D(scrollbar.track_length, thickness));
+            let thumb = Rect(Point2D(start, 0f32), Size2D(length, thickness));
+            self.render_scrollbar(bounds, thumb, scale);
+        }
+    }
+
+    /// Draws one scrollbar, whose track fills `bounds`, with its thumb at `thumb` within it.
+    fn render_scrollbar(&mut self,
+                        bounds: Rect<f32>,
+                        thumb: Rect<f32>,
+                        scale: ScaleFactor<PagePx, DevicePx>) {
+        let page_size = bounds.size;
+        let width = (page_size.width * scale.get()).ceil() as uint;
+        let height = (page_size.height * scale.get()).ceil() as uint;
+        if width == 0 || height == 0 {
+            return
+        }
+        let buffer = ~LayerBuffer {
+            draw_target: DrawTarget::new_with_fbo(self.opts.render_backend,
+                                                  current_gl_context(),
+                                                  Size2D(width as i32, height as i32),
+                                                  B8G8R8A8),
+            rect: TypedRect(TypedPoint2D(0f32, 0f32),
+                            TypedSize2D(page_size.width, page_size.height)),
+            screen_pos: TypedRect(TypedPoint2D(0u, 0u), TypedSize2D(width, height)),
+            resolution: scale,
+            stride: width * 4,
+        };
+
+        {
+            let ctx = RenderContext {
+                canvas: &buffer,
+                font_ctx: self.font_ctx,
+                opts: &self.opts,
+            };
+            let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+            let matrix = matrix.scale(scale.get() as AzFloat, scale.get() as AzFloat);
+            ctx.canvas.draw_target.set_transform(&matrix);
+
+            let track = Rect(Au::zero_point(),
+                             Size2D(Au::from_frac_px(page_size.width as float),
+                                    Au::from_frac_px(page_size.height as float)));
+            let thumb = Rect(Point2D(Au::from_frac_px(thumb.origin.x as float),
+                                     Au::from_frac_px(thumb.origin.y as float)),
+                             Size2D(Au::from_frac_px(thumb.size.width as float),
+                                    Au::from_frac_px(thumb.size.height as float)));
+            ctx.draw_solid_color(&track, Color(0.945, 0.945, 0.945, 1.0));
+            ctx.draw_solid_color(&thumb, Color(0.757, 0.757, 0.757, 1.0));
+            ctx.canvas.draw_target.flush();
+        }
+
+        let texture_layer = @mut TextureLayer::new(@buffer.draw_target.clone() as @TextureManager,
+                                                   Size2D(width, height));
+        let transform = identity().translate(bounds.origin.x, bounds.origin.y, 0.0);
+        let transform = transform.scale(page_size.width, page_size.height, 1.0);
+        texture_layer.common.set_transform(transform);
+        self.parent.add_child_end(TextureLayerKind(texture_layer));
+        self.texture_layers.push(texture_layer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScrollbarState;
+    use geom::point::Point2D;
+    use geom::size::Size2D;
+
+    fn state(page_size: Size2D<f32>) -> ScrollbarState {
+        ScrollbarState {
+            window_size: Size2D(800f32, 600f32),
+            page_size: page_size,
+            offset: Point2D(0f32, 0f32),
+            thickness: 12.0,
+        }
+    }
+
+    #[test]
+    fn scrollbars_only_when_the_page_overflows() {
+        assert!(state(Size2D(800f32, 600f32)).needed() == (false, false));
+        assert!(state(Size2D(800f32, 2000f32)).needed() == (true, false));
+        assert!(state(Size2D(1000f32, 2000f32)).needed() == (true, true));
+    }
+
+    #[test]
+    fn hit_testing() {
+        let tall = state(Size2D(800f32, 2000f32));
+        assert!(tall.scrollbar_at(Point2D(795f32, 100f32)) == Some((true, 100f32)));
+        assert!(tall.scrollbar_at(Point2D(700f32, 100f32)).is_none());
+
+        // The vertical track stops short of the horizontal scrollbar, and the corner between them
+        // is neither's.
+        let both = state(Size2D(1000f32, 2000f32));
+        assert!(both.vertical().unwrap().track_length == 588.0);
+        assert!(both.scrollbar_at(Point2D(795f32, 595f32)).is_none());
+        assert!(both.scrollbar_at(Point2D(100f32, 595f32)) == Some((false, 100f32)));
+    }
+}
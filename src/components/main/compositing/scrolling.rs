@@ -0,0 +1,192 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Animated scrolling. A wheel scroll eases the page towards where it takes it instead of jumping
+//! there, and a fling keeps the page moving, slowed by friction, until it runs out of speed.
+//! Distances are in device pixels, as the user sees them.
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+
+/// How quickly easing settles, in seconds: each tick covers `1 - e^(-dt / EASING_TIME)` of the
+/// distance left.
+static EASING_TIME: float = 0.06;
+/// How far left to go, in device pixels, below which easing finishes in a single step.
+static MIN_EASING_DISTANCE: float = 0.5;
+/// How quickly flings slow down: their speed falls by a factor of e every `1 / FLING_FRICTION`
+/// seconds.
+static FLING_FRICTION: float = 3.0;
+/// The speed, in device pixels per second, below which a fling stops.
+static MIN_FLING_SPEED: float = 20.0;
+/// How much of a window beyond the edge the page is scrolling towards to have tiles for.
+static TILES_AHEAD: f32 = 0.5;
+
+pub struct ScrollAnimation {
+    /// The distance easing has still to scroll the page.
+    remaining: Point2D<f32>,
+    /// The speed of a fling, in device pixels per second.
+    velocity: Point2D<f32>,
+    /// When the animation last moved the page, in seconds.
+    last_tick: float,
+}
+
+impl ScrollAnimation {
+    pub fn new() -> ScrollAnimation {
+        ScrollAnimation {
+            remaining: Point2D(0f32, 0f32),
+            velocity: Point2D(0f32, 0f32),
+            last_tick: 0.0,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.remaining != Point2D(0f32, 0f32) || self.velocity != Point2D(0f32, 0f32)
+    }
+
+    /// Eases the page along by `delta` more, as for a turn of the mouse wheel. Stops any fling.
+    pub fn scroll_by(&mut self, delta: Point2D<f32>, now: float) {
+        if !self.is_running() {
+            self.last_tick = now;
+        }
+        self.velocity = Point2D(0f32, 0f32);
+        self.remaining = self.remaining + delta;
+    }
+
+    /// Sets the page moving at `velocity`, as when the user lifts a finger mid-swipe.
+    pub fn fling(&mut self, velocity: Point2D<f32>, now: float) {
+        if !self.is_running() {
+            self.last_tick = now;
+        }
+        self.velocity = velocity;
+    }
+
+    pub fn stop(&mut self) {
+        self.remaining = Point2D(0f32, 0f32);
+        self.velocity = Point2D(0f32, 0f32);
+    }
+
+    /// Advances the animation to `now`, and returns how far to scroll the page since the last
+    /// tick.
+    pub fn tick(&mut self, now: float) -> Point2D<f32> {
+        let dt = (now - self.last_tick).max(&0.0);
+        self.last_tick = now;
+
+        let fraction = (1.0 - (-dt / EASING_TIME).exp()) as f32;
+        let mut step = Point2D(self.remaining.x * fraction, self.remaining.y * fraction);
+        if distance(self.remaining - step) < MIN_EASING_DISTANCE {
+            step = self.remaining;
+        }
+        self.remaining = self.remaining - step;
+
+        step = step + Point2D(self.velocity.x * dt as f32, self.velocity.y * dt as f32);
+        let slowdown = (-dt * FLING_FRICTION).exp() as f32;
+        self.velocity = Point2D(self.velocity.x * slowdown, self.velocity.y * slowdown);
+        if distance(self.velocity) < MIN_FLING_SPEED {
+            self.velocity = Point2D(0f32, 0f32);
+        }
+        step
+    }
+}
+
+fn distance(vector: Point2D<f32>) -> float {
+    ((vector.x * vector.x + vector.y * vector.y) as float).sqrt()
+}
+
+/// The part of the page, relative to the window, to have tiles for while it scrolls by `delta`:
+/// the window, and some more beyond the edges the page is scrolling towards, so that they're
+/// ready by the time they come into view. A negative delta scrolls towards the bottom or right.
+pub fn tile_rect_ahead(window_size: Size2D<f32>, delta: Point2D<f32>) -> Rect<f32> {
+    let ahead = |delta: f32, length: f32| -> (f32, f32) {
+        if delta < 0.0 {
+            (0.0, length * TILES_AHEAD)
+        } else if delta > 0.0 {
+            (-length * TILES_AHEAD, length * TILES_AHEAD)
+        } else {
+            (0.0, 0.0)
+        }
+    };
+    let (x, extra_width) = ahead(delta.x, window_size.width);
+    let (y, extra_height) = ahead(delta.y, window_size.height);
+    Rect(Point2D(x, y),
+         Size2D(window_size.width + extra_width, window_size.height + extra_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geom::point::Point2D;
+    use geom::rect::Rect;
+    use geom::size::Size2D;
+
+    #[test]
+    fn easing() {
+        let mut animation = ScrollAnimation::new();
+        assert!(!animation.is_running());
+        animation.scroll_by(Point2D(0f32, -100f32), 1.0);
+        assert!(animation.is_running());
+
+        let mut scrolled = 0f32;
+        let mut now = 1.0;
+        let first = animation.tick(now + 1.0 / 60.0);
+        // The page moves most of the way quickly.
+        assert!(first.y < -20.0 && first.y > -100.0);
+        scrolled += first.y;
+        now += 1.0 / 60.0;
+        while animation.is_running() {
+            now += 1.0 / 60.0;
+            let step = animation.tick(now);
+            assert!(step.x == 0.0 && step.y <= 0.0);
+            scrolled += step.y;
+            assert!(now < 3.0);
+        }
+        assert!((scrolled + 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn wheel_scrolls_add_up() {
+        let mut animation = ScrollAnimation::new();
+        animation.scroll_by(Point2D(0f32, -50f32), 0.0);
+        let first = animation.tick(0.01);
+        animation.scroll_by(Point2D(0f32, -50f32), 0.01);
+        let mut scrolled = first.y;
+        let mut now = 0.01;
+        while animation.is_running() {
+            now += 0.01;
+            scrolled += animation.tick(now).y;
+        }
+        assert!((scrolled + 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn flings_slow_down_and_stop() {
+        let mut animation = ScrollAnimation::new();
+        animation.fling(Point2D(1000f32, 0f32), 0.0);
+        let first = animation.tick(1.0 / 60.0);
+        let second = animation.tick(2.0 / 60.0);
+        assert!(first.x > second.x && second.x > 0.0);
+        let mut now = 2.0 / 60.0;
+        while animation.is_running() {
+            now += 1.0 / 60.0;
+            animation.tick(now);
+        }
+        assert!(now < 2.0);
+
+        // A wheel scroll stops a fling.
+        animation.fling(Point2D(1000f32, 0f32), 0.0);
+        animation.scroll_by(Point2D(0f32, 10f32), 0.0);
+        assert!(animation.velocity == Point2D(0f32, 0f32));
+    }
+
+    #[test]
+    fn tiles_ahead() {
+        let window = Size2D(800f32, 600f32);
+        assert!(tile_rect_ahead(window, Point2D(0f32, 0f32)) ==
+                Rect(Point2D(0f32, 0f32), window));
+        assert!(tile_rect_ahead(window, Point2D(0f32, -10f32)) ==
+                Rect(Point2D(0f32, 0f32), Size2D(800f32, 900f32)));
+        assert!(tile_rect_ahead(window, Point2D(10f32, 0f32)) ==
+                Rect(Point2D(-400f32, 0f32), Size2D(1200f32, 600f32)));
+    }
+}
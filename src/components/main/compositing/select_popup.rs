@@ -0,0 +1,225 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The list of options the compositor shows over the page while a `<select>` is open. Script
+//! tells the compositor what the options are; the compositor draws them into a layer of its own,
+//! above the page's, and handles the mouse and keyboard until the user picks one or clicks
+//! somewhere else.
+
+use compositing::compositor_layer::CompositorLayer;
+
+use azure::AzFloat;
+use azure::azure_hl::{B8G8R8A8, Color, DrawTarget, current_gl_context};
+use geom::matrix::identity;
+use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::side_offsets::SideOffsets2D;
+use geom::size::Size2D;
+use gfx::font::{FontStyle, FontWeight400};
+use gfx::font_context::FontContext;
+use gfx::geometry::{Au, to_frac_px};
+use gfx::opts::Opts;
+use gfx::render_context::RenderContext;
+use gfx::text::text_run::TextRun;
+use layers::layers::{ContainerLayer, TextureLayer, TextureLayerKind, TextureManager};
+use servo_msg::compositor_msg::{LayerBuffer, SelectPopup};
+use servo_msg::constellation_msg::PipelineId;
+use servo_util::geometry::{DevicePx, PagePx, ScaleFactor, TypedPoint2D, TypedRect, TypedSize2D};
+use servo_util::range::Range;
+
+/// The space around each option's label, in page pixels.
+static OPTION_PADDING: int = 3;
+
+pub struct SelectPopupLayer {
+    /// The pipeline whose select the popup belongs to.
+    pipeline_id: PipelineId,
+    /// The labels of the options, shaped.
+    runs: ~[@TextRun],
+    /// The height of each option's row.
+    row_height: Au,
+    /// The option under the mouse or moved to with the arrow keys, which is drawn highlighted.
+    highlighted: Option<uint>,
+    /// Where the popup is, in the root layer's page coordinates.
+    bounds: Rect<f32>,
+    /// The layer the popup is drawn into, once it's drawn, and the layer above the page's that
+    /// holds it.
+    texture_layer: Option<@mut TextureLayer>,
+    parent: @mut ContainerLayer,
+    font_ctx: @mut FontContext,
+    opts: Opts,
+}
+
+impl SelectPopupLayer {
+    /// Shows the options of a select whose border box is `select_bounds`, in the root layer's page
+    /// coordinates, in a list just below it.
+    pub fn new(pipeline_id: PipelineId,
+               popup: SelectPopup,
+               select_bounds: Rect<f32>,
+               parent: @mut ContainerLayer,
+               font_ctx: @mut FontContext,
+               opts: &Opts,
+               scale: ScaleFactor<PagePx, DevicePx>)
+               -> SelectPopupLayer {
+        // FIXME: The popup should use the select's font.
+        let style = FontStyle {
+            pt_size: 16f,
+            weight: FontWeight400,
+            italic: false,
+            oblique: false,
+            families: ~"sans-serif",
+        };
+        let font = font_ctx.get_resolved_font_for_style(&style).fonts[0];
+        let runs: ~[@TextRun] = do popup.options.iter().map |label| {
+            @TextRun::new(font, label.clone(), false)
+        }.collect();
+
+        let padding = Au::from_px(OPTION_PADDING);
+        let widest = runs.iter().fold(Au(0), |widest, run| {
+            let width = run.metrics_for_range(&Range::new(0, run.char_len())).advance_width;
+            if width > widest { width } else { widest }
+        });
+        let row_height = font.metrics.ascent + font.metrics.descent + padding + padding;
+        let width = (to_frac_px(widest + padding + padding) as f32).max(&select_bounds.size.width);
+        let height = to_frac_px(row_height) as f32 * (runs.len() as f32);
+        let bounds = Rect(Point2D(select_bounds.origin.x,
+                                  select_bounds.origin.y + select_bounds.size.height),
+                          Size2D(width, height));
+
+        let mut popup_layer = SelectPopupLayer {
+            pipeline_id: pipeline_id,
+            runs: runs,
+            row_height: row_height,
+            highlighted: popup.selected,
+            bounds: bounds,
+            texture_layer: None,
+            parent: parent,
+            font_ctx: font_ctx,
+            opts: opts.clone(),
+        };
+        popup_layer.render(scale);
+        popup_layer
+    }
+
+    /// The option at `point`, in the root layer's page coordinates, if there's one there.
+    pub fn option_at(&self, point: Point2D<f32>) -> Option<uint> {
+        let bounds = &self.bounds;
+        if point.x < bounds.origin.x || point.x >= bounds.origin.x + bounds.size.width ||
+                point.y < bounds.origin.y || point.y >= bounds.origin.y + bounds.size.height {
+            return None
+        }
+        let row = ((point.y - bounds.origin.y) / (to_frac_px(self.row_height) as f32)) as uint;
+        Some(row.min(&(self.runs.len() - 1)))
+    }
+
+    /// Highlights the option at `index`. Returns true if the popup needs compositing again.
+    pub fn highlight(&mut self, index: Option<uint>, scale: ScaleFactor<PagePx, DevicePx>)
+                     -> bool {
+        if self.highlighted == index {
+            return false
+        }
+        self.highlighted = index;
+        self.render(scale);
+        true
+    }
+
+    /// Moves the highlight by `delta` options, as the arrow keys do, stopping at the first and
+    /// last options. Returns true if the popup needs compositing again.
+    pub fn step_highlight(&mut self, delta: int, scale: ScaleFactor<PagePx, DevicePx>) -> bool {
+        let last = self.runs.len() as int - 1;
+        let index = match self.highlighted {
+            Some(index) => (index as int + delta).max(&0).min(&last),
+            None if delta < 0 => last,
+            None => 0,
+        };
+        self.highlight(Some(index as uint), scale)
+    }
+
+    /// Takes the popup off the screen, and tells its pipeline which option the user picked, if
+    /// they picked one.
+    pub fn close(&self, compositor_layer: &Option<CompositorLayer>, index: Option<uint>) {
+        for &texture_layer in self.texture_layer.iter() {
+            self.parent.remove_child(TextureLayerKind(texture_layer));
+        }
+        match *compositor_layer {
+            Some(ref layer) => {
+                if !layer.send_select_option(self.pipeline_id, index) {
+                    debug!("Compositor: select popup for unknown pipeline %?", self.pipeline_id);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Draws the popup into a new buffer at the given scale, and puts it in the texture layer.
+    fn render(&mut self, scale: ScaleFactor<PagePx, DevicePx>) {
+        let page_size = self.bounds.size;
+        let width = (page_size.width * scale.get()).ceil() as uint;
+        let height = (page_size.height * scale.get()).ceil() as uint;
+        let buffer = ~LayerBuffer {
+            draw_target: DrawTarget::new_with_fbo(self.opts.render_backend,
+                                                  current_gl_context(),
+                                                  Size2D(width as i32, height as i32),
+                                                  B8G8R8A8),
+            rect: TypedRect(TypedPoint2D(0f32, 0f32),
+                            TypedSize2D(page_size.width, page_size.height)),
+            screen_pos: TypedRect(TypedPoint2D(0u, 0u), TypedSize2D(width, height)),
+            resolution: scale,
+            stride: width * 4,
+        };
+
+        {
+            let ctx = RenderContext {
+                canvas: &buffer,
+                font_ctx: self.font_ctx,
+                opts: &self.opts,
+            };
+            let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+            let matrix = matrix.scale(scale.get() as AzFloat, scale.get() as AzFloat);
+            ctx.canvas.draw_target.set_transform(&matrix);
+
+            let size = Size2D(Au::from_frac_px(page_size.width as float),
+                              Au::from_frac_px(page_size.height as float));
+            let white = Color(1.0, 1.0, 1.0, 1.0);
+            let black = Color(0.0, 0.0, 0.0, 1.0);
+            ctx.draw_solid_color(&Rect(Au::zero_point(), size), white);
+
+            let padding = Au::from_px(OPTION_PADDING);
+            for (i, &run) in self.runs.iter().enumerate() {
+                let row = Rect(Point2D(Au(0), self.row_height.scale_by(i as float)),
+                               Size2D(size.width, self.row_height));
+                let color = if self.highlighted == Some(i) {
+                    ctx.draw_solid_color(&row, Color(0.2, 0.4, 0.8, 1.0));
+                    white
+                } else {
+                    black
+                };
+                let baseline_origin = Point2D(row.origin.x + padding,
+                                              row.origin.y + padding + run.font.metrics.ascent);
+                run.font.draw_text_into_context(&ctx,
+                                                run,
+                                                &Range::new(0, run.char_len()),
+                                                baseline_origin,
+                                                color);
+            }
+
+            let gray = Color(0.5, 0.5, 0.5, 1.0);
+            ctx.draw_border(&Rect(Au::zero_point(), size),
+                            SideOffsets2D::new_all_same(Au::from_px(1)),
+                            SideOffsets2D::new_all_same(gray));
+            ctx.canvas.draw_target.flush();
+        }
+
+        let texture_layer = @mut TextureLayer::new(@buffer.draw_target.clone() as @TextureManager,
+                                                   Size2D(width, height));
+        let transform = identity().translate(self.bounds.origin.x, self.bounds.origin.y, 0.0);
+        let transform = transform.scale(page_size.width, page_size.height, 1.0);
+        texture_layer.common.set_transform(transform);
+        for &old_texture_layer in self.texture_layer.iter() {
+            self.parent.remove_child(TextureLayerKind(old_texture_layer));
+        }
+        self.parent.add_child_end(TextureLayerKind(texture_layer));
+        self.texture_layer = Some(texture_layer);
+    }
+}
@@ -0,0 +1,252 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Touch input. Every touch goes to the page, and a finger dragged across the screen also scrolls
+//! it, unless the page cancels the `touchstart` or a `touchmove`. Since script answers some time
+//! after the compositor sends it an event, scrolling waits until the page has answered for the
+//! touch. Distances are in device pixels, as the user sees them.
+
+use windowing::{MouseWindowEventClass, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
+use windowing::{MouseMoveWindowEvent, TouchWindowEventClass, TouchWindowStartEvent};
+use windowing::{TouchWindowMoveEvent, TouchWindowEndEvent, WindowEvent};
+
+use geom::point::Point2D;
+use servo_msg::compositor_msg::{TouchEventResult, TouchDefaultPrevented, TouchDefaultAllowed};
+
+/// How far a finger has to move before it starts scrolling the page, so that taps don't.
+static MIN_PAN_DISTANCE: f32 = 20.0;
+/// How much the latest movement counts for in the speed a finger is moving at, against the
+/// movements before it.
+static VELOCITY_WEIGHT: f32 = 0.6;
+
+#[deriving(Clone, Eq)]
+pub enum TouchState {
+    /// No finger is on the screen.
+    Nothing,
+    /// A finger is on the screen, and the page hasn't yet said whether it cancels the touch.
+    WaitingForScript,
+    /// A finger is on the screen, and the page let it be, but it hasn't moved far enough yet to
+    /// scroll.
+    Touching,
+    /// A finger is scrolling the page.
+    Panning,
+    /// The page canceled the touch, which is left to it until every finger is lifted.
+    DefaultPrevented,
+    /// More than one finger is on the screen. Pinching isn't supported, so they don't scroll.
+    MultiTouch,
+}
+
+/// What the compositor should do with the page in response to a touch.
+#[deriving(Eq)]
+pub enum TouchAction {
+    NoAction,
+    /// Scroll the page by this much.
+    Scroll(Point2D<f32>),
+    /// Set the page moving at this speed, in device pixels per second.
+    Fling(Point2D<f32>),
+}
+
+pub struct TouchHandler {
+    state: TouchState,
+    /// The fingers on the screen, with where they were last.
+    active_touch_points: ~[(uint, Point2D<f32>)],
+    /// Where the first finger touched the screen.
+    start_point: Point2D<f32>,
+    /// How fast the page is being scrolled, in device pixels per second.
+    velocity: Point2D<f32>,
+    /// When the finger last moved, in seconds.
+    last_move_time: float,
+}
+
+impl TouchHandler {
+    pub fn new() -> TouchHandler {
+        TouchHandler {
+            state: Nothing,
+            active_touch_points: ~[],
+            start_point: Point2D(0f32, 0f32),
+            velocity: Point2D(0f32, 0f32),
+            last_move_time: 0.0,
+        }
+    }
+
+    pub fn on_touch_down(&mut self, id: uint, point: Point2D<f32>, now: float) {
+        self.remove_touch_point(id);
+        self.active_touch_points.push((id, point));
+        self.state = match self.state {
+            Nothing => {
+                self.start_point = point;
+                self.velocity = Point2D(0f32, 0f32);
+                self.last_move_time = now;
+                WaitingForScript
+            }
+            DefaultPrevented => DefaultPrevented,
+            _ => MultiTouch,
+        };
+    }
+
+    pub fn on_touch_move(&mut self, id: uint, point: Point2D<f32>, now: float) -> TouchAction {
+        let last_point = match self.active_touch_points.iter().position(|&(i, _)| i == id) {
+            Some(index) => {
+                let (_, last_point) = self.active_touch_points[index];
+                self.active_touch_points[index] = (id, point);
+                last_point
+            }
+            None => return NoAction,
+        };
+        let delta = point - last_point;
+        let dt = (now - self.last_move_time) as f32;
+        self.last_move_time = now;
+
+        if self.state == Touching && length(point - self.start_point) >= MIN_PAN_DISTANCE {
+            self.state = Panning;
+        }
+        if self.state != Panning {
+            return NoAction
+        }
+
+        if dt > 0.0 {
+            let current = Point2D(delta.x / dt, delta.y / dt);
+            self.velocity = Point2D(current.x * VELOCITY_WEIGHT +
+                                        self.velocity.x * (1.0 - VELOCITY_WEIGHT),
+                                    current.y * VELOCITY_WEIGHT +
+                                        self.velocity.y * (1.0 - VELOCITY_WEIGHT));
+        }
+        Scroll(delta)
+    }
+
+    pub fn on_touch_up(&mut self, id: uint) -> TouchAction {
+        self.remove_touch_point(id);
+        let action = match self.state {
+            Panning => Fling(self.velocity),
+            _ => NoAction,
+        };
+        if self.active_touch_points.is_empty() {
+            self.state = Nothing;
+        }
+        action
+    }
+
+    pub fn on_touch_cancel(&mut self, id: uint) {
+        self.remove_touch_point(id);
+        if self.active_touch_points.is_empty() {
+            self.state = Nothing;
+        }
+    }
+
+    /// Takes in the page's answer to the last `touchstart` or `touchmove`. A page that lets the
+    /// touch be lets the finger scroll it once it has moved far enough, and a page that cancels
+    /// one stops it scrolling.
+    pub fn on_event_processed(&mut self, result: TouchEventResult) {
+        self.state = match (self.state, result) {
+            (Nothing, _) => Nothing,
+            (MultiTouch, _) => MultiTouch,
+            (_, TouchDefaultPrevented) => DefaultPrevented,
+            (WaitingForScript, TouchDefaultAllowed) => Touching,
+            (state, TouchDefaultAllowed) => state,
+        };
+    }
+
+    fn remove_touch_point(&mut self, id: uint) {
+        for index in self.active_touch_points.iter().position(|&(i, _)| i == id).iter() {
+            self.active_touch_points.remove(*index);
+        }
+    }
+}
+
+fn length(vector: Point2D<f32>) -> f32 {
+    (vector.x * vector.x + vector.y * vector.y).sqrt()
+}
+
+/// Turns presses and drags of the left mouse button into touches of a single finger, for trying
+/// out touch input without a touch screen.
+pub struct TouchEmulator {
+    pressed: bool,
+}
+
+impl TouchEmulator {
+    pub fn new() -> TouchEmulator {
+        TouchEmulator {
+            pressed: false,
+        }
+    }
+
+    /// Returns the touch the given event stands for, or the event itself if it doesn't stand for
+    /// one. Clicks still go through, as taps.
+    pub fn filter(&mut self, event: WindowEvent) -> WindowEvent {
+        match event {
+            MouseWindowEventClass(MouseWindowMouseDownEvent(0, point)) => {
+                self.pressed = true;
+                TouchWindowEventClass(TouchWindowStartEvent(0, point))
+            }
+            MouseWindowEventClass(MouseWindowMouseUpEvent(0, point)) if self.pressed => {
+                self.pressed = false;
+                TouchWindowEventClass(TouchWindowEndEvent(0, point))
+            }
+            MouseMoveWindowEvent(point) if self.pressed => {
+                TouchWindowEventClass(TouchWindowMoveEvent(0, point))
+            }
+            event => event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geom::point::Point2D;
+    use servo_msg::compositor_msg::{TouchDefaultPrevented, TouchDefaultAllowed};
+
+    #[test]
+    fn waits_for_script_before_scrolling() {
+        let mut handler = TouchHandler::new();
+        handler.on_touch_down(1, Point2D(100f32, 100f32), 0.0);
+        assert!(handler.on_touch_move(1, Point2D(100f32, 150f32), 0.01) == NoAction);
+        handler.on_event_processed(TouchDefaultAllowed);
+        // The finger has already moved far enough, so the next movement scrolls.
+        assert!(handler.on_touch_move(1, Point2D(100f32, 160f32), 0.02) ==
+                Scroll(Point2D(0f32, 10f32)));
+        match handler.on_touch_up(1) {
+            Fling(velocity) => assert!(velocity.x == 0.0 && velocity.y > 0.0),
+            _ => fail!(),
+        }
+        assert!(handler.state == Nothing);
+    }
+
+    #[test]
+    fn taps_dont_scroll() {
+        let mut handler = TouchHandler::new();
+        handler.on_touch_down(1, Point2D(100f32, 100f32), 0.0);
+        handler.on_event_processed(TouchDefaultAllowed);
+        assert!(handler.on_touch_move(1, Point2D(105f32, 100f32), 0.01) == NoAction);
+        assert!(handler.on_touch_up(1) == NoAction);
+    }
+
+    #[test]
+    fn canceled_touches_dont_scroll() {
+        let mut handler = TouchHandler::new();
+        handler.on_touch_down(1, Point2D(100f32, 100f32), 0.0);
+        handler.on_event_processed(TouchDefaultPrevented);
+        assert!(handler.on_touch_move(1, Point2D(100f32, 200f32), 0.01) == NoAction);
+        assert!(handler.on_touch_up(1) == NoAction);
+
+        // Canceling a touchmove stops a finger that was already scrolling.
+        handler.on_touch_down(1, Point2D(100f32, 100f32), 1.0);
+        handler.on_event_processed(TouchDefaultAllowed);
+        handler.on_touch_move(1, Point2D(100f32, 150f32), 1.01);
+        handler.on_event_processed(TouchDefaultPrevented);
+        assert!(handler.on_touch_move(1, Point2D(100f32, 200f32), 1.02) == NoAction);
+    }
+
+    #[test]
+    fn second_finger_stops_scrolling() {
+        let mut handler = TouchHandler::new();
+        handler.on_touch_down(1, Point2D(100f32, 100f32), 0.0);
+        handler.on_event_processed(TouchDefaultAllowed);
+        handler.on_touch_down(2, Point2D(200f32, 100f32), 0.01);
+        assert!(handler.on_touch_move(1, Point2D(100f32, 200f32), 0.02) == NoAction);
+        handler.on_touch_up(1);
+        handler.on_touch_up(2);
+        assert!(handler.state == Nothing);
+    }
+}
@@ -0,0 +1,95 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The layers videos are shown in. Layout tells the compositor where each video of a page is, by
+//! way of the renderer; the media task playing the video sends the frames it decodes straight
+//! here. Each frame is drawn into a texture of its own, which is scaled to fit the video's box
+//! without changing its aspect ratio. The page draws the box black underneath.
+
+use azure::AzFloat;
+use azure::azure_hl::{B8G8R8A8, DrawOptions, DrawSurfaceOptions, DrawTarget, Linear};
+use azure::azure_hl::current_gl_context;
+use geom::matrix::identity;
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::opts::Opts;
+use layers::layers::{TextureLayer, TextureManager};
+use servo_msg::compositor_msg::{VideoFrame, VideoLayerGeometry, VideoLayerId};
+
+use std::num::Orderable;
+
+pub struct VideoLayer {
+    id: VideoLayerId,
+    /// Where layout put the video, in the page coordinates of its layer.
+    bounds: Rect<f32>,
+    /// The latest frame, once one has come, and its size in pixels.
+    texture_layer: Option<@mut TextureLayer>,
+    frame_size: Size2D<uint>,
+}
+
+impl VideoLayer {
+    pub fn new(geometry: &VideoLayerGeometry) -> VideoLayer {
+        VideoLayer {
+            id: geometry.id,
+            bounds: geometry.bounds,
+            texture_layer: None,
+            frame_size: Size2D(0, 0),
+        }
+    }
+
+    /// Moves the video to where layout last put it.
+    pub fn set_bounds(&mut self, bounds: Rect<f32>) {
+        self.bounds = bounds;
+        self.update_transform();
+    }
+
+    /// Replaces the frame shown with a new one.
+    pub fn show_frame(&mut self, frame: ~VideoFrame, opts: &Opts) {
+        let size = Size2D(frame.size.width as i32, frame.size.height as i32);
+        let draw_target = DrawTarget::new_with_fbo(opts.render_backend,
+                                                   current_gl_context(),
+                                                   size,
+                                                   B8G8R8A8);
+        draw_target.make_current();
+        let surface = draw_target.create_source_surface_from_data(frame.data,
+                                                                  size,
+                                                                  size.width * 4,
+                                                                  B8G8R8A8);
+        let rect = Rect(Point2D(0.0 as AzFloat, 0.0 as AzFloat),
+                        Size2D(size.width as AzFloat, size.height as AzFloat));
+        draw_target.draw_surface(surface,
+                                 rect,
+                                 rect,
+                                 DrawSurfaceOptions(Linear, true),
+                                 DrawOptions(1.0 as AzFloat, 0));
+        draw_target.flush();
+
+        self.texture_layer = Some(@mut TextureLayer::new(@draw_target as @TextureManager,
+                                                         frame.size));
+        self.frame_size = frame.size;
+        self.update_transform();
+    }
+
+    /// Centers the frame in the video's box, as big as it'll go.
+    fn update_transform(&self) {
+        let texture_layer = match self.texture_layer {
+            Some(texture_layer) => texture_layer,
+            None => return,
+        };
+        if self.frame_size.width == 0 || self.frame_size.height == 0 {
+            return;
+        }
+        let frame_width = self.frame_size.width as f32;
+        let frame_height = self.frame_size.height as f32;
+        let scale = (self.bounds.size.width / frame_width).min(&(self.bounds.size.height /
+                                                                 frame_height));
+        let width = frame_width * scale;
+        let height = frame_height * scale;
+        let x = self.bounds.origin.x + (self.bounds.size.width - width) / 2.0;
+        let y = self.bounds.origin.y + (self.bounds.size.height - height) / 2.0;
+        let transform = identity().translate(x, y, 0.0).scale(width, height, 1.0);
+        texture_layer.common.set_transform(transform);
+    }
+}
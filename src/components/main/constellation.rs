@@ -2,30 +2,45 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use compositing::{CompositorChan, SetIds, SetLayerClipRect};
+use compositing::{ChangeTabs, ChangeUrl, CompositorChan, SetIds, SetLayerClipRect};
 use script::dom::event::ResizeEvent;
 
 use std::cell::Cell;
 use std::comm;
 use std::comm::Port;
+use std::managed;
 use std::task;
 use geom::size::Size2D;
 use geom::rect::Rect;
 use gfx::opts::Opts;
 use pipeline::Pipeline;
-use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, FrameRectMsg};
+use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, FailureMsg, FrameRectMsg};
+use servo_msg::constellation_msg::GetHistoryLengthMsg;
 use servo_msg::constellation_msg::{InitLoadUrlMsg, LoadIframeUrlMsg, LoadUrlMsg};
-use servo_msg::constellation_msg::{Msg, NavigateMsg, NavigationType};
-use servo_msg::constellation_msg::{PipelineId, RendererReadyMsg, ResizedWindowMsg, SubpageId};
+use servo_msg::constellation_msg::{Msg, NavigateMsg, NavigationType, OpenUrlMsg, PushStateMsg};
+use servo_msg::constellation_msg::{PipelineId, PostMessageMsg, RendererReadyMsg, ReplaceStateMsg};
+use servo_msg::constellation_msg::{ReplaceUrlMsg, ResizedWindowMsg, StructuredCloneData};
+use servo_msg::constellation_msg::{SubpageId, TraverseHistoryMsg, WindowTarget};
+use servo_msg::constellation_msg::{CloseTabMsg, GetTabsMsg, NewTabMsg, SwitchTabMsg, TabId};
+use servo_msg::constellation_msg::{PipelineWindow, SubpageWindow, ParentWindow, TopWindow};
+use servo_msg::constellation_msg::{ConsoleLevel, ConsoleMsg, DebugLevel, ErrorLevel, InfoLevel};
+use servo_msg::constellation_msg::{LogLevel, WarnLevel};
+use servo_msg::constellation_msg::{DevtoolsMsg, GetPipelinesMsg};
 use servo_msg::constellation_msg;
-use script::script_task::{SendEventMsg, ResizeInactiveMsg, ExecuteMsg};
+use servo_msg::devtools_msg::{DevtoolScriptControlMsg, PipelineSummary};
+use script::script_task::{SendEventMsg, ResizeInactiveMsg, ExecuteMsg, PopStateMsg};
+use script::script_task;
 use servo_net::image_cache_task::{ImageCacheTask, ImageCacheTaskClient};
-use servo_net::resource_task::ResourceTask;
+use servo_net::resource_task::{LoadData, ResourceTask};
 use servo_net::resource_task;
+use servo_util::memory::MemoryProfilerChan;
 use servo_util::time::ProfilerChan;
+use servo_util::url::is_same_origin;
 use std::hashmap::{HashMap, HashSet};
+use std::util;
 use std::util::replace;
 use extra::url::Url;
+use extra::url;
 use extra::future::{Future, from_value};
 
 /// Maintains the pipelines and navigation context and grants permission to composite
@@ -39,8 +54,21 @@ pub struct Constellation {
     navigation_context: NavigationContext,
     priv next_pipeline_id: PipelineId,
     pending_frames: ~[FrameChange],
+    /// The tab whose session history and pending loads are in `navigation_context` and
+    /// `pending_frames`: the one shown, except while handling a message for a background tab.
+    active_tab: TabId,
+    /// The tabs that aren't shown.
+    background_tabs: ~[BackgroundTab],
+    /// The ids of all the tabs, and which of them is shown.
+    tabs: TabList,
+    /// Whether `active_tab` is the tab shown in the window.
+    showing: bool,
     pending_sizes: HashMap<(PipelineId, SubpageId), Rect<f32>>,
+    /// The pipelines showing error pages in place of pipelines that failed. These aren't replaced
+    /// again if they fail themselves.
+    error_pages: HashSet<PipelineId>,
     profiler_chan: ProfilerChan,
+    memory_profiler_chan: MemoryProfilerChan,
     opts: Opts,
 }
 
@@ -49,6 +77,11 @@ struct FrameTree {
     pipeline: @mut Pipeline,
     parent: Option<@mut Pipeline>,
     children: ~[ChildFrameTree],
+    /// The URLs of the session history entries of the document in the pipeline: the one it was
+    /// loaded from, followed by those `history.pushState` added.
+    history: ~[Url],
+    /// The index in `history` of the entry the document is at.
+    history_index: uint,
 }
 
 // Need to clone the FrameTrees, but _not_ the Pipelines
@@ -61,6 +94,8 @@ impl Clone for FrameTree {
             pipeline: self.pipeline,
             parent: self.parent.clone(),
             children: children.collect(),
+            history: self.history.clone(),
+            history_index: self.history_index,
         }
     }
 }
@@ -101,6 +136,21 @@ impl SendableFrameTree {
 }
 
 impl FrameTree {
+    fn new(pipeline: @mut Pipeline, parent: Option<@mut Pipeline>, url: Url) -> FrameTree {
+        FrameTree {
+            pipeline: pipeline,
+            parent: parent,
+            children: ~[],
+            history: ~[url],
+            history_index: 0,
+        }
+    }
+
+    /// The URL of the session history entry the document is at.
+    fn url(&self) -> Url {
+        self.history[self.history_index].clone()
+    }
+
     fn contains(@mut self, id: PipelineId) -> bool {
         do self.iter().any |frame_tree| {
             id == frame_tree.pipeline.id
@@ -173,6 +223,66 @@ impl Iterator<@mut FrameTree> for FrameTreeIterator {
     }
 }
 
+/// The ids of the tabs, in the order they were opened, and which of them is shown.
+struct TabList {
+    ids: ~[TabId],
+    shown: TabId,
+    priv next_id: TabId,
+}
+
+impl TabList {
+    /// A list of just the first tab, shown.
+    fn new() -> TabList {
+        TabList {
+            ids: ~[TabId(0)],
+            shown: TabId(0),
+            next_id: TabId(1),
+        }
+    }
+
+    /// Adds a tab after the others, in the background, and returns its id.
+    fn open(&mut self) -> TabId {
+        let tab = self.next_id;
+        *self.next_id += 1;
+        self.ids.push(tab);
+        tab
+    }
+
+    /// Shows the given tab. Returns false if it's already shown or there's no such tab.
+    fn switch(&mut self, tab: TabId) -> bool {
+        if tab == self.shown || !self.ids.contains(&tab) {
+            return false;
+        }
+        self.shown = tab;
+        true
+    }
+
+    /// Removes the given tab. Closing the tab that's shown shows the one opened before it, or
+    /// the next one if it was the first. Returns false if there's no such tab or it's the last.
+    fn close(&mut self, tab: TabId) -> bool {
+        let position = match self.ids.iter().position(|&id| id == tab) {
+            Some(position) => position,
+            None => return false,
+        };
+        if self.ids.len() == 1 {
+            debug!("Constellation: not closing the last tab");
+            return false;
+        }
+        if tab == self.shown {
+            self.shown = self.ids[if position > 0 { position - 1 } else { 1 }];
+        }
+        self.ids.remove(position);
+        true
+    }
+}
+
+/// The state of a tab that isn't shown, kept aside while another tab's is in use.
+struct BackgroundTab {
+    id: TabId,
+    navigation_context: NavigationContext,
+    pending_frames: ~[FrameChange],
+}
+
 /// Represents the portion of a page that is changing in navigating.
 struct FrameChange {
     before: Option<PipelineId>,
@@ -224,6 +334,40 @@ impl NavigationContext {
         evicted
     }
 
+    /// Loads a new set of page frames in place of the current session history entry, returning
+    /// all evicted frame trees. Entries the current page's document had before that one stay.
+    pub fn replace(&mut self, frame_tree: @mut FrameTree) -> ~[@mut FrameTree] {
+        debug!("navigating in place to %?", frame_tree);
+        let mut evicted = ~[];
+        for &current in self.current.take().iter() {
+            if current.history_index > 0 {
+                current.history.truncate(current.history_index);
+                current.history_index -= 1;
+                current.pipeline.url = Some(current.url());
+                self.previous.push(current);
+            } else {
+                evicted.push(current);
+            }
+        }
+        self.current = Some(frame_tree);
+        evicted
+    }
+
+    /// Returns the session history entries, oldest first, as the frame tree each belongs to and
+    /// its index in that frame tree's history.
+    pub fn entries(&self) -> ~[(@mut FrameTree, uint)] {
+        let mut entries = ~[];
+        let frame_trees = self.previous.iter()
+            .chain(self.current.iter())
+            .chain(self.next.rev_iter());
+        for &frame_tree in frame_trees {
+            for index in range(0, frame_tree.history.len()) {
+                entries.push((frame_tree, index));
+            }
+        }
+        entries
+    }
+
     /// Returns the frame trees whose keys are pipeline_id.
     pub fn find_all(&mut self, pipeline_id: PipelineId) -> ~[@mut FrameTree] {
         let from_current = do self.current.iter().filter_map |frame_tree| {
@@ -255,7 +399,8 @@ impl Constellation {
                  opts: &Opts,
                  resource_task: ResourceTask,
                  image_cache_task: ImageCacheTask,
-                 profiler_chan: ProfilerChan)
+                 profiler_chan: ProfilerChan,
+                 memory_profiler_chan: MemoryProfilerChan)
                  -> ConstellationChan {
             
         let opts = Cell::new((*opts).clone());
@@ -269,6 +414,7 @@ impl Constellation {
         let resource_task = Cell::new(resource_task);
         let image_cache_task = Cell::new(image_cache_task);
         let profiler_chan = Cell::new(profiler_chan);
+        let memory_profiler_chan = Cell::new(memory_profiler_chan);
 
         do task::spawn {
             let mut constellation = Constellation {
@@ -281,8 +427,14 @@ impl Constellation {
                 navigation_context: NavigationContext::new(),
                 next_pipeline_id: PipelineId(0),
                 pending_frames: ~[],
+                active_tab: TabId(0),
+                background_tabs: ~[],
+                tabs: TabList::new(),
+                showing: true,
                 pending_sizes: HashMap::new(),
+                error_pages: HashSet::new(),
                 profiler_chan: profiler_chan.take(),
+                memory_profiler_chan: memory_profiler_chan.take(),
                 opts: opts.take(),
             };
             constellation.run();
@@ -312,8 +464,26 @@ impl Constellation {
         &self.navigation_context.current
     }
 
-    /// Handles loading pages, navigation, and granting access to the compositor
+    /// Handles `request` with the state of the tab it's about, which is the tab shown unless it
+    /// comes from a page in a background tab.
     fn handle_request(&mut self, request: Msg) -> bool {
+        let background_tab = match request_pipeline_id(&request) {
+            Some(pipeline_id) => self.background_tab_with(pipeline_id),
+            None => None,
+        };
+        match background_tab {
+            Some(index) => {
+                let request = Cell::new(request);
+                do self.in_background_tab(index) |constellation| {
+                    constellation.handle_tab_request(request.take())
+                }
+            }
+            None => self.handle_tab_request(request),
+        }
+    }
+
+    /// Handles loading pages, navigation, and granting access to the compositor
+    fn handle_tab_request(&mut self, request: Msg) -> bool {
         match request {
             ExitMsg(sender) => {
                 self.handle_exit(sender);
@@ -334,13 +504,36 @@ impl Constellation {
             // Load a new page, usually -- but not always -- from a mouse click or typed url
             // If there is already a pending page (self.pending_frames), it will not be overridden;
             // However, if the id is not encompassed by another change, it will be.
-            LoadUrlMsg(source_id, url, size_future) => {
-                self.handle_load_url_msg(source_id, url, size_future);
+            LoadUrlMsg(source_id, load_data, size_future) => {
+                self.handle_load_url_msg(source_id, load_data, size_future,
+                                         constellation_msg::Load);
+            }
+            ReplaceUrlMsg(source_id, load_data, size_future) => {
+                self.handle_load_url_msg(source_id, load_data, size_future,
+                                         constellation_msg::Replace);
+            }
+            OpenUrlMsg(load_data) => {
+                self.handle_open_url_msg(load_data);
             }
             // Handle a forward or back request
             NavigateMsg(direction) => {
                 self.handle_navigate_msg(direction);
             }
+            PushStateMsg(pipeline_id, url) => {
+                self.handle_push_state_msg(pipeline_id, url);
+            }
+            ReplaceStateMsg(pipeline_id, url) => {
+                self.handle_replace_state_msg(pipeline_id, url);
+            }
+            TraverseHistoryMsg(delta) => {
+                self.handle_traverse_history_msg(delta);
+            }
+            GetHistoryLengthMsg(chan) => {
+                chan.send(self.navigation_context.entries().len());
+            }
+            PostMessageMsg(source_id, target, data, origin, target_origin) => {
+                self.handle_post_message_msg(source_id, target, data, origin, target_origin);
+            }
             // Notification that rendering has finished and is requesting permission to paint.
             RendererReadyMsg(pipeline_id) => {
                 self.handle_renderer_ready_msg(pipeline_id);
@@ -349,10 +542,147 @@ impl Constellation {
             ResizedWindowMsg(new_size) => {
                 self.handle_resized_window_msg(new_size);
             }
+            FailureMsg(pipeline_id, subpage_id) => {
+                self.handle_failure_msg(pipeline_id, subpage_id);
+            }
+            NewTabMsg(url, chan) => {
+                let tab = self.handle_new_tab_msg(url);
+                for chan in chan.iter() {
+                    chan.send(tab);
+                }
+            }
+            CloseTabMsg(tab) => {
+                self.handle_close_tab_msg(tab);
+            }
+            SwitchTabMsg(tab) => {
+                self.handle_switch_tab_msg(tab);
+            }
+            GetTabsMsg(chan) => {
+                chan.send((self.tabs.ids.clone(), self.tabs.shown));
+            }
+            ConsoleMsg(pipeline_id, level, message) => {
+                self.handle_console_msg(pipeline_id, level, message);
+            }
+            GetPipelinesMsg(chan) => {
+                chan.send(self.pipeline_summaries());
+            }
+            DevtoolsMsg(pipeline_id, msg) => {
+                self.handle_devtools_msg(pipeline_id, msg);
+            }
         }
         true
     }
 
+    /// The index in `background_tabs` of the tab the given pipeline is in, if it's a background
+    /// tab.
+    fn background_tab_with(&mut self, pipeline_id: PipelineId) -> Option<uint> {
+        do self.background_tabs.mut_iter().position |tab| {
+            tab.navigation_context.contains(pipeline_id) ||
+                do tab.pending_frames.iter().any |frame_change| {
+                    frame_change.after.contains(pipeline_id)
+                }
+        }
+    }
+
+    /// Exchanges the state of the active tab with that of the background tab at `index`.
+    fn swap_tab(&mut self, index: uint) {
+        let tab = &mut self.background_tabs[index];
+        util::swap(&mut self.active_tab, &mut tab.id);
+        util::swap(&mut self.navigation_context, &mut tab.navigation_context);
+        util::swap(&mut self.pending_frames, &mut tab.pending_frames);
+    }
+
+    /// Calls `f` with the state of the background tab at `index` in place of the shown tab's.
+    /// Meanwhile nothing is sent to the compositor or given permission to paint.
+    fn in_background_tab<R>(&mut self, index: uint, f: &fn(&mut Constellation) -> R) -> R {
+        self.swap_tab(index);
+        self.showing = false;
+        let result = f(self);
+        self.showing = true;
+        self.swap_tab(index);
+        result
+    }
+
+    /// Opens a tab, loading `url` in it in the background.
+    fn handle_new_tab_msg(&mut self, url: Url) -> TabId {
+        let tab = self.tabs.open();
+        debug!("Constellation: opening tab %? for %s", tab, url.to_str());
+        self.background_tabs.push(BackgroundTab {
+            id: tab,
+            navigation_context: NavigationContext::new(),
+            pending_frames: ~[],
+        });
+        let url = Cell::new(url);
+        let index = self.background_tabs.len() - 1;
+        do self.in_background_tab(index) |constellation| {
+            let pipeline = constellation.create_top_level_pipeline();
+            constellation.load_top_level(pipeline, LoadData::new(url.take()));
+        }
+        self.update_tabs();
+        tab
+    }
+
+    /// Closes a tab and exits the pipelines of all its pages.
+    fn handle_close_tab_msg(&mut self, tab: TabId) {
+        if !self.tabs.close(tab) {
+            return;
+        }
+        if self.tabs.shown != self.active_tab {
+            let shown = self.tabs.shown;
+            self.show_tab(shown);
+        }
+
+        let index = self.background_tabs.iter().position(|background| background.id == tab);
+        let closed = self.background_tabs.remove(index.unwrap());
+        let history = &closed.navigation_context;
+        let frame_trees = history.previous.iter()
+            .chain(history.current.iter())
+            .chain(history.next.iter())
+            .map(|&frame_tree| frame_tree)
+            .chain(closed.pending_frames.iter().map(|frame_change| frame_change.after));
+        for frame_tree in frame_trees {
+            for frame in frame_tree.iter() {
+                if self.pipelines.pop(&frame.pipeline.id).is_some() {
+                    self.error_pages.remove(&frame.pipeline.id);
+                    frame.pipeline.exit();
+                }
+            }
+        }
+        self.update_tabs();
+    }
+
+    /// Shows a background tab in the window, in place of the one shown.
+    fn handle_switch_tab_msg(&mut self, tab: TabId) {
+        if !self.tabs.switch(tab) {
+            return;
+        }
+        self.show_tab(tab);
+        self.update_tabs();
+    }
+
+    /// Exchanges the state of the shown tab with that of the given background tab, and gives
+    /// the compositor its pages.
+    fn show_tab(&mut self, tab: TabId) {
+        let index = self.background_tabs.iter().position(|background| background.id == tab);
+        debug!("Constellation: switching to tab %?", tab);
+        for &current_frame in self.current_frame().iter() {
+            for frame in current_frame.iter() {
+                frame.pipeline.revoke_paint_permission();
+            }
+        }
+        self.swap_tab(index.unwrap());
+        // A tab whose first page hasn't loaded yet is shown once it's ready to paint.
+        for &current_frame in self.current_frame().iter() {
+            self.set_ids(current_frame);
+        }
+        self.update_url();
+    }
+
+    /// Tells the compositor which tabs there are, for the embedder to show.
+    fn update_tabs(&self) {
+        self.compositor_chan.send(ChangeTabs(self.tabs.ids.clone(), self.tabs.shown));
+    }
+
     fn handle_exit(&self, sender: Chan<()>) {
         for (_id, ref pipeline) in self.pipelines.iter() {
             pipeline.exit();
@@ -364,6 +694,28 @@ impl Constellation {
     }
 
     fn handle_init_load(&mut self, url: Url) {
+        let pipeline = self.create_top_level_pipeline();
+        if url.path.ends_with(".js") {
+            pipeline.script_chan.send(ExecuteMsg(pipeline.id, url));
+        } else {
+            self.load_top_level(pipeline, LoadData::new(url));
+        }
+    }
+
+    /// Loads a page in a new top-level browsing context. There is only the one window, so the
+    /// page takes the place of the current one, which stays in the session history.
+    fn handle_open_url_msg(&mut self, load_data: LoadData) {
+        debug!("Constellation: received message to open %s", load_data.url.to_str());
+        // The new page replaces the whole frame tree, so it would override any pending page.
+        if !self.pending_frames.is_empty() {
+            return;
+        }
+        let pipeline = self.create_top_level_pipeline();
+        self.load_top_level(pipeline, load_data);
+    }
+
+    /// Creates a pipeline for a page that isn't in a frame, sized to the window.
+    fn create_top_level_pipeline(&mut self) -> @mut Pipeline {
         let pipeline = @mut Pipeline::create(self.get_next_pipeline_id(),
                                              None,
                                              self.chan.clone(),
@@ -371,27 +723,25 @@ impl Constellation {
                                              self.image_cache_task.clone(),
                                              self.resource_task.clone(),
                                              self.profiler_chan.clone(),
+                                             self.memory_profiler_chan.clone(),
                                              self.opts.clone(),
                                              {
                                                  let size = self.compositor_chan.get_size();
                                                  from_value(Size2D(size.width as uint, size.height as uint))
                                              });
-        if url.path.ends_with(".js") {
-            pipeline.script_chan.send(ExecuteMsg(pipeline.id, url));
-        } else {
-            pipeline.load(url);
-
-            self.pending_frames.push(FrameChange{
-                before: None,
-                after: @mut FrameTree {
-                    pipeline: pipeline, 
-                    parent: None,
-                    children: ~[],
-                },
-                navigation_type: constellation_msg::Load,
-            });
-        }
         self.pipelines.insert(pipeline.id, pipeline);
+        pipeline
+    }
+
+    /// Loads a page in `pipeline`, which becomes the root frame once it's ready to paint.
+    fn load_top_level(&mut self, pipeline: @mut Pipeline, load_data: LoadData) {
+        let url = load_data.url.clone();
+        pipeline.load(load_data);
+        self.pending_frames.push(FrameChange{
+            before: None,
+            after: @mut FrameTree::new(pipeline, None, url),
+            navigation_type: constellation_msg::Load,
+        });
     }
     
     fn handle_frame_rect_msg(&mut self, pipeline_id: PipelineId, subpage_id: SubpageId, rect: Rect<f32>) {
@@ -412,7 +762,9 @@ impl Constellation {
                         pipeline.script_chan.send(SendEventMsg(pipeline.id.clone(),
                                                                ResizeEvent(width as uint,
                                                                            height as uint))); 
-                        self.compositor_chan.send(SetLayerClipRect(pipeline.id, rect));
+                        if self.showing {
+                            self.compositor_chan.send(SetLayerClipRect(pipeline.id, rect));
+                        }
                         already_sent.insert(pipeline.id.clone());
                         break;
                     }
@@ -489,9 +841,7 @@ impl Constellation {
         source's Url is None. There should never be a LoadUrlIframeMsg from a pipeline
         that was never given a url to load.");
 
-        // FIXME(tkuehn): Need to follow the standardized spec for checking same-origin
-        let pipeline = @mut if (source_url.host == url.host &&
-                               source_url.port == url.port) {
+        let pipeline = @mut if is_same_origin(&source_url, &url) {
             debug!("Constellation: loading same-origin iframe at %?", url);
             // Reuse the script task if same-origin url's
             Pipeline::with_script(next_pipeline_id,
@@ -500,6 +850,7 @@ impl Constellation {
                                   self.compositor_chan.clone(),
                                   self.image_cache_task.clone(),
                                   self.profiler_chan.clone(),
+                                  self.memory_profiler_chan.clone(),
                                   self.opts.clone(),
                                   source_pipeline,
                                   size_future)
@@ -513,32 +864,34 @@ impl Constellation {
                              self.image_cache_task.clone(),
                              self.resource_task.clone(),
                              self.profiler_chan.clone(),
+                             self.memory_profiler_chan.clone(),
                              self.opts.clone(),
                              size_future)
         };
 
         if url.path.ends_with(".js") {
-            pipeline.execute(url);
+            pipeline.execute(url.clone());
         } else {
             debug!("Constellation: sending load msg to %?", pipeline);
-            pipeline.load(url);
+            pipeline.load(LoadData::new(url.clone()));
         }
         let rect = self.pending_sizes.pop(&(source_pipeline_id, subpage_id));
         for frame_tree in frame_trees.iter() {
             frame_tree.children.push(ChildFrameTree {
-                frame_tree: @mut FrameTree {
-                    pipeline: pipeline,
-                    parent: Some(source_pipeline),
-                    children: ~[],
-                },
+                frame_tree: @mut FrameTree::new(pipeline, Some(source_pipeline), url.clone()),
                 rect: rect,
             });
         }
         self.pipelines.insert(pipeline.id, pipeline);
     }
 
-    fn handle_load_url_msg(&mut self, source_id: PipelineId, url: Url, size_future: Future<Size2D<uint>>) {
-        debug!("Constellation: received message to load %s", url.to_str());
+    fn handle_load_url_msg(&mut self,
+                           source_id: PipelineId,
+                           load_data: LoadData,
+                           size_future: Future<Size2D<uint>>,
+                           navigation_type: NavigationType) {
+        debug!("Constellation: received message to %s %s", load_data.method,
+               load_data.url.to_str());
         // Make sure no pending page would be overridden.
         let source_frame = self.current_frame().get_ref().find_mut(source_id).expect(
             "Constellation: received a LoadUrlMsg from a pipeline_id associated
@@ -546,9 +899,11 @@ impl Constellation {
             impossible.");
 
         for frame_change in self.pending_frames.iter() {
-            let old_id = frame_change.before.expect("Constellation: Received load msg
-                from pipeline, but there is no currently active page. This should
-                be impossible.");
+            let old_id = match frame_change.before {
+                Some(old_id) => old_id,
+                // A new top-level page is on its way, which replaces the whole frame tree.
+                None => return,
+            };
             let changing_frame = self.current_frame().get_ref().find_mut(old_id).expect("Constellation:
                 Pending change has non-active source pipeline. This should be
                 impossible.");
@@ -571,22 +926,20 @@ impl Constellation {
                                              self.image_cache_task.clone(),
                                              self.resource_task.clone(),
                                              self.profiler_chan.clone(),
+                                             self.memory_profiler_chan.clone(),
                                              self.opts.clone(),
                                              size_future);
 
-        if url.path.ends_with(".js") {
-            pipeline.script_chan.send(ExecuteMsg(pipeline.id, url));
+        if load_data.url.path.ends_with(".js") {
+            pipeline.script_chan.send(ExecuteMsg(pipeline.id, load_data.url));
         } else {
-            pipeline.load(url);
+            let url = load_data.url.clone();
+            pipeline.load(load_data);
 
             self.pending_frames.push(FrameChange{
                 before: Some(source_id),
-                after: @mut FrameTree {
-                    pipeline: pipeline, 
-                    parent: parent,
-                    children: ~[],
-                },
-                navigation_type: constellation_msg::Load,
+                after: @mut FrameTree::new(pipeline, parent, url),
+                navigation_type: navigation_type,
             });
         }
         self.pipelines.insert(pipeline.id, pipeline);
@@ -594,44 +947,246 @@ impl Constellation {
     
     fn handle_navigate_msg(&mut self, direction: constellation_msg::NavigationDirection) {
         debug!("received message to navigate %?", direction);
+        match direction {
+            constellation_msg::Forward => self.handle_traverse_history_msg(1),
+            constellation_msg::Back => self.handle_traverse_history_msg(-1),
+        }
+    }
+
+    fn handle_traverse_history_msg(&mut self, delta: int) {
+        debug!("received message to traverse the session history by %d", delta);
+        let current_frame = match *self.current_frame() {
+            Some(current_frame) => current_frame,
+            None => return,
+        };
+        if delta == 0 {
+            for frame in current_frame.iter() {
+                frame.pipeline.reload();
+            }
+            return;
+        }
+
+        let entries = self.navigation_context.entries();
+        let position = do entries.iter().position |&(frame_tree, index)| {
+            managed::mut_ptr_eq(frame_tree, current_frame) && index == current_frame.history_index
+        };
+        let position = position.expect("Constellation: the current session history entry is not
+            in the navigation context. This is a bug.");
+        let destination = position as int + delta;
+        if destination < 0 || destination >= entries.len() as int {
+            debug!("no session history entry to traverse to");
+            return;
+        }
+        let (destination_frame, destination_index) = entries[destination as uint];
+
+        // Entries of the same document only change the state script sees.
+        if managed::mut_ptr_eq(destination_frame, current_frame) {
+            self.pop_state(destination_frame, destination_index);
+            self.update_url();
+            return;
+        }
 
         // TODO(tkuehn): what is the "critical point" beyond which pending frames
         // should not be cleared? Currently, the behavior is that forward/back
         // navigation always has navigation priority, and after that new page loading is
         // first come, first served.
-        let destination_frame = match direction {
-            constellation_msg::Forward => {
-                if self.navigation_context.next.is_empty() {
-                    debug!("no next page to navigate to");
-                    return;
-                } else {
-                    let old = self.current_frame().get_ref();
-                    for frame in old.iter() {
-                        frame.pipeline.revoke_paint_permission();
-                    }
-                }
-                self.navigation_context.forward()
-            }
-            constellation_msg::Back => {
-                if self.navigation_context.previous.is_empty() {
-                    debug!("no previous page to navigate to");
-                    return;
-                } else {
-                    let old = self.current_frame().get_ref();
-                    for frame in old.iter() {
-                        frame.pipeline.revoke_paint_permission();
-                    }
-                }
-                self.navigation_context.back()
+        for frame in current_frame.iter() {
+            frame.pipeline.revoke_paint_permission();
+        }
+        // Documents passed over are left at the entry nearest the current one, as they would be
+        // if the user went there one step at a time.
+        while !managed::mut_ptr_eq(self.navigation_context.current.unwrap(), destination_frame) {
+            let passed = self.navigation_context.current.unwrap();
+            if delta < 0 {
+                passed.history_index = 0;
+                self.navigation_context.back();
+            } else {
+                passed.history_index = passed.history.len() - 1;
+                self.navigation_context.forward();
             }
-        };
+        }
 
         for frame in destination_frame.iter() {
             let pipeline = &frame.pipeline;
             pipeline.reload();
         }
+        // The document is kept at the entry it was left at, so landing on another one of its
+        // entries pops the state too.
+        if destination_frame.history_index != destination_index {
+            self.pop_state(destination_frame, destination_index);
+        }
         self.grant_paint_permission(destination_frame, constellation_msg::Navigate);
+    }
 
+    fn handle_push_state_msg(&mut self, pipeline_id: PipelineId, url: Url) {
+        debug!("received message to push a session history entry for %s", url.to_str());
+        let frame_tree = match self.find_page(pipeline_id) {
+            Some(frame_tree) => frame_tree,
+            None => return,
+        };
+        frame_tree.history.truncate(frame_tree.history_index + 1);
+        frame_tree.history.push(url.clone());
+        frame_tree.history_index += 1;
+        frame_tree.pipeline.url = Some(url);
+
+        // As with loading a page, the entries after the new one go away.
+        if self.is_current(frame_tree) {
+            let evicted = replace(&mut self.navigation_context.next, ~[]);
+            self.exit_evicted(evicted);
+            self.update_url();
+        }
+    }
+
+    fn handle_replace_state_msg(&mut self, pipeline_id: PipelineId, url: Url) {
+        debug!("received message to replace a session history entry with %s", url.to_str());
+        let frame_tree = match self.find_page(pipeline_id) {
+            Some(frame_tree) => frame_tree,
+            None => return,
+        };
+        frame_tree.history[frame_tree.history_index] = url.clone();
+        frame_tree.pipeline.url = Some(url);
+        if self.is_current(frame_tree) {
+            self.update_url();
+        }
+    }
+
+    /// Prints a message logged by script in the given pipeline. Every pipeline's messages come
+    /// through here, so that they're printed whole and in the order they were logged.
+    fn handle_console_msg(&self, pipeline_id: PipelineId, level: ConsoleLevel, message: ~str) {
+        let level = match level {
+            LogLevel => "LOG",
+            DebugLevel => "DEBUG",
+            InfoLevel => "INFO",
+            WarnLevel => "WARN",
+            ErrorLevel => "ERROR",
+        };
+        let PipelineId(id) = pipeline_id;
+        println(fmt!("CONSOLE %s (pipeline %u): %s", level, id, message));
+    }
+
+    /// The pages shown in every tab, in the order the tabs were opened, each tab's frames in
+    /// depth-first order.
+    fn pipeline_summaries(&self) -> ~[PipelineSummary] {
+        let mut summaries = ~[];
+        for &tab in self.tabs.ids.iter() {
+            let current = if tab == self.active_tab {
+                self.navigation_context.current
+            } else {
+                match self.background_tabs.iter().find(|background| background.id == tab) {
+                    Some(background) => background.navigation_context.current,
+                    None => None,
+                }
+            };
+            for &frame_tree in current.iter() {
+                for frame_tree in frame_tree.iter() {
+                    summaries.push(PipelineSummary {
+                        id: frame_tree.pipeline.id,
+                        tab: tab,
+                        parent: frame_tree.parent.map(|parent| parent.id),
+                        url: frame_tree.pipeline.url.clone(),
+                    });
+                }
+            }
+        }
+        summaries
+    }
+
+    /// Passes a request of the developer tools on to the script task of the given pipeline.
+    fn handle_devtools_msg(&self, pipeline_id: PipelineId, msg: DevtoolScriptControlMsg) {
+        match self.pipelines.find(&pipeline_id) {
+            Some(pipeline) => pipeline.script_chan.send(script_task::DevtoolsMsg(pipeline_id, msg)),
+            None => debug!("Constellation: no pipeline %? for the developer tools", pipeline_id),
+        }
+    }
+
+    /// Passes a message posted by script in the given pipeline on to the script of the window it
+    /// was posted to. Messages to windows that have gone away are dropped.
+    fn handle_post_message_msg(&mut self,
+                               source_id: PipelineId,
+                               target: WindowTarget,
+                               data: StructuredCloneData,
+                               origin: ~str,
+                               target_origin: Option<~str>) {
+        let target_id = match self.find_window(source_id, target) {
+            Some(target_id) => target_id,
+            None => {
+                debug!("Constellation: no window %? of %? to post a message to", target, source_id);
+                return
+            }
+        };
+        for pipeline in self.pipelines.find(&target_id).iter() {
+            pipeline.script_chan.send(script_task::PostMessageMsg(target_id,
+                                                                  source_id,
+                                                                  origin.clone(),
+                                                                  target_origin.clone(),
+                                                                  data.clone()));
+        }
+    }
+
+    /// The pipeline of the window that `target` means to script in the pipeline `source_id`, in
+    /// the current frame tree.
+    fn find_window(&self, source_id: PipelineId, target: WindowTarget) -> Option<PipelineId> {
+        let current_frame = match *self.current_frame() {
+            Some(current_frame) => current_frame,
+            None => return None,
+        };
+        let source = match current_frame.find_mut(source_id) {
+            Some(source) => source,
+            None => return None,
+        };
+        match target {
+            PipelineWindow(id) => Some(id),
+            SubpageWindow(subpage_id) => {
+                let child = do source.children.iter().find |child| {
+                    child.frame_tree.pipeline.subpage_id == Some(subpage_id)
+                };
+                child.map(|child| child.frame_tree.pipeline.id)
+            }
+            ParentWindow => match source.parent {
+                Some(parent) => Some(parent.id),
+                None => Some(source_id),
+            },
+            TopWindow => Some(current_frame.pipeline.id),
+        }
+    }
+
+    /// Returns the frame tree of the page in the given pipeline, whether it's the current page or
+    /// one still loading.
+    ///
+    /// FIXME: Documents in subframes should add entries to the session history too. For now,
+    /// only pages have more than one.
+    fn find_page(&self, pipeline_id: PipelineId) -> Option<@mut FrameTree> {
+        let current = self.current_frame().iter().map(|&frame_tree| frame_tree);
+        let pending = self.pending_frames.iter().map(|frame_change| frame_change.after);
+        let mut frame_trees = current.chain(pending);
+        do frame_trees.find |frame_tree| {
+            frame_tree.pipeline.id == pipeline_id && frame_tree.parent.is_none()
+        }
+    }
+
+    fn is_current(&self, frame_tree: @mut FrameTree) -> bool {
+        do self.current_frame().iter().any |&current_frame| {
+            managed::mut_ptr_eq(current_frame, frame_tree)
+        }
+    }
+
+    /// Moves the document in the frame tree to another of its session history entries, and has
+    /// script fire `popstate` for it.
+    fn pop_state(&self, frame_tree: @mut FrameTree, index: uint) {
+        frame_tree.history_index = index;
+        let url = frame_tree.url();
+        frame_tree.pipeline.url = Some(url.clone());
+        frame_tree.pipeline.script_chan.send(PopStateMsg(frame_tree.pipeline.id, index, url));
+    }
+
+    /// Shows the URL of the current session history entry in the URL bar.
+    fn update_url(&self) {
+        if !self.showing {
+            return;
+        }
+        for current_frame in self.current_frame().iter() {
+            self.compositor_chan.send(ChangeUrl(current_frame.url()));
+        }
     }
     
     fn handle_renderer_ready_msg(&mut self, pipeline_id: PipelineId) {
@@ -645,8 +1200,10 @@ impl Constellation {
             // TODO(tkuehn): In fact, this kind of message might be provably
             // impossible to occur.
             if current_frame.contains(pipeline_id) {
-                for frame in current_frame.iter() {
-                    frame.pipeline.grant_paint_permission();
+                if self.showing {
+                    for frame in current_frame.iter() {
+                        frame.pipeline.grant_paint_permission();
+                    }
                 }
                 return;
             }
@@ -665,7 +1222,14 @@ impl Constellation {
             // Create the next frame tree that will be given to the compositor
             let next_frame_tree = match to_add.parent {
                 None => to_add, // to_add is the root
-                Some(_parent) => @mut (*self.current_frame().unwrap()).clone(),
+                Some(_parent) => {
+                    // The subframe's page becomes a session history entry of its own.
+                    let current_frame = self.current_frame().unwrap();
+                    let next_frame_tree = @mut (*current_frame).clone();
+                    next_frame_tree.history = ~[current_frame.url()];
+                    next_frame_tree.history_index = 0;
+                    next_frame_tree
+                }
             };
 
             // If there are frames to revoke permission from, do so now.
@@ -715,10 +1279,132 @@ impl Constellation {
         }
     }
 
+    /// Puts an error page in the frame of a pipeline whose tasks failed, in place of its page, and
+    /// forgets the pipeline. The rest of the browser carries on.
+    ///
+    /// FIXME: A failed pipeline that's only in the session history is forgotten, but its entries
+    /// stay there.
+    fn handle_failure_msg(&mut self, pipeline_id: PipelineId, subpage_id: Option<SubpageId>) {
+        debug!("Constellation: pipeline %? failed", pipeline_id);
+        let failed = match self.pipelines.pop(&pipeline_id) {
+            Some(failed) => failed,
+            // Its frame has gone away already.
+            None => return,
+        };
+        let pending_index = do self.pending_frames.iter().position |frame_change| {
+            frame_change.after.pipeline.id == pipeline_id
+        };
+
+        // An error page that fails is left as it is, rather than replaced by another one that
+        // might fail in turn; one that hadn't been shown yet never will be.
+        if self.error_pages.remove(&pipeline_id) {
+            for &index in pending_index.iter() {
+                self.pending_frames.remove(index);
+            }
+            return;
+        }
+
+        // A page that fails while it's loading is replaced before it's shown, and the page it was
+        // to replace goes into the session history, as it would have; one that fails once it's
+        // shown is replaced in the session history, unless a page is already loading to replace
+        // it, in which case that page is left to do so.
+        let (before, parent, navigation_type) = match pending_index {
+            Some(index) => {
+                let frame_change = self.pending_frames.remove(index);
+                (frame_change.before, frame_change.after.parent, frame_change.navigation_type)
+            }
+            None if self.is_being_replaced(pipeline_id) => return,
+            None => {
+                let frame = match *self.current_frame() {
+                    Some(current_frame) => current_frame.find_mut(pipeline_id),
+                    None => None,
+                };
+                match frame {
+                    Some(frame) => (Some(pipeline_id), frame.parent, constellation_msg::Replace),
+                    None => return,
+                }
+            }
+        };
+
+        let url = error_page_url(&failed.url);
+        let size = self.frame_size(pipeline_id);
+        let pipeline = @mut Pipeline::create(self.get_next_pipeline_id(),
+                                             subpage_id,
+                                             self.chan.clone(),
+                                             self.compositor_chan.clone(),
+                                             self.image_cache_task.clone(),
+                                             self.resource_task.clone(),
+                                             self.profiler_chan.clone(),
+                                             self.memory_profiler_chan.clone(),
+                                             self.opts.clone(),
+                                             from_value(size));
+        pipeline.load(LoadData::new(url.clone()));
+        self.pending_frames.push(FrameChange {
+            before: before,
+            after: @mut FrameTree::new(pipeline, parent, url),
+            navigation_type: navigation_type,
+        });
+        self.pipelines.insert(pipeline.id, pipeline);
+        self.error_pages.insert(pipeline.id);
+    }
+
+    /// Whether a pending frame change will take the given pipeline out of the current frame tree:
+    /// a new top-level page, or one replacing its frame or a frame it's in.
+    fn is_being_replaced(&self, pipeline_id: PipelineId) -> bool {
+        do self.pending_frames.iter().any |frame_change| {
+            match frame_change.before {
+                Some(old_id) => {
+                    let old_frame = match *self.current_frame() {
+                        Some(current_frame) => current_frame.find_mut(old_id),
+                        None => None,
+                    };
+                    old_frame.map_default(false, |&old_frame| old_frame.contains(pipeline_id))
+                }
+                None => frame_change.after.parent.is_none(),
+            }
+        }
+    }
+
+    /// The size of the frame of the given pipeline in the current frame tree: that of its iframe,
+    /// if it's in one and it's been laid out, and otherwise that of the window.
+    fn frame_size(&self, pipeline_id: PipelineId) -> Size2D<uint> {
+        for &current_frame in self.current_frame().iter() {
+            for frame_tree in current_frame.iter() {
+                for child in frame_tree.children.iter() {
+                    if child.frame_tree.pipeline.id != pipeline_id {
+                        loop
+                    }
+                    for rect in child.rect.iter() {
+                        return Size2D(rect.size.width as uint, rect.size.height as uint)
+                    }
+                }
+            }
+        }
+        let size = self.compositor_chan.get_size();
+        Size2D(size.width as uint, size.height as uint)
+    }
+
+    /// Tells the top-level pipelines of every tab the window has a new size.
     fn handle_resized_window_msg(&mut self, new_size: Size2D<uint>) {
+        self.resize_tab(new_size);
+        // The tabs share the window, so those in the background take its size too.
+        for index in range(0, self.background_tabs.len()) {
+            self.in_background_tab(index, |constellation| constellation.resize_tab(new_size));
+        }
+    }
+
+    /// Tells the top-level pipelines of the active tab the window has a new size. The current one,
+    /// and any page loading in to replace it, are laid out again at once, and their iframes are
+    /// told their new sizes as they're laid out; pages in the session history catch up when
+    /// they're returned to.
+    fn resize_tab(&mut self, new_size: Size2D<uint>) {
         let mut already_seen = HashSet::new();
-        for &@FrameTree { pipeline: pipeline, _ } in self.current_frame().iter() {
-            let Size2D { width, height } = new_size;
+        let Size2D { width, height } = new_size;
+        let pending = self.pending_frames.iter().filter(|frame_change| {
+            frame_change.after.parent.is_none()
+        }).map(|frame_change| frame_change.after);
+        for frame_tree in self.current_frame().iter().map(|&frame_tree| frame_tree).chain(pending) {
+            let pipeline = frame_tree.pipeline;
             pipeline.script_chan.send(SendEventMsg(pipeline.id.clone(),
                                                    ResizeEvent(width, height)));
             already_seen.insert(pipeline.id.clone());
@@ -735,26 +1421,44 @@ impl Constellation {
 
     // Grants a frame tree permission to paint; optionally updates navigation to reflect a new page
     fn grant_paint_permission(&mut self, frame_tree: @mut FrameTree, navigation_type: NavigationType) {
-        // Give permission to paint to the new frame and all child frames
-        self.set_ids(frame_tree);
+        // Give permission to paint to the new frame and all child frames, if its tab is shown
+        if self.showing {
+            self.set_ids(frame_tree);
+        }
 
         // Don't call navigation_context.load() on a Navigate type (or None, as in the case of
         // parsed iframes that finish loading)
         match navigation_type {
             constellation_msg::Load => {
-                let evicted = self.navigation_context.load(frame_tree);
-                for frame_tree in evicted.iter() {
-                    // exit any pipelines that don't exist outside the evicted frame trees
-                    for frame in frame_tree.iter() {
-                        if !self.navigation_context.contains(frame.pipeline.id) {
-                            frame_tree.pipeline.exit();
-                            self.pipelines.remove(&frame_tree.pipeline.id);
-                        }
-                    }
+                // The entries the current page's document had after the one it's at go away
+                // along with the pages after it.
+                for current_frame in self.navigation_context.current.iter() {
+                    current_frame.history.truncate(current_frame.history_index + 1);
                 }
+                let evicted = self.navigation_context.load(frame_tree);
+                self.exit_evicted(evicted);
+            }
+            constellation_msg::Replace => {
+                let evicted = self.navigation_context.replace(frame_tree);
+                self.exit_evicted(evicted);
             }
             _ => {}
         }
+        self.update_url();
+    }
+
+    /// Exits the pipelines of frame trees evicted from the navigation context.
+    fn exit_evicted(&mut self, evicted: ~[@mut FrameTree]) {
+        for frame_tree in evicted.iter() {
+            // exit any pipelines that don't exist outside the evicted frame trees
+            for frame in frame_tree.iter() {
+                if !self.navigation_context.contains(frame.pipeline.id) {
+                    frame_tree.pipeline.exit();
+                    self.pipelines.remove(&frame_tree.pipeline.id);
+                    self.error_pages.remove(&frame_tree.pipeline.id);
+                }
+            }
+        }
     }
 
     fn set_ids(&self, frame_tree: @mut FrameTree) {
@@ -767,3 +1471,71 @@ impl Constellation {
     }
 }
 
+/// A `data:` URL of a page saying that the page at `failed_url` failed.
+fn error_page_url(failed_url: &Option<Url>) -> Url {
+    let page = match *failed_url {
+        Some(ref failed_url) => failed_url.to_str().replace("&", "&amp;").replace("<", "&lt;"),
+        None => ~"this frame",
+    };
+    let html = fmt!("<title>Page failed</title><h1>Page failed</h1><p>Something went wrong \
+                     showing %s.</p>", page);
+    url::from_str(fmt!("data:text/html,%s", url::encode_component(html))).unwrap()
+}
+
+/// The pipeline a message comes from, for those that come from pages.
+fn request_pipeline_id(request: &Msg) -> Option<PipelineId> {
+    match *request {
+        FrameRectMsg(pipeline_id, _, _) |
+        LoadUrlMsg(pipeline_id, _, _) |
+        ReplaceUrlMsg(pipeline_id, _, _) |
+        LoadIframeUrlMsg(_, pipeline_id, _, _) |
+        PushStateMsg(pipeline_id, _) |
+        ReplaceStateMsg(pipeline_id, _) |
+        PostMessageMsg(pipeline_id, _, _, _, _) |
+        RendererReadyMsg(pipeline_id) |
+        FailureMsg(pipeline_id, _) => Some(pipeline_id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TabList;
+    use servo_msg::constellation_msg::TabId;
+
+    #[test]
+    fn switches_tabs() {
+        let mut tabs = TabList::new();
+        let second = tabs.open();
+        assert!(tabs.shown == TabId(0));
+        assert!(tabs.switch(second));
+        assert!(tabs.shown == second);
+        assert!(!tabs.switch(second));
+        assert!(!tabs.switch(TabId(7)));
+        assert!(tabs.switch(TabId(0)));
+        assert!(tabs.ids == ~[TabId(0), second]);
+    }
+
+    #[test]
+    fn closing_the_shown_tab_shows_the_one_before_it() {
+        let mut tabs = TabList::new();
+        let second = tabs.open();
+        let third = tabs.open();
+        tabs.switch(third);
+        assert!(tabs.close(third));
+        assert!(tabs.shown == second);
+        assert!(tabs.ids == ~[TabId(0), second]);
+
+        // The first tab has none before it, so the next one is shown.
+        tabs.switch(TabId(0));
+        assert!(tabs.close(TabId(0)));
+        assert!(tabs.shown == second);
+
+        // Background tabs close without changing which is shown, and the last stays open.
+        let fourth = tabs.open();
+        assert!(tabs.close(fourth));
+        assert!(tabs.shown == second);
+        assert!(!tabs.close(second));
+        assert!(tabs.ids == ~[second]);
+    }
+}
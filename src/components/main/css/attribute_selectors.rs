@@ -0,0 +1,101 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Matching attribute values against the operators of attribute selectors.
+
+use std::ascii::StrAsciiExt;
+
+/// How an attribute selector compares an attribute's value with the one it gives.
+#[deriving(Eq)]
+pub enum AttrOperator {
+    /// `[attr=value]`
+    EqualsOperator,
+    /// `[attr~=value]`: one of the value's whitespace-separated words.
+    IncludesOperator,
+    /// `[attr|=value]`: the value, or the value followed by a hyphen and anything else.
+    DashMatchOperator,
+    /// `[attr^=value]`
+    PrefixOperator,
+    /// `[attr$=value]`
+    SuffixOperator,
+    /// `[attr*=value]`
+    SubstringOperator,
+}
+
+/// The HTML attributes whose values selectors compare ignoring ASCII case, as listed in HTML.
+static CASE_INSENSITIVE_ATTRIBUTES: &'static [&'static str] = &[
+    "accept", "accept-charset", "align", "alink", "axis", "bgcolor", "charset", "checked",
+    "clear", "codetype", "color", "compact", "declare", "defer", "dir", "direction", "disabled",
+    "enctype", "face", "frame", "hreflang", "http-equiv", "lang", "language", "link", "media",
+    "method", "multiple", "nohref", "noresize", "noshade", "nowrap", "readonly", "rel", "rev",
+    "rules", "scope", "scrolling", "selected", "shape", "target", "text", "type", "valign",
+    "valuetype", "vlink",
+];
+
+/// Whether selectors compare the values of the HTML attribute `name` ignoring ASCII case.
+pub fn has_case_insensitive_value(name: &str) -> bool {
+    CASE_INSENSITIVE_ATTRIBUTES.iter().any(|attribute| name.eq_ignore_ascii_case(*attribute))
+}
+
+/// Whether the attribute value `value` matches `pattern` under `operator`.
+pub fn value_matches(operator: AttrOperator, value: &str, pattern: &str, ignore_case: bool)
+                     -> bool {
+    if ignore_case {
+        let (value, pattern) = (value.to_ascii_lower(), pattern.to_ascii_lower());
+        return value_matches(operator, value.as_slice(), pattern.as_slice(), false);
+    }
+    match operator {
+        EqualsOperator => value == pattern,
+        // A value with whitespace in it can never be one of the words.
+        IncludesOperator => {
+            !pattern.is_empty() && !pattern.iter().any(|c| c.is_whitespace()) &&
+                value.word_iter().any(|word| word == pattern)
+        }
+        DashMatchOperator => {
+            value == pattern ||
+                (value.starts_with(pattern) && value.char_at(pattern.len()) == '-')
+        }
+        // Empty values match nothing, rather than everything.
+        PrefixOperator => !pattern.is_empty() && value.starts_with(pattern),
+        SuffixOperator => !pattern.is_empty() && value.ends_with(pattern),
+        SubstringOperator => !pattern.is_empty() && value.contains(pattern),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators() {
+        assert!(value_matches(EqualsOperator, "text", "text", false));
+        assert!(!value_matches(EqualsOperator, "text", "tex", false));
+        assert!(value_matches(IncludesOperator, "big  red\tbox", "red", false));
+        assert!(!value_matches(IncludesOperator, "big red box", "red box", false));
+        assert!(value_matches(DashMatchOperator, "en-US", "en", false));
+        assert!(value_matches(DashMatchOperator, "en", "en", false));
+        assert!(!value_matches(DashMatchOperator, "english", "en", false));
+        assert!(value_matches(PrefixOperator, "http://example.com/", "http:", false));
+        assert!(value_matches(SuffixOperator, "report.pdf", ".pdf", false));
+        assert!(value_matches(SubstringOperator, "a-warning-box", "warning", false));
+    }
+
+    #[test]
+    fn empty_patterns() {
+        assert!(value_matches(EqualsOperator, "", "", false));
+        assert!(!value_matches(IncludesOperator, "a b", "", false));
+        assert!(!value_matches(PrefixOperator, "text", "", false));
+        assert!(!value_matches(SuffixOperator, "text", "", false));
+        assert!(!value_matches(SubstringOperator, "text", "", false));
+    }
+
+    #[test]
+    fn case() {
+        assert!(has_case_insensitive_value("TYPE"));
+        assert!(!has_case_insensitive_value("class"));
+        assert!(value_matches(EqualsOperator, "Checkbox", "checkbox", true));
+        assert!(value_matches(PrefixOperator, "EN-us", "en-", true));
+        assert!(!value_matches(EqualsOperator, "Checkbox", "checkbox", false));
+    }
+}
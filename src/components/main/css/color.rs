@@ -0,0 +1,65 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS color values in `style` attributes. The values themselves are parsed by `gfx::color`, so
+//! that script can parse the colors it's given too.
+
+use css::style_attribute::{DeclaredValue, declared_value};
+
+pub use gfx::color::RGBA;
+use std::ascii::StrAsciiExt;
+
+/// The specified value of a color property.
+#[deriving(Clone, Eq)]
+pub enum SpecifiedColor {
+    SpecifiedRGBA(RGBA),
+    /// The value of `color` on the same element, which is resolved once that's known. On `color`
+    /// itself, it means `inherit`.
+    CurrentColor,
+}
+
+impl SpecifiedColor {
+    /// Parses a color value. Returns `None` for invalid values.
+    pub fn parse(value: &str) -> Option<SpecifiedColor> {
+        if value.trim().eq_ignore_ascii_case("currentcolor") {
+            return Some(CurrentColor);
+        }
+        RGBA::parse(value).map_move(|rgba| SpecifiedRGBA(rgba))
+    }
+}
+
+/// Finds the value of the color property `name` declared in the text of a `style` attribute, if
+/// any.
+pub fn from_style_attribute(style: &str, name: &str) -> Option<DeclaredValue<SpecifiedColor>> {
+    declared_value(style, name, |value| SpecifiedColor::parse(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css::style_attribute::{Inherit, SpecifiedValue};
+
+    #[test]
+    fn style_attribute() {
+        let style = "color: #00f; background-color: hsl(0, 100%, 50%); color: bogus";
+        assert!(from_style_attribute(style, "color") ==
+                Some(SpecifiedValue(SpecifiedRGBA(RGBA::new(0, 0, 255, 1.0)))));
+        assert!(from_style_attribute(style, "background-color") ==
+                Some(SpecifiedValue(SpecifiedRGBA(RGBA::new(255, 0, 0, 1.0)))));
+        assert!(from_style_attribute(style, "border-top-color").is_none());
+        assert!(from_style_attribute("color: hsl(0, 100%, 50%); color: inherit", "color") ==
+                Some(Inherit));
+    }
+
+    #[test]
+    fn current_color() {
+        assert!(SpecifiedColor::parse(" currentColor ") == Some(CurrentColor));
+        assert!(SpecifiedColor::parse("rgba(0, 0, 255, 0.5)") ==
+                Some(SpecifiedRGBA(RGBA::new(0, 0, 255, 0.5))));
+        assert!(SpecifiedColor::parse("hsla(120, 100%, 50%, 0.25)") ==
+                Some(SpecifiedRGBA(RGBA::new(0, 255, 0, 0.25))));
+        assert!(from_style_attribute("border-color: CURRENTCOLOR", "border-color") ==
+                Some(SpecifiedValue(CurrentColor)));
+    }
+}
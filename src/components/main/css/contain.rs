@@ -0,0 +1,163 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The CSS `contain` and `content-visibility` properties.
+
+use css::style_attribute::{DeclaredValue, declared_value};
+
+use std::ascii::StrAsciiExt;
+
+/// The computed value of `contain`.
+///
+/// A subtree with layout containment is laid out as an independent formatting and sizing root:
+/// floats neither enter nor leave it, its intrinsic widths don't depend on its contents, and
+/// damage inside it doesn't invalidate the layout of its ancestors. A subtree with paint
+/// containment is clipped to its box.
+#[deriving(Clone, Eq)]
+pub struct Containment {
+    layout: bool,
+    paint: bool,
+}
+
+impl Containment {
+    pub fn none() -> Containment {
+        Containment {
+            layout: false,
+            paint: false,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        !self.layout && !self.paint
+    }
+
+    /// Parses a value of the `contain` property. Returns `None` for invalid values.
+    pub fn parse(value: &str) -> Option<Containment> {
+        let value = value.trim().to_ascii_lower();
+        match value.as_slice() {
+            "none" => return Some(Containment::none()),
+            "strict" | "content" => return Some(Containment { layout: true, paint: true }),
+            _ => {}
+        }
+
+        let mut contain = Containment::none();
+        let mut seen_any = false;
+        for keyword in value.word_iter() {
+            match keyword {
+                "layout" if !contain.layout => contain.layout = true,
+                "paint" if !contain.paint => contain.paint = true,
+                // FIXME: We don't implement size or style containment yet, but we shouldn't
+                // reject declarations that use them.
+                "size" | "style" => {}
+                _ => return None,
+            }
+            seen_any = true;
+        }
+        if seen_any {
+            Some(contain)
+        } else {
+            None
+        }
+    }
+
+    /// Finds the value of `contain` in the text of a `style` attribute.
+    ///
+    /// FIXME: libcss has no `contain` property, so rust-css drops it from style sheets. Until
+    /// rust-css parses and cascades it, `contain` only works in inline styles.
+    pub fn from_style_attribute(style: &str) -> Option<DeclaredValue<Containment>> {
+        declared_value(style, "contain", |value| Containment::parse(value))
+    }
+
+    /// The containment implied by a value of `content-visibility` while the contents are being
+    /// skipped.
+    pub fn for_skipped_contents() -> Containment {
+        Containment {
+            layout: true,
+            paint: true,
+        }
+    }
+}
+
+/// The computed value of `content-visibility`.
+#[deriving(Clone, Eq)]
+pub enum ContentVisibility {
+    /// The contents are laid out and painted as usual.
+    ContentVisible,
+    /// The contents are skipped while the element is far away from the viewport.
+    ContentAuto,
+    /// The contents are always skipped.
+    ContentHidden,
+}
+
+impl ContentVisibility {
+    /// Parses a value of the `content-visibility` property. Returns `None` for invalid values.
+    pub fn parse(value: &str) -> Option<ContentVisibility> {
+        match value.trim().to_ascii_lower().as_slice() {
+            "visible" => Some(ContentVisible),
+            "auto" => Some(ContentAuto),
+            "hidden" => Some(ContentHidden),
+            _ => None,
+        }
+    }
+
+    /// Finds the value of `content-visibility` in the text of a `style` attribute.
+    ///
+    /// FIXME: Like `contain`, this is only honoured in inline styles for now.
+    pub fn from_style_attribute(style: &str) -> Option<DeclaredValue<ContentVisibility>> {
+        declared_value(style, "content-visibility", |value| ContentVisibility::parse(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css::style_attribute::{Inherit, Initial, SpecifiedValue};
+
+    #[test]
+    fn parse_keywords() {
+        assert!(Containment::parse("none") == Some(Containment::none()));
+        assert!(Containment::parse("layout") == Some(Containment { layout: true, paint: false }));
+        assert!(Containment::parse("paint") == Some(Containment { layout: false, paint: true }));
+        assert!(Containment::parse(" Paint  layout ") ==
+                Some(Containment { layout: true, paint: true }));
+        assert!(Containment::parse("strict") == Some(Containment { layout: true, paint: true }));
+        assert!(Containment::parse("content") == Some(Containment { layout: true, paint: true }));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Containment::parse("").is_none());
+        assert!(Containment::parse("layout layout").is_none());
+        assert!(Containment::parse("none layout").is_none());
+        assert!(Containment::parse("bogus").is_none());
+    }
+
+    #[test]
+    fn style_attribute() {
+        let contain = Containment::from_style_attribute("color: red; contain: paint");
+        assert!(contain == Some(SpecifiedValue(Containment { layout: false, paint: true })));
+
+        let contain = Containment::from_style_attribute("contain: paint; CONTAIN: bogus");
+        assert!(contain == Some(SpecifiedValue(Containment { layout: false, paint: true })));
+
+        let contain = Containment::from_style_attribute("contain: paint; contain: inherit");
+        assert!(contain == Some(Inherit));
+
+        assert!(Containment::from_style_attribute("width: 10px").is_none());
+    }
+
+    #[test]
+    fn content_visibility() {
+        assert!(ContentVisibility::parse(" Auto ") == Some(ContentAuto));
+        assert!(ContentVisibility::parse("hidden") == Some(ContentHidden));
+        assert!(ContentVisibility::parse("collapse").is_none());
+
+        let style = "content-visibility: auto; content-visibility: bogus";
+        assert!(ContentVisibility::from_style_attribute(style) ==
+                Some(SpecifiedValue(ContentAuto)));
+        let style = "content-visibility: auto; content-visibility: Initial";
+        assert!(ContentVisibility::from_style_attribute(style) == Some(Initial));
+        assert!(ContentVisibility::from_style_attribute("contain: paint").is_none());
+    }
+}
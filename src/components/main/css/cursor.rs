@@ -0,0 +1,67 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The CSS `cursor` property.
+
+use css::style_attribute::{DeclaredValue, declared_value};
+
+use servo_msg::compositor_msg::{Cursor, CrosshairCursor, DefaultCursor, HelpCursor, MoveCursor};
+use servo_msg::compositor_msg::{NotAllowedCursor, PointerCursor, TextCursor, WaitCursor};
+use std::ascii::StrAsciiExt;
+
+/// The specified value of `cursor`.
+#[deriving(Clone, Eq)]
+pub enum CursorValue {
+    /// The user agent picks a cursor based on what's under the mouse.
+    AutoCursor,
+    SpecifiedCursor(Cursor),
+}
+
+impl CursorValue {
+    /// Parses a value of the `cursor` property. Returns `None` for invalid values.
+    pub fn parse(value: &str) -> Option<CursorValue> {
+        let cursor = match value.trim().to_ascii_lower().as_slice() {
+            "auto" => return Some(AutoCursor),
+            "default" => DefaultCursor,
+            "pointer" => PointerCursor,
+            "text" => TextCursor,
+            "crosshair" => CrosshairCursor,
+            "move" => MoveCursor,
+            "wait" | "progress" => WaitCursor,
+            "help" => HelpCursor,
+            "not-allowed" | "no-drop" => NotAllowedCursor,
+            _ => return None,
+        };
+        Some(SpecifiedCursor(cursor))
+    }
+
+    /// Finds the value of `cursor` declared in the text of a `style` attribute, if any.
+    pub fn from_style_attribute(style: &str) -> Option<DeclaredValue<CursorValue>> {
+        declared_value(style, "cursor", |value| CursorValue::parse(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css::style_attribute::{Initial, SpecifiedValue};
+    use servo_msg::compositor_msg::{PointerCursor, WaitCursor};
+
+    #[test]
+    fn parse_keywords() {
+        assert!(CursorValue::parse(" Pointer ") == Some(SpecifiedCursor(PointerCursor)));
+        assert!(CursorValue::parse("progress") == Some(SpecifiedCursor(WaitCursor)));
+        assert!(CursorValue::parse("auto") == Some(AutoCursor));
+        assert!(CursorValue::parse("url(hand.png), pointer").is_none());
+    }
+
+    #[test]
+    fn style_attribute() {
+        assert!(CursorValue::from_style_attribute("cursor: wait; cursor: bogus") ==
+                Some(SpecifiedValue(SpecifiedCursor(WaitCursor))));
+        assert!(CursorValue::from_style_attribute("cursor: wait; cursor: initial") ==
+                Some(Initial));
+        assert!(CursorValue::from_style_attribute("color: red").is_none());
+    }
+}
@@ -0,0 +1,374 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Lengths in units rust-css doesn't know about: `rem`, the viewport units and `calc()`.
+
+use css::style_attribute::{DeclaredValue, declared_value};
+
+use geom::size::Size2D;
+use gfx::geometry::Au;
+use gfx::geometry;
+use std::ascii::StrAsciiExt;
+use std::float;
+
+/// A specified length or percentage. It's kept as the amount of each relative unit in it, so
+/// that `calc()` expressions that mix units can be resolved once the units are known.
+#[deriving(Clone, Eq)]
+pub struct Length {
+    /// The absolute part, in CSS pixels.
+    px: float,
+    em: float,
+    rem: float,
+    vw: float,
+    vh: float,
+    vmin: float,
+    vmax: float,
+    /// Percent of whatever the property resolves percentages against.
+    percentage: float,
+}
+
+/// What relative lengths are resolved against.
+pub struct LengthContext {
+    /// The font size of the element the length is on.
+    font_size: Au,
+    /// The font size of the root element.
+    root_font_size: Au,
+    viewport_size: Size2D<Au>,
+}
+
+impl Length {
+    pub fn zero() -> Length {
+        Length {
+            px: 0.0,
+            em: 0.0,
+            rem: 0.0,
+            vw: 0.0,
+            vh: 0.0,
+            vmin: 0.0,
+            vmax: 0.0,
+            percentage: 0.0,
+        }
+    }
+
+    /// Parses a length, a percentage or a `calc()` expression that comes to one. Returns `None`
+    /// for invalid values.
+    pub fn parse(value: &str) -> Option<Length> {
+        let value = value.trim().to_ascii_lower();
+        if value.starts_with("calc(") {
+            let mut parser = CalcParser {
+                text: value.clone(),
+                position: 0,
+            };
+            return match parser.parse_term() {
+                Some(CalcLength(length)) if parser.at_end() => Some(length),
+                _ => None,
+            }
+        }
+        if value.as_slice() == "0" {
+            return Some(Length::zero());
+        }
+        match parse_dimension(value.as_slice()) {
+            Some((number, unit)) => Length::from_unit(number, unit),
+            None => None,
+        }
+    }
+
+    /// Finds the length declared for the property `name` in the text of a `style` attribute, if
+    /// any.
+    pub fn from_style_attribute(style: &str, name: &str) -> Option<DeclaredValue<Length>> {
+        declared_value(style, name, |value| Length::parse(value))
+    }
+
+    /// Resolves this length against `context`, with percentages of `percentage_basis`.
+    pub fn resolve(&self, context: &LengthContext, percentage_basis: Au) -> Au {
+        let viewport = context.viewport_size;
+        let viewport_min = geometry::min(viewport.width, viewport.height);
+        let viewport_max = geometry::max(viewport.width, viewport.height);
+        Au::from_frac_px(self.px) +
+            context.font_size.scale_by(self.em) +
+            context.root_font_size.scale_by(self.rem) +
+            viewport.width.scale_by(self.vw / 100.0) +
+            viewport.height.scale_by(self.vh / 100.0) +
+            viewport_min.scale_by(self.vmin / 100.0) +
+            viewport_max.scale_by(self.vmax / 100.0) +
+            percentage_basis.scale_by(self.percentage / 100.0)
+    }
+
+    fn from_unit(number: float, unit: &str) -> Option<Length> {
+        let mut length = Length::zero();
+        match unit {
+            "px" => length.px = number,
+            "pt" => length.px = number * 96.0 / 72.0,
+            "pc" => length.px = number * 16.0,
+            "in" => length.px = number * 96.0,
+            "cm" => length.px = number * 96.0 / 2.54,
+            "mm" => length.px = number * 96.0 / 25.4,
+            "em" => length.em = number,
+            "rem" => length.rem = number,
+            "vw" => length.vw = number,
+            "vh" => length.vh = number,
+            "vmin" => length.vmin = number,
+            "vmax" => length.vmax = number,
+            "%" => length.percentage = number,
+            _ => return None,
+        }
+        Some(length)
+    }
+
+    fn plus(&self, other: &Length) -> Length {
+        Length {
+            px: self.px + other.px,
+            em: self.em + other.em,
+            rem: self.rem + other.rem,
+            vw: self.vw + other.vw,
+            vh: self.vh + other.vh,
+            vmin: self.vmin + other.vmin,
+            vmax: self.vmax + other.vmax,
+            percentage: self.percentage + other.percentage,
+        }
+    }
+
+    fn scale_by(&self, factor: float) -> Length {
+        Length {
+            px: self.px * factor,
+            em: self.em * factor,
+            rem: self.rem * factor,
+            vw: self.vw * factor,
+            vh: self.vh * factor,
+            vmin: self.vmin * factor,
+            vmax: self.vmax * factor,
+            percentage: self.percentage * factor,
+        }
+    }
+}
+
+/// Splits a dimension like `-1.5em` into its number and its unit, which may be empty.
+fn parse_dimension<'a>(text: &'a str) -> Option<(float, &'a str)> {
+    let number_end = text.find(|c: char| !(c.is_digit() || c == '.' || c == '-' || c == '+'))
+                         .unwrap_or(text.len());
+    if number_end == 0 {
+        return None
+    }
+    match float::from_str(text.slice_to(number_end)) {
+        Some(number) => Some((number, text.slice_from(number_end))),
+        None => None,
+    }
+}
+
+/// A value inside a `calc()` expression: a length, or a plain number to multiply or divide one
+/// by.
+enum CalcValue {
+    CalcNumber(float),
+    CalcLength(Length),
+}
+
+/// Evaluates `calc()` expressions, which are sums of products of lengths and numbers, as it
+/// parses them.
+struct CalcParser {
+    text: ~str,
+    position: uint,
+}
+
+impl CalcParser {
+    fn at_end(&self) -> bool {
+        self.position == self.text.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        if self.at_end() {
+            None
+        } else {
+            Some(self.text.char_at(self.position))
+        }
+    }
+
+    /// Skips whitespace, and returns whether there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().map_default(false, |c| c.is_whitespace()) {
+            self.position += 1;
+        }
+        self.position > start
+    }
+
+    /// Parses a sum or difference of products. `+` and `-` need whitespace on either side, so
+    /// that they can't be mistaken for signs.
+    fn parse_sum(&mut self) -> Option<CalcValue> {
+        let mut sum = match self.parse_product() {
+            Some(value) => value,
+            None => return None,
+        };
+        loop {
+            let start = self.position;
+            let spaced = self.skip_whitespace();
+            let sign = match self.peek() {
+                Some('+') if spaced => 1.0,
+                Some('-') if spaced => -1.0,
+                _ => {
+                    self.position = start;
+                    return Some(sum)
+                }
+            };
+            self.position += 1;
+            if !self.skip_whitespace() {
+                return None
+            }
+            let term = match self.parse_product() {
+                Some(term) => term,
+                None => return None,
+            };
+            sum = match (sum, term) {
+                (CalcNumber(a), CalcNumber(b)) => CalcNumber(a + sign * b),
+                (CalcLength(a), CalcLength(b)) => CalcLength(a.plus(&b.scale_by(sign))),
+                _ => return None,
+            };
+        }
+    }
+
+    /// Parses a product or quotient of terms. At least one side of each `*` has to be a number,
+    /// and the right side of each `/`.
+    fn parse_product(&mut self) -> Option<CalcValue> {
+        let mut product = match self.parse_term() {
+            Some(value) => value,
+            None => return None,
+        };
+        loop {
+            let start = self.position;
+            self.skip_whitespace();
+            let divide = match self.peek() {
+                Some('*') => false,
+                Some('/') => true,
+                _ => {
+                    self.position = start;
+                    return Some(product)
+                }
+            };
+            self.position += 1;
+            self.skip_whitespace();
+            let factor = match self.parse_term() {
+                Some(factor) => factor,
+                None => return None,
+            };
+            product = match (product, factor, divide) {
+                (_, CalcNumber(b), true) if b == 0.0 => return None,
+                (CalcNumber(a), CalcNumber(b), true) => CalcNumber(a / b),
+                (CalcLength(a), CalcNumber(b), true) => CalcLength(a.scale_by(1.0 / b)),
+                (CalcNumber(a), CalcNumber(b), false) => CalcNumber(a * b),
+                (CalcLength(a), CalcNumber(b), false) |
+                (CalcNumber(b), CalcLength(a), false) => CalcLength(a.scale_by(b)),
+                _ => return None,
+            };
+        }
+    }
+
+    /// Parses a number, a length, a percentage, or a parenthesized or nested `calc()`
+    /// expression.
+    fn parse_term(&mut self) -> Option<CalcValue> {
+        let open = if self.text.slice_from(self.position).starts_with("(") {
+            1
+        } else if self.text.slice_from(self.position).starts_with("calc(") {
+            5
+        } else {
+            0
+        };
+        if open > 0 {
+            self.position += open;
+            self.skip_whitespace();
+            let value = self.parse_sum();
+            self.skip_whitespace();
+            if self.peek() != Some(')') {
+                return None
+            }
+            self.position += 1;
+            return value
+        }
+
+        let (value, end) = {
+            let rest = self.text.slice_from(self.position);
+            let end = rest.find(|c: char| c.is_whitespace() || "()*/".contains_char(c))
+                          .unwrap_or(rest.len());
+            let value = match parse_dimension(rest.slice_to(end)) {
+                Some((number, "")) => Some(CalcNumber(number)),
+                Some((number, unit)) => {
+                    Length::from_unit(number, unit).map_move(|length| CalcLength(length))
+                }
+                None => None,
+            };
+            (value, end)
+        };
+        self.position += end;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geom::size::Size2D;
+    use gfx::geometry::Au;
+
+    fn px(px: float) -> Length {
+        Length {
+            px: px,
+            .. Length::zero()
+        }
+    }
+
+    fn context() -> LengthContext {
+        LengthContext {
+            font_size: Au::from_px(20),
+            root_font_size: Au::from_px(16),
+            viewport_size: Size2D(Au::from_px(800), Au::from_px(600)),
+        }
+    }
+
+    #[test]
+    fn parse_units() {
+        assert!(Length::parse("0") == Some(Length::zero()));
+        assert!(Length::parse("-10px") == Some(px(-10.0)));
+        assert!(Length::parse("1in") == Some(px(96.0)));
+        assert!(Length::parse(" 2EM ") == Some(Length { em: 2.0, .. Length::zero() }));
+        assert!(Length::parse("1.5rem") == Some(Length { rem: 1.5, .. Length::zero() }));
+        assert!(Length::parse("50vmin") == Some(Length { vmin: 50.0, .. Length::zero() }));
+        assert!(Length::parse("12.5%") == Some(Length { percentage: 12.5, .. Length::zero() }));
+        assert!(Length::parse("10").is_none());
+        assert!(Length::parse("px").is_none());
+        assert!(Length::parse("auto").is_none());
+    }
+
+    #[test]
+    fn resolve_units() {
+        let context = context();
+        let basis = Au::from_px(400);
+        assert!(Length::parse("2em").unwrap().resolve(&context, basis) == Au::from_px(40));
+        assert!(Length::parse("2rem").unwrap().resolve(&context, basis) == Au::from_px(32));
+        assert!(Length::parse("10vw").unwrap().resolve(&context, basis) == Au::from_px(80));
+        assert!(Length::parse("10vh").unwrap().resolve(&context, basis) == Au::from_px(60));
+        assert!(Length::parse("10vmin").unwrap().resolve(&context, basis) == Au::from_px(60));
+        assert!(Length::parse("10vmax").unwrap().resolve(&context, basis) == Au::from_px(80));
+        assert!(Length::parse("10%").unwrap().resolve(&context, basis) == Au::from_px(40));
+    }
+
+    #[test]
+    fn calc() {
+        let context = context();
+        let basis = Au::from_px(400);
+        let resolve = |value: &str| Length::parse(value).map(|l| l.resolve(&context, basis));
+        assert!(resolve("calc(50% - 10px)") == Some(Au::from_px(190)));
+        assert!(resolve("calc(2 * (1rem + 2px))") == Some(Au::from_px(36)));
+        assert!(resolve("calc(100vw / 4 + calc(1em * 2))") == Some(Au::from_px(240)));
+        assert!(resolve("CALC( 10px*3 )") == Some(Au::from_px(30)));
+    }
+
+    #[test]
+    fn invalid_calc() {
+        assert!(Length::parse("calc(10px -5px)").is_none());
+        assert!(Length::parse("calc(10px + 5)").is_none());
+        assert!(Length::parse("calc(10px * 5px)").is_none());
+        assert!(Length::parse("calc(10px / 0)").is_none());
+        assert!(Length::parse("calc(2 * 3)").is_none());
+        assert!(Length::parse("calc(10px").is_none());
+        assert!(Length::parse("calc(10px) 5px").is_none());
+    }
+}
@@ -6,15 +6,20 @@
 
 use css::node_util::NodeUtil;
 use css::select_handler::NodeSelectHandler;
+use layout::aux::LayoutAuxMethods;
 use layout::incremental;
+use layout::wrapper::LayoutNodeCast;
 
+use geom::rect::Rect;
+use gfx::geometry::Au;
 use script::dom::node::{AbstractNode, LayoutView};
 use newcss::complete::CompleteSelectResults;
 use newcss::select::{SelectCtx, SelectResults};
 use servo_util::tree::TreeNodeRef;
 
 pub trait MatchMethods {
-    fn restyle_subtree(&self, select_ctx: &SelectCtx);
+    fn restyle_subtree(&self, select_ctx: &SelectCtx, viewport: &Rect<Au>, restyle: bool,
+                       restyle_root: Option<AbstractNode<LayoutView>>);
 }
 
 impl MatchMethods for AbstractNode<LayoutView> {
@@ -24,20 +29,46 @@ impl MatchMethods for AbstractNode<LayoutView> {
      * This is, importantly, the function that updates the layout data for
      * the node (the reader-auxiliary box in the COW model) with the
      * computed style.
+     *
+     * This also decides which subtrees `content-visibility` skips this time around; their
+     * contents aren't matched until they stop being skipped. If `restyle` is false, only the
+     * contents of subtrees that were skipped and no longer are get matched, along with the
+     * subtree at `restyle_root`, if it's found.
      */
-    fn restyle_subtree(&self, select_ctx: &SelectCtx) {
+    fn restyle_subtree(&self, select_ctx: &SelectCtx, viewport: &Rect<Au>, restyle: bool,
+                       restyle_root: Option<AbstractNode<LayoutView>>) {
+        let restyle = restyle || restyle_root == Some(*self);
+
         // Only elements have styles
-        if self.is_element() {
-            do self.with_imm_element |elem| {
-                let inline_style = match elem.style_attribute {
+        match self.as_element() {
+            Some(elem) if restyle => {
+                // Layout parses the `style` attribute of the snapshot itself, since rust-css style
+                // sheets can't be copied from the element's.
+                let style_attribute = self.layout_data().style_attribute;
+                let inline_style = match style_attribute {
                     None => None,
-                    Some(ref sheet) => Some(sheet),
+                    Some(ref sheet) => Some(&**sheet),
                 };
-                let select_handler = NodeSelectHandler { node: *self };
+                let select_handler = NodeSelectHandler { node: *self, visited: false };
                 let incomplete_results = select_ctx.select_style(self, inline_style, &select_handler);
                 // Combine this node's results with its parent's to resolve all inherited values
                 let complete_results = compose_results(*self, incomplete_results);
 
+                // Inside a visited link, match again with `:visited` matching it, for colors.
+                let parent = find_parent_element_node(*self);
+                let in_visited_link = elem.state.visited || match parent {
+                    Some(parent) => parent.get_visited_css_select_results().is_some(),
+                    None => false,
+                };
+                let visited_results = if in_visited_link {
+                    let select_handler = NodeSelectHandler { node: *self, visited: true };
+                    let results = select_ctx.select_style(self, inline_style, &select_handler);
+                    Some(compose_visited_results(*self, results))
+                } else {
+                    None
+                };
+                self.set_visited_css_select_results(visited_results);
+
                 // If there was an existing style, compute the damage that
                 // incremental layout will need to fix.
                 if self.have_css_select_results() {
@@ -45,11 +76,21 @@ impl MatchMethods for AbstractNode<LayoutView> {
                     self.set_restyle_damage(damage);
                 }
                 self.set_css_select_results(complete_results);
-            };
+            }
+            _ => {}
+        }
+
+        let layout_data = self.layout_data();
+        layout_data.skip_contents = self.should_skip_contents(viewport);
+        if layout_data.skip_contents {
+            layout_data.unstyled_contents = layout_data.unstyled_contents || restyle;
+            return;
         }
 
+        let restyle_kids = restyle || layout_data.unstyled_contents;
+        layout_data.unstyled_contents = false;
         for kid in self.children() {
-            kid.restyle_subtree(select_ctx); 
+            kid.restyle_subtree(select_ctx, viewport, restyle_kids, restyle_root);
         }
     }
 }
@@ -65,6 +106,21 @@ fn compose_results(node: AbstractNode<LayoutView>, results: SelectResults)
     }    
 }
 
+/// Like `compose_results`, but inherits from the visited style of the parent, if it has one.
+fn compose_visited_results(node: AbstractNode<LayoutView>, results: SelectResults)
+                           -> CompleteSelectResults {
+    match find_parent_element_node(node) {
+        None => CompleteSelectResults::new_root(results),
+        Some(parent_node) => {
+            let parent_results = match parent_node.get_visited_css_select_results() {
+                Some(parent_results) => parent_results,
+                None => parent_node.get_css_select_results(),
+            };
+            CompleteSelectResults::new_from_parent(parent_results, results)
+        }
+    }
+}
+
 fn find_parent_element_node(node: AbstractNode<LayoutView>) -> Option<AbstractNode<LayoutView>> {
     match node.parent_node() {
         Some(parent) if parent.is_element() => Some(parent),
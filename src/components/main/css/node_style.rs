@@ -4,7 +4,16 @@
 
 // Style retrieval from DOM elements.
 
+use css::color;
+use css::color::SpecifiedColor;
+use css::contain::{Containment, ContentVisibility, ContentVisible};
+use css::cursor::CursorValue;
+use css::length::Length;
+use css::text::Direction;
 use css::node_util::NodeUtil;
+use css::overflow::{Overflow, OverflowVisible};
+use css::style_attribute::{DeclaredValue, Inherit, Initial, SpecifiedValue};
+use layout::aux::LayoutAuxMethods;
 use layout::incremental::RestyleDamage;
 
 use newcss::complete::CompleteStyle;
@@ -13,9 +22,25 @@ use script::dom::node::{AbstractNode, LayoutView};
 /// Node mixin providing `style` method that returns a `NodeStyle`
 pub trait StyledNode {
     fn style(&self) -> CompleteStyle;
+    fn color_style(&self) -> CompleteStyle;
     fn restyle_damage(&self) -> RestyleDamage;
+    fn contain(&self) -> Containment;
+    fn content_visibility(&self) -> ContentVisibility;
+    fn cursor(&self) -> Option<DeclaredValue<CursorValue>>;
+    fn inline_color(&self, name: &str) -> Option<DeclaredValue<SpecifiedColor>>;
+    fn inline_length(&self, name: &str) -> Option<DeclaredValue<Length>>;
+    fn direction(&self) -> Option<DeclaredValue<Direction>>;
+    fn overflow(&self) -> Overflow;
+    fn computed_values(&self) -> ~[(~str, ~str)];
 }
 
+// Describes the computed value of each of the given properties, named as in style sheets.
+macro_rules! describe_properties(
+    ($style:expr, [ $($name:expr => $getter:ident),* ]) => ({
+        ~[ $( ($name.to_owned(), fmt!("%?", $style.$getter())) ),* ]
+    })
+)
+
 impl StyledNode for AbstractNode<LayoutView> {
     fn style(&self) -> CompleteStyle {
         assert!(self.is_element()); // Only elements can have styles
@@ -23,7 +48,135 @@ impl StyledNode for AbstractNode<LayoutView> {
         results.computed_style()
     }
 
+    /// The style to take colors from: inside a visited link, the style it has as one, and
+    /// otherwise the normal style.
+    fn color_style(&self) -> CompleteStyle {
+        assert!(self.is_element());
+        match self.get_visited_css_select_results() {
+            Some(results) => results.computed_style(),
+            None => self.style(),
+        }
+    }
+
     fn restyle_damage(&self) -> RestyleDamage {
         self.get_restyle_damage()
     }
+
+    fn contain(&self) -> Containment {
+        let declared = do with_style_attribute(self) |style| {
+            Containment::from_style_attribute(style)
+        };
+        match declared {
+            Some(SpecifiedValue(contain)) => contain,
+            Some(Inherit) => {
+                match self.parent_node() {
+                    Some(parent) if parent.is_element() => parent.contain(),
+                    _ => Containment::none(),
+                }
+            }
+            Some(Initial) | None => Containment::none(),
+        }
+    }
+
+    fn content_visibility(&self) -> ContentVisibility {
+        let declared = do with_style_attribute(self) |style| {
+            ContentVisibility::from_style_attribute(style)
+        };
+        match declared {
+            Some(SpecifiedValue(visibility)) => visibility,
+            Some(Inherit) => {
+                match self.parent_node() {
+                    Some(parent) if parent.is_element() => parent.content_visibility(),
+                    _ => ContentVisible,
+                }
+            }
+            Some(Initial) | None => ContentVisible,
+        }
+    }
+
+    /// The `cursor` declared on this element, if any.
+    fn cursor(&self) -> Option<DeclaredValue<CursorValue>> {
+        with_style_attribute(self, |style| CursorValue::from_style_attribute(style))
+    }
+
+    /// Finds the value of a color property in the `style` attribute, for colors rust-css can't
+    /// parse.
+    fn inline_color(&self, name: &str) -> Option<DeclaredValue<SpecifiedColor>> {
+        with_style_attribute(self, |style| color::from_style_attribute(style, name))
+    }
+
+    /// Finds the value of a length property in the `style` attribute, for properties rust-css
+    /// doesn't know about and lengths it can't parse.
+    fn inline_length(&self, name: &str) -> Option<DeclaredValue<Length>> {
+        with_style_attribute(self, |style| Length::from_style_attribute(style, name))
+    }
+
+    /// The `direction` declared on this element, if any.
+    fn direction(&self) -> Option<DeclaredValue<Direction>> {
+        with_style_attribute(self, |style| Direction::from_style_attribute(style))
+    }
+
+    fn overflow(&self) -> Overflow {
+        let declared = do with_style_attribute(self) |style| {
+            Overflow::from_style_attribute(style)
+        };
+        match declared {
+            Some(SpecifiedValue(overflow)) => overflow,
+            Some(Inherit) => {
+                match self.parent_node() {
+                    Some(parent) if parent.is_element() => parent.overflow(),
+                    _ => OverflowVisible,
+                }
+            }
+            Some(Initial) | None => OverflowVisible,
+        }
+    }
+
+    /// The names of the properties rust-css computes and their values for this element, as the
+    /// developer tools show them. The properties that are only read from the `style` attribute
+    /// aren't included.
+    fn computed_values(&self) -> ~[(~str, ~str)] {
+        let style = self.style();
+        let mut values = describe_properties!(style, [
+            "background-color" => background_color,
+            "border-top-color" => border_top_color,
+            "border-right-color" => border_right_color,
+            "border-bottom-color" => border_bottom_color,
+            "border-left-color" => border_left_color,
+            "border-top-width" => border_top_width,
+            "border-right-width" => border_right_width,
+            "border-bottom-width" => border_bottom_width,
+            "border-left-width" => border_left_width,
+            "color" => color,
+            "float" => float,
+            "font-family" => font_family,
+            "font-size" => font_size,
+            "font-style" => font_style,
+            "font-weight" => font_weight,
+            "height" => height,
+            "line-height" => line_height,
+            "margin-top" => margin_top,
+            "margin-right" => margin_right,
+            "margin-bottom" => margin_bottom,
+            "margin-left" => margin_left,
+            "padding-top" => padding_top,
+            "padding-right" => padding_right,
+            "padding-bottom" => padding_bottom,
+            "padding-left" => padding_left,
+            "position" => position,
+            "text-align" => text_align,
+            "text-decoration" => text_decoration,
+            "width" => width
+        ]);
+        values.push((~"display", fmt!("%?", style.display(self.is_root()))));
+        values
+    }
+}
+
+/// Calls `f` with the `style` attribute of `node`, which has to be an element, if it has one.
+fn with_style_attribute<R>(node: &AbstractNode<LayoutView>, f: &fn(&str) -> Option<R>)
+                           -> Option<R> {
+    do node.with_snapshot |element| {
+        element.get_attr("style").chain(|style| f(style))
+    }
 }
@@ -13,6 +13,8 @@ pub trait NodeUtil<'self> {
     fn get_css_select_results(self) -> &'self CompleteSelectResults;
     fn set_css_select_results(self, decl: CompleteSelectResults);
     fn have_css_select_results(self) -> bool;
+    fn get_visited_css_select_results(self) -> Option<&'self CompleteSelectResults>;
+    fn set_visited_css_select_results(self, decl: Option<CompleteSelectResults>);
 
     fn get_restyle_damage(self) -> RestyleDamage;
     fn set_restyle_damage(self, damage: RestyleDamage);
@@ -51,6 +53,27 @@ impl<'self> NodeUtil<'self> for AbstractNode<LayoutView> {
         self.layout_data().style = Some(decl);
     }
 
+    /// Returns the style results for the node as if the link it's in were visited, if it's in
+    /// a visited link.
+    fn get_visited_css_select_results(self) -> Option<&'self CompleteSelectResults> {
+        if !self.has_layout_data() {
+            return None;
+        }
+
+        match self.layout_data().visited_style {
+            None => None,
+            Some(ref style) => Some(unsafe { transmute(style) }),
+        }
+    }
+
+    fn set_visited_css_select_results(self, decl: Option<CompleteSelectResults>) {
+        if !self.has_layout_data() {
+            fail!(~"set_visited_css_select_results() called on a node without aux data!");
+        }
+
+        self.layout_data().visited_style = decl;
+    }
+
     /// Get the description of how to account for recent style changes.
     /// This is a simple bitfield and fine to copy by value.
     fn get_restyle_damage(self) -> RestyleDamage {
@@ -0,0 +1,82 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The CSS `overflow` property.
+//!
+//! FIXME: `overflow-x` and `overflow-y` aren't supported; `overflow` sets both axes.
+
+use css::style_attribute::{DeclaredValue, declared_value};
+
+use std::ascii::StrAsciiExt;
+
+/// The value of `overflow`: what a block does with content that doesn't fit in it.
+#[deriving(Clone, Eq)]
+pub enum Overflow {
+    /// The content spills out of the block.
+    OverflowVisible,
+    /// The content is clipped to the padding box, and can only be scrolled from script.
+    OverflowHidden,
+    /// The content is clipped to the padding box, and there are always scrollbars.
+    OverflowScroll,
+    /// The content is clipped to the padding box, and there are scrollbars when it doesn't fit.
+    OverflowAuto,
+}
+
+impl Overflow {
+    /// Parses a value of the `overflow` property. Returns `None` for invalid values.
+    pub fn parse(value: &str) -> Option<Overflow> {
+        match value.trim().to_ascii_lower().as_slice() {
+            "visible" => Some(OverflowVisible),
+            "hidden" => Some(OverflowHidden),
+            "scroll" => Some(OverflowScroll),
+            "auto" => Some(OverflowAuto),
+            _ => None,
+        }
+    }
+
+    /// Finds the value of `overflow` declared in the text of a `style` attribute, if any.
+    pub fn from_style_attribute(style: &str) -> Option<DeclaredValue<Overflow>> {
+        declared_value(style, "overflow", |value| Overflow::parse(value))
+    }
+
+    /// Whether the content is clipped to the padding box and can be scrolled.
+    pub fn scrolls(&self) -> bool {
+        *self != OverflowVisible
+    }
+
+    /// Whether there's a scrollbar along an axis the content is `content_length` long on, where
+    /// `view_length` of it can be seen at once.
+    pub fn has_scrollbar(&self, view_length: f32, content_length: f32) -> bool {
+        match *self {
+            OverflowScroll => true,
+            OverflowAuto => content_length > view_length,
+            OverflowVisible | OverflowHidden => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css::style_attribute::{Initial, SpecifiedValue};
+
+    #[test]
+    fn parse_overflow() {
+        assert!(Overflow::parse(" Auto ") == Some(OverflowAuto));
+        assert!(Overflow::parse("overlay") == None);
+        assert!(Overflow::from_style_attribute("overflow: scroll; overflow: bogus") ==
+                Some(SpecifiedValue(OverflowScroll)));
+        assert!(Overflow::from_style_attribute("overflow: hidden; overflow: initial") ==
+                Some(Initial));
+    }
+
+    #[test]
+    fn scrollbars() {
+        assert!(OverflowScroll.has_scrollbar(100.0, 50.0));
+        assert!(!OverflowAuto.has_scrollbar(100.0, 50.0));
+        assert!(OverflowAuto.has_scrollbar(100.0, 150.0));
+        assert!(!OverflowHidden.has_scrollbar(100.0, 150.0));
+        assert!(!OverflowVisible.scrolls() && OverflowHidden.scrolls());
+    }
+}
@@ -3,28 +3,78 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use extra::url::Url;
+use gfx::opts::Opts;
 use std::cell::Cell;
+use std::io;
 use std::FromStr;
 use newcss::stylesheet::Stylesheet;
 use newcss::select::SelectCtx;
-use newcss::types::OriginUA;
+use newcss::types::{OriginUA, OriginUser};
 use newcss::util::DataStream;
+use servo_util::media_queries::{Device, StylesheetSource};
+use servo_util::url::make_url;
 
-pub fn new_css_select_ctx() -> SelectCtx {
+/// The user agent style sheet, built into Servo.
+static USER_AGENT_STYLE: &'static str = include_str!("user-agent.css");
+
+/// The text of the style sheets named on the command line, which apply to every page.
+pub struct UserStylesheets {
+    /// A style sheet to use in place of the built-in user agent style sheet, if any.
+    user_agent: Option<(Url, ~str)>,
+    /// Style sheets at the user origin.
+    user: ~[(Url, ~str)],
+}
+
+impl UserStylesheets {
+    /// Reads the style sheets named in `opts`. Those that can't be read are left out.
+    pub fn load(opts: &Opts) -> UserStylesheets {
+        UserStylesheets {
+            user_agent: do opts.user_agent_stylesheet.chain_ref |path| {
+                read_stylesheet(path.as_slice())
+            },
+            user: do opts.user_stylesheets.iter().filter_map |path| {
+                read_stylesheet(path.as_slice())
+            }.collect(),
+        }
+    }
+}
+
+/// Makes a selection context with the user agent style sheet and the user style sheets in it.
+/// The cascade sorts declarations by origin and importance, then specificity, then the order the
+/// sheets were appended in and the order of the declarations in them.
+pub fn new_css_select_ctx(user_sheets: &UserStylesheets) -> SelectCtx {
     let mut ctx = SelectCtx::new();
-    ctx.append_sheet(html4_default_style(), OriginUA);
-    ctx.append_sheet(servo_default_style(), OriginUA);
+    let user_agent_sheet = match user_sheets.user_agent {
+        Some((ref url, ref text)) => Stylesheet::new(url.clone(), style_stream(*text)),
+        None => user_agent_style(),
+    };
+    ctx.append_sheet(user_agent_sheet, OriginUA);
+    for &(ref url, ref text) in user_sheets.user.iter() {
+        ctx.append_sheet(Stylesheet::new(url.clone(), style_stream(*text)), OriginUser);
+    }
     return ctx;
 }
 
-fn html4_default_style() -> Stylesheet {
-    Stylesheet::new(default_url("html4_style"),
-                    style_stream(html4_default_style_str()))
+/// Parses an author style sheet and those it imports, with the rules of those of their `@import`
+/// and `@media` rules that apply to `device`.
+pub fn author_stylesheets(source: &StylesheetSource, device: &Device) -> ~[Stylesheet] {
+    do source.sheets_for(device).move_iter().map |(url, text)| {
+        Stylesheet::new(url, style_stream(text.as_slice()))
+    }.collect()
+}
+
+fn user_agent_style() -> Stylesheet {
+    Stylesheet::new(default_url("user_agent_style"), style_stream(USER_AGENT_STYLE))
 }
 
-fn servo_default_style() -> Stylesheet {
-    Stylesheet::new(default_url("servo_style"),
-                    style_stream(servo_default_style_str()))
+fn read_stylesheet(path: &str) -> Option<(Url, ~str)> {
+    match io::read_whole_file_str(&Path(path)) {
+        Ok(text) => Some((make_url(path.to_owned(), None), text)),
+        Err(message) => {
+            error!("couldn't read the style sheet %s: %s", path, message);
+            None
+        }
+    }
 }
 
 fn default_url(name: &str) -> Url {
@@ -40,96 +90,3 @@ fn style_stream(style: &str) -> DataStream {
     };
     return d;
 }
-
-fn html4_default_style_str() -> ~str {
-~"
-html, address,
-blockquote,
-body, dd, div,
-dl, dt, fieldset, form,
-frame, frameset,
-h1, h2, h3, h4,
-h5, h6, noframes,
-ol, p, ul, center,
-    dir, hr, menu, pre   { display: block; unicode-bidi: embed }
-    li              { display: list-item }
-    head            { display: none }
-    table           { display: table }
-    tr              { display: table-row }
-    thead           { display: table-header-group }
-    tbody           { display: table-row-group }
-    tfoot           { display: table-footer-group }
-    col             { display: table-column }
-    colgroup        { display: table-column-group }
-    td, th          { display: table-cell }
-    caption         { display: table-caption }
-    th              { font-weight: bolder; text-align: center }
-    caption         { text-align: center }
-    body            { margin: 8px }
-    h1              { font-size: 2em; margin: .67em 0 }
-    h2              { font-size: 1.5em; margin: .75em 0 }
-    h3              { font-size: 1.17em; margin: .83em 0 }
-h4, p,
-blockquote, ul,
-fieldset, form,
-ol, dl, dir,
-    menu            { margin: 1.12em 0 }
-    h5              { font-size: .83em; margin: 1.5em 0 }
-    h6              { font-size: .75em; margin: 1.67em 0 }
-h1, h2, h3, h4,
-h5, h6, b,
-    strong          { font-weight: bolder }
-    blockquote      { margin-left: 40px; margin-right: 40px }
-i, cite, em,
-    var, address    { font-style: italic }
-pre, tt, code,
-    kbd, samp       { font-family: monospace }
-    pre             { white-space: pre }
-button, textarea,
-    input, select   { display: inline-block }
-    big             { font-size: 1.17em }
-    small, sub, sup { font-size: .83em }
-    sub             { vertical-align: sub }
-    sup             { vertical-align: super }
-    table           { border-spacing: 2px; }
-thead, tbody,
-    tfoot           { vertical-align: middle }
-    td, th, tr      { vertical-align: inherit }
-    s, strike, del  { text-decoration: line-through }
-    hr              { border: 1px inset }
-ol, ul, dir,
-    menu, dd        { margin-left: 40px }
-    ol              { list-style-type: decimal }
-ol ul, ul ol,
-    ul ul, ol ol    { margin-top: 0; margin-bottom: 0 }
-    u, ins          { text-decoration: underline }
-    br:before       { content: \"\\A\"; white-space: pre-line }
-center          { text-align: center }
-:link, :visited { text-decoration: underline }
-:focus          { outline: thin dotted invert }
-
-/* Begin bidirectionality settings (do not change) */
-BDO[DIR=\"ltr\"]  { direction: ltr; unicode-bidi: bidi-override }
-BDO[DIR=\"rtl\"]  { direction: rtl; unicode-bidi: bidi-override }
-
-*[DIR=\"ltr\"]    { direction: ltr; unicode-bidi: embed }
-*[DIR=\"rtl\"]    { direction: rtl; unicode-bidi: embed }
-
-@media print {
-h1            { page-break-before: always }
-  h1, h2, h3,
-h4, h5, h6    { page-break-after: avoid }
-ul, ol, dl    { page-break-before: avoid }
-}
-
-/* Servo additions */
-:link           { color: blue }
-script          { display: none }
-style           { display: none }
-"
-}
-
-fn servo_default_style_str() -> ~str {
-    // libcss want's this to default to 2px..
-    ~"* { border-width: 0px; }"
-}
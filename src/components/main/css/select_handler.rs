@@ -6,23 +6,68 @@
 /// Implementation of the callbacks that the CSS selector engine uses to query the DOM.
 ///
 
+use css::attribute_selectors::{AttrOperator, DashMatchOperator, EqualsOperator};
+use css::attribute_selectors::{IncludesOperator, PrefixOperator, SubstringOperator};
+use css::attribute_selectors::{SuffixOperator, has_case_insensitive_value, value_matches};
+
 use std::str::eq_slice;
 use newcss::select::SelectHandler;
+use layout::aux::LayoutAuxMethods;
+use layout::wrapper::LayoutNodeCast;
+use script::dom::element::Element;
 use script::dom::node::{AbstractNode, LayoutView};
 
 pub struct NodeSelectHandler {
     node: AbstractNode<LayoutView>,
+    /// Whether links to visited pages match `:visited` rather than `:link`. Styles matched this
+    /// way only supply colors.
+    visited: bool,
+}
+
+/// The element `node` is, which it has to be to be styled.
+fn element<'a>(node: &'a AbstractNode<LayoutView>) -> &'a Element {
+    match node.as_element() {
+        Some(element) => element,
+        None => fail!(~"attempting to style non-element node"),
+    }
 }
 
 fn with_node_name<R>(node: AbstractNode<LayoutView>, f: &fn(&str) -> R) -> R {
-    if !node.is_element() {
-        fail!(~"attempting to style non-element node");
+    f(element(&node).tag_name)
+}
+
+/// Calls `f` with the value of the attribute `name` of `node`, if it has one. Attribute names
+/// in HTML ignore case.
+fn with_node_attr<R>(node: AbstractNode<LayoutView>, name: &str, f: &fn(Option<&str>) -> R)
+                     -> R {
+    node.with_snapshot(|element| f(element.get_attr_ignoring_case(name)))
+}
+
+fn node_attr_matches(node: AbstractNode<LayoutView>, name: &str, operator: AttrOperator,
+                     pattern: &str) -> bool {
+    do with_node_attr(node, name) |value| {
+        match value {
+            Some(value) => {
+                value_matches(operator, value, pattern, has_case_insensitive_value(name))
+            }
+            None => false,
+        }
     }
-    do node.with_imm_element |element_n| {
-        f(element_n.tag_name)
+}
+
+fn have_same_name(a: AbstractNode<LayoutView>, b: AbstractNode<LayoutView>) -> bool {
+    do with_node_name(a) |a_name| {
+        with_node_name(b, |b_name| eq_slice(a_name, b_name))
     }
 }
 
+// FIXME: The methods from `node_count_siblings` on, for the structural, state, `:checked` and
+// attribute operator selectors, need a rust-css whose `SelectHandler` declares them and whose
+// libcss callbacks in `select.rs` ask the handler instead of answering "no match". That change
+// has to land in rust-css, and the submodule move to it, before this builds against it. None of
+// this parses `an+b`: libcss parses it with the rest of the selector (`parseNth` in
+// `src/parse/language.c`) and matches it against the counts `node_count_siblings` gives back
+// (`match_nth` in `src/select/select.c`).
 impl SelectHandler<AbstractNode<LayoutView>> for NodeSelectHandler {
     fn with_node_name<R>(&self, node: &AbstractNode<LayoutView>, f: &fn(&str) -> R) -> R {
         with_node_name(*node, f)
@@ -73,31 +118,112 @@ impl SelectHandler<AbstractNode<LayoutView>> for NodeSelectHandler {
         self.parent_node(node).is_none()
     }
 
-    fn node_is_link(&self, node: &AbstractNode<LayoutView>) -> bool {
-        if node.is_element() {
-            do node.with_imm_element |element| {
-                "a" == element.tag_name
+    /// Counts the element siblings before `node`, or after it with `after`, for the structural
+    /// pseudo-classes such as `:nth-child()`. Text and comments between them don't count. With
+    /// `same_name`, only elements with the same name as `node` do, as for `:nth-of-type()`.
+    fn node_count_siblings(&self, node: &AbstractNode<LayoutView>, same_name: bool, after: bool)
+                           -> uint {
+        let mut count = 0;
+        let mut sibling = *node;
+        loop {
+            let next = if after { sibling.next_sibling() } else { sibling.prev_sibling() };
+            match next {
+                None => break,
+                Some(next) => sibling = next,
+            }
+            if sibling.is_element() && (!same_name || have_same_name(sibling, *node)) {
+                count += 1;
             }
-        } else {
-            false
         }
+        count
     }
 
-    fn with_node_classes<R>(&self, node: &AbstractNode<LayoutView>, f: &fn(Option<&str>) -> R) -> R {
-        if !node.is_element() {
-            fail!(~"attempting to style non-element node");
+    /// Whether `node` has no children but comments, as `:empty` matches.
+    fn node_is_empty(&self, node: &AbstractNode<LayoutView>) -> bool {
+        for kid in node.children() {
+            if kid.is_element() {
+                return false;
+            }
+            if kid.as_text().map_default(false, |text| !text.parent.data.is_empty()) {
+                return false;
+            }
         }
-        do node.with_imm_element() |element_n| {
-            f(element_n.get_attr("class"))
+        true
+    }
+
+    fn node_is_link(&self, node: &AbstractNode<LayoutView>) -> bool {
+        do node.as_element().map_default(false) |element| {
+            "a" == element.tag_name && !(self.visited && element.state.visited)
         }
     }
 
-    fn node_has_class(&self, node: &AbstractNode<LayoutView>, class: &str) -> bool {
-        if !node.is_element() {
-            fail!(~"attempting to style non-element node");
+    fn node_is_visited(&self, node: &AbstractNode<LayoutView>) -> bool {
+        self.visited && node.as_element().map_default(false, |element| element.state.visited)
+    }
+
+    fn node_is_checked(&self, node: &AbstractNode<LayoutView>) -> bool {
+        node.is_element() && do node.with_snapshot |element| {
+            match element.control {
+                Some(ref control) if node.is_option_element() => control.checked,
+                Some(ref control) => control.is_checkable() && control.checked,
+                None => false,
+            }
         }
-        do node.with_imm_element |element_n| {
-            match element_n.get_attr("class") {
+    }
+
+    fn node_is_hover(&self, node: &AbstractNode<LayoutView>) -> bool {
+        node.as_element().map_default(false, |element| element.state.hover)
+    }
+
+    fn node_is_active(&self, node: &AbstractNode<LayoutView>) -> bool {
+        node.as_element().map_default(false, |element| element.state.active)
+    }
+
+    fn node_is_focus(&self, node: &AbstractNode<LayoutView>) -> bool {
+        node.as_element().map_default(false, |element| element.state.focus)
+    }
+
+    fn node_has_attribute(&self, node: &AbstractNode<LayoutView>, name: &str) -> bool {
+        with_node_attr(*node, name, |value| value.is_some())
+    }
+
+    fn node_has_attribute_equal(&self, node: &AbstractNode<LayoutView>, name: &str, value: &str)
+                                -> bool {
+        node_attr_matches(*node, name, EqualsOperator, value)
+    }
+
+    fn node_has_attribute_includes(&self, node: &AbstractNode<LayoutView>, name: &str,
+                                   value: &str) -> bool {
+        node_attr_matches(*node, name, IncludesOperator, value)
+    }
+
+    fn node_has_attribute_dashmatch(&self, node: &AbstractNode<LayoutView>, name: &str,
+                                    value: &str) -> bool {
+        node_attr_matches(*node, name, DashMatchOperator, value)
+    }
+
+    fn node_has_attribute_prefix(&self, node: &AbstractNode<LayoutView>, name: &str, value: &str)
+                                 -> bool {
+        node_attr_matches(*node, name, PrefixOperator, value)
+    }
+
+    fn node_has_attribute_suffix(&self, node: &AbstractNode<LayoutView>, name: &str, value: &str)
+                                 -> bool {
+        node_attr_matches(*node, name, SuffixOperator, value)
+    }
+
+    fn node_has_attribute_substring(&self, node: &AbstractNode<LayoutView>, name: &str,
+                                    value: &str) -> bool {
+        node_attr_matches(*node, name, SubstringOperator, value)
+    }
+
+    fn with_node_classes<R>(&self, node: &AbstractNode<LayoutView>, f: &fn(Option<&str>) -> R) -> R {
+        node.with_snapshot(|element| f(element.get_attr("class")))
+    }
+
+    fn node_has_class(&self, node: &AbstractNode<LayoutView>, class: &str) -> bool {
+        do node.with_snapshot |element| {
+            match element.get_attr("class") {
                 None => false,
                 Some(existing_classes) => {
                     let mut ret = false;
@@ -114,23 +240,10 @@ impl SelectHandler<AbstractNode<LayoutView>> for NodeSelectHandler {
     }
 
     fn with_node_id<R>(&self, node: &AbstractNode<LayoutView>, f: &fn(Option<&str>) -> R) -> R {
-        if !node.is_element() {
-            fail!(~"attempting to style non-element node");
-        }
-        do node.with_imm_element() |element_n| {
-            f(element_n.get_attr("id"))
-        }
+        node.with_snapshot(|element| f(element.get_attr("id")))
     }
 
     fn node_has_id(&self, node: &AbstractNode<LayoutView>, id: &str) -> bool {
-        if !node.is_element() {
-            fail!(~"attempting to style non-element node");
-        }
-        do node.with_imm_element |element_n| {
-            match element_n.get_attr("id") {
-                None => false,
-                Some(existing_id) => id == existing_id
-            }
-        }
+        node.with_snapshot(|element| element.get_attr("id") == Some(id))
     }
 }
@@ -0,0 +1,390 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Expansion of shorthand properties in `style` attributes into their longhands. Longhands a
+//! shorthand leaves out are reset to their initial values, as `initial`.
+
+use css::color::SpecifiedColor;
+use css::length::Length;
+
+use std::ascii::StrAsciiExt;
+use std::FromStr;
+
+static SIDES: [&'static str, ..4] = ["top", "right", "bottom", "left"];
+
+/// Expands a declaration of the shorthand `name` into the names and values of its longhands.
+/// Returns `None` if `name` isn't a shorthand or `value` isn't valid for it.
+pub fn expand(name: &str, value: &str) -> Option<~[(~str, ~str)]> {
+    let names = match longhands(name) {
+        Some(names) => names,
+        None => return None,
+    };
+    let tokens = split_tokens(value);
+    if tokens.len() == 1 && is_one_of(tokens[0].as_slice(), ["inherit", "initial"]) {
+        return Some(names.move_iter().map(|name| (name, tokens[0].clone())).collect());
+    }
+
+    let values = match name {
+        "margin" => expand_sides(tokens, |token| is_one_of(token, ["auto"]) || is_length(token)),
+        "padding" => expand_sides(tokens, |token| is_length(token)),
+        "border-color" => expand_sides(tokens, |token| is_color(token)),
+        "border-style" => expand_sides(tokens, |token| is_border_style(token)),
+        "border-width" => expand_sides(tokens, |token| is_border_width(token)),
+        "border" => {
+            // The same border goes on every side.
+            do expand_border(tokens).map_move |side| {
+                let mut values = ~[];
+                for _ in range(0, 4) {
+                    values.push_all(side.as_slice());
+                }
+                values
+            }
+        }
+        "background" => expand_background(tokens),
+        "font" => expand_font(tokens),
+        _ => expand_border(tokens),
+    };
+    values.map_move(|values| names.move_iter().zip(values.move_iter()).collect())
+}
+
+/// The longhands of the shorthand `name`, in the order `expand` gives them values in.
+fn longhands(name: &str) -> Option<~[~str]> {
+    let names = match name {
+        "margin" | "padding" => on_sides(|side| fmt!("%s-%s", name, side)),
+        "border-color" | "border-style" | "border-width" => {
+            let suffix = name.slice_from("border-".len());
+            on_sides(|side| fmt!("border-%s-%s", side, suffix))
+        }
+        "border" => {
+            let mut names = ~[];
+            for side in SIDES.iter() {
+                names.push_all_move(border_side_longhands(fmt!("border-%s", *side)));
+            }
+            names
+        }
+        "border-top" | "border-right" | "border-bottom" | "border-left" => {
+            border_side_longhands(name)
+        }
+        "background" => {
+            ~[~"background-color", ~"background-image", ~"background-repeat",
+              ~"background-attachment", ~"background-position"]
+        }
+        "font" => {
+            ~[~"font-style", ~"font-variant", ~"font-weight", ~"font-size", ~"line-height",
+              ~"font-family"]
+        }
+        _ => return None,
+    };
+    Some(names)
+}
+
+fn on_sides(format: &fn(&str) -> ~str) -> ~[~str] {
+    SIDES.iter().map(|side| format(*side)).collect()
+}
+
+fn border_side_longhands(side: &str) -> ~[~str] {
+    ~[fmt!("%s-width", side), fmt!("%s-style", side), fmt!("%s-color", side)]
+}
+
+/// Splits a value at whitespace outside parentheses, so that functions like `rgb(0, 0, 0)` stay
+/// whole.
+fn split_tokens(value: &str) -> ~[~str] {
+    let value = value.trim();
+    let mut tokens = ~[];
+    let mut token = ~"";
+    let mut depth = 0;
+    for c in value.iter() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ => {}
+        }
+        if c.is_whitespace() && depth == 0 {
+            if !token.is_empty() {
+                tokens.push(token);
+                token = ~"";
+            }
+        } else {
+            token.push_char(c);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Gives the four sides values from one to four tokens: the top, right, bottom and left sides,
+/// with a missing left side copying the right, a missing bottom the top, and a missing right
+/// the top.
+fn expand_sides(tokens: ~[~str], valid: &fn(&str) -> bool) -> Option<~[~str]> {
+    if tokens.len() < 1 || tokens.len() > 4 || !tokens.iter().all(|token| valid(token.as_slice())) {
+        return None
+    }
+    let top = tokens[0].clone();
+    let right = if tokens.len() > 1 { tokens[1].clone() } else { top.clone() };
+    let bottom = if tokens.len() > 2 { tokens[2].clone() } else { top.clone() };
+    let left = if tokens.len() > 3 { tokens[3].clone() } else { right.clone() };
+    Some(~[top, right, bottom, left])
+}
+
+/// Expands the value of `border` or one of its sides into a width, a style and a color, which
+/// can come in any order.
+fn expand_border(tokens: ~[~str]) -> Option<~[~str]> {
+    let mut width = None;
+    let mut style = None;
+    let mut color = None;
+    for token in tokens.move_iter() {
+        if width.is_none() && is_border_width(token.as_slice()) {
+            width = Some(token);
+        } else if style.is_none() && is_border_style(token.as_slice()) {
+            style = Some(token);
+        } else if color.is_none() && is_color(token.as_slice()) {
+            color = Some(token);
+        } else {
+            return None
+        }
+    }
+    Some(~[or_initial(width), or_initial(style), or_initial(color)])
+}
+
+/// Expands the value of `background` into a color, an image, a repetition, an attachment and a
+/// position, which can come in any order, though the position's tokens have to be together.
+fn expand_background(tokens: ~[~str]) -> Option<~[~str]> {
+    let mut color = None;
+    let mut image = None;
+    let mut repeat = None;
+    let mut attachment = None;
+    let mut position: ~[~str] = ~[];
+    let mut position_ended = false;
+    for token in tokens.move_iter() {
+        let is_position = is_length(token.as_slice()) ||
+            is_one_of(token.as_slice(), ["left", "center", "right", "top", "bottom"]);
+        if is_position && !position_ended && position.len() < 2 {
+            position.push(token);
+            loop;
+        }
+        position_ended = !position.is_empty();
+
+        let is_image = is_one_of(token.as_slice(), ["none"]) ||
+            token.to_ascii_lower().starts_with("url(");
+        if image.is_none() && is_image {
+            image = Some(token);
+        } else if repeat.is_none() && is_one_of(token.as_slice(), ["repeat", "repeat-x",
+                                                                    "repeat-y", "no-repeat"]) {
+            repeat = Some(token);
+        } else if attachment.is_none() && is_one_of(token.as_slice(), ["scroll", "fixed"]) {
+            attachment = Some(token);
+        } else if color.is_none() && is_color(token.as_slice()) {
+            color = Some(token);
+        } else {
+            return None
+        }
+    }
+    let position = if position.is_empty() { None } else { Some(position.connect(" ")) };
+    Some(~[or_initial(color), or_initial(image), or_initial(repeat), or_initial(attachment),
+           or_initial(position)])
+}
+
+/// Expands the value of `font`: up to three of a style, a variant and a weight in any order,
+/// then a size, optionally followed by `/` and a line height, then the families.
+fn expand_font(tokens: ~[~str]) -> Option<~[~str]> {
+    let mut style = None;
+    let mut variant = None;
+    let mut weight = None;
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = tokens[index].clone();
+        if is_one_of(token.as_slice(), ["normal"]) {
+            // `normal` is the initial value of all three, so it's given to the first not yet
+            // given.
+            if style.is_none() {
+                style = Some(token);
+            } else if variant.is_none() {
+                variant = Some(token);
+            } else if weight.is_none() {
+                weight = Some(token);
+            } else {
+                return None
+            }
+        } else if style.is_none() && is_one_of(token.as_slice(), ["italic", "oblique"]) {
+            style = Some(token);
+        } else if variant.is_none() && is_one_of(token.as_slice(), ["small-caps"]) {
+            variant = Some(token);
+        } else if weight.is_none() && is_font_weight(token.as_slice()) {
+            weight = Some(token);
+        } else {
+            break
+        }
+        index += 1;
+    }
+
+    // The size, which the line height may be stuck to with or without whitespace around the
+    // slash.
+    if index == tokens.len() {
+        return None
+    }
+    let mut size = tokens[index].clone();
+    index += 1;
+    let mut line_height = None;
+    match size.find('/') {
+        Some(slash) => {
+            let after = size.slice_from(slash + 1).to_owned();
+            size = size.slice_to(slash).to_owned();
+            if after.is_empty() {
+                if index == tokens.len() {
+                    return None
+                }
+                line_height = Some(tokens[index].clone());
+                index += 1;
+            } else {
+                line_height = Some(after);
+            }
+        }
+        None if index < tokens.len() && tokens[index].starts_with("/") => {
+            let after = tokens[index].slice_from(1).to_owned();
+            index += 1;
+            if after.is_empty() {
+                if index == tokens.len() {
+                    return None
+                }
+                line_height = Some(tokens[index].clone());
+                index += 1;
+            } else {
+                line_height = Some(after);
+            }
+        }
+        None => {}
+    }
+    if !is_font_size(size.as_slice()) {
+        return None
+    }
+    for line_height in line_height.iter() {
+        let line_height = line_height.as_slice();
+        if !is_one_of(line_height, ["normal"]) && !is_length(line_height) &&
+                !is_number(line_height) {
+            return None
+        }
+    }
+
+    // The families are required.
+    if index == tokens.len() {
+        return None
+    }
+    let family = tokens.slice_from(index).connect(" ");
+    Some(~[or_initial(style), or_initial(variant), or_initial(weight), size,
+           or_initial(line_height), family])
+}
+
+fn or_initial(value: Option<~str>) -> ~str {
+    value.unwrap_or_default(~"initial")
+}
+
+fn is_one_of(token: &str, keywords: &[&str]) -> bool {
+    keywords.iter().any(|keyword| token.eq_ignore_ascii_case(*keyword))
+}
+
+fn is_length(token: &str) -> bool {
+    Length::parse(token).is_some()
+}
+
+fn is_number(token: &str) -> bool {
+    let number: Option<float> = FromStr::from_str(token);
+    number.is_some()
+}
+
+fn is_color(token: &str) -> bool {
+    SpecifiedColor::parse(token).is_some()
+}
+
+fn is_border_width(token: &str) -> bool {
+    is_one_of(token, ["thin", "medium", "thick"]) || is_length(token)
+}
+
+fn is_border_style(token: &str) -> bool {
+    is_one_of(token, ["none", "hidden", "dotted", "dashed", "solid", "double", "groove", "ridge",
+                      "inset", "outset"])
+}
+
+fn is_font_weight(token: &str) -> bool {
+    is_one_of(token, ["bold", "bolder", "lighter", "100", "200", "300", "400", "500", "600",
+                      "700", "800", "900"])
+}
+
+fn is_font_size(token: &str) -> bool {
+    is_one_of(token, ["xx-small", "x-small", "small", "medium", "large", "x-large", "xx-large",
+                      "larger", "smaller"]) || is_length(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expanded(name: &str, value: &str) -> Option<~[~str]> {
+        do expand(name, value).map_move |longhands| {
+            longhands.move_iter().map(|(name, value)| fmt!("%s: %s", name, value)).collect()
+        }
+    }
+
+    #[test]
+    fn sides() {
+        assert_eq!(expanded("margin", "1px 2px"),
+                   Some(~[~"margin-top: 1px", ~"margin-right: 2px", ~"margin-bottom: 1px",
+                          ~"margin-left: 2px"]));
+        assert_eq!(expanded("padding", "1px 2px 3px"),
+                   Some(~[~"padding-top: 1px", ~"padding-right: 2px", ~"padding-bottom: 3px",
+                          ~"padding-left: 2px"]));
+        assert_eq!(expanded("border-color", "rgb(0, 0, 0) red BLUE green"),
+                   Some(~[~"border-top-color: rgb(0, 0, 0)", ~"border-right-color: red",
+                          ~"border-bottom-color: BLUE", ~"border-left-color: green"]));
+        assert!(expanded("margin", "auto").is_some());
+        assert!(expanded("padding", "auto").is_none());
+        assert!(expanded("margin", "1px 2px 3px 4px 5px").is_none());
+    }
+
+    #[test]
+    fn border() {
+        assert_eq!(expanded("border-left", "red 2px"),
+                   Some(~[~"border-left-width: 2px", ~"border-left-style: initial",
+                          ~"border-left-color: red"]));
+        let border = expanded("border", "solid hsla(0, 0%, 0%, 0.5)").unwrap();
+        assert_eq!(border.len(), 12);
+        assert_eq!(border[0], ~"border-top-width: initial");
+        assert_eq!(border[10], ~"border-left-style: solid");
+        assert_eq!(border[11], ~"border-left-color: hsla(0, 0%, 0%, 0.5)");
+        assert!(expanded("border", "solid dashed").is_none());
+    }
+
+    #[test]
+    fn background() {
+        assert_eq!(expanded("background", "url(a.png) no-repeat left top currentColor"),
+                   Some(~[~"background-color: currentColor", ~"background-image: url(a.png)",
+                          ~"background-repeat: no-repeat", ~"background-attachment: initial",
+                          ~"background-position: left top"]));
+        assert!(expanded("background", "red blue").is_none());
+    }
+
+    #[test]
+    fn font() {
+        assert_eq!(expanded("font", "italic bold 12px/1.5 Helvetica, sans-serif"),
+                   Some(~[~"font-style: italic", ~"font-variant: initial", ~"font-weight: bold",
+                          ~"font-size: 12px", ~"line-height: 1.5",
+                          ~"font-family: Helvetica, sans-serif"]));
+        assert_eq!(expanded("font", "2em / normal serif"),
+                   Some(~[~"font-style: initial", ~"font-variant: initial",
+                          ~"font-weight: initial", ~"font-size: 2em", ~"line-height: normal",
+                          ~"font-family: serif"]));
+        assert!(expanded("font", "bold 12px").is_none());
+        assert!(expanded("font", "serif").is_none());
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(expanded("padding", "inherit"),
+                   Some(~[~"padding-top: inherit", ~"padding-right: inherit",
+                          ~"padding-bottom: inherit", ~"padding-left: inherit"]));
+        assert!(expanded("padding", "inherit 1px").is_none());
+        assert!(expanded("width", "1px").is_none());
+    }
+}
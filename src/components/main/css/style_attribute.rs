@@ -0,0 +1,179 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Scanning of `style` attributes for properties rust-css doesn't know about.
+//!
+//! FIXME: This is a stopgap. Properties read this way are only honoured in inline styles, and
+//! it goes away as soon as rust-css parses them. Until then the same goes for everything parsed
+//! on top of it: the margin, padding, border, background and font shorthands, `rem`, viewport
+//! units and `calc()` in lengths, `currentColor`, `hsl()` and the other color syntaxes, and
+//! `inherit` and `initial` for the properties here. Style sheets are cascaded by libcss, which
+//! drops what it can't parse. Each has to move into rust-css (and libcss), and the submodule to
+//! that revision, for style sheets to get them.
+
+use css::shorthands;
+
+use std::ascii::StrAsciiExt;
+
+/// The value a `style` attribute declares for a property: a value of the property, or one of the
+/// keywords every property takes.
+#[deriving(Clone, Eq)]
+pub enum DeclaredValue<T> {
+    SpecifiedValue(T),
+    /// The parent element's computed value, whether or not the property is inherited.
+    Inherit,
+    /// The property's initial value, whether or not the property is inherited.
+    Initial,
+}
+
+/// Finds the winning declaration of the property `name` in the text of a `style` attribute,
+/// parsing values other than `inherit` and `initial` with `parse`, which returns `None` for
+/// invalid ones.
+pub fn declared_value<T>(style: &str, name: &str, parse: &fn(&str) -> Option<T>)
+                         -> Option<DeclaredValue<T>> {
+    let mut declared = None;
+    do each_declaration_of(style, name) |value| {
+        match value.trim().to_ascii_lower().as_slice() {
+            "inherit" => declared = Some(Inherit),
+            "initial" => declared = Some(Initial),
+            _ => {
+                for parsed in parse(value).move_iter() {
+                    declared = Some(SpecifiedValue(parsed));
+                }
+            }
+        }
+    }
+    declared
+}
+
+/// Calls `callback` with the value of each declaration of the property `name` in the text of a
+/// `style` attribute, in cascade order, so that the last valid one wins: the normal declarations
+/// in order, then the `!important` ones in order, without their `!important`. Shorthands that
+/// set `name` count as declarations of it, with the value they give it.
+pub fn each_declaration_of(style: &str, name: &str, callback: &fn(&str)) {
+    let mut important = ~[];
+    for declaration in style.split_iter(';') {
+        let mut parts = declaration.splitn_iter(':', 1);
+        let declared_name = match parts.next() {
+            Some(declared_name) => declared_name.trim().to_ascii_lower(),
+            None => loop,
+        };
+        let (value, is_important) = match parts.next() {
+            Some(value) => {
+                match strip_important(value) {
+                    Some(value) => (value, true),
+                    None => (value, false),
+                }
+            }
+            None => loop,
+        };
+        let value = if declared_name.as_slice() == name {
+            value.to_owned()
+        } else {
+            match longhand_value(declared_name.as_slice(), value, name) {
+                Some(value) => value,
+                None => loop,
+            }
+        };
+        if is_important {
+            important.push(value);
+        } else {
+            callback(value.as_slice());
+        }
+    }
+    for value in important.iter() {
+        callback(value.as_slice());
+    }
+}
+
+/// The value a declaration of the shorthand `shorthand` gives the longhand `name`, if it's a
+/// valid declaration of a shorthand of `name`.
+fn longhand_value(shorthand: &str, value: &str, name: &str) -> Option<~str> {
+    match shorthands::expand(shorthand, value) {
+        Some(longhands) => {
+            for (longhand, value) in longhands.move_iter() {
+                if longhand.as_slice() == name {
+                    return Some(value)
+                }
+            }
+            None
+        }
+        None => None,
+    }
+}
+
+/// If `value` ends in `!important`, returns the rest of it.
+fn strip_important<'a>(value: &'a str) -> Option<&'a str> {
+    let value = value.trim_right();
+    let bang = match value.rfind('!') {
+        Some(bang) => bang,
+        None => return None,
+    };
+    if value.slice_from(bang + 1).trim_left().eq_ignore_ascii_case("important") {
+        Some(value.slice_to(bang))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::FromStr;
+
+    fn declarations_of(style: &str, name: &str) -> ~[~str] {
+        let mut values = ~[];
+        do each_declaration_of(style, name) |value| {
+            values.push(value.trim().to_owned());
+        }
+        values
+    }
+
+    #[test]
+    fn source_order() {
+        assert_eq!(declarations_of("cursor: wait; color: red; CURSOR: help", "cursor"),
+                   ~[~"wait", ~"help"]);
+        assert_eq!(declarations_of("color: red", "cursor"), ~[]);
+    }
+
+    #[test]
+    fn shorthands() {
+        let style = "border-left-color: red; border: 1px solid";
+        assert_eq!(declarations_of(style, "border-left-color"), ~[~"red", ~"initial"]);
+        assert_eq!(declarations_of("margin: 1px 2px; margin-left: 3px", "margin-left"),
+                   ~[~"2px", ~"3px"]);
+        assert_eq!(declarations_of("background: red !important; background-color: blue",
+                                   "background-color"),
+                   ~[~"blue", ~"red"]);
+        assert_eq!(declarations_of("margin: 1px 2px 3px 4px 5px", "margin-left"), ~[]);
+    }
+
+    #[test]
+    fn important_declarations_come_last() {
+        assert_eq!(declarations_of("cursor: wait !important; cursor: help", "cursor"),
+                   ~[~"help", ~"wait"]);
+        assert_eq!(declarations_of("cursor: wait ! IMPORTANT; cursor: help!important", "cursor"),
+                   ~[~"wait", ~"help"]);
+    }
+
+    fn parse_px(value: &str) -> Option<int> {
+        let value = value.trim();
+        if value.ends_with("px") {
+            FromStr::from_str(value.slice_to(value.len() - 2))
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn keywords_every_property_takes() {
+        assert!(declared_value("width: 10px", "width", parse_px) == Some(SpecifiedValue(10)));
+        assert!(declared_value("width: 10px; width: INHERIT", "width", parse_px) == Some(Inherit));
+        assert!(declared_value("width: initial; width: bogus", "width", parse_px) ==
+                Some(Initial));
+        assert!(declared_value("width: inherit !important; width: 10px", "width", parse_px) ==
+                Some(Inherit));
+        assert!(declared_value("height: 10px", "width", parse_px).is_none());
+    }
+}
@@ -0,0 +1,48 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The CSS `direction` property.
+
+use css::style_attribute::{DeclaredValue, declared_value};
+
+use std::ascii::StrAsciiExt;
+
+/// The value of `direction`: which way inline content runs.
+#[deriving(Clone, Eq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl Direction {
+    /// Parses a value of the `direction` property. Returns `None` for invalid values.
+    pub fn parse(value: &str) -> Option<Direction> {
+        match value.trim().to_ascii_lower().as_slice() {
+            "ltr" => Some(LeftToRight),
+            "rtl" => Some(RightToLeft),
+            _ => None,
+        }
+    }
+
+    /// Finds the value of `direction` declared in the text of a `style` attribute, if any.
+    pub fn from_style_attribute(style: &str) -> Option<DeclaredValue<Direction>> {
+        declared_value(style, "direction", |value| Direction::parse(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use css::style_attribute::{Inherit, SpecifiedValue};
+
+    #[test]
+    fn parse_direction() {
+        assert!(Direction::parse("RTL") == Some(RightToLeft));
+        assert!(Direction::from_style_attribute("direction: ltr; direction: rtl") ==
+                Some(SpecifiedValue(RightToLeft)));
+        assert!(Direction::from_style_attribute("direction: rtl; direction: inherit") ==
+                Some(Inherit));
+        assert!(Direction::parse("backwards") == None);
+    }
+}
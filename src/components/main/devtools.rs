@@ -0,0 +1,256 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A server that lets developer tools in other processes inspect the pages of a running browser.
+//! It listens on a TCP port of the local machine. Tools send requests as JSON objects, one per
+//! line, and each is answered in turn with a JSON object on a line of its own:
+//!
+//! * `{"type": "listPipelines"}` gets `{"pipelines": [...]}`, the page shown in each tab and
+//!   those in its iframes, each with its `id`, the `tab` it's in, the `parent` whose iframe it's
+//!   in, if any, and its `url`.
+//! * `{"type": "getDocument", "pipeline": ID}` gets `{"document": NODE}`, where each node has an
+//!   `id`, a `nodeType`, a `name`, a `value` for text and comments, `attributes` and `children`.
+//! * `{"type": "getComputedStyle", "pipeline": ID, "node": NODE_ID}` gets `{"style": {...}}`,
+//!   with the computed values of the CSS properties of the element with that node id.
+//! * `{"type": "evaluate", "pipeline": ID, "script": SOURCE}` runs the script in the page and
+//!   gets `{"result": RESULT}`, what it evaluated to described as the console would.
+//!
+//! Requests that fail get `{"error": MESSAGE}`. Node ids hold until the document changes.
+//!
+//! FIXME: Anything that can connect to the port can run script in pages, which is why only the
+//! loopback interface is listened on.
+
+use servo_msg::constellation_msg::{ConstellationChan, DevtoolsMsg, GetPipelinesMsg, PipelineId};
+use servo_msg::constellation_msg::TabId;
+use servo_msg::devtools_msg::{DevtoolScriptControlMsg, EvaluateJSMsg, GetComputedStyleMsg};
+use servo_msg::devtools_msg::{GetDocumentMsg, NodeInfo, PipelineSummary};
+
+use std::cell::Cell;
+use std::comm;
+use std::comm::{Chan, Port};
+use std::rt::io::{Acceptor, Listener, Reader, Writer, io_error};
+use std::rt::io::net::ip::{Ipv4Addr, SocketAddr};
+use std::rt::io::net::tcp::{TcpListener, TcpStream};
+use std::str;
+use extra::json::{Json, List, Null, Number, Object, String};
+use extra::json;
+use extra::treemap::TreeMap;
+
+/// The longest request that is read, in bytes. Connections that send longer ones are closed.
+static MAX_REQUEST_LENGTH: uint = 1024 * 1024;
+
+/// Starts a task that serves the developer tools protocol on the given port of the loopback
+/// interface, asking `constellation_chan` about the pages.
+pub fn start(port: u16, constellation_chan: ConstellationChan) {
+    let constellation_chan = Cell::new(constellation_chan);
+    do spawn {
+        let constellation_chan = constellation_chan.take();
+        let address = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: port };
+        let acceptor = do io_error::cond.trap(|_| ()).inside {
+            TcpListener::bind(address).chain(|listener| listener.listen())
+        };
+        let mut acceptor = match acceptor {
+            Some(acceptor) => acceptor,
+            None => {
+                error!("devtools: can't listen on port %u", port as uint);
+                return
+            }
+        };
+        loop {
+            let stream = do io_error::cond.trap(|_| ()).inside {
+                acceptor.accept()
+            };
+            match stream {
+                Some(stream) => {
+                    let stream = Cell::new(stream);
+                    let constellation_chan = constellation_chan.clone();
+                    do spawn {
+                        serve(stream.take(), &constellation_chan);
+                    }
+                }
+                None => debug!("devtools: failed to accept a connection"),
+            }
+        }
+    }
+}
+
+/// Answers the requests that come in on `stream` until it's closed or errs.
+fn serve(mut stream: TcpStream, constellation_chan: &ConstellationChan) {
+    let mut received = ~[];
+    let mut buf = [0u8, ..4096];
+    loop {
+        loop {
+            let newline = match received.iter().position(|&byte| byte == '\n' as u8) {
+                Some(newline) => newline,
+                None => break,
+            };
+            let answer = answer(received.slice_to(newline), constellation_chan);
+            received = received.slice_from(newline + 1).to_owned();
+            let answer = answer.to_str() + "\n";
+            if write_all(&mut stream, answer.as_bytes()).is_err() {
+                return
+            }
+        }
+        if received.len() > MAX_REQUEST_LENGTH {
+            debug!("devtools: closing a connection whose request is too long");
+            return
+        }
+
+        let mut errored = false;
+        let read = do io_error::cond.trap(|_| errored = true).inside {
+            stream.read(buf)
+        };
+        match read {
+            Some(count) if !errored => received.push_all(buf.slice_to(count)),
+            _ => return,
+        }
+    }
+}
+
+/// Answers a request, or says why it failed.
+fn answer(request: &[u8], constellation_chan: &ConstellationChan) -> Json {
+    match handle_request(request, constellation_chan) {
+        Ok(answer) => answer,
+        Err(message) => object(~[(~"error", String(message))]),
+    }
+}
+
+fn handle_request(request: &[u8], constellation_chan: &ConstellationChan) -> Result<Json, ~str> {
+    if !str::is_utf8(request) {
+        return Err(~"requests must be UTF-8")
+    }
+    let request = match json::from_str(str::from_utf8(request)) {
+        Ok(json::Object(request)) => request,
+        _ => return Err(~"requests must be JSON objects"),
+    };
+    let kind = match request.find(&~"type") {
+        Some(&String(ref kind)) => kind.clone(),
+        _ => return Err(~"the request has no type"),
+    };
+
+    if kind == ~"listPipelines" {
+        let (port, chan) = comm::stream();
+        constellation_chan.send(GetPipelinesMsg(chan));
+        let pipelines = port.recv().iter().map(|pipeline| pipeline_json(pipeline)).collect();
+        return Ok(object(~[(~"pipelines", List(pipelines))]))
+    }
+
+    let pipeline_id = match index_field(request, "pipeline") {
+        Ok(id) => PipelineId(id),
+        Err(message) => return Err(message),
+    };
+    if kind == ~"getDocument" {
+        match ask_script(constellation_chan, pipeline_id, |chan| GetDocumentMsg(chan)) {
+            Ok(Some(document)) => Ok(object(~[(~"document", node_json(&document))])),
+            Ok(None) => Err(~"the page has no document yet"),
+            Err(message) => Err(message),
+        }
+    } else if kind == ~"getComputedStyle" {
+        let node_id = match index_field(request, "node") {
+            Ok(node_id) => node_id,
+            Err(message) => return Err(message),
+        };
+        let style = ask_script(constellation_chan, pipeline_id, |chan| {
+            GetComputedStyleMsg(node_id, chan)
+        });
+        match style {
+            Ok(Ok(values)) => {
+                let mut style = ~TreeMap::new();
+                for &(ref name, ref value) in values.iter() {
+                    style.insert(name.clone(), String(value.clone()));
+                }
+                Ok(object(~[(~"style", Object(style))]))
+            }
+            Ok(Err(())) => Err(~"no such element has been styled"),
+            Err(message) => Err(message),
+        }
+    } else if kind == ~"evaluate" {
+        let source = match request.find(&~"script") {
+            Some(&String(ref source)) => source.clone(),
+            _ => return Err(~"the request has no script"),
+        };
+        let source = Cell::new(source);
+        let result = ask_script(constellation_chan, pipeline_id, |chan| {
+            EvaluateJSMsg(source.take(), chan)
+        });
+        match result {
+            Ok(Ok(result)) => Ok(object(~[(~"result", String(result))])),
+            Ok(Err(message)) | Err(message) => Err(message),
+        }
+    } else {
+        Err(fmt!("unknown request type %s", kind))
+    }
+}
+
+/// Sends a request to the script task of the given pipeline and waits for the answer.
+fn ask_script<T: Send>(constellation_chan: &ConstellationChan,
+                       pipeline_id: PipelineId,
+                       request: &fn(Chan<T>) -> DevtoolScriptControlMsg)
+                       -> Result<T, ~str> {
+    let (port, chan): (Port<T>, Chan<T>) = comm::stream();
+    constellation_chan.send(DevtoolsMsg(pipeline_id, request(chan)));
+    match port.try_recv() {
+        Some(answer) => Ok(answer),
+        None => Err(~"no such pipeline"),
+    }
+}
+
+/// Reads a field of a request that has to be a whole number, such as an id.
+fn index_field(request: &json::Object, name: &str) -> Result<uint, ~str> {
+    match request.find(&name.to_owned()) {
+        Some(&Number(number)) if number >= 0.0 && number == number.floor() => Ok(number as uint),
+        _ => Err(fmt!("the request has no %s", name)),
+    }
+}
+
+fn object(fields: ~[(~str, Json)]) -> Json {
+    let mut object = ~TreeMap::new();
+    for (name, value) in fields.move_iter() {
+        object.insert(name, value);
+    }
+    Object(object)
+}
+
+fn optional_string(string: &Option<~str>) -> Json {
+    match *string {
+        Some(ref string) => String(string.clone()),
+        None => Null,
+    }
+}
+
+fn pipeline_json(pipeline: &PipelineSummary) -> Json {
+    let PipelineId(id) = pipeline.id;
+    let TabId(tab) = pipeline.tab;
+    let parent = match pipeline.parent {
+        Some(PipelineId(parent)) => Number(parent as float),
+        None => Null,
+    };
+    object(~[(~"id", Number(id as float)),
+             (~"tab", Number(tab as float)),
+             (~"parent", parent),
+             (~"url", optional_string(&pipeline.url.map(|url| url.to_str())))])
+}
+
+fn node_json(node: &NodeInfo) -> Json {
+    let mut attributes = ~TreeMap::new();
+    for &(ref name, ref value) in node.attributes.iter() {
+        attributes.insert(name.clone(), String(value.clone()));
+    }
+    let children = node.children.iter().map(|child| node_json(child)).collect();
+    object(~[(~"id", Number(node.id as float)),
+             (~"nodeType", Number(node.node_type as float)),
+             (~"name", String(node.name.clone())),
+             (~"value", optional_string(&node.value)),
+             (~"attributes", Object(attributes)),
+             (~"children", List(children))])
+}
+
+/// Writes all of `data`. Fails if the stream errs.
+fn write_all<W: Writer>(writer: &mut W, data: &[u8]) -> Result<(), ()> {
+    let mut errored = false;
+    do io_error::cond.trap(|_| errored = true).inside {
+        writer.write(data);
+    }
+    if errored { Err(()) } else { Ok(()) }
+}
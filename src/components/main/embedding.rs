@@ -0,0 +1,204 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The entry point for applications that embed Servo in a window of their own, rather than
+//! running the shell in `servo.rc`.
+//!
+//! An embedder implements `WindowMethods` for its window and passes it to `run`, which starts a
+//! browser loading the URLs in the options and runs the compositor in that window on the calling
+//! task. The window is both ends of the conversation:
+//!
+//! * Input goes in as the `WindowEvent`s its `recv` returns: mouse, touch and key events, resizes,
+//!   `LoadUrlWindowEvent` to go somewhere else, `NavigationWindowEvent` for back and forward,
+//!   `NewTabWindowEvent`, `SwitchTabWindowEvent` and `CloseTabWindowEvent` for tabs, and
+//!   `QuitWindowEvent` to shut the browser down.
+//! * What the browser shows comes back through its other methods: `present` when a frame has been
+//!   painted into its GL context, `set_ready_state` and `set_render_state` as the page loads and
+//!   renders, `set_url` and `set_page_title` when the page changes, and `set_tabs` when a tab is
+//!   opened, closed or shown.
+//!
+//! `run` returns once the browser has shut down. `run_headless` runs a browser with no window at
+//! all, which loads and lays out pages without drawing them; `run_headless_with` also hands a
+//! `Browser` to a task of the embedder's, to open, switch and close tabs with. With
+//! `devtools_port` set, either serves the protocol of `devtools` on that port.
+
+use compositing::{CompositorChan, CompositorTask};
+use constellation::Constellation;
+use devtools;
+use windowing::WindowMethods;
+
+use gfx::opts::Opts;
+use servo_msg::constellation_msg::{CloseTabMsg, ConstellationChan, ExitMsg, GetTabsMsg};
+use servo_msg::constellation_msg::{InitLoadUrlMsg, NewTabMsg, SwitchTabMsg, TabId};
+use servo_net::image_cache_task::{DEFAULT_MEMORY_LIMIT, ImageCacheTaskWithMemoryLimit};
+use servo_net::image_cache_task::ReportMemory;
+use servo_net::resource_task::{ResourceTaskWithCacheDir, SetThrottle, Throttle};
+use servo_util::memory::{MemoryProfiler, MemoryProfilerChan, RegisterReporterMsg};
+use servo_util::memory;
+use servo_util::time::{Profiler, ProfilerChan, PrintMsg};
+use servo_util::url::make_url;
+use std::cell::Cell;
+use std::comm;
+use std::rt::rtio::RtioTimer;
+use std::rt::io::timer::Timer;
+
+/// A handle on the tabs of a running browser. Its methods wait for the browser to answer, so it
+/// must be used from a task other than the compositor's.
+#[deriving(Clone)]
+pub struct Browser {
+    priv constellation_chan: ConstellationChan,
+}
+
+impl Browser {
+    /// Opens a tab loading the given URL in the background, and returns its id.
+    pub fn new_tab(&self, url: &str) -> TabId {
+        let (port, chan) = comm::stream();
+        self.constellation_chan.send(NewTabMsg(make_url(url.to_owned(), None), Some(chan)));
+        port.recv()
+    }
+
+    /// Closes the given tab. Closing the tab that's shown shows the one opened before it; the
+    /// last tab can't be closed.
+    pub fn close_tab(&self, tab: TabId) {
+        self.constellation_chan.send(CloseTabMsg(tab))
+    }
+
+    /// Shows the given tab.
+    pub fn switch_tab(&self, tab: TabId) {
+        self.constellation_chan.send(SwitchTabMsg(tab))
+    }
+
+    /// Returns the ids of the tabs, in the order they were opened, and that of the one shown.
+    pub fn tabs(&self) -> (~[TabId], TabId) {
+        let (port, chan) = comm::stream();
+        self.constellation_chan.send(GetTabsMsg(chan));
+        port.recv()
+    }
+}
+
+/// Runs a browser with the given options in the given window, until the window quits.
+pub fn run<A, W: WindowMethods<A>>(opts: Opts, window: @mut W) {
+    start(opts, None).run_in_window(window)
+}
+
+/// Runs a browser with the given options without a window, until it's told to exit or, with
+/// `exit_after_load`, the page has loaded.
+pub fn run_headless(opts: Opts) {
+    start(opts, None).run_headless()
+}
+
+/// Runs a browser like `run_headless`, calling `driver` on a task of its own with a handle on the
+/// browser's tabs once it has started.
+pub fn run_headless_with(opts: Opts, driver: ~fn(Browser)) {
+    start(opts, Some(driver)).run_headless()
+}
+
+/// Starts the tasks of a browser with the given options, and returns the compositor to run.
+fn start(opts: Opts, driver: Option<~fn(Browser)>) -> CompositorTask {
+    let (shutdown_port, shutdown_chan) = comm::stream();
+    let (profiler_port, profiler_chan) = comm::stream();
+    let (memory_profiler_port, memory_profiler_chan) = comm::stream();
+    let (compositor_port, compositor_chan) = comm::stream();
+
+    let profiler_chan = ProfilerChan::new(profiler_chan);
+    Profiler::create(profiler_port);
+    do opts.profiler_period.map |&period| {
+        let profiler_chan = profiler_chan.clone();
+        let period = (period * 1000f) as u64;
+        do spawn {
+            let tm = Timer::new().unwrap();
+            loop {
+                tm.sleep(period);
+                profiler_chan.send(PrintMsg);
+            }
+        }
+    };
+    let memory_profiler_chan = MemoryProfilerChan::new(memory_profiler_chan);
+    MemoryProfiler::create(memory_profiler_port);
+    do opts.memory_profiler_period.map |&period| {
+        let memory_profiler_chan = memory_profiler_chan.clone();
+        let period = (period * 1000f) as u64;
+        do spawn {
+            let tm = Timer::new().unwrap();
+            loop {
+                tm.sleep(period);
+                memory_profiler_chan.send(memory::PrintMsg);
+            }
+        }
+    };
+    let compositor_chan = CompositorChan::new(compositor_chan);
+    let profiler_chan_clone = profiler_chan.clone();
+    let memory_profiler_chan_clone = memory_profiler_chan.clone();
+
+    let opts_clone = opts.clone();
+    let driver = Cell::new(driver);
+
+    do spawn {
+        let profiler_chan = profiler_chan_clone.clone();
+        let memory_profiler_chan = memory_profiler_chan_clone.clone();
+        let compositor_chan = compositor_chan.clone();
+
+        let opts = &opts_clone.clone();
+
+        // Create a Servo instance.
+
+        let resource_task = ResourceTaskWithCacheDir(opts.cache_dir.map(|dir| Path(*dir)));
+        if opts.throttle_latency.is_some() || opts.throttle_bandwidth.is_some() {
+            resource_task.send(SetThrottle(Some(Throttle {
+                latency: opts.throttle_latency.unwrap_or_default(),
+                bandwidth: opts.throttle_bandwidth,
+            })));
+        }
+        let image_cache_limit = match opts.image_cache_size {
+            Some(megabytes) => megabytes * 1024 * 1024,
+            None => DEFAULT_MEMORY_LIMIT,
+        };
+        let image_cache_task = ImageCacheTaskWithMemoryLimit(resource_task.clone(),
+                                                             image_cache_limit,
+                                                             true);
+        let image_cache_reporter = image_cache_task.clone();
+        memory_profiler_chan.send(RegisterReporterMsg(~"image-cache", |reports| {
+            image_cache_reporter.try_send(ReportMemory(reports));
+        }));
+        let constellation_chan = Constellation::start(compositor_chan.clone(),
+                                                      opts,
+                                                      resource_task,
+                                                      image_cache_task,
+                                                      profiler_chan.clone(),
+                                                      memory_profiler_chan.clone());
+        for &port in opts.devtools_port.iter() {
+            devtools::start(port, constellation_chan.clone());
+        }
+        match driver.take() {
+            Some(driver) => {
+                let driver = Cell::new(driver);
+                let browser = Browser {
+                    constellation_chan: constellation_chan.clone(),
+                };
+                do spawn {
+                    driver.take()(browser.clone());
+                }
+            }
+            None => {}
+        }
+
+        // Send the URL command to the constellation.
+        for filename in opts.urls.iter() {
+            constellation_chan.send(InitLoadUrlMsg(make_url(filename.clone(), None)))
+        }
+
+        // Wait for the compositor to shut down.
+        shutdown_port.recv();
+
+        // Shut the constellation down.
+        debug!("master: Shut down");
+        let (exit_response_from_constellation, exit_chan) = comm::stream();
+        constellation_chan.send(ExitMsg(exit_chan));
+        exit_response_from_constellation.recv();
+    }
+
+
+    debug!("preparing to enter main loop");
+    CompositorTask::new(opts, compositor_port, profiler_chan, memory_profiler_chan, shutdown_chan)
+}
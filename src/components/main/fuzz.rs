@@ -0,0 +1,116 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Entry points for fuzzers. Each one runs arbitrary bytes through part of the engine without a
+//! window or the network, so that external fuzzers can drive it. Bugs show up as task failure.
+
+use compositing::CompositorChan;
+use compositing::headless;
+use constellation::Constellation;
+
+use std::cell::Cell;
+use std::comm;
+use azure::azure_hl::SkiaBackend;
+use gfx::opts::{DEFAULT_USER_AGENT, GrayscaleTextAntialiasing, LightTextHinting};
+use gfx::opts::Opts;
+use newcss::stylesheet::Stylesheet;
+use newcss::util::DataStream;
+use script::fuzz::{FUZZ_URL, HeadlessScriptListener};
+use servo_msg::compositor_msg::ScriptListener;
+use servo_msg::constellation_msg::{ExitMsg, InitLoadUrlMsg};
+use servo_net::image_cache_task::ImageCacheTask;
+use servo_net::resource_task::ResourceTaskWithData;
+use servo_util::memory::{MemoryProfiler, MemoryProfilerChan};
+use servo_util::time::{Profiler, ProfilerChan};
+use servo_util::url::make_url;
+
+pub use script::fuzz::fuzz_html;
+
+/// Parses `data` as a CSS style sheet.
+pub fn fuzz_css(data: &[u8]) {
+    let data = Cell::new(data.to_owned());
+    let stream: DataStream = || {
+        if data.is_empty() {
+            None
+        } else {
+            Some(data.take())
+        }
+    };
+    Stylesheet::new(make_url(FUZZ_URL.to_owned(), None), stream);
+}
+
+/// Loads `data` as an HTML document and waits for it to be styled and laid out, with the layout
+/// invariants checked. Nothing is painted.
+pub fn fuzz_layout(data: &[u8]) {
+    let url = make_url(FUZZ_URL.to_owned(), None);
+    let resource_task = ResourceTaskWithData(url.clone(), data.to_owned());
+    let image_cache_task = ImageCacheTask(resource_task.clone());
+
+    let (profiler_port, profiler_chan) = comm::stream();
+    Profiler::create(profiler_port);
+    let profiler_chan = ProfilerChan::new(profiler_chan);
+    let (memory_profiler_port, memory_profiler_chan) = comm::stream();
+    MemoryProfiler::create(memory_profiler_port);
+    let memory_profiler_chan = MemoryProfilerChan::new(memory_profiler_chan);
+
+    let (compositor_port, compositor_chan) = comm::stream();
+    let (loaded_port, loaded_chan) = comm::stream();
+    let compositor_port = Cell::new(compositor_port);
+    let loaded_chan = Cell::new(loaded_chan);
+    do spawn {
+        // Keep answering the other tasks after the document is laid out, until they've exited.
+        let mut loaded_chan = Some(loaded_chan.take());
+        do headless::run(&compositor_port.take(), HeadlessScriptListener.get_window_metrics()) {
+            for loaded_chan in loaded_chan.take().iter() {
+                loaded_chan.send(());
+            }
+            false
+        }
+    }
+
+    let constellation_chan = Constellation::start(CompositorChan::new(compositor_chan),
+                                                  &fuzz_opts(),
+                                                  resource_task,
+                                                  image_cache_task,
+                                                  profiler_chan,
+                                                  memory_profiler_chan);
+    constellation_chan.send(InitLoadUrlMsg(url));
+    loaded_port.recv();
+
+    let (exit_response_from_constellation, exit_chan) = comm::stream();
+    constellation_chan.send(ExitMsg(exit_chan));
+    exit_response_from_constellation.recv();
+}
+
+fn fuzz_opts() -> Opts {
+    Opts {
+        urls: ~[FUZZ_URL.to_owned()],
+        render_backend: SkiaBackend,
+        n_render_threads: 1,
+        tile_size: 512,
+        profiler_period: None,
+        memory_profiler_period: None,
+        exit_after_load: true,
+        output_file: None,
+        cache_dir: None,
+        image_cache_size: None,
+        check_layout: true,
+        throttle_latency: None,
+        throttle_bandwidth: None,
+        script_delay: None,
+        device_pixels_per_px: None,
+        emulate_touch: false,
+        text_antialiasing: GrayscaleTextAntialiasing,
+        text_hinting: LightTextHinting,
+        headless: true,
+        user_agent_stylesheet: None,
+        user_stylesheets: ~[],
+        user_agent: DEFAULT_USER_AGENT.to_owned(),
+        devtools_port: None,
+        layout_trace: false,
+        show_tile_borders: false,
+        paint_flashing: false,
+        show_layer_borders: false,
+    }
+}
@@ -6,11 +6,21 @@
 
 use layout::incremental::RestyleDamage;
 use gfx::display_list::DisplayList;
+use gfx::geometry::Au;
 use servo_util::range::Range;
 
 use extra::arc::Arc;
+use geom::rect::Rect;
+use std::local_data;
 use newcss::complete::CompleteSelectResults;
+use newcss::stylesheet::Stylesheet;
+use css::contain::{ContentAuto, ContentHidden, ContentVisible};
+use css::node_style::StyledNode;
+use geom::point::Point2D;
+use geom::size::Size2D;
+use script::dom::element::ElementSnapshot;
 use script::dom::node::{AbstractNode, LayoutView};
+use script::layout_interface::ScrollArea;
 use servo_util::tree::TreeNodeRef;
 
 pub struct DisplayBoxes {
@@ -23,25 +33,84 @@ pub struct LayoutData {
     /// The results of CSS styling for this node.
     style: Option<CompleteSelectResults>,
 
+    /// The results of styling this node as if the link it's in were visited, if it's in a
+    /// visited link. Only colors are taken from them, so that pages can't find out which links
+    /// were visited by measuring them.
+    visited_style: Option<CompleteSelectResults>,
+
     /// Description of how to account for recent style changes.
     restyle_damage: Option<RestyleDamage>,
 
     /// The boxes assosiated with this flow.
     /// Used for getBoundingClientRect and friends.
     boxes: DisplayBoxes,
+
+    /// Whether `content-visibility` skips styling and laying out the contents of this node.
+    skip_contents: bool,
+
+    /// Whether the contents of this node were skipped when they last needed restyling.
+    unstyled_contents: bool,
+
+    /// The absolute bounds of this node's block flow, as of the last display list built.
+    remembered_rect: Option<Rect<Au>>,
+
+    /// The content height of this node's block flow, as of the last time its contents were laid
+    /// out. Used as a placeholder while the contents are skipped.
+    remembered_height: Option<Au>,
+
+    /// How this node's block flow scrolls its overflow, as of the last display list built, if it
+    /// does.
+    scroll_area: Option<ScrollArea>,
+
+    /// The attributes of this node as of the last snapshot script sent, if it's an element.
+    snapshot: Option<ElementSnapshot>,
+
+    /// The `style` attribute in that snapshot, parsed.
+    style_attribute: Option<@Stylesheet>,
+}
+
+// How much layout data has been created on this task and not yet destroyed.
+local_data_key!(LIVE_LAYOUT_DATA: uint)
+
+/// Returns how much layout data created on this task is still alive.
+pub fn live_layout_data() -> uint {
+    do local_data::get(LIVE_LAYOUT_DATA) |count| {
+        count.map_default(0, |&count| count)
+    }
+}
+
+fn set_live_layout_data(count: uint) {
+    local_data::set(LIVE_LAYOUT_DATA, count)
 }
 
 impl LayoutData {
     /// Creates new layout data.
     pub fn new() -> LayoutData {
+        set_live_layout_data(live_layout_data() + 1);
         LayoutData {
             style: None,
+            visited_style: None,
             restyle_damage: None,
             boxes: DisplayBoxes { display_list: None, range: None },
+            skip_contents: false,
+            unstyled_contents: false,
+            remembered_rect: None,
+            remembered_height: None,
+            scroll_area: None,
+            snapshot: None,
+            style_attribute: None,
         }
     }
 }
 
+impl Drop for LayoutData {
+    fn drop(&self) {
+        let live = live_layout_data();
+        assert!(live > 0, "layout data destroyed more often than it was created");
+        set_live_layout_data(live - 1);
+    }
+}
+
 /// Functionality useful for querying the layout-specific data on DOM nodes.
 pub trait LayoutAuxMethods {
     fn layout_data(self) -> @mut LayoutData;
@@ -49,7 +118,9 @@ pub trait LayoutAuxMethods {
     fn set_layout_data(self, data: @mut LayoutData);
 
     fn initialize_layout_data(self) -> Option<@mut LayoutData>;
-    fn initialize_style_for_subtree(self, refs: &mut ~[@mut LayoutData]);
+    fn initialize_style_for_subtree(self, refs: &mut ~[@mut LayoutData]) -> uint;
+    fn should_skip_contents(self, viewport: &Rect<Au>) -> bool;
+    fn with_snapshot<R>(self, f: &fn(&ElementSnapshot) -> R) -> R;
 }
 
 impl LayoutAuxMethods for AbstractNode<LayoutView> {
@@ -85,13 +156,56 @@ impl LayoutAuxMethods for AbstractNode<LayoutView> {
 
     /// Initializes layout data and styles for a Node tree, if any nodes do not have
     /// this data already. Append created layout data to the task's GC roots.
-    fn initialize_style_for_subtree(self, refs: &mut ~[@mut LayoutData]) {
-        let _ = for n in self.traverse_preorder() {
+    /// Returns how many nodes there are in the subtree.
+    fn initialize_style_for_subtree(self, refs: &mut ~[@mut LayoutData]) -> uint {
+        let mut count = 0;
+        for n in self.traverse_preorder() {
             match n.initialize_layout_data() {
                 Some(r) => refs.push(r),
                 None => {}
             }
-        };
+            count += 1;
+        }
+        count
+    }
+
+    /// Decides whether `content-visibility` lets us skip the contents of this node this time
+    /// around. `auto` contents are skipped once we know they're more than a viewport away from
+    /// the viewport, so they're laid out again before they can be scrolled into view.
+    fn should_skip_contents(self, viewport: &Rect<Au>) -> bool {
+        if !self.is_element() {
+            return false;
+        }
+        match self.content_visibility() {
+            ContentVisible => false,
+            ContentHidden => true,
+            ContentAuto => {
+                match self.layout_data().remembered_rect {
+                    // We don't know where the contents are until they've been laid out once.
+                    None => false,
+                    Some(ref rect) => {
+                        let margin = viewport.size;
+                        let nearby = Rect(viewport.origin - Point2D(margin.width, margin.height),
+                                          Size2D(viewport.size.width + margin.width.scale_by(2.0),
+                                                 viewport.size.height + margin.height.scale_by(2.0)));
+                        !rect.intersects(&nearby)
+                    }
+                }
+            }
+        }
     }
-}
 
+    /// Calls `f` with this element's attributes as script last sent them. Layout reads attributes
+    /// only through these, never from the element itself. An element script hasn't snapshotted
+    /// yet is taken to have no attributes.
+    fn with_snapshot<R>(self, f: &fn(&ElementSnapshot) -> R) -> R {
+        let data = self.layout_data();
+        match data.snapshot {
+            Some(ref snapshot) => f(snapshot),
+            None => {
+                debug!("layout: %s has no snapshot", self.debug_str());
+                f(&ElementSnapshot::empty())
+            }
+        }
+    }
+}
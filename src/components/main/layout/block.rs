@@ -4,21 +4,34 @@
 
 //! CSS block layout.
 
+use layout::aux::LayoutAuxMethods;
 use layout::box::{RenderBox};
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
-use layout::flow::{BlockFlow, FlowContext, FlowData, InlineBlockFlow, FloatFlow};
+use layout::flow::{BlockFlow, FlowContext, FlowData, InlineBlockFlow, InlineFlow, FloatFlow};
 use layout::inline::InlineLayout;
-use layout::model::{MaybeAuto, Specified, Auto};
+use layout::wrapper::LayoutNodeCast;
+use layout::model::{MaybeAuto, Specified, Auto, computed_font_size};
 use layout::float_context::{FloatContext, Invalid};
+use css::contain::Containment;
+use css::node_style::StyledNode;
+use css::style_attribute::{DeclaredValue, Inherit, Initial, SpecifiedValue};
+use css::length::Length;
+use css::overflow::{Overflow, OverflowVisible};
+use css::text::{Direction, LeftToRight};
 
 use std::cell::Cell;
 use geom::point::Point2D;
 use geom::size::Size2D;
 use geom::rect::Rect;
-use gfx::display_list::DisplayList;
+use gfx::color::rgb;
+use gfx::display_list::{BaseDisplayItem, DisplayList, SolidColorDisplayItem};
+use gfx::display_list::SolidColorDisplayItemClass;
 use gfx::geometry::{Au, to_frac_px};
 use gfx::geometry;
+use script::dom::node::{AbstractNode, LayoutView};
+use script::layout_interface::ScrollArea;
+use servo_util::scrollbar::SCROLLBAR_WIDTH;
 
 pub struct BlockFlowData {
     /// Data common to all flows.
@@ -28,15 +41,36 @@ pub struct BlockFlowData {
     box: Option<RenderBox>,
 
     /// Whether this block flow is the root flow.
-    is_root: bool
+    is_root: bool,
+
+    /// Whether `content-visibility` is skipping the contents of this block flow, in which case
+    /// it has no children.
+    skips_contents: bool,
+
+    /// What this block flow does with content that doesn't fit in it.
+    overflow: Overflow,
 }
 
 impl BlockFlowData {
     pub fn new(common: FlowData) -> BlockFlowData {
+        let mut common = common;
+        let mut skips_contents = false;
+        let mut overflow = OverflowVisible;
+        if common.node.is_element() {
+            overflow = common.node.overflow();
+            skips_contents = common.node.layout_data().skip_contents;
+            common.contain = if skips_contents {
+                Containment::for_skipped_contents()
+            } else {
+                common.node.contain()
+            };
+        }
         BlockFlowData {
             common: common,
             box: None,
-            is_root: false
+            is_root: false,
+            skips_contents: skips_contents,
+            overflow: overflow,
         }
     }
 
@@ -44,7 +78,9 @@ impl BlockFlowData {
         BlockFlowData {
             common: common,
             box: None,
-            is_root: true
+            is_root: true,
+            skips_contents: false,
+            overflow: OverflowVisible,
         }
     }
 
@@ -91,16 +127,24 @@ impl BlockFlowData {
         let mut min_width = Au(0);
         let mut pref_width = Au(0);
         let mut num_floats = 0;
+        // A block with layout containment is a sizing root: its intrinsic widths don't depend
+        // on its contents.
+        let contain_layout = self.common.contain.layout;
 
         /* find max width from child block contexts */
         for child_ctx in self.common.child_iter() {
             assert!(child_ctx.starts_block_flow() || child_ctx.starts_inline_flow());
 
             do child_ctx.with_mut_base |child_node| {
-                min_width = geometry::max(min_width, child_node.min_width);
-                pref_width = geometry::max(pref_width, child_node.pref_width);
+                if !contain_layout {
+                    min_width = geometry::max(min_width, child_node.min_width);
+                    pref_width = geometry::max(pref_width, child_node.pref_width);
+                }
 
-                num_floats = num_floats + child_node.num_floats;
+                // Floats never escape a block with layout containment.
+                if !child_node.contain.layout {
+                    num_floats = num_floats + child_node.num_floats;
+                }
             }
         }
 
@@ -193,6 +237,7 @@ impl BlockFlowData {
 
         for &box in self.box.iter() {
             let style = box.style();
+            let inline_width = inline_length(ctx, box, "width", remaining_width);
             do box.with_model |model| {
                 // Can compute padding here since we know containing block width.
                 model.compute_padding(style, remaining_width);
@@ -208,8 +253,14 @@ impl BlockFlowData {
                                                            remaining_width,
                                                            style.font_size()).specified_or_zero();
 
+                let width = match inline_width {
+                    Some(width) => Specified(width),
+                    None => {
+                        MaybeAuto::from_width(style.width(), remaining_width, style.font_size())
+                    }
+                };
                 let (width, margin_left, margin_right) =
-                    (MaybeAuto::from_width(style.width(), remaining_width, style.font_size()),
+                    (width,
                      MaybeAuto::from_margin(style.margin_left(), remaining_width, style.font_size()),
                      MaybeAuto::from_margin(style.margin_right(), remaining_width, style.font_size()));
 
@@ -237,6 +288,9 @@ impl BlockFlowData {
             }
         }
 
+        let (text_indent, direction) = self.text_indent_and_direction(ctx, remaining_width);
+        let mut first_kid = true;
+
         let has_inorder_children = self.common.is_inorder || self.common.num_floats > 0;
         for kid in self.common.child_iter() {
             assert!(kid.starts_block_flow() || kid.starts_inline_flow());
@@ -250,7 +304,37 @@ impl BlockFlowData {
                     child_node.floats_in = FloatContext::new(0);
                 }
             }
+
+            match *kid {
+                InlineFlow(ref mut inline) => {
+                    // Only an inline flow that comes before any block children holds the first
+                    // line of this block.
+                    inline.first_line_indent = if first_kid { text_indent } else { Au(0) };
+                    inline.direction = direction;
+                }
+                _ => {}
+            }
+            first_kid = false;
+        }
+    }
+
+    /// The `text-indent` of this block, resolved against the width of its content box, and its
+    /// `direction`.
+    ///
+    /// FIXME: rust-css doesn't know about either property, so they're only read from `style`
+    /// attributes, though they do inherit, and take `inherit` and `initial`.
+    fn text_indent_and_direction(&self, ctx: &LayoutContext, content_width: Au)
+                                 -> (Au, Direction) {
+        let node = self.common.node;
+        if !node.is_element() {
+            return (Au(0), LeftToRight)
         }
+
+        let indent = inherited(node, |node| node.inline_length("text-indent"));
+        let direction = inherited(node, |node| node.direction());
+        let length_context = ctx.length_context(computed_font_size(node.style().font_size()));
+        (indent.unwrap_or_default(Length::zero()).resolve(&length_context, content_width),
+         direction.unwrap_or_default(LeftToRight))
     }
 
     pub fn assign_height_inorder_block(&mut self, ctx: &mut LayoutContext) {
@@ -260,9 +344,9 @@ impl BlockFlowData {
 
     pub fn assign_height_block(&mut self, ctx: &mut LayoutContext) {
         debug!("assign_height_block: assigning height for block %?", self.common.id);
-        // This is the only case in which a block flow can start an inorder
-        // subtraversal.
-        if self.is_root && self.common.num_floats > 0 {
+        // These are the only cases in which a block flow can start an inorder
+        // subtraversal, since both start a new block formatting context.
+        if (self.is_root || self.common.contain.layout) && self.common.num_floats > 0 {
             self.assign_height_inorder_block(ctx);
             return;
         }
@@ -301,7 +385,12 @@ impl BlockFlowData {
             // visit child[i]
             // repeat until all children are visited.
             // last_child.floats_out -> self.floats_out (done at the end of this method)
-            float_ctx = self.common.floats_in.translate(Point2D(-left_offset, -top_offset));
+            float_ctx = if self.common.contain.layout {
+                // Floats outside a block with layout containment don't affect its contents.
+                FloatContext::new(self.common.num_floats)
+            } else {
+                self.common.floats_in.translate(Point2D(-left_offset, -top_offset))
+            };
             for kid in self.common.child_iter() {
                 do kid.with_mut_base |child_node| {
                     child_node.floats_in = float_ctx.clone();
@@ -325,10 +414,26 @@ impl BlockFlowData {
             cur_y - top_offset
         };
 
+        // Skipped contents keep the height they had when they were last laid out, so that
+        // skipping them doesn't move anything else around.
+        if self.box.is_some() && self.common.node.is_element() {
+            let layout_data = self.common.node.layout_data();
+            if self.skips_contents {
+                height = layout_data.remembered_height.unwrap_or(Au(0));
+            } else {
+                layout_data.remembered_height = Some(height);
+            }
+        }
+
         for &box in self.box.iter() {
             let style = box.style();
-            let maybe_height = MaybeAuto::from_height(style.height(), Au(0), style.font_size());
-            let maybe_height = maybe_height.specified_or_zero();
+            let maybe_height = match inline_length(ctx, box, "height", Au(0)) {
+                Some(height) => height,
+                None => {
+                    MaybeAuto::from_height(style.height(), Au(0), style.font_size())
+                        .specified_or_zero()
+                }
+            };
             height = geometry::max(height, maybe_height);
         }
 
@@ -349,7 +454,7 @@ impl BlockFlowData {
         //TODO(eatkinson): compute heights using the 'height' property.
         self.common.position.size.height = height + noncontent_height;
 
-        if inorder {
+        if inorder && !self.common.contain.layout {
             let extra_height = height - (cur_y - top_offset) + bottom_offset; 
             self.common.floats_out = float_ctx.translate(Point2D(left_offset, -extra_height));
         } else {
@@ -376,16 +481,25 @@ impl BlockFlowData {
             let h = self.common.position.size.height - do self.box.map_default(Au(0)) |box| {
                 box.with_model(|model| model.noncontent_height())
             };
-            do self.common.node.with_mut_iframe_element |iframe_element| {
-                iframe_element.size.get_mut_ref().set_rect(Rect(Point2D(to_frac_px(x) as f32,
-                                                                        to_frac_px(y) as f32),
-                                                                Size2D(to_frac_px(w) as f32,
-                                                                       to_frac_px(h) as f32)));
-            }
+            let iframe_element = self.common.node.as_mut_iframe_element().unwrap();
+            iframe_element.size.get_mut_ref().set_rect(Rect(Point2D(to_frac_px(x) as f32,
+                                                                    to_frac_px(y) as f32),
+                                                            Size2D(to_frac_px(w) as f32,
+                                                                   to_frac_px(h) as f32)));
         }
 
+        // Remember where this block is, so `content-visibility` can tell how far it is from the
+        // viewport next time.
         let abs_rect = Rect(self.common.abs_position, self.common.position.size);
-        if !abs_rect.intersects(dirty) {
+        if self.box.is_some() && self.common.node.is_element() {
+            self.common.node.layout_data().remembered_rect = Some(abs_rect);
+        }
+
+        let dirty = match self.common.clip_dirty_rect(dirty) {
+            Some(dirty) => dirty,
+            None => return true,
+        };
+        if !abs_rect.intersects(&dirty) {
             return true;
         }
 
@@ -393,19 +507,197 @@ impl BlockFlowData {
 
         // add box that starts block context
         self.box.map(|&box| {
-            box.build_display_list(builder, dirty, &self.common.abs_position, list)
+            box.build_display_list(builder, &dirty, &self.common.abs_position, list)
         });
 
+        // Paint containment clips the contents to the padding box.
+        let padding_box = match self.box {
+            Some(box) => {
+                do box.with_model |model| {
+                    let origin = self.common.abs_position +
+                        Point2D(model.margin.left + model.border.left,
+                                model.margin.top + model.border.top);
+                    let size = self.common.position.size;
+                    let size = Size2D(size.width - (model.margin.left + model.margin.right +
+                                                    model.border.left + model.border.right),
+                                      size.height - (model.margin.top + model.margin.bottom +
+                                                     model.border.top + model.border.bottom));
+                    Rect(origin, size)
+                }
+            }
+            None => abs_rect,
+        };
+
+        // A block that scrolls its overflow clips its contents to the part of its padding box
+        // its scrollbars leave, and moves them up and left by how far it's scrolled.
+        let mut scroll_offset = Point2D(Au(0), Au(0));
+        let scroll_box = self.box;
+        let child_clip = match scroll_box {
+            Some(box) if self.overflow.scrolls() && self.common.node.is_element() => {
+                let area = self.scroll_area(box, padding_box);
+                let scroll_offset_wanted = self.common.node.as_element().unwrap().scroll_offset;
+                let offset = area.clamp_offset(scroll_offset_wanted);
+                scroll_offset = Point2D(Au::from_frac_px(offset.x as float),
+                                        Au::from_frac_px(offset.y as float));
+                build_scrollbars(box, &area, offset, list);
+                self.common.node.layout_data().scroll_area = Some(area);
+                self.common.clip_to(area.view)
+            }
+            _ => {
+                if self.box.is_some() && self.common.node.is_element() {
+                    self.common.node.layout_data().scroll_area = None;
+                }
+                self.common.clip_for_children(padding_box)
+            }
+        };
 
         // TODO: handle any out-of-flow elements
-        let this_position = self.common.abs_position;
+        let this_position = self.common.abs_position - scroll_offset;
         for child in self.common.child_iter() {
             do child.with_mut_base |base| {
                 base.abs_position = this_position + base.position.origin;
+                base.clip = child_clip;
             }
         }
 
         false
     }
+
+    /// Works out how this block scrolls its overflow, given its padding box: how big its
+    /// contents are, which scrollbars it has, and how much room they leave the contents to show
+    /// through.
+    ///
+    /// FIXME: The scrollbars should take their room from the content box when the block is laid
+    /// out, rather than covering the edge of the contents.
+    fn scroll_area(&mut self, box: RenderBox, padding_box: Rect<Au>) -> ScrollArea {
+        let padding_offset = padding_box.origin - self.common.abs_position;
+        let (padding_right, padding_bottom) = do box.with_model |model| {
+            (model.padding.right, model.padding.bottom)
+        };
+        let mut content_size = padding_box.size;
+        for child in self.common.child_iter() {
+            let position = child.position();
+            content_size.width = geometry::max(content_size.width,
+                                               position.origin.x + position.size.width -
+                                               padding_offset.x + padding_right);
+            content_size.height = geometry::max(content_size.height,
+                                                position.origin.y + position.size.height -
+                                                padding_offset.y + padding_bottom);
+        }
+
+        // A scrollbar along one edge takes room from the other axis, which can then call for a
+        // scrollbar too.
+        let width = to_frac_px(padding_box.size.width) as f32;
+        let height = to_frac_px(padding_box.size.height) as f32;
+        let content_width = to_frac_px(content_size.width) as f32;
+        let content_height = to_frac_px(content_size.height) as f32;
+        let mut vertical = self.overflow.has_scrollbar(height, content_height);
+        let horizontal = self.overflow.has_scrollbar(if vertical {
+            width - SCROLLBAR_WIDTH
+        } else {
+            width
+        }, content_width);
+        if horizontal && !vertical {
+            vertical = self.overflow.has_scrollbar(height - SCROLLBAR_WIDTH, content_height);
+        }
+
+        let gutter = Au::from_frac_px(SCROLLBAR_WIDTH as float);
+        let view_width = padding_box.size.width - if vertical { gutter } else { Au(0) };
+        let view_height = padding_box.size.height - if horizontal { gutter } else { Au(0) };
+        ScrollArea {
+            view: Rect(padding_box.origin,
+                       Size2D(geometry::max(view_width, Au(0)),
+                              geometry::max(view_height, Au(0)))),
+            content_size: content_size,
+            vertical_scrollbar: vertical,
+            horizontal_scrollbar: horizontal,
+        }
+    }
 }
 
+/// Adds the scrollbars of a block that scrolls its overflow by `offset`, in px, along the right
+/// and bottom edges of the part of its padding box the contents show through.
+fn build_scrollbars<E:ExtraDisplayListData>(box: RenderBox,
+                                            area: &ScrollArea,
+                                            offset: Point2D<f32>,
+                                            list: &Cell<DisplayList<E>>) {
+    let view = area.view;
+    let gutter = Au::from_frac_px(SCROLLBAR_WIDTH as float);
+    let track_color = rgb(241, 241, 241);
+    let thumb_color = rgb(193, 193, 193);
+    let mut rects = ~[];
+    if area.vertical_scrollbar {
+        let x = view.origin.x + view.size.width;
+        let (start, length) = area.vertical().thumb(offset.y);
+        rects.push((Rect(Point2D(x, view.origin.y), Size2D(gutter, view.size.height)),
+                    track_color));
+        rects.push((Rect(Point2D(x, view.origin.y + Au::from_frac_px(start as float)),
+                         Size2D(gutter, Au::from_frac_px(length as float))),
+                    thumb_color));
+    }
+    if area.horizontal_scrollbar {
+        let y = view.origin.y + view.size.height;
+        let (start, length) = area.horizontal().thumb(offset.x);
+        rects.push((Rect(Point2D(view.origin.x, y), Size2D(view.size.width, gutter)),
+                    track_color));
+        rects.push((Rect(Point2D(view.origin.x + Au::from_frac_px(start as float), y),
+                         Size2D(Au::from_frac_px(length as float), gutter)),
+                    thumb_color));
+    }
+    if area.vertical_scrollbar && area.horizontal_scrollbar {
+        let corner = view.origin + Point2D(view.size.width, view.size.height);
+        rects.push((Rect(corner, Size2D(gutter, gutter)), track_color));
+    }
+
+    do list.with_mut_ref |list| {
+        for &(bounds, color) in rects.iter() {
+            let item = ~SolidColorDisplayItem {
+                base: BaseDisplayItem {
+                    bounds: bounds,
+                    extra: ExtraDisplayListData::new(box),
+                    metadata: box.display_item_metadata(),
+                },
+                color: color,
+            };
+            list.append_item(SolidColorDisplayItemClass(item))
+        }
+    }
+}
+
+/// Finds the value of a length property in the `style` attribute of the element `box` is for,
+/// where it's in units rust-css can't parse, like `rem`, the viewport units and `calc()`.
+/// Percentages are of `percentage_basis`.
+fn inline_length(ctx: &LayoutContext, box: RenderBox, name: &str, percentage_basis: Au)
+                 -> Option<Au> {
+    let node = box.node();
+    if !node.is_element() {
+        return None
+    }
+    match node.inline_length(name) {
+        Some(SpecifiedValue(length)) => {
+            let font_size = computed_font_size(box.style().font_size());
+            Some(length.resolve(&ctx.length_context(font_size), percentage_basis))
+        }
+        // rust-css resolves `inherit` and `initial` for the properties it knows about.
+        Some(Inherit) | Some(Initial) | None => None,
+    }
+}
+
+/// Finds the value of an inherited property declared on `node` or on the nearest ancestor element
+/// that declares it. Returns `None` if that declaration is `initial`, or there isn't one.
+fn inherited<T>(node: AbstractNode<LayoutView>,
+                declared: &fn(AbstractNode<LayoutView>) -> Option<DeclaredValue<T>>)
+                -> Option<T> {
+    let mut node = node;
+    loop {
+        match declared(node) {
+            Some(SpecifiedValue(value)) => return Some(value),
+            Some(Initial) => return None,
+            Some(Inherit) | None => {}
+        }
+        match node.parent_node() {
+            Some(parent) if parent.is_element() => node = parent,
+            _ => return None,
+        }
+    }
+}
@@ -4,12 +4,17 @@
 
 //! The `RenderBox` type, which represents the leaves of the layout tree.
 
+use css::color::{CurrentColor, SpecifiedRGBA};
+use css::cursor::{AutoCursor, SpecifiedCursor};
 use css::node_style::StyledNode;
+use css::style_attribute::{Inherit, Initial, SpecifiedValue};
+use layout::aux::LayoutAuxMethods;
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData, ToGfxColor};
 use layout::float_context::{ClearType, ClearLeft, ClearRight, ClearBoth};
 use layout::model::{BoxModel, MaybeAuto};
 use layout::text;
+use layout::wrapper::LayoutNodeCast;
 
 use std::cell::Cell;
 use std::cmp::ApproxEq;
@@ -17,12 +22,21 @@ use std::managed;
 use std::num::Zero;
 use geom::{Point2D, Rect, Size2D, SideOffsets2D};
 use gfx::display_list::{BaseDisplayItem, BorderDisplayItem, BorderDisplayItemClass};
+use gfx::display_list::{CaretDisplayItem, CaretDisplayItemClass, Control, ControlDisplayItem};
+use gfx::display_list::{ControlDisplayItemClass, DropdownControl, SelectionDisplayItem};
+use gfx::display_list::{PathDisplayItem, PathDisplayItemClass, SelectionDisplayItemClass};
 use gfx::display_list::{DisplayList, ImageDisplayItem, ImageDisplayItemClass};
 use gfx::display_list::{SolidColorDisplayItem, SolidColorDisplayItemClass, TextDisplayItem};
-use gfx::display_list::{TextDisplayItemClass};
+use gfx::display_list::{VideoDisplayItem, VideoDisplayItemClass};
+use gfx::color::Color;
+use gfx::display_list::{DisplayItemMetadata, TextDisplayItemClass};
 use gfx::font::{FontStyle, FontWeight300};
 use gfx::geometry::Au;
+use gfx::path::PathShape;
+use gfx::text::glyph_run::GlyphRun;
 use gfx::text::text_run::TextRun;
+use gfx::text::util::compressed_offset;
+use CSSColor = newcss::color::Color;
 use newcss::color::rgb;
 use newcss::complete::CompleteStyle;
 use newcss::units::{Em, Px};
@@ -33,10 +47,15 @@ use newcss::values::{CSSFontSizeLength, CSSFontStyleItalic, CSSFontStyleNormal};
 use newcss::values::{CSSFontStyleOblique, CSSTextAlign, CSSTextDecoration, CSSLineHeight};
 use newcss::values::{CSSTextDecorationNone, CSSFloatNone, CSSPositionStatic};
 use newcss::values::{CSSDisplayInlineBlock, CSSDisplayInlineTable};
-use script::dom::node::{AbstractNode, LayoutView};
+use script::dom::element::HTMLAnchorElementTypeId;
+use script::dom::node::{AbstractNode, ElementNodeTypeId, LayoutView};
+use servo_msg::compositor_msg::{Cursor, DefaultCursor, PointerCursor, TextCursor};
+use servo_msg::compositor_msg::VideoLayerId;
+use servo_net::image::base::Image;
 use servo_net::image::holder::ImageHolder;
 use servo_net::local_image_cache::LocalImageCache;
 use servo_util::range::*;
+use extra::arc::Arc;
 use extra::url::Url;
 
 /// Render boxes (`struct RenderBox`) are the leaves of the layout tree. They cannot position
@@ -61,9 +80,13 @@ use extra::url::Url;
 #[deriving(Clone)]
 pub enum RenderBox {
     GenericRenderBoxClass(@mut RenderBoxBase),
+    CanvasRenderBoxClass(@mut CanvasRenderBox),
     ImageRenderBoxClass(@mut ImageRenderBox),
+    InputRenderBoxClass(@mut InputRenderBox),
+    SvgRenderBoxClass(@mut SvgRenderBox),
     TextRenderBoxClass(@mut TextRenderBox),
     UnscannedTextRenderBoxClass(@mut UnscannedTextRenderBox),
+    VideoRenderBoxClass(@mut VideoRenderBox),
 }
 
 impl RenderBox {
@@ -93,6 +116,111 @@ impl ImageRenderBox {
     }
 }
 
+/// A box that represents a canvas and its accompanying borders, shadows, etc. Like an image, it's
+/// replaced content, showing the canvas's bitmap as it was when the box was built.
+pub struct CanvasRenderBox {
+    base: RenderBoxBase,
+    /// A snapshot of the bitmap, if script has drawn on the canvas.
+    image: Option<Arc<~Image>>,
+    /// The size of the bitmap, which the canvas is laid out at.
+    size: Size2D<Au>,
+}
+
+impl CanvasRenderBox {
+    pub fn new(base: RenderBoxBase, image: Option<Arc<~Image>>, size: Size2D<Au>)
+               -> CanvasRenderBox {
+        assert!(base.node.is_canvas_element());
+
+        CanvasRenderBox {
+            base: base,
+            image: image,
+            size: size,
+        }
+    }
+}
+
+/// A box that represents an inline `<svg>` and its accompanying borders, shadows, etc. Like an
+/// image, it's replaced content, drawing the shapes in the `<svg>` rather than laying out its
+/// children.
+pub struct SvgRenderBox {
+    base: RenderBoxBase,
+    /// The shapes, in the order they're drawn, with coordinates in px from the box's top left.
+    shapes: ~[PathShape],
+    /// The size of the `<svg>`'s viewport, which it's laid out at.
+    size: Size2D<Au>,
+}
+
+impl SvgRenderBox {
+    pub fn new(base: RenderBoxBase, shapes: ~[PathShape], size: Size2D<Au>) -> SvgRenderBox {
+        assert!(base.node.is_svg_element());
+
+        SvgRenderBox {
+            base: base,
+            shapes: shapes,
+            size: size,
+        }
+    }
+}
+
+/// A box that represents a `<video>` and its accompanying borders, shadows, etc. Like an image,
+/// it's replaced content. The compositor shows the video's frames over it.
+pub struct VideoRenderBox {
+    base: RenderBoxBase,
+    /// The compositor layer the frames are shown in, once the element has a source.
+    layer: Option<VideoLayerId>,
+    /// The size the video is laid out at.
+    size: Size2D<Au>,
+}
+
+impl VideoRenderBox {
+    pub fn new(base: RenderBoxBase, layer: Option<VideoLayerId>, size: Size2D<Au>)
+               -> VideoRenderBox {
+        assert!(base.node.is_video_element());
+
+        VideoRenderBox {
+            base: base,
+            layer: layer,
+            size: size,
+        }
+    }
+}
+
+/// A box that represents a text field, checkbox, radio button or closed select, and its
+/// accompanying borders and padding. Like an image, it's replaced content: layout draws its value
+/// and caret, or the control, itself, rather than laying out the element's children.
+pub struct InputRenderBox {
+    base: RenderBoxBase,
+    /// The checkbox, radio button or dropdown button to paint, if the box isn't a text field.
+    control: Option<Control>,
+    /// The lines of text shown in the field: its value, or the selected option's label. A text
+    /// area shows as many lines as fit in its rows. Empty lines are `None`.
+    lines: ~[Option<@TextRun>],
+    /// The line the caret is on, and how many characters into it, if the field has focus.
+    caret: Option<(uint, uint)>,
+    /// The height of each line of text.
+    line_height: Au,
+    /// The size of the field's content box.
+    content_size: Size2D<Au>,
+}
+
+impl InputRenderBox {
+    /// The width of the border and padding on each side, which the base's box model holds.
+    fn fringe(&self) -> SideOffsets2D<Au> {
+        let model = &self.base.model;
+        SideOffsets2D::new(model.border.top + model.padding.top,
+                           model.border.right + model.padding.right,
+                           model.border.bottom + model.padding.bottom,
+                           model.border.left + model.padding.left)
+    }
+
+    /// The size of the field, including its borders and padding.
+    pub fn border_box_size(&self) -> Size2D<Au> {
+        let fringe = self.fringe();
+        Size2D(self.content_size.width + fringe.left + fringe.right,
+               self.content_size.height + fringe.top + fringe.bottom)
+    }
+}
+
 /// A box representing a single run of text with a distinct style. A `TextRenderBox` may be split
 /// into two or more render boxes across line breaks. Several `TextBox`es may correspond to a
 /// single DOM text node. Split text boxes are implemented by referring to subsets of a master
@@ -101,6 +229,9 @@ pub struct TextRenderBox {
     base: RenderBoxBase,
     run: @TextRun,
     range: Range,
+    /// Where the text of the box's node starts in `run`, which may hold the text of several
+    /// nodes.
+    node_start: uint,
 }
 
 impl TextRenderBox {
@@ -118,24 +249,23 @@ pub struct UnscannedTextRenderBox {
 impl UnscannedTextRenderBox {
     /// Creates a new instance of `UnscannedTextRenderBox`.
     pub fn new(base: RenderBoxBase) -> UnscannedTextRenderBox {
-        assert!(base.node.is_text());
-
-        do base.node.with_imm_text |text_node| {
-            // FIXME: Don't copy text; atomically reference count it instead.
-            // FIXME(pcwalton): If we're just looking at node data, do we have to ensure this is
-            // a text node?
-            UnscannedTextRenderBox {
-                base: base,
-                text: text_node.parent.data.to_str(),
-            }
+        // FIXME: Don't copy text; atomically reference count it instead.
+        let text = base.node.as_text().expect("not a text node").parent.data.to_str();
+        UnscannedTextRenderBox {
+            base: base,
+            text: text,
         }
     }
 }
 
 pub enum RenderBoxType {
     RenderBox_Generic,
+    RenderBox_Canvas,
     RenderBox_Image,
+    RenderBox_Input,
+    RenderBox_Svg,
     RenderBox_Text,
+    RenderBox_Video,
 }
 
 /// Represents the outcome of attempting to split a render box.
@@ -183,15 +313,27 @@ impl RenderBox {
     pub fn with_base<R>(&self, callback: &fn(&RenderBoxBase) -> R) -> R {
         match *self {
             GenericRenderBoxClass(generic_box) => callback(generic_box),
+            CanvasRenderBoxClass(canvas_box) => {
+                callback(&canvas_box.base)
+            }
             ImageRenderBoxClass(image_box) => {
                 callback(&image_box.base)
             }
+            InputRenderBoxClass(input_box) => {
+                callback(&input_box.base)
+            }
+            SvgRenderBoxClass(svg_box) => {
+                callback(&svg_box.base)
+            }
             TextRenderBoxClass(text_box) => {
                 callback(&text_box.base)
             }
             UnscannedTextRenderBoxClass(unscanned_text_box) => {
                 callback(&unscanned_text_box.base)
             }
+            VideoRenderBoxClass(video_box) => {
+                callback(&video_box.base)
+            }
         }
     }
 
@@ -200,15 +342,27 @@ impl RenderBox {
     pub fn with_mut_base<R>(&self, callback: &fn(&mut RenderBoxBase) -> R) -> R {
         match *self {
             GenericRenderBoxClass(generic_box) => callback(generic_box),
+            CanvasRenderBoxClass(canvas_box) => {
+                callback(&mut canvas_box.base)
+            }
             ImageRenderBoxClass(image_box) => {
                 callback(&mut image_box.base)
             }
+            InputRenderBoxClass(input_box) => {
+                callback(&mut input_box.base)
+            }
+            SvgRenderBoxClass(svg_box) => {
+                callback(&mut svg_box.base)
+            }
             TextRenderBoxClass(text_box) => {
                 callback(&mut text_box.base)
             }
             UnscannedTextRenderBoxClass(unscanned_text_box) => {
                 callback(&mut unscanned_text_box.base)
             }
+            VideoRenderBoxClass(video_box) => {
+                callback(&mut video_box.base)
+            }
         }
     }
 
@@ -230,7 +384,8 @@ impl RenderBox {
     /// and so on.
     pub fn is_replaced(&self) -> bool {
         match *self {
-            ImageRenderBoxClass(*) => true,
+            CanvasRenderBoxClass(*) | ImageRenderBoxClass(*) | InputRenderBoxClass(*) |
+            SvgRenderBoxClass(*) | VideoRenderBoxClass(*) => true,
             _ => false
         }
     }
@@ -271,7 +426,10 @@ impl RenderBox {
     pub fn split_to_width(&self, max_width: Au, starts_line: bool)
                       -> SplitBoxResult {
         match *self {
-            GenericRenderBoxClass(*) | ImageRenderBoxClass(*) => CannotSplit(*self),
+            GenericRenderBoxClass(*) | CanvasRenderBoxClass(*) | ImageRenderBoxClass(*) |
+            InputRenderBoxClass(*) | SvgRenderBoxClass(*) | VideoRenderBoxClass(*) => {
+                CannotSplit(*self)
+            }
             UnscannedTextRenderBoxClass(*) => {
                 fail!(~"WAT: shouldn't be an unscanned text box here.")
             }
@@ -347,7 +505,8 @@ impl RenderBox {
                 let left_box = if left_range.length() > 0 {
                     let new_text_box = @mut text::adapt_textbox_with_range(text_box.base,
                                                                            text_box.run,
-                                                                           left_range);
+                                                                           left_range,
+                                                                           text_box.node_start);
                     Some(TextRenderBoxClass(new_text_box))
                 } else {
                     None
@@ -356,7 +515,8 @@ impl RenderBox {
                 let right_box = do right_range.map_default(None) |range: &Range| {
                     let new_text_box = @mut text::adapt_textbox_with_range(text_box.base,
                                                                            text_box.run,
-                                                                           *range);
+                                                                           *range,
+                                                                           text_box.node_start);
                     Some(TextRenderBoxClass(new_text_box))
                 };
 
@@ -424,12 +584,20 @@ impl RenderBox {
             // arrive at the context width.
             GenericRenderBoxClass(*) => Au(0),
 
+            CanvasRenderBoxClass(canvas_box) => canvas_box.size.width,
+
             ImageRenderBoxClass(image_box) => {
                 // TODO: Consult the CSS `width` property as well as margins and borders.
                 // TODO: If the image isn't available, consult `width`.
                 Au::from_px(image_box.image.get_size().unwrap_or_default(Size2D(0, 0)).width)
             }
 
+            InputRenderBoxClass(input_box) => input_box.content_size.width,
+
+            SvgRenderBoxClass(svg_box) => svg_box.size.width,
+
+            VideoRenderBoxClass(video_box) => video_box.size.width,
+
             TextRenderBoxClass(text_box) => {
                 text_box.run.min_width_for_range(&text_box.range)
             }
@@ -447,10 +615,18 @@ impl RenderBox {
             // arrive at the context width.
             GenericRenderBoxClass(*) => Au(0),
 
+            CanvasRenderBoxClass(canvas_box) => canvas_box.size.width,
+
             ImageRenderBoxClass(image_box) => {
                 Au::from_px(image_box.image.get_size().unwrap_or_default(Size2D(0, 0)).width)
             }
 
+            InputRenderBoxClass(input_box) => input_box.content_size.width,
+
+            SvgRenderBoxClass(svg_box) => svg_box.size.width,
+
+            VideoRenderBoxClass(video_box) => video_box.size.width,
+
             TextRenderBoxClass(text_box) => {
                 // A text box cannot span lines, so assume that this is an unsplit text box.
                 //
@@ -538,6 +714,12 @@ impl RenderBox {
         self.with_base(|base| base.node.style())
     }
 
+    /// The style to take the colors of the DOM node that this render box represents from. See
+    /// `StyledNode::color_style`.
+    pub fn color_style(&self) -> CompleteStyle {
+        self.with_base(|base| base.node.color_style())
+    }
+
     /// A convenience function to access the DOM node that this render box represents.
     pub fn node(&self) -> AbstractNode<LayoutView> {
         self.with_base(|base| base.node)
@@ -560,6 +742,51 @@ impl RenderBox {
         }
     }
 
+    /// The cursor to show over this box. A `cursor` declared on the nearest ancestor-or-self
+    /// element that declares one wins; otherwise links get a pointer and text gets a text cursor.
+    pub fn cursor(&self) -> Cursor {
+        let mut node = self.nearest_ancestor_element();
+        let mut inherit_declared = true;
+        loop {
+            if inherit_declared {
+                match node.cursor() {
+                    Some(SpecifiedValue(SpecifiedCursor(cursor))) => return cursor,
+                    Some(SpecifiedValue(AutoCursor)) | Some(Initial) => inherit_declared = false,
+                    Some(Inherit) | None => {}
+                }
+            }
+
+            if node.type_id() == ElementNodeTypeId(HTMLAnchorElementTypeId) &&
+                    node.with_snapshot(|element| element.get_attr("href").is_some()) {
+                return PointerCursor
+            }
+
+            match node.parent_node() {
+                Some(parent) if parent.is_element() => node = parent,
+                _ => break,
+            }
+        }
+
+        match *self {
+            TextRenderBoxClass(*) => TextCursor,
+            InputRenderBoxClass(input_box) if input_box.control.is_none() => TextCursor,
+            _ => DefaultCursor,
+        }
+    }
+
+    /// The hit testing information attached to the display items of this box.
+    pub fn display_item_metadata(&self) -> DisplayItemMetadata {
+        let node = self.node();
+        let scroll_subpage = do node.as_iframe_element().chain |iframe_element| {
+            iframe_element.size.map(|size| size.subpage_id)
+        };
+        DisplayItemMetadata {
+            node: unsafe { node.raw_object() as uint },
+            cursor: self.cursor(),
+            scroll_subpage: scroll_subpage,
+        }
+    }
+
     //
     // Painting
     //
@@ -579,7 +806,7 @@ impl RenderBox {
     /// items, each box puts its display items into the correct stack layer according to CSS 2.1
     /// Appendix E. Finally, the builder flattens the list.
     pub fn build_display_list<E:ExtraDisplayListData>(&self,
-                                                  _: &DisplayListBuilder,
+                                                  builder: &DisplayListBuilder,
                                                   dirty: &Rect<Au>,
                                                   offset: &Point2D<Au>,
                                                   list: &Cell<DisplayList<E>>) {
@@ -600,7 +827,47 @@ impl RenderBox {
             UnscannedTextRenderBoxClass(*) => fail!(~"Shouldn't see unscanned boxes here."),
             TextRenderBoxClass(text_box) => {
                 let nearest_ancestor_element = self.nearest_ancestor_element();
-                let color = nearest_ancestor_element.style().color().to_gfx_color();
+                let color = self.color_property("color",
+                                                nearest_ancestor_element.color_style().color());
+
+                // Highlight the selected part of the text, behind it.
+                for &(selected_node, begin, end) in builder.ctx.selection.iter() {
+                    if selected_node != text_box.base.node {
+                        loop
+                    }
+                    let text = selected_node.as_text().unwrap().parent.data.to_str();
+                    let range = text_box.range;
+                    let begin = text_box.node_start +
+                        compressed_offset(text, begin.min(&text.len()));
+                    let end = text_box.node_start + compressed_offset(text, end.min(&text.len()));
+                    let begin = begin.max(&range.begin());
+                    let end = end.min(&range.end());
+                    if begin >= end {
+                        loop
+                    }
+                    let selection_x = if begin > range.begin() {
+                        let before = Range::new(range.begin(), begin - range.begin());
+                        text_box.run.metrics_for_range(&before).advance_width
+                    } else {
+                        Au(0)
+                    };
+                    let selected = Range::new(begin, end - begin);
+                    let selection_width = text_box.run.metrics_for_range(&selected).advance_width;
+                    let selection_bounds =
+                        Rect(absolute_box_bounds.origin + Point2D(selection_x, Au(0)),
+                             Size2D(selection_width, absolute_box_bounds.size.height));
+                    do list.with_mut_ref |list| {
+                        let selection_display_item = ~SelectionDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: selection_bounds,
+                                extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
+                            },
+                            color: rgb(179, 215, 255).to_gfx_color(),
+                        };
+                        list.append_item(SelectionDisplayItemClass(selection_display_item))
+                    }
+                }
 
                 // Create the text box.
                 do list.with_mut_ref |list| {
@@ -608,9 +875,9 @@ impl RenderBox {
                         base: BaseDisplayItem {
                             bounds: absolute_box_bounds,
                             extra: ExtraDisplayListData::new(*self),
+                            metadata: self.display_item_metadata(),
                         },
-                        // FIXME(pcwalton): Allocation? Why?!
-                        text_run: ~text_box.run.serialize(),
+                        glyph_run: ~GlyphRun::new(text_box.run, &text_box.range),
                         range: text_box.range,
                         color: color,
                     };
@@ -618,6 +885,43 @@ impl RenderBox {
                     list.append_item(TextDisplayItemClass(text_display_item))
                 }
 
+                // Draw the editing caret, if it's in this box. A caret between two boxes of the
+                // node goes at the start of the second.
+                for &(caret_node, caret) in builder.ctx.caret.iter() {
+                    if caret_node != text_box.base.node {
+                        loop
+                    }
+                    let text = caret_node.as_text().unwrap().parent.data.to_str();
+                    let caret = text_box.node_start +
+                        compressed_offset(text, caret.min(&text.len()));
+                    let node_end = text_box.node_start + compressed_offset(text, text.len());
+                    let range = text_box.range;
+                    if caret < range.begin() || caret > range.end() ||
+                            (caret == range.end() && caret != node_end) {
+                        loop
+                    }
+                    let caret_x = if caret > range.begin() {
+                        let before = Range::new(range.begin(), caret - range.begin());
+                        text_box.run.metrics_for_range(&before).advance_width
+                    } else {
+                        Au(0)
+                    };
+                    let caret_bounds = Rect(absolute_box_bounds.origin + Point2D(caret_x, Au(0)),
+                                            Size2D(Au::from_px(1),
+                                                   absolute_box_bounds.size.height));
+                    do list.with_mut_ref |list| {
+                        let caret_display_item = ~CaretDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: caret_bounds,
+                                extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
+                            },
+                            color: color,
+                        };
+                        list.append_item(CaretDisplayItemClass(caret_display_item))
+                    }
+                }
+
                 // Draw debug frames for text bounds.
                 //
                 // FIXME(pcwalton): This is a bit of an abuse of the logging infrastructure. We
@@ -631,6 +935,7 @@ impl RenderBox {
                             base: BaseDisplayItem {
                                 bounds: absolute_box_bounds,
                                 extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
                             },
                             border: debug_border,
                             color: SideOffsets2D::new_all_same(rgb(0, 0, 200).to_gfx_color())
@@ -652,6 +957,7 @@ impl RenderBox {
                             base: BaseDisplayItem {
                                 bounds: baseline,
                                 extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
                             },
                             border: debug_border,
                             color: SideOffsets2D::new_all_same(rgb(0, 200, 0).to_gfx_color())
@@ -678,6 +984,7 @@ impl RenderBox {
                             base: BaseDisplayItem {
                                 bounds: absolute_box_bounds,
                                 extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
                             },
                             border: debug_border,
                             color: SideOffsets2D::new_all_same(rgb(0, 0, 200).to_gfx_color())
@@ -690,6 +997,60 @@ impl RenderBox {
                 });
 
             },
+            SvgRenderBoxClass(svg_box) => {
+                self.paint_background_if_applicable(list, &absolute_box_bounds);
+
+                if !svg_box.shapes.is_empty() {
+                    do list.with_mut_ref |list| {
+                        let path_display_item = ~PathDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: absolute_box_bounds,
+                                extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
+                            },
+                            shapes: svg_box.shapes.clone(),
+                        };
+                        list.append_item(PathDisplayItemClass(path_display_item))
+                    }
+                }
+            }
+            VideoRenderBoxClass(video_box) => {
+                self.paint_background_if_applicable(list, &absolute_box_bounds);
+
+                // A video without a source shows nothing.
+                for &layer in video_box.layer.iter() {
+                    do list.with_mut_ref |list| {
+                        let video_display_item = ~VideoDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: absolute_box_bounds,
+                                extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
+                            },
+                            layer: layer,
+                        };
+                        list.append_item(VideoDisplayItemClass(video_display_item))
+                    }
+                }
+            }
+            CanvasRenderBoxClass(canvas_box) => {
+                self.paint_background_if_applicable(list, &absolute_box_bounds);
+
+                // A canvas script hasn't drawn on is transparent.
+                for image in canvas_box.image.iter() {
+                    do list.with_mut_ref |list| {
+                        let image_display_item = ~ImageDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: absolute_box_bounds,
+                                extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
+                            },
+                            image: image.clone(),
+                            broken: false,
+                        };
+                        list.append_item(ImageDisplayItemClass(image_display_item))
+                    }
+                }
+            }
             ImageRenderBoxClass(image_box) => {
 
                 // Add the background to the list, if applicable.
@@ -705,20 +1066,97 @@ impl RenderBox {
                                 base: BaseDisplayItem {
                                     bounds: absolute_box_bounds,
                                     extra: ExtraDisplayListData::new(*self),
+                                    metadata: self.display_item_metadata(),
                                 },
                                 image: image.clone(),
+                                broken: image_box.image.is_broken(),
                             };
                             list.append_item(ImageDisplayItemClass(image_display_item))
                         }
                     }
                     None => {
-                        // No image data at all? Do nothing.
-                        //
-                        // TODO: Add some kind of placeholder image.
+                        // The image hasn't arrived yet. Failed images get a placeholder instead.
                         debug!("(building display list) no image :(");
                     }
                 }
             }
+            InputRenderBoxClass(input_box) => {
+                self.paint_background_if_applicable(list, &absolute_box_bounds);
+
+                let fringe = input_box.fringe();
+                let content_origin = absolute_box_bounds.origin + Point2D(fringe.left, fringe.top);
+                let color = self.color_property("color", self.color_style().color());
+
+                for (i, line) in input_box.lines.iter().enumerate() {
+                    let run = match *line {
+                        Some(run) => run,
+                        None => loop,
+                    };
+                    let range = Range::new(0, run.char_len());
+                    let line_y = input_box.line_height.scale_by(i as float);
+                    let line_origin = content_origin + Point2D(Au(0), line_y);
+                    let line_size = Size2D(input_box.content_size.width, input_box.line_height);
+                    do list.with_mut_ref |list| {
+                        let text_display_item = ~TextDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: Rect(line_origin, line_size),
+                                extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
+                            },
+                            glyph_run: ~GlyphRun::new(run, &range),
+                            range: range,
+                            color: color,
+                        };
+                        list.append_item(TextDisplayItemClass(text_display_item))
+                    }
+                }
+
+                for &control in input_box.control.iter() {
+                    // A select's button is a square at the end of its content box, past the label.
+                    let control_bounds = match control {
+                        DropdownControl => {
+                            let side = input_box.content_size.height;
+                            let x = input_box.content_size.width - side;
+                            Rect(content_origin + Point2D(x, Au(0)), Size2D(side, side))
+                        }
+                        _ => Rect(content_origin, input_box.content_size),
+                    };
+                    do list.with_mut_ref |list| {
+                        let control_display_item = ~ControlDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: control_bounds,
+                                extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
+                            },
+                            control: control,
+                        };
+                        list.append_item(ControlDisplayItemClass(control_display_item))
+                    }
+                }
+
+                for &(line, caret) in input_box.caret.iter() {
+                    let caret_x = match input_box.lines[line] {
+                        Some(run) if caret > 0 => {
+                            run.metrics_for_range(&Range::new(0, caret)).advance_width
+                        }
+                        _ => Au(0),
+                    };
+                    let caret_y = input_box.line_height.scale_by(line as float);
+                    let caret_bounds = Rect(content_origin + Point2D(caret_x, caret_y),
+                                            Size2D(Au::from_px(1), input_box.line_height));
+                    do list.with_mut_ref |list| {
+                        let caret_display_item = ~CaretDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: caret_bounds,
+                                extra: ExtraDisplayListData::new(*self),
+                                metadata: self.display_item_metadata(),
+                            },
+                            color: color,
+                        };
+                        list.append_item(CaretDisplayItemClass(caret_display_item))
+                    }
+                }
+            }
         }
 
         // Add a border, if applicable.
@@ -738,15 +1176,18 @@ impl RenderBox {
         // doesn't have a render box".
         let nearest_ancestor_element = self.nearest_ancestor_element();
 
-        let background_color = nearest_ancestor_element.style().background_color();
-        if !background_color.alpha.approx_eq(&0.0) {
+        let background_color =
+            self.color_property("background-color",
+                                nearest_ancestor_element.color_style().background_color());
+        if !background_color.a.approx_eq(&0.0) {
             do list.with_mut_ref |list| {
                 let solid_color_display_item = ~SolidColorDisplayItem {
                     base: BaseDisplayItem {
                         bounds: *absolute_bounds,
                         extra: ExtraDisplayListData::new(*self),
+                        metadata: self.display_item_metadata(),
                     },
-                    color: background_color.to_gfx_color(),
+                    color: background_color,
                 };
 
                 list.append_item(SolidColorDisplayItemClass(solid_color_display_item))
@@ -877,7 +1318,19 @@ impl RenderBox {
     pub fn debug_str(&self) -> ~str {
         let representation = match *self {
             GenericRenderBoxClass(*) => ~"GenericRenderBox",
+            CanvasRenderBoxClass(*) => ~"CanvasRenderBox",
+            SvgRenderBoxClass(svg_box) => fmt!("SvgRenderBox(shapes=%u)", svg_box.shapes.len()),
+            VideoRenderBoxClass(video_box) => fmt!("VideoRenderBox(layer=%?)", video_box.layer),
             ImageRenderBoxClass(*) => ~"ImageRenderBox",
+            InputRenderBoxClass(input_box) => {
+                let value = input_box.lines.map(|line| {
+                    line.map_default(~"", |run| run.text.clone())
+                }).connect("\n");
+                match input_box.control {
+                    None => fmt!("InputRenderBox(value=%s)", value),
+                    Some(control) => fmt!("InputRenderBox(value=%s, control=%?)", value, control),
+                }
+            }
             TextRenderBoxClass(text_box) => {
                 fmt!("TextRenderBox(text=%s)", text_box.run.text.slice_chars(text_box.range.begin(),
                                                                              text_box.range.end()))
@@ -894,6 +1347,108 @@ impl RenderBox {
     // Painting
     //
 
+    /// Finds the value of a color property of the nearest ancestor element, given its computed
+    /// value. rust-css drops colors it can't parse, like `hsl()` and most of the keywords, so
+    /// those are read out of the element's `style` attribute, and out of its ancestors' where
+    /// the value is inherited.
+    ///
+    /// FIXME: Colors rust-css can't parse are only honoured in inline styles.
+    fn color_property(&self, name: &str, computed: CSSColor) -> Color {
+        element_color_property(self.nearest_ancestor_element(), name, computed)
+    }
+
+    /// Adds the background and borders of the non-leaf inline element `element` to the display
+    /// list, over `abs_bounds`: the bounds of the element's boxes on one line, of which this box
+    /// is the first. `first` and `last` say whether the element starts and ends on that line.
+    ///
+    /// Padding and borders go around the boxes. Only the piece an element starts in gets its
+    /// left padding and border, and only the piece it ends in gets its right ones, as with
+    /// `box-decoration-break: slice`.
+    ///
+    /// TODO: The left and right padding and borders should take up room on the line too, once
+    /// there are inline spacer boxes.
+    pub fn paint_inline_element_fragment<E:ExtraDisplayListData>(&self,
+                                                                 element: AbstractNode<LayoutView>,
+                                                                 containing_width: Au,
+                                                                 abs_bounds: &Rect<Au>,
+                                                                 first: bool,
+                                                                 last: bool,
+                                                                 dirty: &Rect<Au>,
+                                                                 list: &Cell<DisplayList<E>>) {
+        let style = element.style();
+        let mut model: BoxModel = Zero::zero();
+        model.compute_borders(style);
+        model.compute_padding(style, containing_width);
+
+        let mut border = model.border;
+        let mut padding = model.padding;
+        if !first {
+            border.left = Au(0);
+            padding.left = Au(0);
+        }
+        if !last {
+            border.right = Au(0);
+            padding.right = Au(0);
+        }
+
+        let left = border.left + padding.left;
+        let top = border.top + padding.top;
+        let bounds = Rect(Point2D(abs_bounds.origin.x - left, abs_bounds.origin.y - top),
+                          Size2D(abs_bounds.size.width + left + padding.right + border.right,
+                                 abs_bounds.size.height + top + padding.bottom + border.bottom));
+        if !bounds.intersects(dirty) {
+            return
+        }
+
+        let colors = element.color_style();
+        let background_color = element_color_property(element,
+                                                      "background-color",
+                                                      colors.background_color());
+        if !background_color.a.approx_eq(&0.0) {
+            do list.with_mut_ref |list| {
+                let solid_color_display_item = ~SolidColorDisplayItem {
+                    base: BaseDisplayItem {
+                        bounds: bounds,
+                        extra: ExtraDisplayListData::new(*self),
+                        metadata: self.display_item_metadata(),
+                    },
+                    color: background_color,
+                };
+
+                list.append_item(SolidColorDisplayItemClass(solid_color_display_item))
+            }
+        }
+
+        if border.is_zero() {
+            return
+        }
+        let top_color = element_color_property(element,
+                                               "border-top-color",
+                                               colors.border_top_color());
+        let right_color = element_color_property(element,
+                                                 "border-right-color",
+                                                 colors.border_right_color());
+        let bottom_color = element_color_property(element,
+                                                  "border-bottom-color",
+                                                  colors.border_bottom_color());
+        let left_color = element_color_property(element,
+                                                "border-left-color",
+                                                colors.border_left_color());
+        do list.with_mut_ref |list| {
+            let border_display_item = ~BorderDisplayItem {
+                base: BaseDisplayItem {
+                    bounds: bounds,
+                    extra: ExtraDisplayListData::new(*self),
+                    metadata: self.display_item_metadata(),
+                },
+                border: border,
+                color: SideOffsets2D::new(top_color, right_color, bottom_color, left_color)
+            };
+
+            list.append_item(BorderDisplayItemClass(border_display_item))
+        }
+    }
+
     /// Adds the display items necessary to paint the borders of this render box to a display list
     /// if necessary.
     pub fn paint_borders_if_applicable<E:ExtraDisplayListData>(&self,
@@ -907,10 +1462,15 @@ impl RenderBox {
             return
         }
 
-        let top_color = self.style().border_top_color();
-        let right_color = self.style().border_right_color();
-        let bottom_color = self.style().border_bottom_color();
-        let left_color = self.style().border_left_color();
+        let style = self.color_style();
+        let top_color = self.color_property("border-top-color",
+                                            style.border_top_color());
+        let right_color = self.color_property("border-right-color",
+                                              style.border_right_color());
+        let bottom_color = self.color_property("border-bottom-color",
+                                               style.border_bottom_color());
+        let left_color = self.color_property("border-left-color",
+                                             style.border_left_color());
 
         // Append the border to the display list.
         do list.with_mut_ref |list| {
@@ -918,18 +1478,55 @@ impl RenderBox {
                 base: BaseDisplayItem {
                     bounds: *abs_bounds,
                     extra: ExtraDisplayListData::new(*self),
+                    metadata: self.display_item_metadata(),
                 },
                 border: SideOffsets2D::new(border.top,
                                            border.right,
                                            border.bottom,
                                            border.left),
-                color: SideOffsets2D::new(top_color.to_gfx_color(),
-                                        right_color.to_gfx_color(),
-                                        bottom_color.to_gfx_color(),
-                                        left_color.to_gfx_color())
+                color: SideOffsets2D::new(top_color, right_color, bottom_color, left_color)
             };
 
             list.append_item(BorderDisplayItemClass(border_display_item))
         }
     }
 }
+
+/// Finds the value of a color property of `element`, given its computed value. See
+/// `RenderBox::color_property`.
+fn element_color_property(element: AbstractNode<LayoutView>, name: &str, computed: CSSColor)
+                          -> Color {
+    // Of the color properties, only `color` itself inherits by default.
+    let inherited = name == "color";
+    let mut element = element;
+    loop {
+        let inherit = match element.inline_color(name) {
+            Some(SpecifiedValue(SpecifiedRGBA(color))) => return color.to_gfx_color(),
+            Some(SpecifiedValue(CurrentColor)) if !inherited => {
+                return element_color_property(element, "color", element.color_style().color())
+            }
+            // rust-css has already resolved these, as far as it could parse the values.
+            Some(Initial) => return computed.to_gfx_color(),
+            Some(SpecifiedValue(CurrentColor)) | Some(Inherit) => true,
+            None => false,
+        };
+
+        // The value is inherited if it was declared so, or if the cascade gave this element
+        // nothing but what its parent has.
+        match element.parent_node() {
+            Some(parent) if parent.is_element() => {
+                if inherit || (inherited && same_color(&parent.color_style().color(), &computed)) {
+                    element = parent
+                } else {
+                    break
+                }
+            }
+            _ => break,
+        }
+    }
+    computed.to_gfx_color()
+}
+
+fn same_color(a: &CSSColor, b: &CSSColor) -> bool {
+    a.red == b.red && a.green == b.green && a.blue == b.blue && a.alpha == b.alpha
+}
@@ -4,17 +4,24 @@
 
 //! Creates CSS boxes from a DOM tree.
 
+use layout::aux::LayoutAuxMethods;
 use layout::block::BlockFlowData;
 use layout::float::FloatFlowData;
-use layout::box::{GenericRenderBoxClass, ImageRenderBox, ImageRenderBoxClass, RenderBox};
+use layout::box::{CanvasRenderBox, CanvasRenderBoxClass, GenericRenderBoxClass};
+use layout::box::{ImageRenderBox, ImageRenderBoxClass, RenderBox, RenderBox_Canvas};
+use layout::box::{InputRenderBox, InputRenderBoxClass, RenderBox_Input};
 use layout::box::{RenderBoxBase, RenderBoxType, RenderBox_Generic, RenderBox_Image};
-use layout::box::{RenderBox_Text, UnscannedTextRenderBox, UnscannedTextRenderBoxClass};
+use layout::box::{RenderBox_Svg, RenderBox_Text, SvgRenderBox, SvgRenderBoxClass};
+use layout::box::{UnscannedTextRenderBox, UnscannedTextRenderBoxClass};
+use layout::box::{RenderBox_Video, VideoRenderBox, VideoRenderBoxClass};
 use layout::context::LayoutContext;
+use layout::incremental::ReshapeText;
 use layout::flow::{AbsoluteFlow, BlockFlow, FloatFlow, Flow_Absolute, Flow_Block, Flow_Float};
 use layout::flow::{Flow_Inline, Flow_InlineBlock, Flow_Root, Flow_Table, FlowContext};
 use layout::flow::{FlowContextType, FlowData, InlineBlockFlow, InlineFlow, TableFlow};
 use layout::inline::{InlineFlowData, InlineLayout};
 use layout::text::TextRunScanner;
+use layout::wrapper::LayoutNodeCast;
 use css::node_style::StyledNode;
 
 use newcss::values::{CSSDisplay, CSSDisplayBlock, CSSDisplayInline, CSSDisplayInlineBlock};
@@ -26,11 +33,25 @@ use newcss::values::{CSSDisplayNone};
 use newcss::values::{CSSFloatNone, CSSFloatLeft, CSSFloatRight};
 use layout::float_context::{FloatLeft, FloatRight};
 use script::dom::element::*;
+use script::dom::htmltextareaelement;
 use script::dom::node::{AbstractNode, CommentNodeTypeId, DoctypeNodeTypeId};
-use script::dom::node::{ElementNodeTypeId, LayoutView, TextNodeTypeId};
+use script::dom::node::{DocumentFragmentNodeTypeId, ElementNodeTypeId, LayoutView};
+use script::dom::node::{NodeTypeId, TextNodeTypeId};
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::canvas_task::SendImage;
+use gfx::color::{Color, RGBA, rgb};
+use gfx::display_list::{CheckboxControl, DropdownControl, RadioControl};
+use gfx::geometry::Au;
+use gfx::path::{PathBuilder, PathShape, parse_path_data};
+use servo_msg::compositor_msg::VideoLayerId;
 use servo_util::range::Range;
 use servo_util::tree::{TreeNodeRef, TreeNode};
 use std::cell::Cell;
+use std::comm;
+use std::float;
+use std::uint;
 
 pub struct LayoutTreeBuilder {
     next_cid: int,
@@ -73,7 +94,7 @@ fn simulate_UA_display_rules(node: AbstractNode<LayoutView>) -> CSSDisplay {
     }
 
     match node.type_id() {
-        DoctypeNodeTypeId | CommentNodeTypeId => CSSDisplayNone,
+        DoctypeNodeTypeId | CommentNodeTypeId | DocumentFragmentNodeTypeId => CSSDisplayNone,
         TextNodeTypeId => CSSDisplayInline,
         ElementNodeTypeId(element_type_id) => {
             match element_type_id {
@@ -231,7 +252,11 @@ impl<'self> BoxGenerator<'self> {
         let result = match ty {
             RenderBox_Generic => GenericRenderBoxClass(@mut base),
             RenderBox_Text => UnscannedTextRenderBoxClass(@mut UnscannedTextRenderBox::new(base)),
+            RenderBox_Canvas => BoxGenerator::make_canvas_box(node, base),
             RenderBox_Image => BoxGenerator::make_image_box(layout_ctx, node, base),
+            RenderBox_Input => BoxGenerator::make_input_box(layout_ctx, node, base),
+            RenderBox_Svg => BoxGenerator::make_svg_box(node, base),
+            RenderBox_Video => BoxGenerator::make_video_box(node, base),
         };
         debug!("BoxGenerator: created box: %s", result.debug_str());
         result
@@ -241,31 +266,331 @@ impl<'self> BoxGenerator<'self> {
                       node: AbstractNode<LayoutView>,
                       base: RenderBoxBase)
                       -> RenderBox {
-        assert!(node.is_image_element());
+        let image_element = node.as_image_element().expect("not an image element");
+        if image_element.image.is_some() {
+            // FIXME(pcwalton): Don't copy URLs.
+            let url = (*image_element.image.get_ref()).clone();
+            ImageRenderBoxClass(@mut ImageRenderBox::new(base, url, layout_ctx.image_cache))
+        } else {
+            info!("Tried to make image box, but couldn't find image. Made generic box \
+                   instead.");
+            GenericRenderBoxClass(@mut base)
+        }
+    }
+
+    /// Makes the box for a canvas, taking a snapshot of what has been drawn on it so far. The
+    /// snapshot is taken after the canvas's task has drawn everything script asked it to before
+    /// this layout.
+    fn make_canvas_box(node: AbstractNode<LayoutView>, base: RenderBoxBase) -> RenderBox {
+        let (size, renderer) = {
+            let canvas = node.as_canvas_element().expect("not a canvas element");
+            (canvas.size(), canvas.renderer())
+        };
+        let image = do renderer.map_move |renderer| {
+            let (port, chan) = comm::stream();
+            renderer.send(SendImage(chan));
+            port.recv()
+        };
+        let size = Size2D(Au::from_px(size.width as int), Au::from_px(size.height as int));
+        CanvasRenderBoxClass(@mut CanvasRenderBox::new(base, image, size))
+    }
+
+    /// Makes the box for an inline `<svg>`, from the geometry of the shapes in it. The box is the
+    /// size of the `<svg>`'s `width` and `height`, 300px by 150px by default; there's no
+    /// `viewBox`, so shapes are drawn at 1px per user unit.
+    fn make_svg_box(node: AbstractNode<LayoutView>, base: RenderBoxBase) -> RenderBox {
+        assert!(node.is_svg_element());
+
+        let size = do node.with_snapshot |element| {
+            Size2D(Au::from_frac_px(svg_length(element, "width", 300.0) as float),
+                   Au::from_frac_px(svg_length(element, "height", 150.0) as float))
+        };
+        let mut shapes = ~[];
+        push_svg_shapes(node, &mut shapes);
+        SvgRenderBoxClass(@mut SvgRenderBox::new(base, shapes, size))
+    }
+
+    /// Makes the box for a `<video>`. The box is the size of its `width` and `height`; a missing
+    /// one follows from the other and the aspect ratio of the video. Until the size of the video
+    /// is known, it's taken to be 300px by 150px.
+    fn make_video_box(node: AbstractNode<LayoutView>, base: RenderBoxBase) -> RenderBox {
+        let (id, video_size) = {
+            let video = node.as_video_element().expect("not a video element");
+            (video.parent.id, video.video_size)
+        };
+        let (width, height) = do node.with_snapshot |element| {
+            let dimension = |name: &str| {
+                element.get_attr(name).chain(|value| uint::from_str(value.trim()))
+            };
+            (dimension("width"), dimension("height"))
+        };
+        let intrinsic = if video_size.width == 0 || video_size.height == 0 {
+            Size2D(300, 150)
+        } else {
+            video_size
+        };
+        let size = match (width, height) {
+            (Some(width), Some(height)) => Size2D(width, height),
+            (Some(width), None) => Size2D(width, width * intrinsic.height / intrinsic.width),
+            (None, Some(height)) => Size2D(height * intrinsic.width / intrinsic.height, height),
+            (None, None) => intrinsic,
+        };
+        let size = Size2D(Au::from_px(size.width as int), Au::from_px(size.height as int));
+        let layer = id.map(|&id| VideoLayerId(*id));
+        VideoRenderBoxClass(@mut VideoRenderBox::new(base, layer, size))
+    }
+
+    /// Makes the box for a text field, shaping its value. The field is wide enough for `size`
+    /// characters, taking a character to be half an em wide, and a line high. Checkboxes and radio
+    /// buttons are a fixed size, and have no borders or padding of their own.
+    fn make_input_box(layout_ctx: &LayoutContext,
+                      node: AbstractNode<LayoutView>,
+                      mut base: RenderBoxBase)
+                      -> RenderBox {
+        if node.is_select_element() {
+            return BoxGenerator::make_select_box(layout_ctx, node, base)
+        }
+        if node.is_textarea_element() {
+            return BoxGenerator::make_textarea_box(layout_ctx, node, base)
+        }
+
+        let state = control_snapshot(node);
+        let control = match state.type_.as_slice() {
+            "checkbox" => Some(CheckboxControl(state.checked)),
+            "radio" => Some(RadioControl(state.checked)),
+            _ => None,
+        };
+        if control.is_some() {
+            return InputRenderBoxClass(@mut InputRenderBox {
+                base: base,
+                control: control,
+                lines: ~[],
+                caret: None,
+                line_height: Au(0),
+                content_size: Size2D(Au::from_px(13), Au::from_px(13)),
+            })
+        }
+
+        let value = state.value.clone();
+        let caret = state.caret.map(|&caret| value.slice_to(caret).char_len());
+        // Password fields show a bullet for each character.
+        let value = if "password" == state.type_.as_slice() {
+            let mut masked = ~"";
+            for _ in value.char_iter() {
+                masked.push_char('•');
+            }
+            masked
+        } else {
+            value
+        };
+        let size = do node.with_snapshot |element| {
+            element.get_attr("size").chain(|size| uint::from_str(size))
+        };
+        let size = size.unwrap_or_default(20);
+
+        let style = node.style();
+        base.model.compute_borders(style);
+        base.model.compute_padding(style, Au(0));
+
+        let input_box = @mut InputRenderBox {
+            base: base,
+            control: None,
+            lines: ~[],
+            caret: caret.map(|&caret| (0, caret)),
+            line_height: Au(0),
+            content_size: Size2D(Au(0), Au(0)),
+        };
+        let box = InputRenderBoxClass(input_box);
+
+        let font_style = box.font_style();
+        let fontgroup = layout_ctx.font_ctx.get_resolved_font_for_style(&font_style);
+        let font = fontgroup.fonts[0];
+        let em_size = font.metrics.em_size;
+        input_box.line_height = em_size.scale_by(1.14);
+        input_box.content_size = Size2D(em_size.scale_by(0.5 * (size as float)),
+                                        input_box.line_height);
+        let run = if value.is_empty() {
+            None
+        } else {
+            let reshape = node.restyle_damage().has(ReshapeText);
+            Some(layout_ctx.text_run_cache.find_or_create(font, value, false, reshape))
+        };
+        input_box.lines = ~[run];
+        box
+    }
+
+    /// A closed select shows its selected option's label, in a box wide enough for any of its
+    /// labels, followed by the button that opens the list of options.
+    fn make_select_box(layout_ctx: &LayoutContext,
+                       node: AbstractNode<LayoutView>,
+                       mut base: RenderBoxBase)
+                       -> RenderBox {
+        let style = node.style();
+        base.model.compute_borders(style);
+        base.model.compute_padding(style, Au(0));
+
+        let input_box = @mut InputRenderBox {
+            base: base,
+            control: Some(DropdownControl),
+            lines: ~[None],
+            caret: None,
+            line_height: Au(0),
+            content_size: Size2D(Au(0), Au(0)),
+        };
+        let box = InputRenderBoxClass(input_box);
+
+        let font_style = box.font_style();
+        let fontgroup = layout_ctx.font_ctx.get_resolved_font_for_style(&font_style);
+        let font = fontgroup.fonts[0];
+        let reshape = node.restyle_damage().has(ReshapeText);
+        let state = control_snapshot(node);
+
+        let mut widest = Au(0);
+        for (i, label) in state.labels.iter().enumerate() {
+            if label.is_empty() {
+                loop
+            }
+            let run = layout_ctx.text_run_cache.find_or_create(font, label.clone(), false,
+                                                               reshape);
+            let width = run.metrics_for_range(&Range::new(0, run.char_len())).advance_width;
+            if width > widest {
+                widest = width;
+            }
+            if state.selected == Some(i) {
+                input_box.lines = ~[Some(run)];
+            }
+        }
+
+        let height = font.metrics.em_size.scale_by(1.14);
+        input_box.line_height = height;
+        input_box.content_size = Size2D(widest + height, height);
+        box
+    }
 
-        do node.with_imm_image_element |image_element| {
-            if image_element.image.is_some() {
-                // FIXME(pcwalton): Don't copy URLs.
-                let url = (*image_element.image.get_ref()).clone();
-                ImageRenderBoxClass(@mut ImageRenderBox::new(base, url, layout_ctx.image_cache))
+    /// A text area is wide enough for `cols` characters and tall enough for `rows` lines. Its
+    /// value breaks into lines at newlines and, unless its `wrap` attribute is `off`, after the
+    /// last space that fits on a line. It shows only `rows` of those lines, scrolled so that the
+    /// caret's line is one of them.
+    fn make_textarea_box(layout_ctx: &LayoutContext,
+                         node: AbstractNode<LayoutView>,
+                         mut base: RenderBoxBase)
+                         -> RenderBox {
+        let state = control_snapshot(node);
+        let (value, caret) = (state.value, state.caret);
+        let (cols, rows, wraps) = do node.with_snapshot |textarea| {
+            (htmltextareaelement::cols(textarea),
+             htmltextareaelement::rows(textarea),
+             htmltextareaelement::wraps(textarea))
+        };
+
+        let style = node.style();
+        base.model.compute_borders(style);
+        base.model.compute_padding(style, Au(0));
+
+        let input_box = @mut InputRenderBox {
+            base: base,
+            control: None,
+            lines: ~[],
+            caret: None,
+            line_height: Au(0),
+            content_size: Size2D(Au(0), Au(0)),
+        };
+        let box = InputRenderBoxClass(input_box);
+
+        let font_style = box.font_style();
+        let fontgroup = layout_ctx.font_ctx.get_resolved_font_for_style(&font_style);
+        let font = fontgroup.fonts[0];
+        let em_size = font.metrics.em_size;
+        let width = em_size.scale_by(0.5 * (cols as float));
+        input_box.line_height = em_size.scale_by(1.14);
+        input_box.content_size = Size2D(width,
+                                        input_box.line_height.scale_by(rows as float));
+        let reshape = node.restyle_damage().has(ReshapeText);
+
+        // The lines, as byte ranges of the value.
+        //
+        // FIXME: Without wrapping, long lines overflow the text area. They should be clipped, and
+        // scrolled sideways to the caret.
+        let mut lines = ~[];
+        let mut start = 0;
+        for logical_line in value.split_iter('\n') {
+            let mut line_start = start;
+            if wraps && !logical_line.is_empty() {
+                let run = layout_ctx.text_run_cache.find_or_create(font,
+                                                                   logical_line.to_owned(),
+                                                                   false,
+                                                                   reshape);
+                let mut advance = Au(0);
+                // The byte and character offsets just past the line's last space.
+                let mut break_after = None;
+                for (i, (offset, ch)) in logical_line.char_offset_iter().enumerate() {
+                    advance = advance + run.metrics_for_range(&Range::new(i, 1)).advance_width;
+                    if advance > width {
+                        match break_after {
+                            Some((byte, char_index)) if byte > line_start => {
+                                lines.push((line_start, byte));
+                                line_start = byte;
+                                let range = Range::new(char_index, i + 1 - char_index);
+                                advance = run.metrics_for_range(&range).advance_width;
+                            }
+                            _ => {}
+                        }
+                    }
+                    if ch == ' ' {
+                        break_after = Some((start + offset + 1, i + 1));
+                    }
+                }
+            }
+            lines.push((line_start, start + logical_line.len()));
+            start = start + logical_line.len() + 1;
+        }
+
+        // Scroll so that the line with the caret is the last one shown, if it's past the rows.
+        let caret = do caret.map |&caret| {
+            let caret_line = match lines.iter().position(|&(line_start, _)| line_start > caret) {
+                Some(next_line) => next_line - 1,
+                None => lines.len() - 1,
+            };
+            let (line_start, line_end) = lines[caret_line];
+            (caret_line, value.slice(line_start, caret.min(&line_end)).char_len())
+        };
+        let first = match caret {
+            Some((caret_line, _)) if caret_line + 1 > rows => caret_line + 1 - rows,
+            _ => 0,
+        };
+
+        for &(line_start, line_end) in lines.iter().skip(first).take(rows) {
+            let line = value.slice(line_start, line_end);
+            if line.is_empty() {
+                input_box.lines.push(None);
             } else {
-                info!("Tried to make image box, but couldn't find image. Made generic box \
-                       instead.");
-                GenericRenderBoxClass(@mut base)
+                let run = layout_ctx.text_run_cache.find_or_create(font,
+                                                                   line.to_owned(),
+                                                                   false,
+                                                                   reshape);
+                input_box.lines.push(Some(run));
             }
         }
+        input_box.caret = caret.map(|&(caret_line, offset)| (caret_line - first, offset));
+        box
     }
 
     fn decide_box_type(&self, node: AbstractNode<LayoutView>, _: CSSDisplay) -> RenderBoxType {
         if node.is_text() {
             RenderBox_Text
+        } else if node.is_canvas_element() {
+            RenderBox_Canvas
+        } else if node.is_svg_element() {
+            RenderBox_Svg
+        } else if node.is_video_element() {
+            RenderBox_Video
         } else if node.is_image_element() {
-            do node.with_imm_image_element |image_element| {
-                match image_element.image {
-                    Some(_) => RenderBox_Image,
-                    None => RenderBox_Generic,
-                }
+            match node.as_image_element().unwrap().image {
+                Some(_) => RenderBox_Image,
+                None => RenderBox_Generic,
             }
+        } else if is_drawn_control(node) {
+            RenderBox_Input
         } else if node.is_element() {
             RenderBox_Generic
         } else {
@@ -359,9 +684,14 @@ impl LayoutTreeBuilder {
         this_generator.push_node(layout_ctx, cur_node, self);
         debug!("point b: %s", cur_node.debug_str());
 
-        // recurse on child nodes.
+        // recurse on child nodes, unless `content-visibility` is skipping them. A text area's
+        // text is its value, which its own box shows, and an `<svg>`'s shapes are drawn by its
+        // box. The children of a `<video>` are its sources and fallback content.
         let prev_gen_cell = Cell::new(Normal(None));
-        for child_node in cur_node.children() {
+        let skip_contents = cur_node.layout_data().skip_contents ||
+            cur_node.is_textarea_element() || cur_node.is_svg_element() ||
+            cur_node.is_video_element();
+        for child_node in cur_node.children().filter(|_| !skip_contents) {
             do parent_generator.with_clone |grandparent_clone| {
                 let grandparent_clone_cell = Cell::new(Some(grandparent_clone));
                 do this_generator.with_clone |parent_clone| {
@@ -435,7 +765,9 @@ impl LayoutTreeBuilder {
 
                 ElementNodeTypeId(_) => CSSDisplayInline,
                 TextNodeTypeId => CSSDisplayInline,
-                DoctypeNodeTypeId | CommentNodeTypeId => return NoGenerator,
+                DoctypeNodeTypeId | CommentNodeTypeId | DocumentFragmentNodeTypeId => {
+                    return NoGenerator
+                }
             }
         };
 
@@ -679,3 +1011,105 @@ impl LayoutTreeBuilder {
         result
     }
 }
+
+/// Adds the shapes in an SVG element to `shapes`, in document order. Elements that aren't shapes,
+/// such as `<g>`, are looked inside, but their attributes don't apply to their children.
+///
+/// FIXME: Only presentation attributes are read; CSS doesn't style SVG.
+fn push_svg_shapes(node: AbstractNode<LayoutView>, shapes: &mut ~[PathShape]) {
+    for child in node.children() {
+        if !child.is_element() {
+            loop
+        }
+        match child.type_id() {
+            ElementNodeTypeId(SVGRectElementTypeId) |
+            ElementNodeTypeId(SVGCircleElementTypeId) |
+            ElementNodeTypeId(SVGPathElementTypeId) => {
+                match child.with_snapshot(|element| svg_shape(child.type_id(), element)) {
+                    Some(shape) => shapes.push(shape),
+                    None => {}
+                }
+            }
+            // Not a shape, but there may be shapes inside.
+            _ => push_svg_shapes(child, shapes),
+        }
+    }
+}
+
+/// Whether `node` is a form control layout draws itself: a text field, checkbox, radio button,
+/// select or text area.
+fn is_drawn_control(node: AbstractNode<LayoutView>) -> bool {
+    if node.is_select_element() || node.is_textarea_element() {
+        return true
+    }
+    if !node.is_input_element() {
+        return false
+    }
+    let control = control_snapshot(node);
+    control.is_text_field() || control.is_checkable()
+}
+
+/// The state of the form control `node` as script last sent it.
+fn control_snapshot(node: AbstractNode<LayoutView>) -> ControlSnapshot {
+    node.with_snapshot(|element| element.control.clone()).unwrap_or_default(ControlSnapshot::new())
+}
+
+/// The shape an SVG shape element of the given type draws, if it draws anything.
+fn svg_shape(type_id: NodeTypeId, element: &ElementSnapshot) -> Option<PathShape> {
+    let mut path = PathBuilder::new();
+    match type_id {
+        ElementNodeTypeId(SVGRectElementTypeId) => {
+            let width = svg_length(element, "width", 0.0);
+            let height = svg_length(element, "height", 0.0);
+            if width > 0.0 && height > 0.0 {
+                path.rect(&Rect(Point2D(svg_length(element, "x", 0.0),
+                                        svg_length(element, "y", 0.0)),
+                                Size2D(width, height)));
+            }
+        }
+        ElementNodeTypeId(SVGCircleElementTypeId) => {
+            let radius = svg_length(element, "r", 0.0);
+            if radius > 0.0 {
+                path.ellipse(Point2D(svg_length(element, "cx", 0.0),
+                                     svg_length(element, "cy", 0.0)),
+                             radius,
+                             radius);
+            }
+        }
+        ElementNodeTypeId(SVGPathElementTypeId) => {
+            for data in element.get_attr("d").iter() {
+                path.subpaths = parse_path_data(*data);
+            }
+        }
+        _ => {}
+    }
+    if path.subpaths.is_empty() {
+        return None
+    }
+    Some(PathShape {
+        subpaths: path.subpaths,
+        fill: svg_paint(element, "fill", Some(rgb(0, 0, 0))),
+        stroke: svg_paint(element, "stroke", None),
+        stroke_width: svg_length(element, "stroke-width", 1.0),
+    })
+}
+
+/// The value of a length attribute, in px, or `default` if it's missing or invalid.
+fn svg_length(element: &ElementSnapshot, name: &str, default: f32) -> f32 {
+    let value = match element.get_attr(name) {
+        Some(value) => value.trim(),
+        None => return default,
+    };
+    let value = if value.ends_with("px") { value.slice_to(value.len() - 2) } else { value };
+    float::from_str(value).map_default(default, |&length| length as f32)
+}
+
+/// The color of a `fill` or `stroke` attribute: `None` for `none`, or `default` if it's missing or
+/// invalid.
+fn svg_paint(element: &ElementSnapshot, name: &str, default: Option<Color>) -> Option<Color> {
+    match element.get_attr(name) {
+        Some(value) if value.trim() == "none" => None,
+        Some(value) => RGBA::parse(value).map_default(default, |color| Some(color.to_gfx_color())),
+        None => default,
+    }
+}
@@ -4,14 +4,38 @@
 
 //! Data needed by the layout task.
 
+use css::length::LengthContext;
+
 use geom::rect::Rect;
 use gfx::font_context::FontContext;
 use gfx::geometry::Au;
+use layout::text::TextRunCache;
+use script::dom::node::{AbstractNode, LayoutView};
 use servo_net::local_image_cache::LocalImageCache;
 
 /// Data needed by the layout task.
 pub struct LayoutContext {
     font_ctx: @mut FontContext,
     image_cache: @mut LocalImageCache,
-    screen_size: Rect<Au>
+    text_run_cache: @mut TextRunCache,
+    screen_size: Rect<Au>,
+    /// The part of the page that's currently scrolled into view.
+    viewport: Rect<Au>,
+    /// The text node with the editing caret, and the caret's byte offset in its text.
+    caret: Option<(AbstractNode<LayoutView>, uint)>,
+    /// Each text node with selected text, and the byte range of its text that's selected.
+    selection: ~[(AbstractNode<LayoutView>, uint, uint)],
+    /// The font size of the root element, which `rem` lengths are relative to.
+    root_font_size: Au,
+}
+
+impl LayoutContext {
+    /// What to resolve lengths on an element with the font size `font_size` against.
+    pub fn length_context(&self, font_size: Au) -> LengthContext {
+        LengthContext {
+            font_size: font_size,
+            root_font_size: self.root_font_size,
+            viewport_size: self.screen_size.size,
+        }
+    }
 }
@@ -297,8 +297,12 @@ impl FloatFlowData {
         if self.common.node.is_iframe_element() {
             error!("float iframe size messaging not implemented yet");
         }
+        let dirty = match self.common.clip_dirty_rect(dirty) {
+            Some(dirty) => dirty,
+            None => return true,
+        };
         let abs_rect = Rect(self.common.abs_position, self.common.position.size);
-        if !abs_rect.intersects(dirty) {
+        if !abs_rect.intersects(&dirty) {
             return true;
         }
 
@@ -306,16 +310,18 @@ impl FloatFlowData {
         let offset = self.common.abs_position + self.rel_pos;
         // add box that starts block context
         self.box.map(|&box| {
-            box.build_display_list(builder, dirty, &offset, list)
+            box.build_display_list(builder, &dirty, &offset, list)
         });
 
 
         // TODO: handle any out-of-flow elements
 
         // go deeper into the flow tree
+        let clip = self.common.clip;
         for child in self.common.child_iter() {
             do child.with_mut_base |base| {
                 base.abs_position = offset + base.position.origin;
+                base.clip = clip;
             }
         }
 
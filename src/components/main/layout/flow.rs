@@ -33,6 +33,7 @@ use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
 use layout::inline::{InlineFlowData};
 use layout::float_context::{FloatContext, Invalid, FloatType};
 use layout::incremental::RestyleDamage;
+use css::contain::Containment;
 use css::node_style::StyledNode;
 use extra::dlist::{DList,MutDListIterator};
 use extra::container::Deque;
@@ -236,6 +237,8 @@ impl<'self> FlowContext {
 pub struct FlowData {
     node: AbstractNode<LayoutView>,
     restyle_damage: RestyleDamage,
+    /// The containment this flow establishes. Only block flows support containment so far.
+    contain: Containment,
 
     children: DList<FlowContext>,
 
@@ -252,6 +255,8 @@ pub struct FlowData {
     floats_out: FloatContext,
     num_floats: uint,
     abs_position: Point2D<Au>,
+    /// The clip rect, in absolute coordinates, imposed by ancestors with paint containment.
+    clip: Option<Rect<Au>>,
     is_inorder: bool,
 }
 
@@ -275,6 +280,7 @@ impl FlowData {
         FlowData {
             node: node,
             restyle_damage: node.restyle_damage(),
+            contain: Containment::none(),
 
             children: DList::new(),
 
@@ -287,6 +293,7 @@ impl FlowData {
             floats_out: Invalid,
             num_floats: 0,
             abs_position: Point2D(Au(0), Au(0)),
+            clip: None,
             is_inorder: false
         }
     }
@@ -295,6 +302,33 @@ impl FlowData {
         self.children.mut_iter()
     }
 
+    /// Intersects the dirty rect with the clip imposed by ancestors with paint containment.
+    /// Returns `None` if nothing in this flow can be painted.
+    pub fn clip_dirty_rect(&self, dirty: &Rect<Au>) -> Option<Rect<Au>> {
+        match self.clip {
+            None => Some(*dirty),
+            Some(ref clip) => dirty.intersection(clip),
+        }
+    }
+
+    /// The clip to impose on the children of this flow, given the absolute bounds of the area
+    /// that paint containment clips them to.
+    pub fn clip_for_children(&self, contained_rect: Rect<Au>) -> Option<Rect<Au>> {
+        if !self.contain.paint {
+            return self.clip;
+        }
+        self.clip_to(contained_rect)
+    }
+
+    /// The clip to impose on the children of this flow when they're clipped to `rect`, in
+    /// absolute coordinates, as well as to what clips this flow.
+    pub fn clip_to(&self, rect: Rect<Au>) -> Option<Rect<Au>> {
+        match self.clip {
+            None => Some(rect),
+            Some(ref clip) => Some(clip.intersection(&rect).unwrap_or(Au::zero_rect())),
+        }
+    }
+
 }
 
 impl<'self> FlowContext {
@@ -401,6 +435,11 @@ impl<'self> FlowContext {
 
         
         debug!("FlowContext: building display list for f%?", self.id());
+        // Everything this flow paints is clipped along with it.
+        let clip = self.with_base(|base| base.clip);
+        do list.with_mut_ref |list| {
+            list.set_clip(clip)
+        }
         match *self {
             BlockFlow(ref mut info)  => info.build_display_list_block(builder, dirty, list),
             InlineFlow(ref mut info) => info.build_display_list_inline(builder, dirty, list),
@@ -24,6 +24,14 @@ pub enum RestyleEffect {
     /// Propagates up the flow tree because the computation is
     /// top-down.
     Reflow = 0x04,
+
+    /// Some flow below this one, inside a subtree with layout containment, needs layout,
+    /// though this flow itself doesn't. Only used to keep traversals from pruning the subtree.
+    ContainedDamage = 0x08,
+
+    /// Shape and measure the node's text again, because its text or font changed. Without this,
+    /// layout reuses the text runs from the last reflow and only breaks lines again.
+    ReshapeText = 0x10,
 }
 
 /// A set of RestyleEffects.
@@ -46,12 +54,12 @@ impl RestyleDamage {
     }
 
     pub fn all() -> RestyleDamage {
-        restyle_damage!(Repaint, BubbleWidths, Reflow)
+        restyle_damage!(Repaint, BubbleWidths, Reflow, ContainedDamage, ReshapeText)
     }
 
-    /// Effects of resizing the window.
+    /// Effects of resizing the window. Text and fonts stay the same, so text isn't reshaped.
     pub fn for_resize() -> RestyleDamage {
-        RestyleDamage::all()
+        restyle_damage!(Repaint, BubbleWidths, Reflow, ContainedDamage)
     }
 
     pub fn is_empty(self) -> bool {
@@ -95,6 +103,17 @@ impl RestyleDamage {
     pub fn propagate_down(self) -> RestyleDamage {
         self.intersect(restyle_damage!(BubbleWidths))
     }
+
+    /// Elements of self which should get set on the parent of a flow with layout containment.
+    /// Nothing inside the contained subtree can change the layout of its ancestors, so they only
+    /// need to know that the subtree must still be visited.
+    pub fn propagate_across_containment(self) -> RestyleDamage {
+        if self.is_empty() {
+            self
+        } else {
+            self.intersect(restyle_damage!(Repaint)).add(ContainedDamage)
+        }
+    }
 }
 
 // NB: We need the braces inside the RHS due to Rust #8012.  This particular
@@ -132,6 +151,9 @@ pub fn compute_damage(node: &AbstractNode<LayoutView>,
           width, height, float, font_family, font_size, font_style, font_weight,
           text_align, text_decoration, line_height ]);
 
+    add_if_not_equal!([ ReshapeText ],
+        [ font_family, font_size, font_style, font_weight, text_decoration ]);
+
     // Handle 'display' specially because it has this 'is_root' parameter.
     let is_root = node.is_root();
     if old.display(is_root) != new.display(is_root) {
@@ -195,4 +217,21 @@ mod restyle_damage_tests {
         assert!(d.lacks(BubbleWidths));
         assert!(d.lacks(Reflow));
     }
+
+    #[test]
+    fn containment_stops_layout_damage() {
+        let d = restyle_damage!(Repaint, BubbleWidths, Reflow).propagate_across_containment();
+        assert!(d.has(Repaint));
+        assert!(d.has(ContainedDamage));
+        assert!(d.lacks(BubbleWidths));
+        assert!(d.lacks(Reflow));
+        assert!(RestyleDamage::none().propagate_across_containment().is_empty());
+    }
+
+    #[test]
+    fn resize_does_not_reshape_text() {
+        assert!(RestyleDamage::for_resize().has(BubbleWidths));
+        assert!(RestyleDamage::for_resize().lacks(ReshapeText));
+        assert!(RestyleDamage::all().has(ReshapeText));
+    }
 }
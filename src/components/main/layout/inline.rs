@@ -3,16 +3,20 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::cell::Cell;
-use layout::box::{CannotSplit, GenericRenderBoxClass, ImageRenderBoxClass, RenderBox};
-use layout::box::{SplitDidFit, SplitDidNotFit, TextRenderBoxClass};
+use css::text::{Direction, LeftToRight, RightToLeft};
+use layout::box::{CanvasRenderBoxClass, CannotSplit, GenericRenderBoxClass, ImageRenderBoxClass};
+use layout::box::{InputRenderBoxClass, RenderBox};
+use layout::box::{SplitDidFit, SplitDidNotFit, SvgRenderBoxClass, TextRenderBoxClass};
+use layout::box::VideoRenderBoxClass;
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
 use layout::flow::{FlowContext, FlowData, InlineFlow};
 use layout::float_context::FloatContext;
-use layout::util::{ElementMapping};
+use layout::util::{ElementMapping, NodeRange};
 use layout::float_context::{PlacementInfo, FloatLeft};
 
 use std::u16;
+use std::uint;
 use std::util;
 use geom::{Point2D, Rect, Size2D};
 use gfx::display_list::DisplayList;
@@ -166,6 +170,21 @@ impl LineboxScanner {
 
     fn box_height(&self, box: RenderBox) -> Au {
         match box {
+            CanvasRenderBoxClass(canvas_box) => {
+                let height = canvas_box.size.height;
+                canvas_box.base.position.size.height = height;
+                height
+            }
+            SvgRenderBoxClass(svg_box) => {
+                let height = svg_box.size.height;
+                svg_box.base.position.size.height = height;
+                height
+            }
+            VideoRenderBoxClass(video_box) => {
+                let height = video_box.size.height;
+                video_box.base.position.size.height = height;
+                height
+            }
             ImageRenderBoxClass(image_box) => {
                 let size = image_box.image.get_size();
                 let height = Au::from_px(size.unwrap_or_default(Size2D(0, 0)).height);
@@ -173,6 +192,11 @@ impl LineboxScanner {
                 debug!("box_height: found image height: %?", height);
                 height
             }
+            InputRenderBoxClass(input_box) => {
+                let height = input_box.border_box_size().height;
+                input_box.base.position.size.height = height;
+                height
+            }
             TextRenderBoxClass(text_box) => {
                 let range = &text_box.range;
                 let run = &text_box.run;
@@ -236,7 +260,7 @@ impl LineboxScanner {
             f_type: FloatLeft
         };
 
-        let line_bounds = self.floats.place_between_floats(&info);
+        let line_bounds = self.indent_first_line(self.floats.place_between_floats(&info), &*flow);
 
         debug!("LineboxScanner: found position for line: %? using placement_info: %?", line_bounds, info);
         
@@ -288,7 +312,8 @@ impl LineboxScanner {
                 };
 
                 info.width = actual_box_width;
-                let new_bounds = self.floats.place_between_floats(&info);
+                let new_bounds = self.indent_first_line(self.floats.place_between_floats(&info),
+                                                        &*flow);
 
                 debug!("LineboxScanner: case=new line position: %?", new_bounds);
                 return (new_bounds, actual_box_width);
@@ -297,6 +322,22 @@ impl LineboxScanner {
         
     }
 
+    /// Leaves room for `text-indent` at the start of the first line.
+    fn indent_first_line(&self, bounds: Rect<Au>, flow: &InlineFlowData) -> Rect<Au> {
+        let indent = flow.first_line_indent;
+        if !self.lines.is_empty() || indent == Au(0) {
+            return bounds
+        }
+
+        let mut bounds = bounds;
+        bounds.size.width = bounds.size.width - indent;
+        match flow.direction {
+            LeftToRight => bounds.origin.x = bounds.origin.x + indent,
+            RightToLeft => {}
+        }
+        bounds
+    }
+
     /// Returns false only if we should break the line.
     fn try_append_to_line(&mut self, in_box: RenderBox, flow: &mut InlineFlowData) -> bool {
         let line_is_empty: bool = self.pending_line.range.length() == 0;
@@ -467,7 +508,12 @@ pub struct InlineFlowData {
     // vec of ranges into boxes that represent elements. These ranges
     // must be well-nested, and are only related to the content of
     // boxes (not lines). Ranges are only kept for non-leaf elements.
-    elems: ElementMapping
+    elems: ElementMapping,
+    /// How far the first line is indented, from `text-indent`. Zero unless this flow holds the
+    /// first line of its block.
+    first_line_indent: Au,
+    /// Which way the lines run, from the `direction` of the block.
+    direction: Direction,
 }
 
 impl InlineFlowData {
@@ -477,6 +523,8 @@ impl InlineFlowData {
             boxes: ~[],
             lines: ~[],
             elems: ElementMapping::new(),
+            first_line_indent: Au(0),
+            direction: LeftToRight,
         }
     }
 
@@ -543,11 +591,23 @@ impl InlineFlowData {
             let this = &mut *self;
             for &box in this.boxes.iter() {
                 match box {
+                    CanvasRenderBoxClass(canvas_box) => {
+                        canvas_box.base.position.size.width = canvas_box.size.width;
+                    }
+                    SvgRenderBoxClass(svg_box) => {
+                        svg_box.base.position.size.width = svg_box.size.width;
+                    }
+                    VideoRenderBoxClass(video_box) => {
+                        video_box.base.position.size.width = video_box.size.width;
+                    }
                     ImageRenderBoxClass(image_box) => {
                         let size = image_box.image.get_size();
                         let width = Au::from_px(size.unwrap_or_default(Size2D(0, 0)).width);
                         image_box.base.position.size.width = width;
                     }
+                    InputRenderBoxClass(input_box) => {
+                        input_box.base.position.size.width = input_box.border_box_size().width;
+                    }
                     TextRenderBoxClass(_) => {
                         // Text boxes are preinitialized.
                     }
@@ -621,6 +681,17 @@ impl InlineFlowData {
                 linebox_align = CSSTextAlignLeft;
             }
 
+            // FIXME: rust-css has no `start`, and gives `left` as the initial value, so
+            // right-to-left lines take `left` to mean the start edge too.
+            //
+            // TODO: The boxes of a right-to-left line should also be laid out right to left.
+            let linebox_align = match (linebox_align, self.direction) {
+                (CSSTextAlignLeft, RightToLeft) | (CSSTextAlignJustify, RightToLeft) => {
+                    CSSTextAlignRight
+                }
+                (align, _) => align,
+            };
+
             // Set the box x positions
             let mut offset_x = line.bounds.origin.x;
             match linebox_align {
@@ -663,6 +734,24 @@ impl InlineFlowData {
                 let cur_box = self.boxes[box_i];
 
                 match cur_box {
+                    CanvasRenderBoxClass(canvas_box) => {
+                        let height = canvas_box.size.height;
+                        canvas_box.base.position.size.height = height;
+
+                        canvas_box.base.position.translate(&Point2D(Au(0), -height))
+                    }
+                    SvgRenderBoxClass(svg_box) => {
+                        let height = svg_box.size.height;
+                        svg_box.base.position.size.height = height;
+
+                        svg_box.base.position.translate(&Point2D(Au(0), -height))
+                    }
+                    VideoRenderBoxClass(video_box) => {
+                        let height = video_box.size.height;
+                        video_box.base.position.size.height = height;
+
+                        video_box.base.position.translate(&Point2D(Au(0), -height))
+                    }
                     ImageRenderBoxClass(image_box) => {
                         let size = image_box.image.get_size();
                         let height = Au::from_px(size.unwrap_or_default(Size2D(0, 0)).height);
@@ -670,6 +759,12 @@ impl InlineFlowData {
 
                         image_box.base.position.translate(&Point2D(Au(0), -height))
                     }
+                    InputRenderBoxClass(input_box) => {
+                        let height = input_box.border_box_size().height;
+                        input_box.base.position.size.height = height;
+
+                        input_box.base.position.translate(&Point2D(Au(0), -height))
+                    }
                     TextRenderBoxClass(text_box) => {
 
                         let range = &text_box.range;
@@ -752,8 +847,12 @@ impl InlineFlowData {
             error!("inline iframe size messaging not implemented yet");
         }
 
+        let dirty = match self.common.clip_dirty_rect(dirty) {
+            Some(dirty) => dirty,
+            None => return true,
+        };
         let abs_rect = Rect(self.common.abs_position, self.common.position.size);
-        if !abs_rect.intersects(dirty) {
+        if !abs_rect.intersects(&dirty) {
             return true;
         }
 
@@ -763,8 +862,10 @@ impl InlineFlowData {
                self.common.id,
                self.boxes.len());
 
+        self.build_display_list_for_inline_elements(&dirty, list);
+
         for box in self.boxes.iter() {
-            box.build_display_list(builder, dirty, &self.common.abs_position, list)
+            box.build_display_list(builder, &dirty, &self.common.abs_position, list)
         }
 
         // TODO(#225): Should `inline-block` elements have flows as children of the inline flow or
@@ -773,5 +874,49 @@ impl InlineFlowData {
         // For now, don't traverse the subtree rooted here
         true
     }
-}
 
+    /// Adds the backgrounds and borders of the non-leaf inline elements in this flow. An element
+    /// broken across lines gets a piece on each line it's on, around its boxes there.
+    fn build_display_list_for_inline_elements<E:ExtraDisplayListData>(
+            &self,
+            dirty: &Rect<Au>,
+            list: &Cell<DisplayList<E>>) {
+        // Elements are mapped as they're closed, so descendants come before their ancestors.
+        // Going backwards puts ancestors underneath.
+        let elems: ~[&NodeRange] = self.elems.eachi().map(|(_, nr)| nr).collect();
+        for nr in elems.rev_iter() {
+            let fragments = self.line_fragments(&nr.range);
+            let count = fragments.len();
+            for (i, &(first_box, bounds)) in fragments.iter().enumerate() {
+                let abs_bounds = bounds.translate(&self.common.abs_position);
+                first_box.paint_inline_element_fragment(nr.node,
+                                                        self.common.position.size.width,
+                                                        &abs_bounds,
+                                                        i == 0,
+                                                        i == count - 1,
+                                                        dirty,
+                                                        list);
+            }
+        }
+    }
+
+    /// Splits the boxes in `elem_range` up by line. Returns the first box on each line the range
+    /// is on, with the bounds of all of its boxes on that line.
+    fn line_fragments(&self, elem_range: &Range) -> ~[(RenderBox, Rect<Au>)] {
+        let mut fragments = ~[];
+        for line in self.lines.iter() {
+            let begin = uint::max(elem_range.begin(), line.range.begin());
+            let end = uint::min(elem_range.end(), line.range.end());
+            if begin >= end {
+                loop
+            }
+
+            let mut bounds = self.boxes[begin].position();
+            for i in range(begin + 1, end) {
+                bounds = bounds.union(&self.boxes[i].position());
+            }
+            fragments.push((self.boxes[begin], bounds));
+        }
+        fragments
+    }
+}
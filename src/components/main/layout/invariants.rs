@@ -0,0 +1,170 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Checks of the structural invariants of the flow tree and of the layout data hanging off the
+//! DOM, run after each layout when the `--check-layout` option is given.
+//!
+//! Breaking one of these invariants doesn't always show up as a rendering bug right away, and when
+//! it does the bug is usually far away from its cause. These checks fail as soon as possible
+//! instead, dumping the flow tree so the culprit can be found.
+
+use css::node_util::NodeUtil;
+use layout::aux::{LayoutAuxMethods, LayoutData, live_layout_data};
+use layout::flow::{BlockFlow, FlowContext, FloatFlow, InlineFlow};
+
+use std::cast::transmute;
+use std::hashmap::HashSet;
+use std::ptr;
+use extra::arc::Arc;
+use gfx::display_list::DisplayList;
+use script::dom::node::{AbstractNode, LayoutView};
+use servo_util::tree::TreeNodeRef;
+
+/// Checks the flow tree after the main layout passes have run over it.
+pub fn check_flow_tree(root: &mut FlowContext) {
+    let mut ids = HashSet::new();
+    let root_node = root.with_base(|base| base.node);
+    match check_flow(root, root_node, &mut ids) {
+        Ok(()) => {}
+        Err(message) => {
+            root.dump();
+            fail!(fmt!("flow tree invariant violated: %s", message))
+        }
+    }
+}
+
+fn check_flow(flow: &mut FlowContext,
+              parent_node: AbstractNode<LayoutView>,
+              ids: &mut HashSet<int>)
+              -> Result<(), ~str> {
+    let (id, node, damage) = do flow.with_base |base| {
+        (base.id, base.node, base.restyle_damage)
+    };
+
+    if !ids.insert(id) {
+        return Err(fmt!("f%d appears twice in the tree", id));
+    }
+    if !is_inclusive_ancestor(parent_node, node) {
+        return Err(fmt!("the node of f%d (%s) isn't in the subtree of its parent's node (%s)",
+                        id, node.debug_str(), parent_node.debug_str()));
+    }
+    if !node.has_layout_data() {
+        return Err(fmt!("the node of f%d (%s) has no layout data", id, node.debug_str()));
+    }
+    if damage.is_nonempty() {
+        return Err(fmt!("f%d still has restyle damage %? after layout", id, damage));
+    }
+
+    // Contents skipped by `content-visibility` must not have been turned into flows.
+    let skips_contents = match *flow {
+        BlockFlow(ref block) => block.skips_contents,
+        _ => false,
+    };
+    if skips_contents && !flow.is_leaf() {
+        return Err(fmt!("f%d skips its contents but has child flows", id));
+    }
+
+    let boxes = match *flow {
+        BlockFlow(*) | FloatFlow(*) | InlineFlow(*) => flow.iter_all_boxes().collect::<~[_]>(),
+        _ => ~[],
+    };
+    for box in boxes.iter() {
+        let box_node = box.node();
+        if !is_inclusive_ancestor(node, box_node) {
+            return Err(fmt!("b%d of f%d belongs to %s, which isn't in the subtree of %s",
+                            box.id(), id, box_node.debug_str(), node.debug_str()));
+        }
+        if !box_node.has_layout_data() {
+            return Err(fmt!("b%d of f%d belongs to %s, which has no layout data",
+                            box.id(), id, box_node.debug_str()));
+        }
+        if box_node.is_element() && !box_node.have_css_select_results() {
+            return Err(fmt!("b%d of f%d belongs to %s, which hasn't been styled",
+                            box.id(), id, box_node.debug_str()));
+        }
+    }
+
+    for child in flow.child_iter() {
+        match check_flow(child, node, ids) {
+            Ok(()) => {}
+            Err(message) => return Err(message),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the layout data of every node in the given subtree is rooted in `refs` exactly once,
+/// without which it could be freed while the node still points at it. Returns how much of the
+/// rooted layout data no node in the subtree points at any more, like that of removed nodes,
+/// which stays alive until the layout task exits.
+pub fn check_layout_data_roots(root: AbstractNode<LayoutView>, refs: &[@mut LayoutData]) -> uint {
+    if refs.len() != live_layout_data() {
+        fail!(fmt!("layout data invariant violated: %u layout data are alive but %u are rooted",
+                   live_layout_data(), refs.len()))
+    }
+    let mut rooted = HashSet::new();
+    for &data in refs.iter() {
+        if !rooted.insert(ptr::to_unsafe_ptr(&*data)) {
+            fail!("layout data invariant violated: layout data is rooted twice")
+        }
+    }
+    let mut attached = 0;
+    for node in root.traverse_preorder() {
+        if !node.has_layout_data() {
+            loop
+        }
+        if !rooted.contains(&ptr::to_unsafe_ptr(&*node.layout_data())) {
+            fail!(fmt!("layout data invariant violated: the layout data of %s isn't rooted",
+                       node.debug_str()))
+        }
+        attached += 1;
+    }
+    refs.len() - attached
+}
+
+/// Checks that the display item ranges recorded in the layout data of every node in the given
+/// subtree point at items for that node in the display list that was just built.
+pub fn check_layout_data(root: AbstractNode<LayoutView>,
+                         display_list: &Arc<DisplayList<AbstractNode<()>>>) {
+    let list = &display_list.get().list;
+    for node in root.traverse_preorder() {
+        if !node.has_layout_data() {
+            loop
+        }
+        let layout_data = node.layout_data();
+        let range = match layout_data.boxes.range {
+            None => loop,
+            Some(range) => range,
+        };
+        // FIXME: Isolate this transmutation into a "bridge" module.
+        let expected_node: AbstractNode<()> = unsafe {
+            transmute(node)
+        };
+        if range.end() > list.len() {
+            fail!(fmt!("layout data invariant violated: the display items of %s (%?) run past \
+                        the end of the display list (%u items)",
+                       node.debug_str(), range, list.len()))
+        }
+        for i in range.eachi() {
+            if list[i].base().extra != expected_node {
+                fail!(fmt!("layout data invariant violated: display item %u is in the range of %s \
+                            but belongs to another node",
+                           i, node.debug_str()))
+            }
+        }
+    }
+}
+
+fn is_inclusive_ancestor(ancestor: AbstractNode<LayoutView>,
+                         node: AbstractNode<LayoutView>)
+                         -> bool {
+    let mut current = Some(node);
+    loop {
+        match current {
+            None => return false,
+            Some(current_node) if current_node == ancestor => return true,
+            Some(current_node) => current = current_node.parent_node(),
+        }
+    }
+}
@@ -6,24 +6,30 @@
 /// rendered.
 
 use css::matching::MatchMethods;
-use css::select::new_css_select_ctx;
-use layout::aux::{LayoutData, LayoutAuxMethods};
+use css::node_style::StyledNode;
+use css::select::{UserStylesheets, author_stylesheets, new_css_select_ctx};
+use layout::aux::{LayoutData, LayoutAuxMethods, live_layout_data};
 use layout::box_builder::LayoutTreeBuilder;
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder};
 use layout::flow::FlowContext;
-use layout::incremental::{RestyleDamage, BubbleWidths};
+use layout::invariants::{check_flow_tree, check_layout_data, check_layout_data_roots};
+use layout::model::computed_font_size;
+use layout::text::TextRunCache;
+use layout::trace::write_trace;
+use layout::incremental::{RestyleDamage, BubbleWidths, ContainedDamage};
 
 use std::cast::transmute;
 use std::cell::Cell;
 use std::comm::{Port};
+use std::hashmap::HashSet;
 use extra::arc::Arc;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
-use gfx::display_list::DisplayList;
+use gfx::display_list::{DisplayList, TextDisplayItemClass};
 use gfx::font_context::FontContext;
-use gfx::geometry::Au;
+use gfx::geometry::{Au, to_frac_px};
 use gfx::opts::Opts;
 use gfx::render_task::{RenderMsg, RenderChan, RenderLayer};
 use newcss::select::SelectCtx;
@@ -31,18 +37,24 @@ use newcss::stylesheet::Stylesheet;
 use newcss::types::OriginAuthor;
 use script::dom::event::ReflowEvent;
 use script::dom::node::{AbstractNode, LayoutView};
-use script::layout_interface::{AddStylesheetMsg, ContentBoxQuery};
+use script::layout_interface::{AddStylesheetMsg, CaretPositionQuery, CaretPositionResponse};
+use script::layout_interface::{ComputedStyleQuery, ComputedStyleResponse, ContentBoxQuery};
 use script::layout_interface::{HitTestQuery, ContentBoxResponse, HitTestResponse};
 use script::layout_interface::{ContentBoxesQuery, ContentBoxesResponse, ExitMsg, LayoutQuery};
-use script::layout_interface::{MatchSelectorsDocumentDamage, Msg};
+use script::layout_interface::{DocumentDamageLevel, MatchSelectorsDocumentDamage, Msg};
 use script::layout_interface::{QueryMsg, Reflow, ReflowDocumentDamage};
-use script::layout_interface::{ReflowForDisplay, ReflowMsg};
+use script::layout_interface::{ReflowForDisplay, ReflowMsg, SetNativeOverlaysMsg};
+use script::layout_interface::{ReplaceStylesheetMsg, ReportMemoryMsg, SetStylesheetEnabledMsg};
+use script::layout_interface::ScrollAreaQuery;
 use script::script_task::{ReflowCompleteMsg, ScriptChan, SendEventMsg};
+use servo_msg::compositor_msg::{NativeOverlay, NativeOverlayGeometry};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId};
 use servo_net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
 use servo_net::local_image_cache::LocalImageCache;
+use servo_util::media_queries::{Device, StylesheetSource};
+use servo_util::memory::{Items, MemoryReport};
 use servo_util::tree::TreeNodeRef;
-use servo_util::time::{ProfilerChan, profile};
+use servo_util::time::{ProfilerChan, profile, profile_url};
 use servo_util::time;
 use servo_util::range::Range;
 use extra::url::Url;
@@ -56,16 +68,49 @@ struct LayoutTask {
     image_cache_task: ImageCacheTask,
     local_image_cache: @mut LocalImageCache,
     font_ctx: @mut FontContext,
+    text_run_cache: @mut TextRunCache,
     doc_url: Option<Url>,
     screen_size: Option<Size2D<Au>>,
+    scroll_offset: Point2D<Au>,
 
     /// This is used to root reader data.
     layout_refs: ~[@mut LayoutData],
 
+    /// How many nodes the document had when it was last laid out.
+    node_count: uint,
+
     display_list: Option<Arc<DisplayList<AbstractNode<()>>>>,
 
     css_select_ctx: @mut SelectCtx,
+
+    /// The style sheets from the command line, which each selection context starts with.
+    user_sheets: UserStylesheets,
+
+    /// The author style sheets, in the order they were added.
+    author_sheets: ~[StylesheetSource],
+
+    /// The indices of the author style sheets that script disabled, e.g. because they're
+    /// alternate style sheets that aren't in the preferred set.
+    disabled_sheets: HashSet<uint>,
+
+    /// Which `@import` and `@media` rules of the author style sheets applied when the selection
+    /// context was last built, or `None` if it needs building again.
+    media_matches: Option<~[bool]>,
+
     profiler_chan: ProfilerChan,
+
+    /// Whether to check the invariants of the flow tree and layout data after each layout.
+    check_layout: bool,
+
+    /// Whether to write a trace of each reflow to a file.
+    layout_trace: bool,
+
+    /// How many reflows have been traced.
+    traced_reflows: uint,
+
+    /// The native overlays the embedder registered, whose elements' geometry goes to the
+    /// compositor with every display list.
+    native_overlays: ~[NativeOverlay],
 }
 
 impl LayoutTask {
@@ -108,6 +153,7 @@ impl LayoutTask {
            profiler_chan: ProfilerChan)
            -> LayoutTask {
         let fctx = @mut FontContext::new(opts.render_backend, true, profiler_chan.clone());
+        let user_sheets = UserStylesheets::load(opts);
 
         LayoutTask {
             id: id,
@@ -118,14 +164,25 @@ impl LayoutTask {
             image_cache_task: image_cache_task.clone(),
             local_image_cache: @mut LocalImageCache(image_cache_task),
             font_ctx: fctx,
+            text_run_cache: @mut TextRunCache::new(),
             doc_url: None,
             screen_size: None,
+            scroll_offset: Point2D(Au(0), Au(0)),
 
             display_list: None,
             
             layout_refs: ~[],
-            css_select_ctx: @mut new_css_select_ctx(),
+            node_count: 0,
+            css_select_ctx: @mut new_css_select_ctx(&user_sheets),
+            user_sheets: user_sheets,
+            author_sheets: ~[],
+            disabled_sheets: HashSet::new(),
+            media_matches: Some(~[]),
             profiler_chan: profiler_chan,
+            check_layout: opts.check_layout,
+            layout_trace: opts.layout_trace,
+            traced_reflows: 0,
+            native_overlays: ~[],
         }
     }
 
@@ -133,28 +190,44 @@ impl LayoutTask {
         while self.handle_request() {
             // Loop indefinitely.
         }
+
+        // Nodes don't keep their layout data alive, so dropping our roots must destroy all of it.
+        self.layout_refs = ~[];
+        let live = live_layout_data();
+        assert!(live == 0, fmt!("layout: %u layout data outlived the layout task", live));
     }
 
     // Create a layout context for use in building display lists, hit testing, &c.
     fn build_layout_context(&self) -> LayoutContext {
         let image_cache = self.local_image_cache;
         let font_ctx = self.font_ctx;
+        let text_run_cache = self.text_run_cache;
         let screen_size = self.screen_size.unwrap();
 
         LayoutContext {
             image_cache: image_cache,
             font_ctx: font_ctx,
+            text_run_cache: text_run_cache,
             screen_size: Rect(Point2D(Au(0), Au(0)), screen_size),
+            viewport: Rect(self.scroll_offset, screen_size),
+            caret: None,
+            selection: ~[],
+            root_font_size: Au::from_px(16),
         }
     }
 
     fn handle_request(&mut self) -> bool {
         match self.port.recv() {
             AddStylesheetMsg(sheet) => self.handle_add_stylesheet(sheet),
+            SetStylesheetEnabledMsg(index, enabled) => {
+                self.handle_set_stylesheet_enabled(index, enabled)
+            }
+            ReplaceStylesheetMsg(index, sheet) => self.handle_replace_stylesheet(index, sheet),
             ReflowMsg(data) => {
+                let url = data.url.to_str();
                 let data = Cell::new(data);
 
-                do profile(time::LayoutPerformCategory, self.profiler_chan.clone()) {
+                do profile_url(time::LayoutPerformCategory, url, self.profiler_chan.clone()) {
                     self.handle_reflow(data.take());
                 }
             }
@@ -164,6 +237,12 @@ impl LayoutTask {
                     self.handle_query(query.take());
                 }
             }
+            SetNativeOverlaysMsg(overlays) => {
+                // Report the new overlays' geometry without waiting for the page to change.
+                self.native_overlays = overlays;
+                self.script_chan.send(SendEventMsg(self.id.clone(), ReflowEvent));
+            }
+            ReportMemoryMsg(reports) => reports.send(self.memory_reports()),
             ExitMsg => {
                 debug!("layout: ExitMsg received");
                 return false
@@ -173,9 +252,79 @@ impl LayoutTask {
         true
     }
 
-    fn handle_add_stylesheet(&self, sheet: Stylesheet) {
-        let sheet = Cell::new(sheet);
-        self.css_select_ctx.append_sheet(sheet.take(), OriginAuthor);
+    /// Style sheets are parsed at the next reflow, when the viewport they're matched for is known.
+    /// Measures the layout data, display list and font caches of this task.
+    fn memory_reports(&self) -> ~[MemoryReport] {
+        let display_items = match self.display_list {
+            Some(ref display_list) => display_list.get().list.len(),
+            None => 0,
+        };
+        let (fonts, shaped_words) = self.font_ctx.cache_sizes();
+        let word_cache_stats = self.font_ctx.word_cache_stats();
+        let report = |path: &str, amount: uint| {
+            MemoryReport {
+                path: path.to_owned(),
+                amount: amount,
+                unit: Items,
+            }
+        };
+        ~[
+            report("dom-nodes", self.node_count),
+            report("layout-data", self.layout_refs.len()),
+            report("display-items", display_items),
+            report("font-cache/fonts", fonts),
+            report("font-cache/shaped-words", shaped_words),
+            report("font-cache/word-hits", word_cache_stats.hits),
+            report("font-cache/word-misses", word_cache_stats.misses),
+        ]
+    }
+
+    fn handle_add_stylesheet(&mut self, sheet: StylesheetSource) {
+        self.author_sheets.push(sheet);
+        self.media_matches = None;
+    }
+
+    fn handle_replace_stylesheet(&mut self, index: uint, sheet: StylesheetSource) {
+        self.author_sheets[index] = sheet;
+        self.media_matches = None;
+    }
+
+    fn handle_set_stylesheet_enabled(&mut self, index: uint, enabled: bool) {
+        let changed = if enabled {
+            self.disabled_sheets.remove(&index)
+        } else {
+            self.disabled_sheets.insert(index)
+        };
+        if changed {
+            self.media_matches = None;
+        }
+    }
+
+    /// Rebuilds the selection context if the author style sheets changed or were enabled or
+    /// disabled, or if the `@media` rules that apply to `device` did. Returns true if it was
+    /// rebuilt and the document needs restyling.
+    fn update_css_select_ctx(&mut self, device: &Device) -> bool {
+        let mut media_matches = ~[];
+        for sheet in self.author_sheets.iter() {
+            media_matches.push_all_move(sheet.media_matches(device));
+        }
+        if self.media_matches == Some(media_matches.clone()) {
+            return false;
+        }
+
+        debug!("layout: rebuilding the selection context for %?", media_matches);
+        let mut ctx = new_css_select_ctx(&self.user_sheets);
+        for (index, source) in self.author_sheets.iter().enumerate() {
+            if self.disabled_sheets.contains(&index) {
+                loop
+            }
+            for sheet in author_stylesheets(source, device).move_iter() {
+                ctx.append_sheet(sheet, OriginAuthor);
+            }
+        }
+        self.css_select_ctx = @mut ctx;
+        self.media_matches = Some(media_matches);
+        true
     }
 
     /// The high-level routine that performs layout tasks.
@@ -197,40 +346,89 @@ impl LayoutTask {
         self.local_image_cache.next_round(self.make_on_image_available_cb(script_chan));
 
         self.doc_url = Some(doc_url);
-        let screen_size = Size2D(Au::from_px(data.window_size.width as int),
-                                 Au::from_px(data.window_size.height as int));
+        let device_pixel_ratio = data.device_pixel_ratio as float;
+        let screen_size = Size2D(Au::from_frac_px(data.window_size.width as float /
+                                                  device_pixel_ratio),
+                                 Au::from_frac_px(data.window_size.height as float /
+                                                  device_pixel_ratio));
         let resized = self.screen_size != Some(screen_size);
         debug!("resized: %?", resized);
         self.screen_size = Some(screen_size);
+        self.scroll_offset = Point2D(Au::from_frac_px(data.scroll_offset.x as float),
+                                     Au::from_frac_px(data.scroll_offset.y as float));
+
+        let device = Device::screen(Size2D(to_frac_px(screen_size.width) as f32,
+                                           to_frac_px(screen_size.height) as f32),
+                                    data.device_pixel_ratio);
+        let mut damage_level: DocumentDamageLevel = data.damage.level;
+        let mut damage_root: AbstractNode<LayoutView> = unsafe {
+            transmute(data.damage.root)
+        };
+        if self.update_css_select_ctx(&device) {
+            damage_level.add(MatchSelectorsDocumentDamage);
+            damage_root = *node;
+        }
 
         // Create a layout context for use throughout the following passes.
         let mut layout_ctx = self.build_layout_context();
+        layout_ctx.caret = unsafe {
+            transmute(data.caret)
+        };
+        layout_ctx.selection = do data.selection.iter().map |&(node, begin, end)| {
+            let node: AbstractNode<LayoutView> = unsafe {
+                transmute(node)
+            };
+            (node, begin, end)
+        }.collect();
 
         // Initialize layout data for each node.
         //
         // FIXME: This is inefficient. We don't need an entire traversal to do this!
         do profile(time::LayoutAuxInitCategory, self.profiler_chan.clone()) {
-            node.initialize_style_for_subtree(&mut self.layout_refs);
-        }
-
-        // Perform CSS selector matching if necessary.
-        match data.damage.level {
-            ReflowDocumentDamage => {}
-            MatchSelectorsDocumentDamage => {
-                do profile(time::LayoutSelectorMatchCategory, self.profiler_chan.clone()) {
-                    node.restyle_subtree(self.css_select_ctx);
+            self.node_count = node.initialize_style_for_subtree(&mut self.layout_refs);
+            for &(element, ref snapshot) in data.snapshots.iter() {
+                let element: AbstractNode<LayoutView> = unsafe {
+                    transmute(element)
+                };
+                let layout_data = element.layout_data();
+                // Parse the `style` attribute here, so that matching doesn't read the element's.
+                let style_changed = match layout_data.snapshot {
+                    None => true,
+                    Some(ref old) => old.get_attr("style") != snapshot.get_attr("style"),
+                };
+                if style_changed {
+                    layout_data.style_attribute = do snapshot.get_attr("style").map |style| {
+                        @Stylesheet::from_attribute(data.url.clone(), *style)
+                    };
                 }
+                layout_data.snapshot = Some(snapshot.clone());
             }
         }
 
-        // Construct the flow tree.
+        // Perform CSS selector matching if necessary. Even if it isn't, contents skipped by
+        // `content-visibility` may have come close enough to the viewport to need styling.
+        // Selectors are only matched again for the damaged subtree.
+        let restyle_root = match damage_level {
+            ReflowDocumentDamage => None,
+            MatchSelectorsDocumentDamage => Some(damage_root),
+        };
+        do profile(time::LayoutSelectorMatchCategory, self.profiler_chan.clone()) {
+            node.restyle_subtree(self.css_select_ctx, &layout_ctx.viewport, false, restyle_root);
+        }
+        if node.is_element() {
+            layout_ctx.root_font_size = computed_font_size(node.style().font_size());
+        }
+
+        // Construct the flow tree. Text runs that the new tree doesn't use are dropped.
         let mut layout_root: FlowContext = do profile(time::LayoutTreeBuilderCategory,
                                                   self.profiler_chan.clone()) {
+            self.text_run_cache.begin_reflow();
             let mut builder = LayoutTreeBuilder::new();
             let layout_root: FlowContext = match builder.construct_trees(&layout_ctx, *node) {
                 Ok(root) => root,
                 Err(*) => fail!(~"Root flow should always exist")
             };
+            self.text_run_cache.end_reflow();
 
             layout_root
         };
@@ -262,7 +460,11 @@ impl LayoutTask {
             };
             for child in flow.child_iter() {
                 do child.with_base |child_base| {
-                    damage.union_in_place(child_base.restyle_damage);
+                    if child_base.contain.layout {
+                        damage.union_in_place(child_base.restyle_damage.propagate_across_containment());
+                    } else {
+                        damage.union_in_place(child_base.restyle_damage);
+                    }
                 }
             }
             do flow.with_mut_base |base| {
@@ -276,23 +478,34 @@ impl LayoutTask {
 
         // Perform the primary layout passes over the flow tree to compute the locations of all
         // the boxes.
-        do profile(time::LayoutMainCategory, self.profiler_chan.clone()) {
-            do layout_root.each_postorder_prune(|f| f.restyle_damage().lacks(BubbleWidths)) |flow| {
-                flow.bubble_widths(&mut layout_ctx);
+        do profile(time::LayoutBubbleWidthsCategory, self.profiler_chan.clone()) {
+            // Damage inside a contained subtree doesn't reach its ancestors, but they have to be
+            // visited to get to it.
+            let prune: &fn(&mut FlowContext) -> bool = |f| {
+                f.restyle_damage().lacks(BubbleWidths) && f.restyle_damage().lacks(ContainedDamage)
+            };
+            do layout_root.each_postorder_prune(prune) |flow| {
+                if flow.restyle_damage().has(BubbleWidths) {
+                    flow.bubble_widths(&mut layout_ctx);
+                }
                 true
             };
+        }
 
-            // FIXME: We want to do
-            //     for flow in layout_root.traverse_preorder_prune(|f| f.restyle_damage().lacks(Reflow)) 
-            // but FloatContext values can't be reused, so we need to recompute them every time.
+        // FIXME: We want to do
+        //     for flow in layout_root.traverse_preorder_prune(|f| f.restyle_damage().lacks(Reflow)) 
+        // but FloatContext values can't be reused, so we need to recompute them every time.
+        do profile(time::LayoutAssignWidthsCategory, self.profiler_chan.clone()) {
             debug!("assigning widths");
             do layout_root.each_preorder |flow| {
                 flow.assign_widths(&mut layout_ctx);
                 true
             };
+        }
 
-            // For now, this is an inorder traversal
-            // FIXME: prune this traversal as well
+        // For now, this is an inorder traversal
+        // FIXME: prune this traversal as well
+        do profile(time::LayoutAssignHeightsCategory, self.profiler_chan.clone()) {
             debug!("assigning height");
             do layout_root.each_bu_sub_inorder |flow| {
                 flow.assign_height(&mut layout_ctx);
@@ -300,6 +513,20 @@ impl LayoutTask {
             };
         }
 
+        // All the damage has been dealt with now.
+        do layout_root.each_preorder |flow| {
+            do flow.with_mut_base |base| {
+                base.restyle_damage = RestyleDamage::none();
+            }
+            true
+        };
+
+        if self.check_layout {
+            check_flow_tree(&mut layout_root);
+            let detached = check_layout_data_roots(*node, self.layout_refs);
+            debug!("layout: %u rooted layout data belong to nodes outside the document", detached);
+        }
+
         // Build the display list if necessary, and send it to the renderer.
         if data.goal == ReflowForDisplay {
             do profile(time::LayoutDispListBuildCategory, self.profiler_chan.clone()) {
@@ -346,10 +573,15 @@ impl LayoutTask {
                     }
                 }
 
+                if self.check_layout {
+                    check_layout_data(*node, &display_list);
+                }
+
                 let render_layer = RenderLayer {
                     display_list: display_list.clone(),
                     size: Size2D(root_size.width.to_nearest_px() as uint,
-                                 root_size.height.to_nearest_px() as uint)
+                                 root_size.height.to_nearest_px() as uint),
+                    native_overlays: self.native_overlay_geometry(*node, display_list.get()),
                 };
 
                 self.display_list = Some(display_list.clone());
@@ -358,6 +590,16 @@ impl LayoutTask {
             } // time(layout: display list building)
         }
 
+        if self.layout_trace {
+            let display_list = if data.goal == ReflowForDisplay {
+                self.display_list.clone()
+            } else {
+                None
+            };
+            self.traced_reflows += 1;
+            write_trace(self.id, self.traced_reflows, &data.url, &mut layout_root, display_list);
+        }
+
         // Tell script that we're done.
         //
         // FIXME(pcwalton): This should probably be *one* channel, but we can't fix this without
@@ -366,6 +608,45 @@ impl LayoutTask {
         data.script_chan.send(ReflowCompleteMsg(self.id));
     }
 
+    /// Finds where the elements of the native overlays are in the given display list. Overlays
+    /// whose elements aren't in the document or weren't painted are left out.
+    fn native_overlay_geometry(&self,
+                               root: AbstractNode<LayoutView>,
+                               display_list: &DisplayList<AbstractNode<()>>)
+                               -> ~[NativeOverlayGeometry] {
+        let mut geometry = ~[];
+        for overlay in self.native_overlays.iter() {
+            let element = do root.traverse_preorder().find |node| {
+                node.is_element() && do node.with_snapshot |element| {
+                    element.get_attr("id") == Some(overlay.element_id.as_slice())
+                }
+            };
+            let element: AbstractNode<()> = match element {
+                Some(element) => unsafe { transmute(element) },
+                None => loop,
+            };
+
+            let mut bounds: Option<Rect<Au>> = None;
+            for item in display_list.list.iter().filter(|item| item.base().extra == element) {
+                bounds = match bounds {
+                    Some(acc) => Some(acc.union(&item.bounds())),
+                    None => Some(item.bounds()),
+                };
+            }
+            for bounds in bounds.iter() {
+                geometry.push(NativeOverlayGeometry {
+                    id: overlay.id,
+                    bounds: Rect(Point2D(to_frac_px(bounds.origin.x) as f32,
+                                         to_frac_px(bounds.origin.y) as f32),
+                                 Size2D(to_frac_px(bounds.size.width) as f32,
+                                        to_frac_px(bounds.size.height) as f32)),
+                    mode: overlay.mode,
+                });
+            }
+        }
+        geometry
+    }
+
     /// Handles a query from the script task. This is the main routine that DOM functions like
     /// `getClientRects()` or `getBoundingClientRect()` ultimately invoke.
     fn handle_query(&self, query: LayoutQuery) {
@@ -431,8 +712,19 @@ impl LayoutTask {
                                     Au::from_frac_px(point.y as float));
                             let mut resp = Err(());
                             // iterate in reverse to ensure we have the most recently painted render box
+                            let mut index = display_list.list.len();
                             for display_item in display_list.list.rev_iter() {
-                                let bounds = display_item.bounds();
+                                index -= 1;
+                                // Items can't be hit where they're clipped away.
+                                let bounds = match display_list.clip_at(index) {
+                                    Some(clip) => {
+                                        match display_item.bounds().intersection(&clip) {
+                                            Some(bounds) => bounds,
+                                            None => loop,
+                                        }
+                                    }
+                                    None => display_item.bounds(),
+                                };
                                 // TODO this check should really be performed by a method of DisplayItem
                                 if x <= bounds.origin.x + bounds.size.width &&
                                     bounds.origin.x <= x &&
@@ -456,6 +748,93 @@ impl LayoutTask {
 
                 reply_chan.send(response)
             }
+            CaretPositionQuery(_, point, reply_chan) => {
+                let response = match self.display_list {
+                    Some(ref list) => {
+                        let (x, y) = (Au::from_frac_px(point.x as float),
+                                      Au::from_frac_px(point.y as float));
+                        let mut resp = Err(());
+                        // Like hit testing, but only text can take the caret.
+                        for display_item in list.get().list.rev_iter() {
+                            let text_item = match *display_item {
+                                TextDisplayItemClass(ref text_item) => text_item,
+                                _ => loop,
+                            };
+                            let bounds = text_item.base.bounds;
+                            if x < bounds.origin.x || bounds.origin.x + bounds.size.width < x ||
+                                    y < bounds.origin.y || bounds.origin.y + bounds.size.height < y {
+                                loop
+                            }
+
+                            // The caret goes before the first character whose middle is past the
+                            // point.
+                            let mut caret = text_item.range.begin();
+                            let mut advance = bounds.origin.x;
+                            for &width in text_item.glyph_run.char_advances.iter() {
+                                if x < advance + width.scale_by(0.5) {
+                                    break
+                                }
+                                advance = advance + width;
+                                caret += 1;
+                            }
+
+                            // The run may hold other nodes' text before this node's, which starts
+                            // where the node's first text item does.
+                            let node: AbstractNode<LayoutView> = unsafe {
+                                transmute(text_item.base.extra)
+                            };
+                            let mut node_start = text_item.range.begin();
+                            for node_items in node.layout_data().boxes.range.iter() {
+                                for i in node_items.eachi() {
+                                    match list.get().list[i] {
+                                        TextDisplayItemClass(ref item) => {
+                                            node_start = node_start.min(&item.range.begin())
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                            }
+                            resp = Ok(CaretPositionResponse(node, caret - node_start));
+                            break
+                        }
+                        resp
+                    }
+                    None => {
+                        error!("Can't place the caret: no display list");
+                        Err(())
+                    }
+                };
+
+                reply_chan.send(response)
+            }
+            ScrollAreaQuery(node, reply_chan) => {
+                let node: AbstractNode<LayoutView> = unsafe {
+                    transmute(node)
+                };
+                let scroll_area = if node.has_layout_data() {
+                    node.layout_data().scroll_area
+                } else {
+                    None
+                };
+                let response = match scroll_area {
+                    Some(area) => Ok(area),
+                    None => Err(()),
+                };
+                reply_chan.send(response)
+            }
+            ComputedStyleQuery(node, reply_chan) => {
+                let node: AbstractNode<LayoutView> = unsafe {
+                    transmute(node)
+                };
+                let styled = node.is_element() && node.has_layout_data() &&
+                    node.layout_data().style.is_some();
+                let response = if styled {
+                    Ok(ComputedStyleResponse(node.computed_values()))
+                } else {
+                    Err(())
+                };
+                reply_chan.send(response)
+            }
         }
     }
 
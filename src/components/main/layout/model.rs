@@ -24,6 +24,14 @@ pub struct BoxModel {
     content_box_width: Au,
 }
 
+/// The font size in a computed style, which is always absolute.
+pub fn computed_font_size(font_size: CSSFontSize) -> Au {
+    match font_size {
+        CSSFontSizeLength(Px(px)) => Au::from_frac_px(px),
+        _ => fail!("expected non-relative font size")
+    }
+}
+
 fn from_length(length: Length, font_size: CSSFontSize) -> Au {
     match length {
         Px(v) => Au::from_frac_px(v),
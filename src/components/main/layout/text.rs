@@ -4,20 +4,29 @@
 
 //! Text layout.
 
+use std::hashmap::HashMap;
+use std::managed::mut_ptr_eq;
 use std::vec;
 
+use css::node_style::StyledNode;
+use gfx::font::Font;
 use gfx::text::text_run::TextRun;
 use gfx::text::util::{CompressWhitespaceNewline, transform_text};
 use layout::box::{RenderBox, RenderBoxBase, TextRenderBox};
 use layout::box::{TextRenderBoxClass, UnscannedTextRenderBoxClass};
 use layout::context::LayoutContext;
 use layout::flow::FlowContext;
+use layout::incremental::ReshapeText;
 use newcss::values::{CSSTextDecoration, CSSTextDecorationUnderline};
 use servo_util::range::Range;
 
 
-/// Creates a TextRenderBox from a range and a text run.
-pub fn adapt_textbox_with_range(mut base: RenderBoxBase, run: @TextRun, range: Range)
+/// Creates a TextRenderBox from a range and a text run, whose text for the box's node starts at
+/// `node_start`.
+pub fn adapt_textbox_with_range(mut base: RenderBoxBase,
+                                run: @TextRun,
+                                range: Range,
+                                node_start: uint)
                                 -> TextRenderBox {
     debug!("Creating textbox with span: (strlen=%u, off=%u, len=%u) of textrun (%s) (len=%u)",
            run.char_len(),
@@ -37,6 +46,7 @@ pub fn adapt_textbox_with_range(mut base: RenderBoxBase, run: @TextRun, range: R
         base: base,
         run: run,
         range: range,
+        node_start: node_start,
     }
 }
 
@@ -54,6 +64,86 @@ impl UnscannedMethods for RenderBox {
     }
 }
 
+/// Text runs kept from one reflow to the next. Shaping text, finding its break opportunities and
+/// measuring it are the expensive parts of text layout, and reflows that change no text or fonts,
+/// like the ones after a window resize, can reuse all of it and only break lines again.
+pub struct TextRunCache {
+    priv runs: HashMap<~str, ~[CachedTextRun]>,
+    /// Incremented at the start of every reflow. Runs that a reflow didn't use are dropped at its
+    /// end.
+    priv generation: uint,
+}
+
+struct CachedTextRun {
+    font: @mut Font,
+    underline: bool,
+    run: @TextRun,
+    /// The last reflow that used this run.
+    generation: uint,
+}
+
+impl TextRunCache {
+    pub fn new() -> TextRunCache {
+        TextRunCache {
+            runs: HashMap::new(),
+            generation: 0,
+        }
+    }
+
+    pub fn begin_reflow(&mut self) {
+        self.generation += 1;
+    }
+
+    /// Drops the runs that the reflow that just finished didn't use.
+    pub fn end_reflow(&mut self) {
+        let generation = self.generation;
+        let mut unused = ~[];
+        for (text, cached_runs) in self.runs.mut_iter() {
+            cached_runs.retain(|cached| cached.generation == generation);
+            if cached_runs.is_empty() {
+                unused.push(text.clone());
+            }
+        }
+        for text in unused.iter() {
+            self.runs.remove(text);
+        }
+    }
+
+    /// Returns the run of `text` in `font`, creating it if this reflow or the last one didn't.
+    /// If `reshape` is set, the style of the text changed since then, and the run is created
+    /// again regardless.
+    pub fn find_or_create(&mut self, font: @mut Font, text: ~str, underline: bool, reshape: bool)
+                          -> @TextRun {
+        let generation = self.generation;
+        if !reshape {
+            match self.runs.find_mut(&text) {
+                Some(cached_runs) => {
+                    for cached in cached_runs.mut_iter() {
+                        if mut_ptr_eq(cached.font, font) && cached.underline == underline {
+                            debug!("TextRunCache: reusing run for %?", text);
+                            cached.generation = generation;
+                            return cached.run;
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+
+        let run = @TextRun::new(font, text.clone(), underline);
+        let cached_runs = self.runs.find_or_insert_with(text, |_| ~[]);
+        cached_runs.retain(|cached| !mut_ptr_eq(cached.font, font) ||
+                                    cached.underline != underline);
+        cached_runs.push(CachedTextRun {
+            font: font,
+            underline: underline,
+            run: run,
+            generation: generation,
+        });
+        run
+    }
+}
+
 /// A stack-allocated object for scanning an inline flow into `TextRun`-containing `TextBox`es.
 struct TextRunScanner {
     clump: Range,
@@ -135,6 +225,12 @@ impl TextRunScanner {
             }
         }
 
+        // Whether the style of the box's text changed since the last reflow in a way that makes
+        // the text runs kept from it stale.
+        fn needs_reshaping(box: &RenderBox) -> bool {
+            box.nearest_ancestor_element().restyle_damage().has(ReshapeText)
+        }
+
         assert!(self.clump.length() > 0);
 
         debug!("TextRunScanner: flushing boxes in range=%?", self.clump);
@@ -171,12 +267,15 @@ impl TextRunScanner {
                     // font group fonts. This is probably achieved by creating the font group above
                     // and then letting `FontGroup` decide which `Font` to stick into the text run.
                     let fontgroup = ctx.font_ctx.get_resolved_font_for_style(&font_style);
-                    let run = @fontgroup.create_textrun(transformed_text, underline);
+                    let run = ctx.text_run_cache.find_or_create(fontgroup.fonts[0],
+                                                                transformed_text,
+                                                                underline,
+                                                                needs_reshaping(&old_box));
 
                     debug!("TextRunScanner: pushing single text box in range: %? (%?)", self.clump, text);
                     let new_box = do old_box.with_base |old_box_base| {
                         let range = Range::new(0, run.char_len());
-                        @mut adapt_textbox_with_range(*old_box_base, run, range)
+                        @mut adapt_textbox_with_range(*old_box_base, run, range, 0)
                     };
 
                     out_boxes.push(TextRenderBoxClass(new_box));
@@ -221,12 +320,16 @@ impl TextRunScanner {
                 let font_style = in_boxes[self.clump.begin()].font_style();
                 let fontgroup = ctx.font_ctx.get_resolved_font_for_style(&font_style);
                 let underline = has_underline(in_boxes[self.clump.begin()].text_decoration());
+                let reshape = self.clump.eachi().any(|i| needs_reshaping(&in_boxes[i]));
 
                 // TextRuns contain a cycle which is usually resolved by the teardown
                 // sequence. If no clump takes ownership, however, it will leak.
                 let clump = self.clump;
                 let run = if clump.length() != 0 && run_str.len() > 0 {
-                    Some(@TextRun::new(fontgroup.fonts[0], run_str, underline))
+                    Some(ctx.text_run_cache.find_or_create(fontgroup.fonts[0],
+                                                           run_str,
+                                                           underline,
+                                                           reshape))
                 } else {
                     None
                 };
@@ -243,7 +346,10 @@ impl TextRunScanner {
                     }
 
                     do in_boxes[i].with_base |base| {
-                        let new_box = @mut adapt_textbox_with_range(*base, run.unwrap(), range);
+                        let new_box = @mut adapt_textbox_with_range(*base,
+                                                                    run.unwrap(),
+                                                                    range,
+                                                                    range.begin());
                         out_boxes.push(TextRenderBoxClass(new_box));
                     }
                 }
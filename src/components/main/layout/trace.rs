@@ -0,0 +1,150 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Traces of layout, written with `--debug layout-trace` so that layout bugs can be looked into
+//! without reading through `dump()` output. After each reflow, the flow tree and the display list,
+//! if one was built, are written as JSON to `layout-trace-PIPELINE-REFLOW.json` in the working
+//! directory, where `REFLOW` counts the reflows of the pipeline from 1.
+//!
+//! Flows and boxes are given with their debugging ids, and lengths in px. Flows, boxes and display
+//! items name the node they were made for by its address, so they can be matched up.
+
+use layout::box::{CanvasRenderBoxClass, GenericRenderBoxClass, ImageRenderBoxClass};
+use layout::box::{InputRenderBoxClass, RenderBox, SvgRenderBoxClass, TextRenderBoxClass};
+use layout::box::{UnscannedTextRenderBoxClass, VideoRenderBoxClass};
+use layout::flow::{AbsoluteFlow, BlockFlow, FloatFlow, FlowContext, InlineBlockFlow, InlineFlow};
+use layout::flow::TableFlow;
+
+use std::io;
+use extra::arc::Arc;
+use extra::json::{Json, List, Null, Number, Object, String};
+use extra::treemap::TreeMap;
+use extra::url::Url;
+use geom::rect::Rect;
+use gfx::display_list::{BorderDisplayItemClass, CaretDisplayItemClass, ControlDisplayItemClass};
+use gfx::display_list::{DisplayItem, DisplayList, ImageDisplayItemClass, PathDisplayItemClass};
+use gfx::display_list::{SelectionDisplayItemClass, SolidColorDisplayItemClass};
+use gfx::display_list::{TextDisplayItemClass, VideoDisplayItemClass};
+use gfx::geometry::{Au, to_frac_px};
+use script::dom::node::AbstractNode;
+use servo_msg::constellation_msg::PipelineId;
+
+/// Writes the trace of a reflow of the document at `url` in the given pipeline.
+pub fn write_trace(pipeline_id: PipelineId,
+                   reflow: uint,
+                   url: &Url,
+                   layout_root: &mut FlowContext,
+                   display_list: Option<Arc<DisplayList<AbstractNode<()>>>>) {
+    let PipelineId(id) = pipeline_id;
+    let display_list = match display_list {
+        Some(display_list) => display_list_json(display_list.get()),
+        None => Null,
+    };
+    let trace = object(~[(~"pipeline", Number(id as float)),
+                         (~"reflow", Number(reflow as float)),
+                         (~"url", String(url.to_str())),
+                         (~"flow_tree", flow_json(layout_root)),
+                         (~"display_list", display_list)]);
+
+    let path = Path(fmt!("layout-trace-%u-%u.json", id, reflow));
+    match io::file_writer(&path, [io::Create, io::Truncate]) {
+        Ok(writer) => writer.write_str(trace.to_pretty_str()),
+        Err(e) => error!("layout: couldn't write %s: %s", path.to_str(), e),
+    }
+}
+
+fn flow_json(flow: &mut FlowContext) -> Json {
+    let (kind, boxes): (&str, ~[Json]) = match *flow {
+        AbsoluteFlow(*) => ("absolute", ~[]),
+        BlockFlow(ref block) => ("block", block.box.iter().map(|box| box_json(box)).collect()),
+        FloatFlow(ref float) => ("float", float.box.iter().map(|box| box_json(box)).collect()),
+        InlineBlockFlow(*) => ("inline_block", ~[]),
+        InlineFlow(ref inline) => {
+            ("inline", inline.boxes.iter().map(|box| box_json(box)).collect())
+        }
+        TableFlow(*) => ("table", ~[]),
+    };
+    let fields = do flow.with_base |base| {
+        ~[(~"id", Number(base.id as float)),
+          (~"type", String(kind.to_owned())),
+          (~"node", node_json(base.node)),
+          (~"bounds", rect_json(&base.position)),
+          (~"floats", Number(base.num_floats as float))]
+    };
+    let mut children = ~[];
+    for child in flow.child_iter() {
+        children.push(flow_json(child));
+    }
+    object(fields + ~[(~"boxes", List(boxes)), (~"children", List(children))])
+}
+
+fn box_json(box: &RenderBox) -> Json {
+    let kind = match *box {
+        GenericRenderBoxClass(*) => "generic",
+        CanvasRenderBoxClass(*) => "canvas",
+        ImageRenderBoxClass(*) => "image",
+        InputRenderBoxClass(*) => "input",
+        SvgRenderBoxClass(*) => "svg",
+        TextRenderBoxClass(*) => "text",
+        UnscannedTextRenderBoxClass(*) => "unscanned_text",
+        VideoRenderBoxClass(*) => "video",
+    };
+    object(~[(~"id", Number(box.id() as float)),
+             (~"type", String(kind.to_owned())),
+             (~"node", node_json(box.node())),
+             (~"bounds", rect_json(&box.position()))])
+}
+
+fn display_list_json(display_list: &DisplayList<AbstractNode<()>>) -> Json {
+    let mut items = ~[];
+    for (index, item) in display_list.list.iter().enumerate() {
+        let clip = match display_list.clip_at(index) {
+            Some(clip) => rect_json(&clip),
+            None => Null,
+        };
+        items.push(object(~[(~"type", String(display_item_kind(item).to_owned())),
+                            (~"bounds", rect_json(&item.bounds())),
+                            (~"clip", clip),
+                            (~"node", node_json(item.base().extra))]));
+    }
+    List(items)
+}
+
+fn display_item_kind<E>(item: &DisplayItem<E>) -> &'static str {
+    match *item {
+        SolidColorDisplayItemClass(*) => "solid_color",
+        TextDisplayItemClass(*) => "text",
+        ImageDisplayItemClass(*) => "image",
+        BorderDisplayItemClass(*) => "border",
+        CaretDisplayItemClass(*) => "caret",
+        ControlDisplayItemClass(*) => "control",
+        SelectionDisplayItemClass(*) => "selection",
+        PathDisplayItemClass(*) => "path",
+        VideoDisplayItemClass(*) => "video",
+    }
+}
+
+/// Names a node by its address.
+fn node_json<View>(node: AbstractNode<View>) -> Json {
+    String(fmt!("0x%x", unsafe { node.raw_object() } as uint))
+}
+
+fn rect_json(rect: &Rect<Au>) -> Json {
+    object(~[(~"x", px(rect.origin.x)),
+             (~"y", px(rect.origin.y)),
+             (~"width", px(rect.size.width)),
+             (~"height", px(rect.size.height))])
+}
+
+fn px(length: Au) -> Json {
+    Number(to_frac_px(length))
+}
+
+fn object(fields: ~[(~str, Json)]) -> Json {
+    let mut object = ~TreeMap::new();
+    for (name, value) in fields.move_iter() {
+        object.insert(name, value);
+    }
+    Object(object)
+}
@@ -0,0 +1,76 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Checked downcasts of DOM nodes to the types of node layout looks into. Each cast checks the
+//! node's type before borrowing it as that type, so the one unsafe cast here is all there is: a
+//! new kind of replaced element only needs another method that says which type ID it has.
+
+use script::dom::bindings::utils::rust_box;
+use script::dom::element::Element;
+use script::dom::htmlcanvaselement::HTMLCanvasElement;
+use script::dom::htmliframeelement::HTMLIFrameElement;
+use script::dom::htmlimageelement::HTMLImageElement;
+use script::dom::htmlvideoelement::HTMLVideoElement;
+use script::dom::node::{AbstractNode, LayoutView, Text};
+
+use std::cast::transmute;
+
+/// Borrows layout's view of a node as one of the DOM types it reads from, if the node is of that
+/// type. Script mustn't change the node while layout holds one of these borrows, which holds
+/// while the layout task has the DOM.
+pub trait LayoutNodeCast {
+    fn as_element<'a>(&'a self) -> Option<&'a Element>;
+    fn as_text<'a>(&'a self) -> Option<&'a Text>;
+    fn as_image_element<'a>(&'a self) -> Option<&'a HTMLImageElement>;
+    fn as_canvas_element<'a>(&'a self) -> Option<&'a HTMLCanvasElement>;
+    fn as_video_element<'a>(&'a self) -> Option<&'a HTMLVideoElement>;
+    fn as_iframe_element<'a>(&'a self) -> Option<&'a HTMLIFrameElement>;
+    /// Layout tells iframes where their frames are, so that they can tell the constellation.
+    fn as_mut_iframe_element<'a>(&'a self) -> Option<&'a mut HTMLIFrameElement>;
+}
+
+impl LayoutNodeCast for AbstractNode<LayoutView> {
+    fn as_element<'a>(&'a self) -> Option<&'a Element> {
+        downcast(self, self.is_element())
+    }
+    fn as_text<'a>(&'a self) -> Option<&'a Text> {
+        downcast(self, self.is_text())
+    }
+    fn as_image_element<'a>(&'a self) -> Option<&'a HTMLImageElement> {
+        downcast(self, self.is_image_element())
+    }
+    fn as_canvas_element<'a>(&'a self) -> Option<&'a HTMLCanvasElement> {
+        downcast(self, self.is_canvas_element())
+    }
+    fn as_video_element<'a>(&'a self) -> Option<&'a HTMLVideoElement> {
+        downcast(self, self.is_video_element())
+    }
+    fn as_iframe_element<'a>(&'a self) -> Option<&'a HTMLIFrameElement> {
+        downcast(self, self.is_iframe_element())
+    }
+    fn as_mut_iframe_element<'a>(&'a self) -> Option<&'a mut HTMLIFrameElement> {
+        downcast_mut(self, self.is_iframe_element())
+    }
+}
+
+/// Borrows `node` as a `T`, if `is_a_t` says that's what it is.
+fn downcast<'a, T>(node: &'a AbstractNode<LayoutView>, is_a_t: bool) -> Option<&'a T> {
+    if !is_a_t {
+        return None
+    }
+    unsafe {
+        let box: *rust_box<T> = transmute(node.raw_object());
+        Some(transmute(&(*box).payload))
+    }
+}
+
+fn downcast_mut<'a, T>(node: &'a AbstractNode<LayoutView>, is_a_t: bool) -> Option<&'a mut T> {
+    if !is_a_t {
+        return None
+    }
+    unsafe {
+        let box: *mut rust_box<T> = transmute(node.raw_object());
+        Some(transmute(&mut (*box).payload))
+    }
+}
@@ -9,18 +9,21 @@ use gfx::render_task::{PaintPermissionGranted, PaintPermissionRevoked};
 use gfx::render_task;
 use gfx::opts::Opts;
 use layout::layout_task::LayoutTask;
-use script::layout_interface::LayoutChan;
+use script::layout_interface::{LayoutChan, ReportMemoryMsg};
 use script::script_task::{ExecuteMsg, LoadMsg};
-use servo_msg::constellation_msg::{ConstellationChan, PipelineId, SubpageId};
+use servo_msg::constellation_msg::{ConstellationChan, FailureMsg, PipelineId, SubpageId};
 use script::dom::node::AbstractNode;
 use script::script_task::{AttachLayoutMsg, NewLayoutInfo, ScriptTask, ScriptChan};
 use script::script_task;
 use servo_net::image_cache_task::ImageCacheTask;
-use servo_net::resource_task::ResourceTask;
+use servo_net::resource_task::{LoadData, ResourceTask};
+use servo_util::memory::{MemoryProfilerChan, RegisterReporterMsg};
 use servo_util::time::ProfilerChan;
 use geom::size::Size2D;
 use extra::future::Future;
+use std::cell::Cell;
 use std::comm;
+use std::task;
 
 /// A uniquely-identifiable pipeline of stript task, layout task, and render task. 
 #[deriving(Clone)]
@@ -42,6 +45,7 @@ impl Pipeline {
                        compositor_chan: CompositorChan,
                        image_cache_task: ImageCacheTask,
                        profiler_chan: ProfilerChan,
+                       memory_profiler_chan: MemoryProfilerChan,
                        opts: Opts,
                        script_pipeline: &Pipeline,
                        size_future: Future<Size2D<uint>>) -> Pipeline {
@@ -49,20 +53,27 @@ impl Pipeline {
         let (layout_port, layout_chan) = special_stream!(LayoutChan);
         let (render_port, render_chan) = special_stream!(RenderChan);
 
-        RenderTask::create(id,
-                           render_port,
-                           compositor_chan.clone(),
-                           opts.clone(),
-                           profiler_chan.clone());
-
-        LayoutTask::create(id,
-                           layout_port,
-                           constellation_chan,
-                           script_pipeline.script_chan.clone(),
-                           render_chan.clone(),
-                           image_cache_task.clone(),
-                           opts.clone(),
-                           profiler_chan);
+        let ports = Cell::new((layout_port, render_port));
+        let script_chan = script_pipeline.script_chan.clone();
+        let layout_render_chan = render_chan.clone();
+        let failure_chan = constellation_chan.clone();
+        do spawn_pipeline_tasks(id, subpage_id, failure_chan) {
+            let (layout_port, render_port) = ports.take();
+            RenderTask::create(id,
+                               render_port,
+                               compositor_chan.clone(),
+                               opts.clone(),
+                               profiler_chan.clone());
+
+            LayoutTask::create(id,
+                               layout_port,
+                               constellation_chan.clone(),
+                               script_chan.clone(),
+                               layout_render_chan.clone(),
+                               image_cache_task.clone(),
+                               opts.clone(),
+                               profiler_chan.clone());
+        }
 
         let new_layout_info = NewLayoutInfo {
             old_id: script_pipeline.id.clone(),
@@ -72,6 +83,7 @@ impl Pipeline {
         };
 
         script_pipeline.script_chan.send(AttachLayoutMsg(new_layout_info));
+        register_layout_reporter(id, &layout_chan, &memory_profiler_chan);
 
         Pipeline::new(id,
                       subpage_id,
@@ -88,6 +100,7 @@ impl Pipeline {
                   image_cache_task: ImageCacheTask,
                   resource_task: ResourceTask,
                   profiler_chan: ProfilerChan,
+                  memory_profiler_chan: MemoryProfilerChan,
                   opts: Opts,
                   size: Future<Size2D<uint>>) -> Pipeline {
 
@@ -95,31 +108,43 @@ impl Pipeline {
         let (layout_port, layout_chan) = special_stream!(LayoutChan);
         let (render_port, render_chan) = special_stream!(RenderChan);
 
-        ScriptTask::create(id,
-                           compositor_chan.clone(),
-                           layout_chan.clone(),
-                           script_port,
-                           script_chan.clone(),
-                           constellation_chan.clone(),
-                           resource_task,
-                           image_cache_task.clone(),
-                           size);
-
-
-        RenderTask::create(id,
-                           render_port,
-                           compositor_chan.clone(),
-                           opts.clone(),
-                           profiler_chan.clone());
-
-        LayoutTask::create(id,
-                           layout_port,
-                           constellation_chan,
-                           script_chan.clone(),
-                           render_chan.clone(),
-                           image_cache_task,
-                           opts.clone(),
-                           profiler_chan);
+        let tasks = Cell::new((script_port, layout_port, render_port, resource_task, size));
+        let (task_script_chan, task_layout_chan) = (script_chan.clone(), layout_chan.clone());
+        let task_render_chan = render_chan.clone();
+        let failure_chan = constellation_chan.clone();
+        do spawn_pipeline_tasks(id, subpage_id, failure_chan) {
+            let (script_port, layout_port, render_port, resource_task, size) = tasks.take();
+            ScriptTask::create(id,
+                               compositor_chan.clone(),
+                               task_layout_chan.clone(),
+                               script_port,
+                               task_script_chan.clone(),
+                               constellation_chan.clone(),
+                               resource_task,
+                               image_cache_task.clone(),
+                               profiler_chan.clone(),
+                               size,
+                               opts.script_delay,
+                               opts.user_agent.clone());
+
+
+            RenderTask::create(id,
+                               render_port,
+                               compositor_chan.clone(),
+                               opts.clone(),
+                               profiler_chan.clone());
+
+            LayoutTask::create(id,
+                               layout_port,
+                               constellation_chan.clone(),
+                               task_script_chan.clone(),
+                               task_render_chan.clone(),
+                               image_cache_task.clone(),
+                               opts.clone(),
+                               profiler_chan.clone());
+        }
+        register_layout_reporter(id, &layout_chan, &memory_profiler_chan);
+
         Pipeline::new(id,
                       subpage_id,
                       script_chan,
@@ -143,9 +168,9 @@ impl Pipeline {
         }
     }
 
-    pub fn load(&mut self, url: Url) {
-        self.url = Some(url.clone());
-        self.script_chan.send(LoadMsg(self.id, url));
+    pub fn load(&mut self, load_data: LoadData) {
+        self.url = Some(load_data.url.clone());
+        self.script_chan.send(LoadMsg(self.id, load_data));
     }
 
     pub fn execute(&mut self, url: Url) {
@@ -153,27 +178,68 @@ impl Pipeline {
         self.script_chan.send(ExecuteMsg(self.id, url));
     }
 
+    // The pipeline's tasks may have failed, so these don't count on the render task being there.
+
     pub fn grant_paint_permission(&self) {
-        self.render_chan.send(PaintPermissionGranted);
+        self.render_chan.chan.try_send(PaintPermissionGranted);
     }
 
     pub fn revoke_paint_permission(&self) {
-        self.render_chan.send(PaintPermissionRevoked);
+        self.render_chan.chan.try_send(PaintPermissionRevoked);
     }
 
     pub fn reload(&mut self) {
         do self.url.clone().map_move() |url| {
-            self.load(url);
+            self.load(LoadData::new(url));
         };
     }
 
+    /// Shuts the pipeline's tasks down, if they haven't failed already.
     pub fn exit(&self) {
         // Script task handles shutting down layout, as well
-        self.script_chan.send(script_task::ExitMsg);
+        self.script_chan.chan.try_send(script_task::ExitMsg);
 
         let (response_port, response_chan) = comm::stream();
-        self.render_chan.send(render_task::ExitMsg(response_chan));
-        response_port.recv();
+        if self.render_chan.chan.try_send(render_task::ExitMsg(response_chan)) {
+            response_port.try_recv();
+        }
     }
 }
 
+/// Has the memory profiler ask the layout task of the pipeline how much it's holding on to. The
+/// profiler forgets the reporter once the task has exited.
+fn register_layout_reporter(id: PipelineId,
+                            layout_chan: &LayoutChan,
+                            memory_profiler_chan: &MemoryProfilerChan) {
+    let PipelineId(index) = id;
+    let layout_chan = layout_chan.chan.clone();
+    memory_profiler_chan.send(RegisterReporterMsg(fmt!("layout-%u", index), |reports| {
+        layout_chan.try_send(ReportMemoryMsg(reports));
+    }));
+}
+
+/// Runs `start`, which starts the tasks of pipeline `id`, in a task that the constellation's
+/// failure takes down but that doesn't take the constellation down with it. The tasks `start`
+/// spawns are linked to each other, so the failure of one takes the rest of the pipeline down;
+/// the constellation is then told, so that it can put an error page in the pipeline's frame.
+fn spawn_pipeline_tasks(id: PipelineId,
+                        subpage_id: Option<SubpageId>,
+                        constellation_chan: ConstellationChan,
+                        start: ~fn()) {
+    let mut result_port = None;
+    let mut builder = task::task();
+    builder.supervised();
+    builder.future_result(|port| result_port = Some(port));
+    builder.spawn(start);
+
+    // The result comes once the tasks of the pipeline have all exited.
+    let result_port = Cell::new(result_port.unwrap());
+    do task::spawn_unlinked {
+        match result_port.take().recv() {
+            task::Success => {}
+            task::Failure => {
+                constellation_chan.chan.try_send(FailureMsg(id, subpage_id));
+            }
+        }
+    }
+}
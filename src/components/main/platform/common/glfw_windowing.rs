@@ -8,14 +8,22 @@ use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
 use windowing::{IdleWindowEvent, ResizeWindowEvent, LoadUrlWindowEvent, MouseWindowEventClass};
 use windowing::{ScrollWindowEvent, ZoomWindowEvent, NavigationWindowEvent, FinishedWindowEvent};
 use windowing::{QuitWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
-use windowing::{Forward, Back};
+use windowing::{MouseMoveWindowEvent, KeyWindowEvent, CopyWindowEvent, PasteWindowEvent, Forward};
+use windowing::{PageZoomWindowEvent, PrintMemoryReportsWindowEvent};
+use windowing::{NewTabWindowEvent, CloseTabWindowEvent, SwitchTabWindowEvent};
 
 use alert::{Alert, AlertMethods};
+use script::dom::event::{CharacterKey, BackspaceKey, DeleteKey, LeftKey, RightKey, HomeKey};
+use script::dom::event::{DownKey, EndKey, EnterKey, UpKey, ShiftTabKey, TabKey};
 use std::libc::c_int;
 use geom::point::Point2D;
+use geom::rect::Rect;
 use geom::size::Size2D;
-use servo_msg::compositor_msg::{IdleRenderState, RenderState, RenderingRenderState};
+use servo_msg::compositor_msg::{Cursor, IdleRenderState, RenderState, RenderingRenderState};
 use servo_msg::compositor_msg::{FinishedLoading, Blank, Loading, PerformingLayout, ReadyState};
+use servo_msg::compositor_msg::NativeOverlayId;
+use servo_msg::constellation_msg::TabId;
+use servo_util::clipboard::Clipboard;
 
 use glfw;
 
@@ -51,6 +59,16 @@ pub struct Window {
     ready_state: ReadyState,
     render_state: RenderState,
     throbber_frame: u8,
+
+    /// The URL of the current page. GLFW windows have no URL bar, so it goes in the title.
+    url: Option<~str>,
+    /// The title of the current page's document, shown in place of the URL if it has one.
+    page_title: ~str,
+
+    /// The ids of the tabs, in the order they were opened, and that of the one shown, once a
+    /// second tab has been opened.
+    tabs: ~[TabId],
+    shown_tab: Option<TabId>,
 }
 
 impl WindowMethods<Application> for Window {
@@ -74,6 +92,12 @@ impl WindowMethods<Application> for Window {
             ready_state: Blank,
             render_state: IdleRenderState,
             throbber_frame: 0,
+
+            url: None,
+            page_title: ~"",
+
+            tabs: ~[],
+            shown_tab: None,
         };
 
         let event_queue = window.event_queue;
@@ -83,10 +107,13 @@ impl WindowMethods<Application> for Window {
             event_queue.push(ResizeWindowEvent(width as uint, height as uint))
         }
         do window.glfw_window.set_key_callback |_win, key, _scancode, action, mods| {
-            if action == glfw::PRESS {
-                window.handle_key(key, mods)
+            if action == glfw::PRESS || action == glfw::REPEAT {
+                window.handle_key(key, action, mods)
             }
         }
+        do window.glfw_window.set_char_callback |_win, character| {
+            event_queue.push(KeyWindowEvent(CharacterKey(character)))
+        }
         do window.glfw_window.set_mouse_button_callback |win, button, action, _mods| {
             let (x, y) = win.get_cursor_pos();
             //handle hidpi displays, since GLFW returns non-hi-def coordinates.
@@ -99,6 +126,13 @@ impl WindowMethods<Application> for Window {
                 window.handle_mouse(button, action, x as i32, y as i32);
             }
         }
+        do window.glfw_window.set_cursor_pos_callback |win, x, y| {
+            //handle hidpi displays, since GLFW returns non-hi-def coordinates.
+            let (backing_size, _) = win.get_framebuffer_size();
+            let (window_size, _) = win.get_size();
+            let hidpi = (backing_size as f32) / (window_size as f32);
+            event_queue.push(MouseMoveWindowEvent(Point2D(x as f32 * hidpi, y as f32 * hidpi)));
+        }
         do window.glfw_window.set_scroll_callback |win, x_offset, y_offset| {
             let dx = (x_offset as f32) * 30.0;
             let dy = (y_offset as f32) * 30.0;
@@ -150,6 +184,18 @@ impl WindowMethods<Application> for Window {
         self.update_window_title()
     }
 
+    /// Sets the URL.
+    fn set_url(@mut self, url: &str) {
+        self.url = Some(url.to_owned());
+        self.update_window_title()
+    }
+
+    /// Sets the title of the page.
+    fn set_page_title(@mut self, title: &str) {
+        self.page_title = title.to_owned();
+        self.update_window_title()
+    }
+
     /// Sets the render state.
     fn set_render_state(@mut self, render_state: RenderState) {
         if self.ready_state == FinishedLoading &&
@@ -163,11 +209,54 @@ impl WindowMethods<Application> for Window {
         self.update_window_title()
     }
 
+    /// Sets the tabs, which Ctrl-PageUp and Ctrl-PageDown switch between.
+    fn set_tabs(@mut self, tabs: ~[TabId], shown: TabId) {
+        self.tabs = tabs;
+        self.shown_tab = Some(shown);
+    }
+
+    fn set_cursor(@mut self, cursor: Cursor) {
+        // FIXME: The GLFW we build against (3.0) has no cursor shapes, so the cursor stays an
+        // arrow over links and text. GLFW 3.1 adds them as glfwCreateStandardCursor.
+        debug!("glfw_windowing: cursor should now be %?", cursor);
+    }
+
+    fn set_native_overlay_rect(@mut self, id: NativeOverlayId, rect: Option<Rect<f32>>) {
+        // FIXME: GLFW windows have no native views to show.
+        debug!("glfw_windowing: native overlay %? should now be at %?", id, rect);
+    }
+
     fn hidpi_factor(@mut self) -> f32 {
         let (backing_size, _) = self.glfw_window.get_framebuffer_size();
         let (window_size, _) = self.glfw_window.get_size();
         (backing_size as f32) / (window_size as f32)
     }
+
+    fn screen_size(@mut self) -> Size2D<f32> {
+        let hidpi = self.hidpi_factor();
+        match glfw::Monitor::get_primary() {
+            Ok(monitor) => {
+                let mode = monitor.get_video_mode().unwrap();
+                Size2D(mode.width as f32 * hidpi, mode.height as f32 * hidpi)
+            }
+            Err(_) => self.size(),
+        }
+    }
+}
+
+impl Clipboard for Window {
+    fn get_text(&self) -> Option<~str> {
+        let text = self.glfw_window.get_clipboard_string();
+        if text.is_empty() {
+            None
+        } else {
+            Some(text)
+        }
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.glfw_window.set_clipboard_string(text)
+    }
 }
 
 impl Window {
@@ -189,29 +278,89 @@ impl Window {
                     RenderingRenderState => {
                         self.glfw_window.set_title(fmt!("%c Rendering — Servo", throbber))
                     }
-                    IdleRenderState => self.glfw_window.set_title("Servo"),
+                    IdleRenderState if !self.page_title.is_empty() => {
+                        self.glfw_window.set_title(fmt!("%s — Servo", self.page_title))
+                    }
+                    IdleRenderState => {
+                        match self.url {
+                            Some(ref url) => self.glfw_window.set_title(fmt!("%s — Servo", *url)),
+                            None => self.glfw_window.set_title("Servo"),
+                        }
+                    }
                 }
             }
         }
     }
 
-    /// Helper function to handle keyboard events.
-    fn handle_key(&self, key: c_int, mods: c_int) {
+    /// Helper function to handle keyboard events. Keys that edit text repeat while held down;
+    /// the others only act when first pressed. Printable characters arrive through the character
+    /// callback instead.
+    fn handle_key(&self, key: c_int, action: c_int, mods: c_int) {
+        let editing_key = match key {
+            glfw::KEY_BACKSPACE if mods & glfw::MOD_SHIFT == 0 => Some(BackspaceKey),
+            glfw::KEY_DELETE => Some(DeleteKey),
+            glfw::KEY_LEFT => Some(LeftKey),
+            glfw::KEY_RIGHT => Some(RightKey),
+            glfw::KEY_HOME => Some(HomeKey),
+            glfw::KEY_END => Some(EndKey),
+            glfw::KEY_ENTER => Some(EnterKey),
+            glfw::KEY_UP => Some(UpKey),
+            glfw::KEY_DOWN => Some(DownKey),
+            glfw::KEY_TAB if mods & glfw::MOD_SHIFT != 0 => Some(ShiftTabKey),
+            glfw::KEY_TAB => Some(TabKey),
+            _ => None,
+        };
+        match editing_key {
+            // The page decides whether Backspace edits text or goes back.
+            Some(editing_key) => self.event_queue.push(KeyWindowEvent(editing_key)),
+            None if action == glfw::PRESS => self.handle_command_key(key, mods),
+            None => {}
+        }
+    }
+
+    /// Helper function to handle keys that control the browser.
+    fn handle_command_key(&self, key: c_int, mods: c_int) {
         match key {
             glfw::KEY_ESCAPE => self.glfw_window.set_should_close(true),
             glfw::KEY_L if mods & glfw::MOD_CONTROL != 0 => self.load_url(), // Ctrl+L
-            glfw::KEY_EQUAL if mods & glfw::MOD_CONTROL != 0 => { // Ctrl-+
+            glfw::KEY_T if mods & glfw::MOD_CONTROL != 0 => self.open_tab(), // Ctrl+T
+            glfw::KEY_W if mods & glfw::MOD_CONTROL != 0 => { // Ctrl+W
+                for &tab in self.shown_tab.iter() {
+                    self.event_queue.push(CloseTabWindowEvent(tab));
+                }
+            }
+            glfw::KEY_PAGE_DOWN if mods & glfw::MOD_CONTROL != 0 => self.switch_tab(1),
+            glfw::KEY_PAGE_UP if mods & glfw::MOD_CONTROL != 0 => self.switch_tab(-1),
+            glfw::KEY_C if mods & glfw::MOD_CONTROL != 0 => { // Ctrl+C
+                self.event_queue.push(CopyWindowEvent);
+            }
+            glfw::KEY_V if mods & glfw::MOD_CONTROL != 0 => { // Ctrl+V
+                self.event_queue.push(PasteWindowEvent);
+            }
+            glfw::KEY_EQUAL if mods & glfw::MOD_CONTROL != 0 && mods & glfw::MOD_ALT != 0 => {
+                // Ctrl-Alt-+ zooms like a pinch would, for want of a touchpad.
                 self.event_queue.push(ZoomWindowEvent(1.1));
             }
-            glfw::KEY_MINUS if mods & glfw::MOD_CONTROL != 0 => { // Ctrl--
+            glfw::KEY_MINUS if mods & glfw::MOD_CONTROL != 0 && mods & glfw::MOD_ALT != 0 => {
+                // Ctrl-Alt--
                 self.event_queue.push(ZoomWindowEvent(0.90909090909));
             }
+            glfw::KEY_EQUAL if mods & glfw::MOD_CONTROL != 0 => { // Ctrl-+
+                self.event_queue.push(PageZoomWindowEvent(Some(1.1)));
+            }
+            glfw::KEY_MINUS if mods & glfw::MOD_CONTROL != 0 => { // Ctrl--
+                self.event_queue.push(PageZoomWindowEvent(Some(0.90909090909)));
+            }
+            glfw::KEY_0 if mods & glfw::MOD_CONTROL != 0 => { // Ctrl-0
+                self.event_queue.push(PageZoomWindowEvent(None));
+            }
+            glfw::KEY_M if mods & glfw::MOD_CONTROL != 0 && mods & glfw::MOD_SHIFT != 0 => {
+                // Ctrl-Shift-M
+                self.event_queue.push(PrintMemoryReportsWindowEvent);
+            }
             glfw::KEY_BACKSPACE if mods & glfw::MOD_SHIFT != 0 => { // Shift-Backspace
                 self.event_queue.push(NavigationWindowEvent(Forward));
             }
-            glfw::KEY_BACKSPACE => { // Backspace
-                self.event_queue.push(NavigationWindowEvent(Back));
-            }
             _ => {}
         }
     }
@@ -256,5 +405,28 @@ impl Window {
             self.event_queue.push(LoadUrlWindowEvent(value))
         }
     }
+
+    /// Helper function to pop up an alert box prompting the user for a URL to open in a new tab.
+    fn open_tab(&self) {
+        let mut alert: Alert = AlertMethods::new("Open in a new tab:");
+        alert.add_prompt();
+        alert.run();
+        let value = alert.prompt_value();
+        if "" != value {
+            self.event_queue.push(NewTabWindowEvent(value))
+        }
+    }
+
+    /// Helper function to show the tab `offset` places after the shown one, wrapping around.
+    fn switch_tab(&self, offset: int) {
+        let shown = match self.shown_tab {
+            Some(shown) => shown,
+            None => return,
+        };
+        let position = self.tabs.iter().position(|&tab| tab == shown).unwrap();
+        let count = self.tabs.len() as int;
+        let next = ((position as int + offset) % count + count) % count;
+        self.event_queue.push(SwitchTabWindowEvent(self.tabs[next]));
+    }
 }
 
@@ -8,21 +8,43 @@ use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
 use windowing::{IdleWindowEvent, ResizeWindowEvent, LoadUrlWindowEvent, MouseWindowEventClass};
 use windowing::{ScrollWindowEvent, ZoomWindowEvent, NavigationWindowEvent, FinishedWindowEvent};
 use windowing::{QuitWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
-use windowing::{Forward, Back};
+use windowing::{CopyWindowEvent, PasteWindowEvent, PageZoomWindowEvent, Forward, Back};
+use windowing::{KeyWindowEvent, NewTabWindowEvent, CloseTabWindowEvent, SwitchTabWindowEvent};
 
 use alert::{Alert, AlertMethods};
 use std::libc::c_int;
 use geom::point::Point2D;
+use geom::rect::Rect;
 use geom::size::Size2D;
-use servo_msg::compositor_msg::{IdleRenderState, RenderState, RenderingRenderState};
+use servo_msg::compositor_msg::{Cursor, IdleRenderState, RenderState, RenderingRenderState};
 use servo_msg::compositor_msg::{FinishedLoading, Blank, Loading, PerformingLayout, ReadyState};
-
-use glut::glut::{ACTIVE_CTRL, ACTIVE_SHIFT, DOUBLE, HAVE_PRECISE_MOUSE_WHEEL, WindowHeight};
-use glut::glut::WindowWidth;
+use servo_msg::compositor_msg::{CrosshairCursor, DefaultCursor, HelpCursor, MoveCursor};
+use servo_msg::compositor_msg::{NotAllowedCursor, PointerCursor, TextCursor, WaitCursor};
+use servo_msg::compositor_msg::NativeOverlayId;
+use servo_msg::constellation_msg::TabId;
+use servo_util::clipboard::{Clipboard, LocalClipboard};
+use script::dom::event::{ShiftTabKey, TabKey};
+
+use glut::glut::{ACTIVE_ALT, ACTIVE_CTRL, ACTIVE_SHIFT, DOUBLE, HAVE_PRECISE_MOUSE_WHEEL};
+use glut::glut::{WindowHeight, WindowWidth};
 use glut::glut;
 
 static THROBBER: [char, ..8] = [ '⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷' ];
 
+// The cursor shapes of GLUT, which rust-glut doesn't bind.
+static GLUT_CURSOR_LEFT_ARROW: c_int = 1;
+static GLUT_CURSOR_INFO: c_int = 2;
+static GLUT_CURSOR_DESTROY: c_int = 3;
+static GLUT_CURSOR_HELP: c_int = 4;
+static GLUT_CURSOR_CYCLE: c_int = 5;
+static GLUT_CURSOR_WAIT: c_int = 7;
+static GLUT_CURSOR_TEXT: c_int = 8;
+static GLUT_CURSOR_CROSSHAIR: c_int = 9;
+
+extern {
+    fn glutSetCursor(cursor: c_int);
+}
+
 /// A structure responsible for setting up and tearing down the entire windowing system.
 pub struct Application;
 
@@ -53,6 +75,16 @@ pub struct Window {
     ready_state: ReadyState,
     render_state: RenderState,
     throbber_frame: u8,
+    url: Option<~str>,
+    page_title: ~str,
+
+    /// The ids of the tabs, in the order they were opened, and that of the one shown, once a
+    /// second tab has been opened.
+    tabs: ~[TabId],
+    shown_tab: Option<TabId>,
+
+    /// GLUT has no clipboard, so text is only copied within Servo.
+    clipboard: LocalClipboard,
 }
 
 impl WindowMethods<Application> for Window {
@@ -76,6 +108,13 @@ impl WindowMethods<Application> for Window {
             ready_state: Blank,
             render_state: IdleRenderState,
             throbber_frame: 0,
+            url: None,
+            page_title: ~"",
+
+            tabs: ~[],
+            shown_tab: None,
+
+            clipboard: LocalClipboard::new(),
         };
 
         let event_queue = window.event_queue;
@@ -136,6 +175,20 @@ impl WindowMethods<Application> for Window {
         //self.update_window_title()
     }
 
+    /// Sets the URL.
+    fn set_url(@mut self, url: &str) {
+        self.url = Some(url.to_owned());
+        //FIXME: set_window_title causes crash with Android version of freeGLUT. Temporarily blocked.
+        //self.update_window_title()
+    }
+
+    /// Sets the title of the page.
+    fn set_page_title(@mut self, title: &str) {
+        self.page_title = title.to_owned();
+        //FIXME: set_window_title causes crash with Android version of freeGLUT. Temporarily blocked.
+        //self.update_window_title()
+    }
+
     /// Sets the render state.
     fn set_render_state(@mut self, render_state: RenderState) {
         if self.ready_state == FinishedLoading &&
@@ -150,10 +203,52 @@ impl WindowMethods<Application> for Window {
         //self.update_window_title()
     }
 
+    /// Sets the tabs, which Ctrl-Tab cycles through.
+    fn set_tabs(@mut self, tabs: ~[TabId], shown: TabId) {
+        self.tabs = tabs;
+        self.shown_tab = Some(shown);
+    }
+
+    fn set_cursor(@mut self, cursor: Cursor) {
+        let glut_cursor = match cursor {
+            DefaultCursor => GLUT_CURSOR_LEFT_ARROW,
+            PointerCursor => GLUT_CURSOR_INFO,
+            TextCursor => GLUT_CURSOR_TEXT,
+            CrosshairCursor => GLUT_CURSOR_CROSSHAIR,
+            MoveCursor => GLUT_CURSOR_CYCLE,
+            WaitCursor => GLUT_CURSOR_WAIT,
+            HelpCursor => GLUT_CURSOR_HELP,
+            NotAllowedCursor => GLUT_CURSOR_DESTROY,
+        };
+        // There's no mouse pointer to speak of on Android, where this does nothing.
+        unsafe {
+            glutSetCursor(glut_cursor)
+        }
+    }
+
+    fn set_native_overlay_rect(@mut self, _: NativeOverlayId, _: Option<Rect<f32>>) {
+        //FIXME: Do nothing in GLUT now.
+    }
+
     fn hidpi_factor(@mut self) -> f32 {
         //FIXME: Do nothing in GLUT now.
     0f32
     }
+
+    fn screen_size(@mut self) -> Size2D<f32> {
+        //FIXME: GLUT windows on Android are always fullscreen, so the window is the screen.
+        self.size()
+    }
+}
+
+impl Clipboard for Window {
+    fn get_text(&self) -> Option<~str> {
+        self.clipboard.get_text()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.clipboard.set_text(text)
+    }
 }
 
 impl Window {
@@ -175,7 +270,18 @@ impl Window {
                     RenderingRenderState => {
                         glut::set_window_title(self.glut_window, fmt!("%c Rendering . Servo", throbber))
                     }
-                    IdleRenderState => glut::set_window_title(self.glut_window, "Servo"),
+                    IdleRenderState if !self.page_title.is_empty() => {
+                        glut::set_window_title(self.glut_window,
+                                               fmt!("%s . Servo", self.page_title))
+                    }
+                    IdleRenderState => {
+                        match self.url {
+                            Some(ref url) => {
+                                glut::set_window_title(self.glut_window, fmt!("%s . Servo", *url))
+                            }
+                            None => glut::set_window_title(self.glut_window, "Servo"),
+                        }
+                    }
                 }
             }
         }
@@ -186,9 +292,36 @@ impl Window {
         debug!("got key: %?", key);
         let modifiers = glut::get_modifiers();
         match key {
+            3 => self.event_queue.push(CopyWindowEvent), // Ctrl+C
+            9 if (modifiers & ACTIVE_CTRL) != 0 => self.switch_to_next_tab(), // Ctrl+Tab
+            9 if (modifiers & ACTIVE_SHIFT) != 0 => self.event_queue.push(KeyWindowEvent(ShiftTabKey)),
+            9 => self.event_queue.push(KeyWindowEvent(TabKey)),
+            20 => self.open_tab(), // Ctrl+T
+            22 => self.event_queue.push(PasteWindowEvent), // Ctrl+V
+            23 => { // Ctrl+W
+                for &tab in self.shown_tab.iter() {
+                    self.event_queue.push(CloseTabWindowEvent(tab));
+                }
+            }
             42 => self.load_url(),
-            43 => self.event_queue.push(ZoomWindowEvent(1.1)),
-            45 => self.event_queue.push(ZoomWindowEvent(0.909090909)),
+            // Control only turns letters and a few other characters into control codes, so '=',
+            // '+', '-' and '0' come through as they are with Ctrl (and Alt) held. As with GLFW,
+            // Ctrl-Alt zooms in and out as a pinch does, and Ctrl alone zooms the page.
+            43 | 61 if (modifiers & ACTIVE_CTRL) != 0 && (modifiers & ACTIVE_ALT) != 0 => {
+                self.event_queue.push(ZoomWindowEvent(1.1))
+            }
+            45 if (modifiers & ACTIVE_CTRL) != 0 && (modifiers & ACTIVE_ALT) != 0 => {
+                self.event_queue.push(ZoomWindowEvent(0.909090909))
+            }
+            43 | 61 if (modifiers & ACTIVE_CTRL) != 0 => { // Ctrl-+
+                self.event_queue.push(PageZoomWindowEvent(Some(1.1)))
+            }
+            45 if (modifiers & ACTIVE_CTRL) != 0 => { // Ctrl--
+                self.event_queue.push(PageZoomWindowEvent(Some(0.909090909)))
+            }
+            48 if (modifiers & ACTIVE_CTRL) != 0 => { // Ctrl-0
+                self.event_queue.push(PageZoomWindowEvent(None))
+            }
             56 => self.event_queue.push(ScrollWindowEvent(Point2D(0.0, 5.0 as f32), Point2D(0.0 as i32, 5.0 as i32))),
             50 => self.event_queue.push(ScrollWindowEvent(Point2D(0.0, -5.0 as f32), Point2D(0.0 as i32, -5.0 as i32))),
             127 => {
@@ -243,5 +376,24 @@ impl Window {
             self.event_queue.push(LoadUrlWindowEvent(value))
         }
     }
-}
 
+    /// Helper function to pop up an alert box prompting the user for a URL to open in a new tab.
+    fn open_tab(&self) {
+        let mut alert: Alert = AlertMethods::new("Open in a new tab:");
+        alert.add_prompt();
+        alert.run();
+        let value = alert.prompt_value();
+        if "" != value {
+            self.event_queue.push(NewTabWindowEvent(value))
+        }
+    }
+
+    /// Helper function to show the tab opened after the shown one, or the first after the last.
+    fn switch_to_next_tab(&self) {
+        for &shown in self.shown_tab.iter() {
+            let position = self.tabs.iter().position(|&tab| tab == shown).unwrap();
+            let next = self.tabs[(position + 1) % self.tabs.len()];
+            self.event_queue.push(SwitchTabWindowEvent(next));
+        }
+    }
+}
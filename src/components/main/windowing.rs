@@ -5,13 +5,29 @@
 //! Abstract windowing methods. The concrete implementations of these can be found in `platform/`.
 
 use geom::point::Point2D;
+use geom::rect::Rect;
 use geom::size::Size2D;
-use servo_msg::compositor_msg::{ReadyState, RenderState};
+use script::dom::event::Key;
+use servo_msg::compositor_msg::{Cursor, NativeOverlay, NativeOverlayId, ReadyState, RenderState};
+use servo_msg::constellation_msg::TabId;
+use servo_util::clipboard::Clipboard;
 
 pub enum MouseWindowEvent {
     MouseWindowClickEvent(uint, Point2D<f32>),
     MouseWindowMouseDownEvent(uint, Point2D<f32>),
     MouseWindowMouseUpEvent(uint, Point2D<f32>),
+    /// The mouse moved while a button was held down, as when dragging out a selection.
+    MouseWindowMoveEvent(Point2D<f32>),
+}
+
+/// A finger touching the screen, moving on it, or leaving it. Each carries an id telling the fingers
+/// on the screen apart, and where the finger is, in device pixels relative to the window.
+pub enum TouchWindowEvent {
+    TouchWindowStartEvent(uint, Point2D<f32>),
+    TouchWindowMoveEvent(uint, Point2D<f32>),
+    TouchWindowEndEvent(uint, Point2D<f32>),
+    /// The system took the touch over, as for a gesture of its own.
+    TouchWindowCancelEvent(uint, Point2D<f32>),
 }
 
 pub enum WindowNavigateMsg {
@@ -30,14 +46,43 @@ pub enum WindowEvent {
     ResizeWindowEvent(uint, uint),
     /// Sent when a new URL is to be loaded.
     LoadUrlWindowEvent(~str),
+    /// Sent when the user opens a tab loading the given URL, in the background.
+    NewTabWindowEvent(~str),
+    /// Sent when the user closes a tab.
+    CloseTabWindowEvent(TabId),
+    /// Sent when the user shows another tab in the window.
+    SwitchTabWindowEvent(TabId),
     /// Sent when a mouse hit test is to be performed.
     MouseWindowEventClass(MouseWindowEvent),
+    /// Sent when the user touches the screen.
+    TouchWindowEventClass(TouchWindowEvent),
+    /// Sent when the mouse moves over the window.
+    MouseMoveWindowEvent(Point2D<f32>),
     /// Sent when the user scrolls. Includes the current cursor position.
     ScrollWindowEvent(Point2D<f32>, Point2D<i32>),
-    /// Sent when the user zooms.
+    /// Sent when the user flings the page, with its velocity in pixels per second and the
+    /// current cursor position. The page keeps scrolling, slowing down as it goes.
+    FlingWindowEvent(Point2D<f32>, Point2D<i32>),
+    /// Sent when the user pinches to zoom, which only scales what's already rendered.
     ZoomWindowEvent(f32),
-    /// Sent when the user uses chrome navigation (i.e. backspace or shift-backspace).
+    /// Sent when the user zooms the page by the given factor (i.e. ctrl-+), or back to its
+    /// natural size, which lays it out again with bigger or smaller CSS pixels.
+    PageZoomWindowEvent(Option<f32>),
+    /// Sent when the user uses chrome navigation (i.e. shift-backspace).
     NavigationWindowEvent(WindowNavigateMsg),
+    /// Sent when the user presses a key the page might want, like one that edits text.
+    KeyWindowEvent(Key),
+    /// Sent when the user copies the selection (i.e. ctrl-c).
+    CopyWindowEvent,
+    /// Sent when the user pastes what's on the clipboard (i.e. ctrl-v).
+    PasteWindowEvent,
+    /// Sent when the embedder wants to show a native view in place of an element of the page.
+    AddNativeOverlayWindowEvent(NativeOverlay),
+    /// Sent when the embedder no longer shows the given native overlay.
+    RemoveNativeOverlayWindowEvent(NativeOverlayId),
+    /// Sent when the user asks how much memory each part of the engine is using
+    /// (i.e. ctrl-shift-m).
+    PrintMemoryReportsWindowEvent,
     /// Sent when rendering is finished.
     FinishedWindowEvent,
     /// Sent when the user quits the application
@@ -49,7 +94,9 @@ pub trait ApplicationMethods {
     fn new() -> Self;
 }
 
-pub trait WindowMethods<A> {
+/// Methods for an abstract window. Windows also give access to the clipboard of the windowing
+/// system.
+pub trait WindowMethods<A> : Clipboard {
     /// Creates a new window.
     fn new(app: &A) -> @mut Self;
     /// Returns the size of the window.
@@ -62,10 +109,24 @@ pub trait WindowMethods<A> {
 
     /// Sets the ready state of the current page.
     fn set_ready_state(@mut self, ready_state: ReadyState);
+    /// Sets the URL of the current page, as shown in the URL bar.
+    fn set_url(@mut self, url: &str);
+    /// Sets the title of the current page's document, which is empty if it has none.
+    fn set_page_title(@mut self, title: &str);
     /// Sets the render state of the current page.
     fn set_render_state(@mut self, render_state: RenderState);
+    /// Sets the ids of the tabs, in the order they were opened, and that of the one shown in the
+    /// window. Called whenever a tab is opened, closed or shown.
+    fn set_tabs(@mut self, tabs: ~[TabId], shown: TabId);
+    /// Sets the mouse cursor shown over the window.
+    fn set_cursor(@mut self, cursor: Cursor);
+    /// Moves a native overlay to the given rect, in device pixels relative to the window, or
+    /// hides it if its element isn't visible.
+    fn set_native_overlay_rect(@mut self, id: NativeOverlayId, rect: Option<Rect<f32>>);
 
     /// Returns the hidpi factor of the monitor.
     fn hidpi_factor(@mut self) -> f32;
+    /// Returns the size of the screen the window is on, in device pixels.
+    fn screen_size(@mut self) -> Size2D<f32>;
 }
 
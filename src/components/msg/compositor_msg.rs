@@ -4,23 +4,25 @@
 
 use azure::azure_hl::DrawTarget;
 use azure::azure::AzGLContext;
+use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
+use servo_util::geometry::{DevicePx, PagePx, ScaleFactor, TypedRect};
 
-use constellation_msg::PipelineId;
+use constellation_msg::{PipelineId, SubpageId};
 
 #[deriving(Clone)]
 pub struct LayerBuffer {
     draw_target: DrawTarget,
 
     // The rect in the containing RenderLayer that this represents.
-    rect: Rect<f32>,
+    rect: TypedRect<PagePx, f32>,
 
     // The rect in pixels that will be drawn to the screen.
-    screen_pos: Rect<uint>,
+    screen_pos: TypedRect<DevicePx, uint>,
 
     // The scale at which this tile is rendered
-    resolution: f32,
+    resolution: ScaleFactor<PagePx, DevicePx>,
 
     // NB: stride is in pixels, like OpenGL GL_UNPACK_ROW_LENGTH.
     stride: uint,
@@ -63,6 +65,130 @@ impl Epoch {
     }
 }
 
+/// Information about the native window and the screen it is on, as exposed to content through
+/// `window.screen`, `window.outerWidth` and friends. Sizes are in device pixels.
+#[deriving(Clone, Eq)]
+pub struct WindowMetrics {
+    /// The size of the whole screen.
+    screen_size: Size2D<uint>,
+    /// The size of the screen available to windows, excluding system UI such as docks.
+    available_screen_size: Size2D<uint>,
+    /// The outer size of the native window.
+    window_size: Size2D<uint>,
+    /// The number of device pixels per CSS pixel.
+    device_pixel_ratio: f32,
+    /// The number of bits used to represent the color of a pixel.
+    color_depth: uint,
+}
+
+/// The mouse cursor to show over a part of a page.
+#[deriving(Clone, Eq)]
+pub enum Cursor {
+    DefaultCursor,
+    PointerCursor,
+    TextCursor,
+    CrosshairCursor,
+    MoveCursor,
+    WaitCursor,
+    HelpCursor,
+    NotAllowedCursor,
+}
+
+/// What the compositor needs to know about a part of a page to answer hit testing questions, like
+/// which cursor to show or what the mouse wheel should scroll, without asking layout.
+#[deriving(Clone)]
+pub struct HitTestItem {
+    /// The bounds of the item, in page coordinates.
+    bounds: Rect<f32>,
+    /// An opaque identifier of the DOM node that the item was generated for.
+    node: uint,
+    /// The cursor to show while the mouse is over the item.
+    cursor: Cursor,
+    /// The subpage whose layer the mouse wheel scrolls over this item, if it's an iframe.
+    scroll_subpage: Option<SubpageId>,
+}
+
+/// Identifies a native overlay that the embedder registered.
+#[deriving(Clone, Eq)]
+pub struct NativeOverlayId(uint);
+
+/// How a native overlay is composited with web content.
+#[deriving(Clone, Eq)]
+pub enum NativeOverlayMode {
+    /// Web content is cleared to transparent where the overlay is, so that a native view behind
+    /// the window's content, like a video player, shows through.
+    PunchOutOverlay,
+    /// The overlay is drawn above web content, like a native dropdown.
+    AboveContentOverlay,
+}
+
+/// A native view that the embedder shows in place of a part of the page.
+#[deriving(Clone)]
+pub struct NativeOverlay {
+    id: NativeOverlayId,
+    /// The `id` attribute of the element whose geometry the overlay follows.
+    element_id: ~str,
+    mode: NativeOverlayMode,
+}
+
+/// Where layout put the element associated with a native overlay.
+#[deriving(Clone)]
+pub struct NativeOverlayGeometry {
+    id: NativeOverlayId,
+    /// The border box of the element, in page coordinates.
+    bounds: Rect<f32>,
+    mode: NativeOverlayMode,
+}
+
+/// The options of a `<select>` the user clicked, for the compositor to show in a popup list over
+/// the page.
+#[deriving(Clone)]
+pub struct SelectPopup {
+    /// The border box of the select, in the page coordinates of its pipeline.
+    bounds: Rect<f32>,
+    /// The labels of the options.
+    options: ~[~str],
+    /// The option that's selected, if any.
+    selected: Option<uint>,
+}
+
+/// What a page did with a `touchstart` or `touchmove` it was sent.
+#[deriving(Clone, Eq)]
+pub enum TouchEventResult {
+    /// A listener canceled it, so the compositor leaves the touch to the page and doesn't scroll.
+    TouchDefaultPrevented,
+    /// The compositor is free to scroll the page along with the finger.
+    TouchDefaultAllowed,
+}
+
+/// Identifies a video in a pipeline. The compositor shows the video's frames in a layer of its
+/// own, above the page's, wherever layout last put the video.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct VideoLayerId(uint);
+
+/// Where layout put a video.
+#[deriving(Clone)]
+pub struct VideoLayerGeometry {
+    id: VideoLayerId,
+    /// The content box of the video, in page coordinates.
+    bounds: Rect<f32>,
+}
+
+/// A decoded frame of a video.
+pub struct VideoFrame {
+    size: Size2D<uint>,
+    /// The pixels, as opaque BGRA rows from the top down.
+    data: ~[u8],
+}
+
+/// Where a media task sends the frames it decodes. Unlike the script listener it comes from, it
+/// can be sent to another task, so frames go straight to the compositor rather than by way of
+/// script and layout.
+pub trait VideoFrameSink {
+    /// Shows a frame in the layer of the given video.
+    fn show_video_frame(&self, PipelineId, VideoLayerId, ~VideoFrame);
+}
+
 /// The interface used by the renderer to acquire draw targets for each render frame and
 /// submit them to be drawn to the display.
 pub trait RenderListener {
@@ -70,6 +196,12 @@ pub trait RenderListener {
     fn new_layer(&self, PipelineId, Size2D<uint>);
     fn set_layer_page_size(&self, PipelineId, Size2D<uint>, Epoch);
     fn set_layer_clip_rect(&self, PipelineId, Rect<uint>);
+    /// Replaces the hit testing information for the given layer, topmost item last.
+    fn set_layer_hit_test_items(&self, PipelineId, ~[HitTestItem]);
+    /// Replaces the geometry of the native overlays whose elements are in the given layer.
+    fn set_layer_native_overlays(&self, PipelineId, ~[NativeOverlayGeometry]);
+    /// Replaces the geometry of the videos in the given layer.
+    fn set_layer_videos(&self, PipelineId, ~[VideoLayerGeometry]);
     fn delete_layer(&self, PipelineId);
     fn paint(&self, id: PipelineId, layer_buffer_set: ~LayerBufferSet, Epoch);
     fn set_render_state(&self, render_state: RenderState);
@@ -79,7 +211,26 @@ pub trait RenderListener {
 /// which is used in displaying the appropriate message in the window's title.
 pub trait ScriptListener : Clone {
     fn set_ready_state(&self, ReadyState);
+    /// Tells the compositor the title of the given pipeline's document.
+    fn set_title(&self, PipelineId, ~str);
     fn invalidate_rect(&self, PipelineId, Rect<uint>);
+    /// Asks the compositor to send the given pipeline a tick on its next frame.
+    fn request_animation_frame(&self, PipelineId);
+    /// Returns the current metrics of the native window and screen.
+    fn get_window_metrics(&self) -> WindowMetrics;
+    /// Shows the options of the given pipeline's focused `<select>` in a popup list. The
+    /// compositor sends back a `SelectOptionEvent` when the user picks one or dismisses it.
+    fn show_select_popup(&self, PipelineId, SelectPopup);
+    /// Puts the given plain text on the system clipboard, as when the user copies the selection.
+    fn set_clipboard_text(&self, ~str);
+    /// Scrolls the given pipeline's page so that the given point, in page coordinates, is at the
+    /// top left of the window, as far as the page size allows.
+    fn scroll_fragment_point(&self, PipelineId, Point2D<f32>);
+    /// Tells the compositor whether the page canceled the last `touchstart` or `touchmove` it was
+    /// sent. The compositor holds off scrolling for a touch until it knows.
+    fn touch_event_processed(&self, TouchEventResult);
+    /// Returns somewhere for a media task to send the frames of videos it decodes.
+    fn video_frame_sink(&self) -> ~VideoFrameSink:Send;
 }
 
 /// The interface used by the quadtree to get info about LayerBuffers
@@ -95,12 +246,12 @@ pub trait Tile {
 impl Tile for ~LayerBuffer {
     fn get_mem(&self) -> uint {
         // This works for now, but in the future we may want a better heuristic
-        self.screen_pos.size.width * self.screen_pos.size.height
+        self.screen_pos.rect.size.width * self.screen_pos.rect.size.height
     }
     fn is_valid(&self, scale: f32) -> bool {
-        self.resolution.approx_eq(&scale)
+        self.resolution.get().approx_eq(&scale)
     }
     fn get_size_2d(&self) -> Size2D<uint> {
-        self.screen_pos.size
+        self.screen_pos.rect.size
     }
 }
@@ -10,6 +10,8 @@ use extra::url::Url;
 use extra::future::Future;
 use geom::size::Size2D;
 use geom::rect::Rect;
+use servo_net::resource_task::LoadData;
+use devtools_msg::{DevtoolScriptControlMsg, PipelineSummary};
 
 #[deriving(Clone)]
 pub struct ConstellationChan {
@@ -31,18 +33,100 @@ pub enum Msg {
     ExitMsg(Chan<()>),
     InitLoadUrlMsg(Url),
     FrameRectMsg(PipelineId, SubpageId, Rect<f32>),
-    LoadUrlMsg(PipelineId, Url, Future<Size2D<uint>>),
+    /// Loads a page in place of the given pipeline's, with the URL, and the method and body
+    /// for e.g. a form submission, given.
+    LoadUrlMsg(PipelineId, LoadData, Future<Size2D<uint>>),
+    /// Like `LoadUrlMsg`, but the page takes the place of the current session history entry
+    /// instead of adding one after it, as `location.replace` asks.
+    ReplaceUrlMsg(PipelineId, LoadData, Future<Size2D<uint>>),
+    /// Loads a page in a new top-level browsing context, as following a link with
+    /// `target="_blank"` does.
+    OpenUrlMsg(LoadData),
     LoadIframeUrlMsg(Url, PipelineId, SubpageId, Future<Size2D<uint>>),
     NavigateMsg(NavigationDirection),
+    /// Adds a session history entry with the given URL after the given pipeline's current one,
+    /// for `history.pushState`.
+    PushStateMsg(PipelineId, Url),
+    /// Changes the URL of the given pipeline's current session history entry, for
+    /// `history.replaceState`.
+    ReplaceStateMsg(PipelineId, Url),
+    /// Moves the given number of entries back, if negative, or forward through the session
+    /// history. Zero reloads the current page.
+    TraverseHistoryMsg(int),
+    /// Asks for the number of entries in the session history.
+    GetHistoryLengthMsg(Chan<uint>),
+    /// Posts a message from the given pipeline to a window, with the origin of the sender and,
+    /// unless any will do, the origin the receiver's document must have.
+    PostMessageMsg(PipelineId, WindowTarget, StructuredCloneData, ~str, Option<~str>),
     RendererReadyMsg(PipelineId),
     ResizedWindowMsg(Size2D<uint>),
+    /// One of the tasks of the given pipeline, in the frame with the given subpage id if it's an
+    /// iframe's, failed, taking the rest of its tasks down with it.
+    FailureMsg(PipelineId, Option<SubpageId>),
+    /// Opens a top-level browsing context of its own, a tab, loading the given URL in the
+    /// background, and sends back its id if given a channel for it.
+    NewTabMsg(Url, Option<Chan<TabId>>),
+    /// Closes the given tab, exiting its pipelines. Closing the tab that's shown shows the one
+    /// opened before it; the last tab can't be closed.
+    CloseTabMsg(TabId),
+    /// Shows the given tab in the window. Navigation and resizing act on the tab that's shown.
+    SwitchTabMsg(TabId),
+    /// Asks for the ids of the tabs, in the order they were opened, and that of the one shown.
+    GetTabsMsg(Chan<(~[TabId], TabId)>),
+    /// Logs a message from script in the given pipeline through the console API.
+    ConsoleMsg(PipelineId, ConsoleLevel, ~str),
+    /// Asks for the pages shown in every tab, including those in iframes, for the developer
+    /// tools.
+    GetPipelinesMsg(Chan<~[PipelineSummary]>),
+    /// Passes a request of the developer tools on to the script task of the given pipeline. If
+    /// there's no such pipeline, the request is dropped, and with it the channel for the answer.
+    DevtoolsMsg(PipelineId, DevtoolScriptControlMsg),
 }
 
-/// Represents the two different ways to which a page can be navigated
+/// Which method of the console API logged a message.
+#[deriving(Clone, Eq)]
+pub enum ConsoleLevel {
+    LogLevel,
+    DebugLevel,
+    InfoLevel,
+    WarnLevel,
+    ErrorLevel,
+}
+
+/// A window that script can post messages to. All but `PipelineWindow` are relative to the
+/// pipeline that posts the message, since the pipelines in a frame change as it navigates.
+#[deriving(Clone, Eq)]
+pub enum WindowTarget {
+    /// The window of the given pipeline, e.g. the one a message came from.
+    PipelineWindow(PipelineId),
+    /// The window in the frame with the given subpage id, in the posting pipeline's document.
+    SubpageWindow(SubpageId),
+    /// The window of the document the posting pipeline's frame is in, or its own if it has none.
+    ParentWindow,
+    /// The window at the root of the posting pipeline's frame tree.
+    TopWindow,
+}
+
+/// A copy of a script value that can be sent to another script task, as `postMessage` does.
+/// Arrays and plain objects are copied along with what's in them.
+#[deriving(Clone)]
+pub enum StructuredCloneData {
+    UndefinedData,
+    NullData,
+    BooleanData(bool),
+    NumberData(f64),
+    StringData(~str),
+    ArrayData(~[StructuredCloneData]),
+    /// The enumerable own properties of an object, in order.
+    ObjectData(~[(~str, StructuredCloneData)]),
+}
+
+/// Represents the different ways to which a page can be navigated
 #[deriving(Clone, Eq, IterBytes)]
 enum NavigationType {
     Load,               // entered or clicked on a url
     Navigate,           // browser forward/back buttons
+    Replace,            // location.replace() or location.reload()
 }
 
 #[deriving(Clone, Eq, IterBytes)]
@@ -55,3 +139,6 @@ pub enum NavigationDirection {
 pub struct PipelineId(uint);
 #[deriving(Clone, Eq, IterBytes)]
 pub struct SubpageId(uint);
+/// Identifies a top-level browsing context, with its own session history.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct TabId(uint);
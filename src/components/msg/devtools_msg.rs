@@ -0,0 +1,49 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! What the developer tools server asks of the constellation and of script, to let tools in other
+//! processes inspect the pages of a running browser.
+
+use constellation_msg::{PipelineId, TabId};
+
+use std::comm::Chan;
+use extra::url::Url;
+
+/// A page the developer tools can inspect.
+#[deriving(Clone)]
+pub struct PipelineSummary {
+    id: PipelineId,
+    /// The tab the page is in.
+    tab: TabId,
+    /// The pipeline of the document whose iframe the page is in, if it's in one.
+    parent: Option<PipelineId>,
+    url: Option<Url>,
+}
+
+/// A node of a document and the nodes under it, as the developer tools show them.
+pub struct NodeInfo {
+    /// The index of the node in a preorder walk of the document, which picks it out until the
+    /// document changes.
+    id: uint,
+    /// The `nodeType` of the node.
+    node_type: u16,
+    /// The tag name of an element, and the `nodeName` of other nodes.
+    name: ~str,
+    /// The text of text nodes and comments.
+    value: Option<~str>,
+    attributes: ~[(~str, ~str)],
+    children: ~[NodeInfo],
+}
+
+/// Requests the constellation passes on to the script task of a pipeline.
+pub enum DevtoolScriptControlMsg {
+    /// Sends back the tree of the document, if it has one yet.
+    GetDocumentMsg(Chan<Option<NodeInfo>>),
+    /// Sends back the computed values of the CSS properties of the element with the given node
+    /// id, or an error if there's no such element or it hasn't been styled.
+    GetComputedStyleMsg(uint, Chan<Result<~[(~str, ~str)], ()>>),
+    /// Runs script in the page and sends back what it evaluated to, described as the console
+    /// would, or why it failed.
+    EvaluateJSMsg(~str, Chan<Result<~str, ~str>>),
+}
@@ -0,0 +1,176 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decoding of `gzip` and `deflate` response bodies (RFC 7230 § 4.2).
+
+use resource_task::Metadata;
+
+use std::ascii::StrAsciiExt;
+use std::cell::Cell;
+use std::task;
+use extra::flate;
+
+/// The `Accept-Encoding` value sent with requests, listing the encodings we can decode.
+pub static ACCEPT_ENCODING: &'static str = "gzip, deflate";
+
+static GZIP_FLAG_HCRC: u8 = 0x02;
+static GZIP_FLAG_EXTRA: u8 = 0x04;
+static GZIP_FLAG_NAME: u8 = 0x08;
+static GZIP_FLAG_COMMENT: u8 = 0x10;
+
+#[deriving(Clone, Eq)]
+pub enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Finds the encoding of a response body from its `Content-Encoding` header. Returns `None`
+    /// if the body is encoded in a way we don't understand.
+    ///
+    /// FIXME: A list of several encodings is rejected rather than decoded in turn.
+    pub fn from_metadata(metadata: &Metadata) -> Option<ContentEncoding> {
+        for &(ref name, ref value) in metadata.headers.iter() {
+            if name.to_ascii_lower() == ~"content-encoding" {
+                return match value.trim().to_ascii_lower().as_slice() {
+                    "" | "identity" => Some(Identity),
+                    "gzip" | "x-gzip" => Some(Gzip),
+                    "deflate" => Some(Deflate),
+                    _ => None,
+                };
+            }
+        }
+        Some(Identity)
+    }
+
+    /// Decodes a whole response body. Returns `Err` if the body is corrupt.
+    pub fn decode(&self, body: ~[u8]) -> Result<~[u8], ()> {
+        let stream = match *self {
+            Identity => return Ok(body),
+            Gzip => gzip_payload(body),
+            Deflate => Some(zlib_payload(body)),
+        };
+        match stream {
+            None => Err(()),
+            Some(stream) => {
+                // `inflate_bytes` fails on corrupt data, so keep that from taking us down with it.
+                let stream = Cell::new(stream);
+                do task::try {
+                    flate::inflate_bytes(stream.take())
+                }.map_err(|_| ())
+            }
+        }
+    }
+}
+
+/// Fixes up the metadata of a response whose body has been decoded.
+pub fn decoded_metadata(mut metadata: Metadata) -> Metadata {
+    metadata.headers = do metadata.headers.consume_iter().filter |&(ref name, _)| {
+        let name = name.to_ascii_lower();
+        name != ~"content-encoding" && name != ~"content-length"
+    }.collect();
+    metadata.content_length = None;
+    metadata
+}
+
+/// Strips the header and trailer from a gzip member (RFC 1952 § 2.3), leaving the raw deflate
+/// stream. Returns `None` if the header is malformed.
+fn gzip_payload(body: ~[u8]) -> Option<~[u8]> {
+    if body.len() < 18 || body[0] != 0x1f || body[1] != 0x8b || body[2] != 8 {
+        return None;
+    }
+    let flags = body[3];
+    let mut start = 10;
+    if flags & GZIP_FLAG_EXTRA != 0 {
+        if start + 2 > body.len() {
+            return None;
+        }
+        start += 2 + (body[start] as uint | (body[start + 1] as uint << 8));
+    }
+    for &flag in [GZIP_FLAG_NAME, GZIP_FLAG_COMMENT].iter() {
+        if flags & flag != 0 {
+            // A zero-terminated string.
+            while start < body.len() && body[start] != 0 {
+                start += 1;
+            }
+            start += 1;
+        }
+    }
+    if flags & GZIP_FLAG_HCRC != 0 {
+        start += 2;
+    }
+    // The stream is followed by a CRC-32 and the uncompressed size.
+    if start + 8 > body.len() {
+        return None;
+    }
+    Some(body.slice(start, body.len() - 8).to_owned())
+}
+
+/// Strips the zlib header (RFC 1950 § 2.2) from a `deflate` body, if it has one. Some servers send
+/// raw deflate streams instead, so those are passed through as is.
+fn zlib_payload(body: ~[u8]) -> ~[u8] {
+    if body.len() >= 6 && body[0] & 0x0f == 8 &&
+            ((body[0] as uint << 8) | body[1] as uint) % 31 == 0 {
+        // The stream is followed by an Adler-32 checksum.
+        body.slice(2, body.len() - 4).to_owned()
+    } else {
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resource_task::Metadata;
+
+    use extra::flate;
+    use extra::url;
+
+    static TEXT: &'static str = "<p>Hello, hello, hello, hello, hello!</p>";
+
+    fn metadata_with_encoding(encoding: &str) -> Metadata {
+        let mut metadata = Metadata::default(url::from_str("http://example.com/").unwrap());
+        metadata.headers = ~[(~"Content-Encoding", encoding.to_owned()),
+                             (~"Content-Length", ~"10")];
+        metadata.content_length = Some(10);
+        metadata
+    }
+
+    #[test]
+    fn encodings() {
+        assert!(ContentEncoding::from_metadata(&metadata_with_encoding("GZip")) == Some(Gzip));
+        assert!(ContentEncoding::from_metadata(&metadata_with_encoding("deflate")) ==
+                Some(Deflate));
+        assert!(ContentEncoding::from_metadata(&metadata_with_encoding("br")).is_none());
+
+        let metadata = decoded_metadata(metadata_with_encoding("gzip"));
+        assert!(metadata.headers.is_empty());
+        assert!(metadata.content_length.is_none());
+        assert!(ContentEncoding::from_metadata(&metadata) == Some(Identity));
+    }
+
+    #[test]
+    fn gzip() {
+        let mut body = ~[0x1f, 0x8b, 8, 0x08, 0, 0, 0, 0, 0, 0xff];
+        body.push_all(bytes!("index.html"));
+        body.push(0);
+        body.push_all(flate::deflate_bytes(TEXT.as_bytes()));
+        body.push_all([0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(Gzip.decode(body) == Ok(TEXT.as_bytes().to_owned()));
+
+        assert!(Gzip.decode(TEXT.as_bytes().to_owned()).is_err());
+    }
+
+    #[test]
+    fn deflate() {
+        let mut body = ~[0x78, 0x9c];
+        body.push_all(flate::deflate_bytes(TEXT.as_bytes()));
+        body.push_all([0, 0, 0, 0]);
+        assert!(Deflate.decode(body) == Ok(TEXT.as_bytes().to_owned()));
+
+        let raw = flate::deflate_bytes(TEXT.as_bytes());
+        assert!(Deflate.decode(raw) == Ok(TEXT.as_bytes().to_owned()));
+    }
+}
@@ -0,0 +1,356 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Cookie parsing and storage, as described in RFC 6265.
+
+use extra::sort::merge_sort;
+use extra::time;
+use extra::url::Url;
+use std::ascii::StrAsciiExt;
+use std::i64;
+use std::uint;
+
+/// Where a cookie is being read or written from. Script can neither see nor set `HttpOnly`
+/// cookies.
+#[deriving(Clone, Eq)]
+pub enum CookieSource {
+    /// An HTTP request or response.
+    HTTP,
+    /// Script, through `document.cookie`.
+    NonHTTP,
+}
+
+#[deriving(Clone, Eq)]
+pub struct Cookie {
+    name: ~str,
+    value: ~str,
+    /// The domain the cookie belongs to, in lower case.
+    domain: ~str,
+    /// Whether the cookie only matches `domain` itself, rather than also its subdomains.
+    host_only: bool,
+    path: ~str,
+    secure: bool,
+    http_only: bool,
+    /// When the cookie expires, in seconds since the epoch. Session cookies have none.
+    expiry: Option<i64>,
+    /// The order in which cookies were created, used to break ties when sorting.
+    creation_index: uint,
+}
+
+impl Cookie {
+    /// Parses the value of a `Set-Cookie` header received in response to a request for `url`.
+    /// Returns `None` if the cookie is malformed or `url` may not set it.
+    pub fn parse(header: &str, url: &Url, source: CookieSource, now: i64) -> Option<Cookie> {
+        let mut parts = header.split_iter(';');
+        let (name, value) = match parts.next() {
+            Some(pair) => match pair.find('=') {
+                Some(index) => (pair.slice_to(index).trim().to_owned(),
+                                pair.slice_from(index + 1).trim().to_owned()),
+                None => return None,
+            },
+            None => return None,
+        };
+        if name.is_empty() {
+            return None;
+        }
+
+        let host = url.host.to_ascii_lower();
+        let mut cookie = Cookie {
+            name: name,
+            value: value,
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url.path),
+            secure: false,
+            http_only: false,
+            expiry: None,
+            creation_index: 0,
+        };
+
+        // Max-Age takes precedence over Expires, wherever it appears.
+        let mut max_age = None;
+        for attribute in parts {
+            let (attr_name, attr_value) = match attribute.find('=') {
+                Some(index) => (attribute.slice_to(index).trim().to_ascii_lower(),
+                                attribute.slice_from(index + 1).trim()),
+                None => (attribute.trim().to_ascii_lower(), ""),
+            };
+            match attr_name.as_slice() {
+                "expires" => {
+                    for expiry in parse_date(attr_value).iter() {
+                        cookie.expiry = Some(*expiry);
+                    }
+                }
+                "max-age" => {
+                    let delta = if attr_value.starts_with("-") {
+                        uint::from_str(attr_value.slice_from(1)).map(|delta| -(*delta as i64))
+                    } else {
+                        uint::from_str(attr_value).map(|delta| *delta as i64)
+                    };
+                    for delta in delta.iter() {
+                        max_age = Some(if *delta <= 0 { i64::min_value } else { now + *delta });
+                    }
+                }
+                "domain" => {
+                    let mut domain = attr_value.to_ascii_lower();
+                    if domain.starts_with(".") {
+                        domain = domain.slice_from(1).to_owned();
+                    }
+                    if !domain.is_empty() {
+                        if !domain_match(host, domain) {
+                            return None;
+                        }
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                }
+                "path" => {
+                    if attr_value.starts_with("/") {
+                        cookie.path = attr_value.to_owned();
+                    }
+                }
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => {}
+            }
+        }
+
+        if max_age.is_some() {
+            cookie.expiry = max_age;
+        }
+        if cookie.http_only && source == NonHTTP {
+            return None;
+        }
+        Some(cookie)
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        match self.expiry {
+            Some(expiry) => expiry <= now,
+            None => false,
+        }
+    }
+
+    /// Whether this cookie should be sent with a request for `url`.
+    pub fn matches(&self, url: &Url, source: CookieSource) -> bool {
+        let host = url.host.to_ascii_lower();
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(host, self.domain)
+        };
+        let path = if url.path.is_empty() { ~"/" } else { url.path.clone() };
+        domain_matches &&
+            path_match(path, self.path) &&
+            (!self.secure || url.scheme == ~"https") &&
+            (!self.http_only || source == HTTP)
+    }
+}
+
+/// The cookies the user agent knows about.
+pub struct CookieJar {
+    priv cookies: ~[Cookie],
+    priv next_creation_index: uint,
+}
+
+impl CookieJar {
+    pub fn new() -> CookieJar {
+        CookieJar {
+            cookies: ~[],
+            next_creation_index: 0,
+        }
+    }
+
+    /// Stores the cookies set by a `Set-Cookie` header (or an assignment to `document.cookie`)
+    /// for `url`, replacing any with the same name, domain and path. Setting an expired cookie
+    /// deletes it.
+    pub fn set_cookie(&mut self, header: &str, url: &Url, source: CookieSource) {
+        let now = time::get_time().sec;
+        let mut cookie = match Cookie::parse(header, url, source, now) {
+            Some(cookie) => cookie,
+            None => return,
+        };
+
+        let existing = self.cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        });
+        match existing {
+            Some(index) => {
+                let old = self.cookies.remove(index);
+                // Script may not overwrite HttpOnly cookies.
+                if old.http_only && source == NonHTTP {
+                    self.cookies.insert(index, old);
+                    return;
+                }
+                cookie.creation_index = old.creation_index;
+            }
+            None => {
+                cookie.creation_index = self.next_creation_index;
+                self.next_creation_index += 1;
+            }
+        }
+
+        if !cookie.is_expired(now) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// Returns the value of the `Cookie` header to send with a request for `url` (which is also
+    /// the value of `document.cookie`), or `None` if there are no matching cookies.
+    pub fn cookies_for_url(&mut self, url: &Url, source: CookieSource) -> Option<~str> {
+        let now = time::get_time().sec;
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let matching: ~[&Cookie] = self.cookies.iter()
+            .filter(|cookie| cookie.matches(url, source))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+
+        // Cookies with longer paths go first, then older ones.
+        let matching = do merge_sort(matching) |a, b| {
+            if a.path.len() != b.path.len() {
+                a.path.len() > b.path.len()
+            } else {
+                a.creation_index <= b.creation_index
+            }
+        };
+        let pairs: ~[~str] = matching.iter().map(|c| fmt!("%s=%s", c.name, c.value)).collect();
+        Some(pairs.connect("; "))
+    }
+}
+
+/// The default path of a cookie set for a URL with the given path (RFC 6265 § 5.1.4).
+fn default_path(path: &str) -> ~str {
+    if !path.starts_with("/") {
+        return ~"/";
+    }
+    match path.rfind('/') {
+        Some(0) | None => ~"/",
+        Some(index) => path.slice_to(index).to_owned(),
+    }
+}
+
+/// Whether `host` is `domain` or one of its subdomains (RFC 6265 § 5.1.3).
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain ||
+        (host.ends_with(domain) &&
+         host.char_at(host.len() - domain.len() - 1) == '.' &&
+         !host.iter().all(|c| c == '.' || c.is_digit()))
+}
+
+/// Whether a request for `path` should carry cookies for `cookie_path` (RFC 6265 § 5.1.4).
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path ||
+        (path.starts_with(cookie_path) &&
+         (cookie_path.ends_with("/") || path.char_at(cookie_path.len()) == '/'))
+}
+
+/// Parses a cookie date, in seconds since the epoch.
+///
+/// FIXME: RFC 6265 § 5.1.1 describes a far more forgiving algorithm; this only accepts the
+/// common formats.
+fn parse_date(date: &str) -> Option<i64> {
+    let formats = ["%a, %d %b %Y %H:%M:%S GMT", "%a, %d-%b-%Y %H:%M:%S GMT"];
+    for format in formats.iter() {
+        match time::strptime(date, *format) {
+            Ok(tm) => return Some(tm.to_timespec().sec),
+            Err(*) => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extra::url;
+
+    fn url(s: &str) -> url::Url {
+        url::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn parse_attributes() {
+        let u = url("http://www.example.com/a/b/c.html");
+        let cookie = Cookie::parse("id=1; Domain=.Example.com; Path=/a; Secure; HttpOnly",
+                                   &u, HTTP, 0).unwrap();
+        assert!(cookie.name == ~"id");
+        assert!(cookie.value == ~"1");
+        assert!(cookie.domain == ~"example.com");
+        assert!(!cookie.host_only);
+        assert!(cookie.path == ~"/a");
+        assert!(cookie.secure);
+        assert!(cookie.http_only);
+        assert!(cookie.expiry.is_none());
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let cookie = Cookie::parse("id=1", &url("http://example.com/a/b/c.html"), HTTP, 0)
+            .unwrap();
+        assert!(cookie.domain == ~"example.com");
+        assert!(cookie.host_only);
+        assert!(cookie.path == ~"/a/b");
+    }
+
+    #[test]
+    fn parse_rejects_bad_cookies() {
+        let u = url("http://example.com/");
+        assert!(Cookie::parse("novalue", &u, HTTP, 0).is_none());
+        assert!(Cookie::parse("=1", &u, HTTP, 0).is_none());
+        assert!(Cookie::parse("id=1; Domain=other.com", &u, HTTP, 0).is_none());
+        assert!(Cookie::parse("id=1; HttpOnly", &u, NonHTTP, 0).is_none());
+    }
+
+    #[test]
+    fn parse_expiry() {
+        let u = url("http://example.com/");
+        let cookie = Cookie::parse("id=1; Max-Age=60", &u, HTTP, 1000).unwrap();
+        assert!(cookie.expiry == Some(1060));
+        assert!(!cookie.is_expired(1059));
+        assert!(cookie.is_expired(1060));
+
+        let cookie = Cookie::parse("id=1; Expires=Thu, 01 Jan 1970 00:16:40 GMT", &u, HTTP, 0)
+            .unwrap();
+        assert!(cookie.expiry == Some(1000));
+
+        let cookie = Cookie::parse("id=1; Max-Age=60; Expires=Thu, 01 Jan 1970 00:16:40 GMT",
+                                   &u, HTTP, 0).unwrap();
+        assert!(cookie.expiry == Some(60));
+    }
+
+    #[test]
+    fn matching() {
+        let cookie = Cookie::parse("id=1; Domain=example.com; Path=/a; Secure",
+                                   &url("https://example.com/"), HTTP, 0).unwrap();
+        assert!(cookie.matches(&url("https://www.example.com/a/b"), HTTP));
+        assert!(!cookie.matches(&url("http://www.example.com/a/b"), HTTP));
+        assert!(!cookie.matches(&url("https://www.example.com/ab"), HTTP));
+        assert!(!cookie.matches(&url("https://example.org/a"), HTTP));
+    }
+
+    #[test]
+    fn jar_round_trip() {
+        let mut jar = CookieJar::new();
+        let u = url("http://example.com/a/page.html");
+        jar.set_cookie("a=1; Path=/", &u, HTTP);
+        jar.set_cookie("b=2", &u, HTTP);
+        jar.set_cookie("c=3; HttpOnly", &u, HTTP);
+        assert!(jar.cookies_for_url(&u, HTTP) == Some(~"b=2; c=3; a=1"));
+        assert!(jar.cookies_for_url(&u, NonHTTP) == Some(~"b=2; a=1"));
+
+        // Replacing a cookie keeps its place; expiring one deletes it.
+        jar.set_cookie("a=4; Path=/", &u, NonHTTP);
+        jar.set_cookie("b=2; Max-Age=0", &u, NonHTTP);
+        assert!(jar.cookies_for_url(&u, NonHTTP) == Some(~"a=4"));
+
+        // Script can't clobber HttpOnly cookies.
+        jar.set_cookie("c=5", &u, NonHTTP);
+        assert!(jar.cookies_for_url(&u, HTTP) == Some(~"c=3; a=4"));
+
+        assert!(jar.cookies_for_url(&url("http://example.org/"), HTTP).is_none());
+    }
+}
@@ -0,0 +1,240 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The CORS protocol, through which a server lets documents of other origins read its responses.
+//! Requests that could do more than a form submission can are checked with the server first, in a
+//! preflight `OPTIONS` request. Responses are only passed on if their `Access-Control-Allow-*`
+//! headers allow the origin that asked for them, and then with only the headers the server
+//! exposes.
+//!
+//! FIXME: Preflight results aren't cached, so every request that needs one makes one, whatever
+//! `Access-Control-Max-Age` says.
+
+use http_cache::header;
+use resource_task::{LoadData, Metadata};
+
+use servo_util::url::Origin;
+use std::ascii::StrAsciiExt;
+use std::util::replace;
+
+/// Methods that a cross-origin request may use without a preflight.
+static SIMPLE_METHODS: [&'static str, ..3] = ["GET", "HEAD", "POST"];
+
+/// Request headers that a cross-origin request may set without a preflight.
+static SIMPLE_HEADERS: [&'static str, ..3] = ["accept", "accept-language", "content-language"];
+
+/// Content types that a cross-origin request may send without a preflight.
+static SIMPLE_CONTENT_TYPES: [&'static str, ..3] = [
+    "application/x-www-form-urlencoded",
+    "multipart/form-data",
+    "text/plain",
+];
+
+/// Response headers that are always exposed to the document that made a cross-origin request.
+static SIMPLE_RESPONSE_HEADERS: [&'static str, ..6] = [
+    "cache-control",
+    "content-language",
+    "content-type",
+    "expires",
+    "last-modified",
+    "pragma",
+];
+
+/// Whether `load_data` is a cross-origin request that must go through CORS.
+pub fn is_cross_origin(load_data: &LoadData) -> bool {
+    match load_data.origin {
+        Some(ref origin) => !origin.is_same_origin(&load_data.url),
+        None => false,
+    }
+}
+
+/// Whether the cross-origin request `load_data` needs a preflight before it can be made.
+pub fn needs_preflight(load_data: &LoadData) -> bool {
+    let simple_headers = do load_data.headers.iter().all |&(ref name, ref value)| {
+        is_simple_header(name.as_slice(), value.as_slice())
+    };
+    !SIMPLE_METHODS.iter().any(|&method| method == load_data.method) || !simple_headers
+}
+
+/// The preflight request that asks the server whether `load_data` may be made.
+pub fn preflight_request(load_data: &LoadData) -> LoadData {
+    let mut preflight = LoadData::new(load_data.url.clone());
+    preflight.method = ~"OPTIONS";
+    for origin in load_data.origin.iter() {
+        preflight.headers.push((~"Origin", origin.to_str()));
+    }
+    preflight.headers.push((~"Access-Control-Request-Method", load_data.method.clone()));
+    let headers: ~[~str] = load_data.headers.iter()
+        .filter(|&&(ref name, ref value)| !is_simple_header(name.as_slice(), value.as_slice()))
+        .map(|&(ref name, _)| name.to_ascii_lower())
+        .collect();
+    if !headers.is_empty() {
+        preflight.headers.push((~"Access-Control-Request-Headers", headers.connect(",")));
+    }
+    preflight
+}
+
+/// Whether the response to the preflight for `load_data` allows it to be made.
+pub fn check_preflight(load_data: &LoadData, metadata: &Metadata) -> bool {
+    if metadata.status < 200 || metadata.status >= 300 {
+        return false
+    }
+    let origin = match load_data.origin {
+        Some(ref origin) => origin,
+        None => return true,
+    };
+    if !check_response(origin, load_data.credentials, metadata) {
+        return false
+    }
+    let methods = header_list(metadata, "access-control-allow-methods");
+    let method_allowed = SIMPLE_METHODS.iter().any(|&method| method == load_data.method) ||
+        methods.iter().any(|method| *method == load_data.method);
+    let headers = header_list(metadata, "access-control-allow-headers");
+    let headers_allowed = do load_data.headers.iter().all |&(ref name, ref value)| {
+        is_simple_header(name.as_slice(), value.as_slice()) ||
+            headers.iter().any(|header| header.to_ascii_lower() == name.to_ascii_lower())
+    };
+    method_allowed && headers_allowed
+}
+
+/// Whether the response to a cross-origin request from `origin` may be read by it. Responses to
+/// requests with credentials have to name the origin, rather than allow any with `*`, and say
+/// they allow credentials.
+pub fn check_response(origin: &Origin, credentials: bool, metadata: &Metadata) -> bool {
+    let allowed = match header(metadata.headers, "access-control-allow-origin") {
+        Some(allowed) => allowed.trim().to_owned(),
+        None => return false,
+    };
+    if credentials {
+        let allows_credentials = header(metadata.headers, "access-control-allow-credentials")
+            .map_default(false, |value| "true" == value.trim());
+        allows_credentials && allowed == origin.to_str()
+    } else {
+        "*" == allowed || allowed == origin.to_str()
+    }
+}
+
+/// The metadata of a cross-origin response, without the headers the server doesn't expose.
+pub fn filter_response(metadata: Metadata) -> Metadata {
+    let exposed = header_list(&metadata, "access-control-expose-headers");
+    let mut metadata = metadata;
+    let headers = replace(&mut metadata.headers, ~[]);
+    metadata.headers = do headers.consume_iter().filter |&(ref name, _)| {
+        let name = name.to_ascii_lower();
+        SIMPLE_RESPONSE_HEADERS.iter().any(|&header| header == name) ||
+            exposed.iter().any(|header| header.to_ascii_lower() == name)
+    }.collect();
+    metadata
+}
+
+/// Whether a request header may be set on a cross-origin request without a preflight. The
+/// `Origin` header is set by the resource task itself.
+fn is_simple_header(name: &str, value: &str) -> bool {
+    let name = name.to_ascii_lower();
+    if "content-type" == name {
+        let mime_type = value.split_iter(';').next().unwrap_or("").trim().to_ascii_lower();
+        SIMPLE_CONTENT_TYPES.iter().any(|&content_type| content_type == mime_type)
+    } else {
+        "origin" == name || SIMPLE_HEADERS.iter().any(|&header| header == name)
+    }
+}
+
+/// The values of a comma-separated response header.
+fn header_list(metadata: &Metadata, name: &str) -> ~[~str] {
+    match header(metadata.headers, name) {
+        Some(value) => {
+            value.split_iter(',')
+                .map(|value| value.trim().to_owned())
+                .filter(|value| !value.is_empty())
+                .collect()
+        }
+        None => ~[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resource_task::{LoadData, Metadata};
+    use servo_util::url::Origin;
+    use extra::url;
+
+    fn request(method: &str, headers: ~[(~str, ~str)]) -> LoadData {
+        let mut load_data = LoadData::new(url::from_str("http://example.org/data").unwrap());
+        load_data.method = method.to_owned();
+        load_data.headers = headers;
+        load_data.origin = Some(origin());
+        load_data
+    }
+
+    fn response(headers: ~[(~str, ~str)]) -> Metadata {
+        let mut metadata = Metadata::default(url::from_str("http://example.org/data").unwrap());
+        metadata.headers = headers;
+        metadata
+    }
+
+    fn origin() -> Origin {
+        Origin::new(&url::from_str("http://example.com/").unwrap())
+    }
+
+    #[test]
+    fn should_preflight_only_requests_a_form_could_not_make() {
+        assert!(!needs_preflight(&request("GET", ~[])));
+        let text = ~[(~"Content-Type", ~"text/plain; charset=utf-8")];
+        assert!(!needs_preflight(&request("POST", text)));
+        assert!(needs_preflight(&request("PUT", ~[])));
+        assert!(needs_preflight(&request("POST", ~[(~"Content-Type", ~"application/json")])));
+        assert!(needs_preflight(&request("GET", ~[(~"X-Custom", ~"1")])));
+    }
+
+    #[test]
+    fn should_ask_for_the_method_and_headers_in_the_preflight() {
+        let preflight = preflight_request(&request("PUT", ~[(~"X-Custom", ~"1"),
+                                                            (~"Accept", ~"*/*")]));
+        assert!(preflight.method == ~"OPTIONS");
+        assert!(preflight.headers == ~[(~"Origin", ~"http://example.com"),
+                                       (~"Access-Control-Request-Method", ~"PUT"),
+                                       (~"Access-Control-Request-Headers", ~"x-custom")]);
+    }
+
+    #[test]
+    fn should_check_the_preflight_allows_the_method_and_headers() {
+        let load_data = request("PUT", ~[(~"X-Custom", ~"1")]);
+        let allowed = response(~[(~"Access-Control-Allow-Origin", ~"*"),
+                                 (~"Access-Control-Allow-Methods", ~"PUT, DELETE"),
+                                 (~"Access-Control-Allow-Headers", ~"X-Custom")]);
+        assert!(check_preflight(&load_data, &allowed));
+        let no_headers = response(~[(~"Access-Control-Allow-Origin", ~"*"),
+                                    (~"Access-Control-Allow-Methods", ~"PUT")]);
+        assert!(!check_preflight(&load_data, &no_headers));
+        let no_methods = response(~[(~"Access-Control-Allow-Origin", ~"*"),
+                                    (~"Access-Control-Allow-Headers", ~"X-Custom")]);
+        assert!(!check_preflight(&load_data, &no_methods));
+    }
+
+    #[test]
+    fn should_require_the_origin_to_be_named_with_credentials() {
+        let any = response(~[(~"Access-Control-Allow-Origin", ~"*"),
+                             (~"Access-Control-Allow-Credentials", ~"true")]);
+        assert!(check_response(&origin(), false, &any));
+        assert!(!check_response(&origin(), true, &any));
+        let named = response(~[(~"Access-Control-Allow-Origin", ~"http://example.com"),
+                               (~"Access-Control-Allow-Credentials", ~"true")]);
+        assert!(check_response(&origin(), true, &named));
+        let other = response(~[(~"Access-Control-Allow-Origin", ~"http://example.net")]);
+        assert!(!check_response(&origin(), false, &other));
+        assert!(!check_response(&origin(), false, &response(~[])));
+    }
+
+    #[test]
+    fn should_only_expose_simple_and_listed_headers() {
+        let metadata = filter_response(response(~[(~"Content-Type", ~"text/plain"),
+                                                  (~"Set-Cookie", ~"id=1"),
+                                                  (~"X-Secret", ~"1"),
+                                                  (~"X-Public", ~"2"),
+                                                  (~"Access-Control-Expose-Headers",
+                                                   ~"x-public")]));
+        assert!(metadata.headers == ~[(~"Content-Type", ~"text/plain"), (~"X-Public", ~"2")]);
+    }
+}
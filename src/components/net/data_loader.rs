@@ -0,0 +1,157 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use resource_task::{Done, Headers, LoaderTask, Metadata, Payload};
+
+use std::ascii::StrAsciiExt;
+use std::char;
+use std::str;
+use std::task;
+use extra::base64::FromBase64;
+use extra::url::Url;
+
+pub fn factory() -> LoaderTask {
+	let f: LoaderTask = |load_data, progress_chan| {
+		let url = load_data.url;
+		assert!("data" == url.scheme);
+		do task::spawn {
+			match parse_data_url(&url) {
+				Ok((content_type, body)) => {
+					let mut metadata = Metadata::default(url.clone());
+					metadata.headers = ~[(~"Content-Type", content_type.clone())];
+					metadata.content_type = Some(content_type);
+					metadata.content_length = Some(body.len());
+					progress_chan.send(Headers(metadata));
+					progress_chan.send(Payload(body));
+					progress_chan.send(Done(Ok(())));
+				}
+				Err(()) => {
+					debug!("data_loader: malformed data: URL %s", url.to_str());
+					progress_chan.send(Done(Err(())));
+				}
+			}
+		}
+	};
+	f
+}
+
+/// Splits a `data:` URL (RFC 2397) into its MIME type and the bytes it encodes.
+pub fn parse_data_url(url: &Url) -> Result<(~str, ~[u8]), ()> {
+	// FIXME: `extra::url` decodes the parts of the URL it recognizes, so we have to put it back
+	// together to find the original text.
+	let url_str = url.to_str();
+	let contents = match url_str.find(':') {
+		Some(index) => url_str.slice_from(index + 1),
+		None => return Err(()),
+	};
+	// The fragment isn't part of the data.
+	let contents = match contents.find('#') {
+		Some(index) => contents.slice_to(index),
+		None => contents,
+	};
+	let (header, data) = match contents.find(',') {
+		Some(index) => (contents.slice_to(index), contents.slice_from(index + 1)),
+		None => return Err(()),
+	};
+
+	let mut params: ~[~str] = header.split_iter(';').map(|param| {
+		param.trim().to_owned()
+	}).collect();
+	let is_base64 = params.len() > 1 &&
+		params.last().to_ascii_lower() == ~"base64";
+	if is_base64 {
+		params.pop();
+	}
+	let content_type = if params.is_empty() || params[0].is_empty() {
+		~"text/plain;charset=US-ASCII"
+	} else {
+		percent_decode_str(params.connect(";"))
+	};
+
+	let body = percent_decode(data);
+	if !is_base64 {
+		return Ok((content_type, body));
+	}
+	let body: ~[u8] = body.consume_iter().filter(|&b| !char::is_whitespace(b as char)).collect();
+	if !str::is_utf8(body) {
+		return Err(());
+	}
+	match str::from_utf8(body).from_base64() {
+		Ok(decoded) => Ok((content_type, decoded)),
+		Err(_) => Err(()),
+	}
+}
+
+/// Decodes `%XX` escapes into the bytes they stand for. Malformed escapes are left alone.
+fn percent_decode(input: &str) -> ~[u8] {
+	let bytes = input.as_bytes();
+	let mut result = ~[];
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == '%' as u8 && i + 2 < bytes.len() {
+			match (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+				(Some(high), Some(low)) => {
+					result.push(high << 4 | low);
+					i += 3;
+					loop;
+				}
+				_ => {}
+			}
+		}
+		result.push(bytes[i]);
+		i += 1;
+	}
+	result
+}
+
+fn percent_decode_str(input: &str) -> ~str {
+	let bytes = percent_decode(input);
+	if str::is_utf8(bytes) {
+		str::from_utf8(bytes)
+	} else {
+		input.to_owned()
+	}
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+	match byte as char {
+		'0'..'9' => Some(byte - ('0' as u8)),
+		'a'..'f' => Some(byte - ('a' as u8) + 10),
+		'A'..'F' => Some(byte - ('A' as u8) + 10),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use extra::url;
+
+	fn parse(url: &str) -> Result<(~str, ~[u8]), ()> {
+		parse_data_url(&url::from_str(url).unwrap())
+	}
+
+	#[test]
+	fn plain() {
+		assert!(parse("data:,hello%20world") ==
+				Ok((~"text/plain;charset=US-ASCII", bytes!("hello world").to_owned())));
+		assert!(parse("data:text/css,p{color:red}#frag") ==
+				Ok((~"text/css", bytes!("p{color:red}").to_owned())));
+		assert!(parse("data:,%ff") == Ok((~"text/plain;charset=US-ASCII", ~[0xff])));
+	}
+
+	#[test]
+	fn base64() {
+		assert!(parse("data:image/gif;base64,R0lG") ==
+				Ok((~"image/gif", bytes!("GIF").to_owned())));
+		assert!(parse("data:text/plain;charset=utf-8;BASE64,aGk=") ==
+				Ok((~"text/plain;charset=utf-8", bytes!("hi").to_owned())));
+	}
+
+	#[test]
+	fn malformed() {
+		assert!(parse("data:text/plain").is_err());
+		assert!(parse("data:;base64,!!!").is_err());
+	}
+}
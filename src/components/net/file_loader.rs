@@ -2,20 +2,26 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use resource_task::{Done, LoaderTask, Payload};
+use resource_task::{Done, Headers, LoaderTask, Metadata, Payload};
 
-use std::io::{ReaderUtil, file_reader};
+use std::io::{ReaderUtil, SeekEnd, SeekSet, file_reader};
 use std::task;
 
 static READ_SIZE: uint = 1024;
 
 pub fn factory() -> LoaderTask {
-	let f: LoaderTask = |url, progress_chan| {
+	let f: LoaderTask = |load_data, progress_chan| {
+		let url = load_data.url;
 		assert!("file" == url.scheme);
 		do task::spawn {
 			// FIXME: Resolve bug prevents us from moving the path out of the URL.
 			match file_reader(&Path(url.path)) {
 				Ok(reader) => {
+					let mut metadata = Metadata::default(url.clone());
+					reader.seek(0, SeekEnd);
+					metadata.content_length = Some(reader.tell());
+					reader.seek(0, SeekSet);
+					progress_chan.send(Headers(metadata));
 					while !reader.eof() {
 						let data = reader.read_bytes(READ_SIZE);
 						progress_chan.send(Payload(data));
@@ -0,0 +1,68 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The global history: the pages that have been visited, which links to match `:visited`.
+
+use extra::url::Url;
+use std::hashmap::HashSet;
+
+pub struct History {
+    /// The visited URLs, without their fragments.
+    priv visited: HashSet<~str>,
+}
+
+/// The URL as the history stores it. Links to other parts of a visited page count as visited.
+fn key(url: &Url) -> ~str {
+    let mut url = url.clone();
+    url.fragment = None;
+    url.to_str()
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Records a visit to `url`.
+    pub fn add(&mut self, url: &Url) {
+        self.visited.insert(key(url));
+    }
+
+    /// Whether `url` was visited.
+    pub fn contains(&self, url: &Url) -> bool {
+        self.visited.contains(&key(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extra::url::Url;
+    use std::from_str::FromStr;
+
+    fn url(url: &str) -> Url {
+        let url: Option<Url> = FromStr::from_str(url);
+        url.unwrap()
+    }
+
+    #[test]
+    fn visits() {
+        let mut history = History::new();
+        assert!(!history.contains(&url("http://example.com/a")));
+        history.add(&url("http://example.com/a"));
+        assert!(history.contains(&url("http://example.com/a")));
+        assert!(!history.contains(&url("http://example.com/b")));
+        assert!(!history.contains(&url("http://example.com/a?b")));
+    }
+
+    #[test]
+    fn fragments() {
+        let mut history = History::new();
+        history.add(&url("http://example.com/a#top"));
+        assert!(history.contains(&url("http://example.com/a")));
+        assert!(history.contains(&url("http://example.com/a#bottom")));
+    }
+}
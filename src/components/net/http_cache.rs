@@ -0,0 +1,557 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An HTTP cache, as described in RFC 7234. Responses are kept in memory, least recently used
+//! first out, and optionally written through to a directory on disk so they survive restarts.
+
+use resource_task::{LoadData, Metadata};
+
+use extra::time;
+use extra::url;
+use extra::url::Url;
+use std::ascii::StrAsciiExt;
+use std::hash::Hash;
+use std::i64;
+use std::io;
+use std::io::WriterUtil;
+use std::os;
+use std::str;
+use std::uint;
+
+/// The default amount of memory, in bytes, the cache may use for responses.
+pub static DEFAULT_MEMORY_LIMIT: uint = 32 * 1024 * 1024;
+
+/// The first line of every response stored on disk. Bump the version when the format changes.
+static DISK_FORMAT_MAGIC: &'static str = "servo-http-cache 1";
+
+/// Status codes whose responses may be stored without explicit freshness information
+/// (RFC 7231 § 6.1).
+static CACHEABLE_STATUSES: [uint, ..10] = [200, 203, 204, 300, 301, 404, 405, 410, 414, 501];
+
+/// A response stored in the cache.
+#[deriving(Clone)]
+pub struct CachedResponse {
+    metadata: Metadata,
+    body: ~[u8],
+    /// When the response was received, in seconds since the epoch.
+    response_time: i64,
+    /// How old the response already was when it was received, in seconds.
+    initial_age: i64,
+    /// How long after `response_time` the response may be used without revalidation.
+    freshness_lifetime: i64,
+}
+
+impl CachedResponse {
+    fn new(metadata: Metadata, body: ~[u8], now: i64) -> CachedResponse {
+        let mut response = CachedResponse {
+            metadata: metadata,
+            body: body,
+            response_time: now,
+            initial_age: 0,
+            freshness_lifetime: 0,
+        };
+        response.update_freshness(now);
+        response
+    }
+
+    /// Recomputes how fresh the response is from its headers (RFC 7234 § 4.2).
+    fn update_freshness(&mut self, now: i64) {
+        self.response_time = now;
+        self.initial_age = match header(self.metadata.headers, "age") {
+            Some(age) => i64::from_str(age.trim()).unwrap_or(0).max(&0),
+            None => 0,
+        };
+
+        let directives = CacheDirectives::parse(self.metadata.headers);
+        let date = header(self.metadata.headers, "date").chain(|date| parse_http_date(date));
+        let date = date.unwrap_or(now);
+        self.freshness_lifetime = if directives.no_cache {
+            0
+        } else {
+            match directives.max_age {
+                Some(max_age) => max_age,
+                None => {
+                    let expires = header(self.metadata.headers, "expires");
+                    let last_modified = header(self.metadata.headers, "last-modified");
+                    match (expires, last_modified) {
+                        // An unparseable `Expires` means the response has already expired.
+                        (Some(expires), _) => parse_http_date(expires).map_default(0, |&expires| {
+                            expires - date
+                        }),
+                        // Heuristic freshness: a tenth of the time since the last modification.
+                        (None, Some(last_modified)) => {
+                            parse_http_date(last_modified).map_default(0, |&last_modified| {
+                                (date - last_modified) / 10
+                            })
+                        }
+                        (None, None) => 0,
+                    }
+                }
+            }
+        };
+    }
+
+    /// How old the response is now, in seconds.
+    pub fn current_age(&self, now: i64) -> i64 {
+        self.initial_age + (now - self.response_time)
+    }
+
+    /// Whether the response may be used without asking the server first.
+    pub fn is_fresh(&self, now: i64) -> bool {
+        self.current_age(now) < self.freshness_lifetime
+    }
+
+    /// Adds the headers that ask the server whether this response is still good (RFC 7232).
+    pub fn add_validators(&self, load_data: &mut LoadData) {
+        for etag in header(self.metadata.headers, "etag").iter() {
+            load_data.headers.push((~"If-None-Match", etag.to_owned()));
+        }
+        for last_modified in header(self.metadata.headers, "last-modified").iter() {
+            load_data.headers.push((~"If-Modified-Since", last_modified.to_owned()));
+        }
+    }
+
+    fn has_validators(&self) -> bool {
+        header(self.metadata.headers, "etag").is_some() ||
+            header(self.metadata.headers, "last-modified").is_some()
+    }
+
+    /// Roughly how much memory the response takes up.
+    fn size(&self) -> uint {
+        let headers_size = do self.metadata.headers.iter().fold(0) |size, &(ref name, ref value)| {
+            size + name.len() + value.len()
+        };
+        self.body.len() + headers_size
+    }
+}
+
+/// The `Cache-Control` directives of a response that the cache cares about.
+struct CacheDirectives {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<i64>,
+}
+
+impl CacheDirectives {
+    fn parse(headers: &[(~str, ~str)]) -> CacheDirectives {
+        let mut directives = CacheDirectives {
+            no_store: false,
+            no_cache: false,
+            max_age: None,
+        };
+        for &(ref name, ref value) in headers.iter() {
+            if name.to_ascii_lower() != ~"cache-control" {
+                loop;
+            }
+            for directive in value.split_iter(',') {
+                let directive = directive.trim().to_ascii_lower();
+                let (name, argument) = match directive.find('=') {
+                    Some(index) => (directive.slice_to(index).trim(),
+                                    Some(directive.slice_from(index + 1).trim().trim_chars(&'"'))),
+                    None => (directive.as_slice(), None),
+                };
+                match name {
+                    "no-store" => directives.no_store = true,
+                    "no-cache" => directives.no_cache = true,
+                    "max-age" => {
+                        directives.max_age = argument.chain(|argument| i64::from_str(argument));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        directives
+    }
+}
+
+/// What the cache has for a request.
+pub enum CacheLookup {
+    /// A response that can be used as is.
+    Fresh(CachedResponse),
+    /// A response that has to be revalidated with the server before it can be used.
+    Stale(CachedResponse),
+    /// Nothing usable.
+    Miss,
+}
+
+pub struct HttpCache {
+    /// The responses kept in memory, keyed by URL, least recently used first.
+    priv entries: ~[(~str, CachedResponse)],
+    /// The sum of the sizes of `entries`.
+    priv memory_used: uint,
+    priv memory_limit: uint,
+    /// The directory responses are written through to, if any.
+    priv disk_dir: Option<Path>,
+}
+
+impl HttpCache {
+    pub fn new(memory_limit: uint, disk_dir: Option<Path>) -> HttpCache {
+        for dir in disk_dir.iter() {
+            if !os::path_is_dir(dir) && !os::make_dir(dir, 448 /* 0700 */) {
+                error!("http_cache: couldn't create cache directory %s", dir.to_str());
+            }
+        }
+        HttpCache {
+            entries: ~[],
+            memory_used: 0,
+            memory_limit: memory_limit,
+            disk_dir: disk_dir,
+        }
+    }
+
+    /// Whether requests like this one may be answered from the cache. Only plain `GET`s are.
+    pub fn can_answer(load_data: &LoadData) -> bool {
+        load_data.method == ~"GET" && load_data.data.is_none()
+    }
+
+    /// Whether a response with the given metadata may be stored.
+    pub fn is_storable(metadata: &Metadata) -> bool {
+        let directives = CacheDirectives::parse(metadata.headers);
+        // FIXME: Keep track of the request headers a response varies on instead of giving up.
+        let varies = header(metadata.headers, "vary").map_default(false, |vary| {
+            !vary.trim().is_empty()
+        });
+        CACHEABLE_STATUSES.iter().any(|&status| status == metadata.status) &&
+            !directives.no_store && !varies
+    }
+
+    /// Looks for a response to the given request.
+    pub fn lookup(&mut self, load_data: &LoadData, now: i64) -> CacheLookup {
+        if !HttpCache::can_answer(load_data) {
+            return Miss;
+        }
+
+        let key = load_data.url.to_str();
+        let response = match self.entries.iter().position(|&(ref url, _)| *url == key) {
+            Some(index) => {
+                // Move the entry to the back of the line.
+                let entry = self.entries.remove(index);
+                let response = entry.second_ref().clone();
+                self.entries.push(entry);
+                response
+            }
+            None => {
+                match self.read_from_disk(&key) {
+                    Some(response) => {
+                        self.insert_in_memory(key.clone(), response.clone());
+                        response
+                    }
+                    None => return Miss,
+                }
+            }
+        };
+
+        if response.is_fresh(now) {
+            Fresh(response)
+        } else if response.has_validators() {
+            Stale(response)
+        } else {
+            self.remove(&load_data.url);
+            Miss
+        }
+    }
+
+    /// Stores a response to a `GET` of `url`, if it may be stored and is any use to store.
+    pub fn store(&mut self, url: &Url, metadata: Metadata, body: ~[u8], now: i64) {
+        self.remove(url);
+        if !HttpCache::is_storable(&metadata) {
+            return;
+        }
+        let response = CachedResponse::new(metadata, body, now);
+        if response.freshness_lifetime <= 0 && !response.has_validators() {
+            return;
+        }
+
+        let key = url.to_str();
+        self.write_to_disk(&key, &response);
+        self.insert_in_memory(key, response);
+    }
+
+    /// Updates a stored response with the headers of a `304 Not Modified` response to a request
+    /// revalidating it, and returns the updated response (RFC 7234 § 4.3.4).
+    pub fn refresh(&mut self, url: &Url, not_modified: &Metadata, now: i64)
+                   -> Option<CachedResponse> {
+        let key = url.to_str();
+        let index = match self.entries.iter().position(|&(ref url, _)| *url == key) {
+            Some(index) => index,
+            None => return None,
+        };
+        let (key, mut response) = self.entries.remove(index);
+        self.memory_used -= response.size();
+
+        for &(ref name, ref value) in not_modified.headers.iter() {
+            let lower_name = name.to_ascii_lower();
+            response.metadata.headers.retain(|&(ref old_name, _)| {
+                old_name.to_ascii_lower() != lower_name
+            });
+            response.metadata.headers.push((name.clone(), value.clone()));
+        }
+        response.update_freshness(now);
+
+        self.write_to_disk(&key, &response);
+        self.insert_in_memory(key, response.clone());
+        Some(response)
+    }
+
+    /// Forgets any response for `url`. Used when a response can no longer be trusted, such as
+    /// after a `POST` to the same URL (RFC 7234 § 4.4).
+    pub fn remove(&mut self, url: &Url) {
+        let key = url.to_str();
+        match self.entries.iter().position(|&(ref url, _)| *url == key) {
+            Some(index) => {
+                let (_, response) = self.entries.remove(index);
+                self.memory_used -= response.size();
+            }
+            None => {}
+        }
+        for path in self.disk_path(&key).iter() {
+            if os::path_exists(path) {
+                os::remove_file(path);
+            }
+        }
+    }
+
+    fn insert_in_memory(&mut self, key: ~str, response: CachedResponse) {
+        let size = response.size();
+        if size > self.memory_limit {
+            return;
+        }
+        while self.memory_used + size > self.memory_limit {
+            let (_, evicted) = self.entries.shift();
+            self.memory_used -= evicted.size();
+        }
+        self.memory_used += size;
+        self.entries.push((key, response));
+    }
+
+    fn disk_path(&self, key: &str) -> Option<Path> {
+        do self.disk_dir.map |dir| {
+            dir.push(fmt!("%016x", key.hash() as uint))
+        }
+    }
+
+    /// Writes a response to disk. The format is a header of newline-terminated lines - the
+    /// magic, URL, status, status text, content type, response time, initial age, number of
+    /// headers and then the headers themselves - followed by the body.
+    fn write_to_disk(&self, key: &str, response: &CachedResponse) {
+        let path = match self.disk_path(key) {
+            Some(path) => path,
+            None => return,
+        };
+        let metadata = &response.metadata;
+        let mut lines = ~[DISK_FORMAT_MAGIC.to_owned(),
+                          key.to_owned(),
+                          metadata.status.to_str(),
+                          metadata.status_text.clone(),
+                          metadata.content_type.clone().unwrap_or(~""),
+                          response.response_time.to_str(),
+                          response.initial_age.to_str(),
+                          metadata.headers.len().to_str()];
+        for &(ref name, ref value) in metadata.headers.iter() {
+            lines.push(fmt!("%s: %s", *name, *value));
+        }
+
+        match io::file_writer(&path, [io::Create, io::Truncate]) {
+            Ok(writer) => {
+                for line in lines.iter() {
+                    // Header values can't contain newlines, so these lines are unambiguous.
+                    writer.write_str(*line);
+                    writer.write_char('\n');
+                }
+                writer.write(response.body);
+            }
+            Err(e) => error!("http_cache: couldn't write %s: %s", path.to_str(), e),
+        }
+    }
+
+    fn read_from_disk(&self, key: &str) -> Option<CachedResponse> {
+        let path = match self.disk_path(key) {
+            Some(path) => path,
+            None => return None,
+        };
+        if !os::path_exists(&path) {
+            return None;
+        }
+        let data = match io::read_whole_file(&path) {
+            Ok(data) => data,
+            Err(*) => return None,
+        };
+
+        let mut offset = 0;
+        let mut next_line = || -> Option<~str> {
+            let rest = data.slice_from(offset);
+            do rest.iter().position(|&byte| byte == '\n' as u8).chain |length| {
+                offset += length + 1;
+                let line = rest.slice_to(length);
+                if str::is_utf8(line) { Some(str::from_utf8(line)) } else { None }
+            }
+        };
+
+        if next_line() != Some(DISK_FORMAT_MAGIC.to_owned()) ||
+                next_line() != Some(key.to_owned()) {
+            return None;
+        }
+        let status = next_line().chain(|status| uint::from_str(status));
+        let status_text = next_line();
+        let content_type = next_line();
+        let response_time = next_line().chain(|time| i64::from_str(time));
+        let initial_age = next_line().chain(|age| i64::from_str(age));
+        let header_count = next_line().chain(|count| uint::from_str(count));
+        let (status, status_text, content_type, response_time, initial_age, header_count) =
+            match (status, status_text, content_type, response_time, initial_age, header_count) {
+                (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f)) => (a, b, c, d, e, f),
+                _ => return None,
+            };
+
+        let mut headers = ~[];
+        for _ in range(0, header_count) {
+            let line = match next_line() {
+                Some(line) => line,
+                None => return None,
+            };
+            match line.find(':') {
+                Some(index) => headers.push((line.slice_to(index).to_owned(),
+                                             line.slice_from(index + 1).trim().to_owned())),
+                None => return None,
+            }
+        }
+
+        let url = match url::from_str(key) {
+            Ok(url) => url,
+            Err(*) => return None,
+        };
+        let body = data.slice_from(offset).to_owned();
+        let mut metadata = Metadata::default(url);
+        metadata.status = status;
+        metadata.status_text = status_text;
+        metadata.content_type = if content_type.is_empty() { None } else { Some(content_type) };
+        metadata.content_length = Some(body.len());
+        metadata.headers = headers;
+
+        let mut response = CachedResponse::new(metadata, body, response_time);
+        response.initial_age = initial_age;
+        Some(response)
+    }
+}
+
+/// Returns the value of the first header with the given (lower case) name.
+pub fn header<'a>(headers: &'a [(~str, ~str)], name: &str) -> Option<&'a str> {
+    for &(ref header_name, ref value) in headers.iter() {
+        if header_name.to_ascii_lower().as_slice() == name {
+            return Some(value.as_slice());
+        }
+    }
+    None
+}
+
+/// Parses an HTTP date, in seconds since the epoch (RFC 7231 § 7.1.1.1).
+fn parse_http_date(date: &str) -> Option<i64> {
+    let formats = ["%a, %d %b %Y %H:%M:%S GMT",
+                   "%A, %d-%b-%y %H:%M:%S GMT",
+                   "%a %b %e %H:%M:%S %Y"];
+    for format in formats.iter() {
+        match time::strptime(date.trim(), *format) {
+            Ok(tm) => return Some(tm.to_timespec().sec),
+            Err(*) => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resource_task::{LoadData, Metadata};
+    use extra::url;
+
+    fn metadata(url: &str, headers: ~[(~str, ~str)]) -> Metadata {
+        let mut metadata = Metadata::default(url::from_str(url).unwrap());
+        metadata.headers = headers;
+        metadata
+    }
+
+    fn lookup(cache: &mut HttpCache, url: &str, now: i64) -> CacheLookup {
+        cache.lookup(&LoadData::new(url::from_str(url).unwrap()), now)
+    }
+
+    #[test]
+    fn max_age() {
+        let mut cache = HttpCache::new(DEFAULT_MEMORY_LIMIT, None);
+        let url = url::from_str("http://example.com/a").unwrap();
+        cache.store(&url, metadata("http://example.com/a",
+                                   ~[(~"Cache-Control", ~"public, max-age=60")]), ~[1, 2], 1000);
+        match lookup(&mut cache, "http://example.com/a", 1059) {
+            Fresh(response) => assert!(response.body == ~[1, 2]),
+            _ => fail!("expected a fresh response"),
+        }
+        // Stale, and there's no way to revalidate it.
+        match lookup(&mut cache, "http://example.com/a", 1060) {
+            Miss => {}
+            _ => fail!("expected a miss"),
+        }
+    }
+
+    #[test]
+    fn revalidation() {
+        let mut cache = HttpCache::new(DEFAULT_MEMORY_LIMIT, None);
+        let url = url::from_str("http://example.com/b").unwrap();
+        cache.store(&url, metadata("http://example.com/b",
+                                   ~[(~"Cache-Control", ~"no-cache"), (~"ETag", ~"\"v1\"")]),
+                    ~[3], 1000);
+        let response = match lookup(&mut cache, "http://example.com/b", 1000) {
+            Stale(response) => response,
+            _ => fail!("expected a stale response"),
+        };
+        let mut load_data = LoadData::new(url.clone());
+        response.add_validators(&mut load_data);
+        assert!(load_data.headers == ~[(~"If-None-Match", ~"\"v1\"")]);
+
+        let not_modified = metadata("http://example.com/b",
+                                    ~[(~"Cache-Control", ~"max-age=10")]);
+        let refreshed = cache.refresh(&url, &not_modified, 2000).unwrap();
+        assert!(refreshed.body == ~[3]);
+        match lookup(&mut cache, "http://example.com/b", 2005) {
+            Fresh(*) => {}
+            _ => fail!("expected a fresh response"),
+        }
+    }
+
+    #[test]
+    fn not_storable() {
+        let mut cache = HttpCache::new(DEFAULT_MEMORY_LIMIT, None);
+        let url = url::from_str("http://example.com/c").unwrap();
+        cache.store(&url, metadata("http://example.com/c",
+                                   ~[(~"Cache-Control", ~"no-store, max-age=60")]), ~[], 1000);
+        match lookup(&mut cache, "http://example.com/c", 1000) {
+            Miss => {}
+            _ => fail!("no-store responses must not be stored"),
+        }
+        // Responses without freshness information or validators are no use.
+        cache.store(&url, metadata("http://example.com/c", ~[]), ~[], 1000);
+        match lookup(&mut cache, "http://example.com/c", 1000) {
+            Miss => {}
+            _ => fail!("expected a miss"),
+        }
+    }
+
+    #[test]
+    fn eviction() {
+        let headers = ~[(~"Cache-Control", ~"max-age=60")];
+        let size = headers[0].first_ref().len() + headers[0].second_ref().len();
+        let mut cache = HttpCache::new(size * 2 + 8, None);
+        let urls = ["http://example.com/1", "http://example.com/2", "http://example.com/3"];
+        for name in urls.iter() {
+            let url = url::from_str(*name).unwrap();
+            cache.store(&url, metadata(*name, headers.clone()), ~[0, 0, 0, 0], 1000);
+        }
+        match lookup(&mut cache, "http://example.com/1", 1000) {
+            Miss => {}
+            _ => fail!("the least recently used response should have been evicted"),
+        }
+        match lookup(&mut cache, "http://example.com/3", 1000) {
+            Fresh(*) => {}
+            _ => fail!("expected a fresh response"),
+        }
+    }
+}
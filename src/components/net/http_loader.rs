@@ -2,47 +2,386 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use resource_task::{Payload, Done, LoaderTask};
+//! Loads `http:` URLs with HTTP/1.1, over a connection of their own that's closed once the
+//! response has arrived. Redirects are followed; every other response, whatever its status, is
+//! passed on as it is, with its status line and headers in the metadata.
+//!
+//! FIXME: Connections aren't kept alive, and there's no TLS, so `https:` can't be loaded.
 
-use std::comm::SharedChan;
+use http_cache::header;
+use resource_task::{Done, Headers, LoadData, LoaderTask, Metadata, Payload, ProgressMsg};
+use util::{open_stream, read_exact, write_all};
+
+use std::ascii::StrAsciiExt;
+use std::cell::Cell;
+use std::comm::Chan;
+use std::rt::io::{Reader, io_error};
+use std::str;
 use std::task;
-use http_client::uv_http_request;
-use http_client;
+use std::uint;
+use std::vec;
+use extra::url::{Url, query_to_str};
+use servo_util::url::make_url;
+
+/// The most redirects that are followed before the load fails.
+static MAX_REDIRECTS: uint = 20;
+/// The longest status line and headers that are read, in bytes.
+static MAX_HEAD_LENGTH: uint = 64 * 1024;
+/// How much of the body is read at a time.
+static READ_SIZE: uint = 16 * 1024;
 
 pub fn factory() -> LoaderTask {
-	let f: LoaderTask = |url, progress_chan| {
-		assert!(url.scheme == ~"http");
-
-		let progress_chan = SharedChan::new(progress_chan);
-		do task::spawn {
-			debug!("http_loader: requesting via http: %?", url.clone());
-			let mut request = uv_http_request(url.clone());
-			let errored = @mut false;
-			let url = url.clone();
-			{
-				let progress_chan = progress_chan.clone();
-				do request.begin |event| {
-					let url = url.clone();
-					match event {
-						http_client::Status(*) => { }
-						http_client::Payload(data) => {
-							debug!("http_loader: got data from %?", url);
-							let data = data.take();
-							progress_chan.send(Payload(data));
-						}
-						http_client::Error(*) => {
-							debug!("http_loader: error loading %?", url);
-							*errored = true;
-							progress_chan.send(Done(Err(())));
-						}
-					}
-				}
-			}
-
-			if !*errored {
-				progress_chan.send(Done(Ok(())));
-			}
-		}
-	};
-	f
+    let f: LoaderTask = |load_data, progress_chan| {
+        assert!(load_data.url.scheme == ~"http");
+        let load_data = Cell::new(load_data);
+        let progress_chan = Cell::new(progress_chan);
+        do task::spawn {
+            load(load_data.take(), &progress_chan.take());
+        }
+    };
+    f
+}
+
+/// Makes the request `load_data`, following any redirects, and streams the response back.
+fn load(load_data: LoadData, progress_chan: &Chan<ProgressMsg>) {
+    let mut load_data = load_data;
+    let mut redirects = 0;
+    loop {
+        let url = load_data.url.clone();
+        debug!("http_loader: requesting %s %s", load_data.method, url.to_str());
+        let mut stream = match open_stream(&url) {
+            Some(stream) => stream,
+            None => {
+                debug!("http_loader: can't connect to %s", url.to_str());
+                progress_chan.send(Done(Err(())));
+                return
+            }
+        };
+        if write_all(&mut stream, request(&load_data)).is_err() {
+            progress_chan.send(Done(Err(())));
+            return
+        }
+        let metadata = match read_response_head(&mut stream) {
+            Ok(head) => parse_response_head(url.clone(), head),
+            Err(()) => None,
+        };
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => {
+                debug!("http_loader: bad response from %s", url.to_str());
+                progress_chan.send(Done(Err(())));
+                return
+            }
+        };
+
+        match redirect(&load_data, &metadata) {
+            Some(redirected) => {
+                redirects += 1;
+                if redirects > MAX_REDIRECTS || redirected.url.scheme != ~"http" {
+                    debug!("http_loader: not following the redirect from %s", url.to_str());
+                    progress_chan.send(Done(Err(())));
+                    return
+                }
+                load_data = redirected;
+                loop;
+            }
+            None => {}
+        }
+
+        let length = body_length(&load_data, &metadata);
+        progress_chan.send(Headers(metadata));
+        let result = match length {
+            NoBody => Ok(()),
+            Length(length) => read_body(&mut stream, Some(length), progress_chan),
+            UntilClosed => read_body(&mut stream, None, progress_chan),
+            Chunked => read_chunked_body(&mut stream, progress_chan),
+        };
+        progress_chan.send(Done(result));
+        return
+    }
+}
+
+/// The request line, headers and body of `load_data`.
+fn request(load_data: &LoadData) -> ~[u8] {
+    let url = &load_data.url;
+    let mut resource = if url.path.is_empty() { ~"/" } else { url.path.clone() };
+    if !url.query.is_empty() {
+        resource.push_str(fmt!("?%s", query_to_str(&url.query)));
+    }
+    let host = match url.port {
+        Some(ref port) => fmt!("%s:%s", url.host, *port),
+        None => url.host.clone(),
+    };
+    let mut head = fmt!("%s %s HTTP/1.1\r\n", load_data.method, resource);
+    head.push_str(fmt!("Host: %s\r\n", host));
+    head.push_str("Connection: close\r\n");
+    for &(ref name, ref value) in load_data.headers.iter() {
+        head.push_str(fmt!("%s: %s\r\n", *name, *value));
+    }
+    match load_data.data {
+        Some(ref data) => head.push_str(fmt!("Content-Length: %u\r\n", data.len())),
+        // Servers may refuse these without a length, even when there's no body.
+        None if load_data.method == ~"POST" || load_data.method == ~"PUT" => {
+            head.push_str("Content-Length: 0\r\n");
+        }
+        None => {}
+    }
+    head.push_str("\r\n");
+
+    let mut request = head.as_bytes().to_owned();
+    for data in load_data.data.iter() {
+        request.push_all(*data);
+    }
+    request
+}
+
+/// Reads the status line and headers of a response.
+fn read_response_head<R: Reader>(reader: &mut R) -> Result<~str, ()> {
+    let mut head = ~[];
+    let mut byte = [0u8];
+    while head.len() < 4 || head.slice_from(head.len() - 4) != bytes!("\r\n\r\n") {
+        if head.len() >= MAX_HEAD_LENGTH || read_exact(reader, byte).is_err() {
+            return Err(())
+        }
+        head.push(byte[0]);
+    }
+    if str::is_utf8(head) { Ok(str::from_utf8(head)) } else { Err(()) }
+}
+
+/// The metadata of a response from `url` with the given status line and headers.
+fn parse_response_head(url: Url, head: &str) -> Option<Metadata> {
+    let mut lines = head.split_str_iter("\r\n");
+    let status_line = lines.next().unwrap_or("");
+    let status: ~[&str] = status_line.splitn_iter(' ', 2).collect();
+    if status.len() < 2 || !status[0].starts_with("HTTP/") {
+        return None
+    }
+
+    let mut metadata = Metadata::default(url);
+    metadata.status = match from_str::<uint>(status[1]) {
+        Some(status) => status,
+        None => return None,
+    };
+    metadata.status_text = if status.len() > 2 { status[2].to_owned() } else { ~"" };
+    for line in lines {
+        match line.find(':') {
+            Some(index) => {
+                let name = line.slice_to(index).trim().to_owned();
+                let value = line.slice_from(index + 1).trim().to_owned();
+                match name.to_ascii_lower().as_slice() {
+                    "content-type" => metadata.content_type = Some(value.clone()),
+                    "content-length" => metadata.content_length = from_str::<uint>(value.as_slice()),
+                    _ => {}
+                }
+                metadata.headers.push((name, value));
+            }
+            None => {}
+        }
+    }
+    Some(metadata)
+}
+
+/// The request to make next if the response to `load_data` redirects it elsewhere.
+fn redirect(load_data: &LoadData, metadata: &Metadata) -> Option<LoadData> {
+    match metadata.status {
+        301 | 302 | 303 | 307 | 308 => {}
+        _ => return None,
+    }
+    let location = match header(metadata.headers, "location") {
+        Some(location) => location.to_owned(),
+        None => return None,
+    };
+    let mut redirected = load_data.clone();
+    redirected.url = make_url(location, Some(load_data.url.clone()));
+    // As browsers do, a POST that's moved is followed with a GET.
+    let to_get = match metadata.status {
+        303 => load_data.method != ~"HEAD",
+        301 | 302 => load_data.method == ~"POST",
+        _ => false,
+    };
+    if to_get {
+        redirected.method = ~"GET";
+        redirected.data = None;
+        redirected.headers.retain(|&(ref name, _)| !name.eq_ignore_ascii_case("content-type"));
+    }
+    Some(redirected)
+}
+
+/// How the end of a response body is found.
+enum BodyLength {
+    NoBody,
+    /// The body is the given number of bytes long.
+    Length(uint),
+    /// The body is sent in chunks, each with its length, ending with an empty one.
+    Chunked,
+    /// The body goes on until the server closes the connection.
+    UntilClosed,
+}
+
+fn body_length(load_data: &LoadData, metadata: &Metadata) -> BodyLength {
+    if load_data.method == ~"HEAD" || metadata.status / 100 == 1 || metadata.status == 204 ||
+            metadata.status == 304 {
+        return NoBody
+    }
+    let chunked = do header(metadata.headers, "transfer-encoding").map_default(false) |encoding| {
+        encoding.split_iter(',').any(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+    };
+    if chunked {
+        return Chunked
+    }
+    match metadata.content_length {
+        Some(length) => Length(length),
+        None => UntilClosed,
+    }
+}
+
+/// Reads some of the body into `buf`, returning how much was read, or None once the server has
+/// closed the connection.
+fn read_some<R: Reader>(reader: &mut R, buf: &mut [u8]) -> Result<Option<uint>, ()> {
+    let mut errored = false;
+    let read = do io_error::cond.trap(|_| errored = true).inside {
+        reader.read(buf)
+    };
+    if errored { Err(()) } else { Ok(read) }
+}
+
+/// Sends the body as it arrives, until `length` bytes have, or, if it's None, until the server
+/// closes the connection.
+fn read_body<R: Reader>(reader: &mut R, length: Option<uint>, progress_chan: &Chan<ProgressMsg>)
+                        -> Result<(), ()> {
+    let mut remaining = length;
+    let mut buf = [0u8, ..READ_SIZE];
+    loop {
+        let size = match remaining {
+            Some(0) => return Ok(()),
+            Some(remaining) if remaining < READ_SIZE => remaining,
+            _ => READ_SIZE,
+        };
+        match read_some(reader, buf.mut_slice_to(size)) {
+            Ok(Some(read)) => {
+                progress_chan.send(Payload(buf.slice_to(read).to_owned()));
+                remaining = remaining.map(|&remaining| remaining - read);
+            }
+            // A body cut short of its length is a network error.
+            Ok(None) => return if remaining.is_none() { Ok(()) } else { Err(()) },
+            Err(()) => return Err(()),
+        }
+    }
+}
+
+/// Sends each chunk of a chunked body as it arrives.
+fn read_chunked_body<R: Reader>(reader: &mut R, progress_chan: &Chan<ProgressMsg>)
+                                -> Result<(), ()> {
+    loop {
+        let line = match read_line(reader) {
+            Ok(line) => line,
+            Err(()) => return Err(()),
+        };
+        // Chunk extensions, after a semicolon, mean nothing to us.
+        let size = line.split_iter(';').next().unwrap_or("").trim();
+        let size = match uint::from_str_radix(size, 16) {
+            Some(size) => size,
+            None => return Err(()),
+        };
+        if size == 0 {
+            // Skip the trailer.
+            loop {
+                match read_line(reader) {
+                    Ok(line) => if line.is_empty() { return Ok(()) },
+                    Err(()) => return Err(()),
+                }
+            }
+        }
+        let mut chunk = vec::from_elem(size, 0u8);
+        if read_exact(reader, chunk).is_err() || read_line(reader) != Ok(~"") {
+            return Err(())
+        }
+        progress_chan.send(Payload(chunk));
+    }
+}
+
+/// Reads a line ending with CRLF, without the CRLF.
+fn read_line<R: Reader>(reader: &mut R) -> Result<~str, ()> {
+    let mut line = ~[];
+    let mut byte = [0u8];
+    while line.len() < 2 || line.slice_from(line.len() - 2) != bytes!("\r\n") {
+        if line.len() >= MAX_HEAD_LENGTH || read_exact(reader, byte).is_err() {
+            return Err(())
+        }
+        line.push(byte[0]);
+    }
+    let line = line.slice_to(line.len() - 2);
+    if str::is_utf8(line) { Ok(str::from_utf8(line)) } else { Err(()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunked, Length, NoBody, UntilClosed, body_length, parse_response_head};
+    use super::{read_chunked_body, request};
+    use resource_task::{LoadData, Payload};
+    use std::comm;
+    use std::rt::io::mem::MemReader;
+    use extra::url;
+
+    #[test]
+    fn should_send_the_method_headers_and_body() {
+        let mut load_data = LoadData::new(url::from_str("http://example.com:8000/a?b=c").unwrap());
+        load_data.method = ~"POST";
+        load_data.headers = ~[(~"Content-Type", ~"text/plain")];
+        load_data.data = Some(bytes!("hi").to_owned());
+        assert!(request(&load_data) == bytes!("POST /a?b=c HTTP/1.1\r\n\
+                                               Host: example.com:8000\r\n\
+                                               Connection: close\r\n\
+                                               Content-Type: text/plain\r\n\
+                                               Content-Length: 2\r\n\r\n\
+                                               hi").to_owned());
+    }
+
+    #[test]
+    fn should_parse_the_status_and_headers() {
+        let url = url::from_str("http://example.com/").unwrap();
+        let head = "HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\nContent-Length: 5\r\n\r\n";
+        let metadata = parse_response_head(url.clone(), head).unwrap();
+        assert!(metadata.status == 404);
+        assert!(metadata.status_text == ~"Not Found");
+        assert!(metadata.content_type == Some(~"text/html"));
+        assert!(metadata.content_length == Some(5));
+        assert!(metadata.headers == ~[(~"Content-Type", ~"text/html"), (~"Content-Length", ~"5")]);
+        assert!(parse_response_head(url, "ICY 200 OK\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn should_find_the_end_of_the_body() {
+        let url = url::from_str("http://example.com/").unwrap();
+        let load_data = LoadData::new(url.clone());
+        let metadata = |head: &str| parse_response_head(url.clone(), head).unwrap();
+        let length = |head: &str| body_length(&load_data, &metadata(head));
+        match length("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n") {
+            Length(5) => {}
+            _ => fail!(~"expected a length"),
+        }
+        match length("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n") {
+            Chunked => {}
+            _ => fail!(~"expected chunks"),
+        }
+        match length("HTTP/1.1 200 OK\r\n") {
+            UntilClosed => {}
+            _ => fail!(~"expected the body to go on until the connection is closed"),
+        }
+        match length("HTTP/1.1 304 Not Modified\r\n") {
+            NoBody => {}
+            _ => fail!(~"expected no body"),
+        }
+    }
+
+    #[test]
+    fn should_read_chunked_bodies() {
+        let (port, chan) = comm::stream();
+        let body = bytes!("5\r\nhello\r\n1;ext=1\r\n!\r\n0\r\nTrailer: 1\r\n\r\n").to_owned();
+        assert!(read_chunked_body(&mut MemReader::new(body), &chan) == Ok(()));
+        assert!(port.recv() == Payload(bytes!("hello").to_owned()));
+        assert!(port.recv() == Payload(bytes!("!").to_owned()));
+        let cut_short = bytes!("5\r\nhel").to_owned();
+        assert!(read_chunked_body(&mut MemReader::new(cut_short), &chan) == Err(()));
+    }
 }
@@ -2,7 +2,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use image::webp;
+
 use std::vec;
+use extra::arc::Arc;
 use stb_image = stb_image::image;
 
 // FIXME: Images must not be copied every frame. Instead we should atomically
@@ -14,6 +17,88 @@ pub fn Image(width: uint, height: uint, depth: uint, data: ~[u8]) -> Image {
     stb_image::new_image(width, height, depth, data)
 }
 
+/// What happens to a frame of an animation once its delay is up, before the next one is drawn.
+#[deriving(Clone, Eq)]
+pub enum DisposalMethod {
+    /// Leave it in place, for the next frame to be drawn over.
+    DisposeNone,
+    /// Clear its area to transparent.
+    DisposeToBackground,
+    /// Put back what was there before it was drawn.
+    DisposeToPrevious,
+}
+
+pub struct AnimationFrame {
+    /// The whole image as it looks while this frame is shown.
+    image: Arc<~Image>,
+    /// How long to show the frame for, in milliseconds.
+    delay: uint,
+    disposal: DisposalMethod,
+}
+
+/// An image with more than one frame.
+pub struct Animation {
+    width: uint,
+    height: uint,
+    frames: ~[AnimationFrame],
+    /// How many times to play the animation through, or `None` to loop forever.
+    play_count: Option<uint>,
+}
+
+impl Animation {
+    /// Finds the frame to show `elapsed` milliseconds after the animation started, and how many
+    /// milliseconds are left until the next one. Once the animation has finished playing, the last
+    /// frame stays up and there is no next one.
+    pub fn frame_at(&self, elapsed: u64) -> (uint, Option<uint>) {
+        let total = self.frames.iter().fold(0u64, |total, frame| total + frame.delay as u64);
+        if self.frames.len() <= 1 || total == 0 {
+            return (0, None);
+        }
+        match self.play_count {
+            Some(count) if elapsed >= total * count as u64 => {
+                return (self.frames.len() - 1, None)
+            }
+            _ => {}
+        }
+
+        let mut remaining = elapsed % total;
+        for (i, frame) in self.frames.iter().enumerate() {
+            if remaining < frame.delay as u64 {
+                return (i, Some((frame.delay as u64 - remaining) as uint));
+            }
+            remaining -= frame.delay as u64;
+        }
+        (self.frames.len() - 1, None)
+    }
+}
+
+/// The width and height of the placeholder drawn in place of broken images.
+pub static BROKEN_IMAGE_SIZE: uint = 16;
+
+/// Creates the placeholder drawn in place of images that failed to load or decode: a light gray
+/// square with a darker frame and a red cross through it.
+pub fn broken_image() -> Image {
+    let size = BROKEN_IMAGE_SIZE;
+    let data = do vec::from_fn(size * size * 4) |i| {
+        let (x, y) = ((i / 4) % size, (i / 4) / size);
+        let (r, g, b) = if x == 0 || y == 0 || x == size - 1 || y == size - 1 {
+            (0x80u8, 0x80u8, 0x80u8)
+        } else if x == y || x == size - 1 - y {
+            (0xccu8, 0x00u8, 0x00u8)
+        } else {
+            (0xe0u8, 0xe0u8, 0xe0u8)
+        };
+        // Same byte order as decoded images.
+        match i % 4 {
+            0 => b,
+            1 => g,
+            2 => r,
+            _ => 0xffu8,
+        }
+    };
+    Image(size, size, 4, data)
+}
+
 static TEST_IMAGE: [u8, ..4962] = include_bin!("test.jpeg");
 
 pub fn test_image_bin() -> ~[u8] {
@@ -24,6 +109,11 @@ pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
     // Can't remember why we do this. Maybe it's what cairo wants
     static FORCE_DEPTH: uint = 4;
 
+    // stb_image doesn't know WebP.
+    if webp::is_webp(buffer) {
+        return webp::decode(buffer);
+    }
+
     match stb_image::load_from_memory_with_depth(buffer, FORCE_DEPTH, true) {
         stb_image::ImageU8(image) => {
             assert!(image.depth == 4);
@@ -0,0 +1,50 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The tasks that images are decoded on. Each has a thread of its own, so that decoding a big
+//! image doesn't hold up loads, layout or script running on the other threads.
+
+use std::cell::Cell;
+use std::comm::{Chan, SharedPort, stream};
+use std::rt::util::num_cpus;
+use std::task::{SingleThreaded, task};
+
+/// How many images can be decoded at once by default: one per core.
+pub fn default_decoder_threads() -> uint {
+    num_cpus()
+}
+
+pub struct DecoderPool {
+    priv chan: Chan<~fn()>,
+}
+
+impl DecoderPool {
+    pub fn new(threads: uint) -> DecoderPool {
+        let (port, chan) = stream();
+        let port = SharedPort::new(port);
+        for _ in range(0, threads) {
+            let port = Cell::new(port.clone());
+            let mut builder = task();
+            builder.sched_mode(SingleThreaded);
+            do builder.spawn {
+                let port = port.take();
+                loop {
+                    match port.try_recv() {
+                        Some(job) => job(),
+                        // The pool has been dropped.
+                        None => break
+                    }
+                }
+            }
+        }
+        DecoderPool {
+            chan: chan,
+        }
+    }
+
+    /// Runs `job` on the first decoder thread to come free.
+    pub fn execute(&self, job: ~fn()) {
+        self.chan.send(job);
+    }
+}
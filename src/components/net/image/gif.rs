@@ -0,0 +1,447 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A GIF decoder (GIF89a, including animation).
+//!
+//! Every frame is composited onto the logical screen, so each frame of the resulting animation is
+//! a whole image that can be drawn on its own.
+
+use image::base::{Animation, AnimationFrame, DisposalMethod, DisposeNone, DisposeToBackground};
+use image::base::{DisposeToPrevious, Image};
+
+use std::vec;
+use extra::arc::Arc;
+
+/// The longest LZW code in a GIF is 12 bits.
+static MAX_CODES: uint = 4096;
+
+/// Frames with shorter delays than this, in milliseconds, are slowed down to `DEFAULT_DELAY`, as
+/// other browsers do; a lot of GIFs in the wild depend on it.
+static MIN_DELAY: uint = 20;
+static DEFAULT_DELAY: uint = 100;
+
+macro_rules! try_opt(
+    ($e:expr) => (
+        match $e {
+            Some(value) => value,
+            None => return None,
+        }
+    )
+)
+
+/// Returns true if the data starts like a GIF.
+pub fn is_gif(data: &[u8]) -> bool {
+    data.len() >= 6 && data.slice_to(6) == bytes!("GIF87a") ||
+        data.len() >= 6 && data.slice_to(6) == bytes!("GIF89a")
+}
+
+/// The graphic control extension that applies to the next frame.
+struct GraphicControl {
+    disposal: DisposalMethod,
+    delay: uint,
+    transparent_index: Option<u8>,
+}
+
+impl GraphicControl {
+    fn new() -> GraphicControl {
+        GraphicControl {
+            disposal: DisposeNone,
+            delay: 0,
+            transparent_index: None,
+        }
+    }
+}
+
+struct Reader<'self> {
+    data: &'self [u8],
+    position: uint,
+}
+
+impl<'self> Reader<'self> {
+    fn byte(&mut self) -> Option<u8> {
+        if self.position < self.data.len() {
+            self.position += 1;
+            Some(self.data[self.position - 1])
+        } else {
+            None
+        }
+    }
+
+    fn u16(&mut self) -> Option<uint> {
+        let low = try_opt!(self.byte());
+        let high = try_opt!(self.byte());
+        Some(low as uint | (high as uint << 8))
+    }
+
+    fn bytes(&mut self, count: uint) -> Option<&'self [u8]> {
+        if self.position + count <= self.data.len() {
+            self.position += count;
+            Some(self.data.slice(self.position - count, self.position))
+        } else {
+            None
+        }
+    }
+
+    /// Reads a sequence of data sub-blocks, up to and including the empty block that ends it.
+    fn sub_blocks(&mut self) -> Option<~[u8]> {
+        let mut result = ~[];
+        loop {
+            let size = try_opt!(self.byte()) as uint;
+            if size == 0 {
+                return Some(result);
+            }
+            result.push_all(try_opt!(self.bytes(size)));
+        }
+    }
+
+    /// Reads a color table with the given number of entries.
+    fn color_table(&mut self, entries: uint) -> Option<~[u8]> {
+        Some(try_opt!(self.bytes(entries * 3)).to_owned())
+    }
+}
+
+/// Decodes a GIF into its frames. Returns `None` if the data is corrupt or has no frames at all.
+/// Data that's cut off partway through a frame decodes as far as it goes.
+pub fn decode(data: &[u8]) -> Option<Animation> {
+    if !is_gif(data) {
+        return None;
+    }
+    let mut reader = Reader { data: data, position: 6 };
+
+    // The logical screen descriptor.
+    let width = try_opt!(reader.u16());
+    let height = try_opt!(reader.u16());
+    let flags = try_opt!(reader.byte());
+    let _background_index = try_opt!(reader.byte());
+    let _aspect_ratio = try_opt!(reader.byte());
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let global_colors = if flags & 0x80 != 0 {
+        Some(try_opt!(reader.color_table(1 << ((flags & 0x07) + 1))))
+    } else {
+        None
+    };
+
+    let mut canvas = vec::from_elem(width * height * 4, 0u8);
+    let mut frames = ~[];
+    let mut control = GraphicControl::new();
+    let mut play_count = Some(1);
+
+    loop {
+        let introducer = match reader.byte() {
+            // A missing trailer just means the file was cut off.
+            None | Some(0x3b) => break,
+            Some(introducer) => introducer,
+        };
+        match introducer {
+            0x21 => {
+                let label = match reader.byte() {
+                    None => break,
+                    Some(label) => label,
+                };
+                let block = match reader.sub_blocks() {
+                    None => break,
+                    Some(block) => block,
+                };
+                match label {
+                    0xf9 if block.len() >= 4 => {
+                        let delay = (block[1] as uint | (block[2] as uint << 8)) * 10;
+                        control = GraphicControl {
+                            disposal: match (block[0] >> 2) & 0x07 {
+                                2 => DisposeToBackground,
+                                3 => DisposeToPrevious,
+                                _ => DisposeNone,
+                            },
+                            delay: if delay < MIN_DELAY { DEFAULT_DELAY } else { delay },
+                            transparent_index: if block[0] & 0x01 != 0 {
+                                Some(block[3])
+                            } else {
+                                None
+                            },
+                        };
+                    }
+                    0xff if block.len() >= 14 && block.slice_to(11) == bytes!("NETSCAPE2.0") => {
+                        // The number of times to repeat the animation, zero meaning forever.
+                        let repeats = block[12] as uint | (block[13] as uint << 8);
+                        play_count = if repeats == 0 { None } else { Some(repeats + 1) };
+                    }
+                    _ => {}
+                }
+            }
+            0x2c => {
+                let frame = decode_frame(&mut reader, &global_colors, &control, width, height,
+                                         canvas);
+                match frame {
+                    None => break,
+                    Some(frame) => frames.push(frame),
+                }
+                control = GraphicControl::new();
+            }
+            _ => return None,
+        }
+    }
+
+    if frames.is_empty() {
+        return None;
+    }
+    Some(Animation {
+        width: width,
+        height: height,
+        frames: frames,
+        play_count: play_count,
+    })
+}
+
+/// Decodes the image starting at the reader's position, composites it onto the canvas and
+/// returns the result, then disposes of the frame as it asks.
+fn decode_frame(reader: &mut Reader,
+                global_colors: &Option<~[u8]>,
+                control: &GraphicControl,
+                width: uint,
+                height: uint,
+                canvas: &mut [u8])
+                -> Option<AnimationFrame> {
+    let left = try_opt!(reader.u16());
+    let top = try_opt!(reader.u16());
+    let frame_width = try_opt!(reader.u16());
+    let frame_height = try_opt!(reader.u16());
+    let flags = try_opt!(reader.byte());
+    let interlaced = flags & 0x40 != 0;
+    let colors = if flags & 0x80 != 0 {
+        try_opt!(reader.color_table(1 << ((flags & 0x07) + 1)))
+    } else {
+        match *global_colors {
+            Some(ref colors) => colors.clone(),
+            None => return None,
+        }
+    };
+    let min_code_size = try_opt!(reader.byte()) as uint;
+    // The image data may be cut off, in which case we show what there is.
+    let data = reader.sub_blocks().unwrap_or_default(~[]);
+    let indices = try_opt!(lzw_decode(data, min_code_size, frame_width * frame_height));
+
+    let previous = match control.disposal {
+        DisposeToPrevious => Some(canvas.to_owned()),
+        DisposeNone | DisposeToBackground => None,
+    };
+
+    for (i, &index) in indices.iter().enumerate() {
+        if control.transparent_index == Some(index) || index as uint * 3 + 2 >= colors.len() {
+            loop
+        }
+        let row = interlaced_row(i / frame_width, frame_height, interlaced);
+        let (x, y) = (left + i % frame_width, top + row);
+        if x >= width || y >= height {
+            loop
+        }
+        // Same byte order as the other decoders.
+        let offset = (y * width + x) * 4;
+        canvas[offset] = colors[index as uint * 3 + 2];
+        canvas[offset + 1] = colors[index as uint * 3 + 1];
+        canvas[offset + 2] = colors[index as uint * 3];
+        canvas[offset + 3] = 0xff;
+    }
+
+    let image = Image(width, height, 4, canvas.to_owned());
+
+    match control.disposal {
+        DisposeNone => {}
+        DisposeToBackground => {
+            for y in range(top, height.min(&(top + frame_height))) {
+                for x in range(left, width.min(&(left + frame_width))) {
+                    let offset = (y * width + x) * 4;
+                    for byte in canvas.mut_slice(offset, offset + 4).mut_iter() {
+                        *byte = 0;
+                    }
+                }
+            }
+        }
+        DisposeToPrevious => vec::bytes::copy_memory(canvas, previous.unwrap(), canvas.len()),
+    }
+
+    Some(AnimationFrame {
+        image: Arc::new(~image),
+        delay: control.delay,
+        disposal: control.disposal,
+    })
+}
+
+/// Maps the `pass_row`th row stored in the data to the row of the frame it belongs to. Interlaced
+/// images store every 8th row starting at 0, then every 8th starting at 4, then every 4th starting
+/// at 2, then every other row starting at 1.
+fn interlaced_row(pass_row: uint, height: uint, interlaced: bool) -> uint {
+    if !interlaced {
+        return pass_row;
+    }
+    let passes = [(0, 8), (4, 8), (2, 4), (1, 2)];
+    let mut remaining = pass_row;
+    for &(start, step) in passes.iter() {
+        let rows = if height > start { (height - start + step - 1) / step } else { 0 };
+        if remaining < rows {
+            return start + remaining * step;
+        }
+        remaining -= rows;
+    }
+    pass_row
+}
+
+/// Decompresses GIF image data into color indices. Stops at `pixel_count` indices, and returns
+/// fewer if the data runs out. Returns `None` if the data is corrupt.
+fn lzw_decode(data: &[u8], min_code_size: uint, pixel_count: uint) -> Option<~[u8]> {
+    if min_code_size < 1 || min_code_size > 8 {
+        return None;
+    }
+    let clear_code = 1u << min_code_size;
+    let end_code = clear_code + 1;
+
+    // Each code stands for the string of its prefix code followed by its suffix.
+    let mut prefixes = vec::from_elem(MAX_CODES, 0u16);
+    let mut suffixes = vec::from_elem(MAX_CODES, 0u8);
+    let mut firsts = vec::from_elem(MAX_CODES, 0u8);
+    let mut lengths = vec::from_elem(MAX_CODES, 0u16);
+    for code in range(0, clear_code) {
+        suffixes[code] = code as u8;
+        firsts[code] = code as u8;
+        lengths[code] = 1;
+    }
+
+    let mut next_code = clear_code + 2;
+    let mut code_size = min_code_size + 1;
+    let mut previous: Option<uint> = None;
+    let mut output = vec::with_capacity(pixel_count);
+
+    let mut bits = 0u32;
+    let mut bit_count = 0u;
+    let mut position = 0u;
+
+    while output.len() < pixel_count {
+        while bit_count < code_size && position < data.len() {
+            bits |= data[position] as u32 << bit_count;
+            bit_count += 8;
+            position += 1;
+        }
+        if bit_count < code_size {
+            break;
+        }
+        let code = (bits & ((1 << code_size) - 1)) as uint;
+        bits >>= code_size;
+        bit_count -= code_size;
+
+        if code == clear_code {
+            next_code = clear_code + 2;
+            code_size = min_code_size + 1;
+            previous = None;
+            loop;
+        }
+        if code == end_code {
+            break;
+        }
+
+        match previous {
+            None => {
+                if code >= clear_code {
+                    return None;
+                }
+            }
+            Some(previous) => {
+                let first = if code < next_code {
+                    firsts[code]
+                } else if code == next_code {
+                    // The code being defined right now: the previous string plus its own first
+                    // byte.
+                    firsts[previous]
+                } else {
+                    return None;
+                };
+                if next_code < MAX_CODES {
+                    prefixes[next_code] = previous as u16;
+                    suffixes[next_code] = first;
+                    firsts[next_code] = firsts[previous];
+                    lengths[next_code] = lengths[previous] + 1;
+                    next_code += 1;
+                    if next_code == 1 << code_size && code_size < 12 {
+                        code_size += 1;
+                    }
+                }
+            }
+        }
+
+        // Write out the string for the code, back to front.
+        let length = lengths[code] as uint;
+        let start = output.len();
+        output.grow(length, &0u8);
+        let mut current = code;
+        for i in range(0, length).invert() {
+            output[start + i] = suffixes[current];
+            current = prefixes[current] as uint;
+        }
+        previous = Some(code);
+    }
+
+    output.truncate(pixel_count);
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::base::{DisposeToBackground, DisposeNone};
+
+    /// A 2x1 animation: a red pixel and a transparent one, then a green pixel in the second
+    /// column drawn over it, looping forever.
+    static ANIMATION: &'static [u8] = &[
+        // Header and logical screen descriptor, with a two-color global color table.
+        0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x02, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00,
+        0xff, 0x00, 0x00, 0x00, 0xff, 0x00,
+        // NETSCAPE2.0 looping extension.
+        0x21, 0xff, 0x0b, 0x4e, 0x45, 0x54, 0x53, 0x43, 0x41, 0x50, 0x45, 0x32, 0x2e, 0x30,
+        0x03, 0x01, 0x00, 0x00, 0x00,
+        // Graphic control: 50ms, restore to background, index 1 transparent.
+        0x21, 0xf9, 0x04, 0x09, 0x05, 0x00, 0x01, 0x00,
+        // The first frame, indices 0 and 1.
+        0x2c, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00,
+        0x02, 0x02, 0x44, 0x0a, 0x00,
+        // Graphic control: 1s.
+        0x21, 0xf9, 0x04, 0x00, 0x64, 0x00, 0x00, 0x00,
+        // The second frame, index 1 at x = 1.
+        0x2c, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00,
+        0x02, 0x02, 0x4c, 0x01, 0x00,
+        0x3b,
+    ];
+
+    #[test]
+    fn sniffing() {
+        assert!(is_gif(ANIMATION));
+        assert!(!is_gif(bytes!("GIF8")));
+        assert!(!is_gif(bytes!("\x89PNG\r\n")));
+    }
+
+    #[test]
+    fn frames() {
+        let animation = decode(ANIMATION).unwrap();
+        assert!(animation.width == 2 && animation.height == 1);
+        assert!(animation.play_count.is_none());
+        assert!(animation.frames.len() == 2);
+
+        let first = &animation.frames[0];
+        assert!(first.delay == 50);
+        assert!(first.disposal == DisposeToBackground);
+        assert!(first.image.get().data == ~[0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00]);
+
+        // The first frame was cleared away before the second was drawn.
+        let second = &animation.frames[1];
+        assert!(second.delay == 1000);
+        assert!(second.disposal == DisposeNone);
+        assert!(second.image.get().data == ~[0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x00, 0xff]);
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(decode(ANIMATION.slice_to(13)).is_none());
+        // Cut off in the middle of the second frame.
+        let animation = decode(ANIMATION.slice_to(ANIMATION.len() - 3)).unwrap();
+        assert!(animation.frames.len() == 2);
+    }
+}
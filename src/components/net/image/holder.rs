@@ -2,8 +2,9 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use image::base::Image;
-use image_cache_task::{ImageReady, ImageNotReady, ImageFailed};
+use image::base::{Animation, Image, broken_image};
+use image_cache_task::{AnimatedImageReady, ImageError, ImageReady, ImageNotReady, ImageFailed};
+use image_cache_task::PartialImageReady;
 use local_image_cache::LocalImageCache;
 
 use std::util::replace;
@@ -20,6 +21,11 @@ use extra::arc::Arc;
 pub struct ImageHolder {
     url: Url,
     image: Option<Arc<~Image>>,
+    /// The frames of the image, if it's animated. `image` holds the frame that was last shown.
+    animation: Option<Arc<~Animation>>,
+    /// Why the image couldn't be had, if it couldn't. `image` holds the broken image placeholder
+    /// in that case.
+    error: Option<ImageError>,
     cached_size: Size2D<int>,
     local_image_cache: @mut LocalImageCache,
 }
@@ -30,6 +36,8 @@ impl ImageHolder {
         let holder = ImageHolder {
             url: url,
             image: None,
+            animation: None,
+            error: None,
             cached_size: Size2D(0,0),
             local_image_cache: local_image_cache,
         };
@@ -65,6 +73,12 @@ impl ImageHolder {
         }
     }
 
+    /// Returns true if the image failed to load or decode, in which case `get_image` returns the
+    /// broken image placeholder.
+    pub fn is_broken(&self) -> bool {
+        self.error.is_some()
+    }
+
     pub fn get_image(&mut self) -> Option<Arc<~Image>> {
         debug!("get_image() %?", self.url);
 
@@ -75,15 +89,29 @@ impl ImageHolder {
                 ImageReady(image) => {
                     self.image = Some(image);
                 }
+                AnimatedImageReady(animation) => {
+                    self.animation = Some(animation);
+                }
+                PartialImageReady(image) => {
+                    // Not kept, so that the next call asks for more of the image
+                    return Some(image);
+                }
                 ImageNotReady => {
                     debug!("image not ready for %s", self.url.to_str());
                 }
-                ImageFailed => {
-                    debug!("image decoding failed for %s", self.url.to_str());
+                ImageFailed(error) => {
+                    debug!("image loading failed for %s: %?", self.url.to_str(), error);
+                    self.image = Some(Arc::new(~broken_image()));
+                    self.error = Some(error);
                 }
             }
         }
 
+        // Animated images show a different frame depending on when they're asked for
+        for animation in self.animation.iter() {
+            self.image = Some(self.local_image_cache.current_frame(&self.url, animation.get()));
+        }
+
         // Clone isn't pure so we have to swap out the mutable image option
         let image = replace(&mut self.image, None);
         let result = image.clone();
@@ -0,0 +1,114 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! PNG encoding, for `toDataURL()`. Images are written as 8-bit RGBA, one IDAT chunk holding the
+//! whole deflated image, with no filtering.
+
+use std::vec;
+use extra::flate;
+
+static SIGNATURE: [u8, ..8] = [0x89, 'P' as u8, 'N' as u8, 'G' as u8, '\r' as u8, '\n' as u8,
+                               0x1a, '\n' as u8];
+
+/// The color type of truecolor images with an alpha channel.
+static COLOR_TYPE_RGBA: u8 = 6;
+
+/// Encodes an image of `width` by `height` pixels, given as straight (not premultiplied) RGBA
+/// rows from the top down.
+pub fn encode(width: uint, height: uint, pixels: &[u8]) -> ~[u8] {
+    assert!(pixels.len() == width * height * 4);
+
+    let mut png = SIGNATURE.to_owned();
+
+    let mut header = ~[];
+    push_u32(&mut header, width as u32);
+    push_u32(&mut header, height as u32);
+    // Bit depth, color type, compression, filter and interlace methods.
+    header.push_all([8, COLOR_TYPE_RGBA, 0, 0, 0]);
+    push_chunk(&mut png, "IHDR", header);
+
+    // Each row starts with the filter type it uses, 0 for none.
+    let row_length = width * 4;
+    let mut raw = vec::with_capacity((row_length + 1) * height);
+    for row in range(0, height) {
+        raw.push(0);
+        raw.push_all(pixels.slice(row * row_length, (row + 1) * row_length));
+    }
+    push_chunk(&mut png, "IDAT", zlib(raw));
+
+    push_chunk(&mut png, "IEND", []);
+    png
+}
+
+/// Wraps the deflated `data` in a zlib stream, as PNG wants.
+fn zlib(data: &[u8]) -> ~[u8] {
+    let mut stream = ~[0x78, 0x9c];
+    stream.push_all(flate::deflate_bytes(data));
+    push_u32(&mut stream, adler32(data));
+    stream
+}
+
+/// Appends a chunk of the given type, with its length and checksum.
+fn push_chunk(png: &mut ~[u8], chunk_type: &str, data: &[u8]) {
+    push_u32(png, data.len() as u32);
+    let mut checked = chunk_type.as_bytes().to_owned();
+    checked.push_all(data);
+    png.push_all(checked);
+    push_u32(png, crc32(checked));
+}
+
+/// Appends a big-endian 32-bit number.
+fn push_u32(data: &mut ~[u8], n: u32) {
+    data.push_all([(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]);
+}
+
+/// The CRC-32 of ISO 3309, which PNG checks chunks with.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &byte in data.iter() {
+        crc ^= byte as u32;
+        for _ in range(0, 8) {
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    crc ^ 0xffffffff
+}
+
+/// The Adler-32 checksum zlib streams end with.
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for &byte in data.iter() {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, crc32, encode};
+    use stb_image = stb_image::image;
+
+    #[test]
+    fn checksums() {
+        assert!(crc32(bytes!("IEND")) == 0xae426082);
+        assert!(adler32(bytes!("Wikipedia")) == 0x11e60398);
+    }
+
+    #[test]
+    fn round_trip() {
+        let pixels = ~[0xff, 0x00, 0x00, 0xff,  0x00, 0xff, 0x00, 0x80,
+                       0x00, 0x00, 0xff, 0x00,  0x10, 0x20, 0x30, 0x40,
+                       0x01, 0x02, 0x03, 0x04,  0xff, 0xff, 0xff, 0xff];
+        let png = encode(2, 3, pixels);
+        match stb_image::load_from_memory_with_depth(png, 4, false) {
+            stb_image::ImageU8(image) => {
+                assert!(image.width == 2 && image.height == 3);
+                assert!(image.data == pixels);
+            }
+            _ => fail!(~"couldn't decode the encoded image"),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! WebP decoding, lossy and lossless, by way of libwebp.
+
+use image::base::Image;
+
+use std::libc::{c_int, c_void, free, size_t};
+use std::vec;
+
+#[link_args = "-lwebp"]
+extern {
+    fn WebPGetInfo(data: *u8, data_size: size_t, width: *mut c_int, height: *mut c_int) -> c_int;
+    fn WebPDecodeBGRA(data: *u8, data_size: size_t, width: *mut c_int, height: *mut c_int)
+                      -> *mut u8;
+}
+
+/// Returns true if the data starts like a WebP file: a RIFF container holding WebP.
+pub fn is_webp(data: &[u8]) -> bool {
+    data.len() >= 12 && data.slice(0, 4) == bytes!("RIFF") && data.slice(8, 12) == bytes!("WEBP")
+}
+
+/// Decodes a WebP image into premultiplied BGRA, the same byte order as the other decoders.
+/// Returns `None` if the data is corrupt.
+pub fn decode(data: &[u8]) -> Option<Image> {
+    let mut width: c_int = 0;
+    let mut height: c_int = 0;
+    unsafe {
+        if WebPGetInfo(vec::raw::to_ptr(data), data.len() as size_t, &mut width, &mut height) == 0 {
+            return None;
+        }
+        let pixels = WebPDecodeBGRA(vec::raw::to_ptr(data),
+                                    data.len() as size_t,
+                                    &mut width,
+                                    &mut height);
+        if pixels.is_null() {
+            return None;
+        }
+        let length = width as uint * height as uint * 4;
+        let mut result = vec::raw::from_buf_raw(pixels as *u8, length);
+        free(pixels as *c_void);
+
+        premultiply(result);
+        Some(Image(width as uint, height as uint, 4, result))
+    }
+}
+
+/// libwebp hands out straight alpha, but drawing expects the color channels of BGRA pixels to be
+/// multiplied by their alpha already.
+fn premultiply(pixels: &mut [u8]) {
+    for i in range(0, pixels.len() / 4) {
+        let alpha = pixels[i * 4 + 3] as uint;
+        if alpha == 0xff {
+            loop
+        }
+        for j in range(i * 4, i * 4 + 3) {
+            pixels[j] = ((pixels[j] as uint * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_webp, premultiply};
+
+    #[test]
+    fn sniffing() {
+        assert!(is_webp(bytes!("RIFF\x24\x00\x00\x00WEBPVP8L")));
+        assert!(!is_webp(bytes!("RIFF\x24\x00\x00\x00WAVEfmt ")));
+        assert!(!is_webp(bytes!("RIFF")));
+    }
+
+    #[test]
+    fn premultiplied() {
+        let mut pixels = ~[0xff, 0x80, 0x00, 0xff,
+                           0xff, 0x80, 0x00, 0x80,
+                           0xff, 0xff, 0xff, 0x00];
+        premultiply(pixels);
+        assert!(pixels == ~[0xff, 0x80, 0x00, 0xff,
+                            0x80, 0x40, 0x00, 0x80,
+                            0x00, 0x00, 0x00, 0x00]);
+    }
+}
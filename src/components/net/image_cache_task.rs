@@ -2,13 +2,17 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use image::base::{Image, load_from_memory};
+use image::base::{Animation, Image, load_from_memory};
+use image::decoder_pool::{DecoderPool, default_decoder_threads};
+use image::gif;
 use resource_task;
-use resource_task::ResourceTask;
+use resource_task::{LoadData, ResourceTask};
+use servo_util::memory::{Bytes, MemoryReport};
 use servo_util::url::{UrlMap, url_map};
 
 use std::cell::Cell;
 use std::comm::{Chan, Port, SharedChan, stream};
+use std::task;
 use std::task::spawn;
 use std::to_str::ToStr;
 use std::util::replace;
@@ -25,11 +29,21 @@ pub enum Msg {
     /// Used be the prefetch tasks to post back image binaries
     priv StorePrefetchedImageData(Url, Result<Cell<~[u8]>, ()>),
 
+    /// Used by the prefetch tasks to post the image data received so far, so that part of the
+    /// image can be shown while the rest loads
+    priv StorePartialImageData(Url, ~[u8]),
+
     /// Tell the cache to decode an image. Must be posted before GetImage/WaitForImage
     Decode(Url),
 
     /// Used by the decoder tasks to post decoded images back to the cache
-    priv StoreImage(Url, Option<Arc<~Image>>),
+    priv StoreImage(Url, Result<Arc<~Image>, ImageError>),
+
+    /// Used by the decoder tasks to post decoded animations back to the cache
+    priv StoreAnimation(Url, Arc<~Animation>),
+
+    /// Used by the decoder tasks to post images decoded from partial data back to the cache
+    priv StorePartialImage(Url, Option<Arc<~Image>>),
 
     /// Request an Image object for a URL. If the image is not is not immediately
     /// available then ImageNotReady is returned.
@@ -38,6 +52,14 @@ pub enum Msg {
     /// Wait for an image to become available (or fail to load).
     WaitForImage(Url, Chan<ImageResponseMsg>),
 
+    /// Wait for more of an image to become available: a fuller partial image, the whole image,
+    /// or failure.
+    WaitForProgress(Url, Chan<ImageResponseMsg>),
+
+    /// Asks how much memory the decoded images and kept encoded data take up, for the memory
+    /// profiler.
+    ReportMemory(Chan<~[MemoryReport]>),
+
     /// For testing
     priv OnMsg(~fn(msg: &Msg)),
 
@@ -47,16 +69,32 @@ pub enum Msg {
 
 pub enum ImageResponseMsg {
     ImageReady(Arc<~Image>),
+    /// The image has more than one frame. Which one to show depends on the time.
+    AnimatedImageReady(Arc<~Animation>),
+    /// Only part of the image's data has arrived. The rest of the image is blank for now.
+    PartialImageReady(Arc<~Image>),
     ImageNotReady,
-    ImageFailed
+    ImageFailed(ImageError)
+}
+
+/// Why there is no image for a URL.
+#[deriving(Clone, Eq)]
+pub enum ImageError {
+    /// The image data couldn't be loaded.
+    LoadFailed,
+    /// The image data was loaded but couldn't be decoded, either because it's corrupt or because
+    /// the decoder failed partway through.
+    DecodeFailed,
 }
 
 impl ImageResponseMsg {
     fn clone(&self) -> ImageResponseMsg {
         match *self {
             ImageReady(ref img) => ImageReady(img.clone()),
+            AnimatedImageReady(ref animation) => AnimatedImageReady(animation.clone()),
+            PartialImageReady(ref img) => PartialImageReady(img.clone()),
             ImageNotReady => ImageNotReady,
-            ImageFailed => ImageFailed,
+            ImageFailed(error) => ImageFailed(error),
         }
     }
 }
@@ -66,10 +104,13 @@ impl Eq for ImageResponseMsg {
         // FIXME: Bad copies
         match (self.clone(), other.clone()) {
             (ImageReady(*), ImageReady(*)) => fail!(~"unimplemented comparison"),
+            (AnimatedImageReady(*), AnimatedImageReady(*)) => fail!(~"unimplemented comparison"),
+            (PartialImageReady(*), PartialImageReady(*)) => fail!(~"unimplemented comparison"),
             (ImageNotReady, ImageNotReady) => true,
-            (ImageFailed, ImageFailed) => true,
+            (ImageFailed(error), ImageFailed(other_error)) => error == other_error,
 
-            (ImageReady(*), _) | (ImageNotReady, _) | (ImageFailed, _) => false
+            (ImageReady(*), _) | (AnimatedImageReady(*), _) | (PartialImageReady(*), _) |
+            (ImageNotReady, _) | (ImageFailed(*), _) => false
         }
     }
 
@@ -82,12 +123,33 @@ pub type ImageCacheTask = SharedChan<Msg>;
 
 type DecoderFactory = ~fn() -> ~fn(&[u8]) -> Option<Image>;
 
+/// The default amount of memory, in bytes, the cache may use for decoded images.
+pub static DEFAULT_MEMORY_LIMIT: uint = 64 * 1024 * 1024;
+
 pub fn ImageCacheTask(resource_task: ResourceTask) -> ImageCacheTask {
-    ImageCacheTask_(resource_task, default_decoder_factory)
+    ImageCacheTaskWithMemoryLimit(resource_task, DEFAULT_MEMORY_LIMIT, true)
+}
+
+/// Create an ImageCacheTask that keeps at most `memory_limit` bytes of decoded images, evicting
+/// the least recently used ones past that. Evicted images are decoded again when next asked for,
+/// from a copy of their encoded data if `keep_encoded` is set, or else from a fresh load.
+pub fn ImageCacheTaskWithMemoryLimit(resource_task: ResourceTask,
+                                     memory_limit: uint,
+                                     keep_encoded: bool)
+                                     -> ImageCacheTask {
+    create_image_cache_task(resource_task, default_decoder_factory, memory_limit, keep_encoded)
 }
 
 pub fn ImageCacheTask_(resource_task: ResourceTask, decoder_factory: DecoderFactory)
                        -> ImageCacheTask {
+    create_image_cache_task(resource_task, decoder_factory, DEFAULT_MEMORY_LIMIT, true)
+}
+
+fn create_image_cache_task(resource_task: ResourceTask,
+                           decoder_factory: DecoderFactory,
+                           memory_limit: uint,
+                           keep_encoded: bool)
+                           -> ImageCacheTask {
     // FIXME: Doing some dancing to avoid copying decoder_factory, our test
     // version of which contains an uncopyable type which rust will currently
     // copy unsoundly
@@ -104,8 +166,16 @@ pub fn ImageCacheTask_(resource_task: ResourceTask, decoder_factory: DecoderFact
             decoder_factory: decoder_factory_cell.take(),
             port: port_cell.take(),
             chan: chan_cell.take(),
+            decoders: DecoderPool::new(default_decoder_threads()),
             state_map: url_map(),
             wait_map: url_map(),
+            partial_map: url_map(),
+            progress_wait_map: url_map(),
+            decoded_lru: ~[],
+            memory_used: 0,
+            memory_limit: memory_limit,
+            keep_encoded: keep_encoded,
+            encoded_map: url_map(),
             need_exit: None
         };
         cache.run();
@@ -150,13 +220,39 @@ struct ImageCache {
     port: Port<Msg>,
     /// A copy of the shared chan to give to child tasks
     chan: SharedChan<Msg>,
+    /// The threads images are decoded on
+    decoders: DecoderPool,
     /// The state of processsing an image for a URL
     state_map: UrlMap<ImageState>,
     /// List of clients waiting on a WaitForImage response
     wait_map: UrlMap<@mut ~[Chan<ImageResponseMsg>]>,
+    /// Images decoded from the data received so far, for images still loading
+    partial_map: UrlMap<@mut PartialImage>,
+    /// List of clients waiting on a WaitForProgress response
+    progress_wait_map: UrlMap<@mut ~[Chan<ImageResponseMsg>]>,
+    /// The URLs of the decoded images and animations, least recently used first
+    decoded_lru: ~[Url],
+    /// The memory used by the decoded images and animations
+    memory_used: uint,
+    memory_limit: uint,
+    /// Whether to keep the encoded data of decoded images, to decode them again from if they're
+    /// evicted
+    keep_encoded: bool,
+    /// The encoded data of decoded images, when `keep_encoded` is set
+    encoded_map: UrlMap<~[u8]>,
     need_exit: Option<Chan<()>>,
 }
 
+/// What has been decoded of an image whose data is still arriving.
+struct PartialImage {
+    /// The last image decoded from partial data, if any decoded
+    image: Option<Arc<~Image>>,
+    /// Whether a partial decode is running
+    decoding: bool,
+    /// Data that arrived while a partial decode was running, to decode next
+    pending_data: Option<~[u8]>,
+}
+
 #[deriving(Clone)]
 enum ImageState {
     Init,
@@ -164,7 +260,10 @@ enum ImageState {
     Prefetched(@Cell<~[u8]>),
     Decoding,
     Decoded(@Arc<~Image>),
-    Failed
+    DecodedAnimation(@Arc<~Animation>),
+    /// The image was decoded, but was evicted to stay within the memory limit
+    Evicted,
+    Failed(ImageError)
 }
 
 #[deriving(Clone)]
@@ -193,10 +292,15 @@ impl ImageCache {
                 }
                 Decode(url) => self.decode(url),
                 StoreImage(url, image) => self.store_image(url, image),
+                StoreAnimation(url, animation) => self.store_animation(url, animation),
+                StorePartialImageData(url, data) => self.store_partial_image_data(url, data),
+                StorePartialImage(url, image) => self.store_partial_image(url, image),
                 GetImage(url, response) => self.get_image(url, response),
                 WaitForImage(url, response) => {
                     self.wait_for_image(url, response)
                 }
+                WaitForProgress(url, response) => self.wait_for_progress(url, response),
+                ReportMemory(reports) => reports.send(self.memory_reports()),
                 OnMsg(handler) => msg_handlers.push(handler),
                 Exit(response) => {
                     assert!(self.need_exit.is_none());
@@ -216,7 +320,13 @@ impl ImageCache {
                         Prefetching(*) => can_exit = false,
                         Decoding => can_exit = false,
 
-                        Init | Prefetched(*) | Decoded(*) | Failed => ()
+                        Init | Prefetched(*) | Decoded(*) | DecodedAnimation(*) | Evicted |
+                        Failed(*) => ()
+                    }
+                }
+                for (_, partial) in self.partial_map.iter() {
+                    if partial.decoding {
+                        can_exit = false;
                     }
                 }
 
@@ -254,7 +364,9 @@ impl ImageCache {
                     let url = url_cell.take();
                     debug!("image_cache_task: started fetch for %s", url.to_str());
 
-                    let image = load_image_data(url.clone(), resource_task.clone());
+                    let image = do load_image_data(url.clone(), resource_task.clone()) |data| {
+                        to_cache.send(StorePartialImageData(url.clone(), data.to_owned()));
+                    };
 
                     let result = if image.is_ok() {
                         Ok(Cell::new(image.unwrap()))
@@ -268,7 +380,8 @@ impl ImageCache {
                 self.set_state(url, Prefetching(DoNotDecode));
             }
 
-            Prefetching(*) | Prefetched(*) | Decoding | Decoded(*) | Failed => {
+            Prefetching(*) | Prefetched(*) | Decoding | Decoded(*) | DecodedAnimation(*) |
+            Evicted | Failed(*) => {
                 // We've already begun working on this image
             }
         }
@@ -287,8 +400,9 @@ impl ImageCache {
                 }
               }
               Err(*) => {
-                self.set_state(url.clone(), Failed);
-                self.purge_waiters(url, || ImageFailed);
+                self.set_state(url.clone(), Failed(LoadFailed));
+                self.discard_partial_image(&url);
+                self.purge_waiters(url, || ImageFailed(LoadFailed));
               }
             }
           }
@@ -297,7 +411,9 @@ impl ImageCache {
           | Prefetched(*)
           | Decoding
           | Decoded(*)
-          | Failed => {
+          | DecodedAnimation(*)
+          | Evicted
+          | Failed(*) => {
             fail!(~"wrong state for storing prefetched image")
           }
         }
@@ -320,44 +436,76 @@ impl ImageCache {
                 assert!(!data_cell.is_empty());
 
                 let data = data_cell.take();
+                if self.keep_encoded && !self.encoded_map.contains_key(&url) {
+                    self.encoded_map.insert(url.clone(), data.clone());
+                }
+                let data = Cell::new(data);
                 let to_cache = self.chan.clone();
                 let url_cell = Cell::new(url.clone());
-                let decode = (self.decoder_factory)();
+                let decode = Cell::new((self.decoder_factory)());
 
-                do spawn {
+                do self.decoders.execute {
                     let url = url_cell.take();
                     debug!("image_cache_task: started image decode for %s", url.to_str());
-                    let image = decode(data);
-                    let image = if image.is_some() {
-                        Some(Arc::new(~image.unwrap()))
-                    } else {
-                        None
+                    // A decoder that fails partway through must not leave the image decoding
+                    // forever, or hand out a partially-filled buffer.
+                    let decoded = do task::try {
+                        let decode = decode.take();
+                        let data = data.take();
+                        // GIFs are decoded here rather than by the decoder, since they can have
+                        // any number of frames.
+                        if gif::is_gif(data) {
+                            match gif::decode(data) {
+                                Some(animation) => {
+                                    if animation.frames.len() == 1 {
+                                        Some(Left(animation.frames[0].image.clone()))
+                                    } else {
+                                        Some(Right(Arc::new(~animation)))
+                                    }
+                                }
+                                None => None,
+                            }
+                        } else {
+                            decode(data).map_move(|image| Left(Arc::new(~image)))
+                        }
                     };
-                    to_cache.send(StoreImage(url.clone(), image));
+                    match decoded {
+                        Ok(Some(Left(image))) => to_cache.send(StoreImage(url.clone(), Ok(image))),
+                        Ok(Some(Right(animation))) => {
+                            to_cache.send(StoreAnimation(url.clone(), animation))
+                        }
+                        Ok(None) | Err(*) => to_cache.send(StoreImage(url.clone(),
+                                                                      Err(DecodeFailed))),
+                    }
                     debug!("image_cache_task: ended image decode for %s", url.to_str());
                 }
 
                 self.set_state(url, Decoding);
             }
 
-            Decoding | Decoded(*) | Failed => {
+            Evicted => self.redecode(url),
+
+            Decoding | Decoded(*) | DecodedAnimation(*) | Failed(*) => {
                 // We've already begun decoding
             }
         }
     }
 
-    fn store_image(&self, url: Url, image: Option<Arc<~Image>>) {
+    fn store_image(&mut self, url: Url, image: Result<Arc<~Image>, ImageError>) {
 
         match self.get_state(url.clone()) {
           Decoding => {
             match image {
-              Some(image) => {
+              Ok(image) => {
                 self.set_state(url.clone(), Decoded(@image.clone()));
+                self.add_to_memory(url.clone(), image_size(image.get()));
+                self.discard_partial_image(&url);
                 self.purge_waiters(url, || ImageReady(image.clone()) );
               }
-              None => {
-                self.set_state(url.clone(), Failed);
-                self.purge_waiters(url, || ImageFailed );
+              Err(error) => {
+                self.set_state(url.clone(), Failed(error));
+                self.discard_partial_image(&url);
+                self.purge_waiters(url, || ImageFailed(error) );
               }
             }
           }
@@ -366,13 +514,186 @@ impl ImageCache {
           | Prefetching(*)
           | Prefetched(*)
           | Decoded(*)
-          | Failed => {
+          | DecodedAnimation(*)
+          | Evicted
+          | Failed(*) => {
             fail!(~"incorrect state in store_image")
           }
         }
 
     }
 
+    fn store_animation(&mut self, url: Url, animation: Arc<~Animation>) {
+        match self.get_state(url.clone()) {
+            Decoding => {
+                self.set_state(url.clone(), DecodedAnimation(@animation.clone()));
+                let size = animation.get().frames.iter().fold(0, |size, frame| {
+                    size + image_size(frame.image.get())
+                });
+                self.add_to_memory(url.clone(), size);
+                self.discard_partial_image(&url);
+                self.purge_waiters(url, || AnimatedImageReady(animation.clone()));
+            }
+
+            Init | Prefetching(*) | Prefetched(*) | Decoded(*) | DecodedAnimation(*) | Evicted |
+            Failed(*) => {
+                fail!(~"incorrect state in store_animation")
+            }
+        }
+    }
+
+    /// Accounts for a newly decoded image of `size` bytes, evicting the least recently used
+    /// images until there's room for it. An image bigger than the whole limit evicts everything
+    /// else, but is kept itself.
+    fn add_to_memory(&mut self, url: Url, size: uint) {
+        while !self.decoded_lru.is_empty() && self.memory_used + size > self.memory_limit {
+            let evicted = self.decoded_lru.shift();
+            self.evict(evicted);
+        }
+        self.memory_used += size;
+        self.decoded_lru.push(url);
+    }
+
+    fn evict(&mut self, url: Url) {
+        debug!("image_cache_task: evicting %s", url.to_str());
+        let size = match self.get_state(url.clone()) {
+            Decoded(image) => image_size(image.get()),
+            DecodedAnimation(animation) => {
+                animation.get().frames.iter().fold(0, |size, frame| {
+                    size + image_size(frame.image.get())
+                })
+            }
+            _ => fail!(~"evicting an image that isn't decoded")
+        };
+        self.memory_used -= size;
+        self.set_state(url, Evicted);
+    }
+
+    fn memory_reports(&self) -> ~[MemoryReport] {
+        let encoded = self.encoded_map.iter().fold(0, |size, (_, data)| size + data.len());
+        ~[
+            MemoryReport {
+                path: ~"decoded",
+                amount: self.memory_used,
+                unit: Bytes,
+            },
+            MemoryReport {
+                path: ~"encoded",
+                amount: encoded,
+                unit: Bytes,
+            },
+        ]
+    }
+
+    /// Marks a decoded image as the most recently used.
+    fn touch(&mut self, url: &Url) {
+        match self.decoded_lru.iter().position(|lru_url| lru_url == url) {
+            Some(index) => {
+                let url = self.decoded_lru.remove(index);
+                self.decoded_lru.push(url);
+            }
+            None => {}
+        }
+    }
+
+    /// Starts decoding an evicted image again, from its encoded data if it was kept, or else by
+    /// loading it again.
+    fn redecode(&self, url: Url) {
+        debug!("image_cache_task: decoding %s again", url.to_str());
+        let data = self.encoded_map.find(&url).map(|data| (*data).clone());
+        match data {
+            Some(data) => self.set_state(url.clone(), Prefetched(@Cell::new(data))),
+            None => {
+                self.set_state(url.clone(), Init);
+                self.prefetch(url.clone());
+            }
+        }
+        self.decode(url);
+    }
+
+    fn store_partial_image_data(&self, url: Url, data: ~[u8]) {
+        match self.get_state(url.clone()) {
+            Prefetching(DoDecode) => {}
+            // Nobody has asked to see the image yet, or the whole of it is already here.
+            _ => return
+        }
+
+        let partial = do self.partial_map.find_or_insert_with(url.clone()) |_| {
+            @mut PartialImage {
+                image: None,
+                decoding: false,
+                pending_data: None,
+            }
+        };
+        let partial = *partial;
+        if partial.decoding {
+            // Newer data supersedes whatever was waiting.
+            partial.pending_data = Some(data);
+        } else {
+            self.decode_partial(url, partial, data);
+        }
+    }
+
+    fn decode_partial(&self, url: Url, partial: @mut PartialImage, data: ~[u8]) {
+        partial.decoding = true;
+
+        let to_cache = self.chan.clone();
+        let url_cell = Cell::new(url);
+        let data = Cell::new(data);
+        do self.decoders.execute {
+            let url = url_cell.take();
+            debug!("image_cache_task: started partial decode for %s", url.to_str());
+            let decoded = do task::try {
+                decode_partial(data.take())
+            };
+            let image = match decoded {
+                Ok(image) => image,
+                Err(*) => None,
+            };
+            to_cache.send(StorePartialImage(url.clone(), image));
+            debug!("image_cache_task: ended partial decode for %s", url.to_str());
+        }
+    }
+
+    fn store_partial_image(&self, url: Url, image: Option<Arc<~Image>>) {
+        let partial = match self.partial_map.find(&url) {
+            Some(partial) => *partial,
+            None => fail!(~"partial image stored without a partial decode"),
+        };
+        partial.decoding = false;
+
+        match self.get_state(url.clone()) {
+            Prefetching(DoDecode) | Decoding => {}
+            // The whole image got here first.
+            _ => {
+                self.partial_map.pop(&url);
+                return
+            }
+        }
+
+        for image in image.iter() {
+            partial.image = Some(image.clone());
+            self.purge_progress_waiters(url.clone(), || PartialImageReady(image.clone()));
+        }
+
+        match replace(&mut partial.pending_data, None) {
+            Some(data) => self.decode_partial(url, partial, data),
+            None => {}
+        }
+    }
+
+    /// Forgets the partial image once the whole image is in. A partial decode that's still
+    /// running forgets it when it finishes.
+    fn discard_partial_image(&self, url: &Url) {
+        let decoding = match self.partial_map.find(url) {
+            Some(partial) => partial.decoding,
+            None => return
+        };
+        if !decoding {
+            self.partial_map.pop(url);
+        }
+    }
+
     fn purge_waiters(&self, url: Url, f: &fn() -> ImageResponseMsg) {
         match self.wait_map.pop(&url) {
             Some(waiters) => {
@@ -382,21 +703,62 @@ impl ImageCache {
             }
             None => ()
         }
+        self.purge_progress_waiters(url, f);
+    }
+
+    fn purge_progress_waiters(&self, url: Url, f: &fn() -> ImageResponseMsg) {
+        match self.progress_wait_map.pop(&url) {
+            Some(waiters) => {
+                for response in waiters.iter() {
+                    response.send(f());
+                }
+            }
+            None => ()
+        }
     }
 
-    fn get_image(&self, url: Url, response: Chan<ImageResponseMsg>) {
+    /// The response for an image that is still loading.
+    fn partial_response(&self, url: &Url) -> ImageResponseMsg {
+        match self.partial_map.find(url) {
+            Some(&partial) => {
+                match partial.image {
+                    Some(ref image) => PartialImageReady(image.clone()),
+                    None => ImageNotReady,
+                }
+            }
+            None => ImageNotReady
+        }
+    }
+
+    fn get_image(&mut self, url: Url, response: Chan<ImageResponseMsg>) {
         match self.get_state(url.clone()) {
+            Evicted => {
+                self.redecode(url.clone());
+                self.get_image(url, response);
+            }
             Init => fail!(~"request for image before prefetch"),
-            Prefetching(DoDecode) => response.send(ImageNotReady),
+            Prefetching(DoDecode) => response.send(self.partial_response(&url)),
             Prefetching(DoNotDecode) | Prefetched(*) => fail!(~"request for image before decode"),
-            Decoding => response.send(ImageNotReady),
-            Decoded(image) => response.send(ImageReady((*image).clone())),
-            Failed => response.send(ImageFailed),
+            Decoding => response.send(self.partial_response(&url)),
+            Decoded(image) => {
+                self.touch(&url);
+                response.send(ImageReady((*image).clone()))
+            }
+            DecodedAnimation(animation) => {
+                self.touch(&url);
+                response.send(AnimatedImageReady((*animation).clone()))
+            }
+            Failed(error) => response.send(ImageFailed(error)),
         }
     }
 
-    fn wait_for_image(&self, url: Url, response: Chan<ImageResponseMsg>) {
+    fn wait_for_image(&mut self, url: Url, response: Chan<ImageResponseMsg>) {
         match self.get_state(url.clone()) {
+            Evicted => {
+                self.redecode(url.clone());
+                self.wait_for_image(url, response);
+            }
+
             Init => fail!(~"request for image before prefetch"),
 
             Prefetching(DoNotDecode) | Prefetched(*) => fail!(~"request for image before decode"),
@@ -412,15 +774,37 @@ impl ImageCache {
             }
 
             Decoded(image) => {
+                self.touch(&url);
                 response.send(ImageReady((*image).clone()));
             }
 
-            Failed => {
-                response.send(ImageFailed);
+            DecodedAnimation(animation) => {
+                self.touch(&url);
+                response.send(AnimatedImageReady((*animation).clone()));
+            }
+
+            Failed(error) => {
+                response.send(ImageFailed(error));
             }
         }
     }
 
+    fn wait_for_progress(&mut self, url: Url, response: Chan<ImageResponseMsg>) {
+        match self.get_state(url.clone()) {
+            Prefetching(DoDecode) | Decoding => {
+                if self.progress_wait_map.contains_key(&url) {
+                    let waiters = self.progress_wait_map.find_mut(&url).unwrap();
+                    waiters.push(response);
+                } else {
+                    self.progress_wait_map.insert(url, @mut ~[response]);
+                }
+            }
+
+            // Anything else is already as far along as the image will get.
+            _ => self.wait_for_image(url, response)
+        }
+    }
+
 }
 
 
@@ -436,16 +820,35 @@ impl ImageCacheTaskClient for ImageCacheTask {
     }
 }
 
-fn load_image_data(url: Url, resource_task: ResourceTask) -> Result<~[u8], ()> {
+/// The `Accept` header sent with image requests. Servers that negotiate formats should only send
+/// WebP to clients that ask for it.
+static ACCEPT_IMAGE: &'static str = "image/webp,*/*;q=0.8";
+
+/// How much image data has to arrive before it's worth trying to decode part of the image.
+static PARTIAL_DECODE_MIN_BYTES: uint = 16 * 1024;
+
+/// Loads the whole of the image data, passing the data received so far to `progress` along the
+/// way. It's passed each time the data at least doubles, so that copying it stays linear in the
+/// size of the image.
+fn load_image_data(url: Url, resource_task: ResourceTask, progress: &fn(&[u8]))
+                   -> Result<~[u8], ()> {
     let (response_port, response_chan) = stream();
-    resource_task.send(resource_task::Load(url, response_chan));
+    let mut load_data = LoadData::new(url);
+    load_data.headers.push((~"Accept", ACCEPT_IMAGE.to_owned()));
+    resource_task.send(resource_task::Load(load_data, response_chan));
 
     let mut image_data = ~[];
+    let mut next_progress = PARTIAL_DECODE_MIN_BYTES;
 
     loop {
         match response_port.recv() {
+            resource_task::Headers(*) => {}
             resource_task::Payload(data) => {
                 image_data.push_all(data);
+                if image_data.len() >= next_progress {
+                    progress(image_data);
+                    next_progress = image_data.len() * 2;
+                }
             }
             resource_task::Done(result::Ok(*)) => {
                 return Ok(image_data);
@@ -457,6 +860,27 @@ fn load_image_data(url: Url, resource_task: ResourceTask) -> Result<~[u8], ()> {
     }
 }
 
+/// The memory the pixels of a decoded image take up.
+fn image_size(image: &Image) -> uint {
+    image.data.len()
+}
+
+/// Decodes what it can of an image whose data is still arriving. Returns `None` if there isn't
+/// enough of it yet, or the format can't be decoded in pieces.
+fn decode_partial(data: &[u8]) -> Option<Arc<~Image>> {
+    if gif::is_gif(data) {
+        // Frames that are cut short come out with their missing rows transparent.
+        return gif::decode(data).map_move(|animation| animation.frames[0].image.clone());
+    }
+    if data.len() >= 3 && data[0] == 0xff && data[1] == 0xd8 && data[2] == 0xff {
+        // FIXME: stb_image decodes the scanlines of a baseline JPEG that haven't arrived as gray.
+        // It can't show progressive JPEGs pass by pass, and gives up on truncated PNGs, so those
+        // still appear all at once.
+        return load_from_memory(data).map_move(|image| Arc::new(~image));
+    }
+    None
+}
+
 fn default_decoder_factory() -> ~fn(&[u8]) -> Option<Image> {
     let foo: ~fn(&[u8]) -> Option<Image> = |data: &[u8]| { load_from_memory(data) };
     foo
@@ -794,7 +1218,7 @@ fn should_return_failed_if_image_bin_cannot_be_fetched() {
     let (response_chan, response_port) = stream();
     image_cache_task.send(GetImage(url, response_chan));
     match response_port.recv() {
-      ImageFailed => (),
+      ImageFailed(*) => (),
       _ => fail
     }
 
@@ -832,7 +1256,7 @@ fn should_return_failed_for_multiple_get_image_requests_if_image_bin_cannot_be_f
     let (response_chan, response_port) = stream();
     image_cache_task.send(GetImage(url.clone(), response_chan));
     match response_port.recv() {
-      ImageFailed => (),
+      ImageFailed(*) => (),
       _ => fail
     }
 
@@ -840,7 +1264,7 @@ fn should_return_failed_for_multiple_get_image_requests_if_image_bin_cannot_be_f
     let (response_chan, response_port) = stream();
     image_cache_task.send(GetImage(url, response_chan));
     match response_port.recv() {
-      ImageFailed => (),
+      ImageFailed(*) => (),
       _ => fail
     }
 
@@ -934,7 +1358,7 @@ fn should_return_failed_if_image_decode_fails() {
     image_cache_task.send(GetImage(url, response_chan));
 
     match response_port.recv() {
-      ImageFailed => (),
+      ImageFailed(*) => (),
       _ => fail
     }
 
@@ -1009,6 +1433,37 @@ fn should_return_image_on_wait_if_image_is_not_yet_loaded() {
     mock_resource_task.send(resource_task::Exit);
 }
 
+#[test]
+fn should_return_image_on_wait_for_progress_if_image_loads_at_once() {
+    let (wait_chan, wait_port) = pipes::stream();
+
+    let mock_resource_task = do mock_resource_task |response| {
+        wait_port.recv();
+        response.send(resource_task::Payload(test_image_bin()));
+        response.send(resource_task::Done(result::Ok(())));
+    };
+
+    let image_cache_task = ImageCacheTask(mock_resource_task);
+    let url = make_url(~"file", None);
+
+    image_cache_task.send(Prefetch(url.clone()));
+    image_cache_task.send(Decode(url.clone()));
+
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(WaitForProgress(url, response_chan));
+
+    wait_chan.send(());
+
+    // The data is too small to be worth decoding in parts, so the first progress is the whole.
+    match response_port.recv() {
+      ImageReady(*) => (),
+      _ => fail
+    }
+
+    image_cache_task.exit();
+    mock_resource_task.send(resource_task::Exit);
+}
+
 #[test]
 fn should_return_image_failed_on_wait_if_image_fails_to_load() {
     let (wait_chan, wait_port) = pipes::stream();
@@ -1031,7 +1486,7 @@ fn should_return_image_failed_on_wait_if_image_fails_to_load() {
     wait_chan.send(());
 
     match response_port.recv() {
-      ImageFailed => (),
+      ImageFailed(*) => (),
       _ => fail
     }
 
@@ -1039,6 +1494,32 @@ fn should_return_image_failed_on_wait_if_image_fails_to_load() {
     mock_resource_task.send(resource_task::Exit);
 }
 
+#[test]
+fn should_return_decode_failed_if_decoder_fails_midway() {
+    let mock_resource_task = do mock_resource_task |response| {
+        response.send(resource_task::Payload(test_image_bin()));
+        response.send(resource_task::Done(result::Ok(())));
+    };
+
+    let decoder_factory: DecoderFactory = || {
+        let decode: ~fn(&[u8]) -> Option<Image> = |_| fail!(~"truncated image");
+        decode
+    };
+
+    let image_cache_task = ImageCacheTask_(mock_resource_task, decoder_factory);
+    let url = make_url(~"file", None);
+
+    image_cache_task.send(Prefetch(url.clone()));
+    image_cache_task.send(Decode(url.clone()));
+
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(WaitForImage(url, response_chan));
+    assert!(response_port.recv() == ImageFailed(DecodeFailed));
+
+    image_cache_task.exit();
+    mock_resource_task.send(resource_task::Exit);
+}
+
 #[test]
 fn sync_cache_should_wait_for_images() {
     let mock_resource_task = do mock_resource_task |response| {
@@ -1063,3 +1544,76 @@ fn sync_cache_should_wait_for_images() {
     mock_resource_task.send(resource_task::Exit);
 }
 
+
+#[test]
+fn should_decode_evicted_images_again() {
+    let mock_resource_task = do mock_resource_task |response| {
+        response.send(resource_task::Payload(test_image_bin()));
+        response.send(resource_task::Done(result::Ok(())));
+    };
+
+    // Too little memory to keep more than one image at a time.
+    let image_cache_task = ImageCacheTaskWithMemoryLimit(mock_resource_task.clone(), 1, true);
+    let first_url = make_url(~"file:///first.png", None);
+    let second_url = make_url(~"file:///second.png", None);
+
+    for url in [first_url.clone(), second_url].iter() {
+        image_cache_task.send(Prefetch(url.clone()));
+        image_cache_task.send(Decode(url.clone()));
+
+        let (response_chan, response_port) = stream();
+        image_cache_task.send(WaitForImage(url.clone(), response_chan));
+        match response_port.recv() {
+          ImageReady(*) => (),
+          _ => fail
+        }
+    }
+
+    // Decoding the second image evicted the first, so it has to be decoded again.
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(GetImage(first_url.clone(), response_chan));
+    assert!(response_port.recv() == ImageNotReady);
+
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(WaitForImage(first_url, response_chan));
+    match response_port.recv() {
+      ImageReady(*) => (),
+      _ => fail
+    }
+
+    image_cache_task.exit();
+    mock_resource_task.send(resource_task::Exit);
+}
+
+#[test]
+fn should_decode_several_images_concurrently() {
+    let mock_resource_task = do mock_resource_task |response| {
+        response.send(resource_task::Payload(test_image_bin()));
+        response.send(resource_task::Done(result::Ok(())));
+    };
+
+    let image_cache_task = ImageCacheTask(mock_resource_task.clone());
+
+    // Start every decode before waiting on any, so that they run on the decoder threads at once.
+    let mut response_ports = ~[];
+    for i in range(0u, 8) {
+        let url = make_url(fmt!("file:///%u.png", i), None);
+        image_cache_task.send(Prefetch(url.clone()));
+        image_cache_task.send(Decode(url.clone()));
+        for _ in range(0, 2) {
+            let (response_chan, response_port) = stream();
+            image_cache_task.send(WaitForImage(url.clone(), response_chan));
+            response_ports.push(response_port);
+        }
+    }
+
+    for response_port in response_ports.iter() {
+        match response_port.recv() {
+          ImageReady(*) => (),
+          _ => fail
+        }
+    }
+
+    image_cache_task.exit();
+    mock_resource_task.send(resource_task::Exit);
+}
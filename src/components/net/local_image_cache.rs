@@ -8,13 +8,19 @@ extra message traffic, it also avoids waiting on the same image
 multiple times and thus triggering reflows multiple times.
 */
 
-use image_cache_task::{Decode, GetImage, ImageCacheTask, ImageFailed, ImageNotReady, ImageReady};
-use image_cache_task::{ImageResponseMsg, Prefetch, WaitForImage};
+use image::base::{Animation, Image};
+use image_cache_task::{AnimatedImageReady, Decode, GetImage, ImageCacheTask, ImageFailed};
+use image_cache_task::{ImageNotReady, ImageReady, ImageResponseMsg, PartialImageReady, Prefetch};
+use image_cache_task::WaitForProgress;
 
 use std::comm;
 use std::comm::Port;
+use std::rt::rtio::RtioTimer;
+use std::rt::io::timer::Timer;
 use std::task;
 use servo_util::url::{UrlMap, url_map};
+use extra::arc::Arc;
+use extra::time::precise_time_ns;
 use extra::url::Url;
 
 pub fn LocalImageCache(image_cache_task: ImageCacheTask) -> LocalImageCache {
@@ -22,6 +28,7 @@ pub fn LocalImageCache(image_cache_task: ImageCacheTask) -> LocalImageCache {
         image_cache_task: image_cache_task,
         round_number: 1,
         on_image_available: None,
+        next_animation_reflow: None,
         state_map: url_map()
     }
 }
@@ -30,6 +37,8 @@ pub struct LocalImageCache {
     priv image_cache_task: ImageCacheTask,
     priv round_number: uint,
     priv on_image_available: Option<@fn() -> ~fn(ImageResponseMsg)>,
+    /// When the reflow that advances animated images is due, in nanoseconds, if one is scheduled.
+    priv next_animation_reflow: Option<u64>,
     priv state_map: UrlMap<@mut ImageState>
 }
 
@@ -37,7 +46,9 @@ struct ImageState {
     prefetched: bool,
     decoded: bool,
     last_request_round: uint,
-    last_response: ImageResponseMsg
+    last_response: ImageResponseMsg,
+    /// When the image was first shown, in nanoseconds, if it's animated.
+    animation_start: Option<u64>,
 }
 
 impl LocalImageCache {
@@ -79,6 +90,11 @@ impl LocalImageCache {
                 chan.send(ImageReady(image.clone()));
                 return port;
             }
+            AnimatedImageReady(ref animation) => {
+                let (port, chan) = comm::stream();
+                chan.send(AnimatedImageReady(animation.clone()));
+                return port;
+            }
             ImageNotReady => {
                 if last_round == self.round_number {
                     let (port, chan) = comm::stream();
@@ -89,9 +105,18 @@ impl LocalImageCache {
                     // remote cache this round
                 }
             }
-            ImageFailed => {
+            PartialImageReady(ref image) => {
+                if last_round == self.round_number {
+                    let (port, chan) = comm::stream();
+                    chan.send(PartialImageReady(image.clone()));
+                    return port;
+                } else {
+                    // More of the image may have loaded since we last asked
+                }
+            }
+            ImageFailed(error) => {
                 let (port, chan) = comm::stream();
-                chan.send(ImageFailed);
+                chan.send(ImageFailed(error));
                 return port;
             }
         }
@@ -101,8 +126,8 @@ impl LocalImageCache {
 
         let response = response_port.recv();
         match response {
-            ImageNotReady => {
-                // Need to reflow when the image is available
+            ImageNotReady | PartialImageReady(*) => {
+                // Need to reflow when more of the image is available
                 // FIXME: Instead we should be just passing a Future
                 // to the caller, then to the display list. Finally,
                 // the compositor should be resonsible for waiting
@@ -113,7 +138,7 @@ impl LocalImageCache {
                 let url = (*url).clone();
                 do task::spawn {
                     let (response_port, response_chan) = comm::stream();
-                    image_cache_task.send(WaitForImage(url.clone(), response_chan));
+                    image_cache_task.send(WaitForProgress(url.clone(), response_chan));
                     on_image_available(response_port.recv());
                 }
             }
@@ -123,8 +148,10 @@ impl LocalImageCache {
         // Put a copy of the response in the cache
         let response_copy = match response {
             ImageReady(ref image) => ImageReady(image.clone()),
+            AnimatedImageReady(ref animation) => AnimatedImageReady(animation.clone()),
+            PartialImageReady(ref image) => PartialImageReady(image.clone()),
             ImageNotReady => ImageNotReady,
-            ImageFailed => ImageFailed
+            ImageFailed(error) => ImageFailed(error)
         };
         state.last_response = response_copy;
 
@@ -133,13 +160,53 @@ impl LocalImageCache {
         return port;
     }
 
+    /// Returns the frame of an animated image to show now. The animation starts the first time
+    /// this is called for the URL, and a reflow is scheduled for when the frame changes.
+    pub fn current_frame(&mut self, url: &Url, animation: &Animation) -> Arc<~Image> {
+        let now = precise_time_ns();
+        let state = self.get_state(url);
+        let start = match state.animation_start {
+            Some(start) => start,
+            None => {
+                state.animation_start = Some(now);
+                now
+            }
+        };
+
+        let (index, next_frame_delay) = animation.frame_at((now - start) / 1000000);
+        for &delay in next_frame_delay.iter() {
+            self.schedule_animation_reflow(now, delay);
+        }
+        animation.frames[index].image.clone()
+    }
+
+    /// Asks for a reflow `delay` milliseconds from `now`, unless one is already due before then.
+    /// All animated images share the one reflow.
+    fn schedule_animation_reflow(&mut self, now: u64, delay: uint) {
+        let due = now + delay as u64 * 1000000;
+        match self.next_animation_reflow {
+            Some(scheduled) if scheduled > now && scheduled <= due => return,
+            _ => {}
+        }
+        self.next_animation_reflow = Some(due);
+
+        assert!(self.on_image_available.is_some());
+        let on_image_available = self.on_image_available.unwrap()();
+        let timer = Timer::new().unwrap();
+        do task::spawn {
+            timer.sleep(delay as u64);
+            on_image_available(ImageNotReady);
+        }
+    }
+
     fn get_state(&self, url: &Url) -> @mut ImageState {
         let state = do self.state_map.find_or_insert_with(url.clone()) |_| {
             let new_state = @mut ImageState {
                 prefetched: false,
                 decoded: false,
                 last_request_round: 0,
-                last_response: ImageNotReady
+                last_response: ImageNotReady,
+                animation_start: None,
             };
             new_state
         };
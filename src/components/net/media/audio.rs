@@ -0,0 +1,105 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decoded audio, and where it's played. Each platform plays audio its own way behind
+//! `AudioSink`; where there's no way yet, the audio goes nowhere but still takes up time, so
+//! media that has it plays at the right pace.
+
+use media::vorbis;
+use media::wav;
+
+#[cfg(target_os="linux")] use media::pulse::PulseAudioSink;
+
+/// Interleaved 16-bit samples.
+pub struct Samples {
+    channels: uint,
+    /// Frames of samples, one for each channel, per second.
+    rate: uint,
+    data: ~[i16],
+}
+
+impl Samples {
+    /// How many frames of samples there are.
+    pub fn frames(&self) -> uint {
+        self.data.len() / self.channels
+    }
+
+    /// How long the audio lasts, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.frames() as f64 / self.rate as f64
+    }
+}
+
+/// Whether `data` starts like audio that `decode` knows.
+pub fn is_audio(data: &[u8]) -> bool {
+    wav::is_wav(data) || vorbis::is_ogg(data)
+}
+
+/// Decodes a WAV or Ogg Vorbis file.
+pub fn decode(data: &[u8]) -> Option<Samples> {
+    if wav::is_wav(data) {
+        wav::decode(data)
+    } else if vorbis::is_ogg(data) {
+        vorbis::decode(data)
+    } else {
+        None
+    }
+}
+
+/// Somewhere to play audio. Sinks buffer what they're given; `write` blocks when the buffer is
+/// full.
+pub trait AudioSink {
+    /// Queues interleaved samples to be played after those written before.
+    fn write(&mut self, samples: &[i16]);
+    /// Drops the samples queued but not played yet.
+    fn flush(&mut self);
+}
+
+/// A sink that plays nothing.
+pub struct NullAudioSink;
+
+impl AudioSink for NullAudioSink {
+    fn write(&mut self, _samples: &[i16]) {
+    }
+
+    fn flush(&mut self) {
+    }
+}
+
+/// Opens a sink for audio with the given number of channels and frames per second. Falls back to
+/// a `NullAudioSink` if the platform's audio output can't be opened.
+#[cfg(target_os="linux")]
+pub fn open_sink(name: &str, channels: uint, rate: uint) -> ~AudioSink {
+    match PulseAudioSink::new(name, channels, rate) {
+        Some(sink) => ~sink as ~AudioSink,
+        None => {
+            debug!("audio: couldn't connect to PulseAudio, playing nothing");
+            ~NullAudioSink as ~AudioSink
+        }
+    }
+}
+
+/// Opens a sink for audio with the given number of channels and frames per second.
+///
+/// FIXME: Play audio through Core Audio on Mac OS X, and OpenSL ES on Android.
+#[cfg(not(target_os="linux"))]
+pub fn open_sink(_name: &str, _channels: uint, _rate: uint) -> ~AudioSink {
+    ~NullAudioSink as ~AudioSink
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Samples;
+
+    #[test]
+    fn duration() {
+        let samples = Samples {
+            channels: 2,
+            rate: 4,
+            data: ~[0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5],
+        };
+        assert!(samples.frames() == 6);
+        assert!(samples.duration() == 1.5);
+    }
+}
@@ -0,0 +1,94 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Audio output on Linux, through the simple API of PulseAudio.
+
+use media::audio::AudioSink;
+
+use std::c_str::ToCStr;
+use std::libc::{c_char, c_int, c_void, size_t};
+use std::ptr;
+use std::sys;
+use std::vec;
+
+static PA_STREAM_PLAYBACK: c_int = 1;
+static PA_SAMPLE_S16LE: c_int = 3;
+
+struct pa_sample_spec {
+    format: c_int,
+    rate: u32,
+    channels: u8,
+}
+
+#[link_args = "-lpulse-simple -lpulse"]
+extern {
+    fn pa_simple_new(server: *c_char, name: *c_char, dir: c_int, dev: *c_char,
+                     stream_name: *c_char, ss: *pa_sample_spec, map: *c_void, attr: *c_void,
+                     error: *mut c_int) -> *c_void;
+    fn pa_simple_write(s: *c_void, data: *c_void, bytes: size_t, error: *mut c_int) -> c_int;
+    fn pa_simple_flush(s: *c_void, error: *mut c_int) -> c_int;
+    fn pa_simple_free(s: *c_void);
+}
+
+pub struct PulseAudioSink {
+    priv connection: *c_void,
+}
+
+impl PulseAudioSink {
+    /// Connects to the default server and opens a playback stream, or returns `None` if that
+    /// can't be done, e.g. because no server is running.
+    pub fn new(name: &str, channels: uint, rate: uint) -> Option<PulseAudioSink> {
+        if channels == 0 || channels > 32 {
+            return None;
+        }
+        let spec = pa_sample_spec {
+            format: PA_SAMPLE_S16LE,
+            rate: rate as u32,
+            channels: channels as u8,
+        };
+        let mut error = 0;
+        let connection = do "Servo".to_c_str().with_ref |client_name| {
+            do name.to_c_str().with_ref |stream_name| {
+                unsafe {
+                    pa_simple_new(ptr::null(), client_name, PA_STREAM_PLAYBACK, ptr::null(),
+                                  stream_name, &spec, ptr::null(), ptr::null(), &mut error)
+                }
+            }
+        };
+        if connection.is_null() {
+            return None;
+        }
+        Some(PulseAudioSink {
+            connection: connection,
+        })
+    }
+}
+
+impl AudioSink for PulseAudioSink {
+    fn write(&mut self, samples: &[i16]) {
+        let mut error = 0;
+        unsafe {
+            let bytes = samples.len() * sys::size_of::<i16>();
+            if pa_simple_write(self.connection, vec::raw::to_ptr(samples) as *c_void,
+                               bytes as size_t, &mut error) < 0 {
+                debug!("audio: couldn't write to PulseAudio (error %d)", error as int);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let mut error = 0;
+        unsafe {
+            pa_simple_flush(self.connection, &mut error);
+        }
+    }
+}
+
+impl Drop for PulseAudioSink {
+    fn drop(&self) {
+        unsafe {
+            pa_simple_free(self.connection);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Ogg Vorbis decoding, by way of libvorbisfile. The file is decoded from memory through a stdio
+//! stream, which is the simplest way in that doesn't take callbacks.
+//!
+//! FIXME: The C library of Mac OS X has no `fmemopen`; decoding there needs `ov_open_callbacks`.
+
+use media::audio::Samples;
+
+use std::c_str::ToCStr;
+use std::libc::{c_char, c_int, c_long, c_void, size_t, FILE};
+use std::ptr;
+use std::vec;
+
+/// Room for an `OggVorbis_File`, which is opaque here. It's under a kilobyte on the platforms
+/// we build for; this leaves plenty to spare, and `u64`s keep it aligned.
+static OV_FILE_WORDS: uint = 256;
+
+/// What `ov_read` returns for a gap in the data, which it carries on past.
+static OV_HOLE: c_long = -3;
+
+/// How many bytes to ask for at a time.
+static READ_SIZE: c_int = 4096;
+
+struct vorbis_info {
+    version: c_int,
+    channels: c_int,
+    rate: c_long,
+}
+
+#[link_args = "-lvorbisfile"]
+extern {
+    fn ov_open(f: *FILE, vf: *mut c_void, initial: *c_char, ibytes: c_long) -> c_int;
+    fn ov_info(vf: *mut c_void, link: c_int) -> *vorbis_info;
+    fn ov_read(vf: *mut c_void, buffer: *mut c_char, length: c_int, bigendianp: c_int,
+               word: c_int, sgned: c_int, bitstream: *mut c_int) -> c_long;
+    fn ov_clear(vf: *mut c_void) -> c_int;
+}
+
+extern {
+    fn fmemopen(buf: *c_void, size: size_t, mode: *c_char) -> *FILE;
+    fn fclose(f: *FILE) -> c_int;
+}
+
+/// Whether `data` starts like an Ogg file. It may hold something other than Vorbis.
+pub fn is_ogg(data: &[u8]) -> bool {
+    data.len() >= 4 && data.slice_to(4) == bytes!("OggS")
+}
+
+/// Decodes an Ogg Vorbis file to 16-bit samples, or returns `None` if it's not one. A file with
+/// several chained streams is decoded as if it were all in the format of the first.
+pub fn decode(data: &[u8]) -> Option<Samples> {
+    if !is_ogg(data) {
+        return None;
+    }
+
+    unsafe {
+        let file = do "rb".to_c_str().with_ref |mode| {
+            fmemopen(vec::raw::to_ptr(data) as *c_void, data.len() as size_t, mode)
+        };
+        if file.is_null() {
+            return None;
+        }
+        let mut ov_file = vec::from_elem(OV_FILE_WORDS, 0u64);
+        let vf = vec::raw::to_mut_ptr(ov_file) as *mut c_void;
+        // On success the stream belongs to libvorbisfile, and `ov_clear` closes it.
+        if ov_open(file, vf, ptr::null(), 0) != 0 {
+            fclose(file);
+            return None;
+        }
+
+        let info = ov_info(vf, -1);
+        if info.is_null() {
+            ov_clear(vf);
+            return None;
+        }
+        let (channels, rate) = ((*info).channels as uint, (*info).rate as uint);
+        let mut bytes: ~[u8] = ~[];
+        let mut buffer = vec::from_elem(READ_SIZE as uint, 0u8);
+        let mut bitstream = 0;
+        loop {
+            let read = ov_read(vf,
+                               vec::raw::to_mut_ptr(buffer) as *mut c_char,
+                               READ_SIZE,
+                               0,
+                               2,
+                               1,
+                               &mut bitstream);
+            // Holes in the data are skipped over; anything else negative is fatal.
+            if read == 0 {
+                break
+            } else if read > 0 {
+                bytes.push_all(buffer.slice_to(read as uint));
+            } else if read != OV_HOLE {
+                ov_clear(vf);
+                return None;
+            }
+        }
+        ov_clear(vf);
+
+        if channels == 0 || rate == 0 {
+            return None;
+        }
+        let samples = vec::from_fn(bytes.len() / 2, |i| {
+            ((bytes[i * 2] as u16) | (bytes[i * 2 + 1] as u16 << 8)) as i16
+        });
+        Some(Samples {
+            channels: channels,
+            rate: rate,
+            data: samples,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, is_ogg};
+
+    #[test]
+    fn sniffing() {
+        assert!(is_ogg(bytes!("OggS\x00\x02")));
+        assert!(!is_ogg(bytes!("RIFF")));
+        assert!(!is_ogg(bytes!("Ogg")));
+    }
+
+    #[test]
+    fn not_vorbis() {
+        assert!(decode(bytes!("OggS but not really an Ogg file")).is_none());
+    }
+}
@@ -0,0 +1,188 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! VP8 decoding, by way of libvpx.
+
+use image::base::Image;
+
+use std::libc::{c_char, c_int, c_long, c_uint, c_void};
+use std::ptr;
+use std::vec;
+
+/// The decoder ABI version of the libvpx headers this was written against (libvpx 1.2), which
+/// libvpx checks on initialization.
+static VPX_DECODER_ABI_VERSION: c_int = 5;
+
+static VPX_CODEC_OK: c_int = 0;
+
+/// The planar YUV 4:2:0 format VP8 decodes to.
+static VPX_IMG_FMT_I420: c_int = 0x102;
+
+struct vpx_codec_ctx_t {
+    name: *c_char,
+    iface: *c_void,
+    err: c_int,
+    err_detail: *c_char,
+    init_flags: c_long,
+    config: *c_void,
+    priv_data: *c_void,
+}
+
+/// The start of `vpx_image_t`; only the fields up to the planes are read.
+struct vpx_image_t {
+    fmt: c_int,
+    w: c_uint,
+    h: c_uint,
+    d_w: c_uint,
+    d_h: c_uint,
+    x_chroma_shift: c_uint,
+    y_chroma_shift: c_uint,
+    planes: [*u8, ..4],
+    stride: [c_int, ..4],
+}
+
+#[link_args = "-lvpx"]
+extern {
+    fn vpx_codec_vp8_dx() -> *c_void;
+    fn vpx_codec_dec_init_ver(ctx: *mut vpx_codec_ctx_t, iface: *c_void, cfg: *c_void,
+                              flags: c_long, ver: c_int) -> c_int;
+    fn vpx_codec_decode(ctx: *mut vpx_codec_ctx_t, data: *u8, data_sz: c_uint,
+                        user_priv: *c_void, deadline: c_long) -> c_int;
+    fn vpx_codec_get_frame(ctx: *mut vpx_codec_ctx_t, iter: *mut *c_void) -> *vpx_image_t;
+    fn vpx_codec_destroy(ctx: *mut vpx_codec_ctx_t) -> c_int;
+}
+
+/// A VP8 decoder. Frames have to be decoded in order, starting from a keyframe.
+pub struct Decoder {
+    /// Boxed, since libvpx keeps pointers into it.
+    priv ctx: ~vpx_codec_ctx_t,
+}
+
+impl Decoder {
+    /// Makes a decoder, or returns `None` if libvpx can't.
+    pub fn new() -> Option<Decoder> {
+        let mut ctx = ~vpx_codec_ctx_t {
+            name: ptr::null(),
+            iface: ptr::null(),
+            err: 0,
+            err_detail: ptr::null(),
+            init_flags: 0,
+            config: ptr::null(),
+            priv_data: ptr::null(),
+        };
+        unsafe {
+            let result = vpx_codec_dec_init_ver(&mut *ctx,
+                                                vpx_codec_vp8_dx(),
+                                                ptr::null(),
+                                                0,
+                                                VPX_DECODER_ABI_VERSION);
+            if result != VPX_CODEC_OK {
+                return None;
+            }
+        }
+        Some(Decoder { ctx: ctx })
+    }
+
+    /// Decodes a frame into opaque BGRA, the same byte order as the image decoders. Returns
+    /// `None` if the data is corrupt or the frame isn't meant to be shown.
+    pub fn decode(&mut self, data: &[u8]) -> Option<Image> {
+        unsafe {
+            let result = vpx_codec_decode(&mut *self.ctx,
+                                          vec::raw::to_ptr(data),
+                                          data.len() as c_uint,
+                                          ptr::null(),
+                                          0);
+            if result != VPX_CODEC_OK {
+                return None;
+            }
+
+            let mut iter = ptr::null();
+            let image = vpx_codec_get_frame(&mut *self.ctx, &mut iter);
+            if image.is_null() || (*image).fmt != VPX_IMG_FMT_I420 {
+                return None;
+            }
+
+            let width = (*image).d_w as uint;
+            let height = (*image).d_h as uint;
+            let plane = |index: uint, rows: uint| {
+                let length = (*image).stride[index] as uint * rows;
+                vec::raw::from_buf_raw((*image).planes[index], length)
+            };
+            let chroma_height = (height + 1) / 2;
+            let y = plane(0, height);
+            let u = plane(1, chroma_height);
+            let v = plane(2, chroma_height);
+            let pixels = i420_to_bgra(width, height,
+                                      y, (*image).stride[0] as uint,
+                                      u, (*image).stride[1] as uint,
+                                      v, (*image).stride[2] as uint);
+            Some(Image(width, height, 4, pixels))
+        }
+    }
+}
+
+#[unsafe_destructor]
+impl Drop for Decoder {
+    fn drop(&self) {
+        unsafe {
+            let ctx: *mut vpx_codec_ctx_t = &*self.ctx as *vpx_codec_ctx_t as *mut vpx_codec_ctx_t;
+            vpx_codec_destroy(ctx);
+        }
+    }
+}
+
+/// Converts planar YUV 4:2:0 with the video range and BT.601 coefficients VP8 uses to opaque
+/// BGRA. Each chroma sample covers two by two pixels.
+fn i420_to_bgra(width: uint, height: uint,
+                y: &[u8], y_stride: uint,
+                u: &[u8], u_stride: uint,
+                v: &[u8], v_stride: uint)
+                -> ~[u8] {
+    let clamp = |value: int| -> u8 {
+        if value < 0 { 0 } else if value > 255 { 255 } else { value as u8 }
+    };
+
+    let mut pixels = vec::with_capacity(width * height * 4);
+    for row in range(0, height) {
+        for column in range(0, width) {
+            let c = 298 * (y[row * y_stride + column] as int - 16);
+            let d = u[(row / 2) * u_stride + column / 2] as int - 128;
+            let e = v[(row / 2) * v_stride + column / 2] as int - 128;
+            pixels.push(clamp((c + 516 * d + 128) >> 8));
+            pixels.push(clamp((c - 100 * d - 208 * e + 128) >> 8));
+            pixels.push(clamp((c + 409 * e + 128) >> 8));
+            pixels.push(0xff);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::i420_to_bgra;
+
+    #[test]
+    fn color_conversion() {
+        // Black, white and gray, then red, on top; blue underneath.
+        let y = [16, 235, 81, 81,
+                 126, 126, 81, 81,
+                 41, 41, 41, 41,
+                 41, 41, 41, 41];
+        let u = [128, 90,
+                 240, 240];
+        let v = [128, 240,
+                 110, 110];
+        let pixels = i420_to_bgra(4, 4, y, 4, u, 2, v, 2);
+        assert!(pixels.len() == 4 * 4 * 4);
+        let pixel = |column: uint, row: uint| {
+            let start = (row * 4 + column) * 4;
+            pixels.slice(start, start + 4).to_owned()
+        };
+        assert!(pixel(0, 0) == ~[0x00, 0x00, 0x00, 0xff]);
+        assert!(pixel(1, 0) == ~[0xff, 0xff, 0xff, 0xff]);
+        assert!(pixel(0, 1) == ~[0x80, 0x80, 0x80, 0xff]);
+        assert!(pixel(3, 1) == ~[0x00, 0x00, 0xff, 0xff]);
+        assert!(pixel(2, 3) == ~[0xff, 0x00, 0x00, 0xff]);
+    }
+}
@@ -0,0 +1,137 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! WAV decoding. Only uncompressed PCM with 8 or 16 bits per sample is supported, which is what
+//! nearly every WAV file holds.
+
+use media::audio::Samples;
+
+use std::vec;
+
+static WAVE_FORMAT_PCM: u16 = 1;
+
+/// Whether `data` starts like a WAV file.
+pub fn is_wav(data: &[u8]) -> bool {
+    data.len() >= 12 && data.slice_to(4) == bytes!("RIFF") && data.slice(8, 12) == bytes!("WAVE")
+}
+
+/// Decodes a WAV file to 16-bit samples, or returns `None` if it's not one that's supported.
+pub fn decode(data: &[u8]) -> Option<Samples> {
+    if !is_wav(data) {
+        return None;
+    }
+
+    let mut format: Option<(uint, uint, uint)> = None;
+    let mut position = 12;
+    while position + 8 <= data.len() {
+        let id = data.slice(position, position + 4);
+        let size = u32_at(data, position + 4) as uint;
+        let start = position + 8;
+        // A chunk that claims to run past the end of the file is cut short.
+        let end = if size > data.len() - start { data.len() } else { start + size };
+        let body = data.slice(start, end);
+
+        if id == bytes!("fmt ") {
+            if body.len() < 16 || u16_at(body, 0) != WAVE_FORMAT_PCM {
+                return None;
+            }
+            let channels = u16_at(body, 2) as uint;
+            let rate = u32_at(body, 4) as uint;
+            let bits = u16_at(body, 14) as uint;
+            if channels == 0 || rate == 0 || (bits != 8 && bits != 16) {
+                return None;
+            }
+            format = Some((channels, rate, bits));
+        } else if id == bytes!("data") {
+            let (channels, rate, bits) = match format {
+                Some(format) => format,
+                None => return None,
+            };
+            let samples = if bits == 8 {
+                // 8-bit samples are unsigned.
+                body.iter().map(|&sample| ((sample as i16) - 128) << 8).collect()
+            } else {
+                vec::from_fn(body.len() / 2, |i| u16_at(body, i * 2) as i16)
+            };
+            return Some(Samples {
+                channels: channels,
+                rate: rate,
+                data: samples,
+            });
+        }
+
+        // Chunks are padded to an even size.
+        position = end + (size & 1);
+    }
+    None
+}
+
+fn u16_at(data: &[u8], index: uint) -> u16 {
+    (data[index] as u16) | (data[index + 1] as u16 << 8)
+}
+
+fn u32_at(data: &[u8], index: uint) -> u32 {
+    (u16_at(data, index) as u32) | (u16_at(data, index + 2) as u32 << 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, is_wav};
+
+    /// A WAV header for `data_size` bytes of PCM, with a chunk that should be skipped before the
+    /// data.
+    fn header(channels: u8, rate: u32, bits: u8, data_size: u8) -> ~[u8] {
+        let rate = [rate as u8, (rate >> 8) as u8, (rate >> 16) as u8, (rate >> 24) as u8];
+        let mut wav = ~[];
+        wav.push_all(bytes!("RIFF"));
+        wav.push_all([0, 0, 0, 0]);
+        wav.push_all(bytes!("WAVE"));
+        wav.push_all(bytes!("fmt "));
+        wav.push_all([16, 0, 0, 0, 1, 0, channels, 0]);
+        wav.push_all(rate);
+        wav.push_all([0, 0, 0, 0, 0, 0, bits, 0]);
+        wav.push_all(bytes!("LIST"));
+        wav.push_all([3, 0, 0, 0, 1, 2, 3, 0]);
+        wav.push_all(bytes!("data"));
+        wav.push_all([data_size, 0, 0, 0]);
+        wav
+    }
+
+    #[test]
+    fn sniffing() {
+        assert!(is_wav(header(1, 8000, 16, 0)));
+        assert!(!is_wav(bytes!("RIFF\x00\x00\x00\x00AVI ")));
+        assert!(!is_wav(bytes!("OggS")));
+    }
+
+    #[test]
+    fn sixteen_bit() {
+        let mut wav = header(2, 44100, 16, 8);
+        wav.push_all([0x00, 0x00, 0xff, 0x7f, 0x00, 0x80, 0x01, 0x00]);
+        let samples = decode(wav).unwrap();
+        assert!(samples.channels == 2);
+        assert!(samples.rate == 44100);
+        assert!(samples.data == ~[0, 32767, -32768, 1]);
+    }
+
+    #[test]
+    fn eight_bit() {
+        let mut wav = header(1, 8000, 8, 3);
+        wav.push_all([0x80, 0xff, 0x00]);
+        let samples = decode(wav).unwrap();
+        assert!(samples.channels == 1);
+        assert!(samples.rate == 8000);
+        assert!(samples.data == ~[0, 127 << 8, -128 << 8]);
+    }
+
+    #[test]
+    fn unsupported() {
+        let mut wav = header(1, 8000, 24, 3);
+        wav.push_all([0, 0, 0]);
+        assert!(decode(wav).is_none());
+        // No data chunk.
+        let wav = header(1, 8000, 16, 0);
+        assert!(decode(wav.slice_to(wav.len() - 8)).is_none());
+    }
+}
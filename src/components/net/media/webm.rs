@@ -0,0 +1,360 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A WebM demuxer. WebM is a subset of Matroska, which is in turn made of EBML elements: an ID, a
+//! size and a body, each of the first two a variable-length integer.
+//!
+//! The whole file is demuxed at once. Master elements are walked into rather than parsed
+//! recursively, so elements of unknown size, which live streams use for segments and clusters,
+//! just run on to whatever follows them.
+
+use std::cast;
+use std::str;
+
+static EBML: u64 = 0x1a45dfa3;
+static DOC_TYPE: u64 = 0x4282;
+static SEGMENT: u64 = 0x18538067;
+static INFO: u64 = 0x1549a966;
+static TIMECODE_SCALE: u64 = 0x2ad7b1;
+static DURATION: u64 = 0x4489;
+static TRACKS: u64 = 0x1654ae6b;
+static TRACK_ENTRY: u64 = 0xae;
+static TRACK_NUMBER: u64 = 0xd7;
+static TRACK_TYPE: u64 = 0x83;
+static CODEC_ID: u64 = 0x86;
+static VIDEO: u64 = 0xe0;
+static PIXEL_WIDTH: u64 = 0xb0;
+static PIXEL_HEIGHT: u64 = 0xba;
+static CLUSTER: u64 = 0x1f43b675;
+static TIMECODE: u64 = 0xe7;
+static SIMPLE_BLOCK: u64 = 0xa3;
+static BLOCK_GROUP: u64 = 0xa0;
+static BLOCK: u64 = 0xa1;
+static REFERENCE_BLOCK: u64 = 0xfb;
+
+/// Timecodes are in milliseconds unless the file says otherwise.
+static DEFAULT_TIMECODE_SCALE: u64 = 1000000;
+
+#[deriving(Clone, Eq)]
+pub enum TrackKind {
+    VideoTrack,
+    AudioTrack,
+    OtherTrack,
+}
+
+#[deriving(Clone)]
+pub struct Track {
+    number: u64,
+    kind: TrackKind,
+    /// The Matroska codec ID, such as `V_VP8`.
+    codec: ~str,
+    /// The size of the pictures of a video track, in pixels.
+    width: uint,
+    height: uint,
+}
+
+/// One block of compressed data.
+pub struct Frame {
+    track: u64,
+    /// When the frame is shown, in seconds from the start.
+    time: f64,
+    /// Whether the frame can be decoded without the ones before it.
+    keyframe: bool,
+    data: ~[u8],
+}
+
+pub struct WebM {
+    /// How long the media lasts, in seconds, if the file says.
+    duration: Option<f64>,
+    tracks: ~[Track],
+    /// The frames of every track, in the order they're stored.
+    frames: ~[Frame],
+}
+
+impl WebM {
+    /// The first video track, if there is one.
+    pub fn video_track<'a>(&'a self) -> Option<&'a Track> {
+        self.tracks.iter().find(|track| track.kind == VideoTrack)
+    }
+}
+
+/// Returns true if the data starts like an EBML file, as WebM files do.
+pub fn is_webm(data: &[u8]) -> bool {
+    data.len() >= 4 && unsigned(data.slice_to(4)) == EBML
+}
+
+struct Reader<'self> {
+    data: &'self [u8],
+    position: uint,
+}
+
+impl<'self> Reader<'self> {
+    fn at_end(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn remaining(&self) -> uint {
+        self.data.len() - self.position
+    }
+
+    /// Reads a variable-length integer. The number of leading zero bits of its first byte says
+    /// how many bytes follow. IDs are written with the marker bit after the zeroes and sizes are
+    /// not, so `keep_marker` keeps it for reading IDs.
+    fn read_vint(&mut self, keep_marker: bool) -> Option<u64> {
+        if self.at_end() {
+            return None;
+        }
+        let first = self.data[self.position];
+        let mut length = 1;
+        while length <= 8 && first & (0x80 >> (length - 1)) == 0 {
+            length += 1;
+        }
+        if length > 8 || length > self.remaining() {
+            return None;
+        }
+        let mut value = if keep_marker {
+            first as u64
+        } else {
+            (first as u64) & ((1 << (8 - length)) - 1)
+        };
+        for i in range(1, length) {
+            value = (value << 8) | self.data[self.position + i] as u64;
+        }
+        self.position += length;
+        Some(value)
+    }
+
+    /// Reads the ID and body size of the next element. A size of all ones means the size isn't
+    /// known, which is taken as the rest of the file.
+    fn read_header(&mut self) -> Option<(u64, uint)> {
+        let id = match self.read_vint(true) {
+            Some(id) => id,
+            None => return None,
+        };
+        let start = self.position;
+        let size = match self.read_vint(false) {
+            Some(size) => size,
+            None => return None,
+        };
+        let length = self.position - start;
+        let unknown = (1 << (7 * length)) - 1;
+        if size == unknown || size > self.remaining() as u64 {
+            Some((id, self.remaining()))
+        } else {
+            Some((id, size as uint))
+        }
+    }
+
+    fn read_bytes(&mut self, size: uint) -> &'self [u8] {
+        let bytes = self.data.slice(self.position, self.position + size);
+        self.position += size;
+        bytes
+    }
+}
+
+/// Reads the body of an unsigned integer element.
+fn unsigned(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |value, &byte| (value << 8) | byte as u64)
+}
+
+/// Reads the body of a float element, which is either 4 or 8 bytes.
+fn float(bytes: &[u8]) -> Option<f64> {
+    match bytes.len() {
+        4 => Some(unsafe { cast::transmute::<u32, f32>(unsigned(bytes) as u32) } as f64),
+        8 => Some(unsafe { cast::transmute::<u64, f64>(unsigned(bytes)) }),
+        _ => None,
+    }
+}
+
+/// Demuxes a whole WebM file. Returns `None` if it isn't one.
+pub fn demux(data: &[u8]) -> Option<WebM> {
+    if !is_webm(data) {
+        return None;
+    }
+
+    let mut reader = Reader { data: data, position: 0 };
+    let mut webm = WebM { duration: None, tracks: ~[], frames: ~[] };
+    let mut timecode_scale = DEFAULT_TIMECODE_SCALE;
+    let mut cluster_time = 0;
+    let mut duration_ticks = None;
+
+    while !reader.at_end() {
+        let (id, size) = match reader.read_header() {
+            Some(header) => header,
+            None => break,
+        };
+        match id {
+            // Walk into the body of master elements, so their children are read in turn.
+            EBML | SEGMENT | INFO | TRACKS | VIDEO | CLUSTER | BLOCK_GROUP => {}
+            TRACK_ENTRY => {
+                webm.tracks.push(Track {
+                    number: 0,
+                    kind: OtherTrack,
+                    codec: ~"",
+                    width: 0,
+                    height: 0,
+                });
+            }
+            _ => {
+                let body = reader.read_bytes(size);
+                match id {
+                    DOC_TYPE => {
+                        if body != bytes!("webm") && body != bytes!("matroska") {
+                            return None;
+                        }
+                    }
+                    TIMECODE_SCALE => timecode_scale = unsigned(body),
+                    DURATION => duration_ticks = float(body),
+                    TRACK_NUMBER | TRACK_TYPE | CODEC_ID | PIXEL_WIDTH | PIXEL_HEIGHT => {
+                        let count = webm.tracks.len();
+                        if count > 0 {
+                            read_track_field(&mut webm.tracks[count - 1], id, body);
+                        }
+                    }
+                    TIMECODE => cluster_time = unsigned(body) as i64,
+                    SIMPLE_BLOCK | BLOCK => {
+                        match read_block(body, id == SIMPLE_BLOCK) {
+                            Some((track, time, keyframe, data)) => {
+                                let ticks = (cluster_time + time as i64) as f64;
+                                webm.frames.push(Frame {
+                                    track: track,
+                                    time: ticks * timecode_scale as f64 / 1e9,
+                                    keyframe: keyframe,
+                                    data: data.to_owned(),
+                                });
+                            }
+                            None => {}
+                        }
+                    }
+                    REFERENCE_BLOCK => {
+                        // A block in a group that refers to another one isn't a keyframe.
+                        let count = webm.frames.len();
+                        if count > 0 {
+                            webm.frames[count - 1].keyframe = false;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    webm.duration = do duration_ticks.map_move |ticks| {
+        ticks * timecode_scale as f64 / 1e9
+    };
+    Some(webm)
+}
+
+fn read_track_field(track: &mut Track, id: u64, body: &[u8]) {
+    match id {
+        TRACK_NUMBER => track.number = unsigned(body),
+        TRACK_TYPE => {
+            track.kind = match unsigned(body) {
+                1 => VideoTrack,
+                2 => AudioTrack,
+                _ => OtherTrack,
+            }
+        }
+        CODEC_ID => {
+            track.codec = if str::is_utf8(body) { str::from_utf8(body) } else { ~"" }
+        }
+        PIXEL_WIDTH => track.width = unsigned(body) as uint,
+        PIXEL_HEIGHT => track.height = unsigned(body) as uint,
+        _ => {}
+    }
+}
+
+/// Reads the track number, timecode relative to its cluster, whether it's a keyframe and the
+/// data of a block.
+///
+/// FIXME: Laced blocks, which hold more than one frame, are skipped. Video tracks don't use
+/// lacing, but audio ones often do.
+fn read_block<'a>(body: &'a [u8], simple: bool) -> Option<(u64, i16, bool, &'a [u8])> {
+    let mut reader = Reader { data: body, position: 0 };
+    let track = match reader.read_vint(false) {
+        Some(track) => track,
+        None => return None,
+    };
+    if reader.remaining() < 3 {
+        return None;
+    }
+    let time = unsigned(reader.read_bytes(2)) as u16 as i16;
+    let flags = reader.read_bytes(1)[0];
+    if flags & 0x06 != 0 {
+        return None;
+    }
+    // Only simple blocks have a keyframe flag. Blocks in groups are keyframes unless the group
+    // has a reference block.
+    let keyframe = !simple || flags & 0x80 != 0;
+    let remaining = reader.remaining();
+    Some((track, time, keyframe, reader.read_bytes(remaining)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VideoTrack, demux, float, is_webm};
+
+    /// Writes an element with a one-byte size.
+    fn element(id: &[u8], body: &[u8]) -> ~[u8] {
+        let mut bytes = id.to_owned();
+        bytes.push(0x80 | body.len() as u8);
+        bytes.push_all(body);
+        bytes
+    }
+
+    fn sample() -> ~[u8] {
+        let mut file = element([0x1a, 0x45, 0xdf, 0xa3], element([0x42, 0x82], bytes!("webm")));
+
+        let info = element([0x15, 0x49, 0xa9, 0x66],
+                           element([0x44, 0x89], [0x40, 0x8f, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00]));
+        let video = element([0xb0], [0x01, 0x40]) + element([0xba], [0xf0]);
+        let entry = element([0xd7], [1]) + element([0x83], [1]) +
+            element([0x86], bytes!("V_VP8")) + element([0xe0], video);
+        let tracks = element([0x16, 0x54, 0xae, 0x6b], element([0xae], entry));
+        let cluster = element([0xe7], [0x03, 0xe8]) +
+            element([0xa3], [0x81, 0x00, 0x00, 0x80, 0xaa]) +
+            element([0xa3], [0x81, 0x00, 0x21, 0x00, 0xbb, 0xcc]);
+        let cluster = element([0x1f, 0x43, 0xb6, 0x75], cluster);
+
+        // The segment is of unknown size, as in a live stream.
+        file.push_all([0x18, 0x53, 0x80, 0x67, 0xff]);
+        file.push_all(info + tracks + cluster);
+        file
+    }
+
+    #[test]
+    fn sniffing() {
+        assert!(is_webm(sample()));
+        assert!(!is_webm(bytes!("RIFF\x24\x00\x00\x00WEBPVP8L")));
+    }
+
+    #[test]
+    fn floats() {
+        assert!(float([0x3f, 0xc0, 0x00, 0x00]) == Some(1.5));
+        assert!(float([0x40, 0x8f, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00]) == Some(1000.0));
+        assert!(float([0x00]) == None);
+    }
+
+    #[test]
+    fn demuxing() {
+        let webm = demux(sample()).unwrap();
+        assert!(webm.duration == Some(1.0));
+
+        let track = webm.video_track().unwrap();
+        assert!(track.number == 1 && track.kind == VideoTrack && track.codec == ~"V_VP8");
+        assert!(track.width == 320 && track.height == 240);
+
+        assert!(webm.frames.len() == 2);
+        assert!(webm.frames[0].time == 1.0 && webm.frames[0].keyframe);
+        assert!(webm.frames[0].data == ~[0xaa]);
+        assert!(webm.frames[1].time == 1.033 && !webm.frames[1].keyframe);
+        assert!(webm.frames[1].data == ~[0xbb, 0xcc]);
+    }
+
+    #[test]
+    fn other_doc_types() {
+        let file = element([0x1a, 0x45, 0xdf, 0xa3], element([0x42, 0x82], bytes!("other")));
+        assert!(demux(file).is_none());
+    }
+}
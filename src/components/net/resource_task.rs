@@ -4,29 +4,157 @@
 
 //! A task that takes a URL and streams back the binary data.
 
+use content_encoding::{ACCEPT_ENCODING, ContentEncoding, Identity, decoded_metadata};
+use cookie::{CookieJar, CookieSource, HTTP};
+use cors;
+use data_loader;
 use file_loader;
-//use http_loader;
+use history::History;
+use http_cache::{CachedResponse, DEFAULT_MEMORY_LIMIT, Fresh, HttpCache, Miss, Stale};
+use http_loader;
 
+use std::ascii::StrAsciiExt;
 use std::cell::Cell;
+use std::comm;
 use std::comm::{Chan, Port, SharedChan};
+use std::rt::io::timer::Timer;
+use std::rt::rtio::RtioTimer;
+use std::util::replace;
+use extra::arc::RWArc;
+use extra::time;
 use extra::url::Url;
+use servo_util::url::Origin;
 use util::spawn_listener;
 
 pub enum ControlMsg {
     /// Request the data associated with a particular URL
-    Load(Url, Chan<ProgressMsg>),
+    Load(LoadData, Chan<ProgressMsg>),
+    /// Stores the cookies in a `Set-Cookie` header (or `document.cookie` assignment) for a URL
+    SetCookiesForUrl(Url, ~str, CookieSource),
+    /// Retrieves the value of the `Cookie` header for a URL, if there are any cookies for it
+    GetCookiesForUrl(Url, Chan<Option<~str>>, CookieSource),
+    /// Slows down all subsequent loads as described, or stops slowing them down
+    SetThrottle(Option<Throttle>),
+    /// Records a visit to a page in the global history
+    AddVisit(Url),
+    /// Asks which of the given URLs were visited, so that links to them match `:visited`
+    GetVisited(~[Url], Chan<~[bool]>),
     Exit
 }
 
+/// Everything a loader needs in order to make a request.
+#[deriving(Clone)]
+pub struct LoadData {
+    url: Url,
+    /// The request method, e.g. "GET" or "POST".
+    method: ~str,
+    /// Extra request headers, as name/value pairs.
+    headers: ~[(~str, ~str)],
+    /// The request body, if any.
+    data: Option<~[u8]>,
+    /// The origin of the document that made the request, for requests whose responses it may
+    /// only read if they're of its origin or allow it through CORS, like those of
+    /// `XMLHttpRequest`.
+    origin: Option<Origin>,
+    /// Whether a cross-origin request sends and stores cookies. Same-origin requests always do.
+    credentials: bool,
+}
+
+impl LoadData {
+    /// Creates the data for a plain GET of the given URL.
+    pub fn new(url: Url) -> LoadData {
+        LoadData {
+            url: url,
+            method: ~"GET",
+            headers: ~[],
+            data: None,
+            origin: None,
+            credentials: false,
+        }
+    }
+}
+
+/// Information about a response that is known before its body arrives.
+#[deriving(Clone, Eq)]
+pub struct Metadata {
+    /// The URL the response actually came from, after any redirects.
+    final_url: Url,
+    /// The MIME type of the response, if known.
+    content_type: Option<~str>,
+    /// The length of the body in bytes, if known in advance.
+    content_length: Option<uint>,
+    /// The status code, e.g. 200 or 404.
+    status: uint,
+    /// The reason phrase that came with the status code, e.g. "OK".
+    status_text: ~str,
+    /// The response headers, as name/value pairs.
+    headers: ~[(~str, ~str)],
+}
+
+impl Metadata {
+    /// Metadata for a successful response from the given URL with no headers.
+    pub fn default(url: Url) -> Metadata {
+        Metadata {
+            final_url: url,
+            content_type: None,
+            content_length: None,
+            status: 200,
+            status_text: ~"OK",
+            headers: ~[],
+        }
+    }
+}
+
 /// Messages sent in response to a `Load` message
 #[deriving(Eq)]
 pub enum ProgressMsg {
-    /// Binary data - there may be multiple of these
+    /// The response metadata. This is sent at most once, before any payload.
+    Headers(Metadata),
+    /// A chunk of the body, in order - there may be multiple of these. Consumers should handle
+    /// each chunk as it arrives rather than waiting for the whole body.
     Payload(~[u8]),
     /// Indicates loading is complete, either successfully or not
     Done(Result<(), ()>)
 }
 
+/// Artificial limits on loads, for reproducing how pages behave on slow networks. The delays
+/// depend only on the size of the responses, so a throttled load takes the same time every run.
+#[deriving(Clone, Eq)]
+pub struct Throttle {
+    /// How long to hold back each response before its headers arrive, in milliseconds.
+    latency: uint,
+    /// How many bytes of a body may arrive per second, if limited.
+    bandwidth: Option<uint>,
+}
+
+/// How much of a response body has arrived, for consumers that report progress as the body
+/// streams in.
+#[deriving(Clone, Eq)]
+pub struct LoadProgress {
+    /// The number of bytes of the body received so far.
+    loaded: uint,
+    /// The length of the body, if the response said what it would be.
+    total: Option<uint>,
+}
+
+impl LoadProgress {
+    pub fn new() -> LoadProgress {
+        LoadProgress {
+            loaded: 0,
+            total: None,
+        }
+    }
+
+    /// Accounts for a message from the resource task.
+    pub fn update(&mut self, msg: &ProgressMsg) {
+        match *msg {
+            Headers(ref metadata) => self.total = metadata.content_length,
+            Payload(ref data) => self.loaded += data.len(),
+            Done(*) => {}
+        }
+    }
+}
+
 /// Handle to a resource task
 pub type ResourceTask = SharedChan<ControlMsg>;
 
@@ -36,26 +164,58 @@ Creates a task to load a specific resource
 The ResourceManager delegates loading to a different type of loader task for
 each URL scheme
 */
-type LoaderTaskFactory = ~fn() -> ~fn(url: Url, Chan<ProgressMsg>);
+type LoaderTaskFactory = ~fn() -> ~fn(load_data: LoadData, Chan<ProgressMsg>);
 
-pub type LoaderTask = ~fn(url: Url, Chan<ProgressMsg>);
+pub type LoaderTask = ~fn(load_data: LoadData, Chan<ProgressMsg>);
 
-/// Create a ResourceTask with the default loaders
+/// Create a ResourceTask with the default loaders and an in-memory HTTP cache
 pub fn ResourceTask() -> ResourceTask {
+    ResourceTaskWithCacheDir(None)
+}
+
+/// Create a ResourceTask with the default loaders, whose HTTP cache is also stored in the given
+/// directory, if any
+pub fn ResourceTaskWithCacheDir(cache_dir: Option<Path>) -> ResourceTask {
     let file_loader_factory: LoaderTaskFactory = file_loader::factory;
-    //let http_loader_factory: LoaderTaskFactory = http_loader::factory;
+    let data_loader_factory: LoaderTaskFactory = data_loader::factory;
+    let http_loader_factory: LoaderTaskFactory = http_loader::factory;
     let loaders = ~[
         (~"file", file_loader_factory),
-        //(~"http", http_loader_factory)
+        (~"data", data_loader_factory),
+        (~"http", http_loader_factory),
     ];
-    create_resource_task_with_loaders(loaders)
+    create_resource_task_with_loaders(loaders, cache_dir)
+}
+
+/// Create a ResourceTask that answers loads of `url` with `data` and fails all other loads, for
+/// running the engine without touching the network or the file system
+pub fn ResourceTaskWithData(url: Url, data: ~[u8]) -> ResourceTask {
+    let scheme = url.scheme.clone();
+    let data_loader_factory: LoaderTaskFactory = || {
+        let (url, data) = (url.clone(), data.clone());
+        let loader: LoaderTask = |load_data, progress_chan| {
+            if load_data.url == url {
+                progress_chan.send(Headers(Metadata::default(url.clone())));
+                progress_chan.send(Payload(data.clone()));
+                progress_chan.send(Done(Ok(())));
+            } else {
+                progress_chan.send(Done(Err(())));
+            }
+        };
+        loader
+    };
+    create_resource_task_with_loaders(~[(scheme, data_loader_factory)], None)
 }
 
-fn create_resource_task_with_loaders(loaders: ~[(~str, LoaderTaskFactory)]) -> ResourceTask {
+fn create_resource_task_with_loaders(loaders: ~[(~str, LoaderTaskFactory)],
+                                     cache_dir: Option<Path>) -> ResourceTask {
     let loaders_cell = Cell::new(loaders);
+    let cache_dir_cell = Cell::new(cache_dir);
     let chan = do spawn_listener |from_client| {
         // TODO: change copy to move once we can move out of closures
-        ResourceManager(from_client, loaders_cell.take()).start()
+        let http_cache = HttpCache::new(DEFAULT_MEMORY_LIMIT, cache_dir_cell.take());
+        let mut resource_manager = ResourceManager(from_client, loaders_cell.take(), http_cache);
+        resource_manager.start()
     };
     SharedChan::new(chan)
 }
@@ -64,24 +224,57 @@ pub struct ResourceManager {
     from_client: Port<ControlMsg>,
     /// Per-scheme resource loaders
     loaders: ~[(~str, LoaderTaskFactory)],
+    /// The cookies, shared with the tasks that watch responses for `Set-Cookie` headers
+    cookie_jar: RWArc<CookieJar>,
+    /// The HTTP cache, shared with the tasks that watch responses to store them
+    http_cache: RWArc<HttpCache>,
+    /// The limits to slow loads down to, if any
+    throttle: Option<Throttle>,
+    /// The pages that were visited
+    history: History,
 }
 
 
 pub fn ResourceManager(from_client: Port<ControlMsg>, 
-                       loaders: ~[(~str, LoaderTaskFactory)]) -> ResourceManager {
+                       loaders: ~[(~str, LoaderTaskFactory)],
+                       http_cache: HttpCache) -> ResourceManager {
     ResourceManager {
         from_client : from_client,
         loaders : loaders,
+        cookie_jar: RWArc::new(CookieJar::new()),
+        http_cache: RWArc::new(http_cache),
+        throttle: None,
+        history: History::new(),
     }
 }
 
 
 impl ResourceManager {
-    fn start(&self) {
+    fn start(&mut self) {
         loop {
             match self.from_client.recv() {
-              Load(url, progress_chan) => {
-                self.load(url.clone(), progress_chan)
+              Load(load_data, progress_chan) => {
+                self.load(load_data, progress_chan)
+              }
+              SetCookiesForUrl(url, cookies, source) => {
+                do self.cookie_jar.write |jar| {
+                    jar.set_cookie(cookies, &url, source);
+                }
+              }
+              GetCookiesForUrl(url, response_chan, source) => {
+                let cookies = do self.cookie_jar.write |jar| {
+                    jar.cookies_for_url(&url, source)
+                };
+                response_chan.send(cookies);
+              }
+              SetThrottle(throttle) => {
+                self.throttle = throttle;
+              }
+              AddVisit(url) => {
+                self.history.add(&url);
+              }
+              GetVisited(urls, response_chan) => {
+                response_chan.send(urls.map(|url| self.history.contains(url)));
               }
               Exit => {
                 break
@@ -90,20 +283,179 @@ impl ResourceManager {
         }
     }
 
-    fn load(&self, url: Url, progress_chan: Chan<ProgressMsg>) {
+    fn load(&self, load_data: LoadData, progress_chan: Chan<ProgressMsg>) {
+        let mut load_data = load_data;
+        let cross_origin = cors::is_cross_origin(&load_data);
+        let preflight = if cross_origin && cors::needs_preflight(&load_data) {
+            Some(cors::preflight_request(&load_data))
+        } else {
+            None
+        };
+        let credentials = !cross_origin || load_data.credentials;
+        let progress_chan = match load_data.origin {
+            Some(ref origin) => {
+                if cross_origin {
+                    load_data.headers.push((~"Origin", origin.to_str()));
+                }
+                cors_from(origin.clone(), load_data.credentials, progress_chan)
+            }
+            None => progress_chan,
+        };
+
+        if credentials {
+            let cookies = do self.cookie_jar.write |jar| {
+                jar.cookies_for_url(&load_data.url, HTTP)
+            };
+            match cookies {
+                Some(cookies) => load_data.headers.push((~"Cookie", cookies)),
+                None => {}
+            }
+        }
+
+        let progress_chan = if HttpCache::can_answer(&load_data) {
+            let now = time::get_time().sec;
+            let lookup = do self.http_cache.write |cache| {
+                cache.lookup(&load_data, now)
+            };
+            match lookup {
+                Fresh(response) => {
+                    debug!("resource_task: answering from cache: %s", load_data.url.to_str());
+                    replay(&response, &progress_chan);
+                    progress_chan.send(Done(Ok(())));
+                    return;
+                }
+                Stale(response) => {
+                    response.add_validators(&mut load_data);
+                    self.cache_response_from(load_data.url.clone(), Some(response), progress_chan)
+                }
+                Miss => self.cache_response_from(load_data.url.clone(), None, progress_chan),
+            }
+        } else {
+            // Anything but a GET may change what a GET of the same URL returns.
+            do self.http_cache.write |cache| {
+                cache.remove(&load_data.url);
+            }
+            progress_chan
+        };
+        let progress_chan = if credentials {
+            self.store_cookies_from(progress_chan)
+        } else {
+            progress_chan
+        };
+
+        if load_data.url.scheme == ~"http" || load_data.url.scheme == ~"https" {
+            load_data.headers.push((~"Accept-Encoding", ACCEPT_ENCODING.to_owned()));
+        }
+        let progress_chan = decode_content_from(progress_chan);
+        let progress_chan = match self.throttle {
+            Some(ref throttle) => throttle_from(throttle.clone(), progress_chan),
+            None => progress_chan,
+        };
 
-        match self.get_loader_factory(&url) {
+        match self.get_loader_factory(&load_data.url) {
             Some(loader_factory) => {
-                debug!("resource_task: loading url: %s", url.to_str());
-                loader_factory(url, progress_chan);
+                debug!("resource_task: loading url: %s", load_data.url.to_str());
+                match preflight {
+                    Some(preflight) => preflight_then(loader_factory, preflight, load_data,
+                                                      progress_chan),
+                    None => loader_factory(load_data, progress_chan),
+                }
             }
             None => {
-                debug!("resource_task: no loader for scheme %s", url.scheme);
+                debug!("resource_task: no loader for scheme %s", load_data.url.scheme);
                 progress_chan.send(Done(Err(())));
             }
         }
     }
 
+    /// Returns a channel that forwards the progress of a load to `progress_chan`, storing any
+    /// cookies the response sets along the way.
+    fn store_cookies_from(&self, progress_chan: Chan<ProgressMsg>) -> Chan<ProgressMsg> {
+        let (progress_port, intercepted_chan) = comm::stream();
+        let cookie_jar = self.cookie_jar.clone();
+        do spawn {
+            loop {
+                let msg = progress_port.recv();
+                let done = match msg {
+                    Headers(ref metadata) => {
+                        for &(ref name, ref value) in metadata.headers.iter() {
+                            if name.to_ascii_lower() == ~"set-cookie" {
+                                do cookie_jar.write |jar| {
+                                    jar.set_cookie(*value, &metadata.final_url, HTTP);
+                                }
+                            }
+                        }
+                        false
+                    }
+                    Payload(*) => false,
+                    Done(*) => true,
+                };
+                progress_chan.send(msg);
+                if done {
+                    break;
+                }
+            }
+        }
+        intercepted_chan
+    }
+
+    /// Returns a channel that forwards the progress of a GET of `url` to `progress_chan`, storing
+    /// the response in the cache when it's done. If `stale` is the cached response being
+    /// revalidated and the server says it hasn't changed, the cached response is sent instead.
+    fn cache_response_from(&self, url: Url, stale: Option<CachedResponse>,
+                           progress_chan: Chan<ProgressMsg>) -> Chan<ProgressMsg> {
+        let (progress_port, intercepted_chan) = comm::stream();
+        let http_cache = self.http_cache.clone();
+        let stale = Cell::new(stale);
+        do spawn {
+            let stale = stale.take();
+            let mut metadata = None;
+            let mut body = ~[];
+            let mut not_modified = false;
+            loop {
+                match progress_port.recv() {
+                    Headers(new_metadata) => {
+                        let now = time::get_time().sec;
+                        if stale.is_some() && new_metadata.status == 304 {
+                            let refreshed = do http_cache.write |cache| {
+                                cache.refresh(&url, &new_metadata, now)
+                            };
+                            replay(&refreshed.unwrap_or(stale.get_ref().clone()), &progress_chan);
+                            not_modified = true;
+                        } else {
+                            if HttpCache::is_storable(&new_metadata) {
+                                metadata = Some(new_metadata.clone());
+                            }
+                            progress_chan.send(Headers(new_metadata));
+                        }
+                    }
+                    Payload(data) => {
+                        // The body of a 304 is empty anyway.
+                        if !not_modified {
+                            if metadata.is_some() {
+                                body.push_all(data);
+                            }
+                            progress_chan.send(Payload(data));
+                        }
+                    }
+                    Done(result) => {
+                        if result.is_ok() && metadata.is_some() {
+                            let now = time::get_time().sec;
+                            let metadata = metadata.take_unwrap();
+                            let body = replace(&mut body, ~[]);
+                            do http_cache.write |cache| {
+                                cache.store(&url, metadata.clone(), body.clone(), now);
+                            }
+                        }
+                        progress_chan.send(Done(if not_modified { Ok(()) } else { result }));
+                        break;
+                    }
+                }
+            }
+        }
+        intercepted_chan
+    }
+
     fn get_loader_factory(&self, url: &Url) -> Option<LoaderTask> {
         for scheme_loader in self.loaders.iter() {
             match *scheme_loader {
@@ -118,6 +470,166 @@ impl ResourceManager {
     }
 }
 
+/// Returns a channel that forwards the progress of a load to `progress_chan`, decoding the body if
+/// the response has a `Content-Encoding`.
+///
+/// FIXME: Encoded bodies are decoded all at once when the load is done, rather than as they
+/// arrive, because `extra::flate` can't decode a stream incrementally.
+fn decode_content_from(progress_chan: Chan<ProgressMsg>) -> Chan<ProgressMsg> {
+    let (progress_port, intercepted_chan) = comm::stream();
+    do spawn {
+        let mut encoding = Identity;
+        let mut body = ~[];
+        loop {
+            match progress_port.recv() {
+                Headers(metadata) => {
+                    match ContentEncoding::from_metadata(&metadata) {
+                        Some(Identity) => progress_chan.send(Headers(metadata)),
+                        Some(new_encoding) => {
+                            encoding = new_encoding;
+                            progress_chan.send(Headers(decoded_metadata(metadata)));
+                        }
+                        None => {
+                            debug!("resource_task: unknown content encoding for %s",
+                                   metadata.final_url.to_str());
+                            progress_chan.send(Done(Err(())));
+                            break;
+                        }
+                    }
+                }
+                Payload(data) => {
+                    if encoding == Identity {
+                        progress_chan.send(Payload(data));
+                    } else {
+                        body.push_all_move(data);
+                    }
+                }
+                Done(Ok(())) if encoding != Identity => {
+                    match encoding.decode(replace(&mut body, ~[])) {
+                        Ok(decoded) => {
+                            progress_chan.send(Payload(decoded));
+                            progress_chan.send(Done(Ok(())));
+                        }
+                        Err(()) => progress_chan.send(Done(Err(()))),
+                    }
+                    break;
+                }
+                Done(result) => {
+                    progress_chan.send(Done(result));
+                    break;
+                }
+            }
+        }
+    }
+    intercepted_chan
+}
+
+/// Makes the preflight request `preflight` with `loader`, then the request `load_data` if the
+/// response allows it. Otherwise the load fails without the request ever being made.
+fn preflight_then(loader: LoaderTask, preflight: LoadData, load_data: LoadData,
+                  progress_chan: Chan<ProgressMsg>) {
+    let loader = Cell::new(loader);
+    let preflight = Cell::new(preflight);
+    let load_data = Cell::new(load_data);
+    let progress_chan = Cell::new(progress_chan);
+    do spawn {
+        let loader = loader.take();
+        let load_data = load_data.take();
+        let progress_chan = progress_chan.take();
+        let (preflight_port, preflight_chan) = comm::stream();
+        loader(preflight.take(), preflight_chan);
+        let mut allowed = false;
+        loop {
+            match preflight_port.recv() {
+                Headers(metadata) => allowed = cors::check_preflight(&load_data, &metadata),
+                Payload(*) => {}
+                Done(result) => {
+                    allowed = allowed && result.is_ok();
+                    break;
+                }
+            }
+        }
+        if allowed {
+            loader(load_data, progress_chan);
+        } else {
+            debug!("resource_task: preflight refused %s", load_data.url.to_str());
+            progress_chan.send(Done(Err(())));
+        }
+    }
+}
+
+/// Returns a channel that forwards the progress of a load made for a document of `origin` to
+/// `progress_chan`. A response from another origin, even after a redirect, fails the load unless
+/// it allows the origin through CORS, and then only the headers it exposes are passed on.
+fn cors_from(origin: Origin, credentials: bool, progress_chan: Chan<ProgressMsg>)
+             -> Chan<ProgressMsg> {
+    let (progress_port, intercepted_chan) = comm::stream();
+    do spawn {
+        loop {
+            let msg = match progress_port.recv() {
+                Headers(metadata) => {
+                    if origin.is_same_origin(&metadata.final_url) {
+                        Headers(metadata)
+                    } else if cors::check_response(&origin, credentials, &metadata) {
+                        Headers(cors::filter_response(metadata))
+                    } else {
+                        debug!("resource_task: %s doesn't allow %s to read it",
+                               metadata.final_url.to_str(), origin.to_str());
+                        progress_chan.send(Done(Err(())));
+                        break;
+                    }
+                }
+                msg => msg,
+            };
+            let done = match msg { Done(_) => true, _ => false };
+            progress_chan.send(msg);
+            if done {
+                break;
+            }
+        }
+    }
+    intercepted_chan
+}
+
+/// Returns a channel that forwards the progress of a load to `progress_chan`, delaying the
+/// response by the latency of `throttle` and each chunk of the body by the time it would take to
+/// arrive at its bandwidth.
+fn throttle_from(throttle: Throttle, progress_chan: Chan<ProgressMsg>) -> Chan<ProgressMsg> {
+    let (progress_port, intercepted_chan) = comm::stream();
+    do spawn {
+        let timer = Timer::new().unwrap();
+        let mut waited_for_latency = false;
+        loop {
+            let msg = progress_port.recv();
+            if !waited_for_latency {
+                timer.sleep(throttle.latency as u64);
+                waited_for_latency = true;
+            }
+            let done = match msg {
+                Payload(ref data) => {
+                    for &bandwidth in throttle.bandwidth.iter() {
+                        timer.sleep((data.len() * 1000 / bandwidth) as u64);
+                    }
+                    false
+                }
+                Headers(*) => false,
+                Done(*) => true,
+            };
+            progress_chan.send(msg);
+            if done {
+                break;
+            }
+        }
+    }
+    intercepted_chan
+}
+
+/// Sends the metadata and body of a cached response.
+fn replay(response: &CachedResponse, progress_chan: &Chan<ProgressMsg>) {
+    progress_chan.send(Headers(response.metadata.clone()));
+    progress_chan.send(Payload(response.body.clone()));
+}
+
 #[test]
 fn test_exit() {
     let resource_task = ResourceTask();
@@ -129,7 +641,7 @@ fn test_exit() {
 fn test_bad_scheme() {
     let resource_task = ResourceTask();
     let progress = Port();
-    resource_task.send(Load(url::from_str(~"bogus://whatever").get(), progress.chan()));
+    resource_task.send(Load(LoadData::new(url::from_str(~"bogus://whatever").get()), progress.chan()));
     match progress.recv() {
       Done(result) => { assert!(result.is_err()) }
       _ => fail
@@ -141,15 +653,345 @@ fn test_bad_scheme() {
 #[allow(non_implicitly_copyable_typarams)]
 fn should_delegate_to_scheme_loader() {
     let payload = ~[1, 2, 3];
-    let loader_factory = |_url: Url, progress_chan: Chan<ProgressMsg>| {
+    let loader_factory = |load_data: LoadData, progress_chan: Chan<ProgressMsg>| {
+        progress_chan.send(Headers(Metadata::default(load_data.url.clone())));
         progress_chan.send(Payload(payload.clone()));
         progress_chan.send(Done(Ok(())));
     };
     let loader_factories = ~[(~"snicklefritz", loader_factory)];
-    let resource_task = create_resource_task_with_loaders(loader_factories);
+    let resource_task = create_resource_task_with_loaders(loader_factories, None);
     let progress = Port();
-    resource_task.send(Load(url::from_str(~"snicklefritz://heya").get(), progress.chan()));
+    let url = url::from_str(~"snicklefritz://heya").get();
+    resource_task.send(Load(LoadData::new(url.clone()), progress.chan()));
+    assert!(progress.recv() == Headers(Metadata::default(url)));
     assert!(progress.recv() == Payload(payload));
     assert!(progress.recv() == Done(Ok(())));
     resource_task.send(Exit);
 }
+
+#[test]
+fn should_store_and_send_cookies() {
+    use cookie::NonHTTP;
+
+    let loader_factory = |load_data: LoadData, progress_chan: Chan<ProgressMsg>| {
+        let mut metadata = Metadata::default(load_data.url.clone());
+        metadata.headers = ~[(~"Set-Cookie", ~"id=1")];
+        progress_chan.send(Headers(metadata));
+        progress_chan.send(Done(Ok(())));
+    };
+    let loader_factories = ~[(~"snicklefritz", loader_factory)];
+    let resource_task = create_resource_task_with_loaders(loader_factories, None);
+    let url = url::from_str(~"snicklefritz://heya/").get();
+
+    let progress = Port();
+    resource_task.send(Load(LoadData::new(url.clone()), progress.chan()));
+    progress.recv();
+    assert!(progress.recv() == Done(Ok(())));
+
+    let cookies = Port();
+    resource_task.send(GetCookiesForUrl(url.clone(), cookies.chan(), HTTP));
+    assert!(cookies.recv() == Some(~"id=1"));
+    resource_task.send(SetCookiesForUrl(url.clone(), ~"id=2", NonHTTP));
+    resource_task.send(GetCookiesForUrl(url, cookies.chan(), NonHTTP));
+    assert!(cookies.recv() == Some(~"id=2"));
+    resource_task.send(Exit);
+}
+
+#[test]
+fn should_only_pass_on_cross_origin_responses_that_allow_the_origin() {
+    let loader_factory = |load_data: LoadData, progress_chan: Chan<ProgressMsg>| {
+        let mut metadata = Metadata::default(load_data.url.clone());
+        if load_data.url.path == ~"/public" {
+            metadata.headers = ~[(~"Access-Control-Allow-Origin", ~"*"), (~"X-Secret", ~"1")];
+        }
+        progress_chan.send(Headers(metadata));
+        progress_chan.send(Done(Ok(())));
+    };
+    let loader_factories = ~[(~"snicklefritz", loader_factory)];
+    let resource_task = create_resource_task_with_loaders(loader_factories, None);
+    let load = |url: &str, origin: &str, method: &str| {
+        let progress = Port();
+        let mut load_data = LoadData::new(url::from_str(url).unwrap());
+        load_data.method = method.to_owned();
+        load_data.origin = Some(Origin::new(&url::from_str(origin).unwrap()));
+        resource_task.send(Load(load_data, progress.chan()));
+        progress
+    };
+
+    let progress = load("snicklefritz://heya/private", "snicklefritz://heya/", "GET");
+    match progress.recv() {
+        Headers(*) => {}
+        _ => fail!("same-origin responses should be passed on"),
+    }
+    let progress = load("snicklefritz://heya/private", "snicklefritz://hiya/", "GET");
+    assert!(progress.recv() == Done(Err(())));
+    let progress = load("snicklefritz://heya/public", "snicklefritz://hiya/", "GET");
+    match progress.recv() {
+        Headers(metadata) => assert!(metadata.headers.is_empty()),
+        _ => fail!("responses that allow any origin should be passed on"),
+    }
+    assert!(progress.recv() == Done(Ok(())));
+    // The preflight doesn't allow the method.
+    let progress = load("snicklefritz://heya/public", "snicklefritz://hiya/", "PUT");
+    assert!(progress.recv() == Done(Err(())));
+    resource_task.send(Exit);
+}
+
+#[test]
+fn load_progress_counts_payload() {
+    let mut progress = LoadProgress::new();
+    let mut metadata = Metadata::default(url::from_str(~"file:///whatever").get());
+    metadata.content_length = Some(5);
+    progress.update(&Headers(metadata));
+    progress.update(&Payload(~[1, 2, 3]));
+    assert!(progress == LoadProgress { loaded: 3, total: Some(5) });
+    progress.update(&Payload(~[4, 5]));
+    progress.update(&Done(Ok(())));
+    assert!(progress == LoadProgress { loaded: 5, total: Some(5) });
+}
+
+#[test]
+fn should_decode_content_encodings() {
+    use extra::flate;
+
+    let loader_factory = |load_data: LoadData, progress_chan: Chan<ProgressMsg>| {
+        let mut metadata = Metadata::default(load_data.url.clone());
+        metadata.headers = ~[(~"Content-Encoding", ~"deflate")];
+        progress_chan.send(Headers(metadata));
+        let body = flate::deflate_bytes(bytes!("hello"));
+        let middle = body.len() / 2;
+        progress_chan.send(Payload(body.slice_to(middle).to_owned()));
+        progress_chan.send(Payload(body.slice_from(middle).to_owned()));
+        progress_chan.send(Done(Ok(())));
+    };
+    let loader_factories = ~[(~"snicklefritz", loader_factory)];
+    let resource_task = create_resource_task_with_loaders(loader_factories, None);
+    let url = url::from_str(~"snicklefritz://heya/").get();
+
+    let progress = Port();
+    resource_task.send(Load(LoadData::new(url.clone()), progress.chan()));
+    match progress.recv() {
+        Headers(metadata) => assert!(metadata.headers.is_empty()),
+        _ => fail!(~"expected headers"),
+    }
+    assert!(progress.recv() == Payload(bytes!("hello").to_owned()));
+    assert!(progress.recv() == Done(Ok(())));
+    resource_task.send(Exit);
+}
+
+#[test]
+fn should_deliver_throttled_loads_intact() {
+    let loader_factory = |load_data: LoadData, progress_chan: Chan<ProgressMsg>| {
+        progress_chan.send(Headers(Metadata::default(load_data.url.clone())));
+        progress_chan.send(Payload(~[1, 2, 3]));
+        progress_chan.send(Payload(~[4, 5]));
+        progress_chan.send(Done(Ok(())));
+    };
+    let loader_factories = ~[(~"snicklefritz", loader_factory)];
+    let resource_task = create_resource_task_with_loaders(loader_factories, None);
+    let url = url::from_str(~"snicklefritz://heya/").get();
+
+    resource_task.send(SetThrottle(Some(Throttle { latency: 10, bandwidth: Some(1000) })));
+    let progress = Port();
+    resource_task.send(Load(LoadData::new(url.clone()), progress.chan()));
+    assert!(progress.recv() == Headers(Metadata::default(url)));
+    assert!(progress.recv() == Payload(~[1, 2, 3]));
+    assert!(progress.recv() == Payload(~[4, 5]));
+    assert!(progress.recv() == Done(Ok(())));
+    resource_task.send(Exit);
+}
+
+#[test]
+fn should_answer_fresh_responses_from_cache() {
+    let (loads_port, loads_chan) = comm::stream();
+    let loads_chan = SharedChan::new(loads_chan);
+    let loader_factory = |load_data: LoadData, progress_chan: Chan<ProgressMsg>| {
+        loads_chan.send(());
+        let mut metadata = Metadata::default(load_data.url.clone());
+        metadata.headers = ~[(~"Cache-Control", ~"max-age=60")];
+        progress_chan.send(Headers(metadata));
+        progress_chan.send(Payload(~[1, 2, 3]));
+        progress_chan.send(Done(Ok(())));
+    };
+    let loader_factories = ~[(~"snicklefritz", loader_factory)];
+    let resource_task = create_resource_task_with_loaders(loader_factories, None);
+    let url = url::from_str(~"snicklefritz://heya/").get();
+
+    for _ in range(0, 2) {
+        let progress = Port();
+        resource_task.send(Load(LoadData::new(url.clone()), progress.chan()));
+        progress.recv();
+        assert!(progress.recv() == Payload(~[1, 2, 3]));
+        assert!(progress.recv() == Done(Ok(())));
+    }
+
+    loads_port.recv();
+    assert!(!loads_port.peek());
+    resource_task.send(Exit);
+}
+
+/// Serves `responses` over HTTP on a port of the loopback interface, one to each connection in
+/// turn, sending the head of each request it answers back on the port returned with its URL.
+#[cfg(test)]
+fn serve_http(responses: ~[~[u8]]) -> (Url, Port<~str>) {
+    use std::rt::io::{Acceptor, Listener, io_error};
+    use std::rt::io::net::ip::{Ipv4Addr, SocketAddr};
+    use std::rt::io::net::tcp::TcpListener;
+    use std::str;
+    use extra::url;
+    use util::{read_exact, write_all};
+
+    // Another test may be listening already, so look for a free port.
+    let mut port = 47000u16;
+    let mut acceptor = None;
+    while acceptor.is_none() {
+        let address = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: port };
+        acceptor = do io_error::cond.trap(|_| ()).inside {
+            TcpListener::bind(address).chain(|listener| listener.listen())
+        };
+        if acceptor.is_none() {
+            port += 1;
+        }
+    }
+    let acceptor = Cell::new(acceptor.unwrap());
+    let responses = Cell::new(responses);
+    let (requests_port, requests_chan) = comm::stream();
+    do spawn {
+        let mut acceptor = acceptor.take();
+        for response in responses.take().move_iter() {
+            let mut stream = acceptor.accept().unwrap();
+            let mut request = ~[];
+            let mut byte = [0u8];
+            while request.len() < 4 || request.slice_from(request.len() - 4) != bytes!("\r\n\r\n") {
+                if read_exact(&mut stream, byte).is_err() {
+                    break
+                }
+                request.push(byte[0]);
+            }
+            requests_chan.send(str::from_utf8(request));
+            write_all(&mut stream, response);
+        }
+    }
+    (url::from_str(fmt!("http://127.0.0.1:%u/", port as uint)).unwrap(), requests_port)
+}
+
+/// Makes a load with `resource_task`, returning the metadata and body of the response if the load
+/// succeeds.
+#[cfg(test)]
+fn load_all(resource_task: &ResourceTask, load_data: LoadData) -> Option<(Metadata, ~[u8])> {
+    let (progress, progress_chan) = comm::stream();
+    resource_task.send(Load(load_data, progress_chan));
+    let mut metadata = None;
+    let mut body = ~[];
+    loop {
+        match progress.recv() {
+            Headers(new_metadata) => metadata = Some(new_metadata),
+            Payload(data) => body.push_all_move(data),
+            Done(Ok(())) => return Some((metadata.unwrap(), body)),
+            Done(Err(())) => return None,
+        }
+    }
+}
+
+#[test]
+fn should_store_and_send_cookies_over_http() {
+    let (url, requests) = serve_http(~[
+        bytes!("HTTP/1.1 200 OK\r\nSet-Cookie: id=1\r\n\r\n").to_owned(),
+        bytes!("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").to_owned(),
+    ]);
+    let resource_task = ResourceTask();
+    assert!(load_all(&resource_task, LoadData::new(url.clone())).is_some());
+    assert!(load_all(&resource_task, LoadData::new(url)).is_some());
+    assert!(!requests.recv().contains("Cookie:"));
+    assert!(requests.recv().contains("\r\nCookie: id=1\r\n"));
+    resource_task.send(Exit);
+}
+
+#[test]
+fn should_revalidate_stale_responses_over_http() {
+    let (url, requests) = serve_http(~[
+        bytes!("HTTP/1.1 200 OK\r\n\
+                Cache-Control: no-cache\r\n\
+                ETag: \"v1\"\r\n\
+                Content-Length: 5\r\n\r\n\
+                hello").to_owned(),
+        bytes!("HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\n\r\n").to_owned(),
+    ]);
+    let resource_task = ResourceTask();
+    for _ in range(0, 2) {
+        let (metadata, body) = load_all(&resource_task, LoadData::new(url.clone())).unwrap();
+        assert!(metadata.status == 200);
+        assert!(body == bytes!("hello").to_owned());
+    }
+    assert!(!requests.recv().contains("If-None-Match:"));
+    assert!(requests.recv().contains("\r\nIf-None-Match: \"v1\"\r\n"));
+    resource_task.send(Exit);
+}
+
+#[test]
+fn should_decode_chunked_and_encoded_bodies_over_http() {
+    use extra::flate;
+
+    let body = flate::deflate_bytes(bytes!("hello"));
+    let middle = body.len() / 2;
+    let mut response = bytes!("HTTP/1.1 200 OK\r\n\
+                               Content-Encoding: deflate\r\n\
+                               Transfer-Encoding: chunked\r\n\r\n").to_owned();
+    for chunk in [body.slice_to(middle), body.slice_from(middle)].iter() {
+        response.push_all(fmt!("%x\r\n", chunk.len()).as_bytes());
+        response.push_all(*chunk);
+        response.push_all(bytes!("\r\n"));
+    }
+    response.push_all(bytes!("0\r\n\r\n"));
+    let (url, requests) = serve_http(~[response]);
+    let resource_task = ResourceTask();
+    let (metadata, body) = load_all(&resource_task, LoadData::new(url)).unwrap();
+    assert!(body == bytes!("hello").to_owned());
+    assert!(!metadata.headers.iter().any(|&(ref name, _)| *name == ~"Content-Encoding"));
+    let accept_encoding = fmt!("\r\nAccept-Encoding: %s\r\n", ACCEPT_ENCODING);
+    assert!(requests.recv().contains(accept_encoding.as_slice()));
+    resource_task.send(Exit);
+}
+
+#[test]
+fn should_check_cross_origin_responses_over_http() {
+    use extra::url;
+
+    let (url, requests) = serve_http(~[
+        bytes!("HTTP/1.1 200 OK\r\n\
+                Access-Control-Allow-Origin: *\r\n\
+                X-Secret: 1\r\n\
+                Content-Length: 0\r\n\r\n").to_owned(),
+        bytes!("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").to_owned(),
+        bytes!("HTTP/1.1 200 OK\r\n\
+                Access-Control-Allow-Origin: http://example.com\r\n\
+                Access-Control-Allow-Methods: PUT\r\n\
+                Content-Length: 0\r\n\r\n").to_owned(),
+        bytes!("HTTP/1.1 201 Created\r\n\
+                Access-Control-Allow-Origin: http://example.com\r\n\
+                Content-Length: 0\r\n\r\n").to_owned(),
+    ]);
+    let resource_task = ResourceTask();
+    let load_data = |method: &str| {
+        let mut load_data = LoadData::new(url.clone());
+        load_data.method = method.to_owned();
+        load_data.origin = Some(Origin::new(&url::from_str("http://example.com/").unwrap()));
+        load_data
+    };
+
+    // Only the headers the server exposes are passed on.
+    let (metadata, _) = load_all(&resource_task, load_data("GET")).unwrap();
+    assert!(metadata.headers.is_empty());
+    assert!(requests.recv().contains("\r\nOrigin: http://example.com\r\n"));
+    // Responses that don't allow the origin fail the load.
+    assert!(load_all(&resource_task, load_data("GET")).is_none());
+    requests.recv();
+    // Other methods are only used once a preflight allows them.
+    let (metadata, _) = load_all(&resource_task, load_data("PUT")).unwrap();
+    assert!(metadata.status == 201);
+    let preflight = requests.recv();
+    assert!(preflight.starts_with("OPTIONS / HTTP/1.1\r\n"));
+    assert!(preflight.contains("\r\nAccess-Control-Request-Method: PUT\r\n"));
+    assert!(requests.recv().starts_with("PUT / HTTP/1.1\r\n"));
+    resource_task.send(Exit);
+}
@@ -4,7 +4,12 @@
 
 use std::comm;
 use std::comm::{Chan, Port};
+use std::rt::io::{Reader, Writer, io_error};
+use std::rt::io::net::get_host_addresses;
+use std::rt::io::net::ip::{IpAddr, SocketAddr};
+use std::rt::io::net::tcp::TcpStream;
 use std::task;
+use extra::url::Url;
 
 pub fn spawn_listener<A: Send>(f: ~fn(Port<A>)) -> Chan<A> {
     let (setup_port, setup_chan) = comm::stream();
@@ -15,3 +20,52 @@ pub fn spawn_listener<A: Send>(f: ~fn(Port<A>)) -> Chan<A> {
     }
     setup_port.recv()
 }
+
+/// Opens a TCP connection to the host of `url`, trying each of its addresses in turn.
+pub fn open_stream(url: &Url) -> Option<TcpStream> {
+    let port = match url.port {
+        Some(ref port) => match from_str::<u16>(*port) {
+            Some(port) => port,
+            None => return None,
+        },
+        None => 80,
+    };
+    let addresses = match from_str::<IpAddr>(url.host) {
+        Some(address) => ~[address],
+        None => get_host_addresses(url.host).unwrap_or(~[]),
+    };
+    for &address in addresses.iter() {
+        let stream = do io_error::cond.trap(|_| ()).inside {
+            TcpStream::connect(SocketAddr { ip: address, port: port })
+        };
+        if stream.is_some() {
+            return stream
+        }
+    }
+    None
+}
+
+/// Reads exactly enough bytes to fill `buf`. Fails if the stream ends or errs first.
+pub fn read_exact<R: Reader>(reader: &mut R, buf: &mut [u8]) -> Result<(), ()> {
+    let mut count = 0;
+    while count < buf.len() {
+        let mut errored = false;
+        let read = do io_error::cond.trap(|_| errored = true).inside {
+            reader.read(buf.mut_slice_from(count))
+        };
+        match read {
+            Some(read) if !errored => count += read,
+            _ => return Err(()),
+        }
+    }
+    Ok(())
+}
+
+/// Writes all of `data`. Fails if the stream errs.
+pub fn write_all<W: Writer>(writer: &mut W, data: &[u8]) -> Result<(), ()> {
+    let mut errored = false;
+    do io_error::cond.trap(|_| errored = true).inside {
+        writer.write(data);
+    }
+    if errored { Err(()) } else { Ok(()) }
+}
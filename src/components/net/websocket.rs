@@ -0,0 +1,548 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A WebSocket client (RFC 6455). `connect` opens the connection and does the opening handshake
+//! on a task of its own, then passes on what the server sends as `WebSocketEvent`s and sends what
+//! it's asked to with `WebSocketMsg`s. Pings are answered as they arrive.
+//!
+//! FIXME: Only `ws:` URLs can be connected to, since there's no TLS for `wss:`. No extensions are
+//! negotiated, and cookies aren't sent with the handshake.
+
+use servo_util::url::Origin;
+use util::{open_stream, read_exact, write_all};
+
+use std::ascii::StrAsciiExt;
+use std::cell::Cell;
+use std::comm;
+use std::comm::{Chan, Port, SharedChan};
+use std::rand;
+use std::rand::Rng;
+use std::rt::io::Reader;
+use std::rt::io::net::tcp::TcpStream;
+use std::str;
+use std::unstable::sync::UnsafeArc;
+use std::vec;
+use extra::base64::{STANDARD, ToBase64};
+use extra::digest::Digest;
+use extra::sha1::Sha1;
+use extra::url::{Url, query_to_str};
+
+/// Appended to the key of the opening handshake to make the one the server must answer with.
+static ACCEPT_GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The longest response to the opening handshake that is read, in bytes.
+static MAX_HANDSHAKE_LENGTH: uint = 8192;
+
+/// The opcodes of frames.
+static CONTINUATION: u8 = 0x0;
+static TEXT: u8 = 0x1;
+static BINARY: u8 = 0x2;
+static CLOSE: u8 = 0x8;
+static PING: u8 = 0x9;
+static PONG: u8 = 0xA;
+
+/// Status codes for closing a connection (RFC 6455 § 7.4.1).
+pub static NORMAL_CLOSURE: u16 = 1000;
+pub static GOING_AWAY: u16 = 1001;
+pub static PROTOCOL_ERROR: u16 = 1002;
+pub static UNSUPPORTED_DATA: u16 = 1003;
+/// Reported when a close frame has no status code. Never sent.
+pub static NO_STATUS_RECEIVED: u16 = 1005;
+/// Reported when the connection went away without a close frame. Never sent.
+pub static ABNORMAL_CLOSURE: u16 = 1006;
+pub static INVALID_FRAME_PAYLOAD_DATA: u16 = 1007;
+
+/// Messages to a connection.
+pub enum WebSocketMsg {
+    /// Sends a text message.
+    SendText(~str),
+    /// Sends a binary message.
+    SendBinary(~[u8]),
+    /// Starts the closing handshake, with a status code and reason for the server. Once it has
+    /// been started, nothing more is sent.
+    Close(Option<u16>, ~str),
+    /// Answers a ping from the server. Only the connection sends this to itself.
+    SendPong(~[u8]),
+    /// Stops sending, as the connection is closed. Only the connection sends this to itself.
+    Shutdown,
+}
+
+/// What happens to a connection.
+#[deriving(Eq)]
+pub enum WebSocketEvent {
+    /// The opening handshake succeeded, with the subprotocol the server chose, if any.
+    Opened(Option<~str>),
+    /// A text message arrived.
+    TextMessage(~str),
+    /// A binary message arrived.
+    BinaryMessage(~[u8]),
+    /// The connection failed, either because it couldn't be opened or because the server broke
+    /// the protocol. `Closed` follows.
+    Failed,
+    /// The connection is closed, whether cleanly with a closing handshake, and with what status
+    /// code and reason. Nothing follows.
+    Closed(bool, u16, ~str),
+}
+
+/// A frame, with its payload unmasked.
+#[deriving(Eq)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: ~[u8],
+}
+
+/// Opens a connection to `url` for a document of `origin`, asking the server for one of
+/// `protocols`. What happens to it is sent on `events`.
+pub fn connect(url: Url, protocols: ~[~str], origin: Origin, events: Chan<WebSocketEvent>)
+               -> SharedChan<WebSocketMsg> {
+    let (port, chan) = comm::stream();
+    let chan = SharedChan::new(chan);
+    let writer_chan = Cell::new(chan.clone());
+    let port = Cell::new(port);
+    let url = Cell::new(url);
+    let protocols = Cell::new(protocols);
+    let origin = Cell::new(origin);
+    let events = Cell::new(events);
+    do spawn {
+        let events = events.take();
+        match open(url.take(), protocols.take(), origin.take()) {
+            Ok((stream, protocol)) => {
+                events.send(Opened(protocol));
+                run(stream, port.take(), writer_chan.take(), events);
+            }
+            Err(()) => {
+                events.send(Failed);
+                events.send(Closed(false, ABNORMAL_CLOSURE, ~""));
+            }
+        }
+    }
+    chan
+}
+
+/// Connects to `url` and does the opening handshake, returning the stream and the subprotocol the
+/// server chose.
+fn open(url: Url, protocols: ~[~str], origin: Origin) -> Result<(TcpStream, Option<~str>), ()> {
+    if url.scheme != ~"ws" {
+        debug!("websocket: can't connect to %s", url.to_str());
+        return Err(())
+    }
+    let mut stream = match open_stream(&url) {
+        Some(stream) => stream,
+        None => {
+            debug!("websocket: can't connect to %s", url.to_str());
+            return Err(())
+        }
+    };
+
+    let key = rand::rng().gen_bytes(16).to_base64(STANDARD);
+    let request = handshake_request(&url, key.as_slice(), protocols, &origin);
+    if write_all(&mut stream, request.as_bytes()).is_err() {
+        return Err(())
+    }
+    let response = match read_handshake_response(&mut stream) {
+        Ok(response) => response,
+        Err(()) => return Err(()),
+    };
+    match check_handshake_response(response.as_slice(), key.as_slice(), protocols) {
+        Ok(protocol) => Ok((stream, protocol)),
+        Err(()) => {
+            debug!("websocket: %s refused the handshake", url.to_str());
+            Err(())
+        }
+    }
+}
+
+/// Sends what script asks to on `stream`, while another task reads what the server sends.
+///
+/// FIXME: rt::io has no way to split a stream into halves that can be used from different tasks,
+/// so both share it unsafely. Each only ever reads, or only ever writes.
+fn run(stream: TcpStream, port: Port<WebSocketMsg>, chan: SharedChan<WebSocketMsg>,
+       events: Chan<WebSocketEvent>) {
+    let stream = UnsafeArc::new(stream);
+    let reader_stream = Cell::new(stream.clone());
+    let events = Cell::new(events);
+    do spawn {
+        let reader_stream = reader_stream.take();
+        let stream = unsafe { &mut *reader_stream.get() };
+        receive(stream, &chan, &events.take());
+    }
+
+    let stream = unsafe { &mut *stream.get() };
+    let mut close_sent = false;
+    loop {
+        let frame = match port.recv() {
+            SendText(text) => (TEXT, text.as_bytes().to_owned()),
+            SendBinary(data) => (BINARY, data),
+            SendPong(data) => (PONG, data),
+            Close(code, reason) => {
+                let mut payload = ~[];
+                for &code in code.iter() {
+                    payload.push((code >> 8) as u8);
+                    payload.push(code as u8);
+                    payload.push_all(reason.as_bytes());
+                }
+                (CLOSE, payload)
+            }
+            Shutdown => break,
+        };
+        // Nothing may follow a close frame.
+        if close_sent {
+            loop
+        }
+        let (opcode, payload) = frame;
+        close_sent = opcode == CLOSE;
+        let mask = rand::rng().gen_bytes(4);
+        if write_all(stream, encode_frame(opcode, payload, mask)).is_err() {
+            break
+        }
+    }
+}
+
+/// Reads what the server sends on `stream` until the connection is closed, passing messages on to
+/// `events` and answering pings and close frames through `chan`.
+fn receive(stream: &mut TcpStream, chan: &SharedChan<WebSocketMsg>,
+           events: &Chan<WebSocketEvent>) {
+    // The opcode and payload so far of a message that arrives in more than one frame.
+    let mut message: Option<(u8, ~[u8])> = None;
+    loop {
+        let Frame { fin, opcode, payload } = match read_frame(stream) {
+            Ok(frame) => frame,
+            Err(error) => {
+                fail_connection(chan, events, error);
+                return
+            }
+        };
+        match opcode {
+            TEXT | BINARY | CONTINUATION => {
+                let (opcode, payload) = match (opcode, message.take()) {
+                    (CONTINUATION, Some((opcode, first_payload))) => {
+                        let mut first_payload = first_payload;
+                        first_payload.push_all_move(payload);
+                        (opcode, first_payload)
+                    }
+                    (CONTINUATION, None) | (_, Some(_)) => {
+                        fail_connection(chan, events, PROTOCOL_ERROR);
+                        return
+                    }
+                    (opcode, None) => (opcode, payload),
+                };
+                if !fin {
+                    message = Some((opcode, payload));
+                } else if opcode == BINARY {
+                    events.send(BinaryMessage(payload));
+                } else if str::is_utf8(payload) {
+                    events.send(TextMessage(str::from_utf8(payload)));
+                } else {
+                    fail_connection(chan, events, INVALID_FRAME_PAYLOAD_DATA);
+                    return
+                }
+            }
+            PING => chan.send(SendPong(payload)),
+            PONG => {}
+            CLOSE => {
+                let (code, reason) = parse_close_payload(payload);
+                // Echo the status code back, unless this answers a close frame of ours.
+                chan.send(Close(Some(code.unwrap_or(NORMAL_CLOSURE)), ~""));
+                chan.send(Shutdown);
+                events.send(Closed(true, code.unwrap_or(NO_STATUS_RECEIVED), reason));
+                return
+            }
+            _ => {
+                fail_connection(chan, events, PROTOCOL_ERROR);
+                return
+            }
+        }
+    }
+}
+
+/// Fails the connection, telling the server why if it's still there.
+fn fail_connection(chan: &SharedChan<WebSocketMsg>, events: &Chan<WebSocketEvent>, code: u16) {
+    if code != ABNORMAL_CLOSURE {
+        chan.send(Close(Some(code), ~""));
+    }
+    chan.send(Shutdown);
+    events.send(Failed);
+    events.send(Closed(false, ABNORMAL_CLOSURE, ~""));
+}
+
+/// The opening handshake request for `url`, with the random `key` the server has to answer.
+fn handshake_request(url: &Url, key: &str, protocols: &[~str], origin: &Origin) -> ~str {
+    let mut resource = if url.path.is_empty() { ~"/" } else { url.path.clone() };
+    if !url.query.is_empty() {
+        resource.push_str(fmt!("?%s", query_to_str(&url.query)));
+    }
+    let host = match url.port {
+        Some(ref port) => fmt!("%s:%s", url.host, *port),
+        None => url.host.clone(),
+    };
+    let mut request = fmt!("GET %s HTTP/1.1\r\n", resource);
+    request.push_str(fmt!("Host: %s\r\n", host));
+    request.push_str("Upgrade: websocket\r\n");
+    request.push_str("Connection: Upgrade\r\n");
+    request.push_str(fmt!("Sec-WebSocket-Key: %s\r\n", key));
+    request.push_str("Sec-WebSocket-Version: 13\r\n");
+    request.push_str(fmt!("Origin: %s\r\n", origin.to_str()));
+    if !protocols.is_empty() {
+        request.push_str(fmt!("Sec-WebSocket-Protocol: %s\r\n", protocols.connect(", ")));
+    }
+    request.push_str("\r\n");
+    request
+}
+
+/// What the server has to answer the opening handshake with `key` with.
+fn accept_key(key: &str) -> ~str {
+    let mut sha = Sha1::new();
+    sha.input_str(key);
+    sha.input_str(ACCEPT_GUID);
+    let mut digest = [0u8, ..20];
+    sha.result(digest);
+    digest.to_base64(STANDARD)
+}
+
+/// Reads the status line and headers of the response to the opening handshake.
+fn read_handshake_response<R: Reader>(reader: &mut R) -> Result<~str, ()> {
+    let mut response = ~[];
+    let mut byte = [0u8];
+    while response.len() < 4 || response.slice_from(response.len() - 4) != bytes!("\r\n\r\n") {
+        if response.len() >= MAX_HANDSHAKE_LENGTH || read_exact(reader, byte).is_err() {
+            return Err(())
+        }
+        response.push(byte[0]);
+    }
+    if str::is_utf8(response) { Ok(str::from_utf8(response)) } else { Err(()) }
+}
+
+/// Checks that `response` accepts the opening handshake with `key`, returning the subprotocol
+/// the server chose from `protocols`, if any.
+fn check_handshake_response(response: &str, key: &str, protocols: &[~str])
+                            -> Result<Option<~str>, ()> {
+    let mut lines = response.split_str_iter("\r\n");
+    let status_line = lines.next().unwrap_or("");
+    let status: ~[&str] = status_line.split_iter(' ').collect();
+    if status.len() < 2 || !status[0].starts_with("HTTP/") || status[1] != "101" {
+        return Err(())
+    }
+
+    let mut headers = ~[];
+    for line in lines {
+        match line.find(':') {
+            Some(index) => {
+                headers.push((line.slice_to(index).trim().to_ascii_lower(),
+                              line.slice_from(index + 1).trim().to_owned()));
+            }
+            None => {}
+        }
+    }
+    let header = |name: &str| -> Option<~str> {
+        headers.iter().find(|&&(ref header, _)| header.as_slice() == name).map(|&(_, ref value)| {
+            value.clone()
+        })
+    };
+
+    let upgrade = header("upgrade").map_default(false, |value| {
+        value.to_ascii_lower() == ~"websocket"
+    });
+    let connection = header("connection").map_default(false, |value| {
+        value.split_iter(',').any(|token| "upgrade" == token.trim().to_ascii_lower())
+    });
+    let accepted = header("sec-websocket-accept") == Some(accept_key(key));
+    if !upgrade || !connection || !accepted || header("sec-websocket-extensions").is_some() {
+        return Err(())
+    }
+    match header("sec-websocket-protocol") {
+        Some(protocol) => {
+            if protocols.iter().any(|requested| *requested == protocol) {
+                Ok(Some(protocol))
+            } else {
+                Err(())
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+/// Makes a frame of a whole message, masked with `mask` as frames from clients have to be.
+fn encode_frame(opcode: u8, payload: &[u8], mask: &[u8]) -> ~[u8] {
+    let mut frame = ~[0x80 | opcode];
+    let length = payload.len();
+    if length < 126 {
+        frame.push(0x80 | length as u8);
+    } else if length < 65536 {
+        frame.push(0x80 | 126);
+        frame.push((length >> 8) as u8);
+        frame.push(length as u8);
+    } else {
+        frame.push(0x80 | 127);
+        for shift in range(0u64, 8).invert() {
+            frame.push(((length as u64) >> (shift * 8)) as u8);
+        }
+    }
+    frame.push_all(mask);
+    for (index, &byte) in payload.iter().enumerate() {
+        frame.push(byte ^ mask[index % 4]);
+    }
+    frame
+}
+
+/// Reads a frame from the server. Fails with the status code to close the connection with if
+/// the frame breaks the protocol, or `ABNORMAL_CLOSURE` if the connection went away.
+fn read_frame<R: Reader>(reader: &mut R) -> Result<Frame, u16> {
+    let mut header = [0u8, ..2];
+    if read_exact(reader, header).is_err() {
+        return Err(ABNORMAL_CLOSURE)
+    }
+    let fin = header[0] & 0x80 != 0;
+    let reserved = header[0] & 0x70;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    // Servers must not mask their frames, and no extensions that use the reserved bits were
+    // negotiated.
+    if reserved != 0 || masked {
+        return Err(PROTOCOL_ERROR)
+    }
+
+    let length = match header[1] & 0x7F {
+        126 => {
+            let mut length = [0u8, ..2];
+            if read_exact(reader, length).is_err() {
+                return Err(ABNORMAL_CLOSURE)
+            }
+            (length[0] as u64 << 8) | length[1] as u64
+        }
+        127 => {
+            let mut length = [0u8, ..8];
+            if read_exact(reader, length).is_err() {
+                return Err(ABNORMAL_CLOSURE)
+            }
+            length.iter().fold(0u64, |length, &byte| (length << 8) | byte as u64)
+        }
+        length => length as u64,
+    };
+    // Control frames can't be fragmented, and have short payloads.
+    if opcode >= CLOSE && (!fin || length > 125) {
+        return Err(PROTOCOL_ERROR)
+    }
+    if length > (1 << 31) {
+        return Err(UNSUPPORTED_DATA)
+    }
+
+    let mut payload = vec::from_elem(length as uint, 0u8);
+    if read_exact(reader, payload).is_err() {
+        return Err(ABNORMAL_CLOSURE)
+    }
+    Ok(Frame {
+        fin: fin,
+        opcode: opcode,
+        payload: payload,
+    })
+}
+
+/// The status code and reason in the payload of a close frame.
+fn parse_close_payload(payload: &[u8]) -> (Option<u16>, ~str) {
+    if payload.len() < 2 {
+        return (None, ~"")
+    }
+    let code = (payload[0] as u16 << 8) | payload[1] as u16;
+    let reason = payload.slice_from(2);
+    let reason = if str::is_utf8(reason) { str::from_utf8(reason) } else { ~"" };
+    (Some(code), reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ABNORMAL_CLOSURE, BINARY, CLOSE, CONTINUATION, Frame, PING, PROTOCOL_ERROR, TEXT};
+    use super::{accept_key, check_handshake_response, encode_frame, handshake_request};
+    use super::{parse_close_payload, read_frame};
+    use servo_util::url::Origin;
+    use std::rt::io::mem::MemReader;
+    use extra::url;
+
+    fn frame(bytes: ~[u8]) -> Result<Frame, u16> {
+        read_frame(&mut MemReader::new(bytes))
+    }
+
+    #[test]
+    fn should_compute_the_accept_key() {
+        // The example from RFC 6455 § 1.3.
+        assert!(accept_key("dGhlIHNhbXBsZSBub25jZQ==") == ~"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn should_request_an_upgrade() {
+        let url = url::from_str("ws://example.com:8000/chat?room=1").unwrap();
+        let origin = Origin::new(&url::from_str("http://example.com/").unwrap());
+        let request = handshake_request(&url, "key", [~"chat"], &origin);
+        assert!(request == ~"GET /chat?room=1 HTTP/1.1\r\n\
+                             Host: example.com:8000\r\n\
+                             Upgrade: websocket\r\n\
+                             Connection: Upgrade\r\n\
+                             Sec-WebSocket-Key: key\r\n\
+                             Sec-WebSocket-Version: 13\r\n\
+                             Origin: http://example.com\r\n\
+                             Sec-WebSocket-Protocol: chat\r\n\r\n");
+    }
+
+    #[test]
+    fn should_check_the_handshake_response() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let response = ~"HTTP/1.1 101 Switching Protocols\r\n\
+                         Upgrade: websocket\r\n\
+                         Connection: Upgrade\r\n\
+                         Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n";
+        assert!(check_handshake_response(response.as_slice(), key, []) == Ok(None));
+        let with_protocol = response + "Sec-WebSocket-Protocol: chat\r\n";
+        let chat = check_handshake_response(with_protocol.as_slice(), key, [~"chat"]);
+        assert!(chat == Ok(Some(~"chat")));
+        assert!(check_handshake_response(with_protocol.as_slice(), key, [~"superchat"]).is_err());
+        assert!(check_handshake_response(response.as_slice(), "other key", []).is_err());
+        let refused = "HTTP/1.1 403 Forbidden\r\n";
+        assert!(check_handshake_response(refused, key, []).is_err());
+    }
+
+    #[test]
+    fn should_mask_frames() {
+        let frame = encode_frame(TEXT, bytes!("Hello"), [0x37, 0xfa, 0x21, 0x3d]);
+        // The example from RFC 6455 § 5.7.
+        assert!(frame == ~[0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]);
+        let frame = encode_frame(BINARY, [0, ..256], [0, 0, 0, 0]);
+        assert!(frame.slice_to(4) == [0x82, 0x80 | 126, 0x01, 0x00]);
+        assert!(frame.len() == 4 + 4 + 256);
+    }
+
+    #[test]
+    fn should_read_frames() {
+        assert!(frame(~[0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]) == Ok(Frame {
+            fin: true,
+            opcode: TEXT,
+            payload: bytes!("Hello").to_owned(),
+        }));
+        assert!(frame(~[0x01, 0x03, 0x48, 0x65, 0x6c]) == Ok(Frame {
+            fin: false,
+            opcode: TEXT,
+            payload: bytes!("Hel").to_owned(),
+        }));
+        assert!(frame(~[0x80, 0x02, 0x6c, 0x6f]).map(|frame| frame.opcode) == Ok(CONTINUATION));
+        let mut long = ~[0x82, 126, 0x01, 0x00];
+        long.push_all([7, ..256]);
+        assert!(frame(long).map(|frame| frame.payload.len()) == Ok(256));
+    }
+
+    #[test]
+    fn should_refuse_frames_that_break_the_protocol() {
+        // Masked.
+        assert!(frame(~[0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f]) == Err(PROTOCOL_ERROR));
+        // A fragmented ping.
+        assert!(frame(~[PING, 0x00]) == Err(PROTOCOL_ERROR));
+        // Cut short.
+        assert!(frame(~[0x81, 0x05, 0x48]) == Err(ABNORMAL_CLOSURE));
+    }
+
+    #[test]
+    fn should_parse_close_frames() {
+        assert!(parse_close_payload([0x03, 0xe8, 0x62, 0x79, 0x65]) == (Some(1000), ~"bye"));
+        assert!(parse_close_payload([]) == (None, ~""));
+        assert!(frame(~[0x88, 0x00]).map(|frame| frame.opcode) == Ok(CLOSE));
+    }
+}
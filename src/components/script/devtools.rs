@@ -0,0 +1,113 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Answers the requests of the developer tools server about a page. Nodes are picked out by their
+//! index in a preorder walk of the document, so the ids `document_info` gives hold until the
+//! document changes.
+
+use dom::bindings::console;
+use dom::node::{AbstractNode, Comment, CommentNodeTypeId, Doctype, DoctypeNodeTypeId};
+use dom::node::{DocumentFragmentNodeTypeId, ElementNodeTypeId, ScriptView, TextNodeTypeId};
+use layout_interface::{ComputedStyleQuery, ComputedStyleResponse};
+use script_task::Page;
+use servo_msg::devtools_msg::NodeInfo;
+
+use js::JSVAL_NULL;
+use js::jsapi::JS_EvaluateUCScript;
+use servo_util::tree::TreeNodeRef;
+use std::libc::c_uint;
+
+/// The tree of the page's document, if it has one yet.
+pub fn document_info(page: &mut Page) -> Option<NodeInfo> {
+    do root(page).map |&root| {
+        let mut next_id = 0;
+        node_info(root, &mut next_id)
+    }
+}
+
+/// Describes `node` and the nodes under it, numbering them from `next_id` on.
+fn node_info(node: AbstractNode<ScriptView>, next_id: &mut uint) -> NodeInfo {
+    let id = *next_id;
+    *next_id += 1;
+    let (node_type, name, value, attributes) = match node.type_id() {
+        ElementNodeTypeId(_) => {
+            do node.with_imm_element |element| {
+                let attributes = do element.attrs.iter().map |attr| {
+                    (attr.name.clone(), attr.value.clone())
+                }.collect();
+                (1, element.tag_name.clone(), None, attributes)
+            }
+        }
+        TextNodeTypeId => {
+            let data = node.with_imm_text(|text| text.parent.data.to_str());
+            (3, ~"#text", Some(data), ~[])
+        }
+        CommentNodeTypeId => {
+            let data = node.transmute(|comment: &Comment| comment.parent.data.to_str());
+            (8, ~"#comment", Some(data), ~[])
+        }
+        DoctypeNodeTypeId => {
+            let name = node.transmute(|doctype: &Doctype<ScriptView>| doctype.name.clone());
+            (10, name, None, ~[])
+        }
+        DocumentFragmentNodeTypeId => (11, ~"#document-fragment", None, ~[]),
+    };
+    let mut children = ~[];
+    for child in node.children() {
+        children.push(node_info(child, next_id));
+    }
+    NodeInfo {
+        id: id,
+        node_type: node_type,
+        name: name,
+        value: value,
+        attributes: attributes,
+        children: children,
+    }
+}
+
+/// The computed values of the CSS properties of the element with the given node id. Fails if
+/// there's no such element or layout hasn't styled it.
+pub fn computed_style(page: &mut Page, node_id: uint) -> Result<~[(~str, ~str)], ()> {
+    let node = match root(page) {
+        Some(root) => root.traverse_preorder().nth(node_id),
+        None => None,
+    };
+    let node = match node {
+        Some(node) if node.is_element() => node,
+        _ => return Err(()),
+    };
+    match page.query_layout(|chan| ComputedStyleQuery(node, chan)) {
+        Ok(ComputedStyleResponse(values)) => Ok(values),
+        Err(()) => Err(()),
+    }
+}
+
+/// Runs `source` in the page's global scope, returning what it evaluated to, described as the
+/// console would. Exceptions it throws are reported as those of other scripts are.
+pub fn evaluate(page: &mut Page, source: ~str) -> Result<~str, ~str> {
+    let js_info = match page.js_info {
+        Some(ref js_info) => js_info,
+        None => return Err(~"the page has no script global yet"),
+    };
+    let cx = js_info.js_context.ptr;
+    let global = js_info.js_compartment.global_obj.ptr;
+    let rval = JSVAL_NULL;
+    let source = source.to_utf16();
+    let ok = do source.as_imm_buf |chars, length| {
+        do "devtools".to_c_str().with_ref |filename| {
+            unsafe {
+                JS_EvaluateUCScript(cx, global, chars, length as c_uint, filename, 1, &rval)
+            }
+        }
+    };
+    if ok == 0 {
+        return Err(~"the script threw an exception")
+    }
+    Ok(console::stringify(cx, rval))
+}
+
+fn root(page: &Page) -> Option<AbstractNode<ScriptView>> {
+    page.frame.map(|frame| frame.document.with_base(|doc| doc.root))
+}
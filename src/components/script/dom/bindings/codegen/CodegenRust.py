@@ -1241,12 +1241,15 @@ for (uint32_t i = 0; i < length; ++i) {
                not isinstance(defaultValue, IDLNullValue))
         dataLoc = "${declName}"
         #XXXjdm conversionBehavior should be used
+        # Optional arguments without a default are passed as None when they're missing, so that
+        # a missing argument can be told apart from one that was given as 0.
+        value = "Some(v)" if isOptional else "v"
         template = (
             "match JSValConvertible::from_jsval::<%s>(${val}) {\n"
             "  None => return 0,\n"
-            "  Some(v) => %s = v\n"
-            "}" % (typeName, dataLoc))
-        declType = CGGeneric(typeName)
+            "  Some(v) => %s = %s\n"
+            "}" % (typeName, dataLoc, value))
+        declType = CGGeneric("Option<%s>" % typeName if isOptional else typeName)
     if (defaultValue is not None and
         # We already handled IDLNullValue, so just deal with the other ones
         not isinstance(defaultValue, IDLNullValue)):
@@ -1263,7 +1266,8 @@ for (uint32_t i = 0; i < length; ++i) {
                                    "  %s = %s;\n"
                                    "}" % (dataLoc, defaultStr))).define()
 
-    return (template, declType, None, isOptional, "0 as %s" % typeName)
+    initialValue = "None" if isOptional else "0 as %s" % typeName
+    return (template, declType, None, isOptional, initialValue)
 
 def instantiateJSToNativeConversionTemplate(templateTuple, replacements,
                                             argcAndIndex=None):
@@ -3085,8 +3089,8 @@ class CGGetterCall(CGPerSignatureCall):
     A class to generate a native object getter call for a particular IDL
     getter.
     """
-    def __init__(self, returnType, nativeMethodName, descriptor, attr):
-        CGPerSignatureCall.__init__(self, returnType, [], [],
+    def __init__(self, argsPre, returnType, nativeMethodName, descriptor, attr):
+        CGPerSignatureCall.__init__(self, returnType, argsPre, [],
                                     nativeMethodName, False, descriptor,
                                     attr, getter=True)
 
@@ -3110,8 +3114,8 @@ class CGSetterCall(CGPerSignatureCall):
     A class to generate a native object setter call for a particular IDL
     setter.
     """
-    def __init__(self, argType, nativeMethodName, descriptor, attr):
-        CGPerSignatureCall.__init__(self, None, [],
+    def __init__(self, argsPre, argType, nativeMethodName, descriptor, attr):
+        CGPerSignatureCall.__init__(self, None, argsPre,
                                     [FakeArgument(argType, attr)],
                                     nativeMethodName, False, descriptor, attr,
                                     setter=True)
@@ -3191,6 +3195,18 @@ class CGAbstractStaticMethod(CGAbstractMethod):
         # We only have implementation
         return ""
 
+def abstractThis(descriptor, name):
+    """
+    Returns the code to get the abstract pointer to the object, and the
+    arguments to pass it to the native member with, if the member named
+    |name| needs it.
+    """
+    if name not in descriptor.needsAbstract:
+        return ('', [])
+    abstractName = re.sub(r'<\w+>', '', descriptor.nativeType)
+    return ('  let abstract_this = %s::from_box(this);\n' % abstractName,
+            ['abstract_this'])
+
 class CGSpecializedMethod(CGAbstractExternMethod):
     """
     A class for generating the C++ code for a specialized method that the JIT
@@ -3207,12 +3223,7 @@ class CGSpecializedMethod(CGAbstractExternMethod):
     def definition_body(self):
         name = self.method.identifier.name
         nativeName = MakeNativeName(self.descriptor.binaryNames.get(name, name))
-        extraPre = ''
-        argsPre = []
-        if name in self.descriptor.needsAbstract:
-            abstractName = re.sub(r'<\w+>', '', self.descriptor.nativeType)
-            extraPre = '  let abstract_this = %s::from_box(this);\n' % abstractName
-            argsPre = ['abstract_this']
+        (extraPre, argsPre) = abstractThis(self.descriptor, name)
         return CGWrapper(CGMethodCall(argsPre, nativeName, self.method.isStatic(),
                                       self.descriptor, self.method),
                          pre=extraPre +
@@ -3269,9 +3280,11 @@ class CGSpecializedGetter(CGAbstractExternMethod):
                                                             getter=True))
         if resultOutParam or self.attr.type.nullable() or not infallible:
             nativeName = "Get" + nativeName
-        return CGWrapper(CGIndenter(CGGetterCall(self.attr.type, nativeName,
+        (extraPre, argsPre) = abstractThis(self.descriptor, name)
+        return CGWrapper(CGIndenter(CGGetterCall(argsPre, self.attr.type, nativeName,
                                                  self.descriptor, self.attr)),
-                         pre="  let obj = (*obj.unnamed);\n" +
+                         pre=extraPre +
+                             "  let obj = (*obj.unnamed);\n" +
                              "  let this = &mut (*this).payload;\n").define()
 
 class CGGenericSetter(CGAbstractBindingMethod):
@@ -3320,9 +3333,11 @@ class CGSpecializedSetter(CGAbstractExternMethod):
     def definition_body(self):
         name = self.attr.identifier.name
         nativeName = "Set" + MakeNativeName(self.descriptor.binaryNames.get(name, name))
-        return CGWrapper(CGIndenter(CGSetterCall(self.attr.type, nativeName,
+        (extraPre, argsPre) = abstractThis(self.descriptor, name)
+        return CGWrapper(CGIndenter(CGSetterCall(argsPre, self.attr.type, nativeName,
                                                  self.descriptor, self.attr)),
-                         pre="  let obj = (*obj.unnamed);\n" +
+                         pre=extraPre +
+                             "  let obj = (*obj.unnamed);\n" +
                              "  let this = &mut (*this).payload;\n").define()
 
 def infallibleForMember(member, type, descriptorProvider):
@@ -4620,6 +4635,7 @@ class CGBindingRoot(CGThing):
                           'dom::htmlanchorelement::HTMLAnchorElement', #XXXjdm
                           'dom::htmlappletelement::HTMLAppletElement', #XXXjune0cho
                           'dom::htmlareaelement::HTMLAreaElement', #XXXjune0cho
+                          'dom::htmlaudioelement::HTMLAudioElement',
                           'dom::htmlbaseelement::HTMLBaseElement', #XXXjune0cho
                           'dom::htmlbodyelement::HTMLBodyElement', #XXXjune0cho
                           'dom::htmlbrelement::HTMLBRElement', #XXXrecrack
@@ -4628,17 +4644,21 @@ class CGBindingRoot(CGThing):
                           'dom::htmldlistelement::HTMLDListElement',
                           'dom::htmlelement::HTMLElement', #XXXjdm
                           'dom::htmldocument::HTMLDocument', #XXXjdm
+                          'dom::htmlformelement::HTMLFormElement',
                           'dom::htmlhrelement::HTMLHRElement',
                           'dom::htmliframeelement::HTMLIFrameElement', #XXXjdm
                           'dom::htmlimageelement::HTMLImageElement', #XXXjdm
                           'dom::htmlinputelement::HTMLInputElement',
                           'dom::htmllielement::HTMLLIElement',
                           'dom::htmllinkelement::HTMLLinkElement', #XXXrecrack
+                          'dom::htmlmediaelement::HTMLMediaElement',
                           'dom::htmlmetaelement::HTMLMetaElement',
                           'dom::htmlolistelement::HTMLOListElement',
+                          'dom::htmloptionelement::HTMLOptionElement',
                           'dom::htmlprogresselement::HTMLProgressElement',
                           'dom::htmlquoteelement::HTMLQuoteElement',
                           'dom::htmlscriptelement::HTMLScriptElement',
+                          'dom::htmlselectelement::HTMLSelectElement',
                           'dom::htmlsourceelement::HTMLSourceElement',
                           'dom::htmlstyleelement::HTMLStyleElement',
                           'dom::htmltablecaptionelement::HTMLTableCaptionElement',
@@ -4651,12 +4671,15 @@ class CGBindingRoot(CGThing):
                           'dom::htmltimeelement::HTMLTimeElement',
                           'dom::htmltitleelement::HTMLTitleElement', #XXXyusukesuzuki
                           'dom::htmlulistelement::HTMLUListElement',
+                          'dom::htmlvideoelement::HTMLVideoElement',
                           'dom::bindings::utils::*',
                           'dom::bindings::conversions::*',
                           'dom::blob::*', #XXXjdm
+                          'dom::canvasrenderingcontext2d::CanvasRenderingContext2D',
                           'dom::clientrect::*', #XXXjdm
                           'dom::clientrectlist::*', #XXXjdm
                           'dom::htmlcollection::*', #XXXjdm
+                          'dom::imagedata::ImageData',
                           'dom::bindings::proxyhandler::*',
                           'dom::domparser::*', #XXXjdm
                           'dom::event::*', #XXXjdm
@@ -0,0 +1,201 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The `console` object. Its methods take any number of arguments, which the generated bindings
+//! can't express yet, so it's defined by hand. What's logged is formatted here and sent to the
+//! constellation, which prints the messages of all pipelines in one place.
+
+use dom::bindings::structuredclone;
+use dom::bindings::utils::jsval_to_str;
+use dom::bindings::utils;
+use script_task::page_from_context;
+use servo_msg::constellation_msg::{ArrayData, BooleanData, NullData, NumberData, ObjectData};
+use servo_msg::constellation_msg::{StringData, StructuredCloneData, UndefinedData};
+use servo_msg::constellation_msg::{ConsoleLevel, ConsoleMsg, DebugLevel, ErrorLevel, InfoLevel};
+use servo_msg::constellation_msg::{LogLevel, WarnLevel};
+
+use js::glue::{RUST_JSVAL_IS_DOUBLE, RUST_JSVAL_IS_INT, RUST_JSVAL_IS_STRING};
+use js::glue::{RUST_JSVAL_TO_DOUBLE, RUST_JSVAL_TO_INT};
+use js::jsapi::{JSBool, JSContext, JSFunctionSpec, JSNative, JSNativeWrapper, JSVal};
+use js::jsapi::JS_DefineFunctions;
+use js::rust::Compartment;
+use js::JS_ARGV;
+
+use std::cast;
+use std::f64;
+use std::libc::c_uint;
+use std::ptr::null;
+use std::vec;
+
+pub fn init(compartment: @mut Compartment) {
+    let obj = utils::define_empty_prototype(~"console", None, compartment);
+    let methods = @~[method(compartment, ~"log", log),
+                     method(compartment, ~"debug", debug),
+                     method(compartment, ~"info", info),
+                     method(compartment, ~"warn", warn),
+                     method(compartment, ~"error", error),
+                     JSFunctionSpec {name: null(),
+                                     call: JSNativeWrapper {op: null(), info: null()},
+                                     nargs: 0,
+                                     flags: 0,
+                                     selfHostedName: null()}];
+    do methods.as_imm_buf |fns, _len| {
+        unsafe {
+            JS_DefineFunctions(compartment.cx.ptr, obj.ptr, fns);
+        }
+    }
+}
+
+fn method(compartment: @mut Compartment, name: ~str, op: JSNative) -> JSFunctionSpec {
+    JSFunctionSpec {name: compartment.add_name(name),
+                    call: JSNativeWrapper {op: op, info: null()},
+                    nargs: 0,
+                    flags: 0,
+                    selfHostedName: null()}
+}
+
+extern fn log(cx: *JSContext, argc: c_uint, vp: *JSVal) -> JSBool {
+    send(cx, argc, vp, LogLevel)
+}
+
+extern fn debug(cx: *JSContext, argc: c_uint, vp: *JSVal) -> JSBool {
+    send(cx, argc, vp, DebugLevel)
+}
+
+extern fn info(cx: *JSContext, argc: c_uint, vp: *JSVal) -> JSBool {
+    send(cx, argc, vp, InfoLevel)
+}
+
+extern fn warn(cx: *JSContext, argc: c_uint, vp: *JSVal) -> JSBool {
+    send(cx, argc, vp, WarnLevel)
+}
+
+extern fn error(cx: *JSContext, argc: c_uint, vp: *JSVal) -> JSBool {
+    send(cx, argc, vp, ErrorLevel)
+}
+
+/// Formats the arguments of a call to a console method and sends them to the constellation.
+fn send(cx: *JSContext, argc: c_uint, vp: *JSVal, level: ConsoleLevel) -> JSBool {
+    unsafe {
+        let argv = JS_ARGV(cx, cast::transmute(vp));
+        let args = do vec::from_fn(argc as uint) |i| { *argv.offset(i as int) };
+        let message = format(cx, args);
+        let page = page_from_context(cx);
+        for frame in (*page).frame.iter() {
+            frame.window.constellation_chan.send(ConsoleMsg((*page).id, level, message.clone()));
+        }
+        return 1;
+    }
+}
+
+/// Formats arguments the way browsers do: if the first is a string, its `%s`, `%d`, `%i`, `%f`,
+/// `%o` and `%O` are replaced with the arguments after it, and `%c` takes one and ignores it.
+/// The arguments left over are appended, separated by spaces.
+fn format(cx: *JSContext, args: &[JSVal]) -> ~str {
+    if args.is_empty() {
+        return ~""
+    }
+    let mut rest = args.iter();
+    let mut pieces = ~[];
+    if unsafe { RUST_JSVAL_IS_STRING(args[0]) == 1 } {
+        rest.next();
+        let template = to_str(cx, args[0], "");
+        let mut piece = ~"";
+        let mut chars = template.iter();
+        loop {
+            match chars.next() {
+                None => break,
+                Some('%') => {
+                    match chars.next() {
+                        Some('%') => piece.push_char('%'),
+                        Some(c) if "sdifoOc".contains_char(c) => {
+                            match rest.next() {
+                                Some(&value) => match c {
+                                    'd' | 'i' => piece.push_str(fmt!("%d", number(value) as int)),
+                                    'f' => piece.push_str(number(value).to_str()),
+                                    'c' => {}
+                                    _ => piece.push_str(stringify(cx, value)),
+                                },
+                                None => {
+                                    piece.push_char('%');
+                                    piece.push_char(c);
+                                }
+                            }
+                        }
+                        Some(c) => {
+                            piece.push_char('%');
+                            piece.push_char(c);
+                        }
+                        None => piece.push_char('%'),
+                    }
+                }
+                Some(c) => piece.push_char(c),
+            }
+        }
+        pieces.push(piece);
+    }
+    for &value in rest {
+        pieces.push(stringify(cx, value));
+    }
+    pieces.connect(" ")
+}
+
+/// Converts a script value to a number for `%d`, `%i` and `%f`.
+fn number(value: JSVal) -> f64 {
+    unsafe {
+        if RUST_JSVAL_IS_INT(value) == 1 {
+            RUST_JSVAL_TO_INT(value) as f64
+        } else if RUST_JSVAL_IS_DOUBLE(value) == 1 {
+            RUST_JSVAL_TO_DOUBLE(value)
+        } else {
+            f64::NaN
+        }
+    }
+}
+
+/// Describes a script value. Arrays and plain objects are written out with what's in them, and
+/// other objects as script would convert them to strings.
+pub fn stringify(cx: *JSContext, value: JSVal) -> ~str {
+    if unsafe { RUST_JSVAL_IS_STRING(value) == 1 } {
+        return to_str(cx, value, "")
+    }
+    match structuredclone::write(cx, value) {
+        Ok(data) => describe(&data),
+        Err(_) => to_str(cx, value, "[object]"),
+    }
+}
+
+/// Converts a script value to a string as script would, or returns `default` if that fails.
+fn to_str(cx: *JSContext, value: JSVal, default: &str) -> ~str {
+    match jsval_to_str(cx, value) {
+        Ok(string) => string,
+        Err(()) => default.to_owned(),
+    }
+}
+
+fn describe(data: &StructuredCloneData) -> ~str {
+    match *data {
+        UndefinedData => ~"undefined",
+        NullData => ~"null",
+        BooleanData(value) => value.to_str(),
+        NumberData(value) => {
+            if value == value.floor() && value.abs() < 1e15 {
+                (value as i64).to_str()
+            } else {
+                value.to_str()
+            }
+        }
+        StringData(ref value) => fmt!("\"%s\"", *value),
+        ArrayData(ref elements) => {
+            let elements: ~[~str] = elements.iter().map(|element| describe(element)).collect();
+            fmt!("[%s]", elements.connect(", "))
+        }
+        ObjectData(ref properties) => {
+            let properties: ~[~str] = do properties.iter().map |&(ref name, ref value)| {
+                fmt!("%s: %s", *name, describe(value))
+            }.collect();
+            fmt!("{%s}", properties.connect(", "))
+        }
+    }
+}
@@ -18,7 +18,6 @@ use std::cast;
 use std::i32;
 use std::libc;
 use std::libc::c_uint;
-use std::comm;
 use std::ptr;
 use std::ptr::null;
 use js::glue::*;
@@ -220,9 +219,8 @@ extern fn HTMLImageElement_getWidth(cx: *JSContext, _argc: c_uint, vp: *mut JSVa
         let width = match node.type_id() {
             ElementNodeTypeId(HTMLImageElementTypeId) => {
                 let page = page_from_context(cx);
-                let (port, chan) = comm::stream();
                 // TODO(tkuehn): currently this just queries top-level page's layout. Need to handle subframes.
-                match (*page).query_layout(ContentBoxQuery(node, chan), port) {
+                match (*page).query_layout(|chan| ContentBoxQuery(node, chan)) {
                     Ok(ContentBoxResponse(rect)) => rect.size.width.to_nearest_px(),
                     Err(()) => 0
                 }
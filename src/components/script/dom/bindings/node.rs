@@ -8,26 +8,30 @@ use dom::bindings::utils;
 use dom::bindings::utils::{CacheableWrapper, WrapperCache, DerivedWrapper};
 use dom::element::{HTMLElementTypeId,
                    HTMLAnchorElementTypeId, HTMLAppletElementTypeId,
-                   HTMLAreaElementTypeId, HTMLBaseElementTypeId,
+                   HTMLAreaElementTypeId, HTMLAudioElementTypeId, HTMLBaseElementTypeId,
                    HTMLBodyElementTypeId, HTMLBRElementTypeId,
                    HTMLCanvasElementTypeId, HTMLDataElementTypeId,
-                   HTMLDivElementTypeId, HTMLHeadElementTypeId, HTMLHRElementTypeId,
+                   HTMLDivElementTypeId, HTMLFormElementTypeId, HTMLHeadElementTypeId,
+                   HTMLHRElementTypeId,
                    HTMLHtmlElementTypeId, HTMLIframeElementTypeId, HTMLImageElementTypeId,
                    HTMLInputElementTypeId, HTMLLIElementTypeId, HTMLLinkElementTypeId,
-                   HTMLMetaElementTypeId, HTMLOListElementTypeId,
+                   HTMLMetaElementTypeId, HTMLOListElementTypeId, HTMLOptionElementTypeId,
                    HTMLParagraphElementTypeId, HTMLProgressElementTypeId,
-                   HTMLQuoteElementTypeId, HTMLScriptElementTypeId,
+                   HTMLQuoteElementTypeId, HTMLScriptElementTypeId, HTMLSelectElementTypeId,
                    HTMLSpanElementTypeId, HTMLSourceElementTypeId,
                    HTMLStyleElementTypeId, HTMLTextAreaElementTypeId,
                    HTMLTableElementTypeId, HTMLTableCaptionElementTypeId, HTMLTableCellElementTypeId,
                    HTMLTableColElementTypeId,
                    HTMLTableRowElementTypeId, HTMLTableSectionElementTypeId, HTMLTimeElementTypeId,
-                   HTMLTitleElementTypeId, HTMLUListElementTypeId, HTMLDListElementTypeId};
+                   HTMLTitleElementTypeId, HTMLUListElementTypeId, HTMLDListElementTypeId,
+                   HTMLVideoElementTypeId};
 use dom::element::{HTMLHeadElement,HTMLHtmlElement, HTMLDivElement, HTMLParagraphElement, HTMLSpanElement};
+use dom::documentfragment::DocumentFragment;
 use dom::htmlelement::HTMLElement;
 use dom::htmlanchorelement::HTMLAnchorElement;
 use dom::htmlappletelement::HTMLAppletElement;
 use dom::htmlareaelement::HTMLAreaElement;
+use dom::htmlaudioelement::HTMLAudioElement;
 use dom::htmlbaseelement::HTMLBaseElement;
 use dom::htmlbodyelement::HTMLBodyElement;
 use dom::htmlhrelement::HTMLHRElement;
@@ -35,6 +39,7 @@ use dom::htmlbrelement::HTMLBRElement;
 use dom::htmlcanvaselement::HTMLCanvasElement;
 use dom::htmldataelement::HTMLDataElement;
 use dom::htmldlistelement::HTMLDListElement;
+use dom::htmlformelement::HTMLFormElement;
 use dom::htmliframeelement::HTMLIFrameElement;
 use dom::htmlimageelement::HTMLImageElement;
 use dom::htmlinputelement::HTMLInputElement;
@@ -42,9 +47,11 @@ use dom::htmllielement::HTMLLIElement;
 use dom::htmllinkelement::HTMLLinkElement;
 use dom::htmlmetaelement::HTMLMetaElement;
 use dom::htmlolistelement::HTMLOListElement;
+use dom::htmloptionelement::HTMLOptionElement;
 use dom::htmlprogresselement::HTMLProgressElement;
 use dom::htmlquoteelement::HTMLQuoteElement;
 use dom::htmlscriptelement::HTMLScriptElement;
+use dom::htmlselectelement::HTMLSelectElement;
 use dom::htmlsourceelement::HTMLSourceElement;
 use dom::htmlstyleelement::HTMLStyleElement;
 use dom::htmltableelement::HTMLTableElement;
@@ -57,8 +64,9 @@ use dom::htmltextareaelement::HTMLTextAreaElement;
 use dom::htmltimeelement::HTMLTimeElement;
 use dom::htmltitleelement::HTMLTitleElement;
 use dom::htmlulistelement::HTMLUListElement;
+use dom::htmlvideoelement::HTMLVideoElement;
 use dom::node::{AbstractNode, Node, ElementNodeTypeId, TextNodeTypeId, CommentNodeTypeId};
-use dom::node::{DoctypeNodeTypeId, ScriptView, Text};
+use dom::node::{DoctypeNodeTypeId, DocumentFragmentNodeTypeId, ScriptView, Text};
 
 use std::cast;
 use std::libc::c_uint;
@@ -125,6 +133,7 @@ pub fn create(cx: *JSContext, node: &mut AbstractNode<ScriptView>) -> *JSObject
         ElementNodeTypeId(HTMLAnchorElementTypeId) => generate_element!(HTMLAnchorElement),
         ElementNodeTypeId(HTMLAppletElementTypeId) => generate_element!(HTMLAppletElement),
         ElementNodeTypeId(HTMLAreaElementTypeId) => generate_element!(HTMLAreaElement),
+        ElementNodeTypeId(HTMLAudioElementTypeId) => generate_element!(HTMLAudioElement),
         ElementNodeTypeId(HTMLBaseElementTypeId) => generate_element!(HTMLBaseElement),
         ElementNodeTypeId(HTMLBodyElementTypeId) => generate_element!(HTMLBodyElement),
         ElementNodeTypeId(HTMLBRElementTypeId) => generate_element!(HTMLBRElement),
@@ -132,6 +141,7 @@ pub fn create(cx: *JSContext, node: &mut AbstractNode<ScriptView>) -> *JSObject
         ElementNodeTypeId(HTMLDataElementTypeId) => generate_element!(HTMLDataElement),
         ElementNodeTypeId(HTMLDListElementTypeId) => generate_element!(HTMLDListElement),
         ElementNodeTypeId(HTMLDivElementTypeId) => generate_element!(HTMLDivElement),
+        ElementNodeTypeId(HTMLFormElementTypeId) => generate_element!(HTMLFormElement),
         ElementNodeTypeId(HTMLHeadElementTypeId) => generate_element!(HTMLHeadElement),
         ElementNodeTypeId(HTMLHRElementTypeId) => generate_element!(HTMLHRElement),
         ElementNodeTypeId(HTMLHtmlElementTypeId) => generate_element!(HTMLHtmlElement),
@@ -142,10 +152,12 @@ pub fn create(cx: *JSContext, node: &mut AbstractNode<ScriptView>) -> *JSObject
         ElementNodeTypeId(HTMLLinkElementTypeId) => generate_element!(HTMLLinkElement),
         ElementNodeTypeId(HTMLMetaElementTypeId) => generate_element!(HTMLMetaElement),
         ElementNodeTypeId(HTMLOListElementTypeId) => generate_element!(HTMLOListElement),
+        ElementNodeTypeId(HTMLOptionElementTypeId) => generate_element!(HTMLOptionElement),
         ElementNodeTypeId(HTMLParagraphElementTypeId) => generate_element!(HTMLParagraphElement),
         ElementNodeTypeId(HTMLProgressElementTypeId) => generate_element!(HTMLProgressElement),
         ElementNodeTypeId(HTMLQuoteElementTypeId) => generate_element!(HTMLQuoteElement),
         ElementNodeTypeId(HTMLScriptElementTypeId) => generate_element!(HTMLScriptElement),
+        ElementNodeTypeId(HTMLSelectElementTypeId) => generate_element!(HTMLSelectElement),
         ElementNodeTypeId(HTMLSourceElementTypeId) => generate_element!(HTMLSourceElement),
         ElementNodeTypeId(HTMLSpanElementTypeId) => generate_element!(HTMLSpanElement),
         ElementNodeTypeId(HTMLStyleElementTypeId) => generate_element!(HTMLStyleElement),
@@ -159,6 +171,7 @@ pub fn create(cx: *JSContext, node: &mut AbstractNode<ScriptView>) -> *JSObject
         ElementNodeTypeId(HTMLTimeElementTypeId) => generate_element!(HTMLTimeElement),
         ElementNodeTypeId(HTMLTitleElementTypeId) => generate_element!(HTMLTitleElement),
         ElementNodeTypeId(HTMLUListElementTypeId) => generate_element!(HTMLUListElement),
+        ElementNodeTypeId(HTMLVideoElementTypeId) => generate_element!(HTMLVideoElement),
         ElementNodeTypeId(_) => element::create(cx, node).ptr,
         CommentNodeTypeId |
         DoctypeNodeTypeId => text::create(cx, node).ptr,
@@ -166,6 +179,10 @@ pub fn create(cx: *JSContext, node: &mut AbstractNode<ScriptView>) -> *JSObject
             let node: @mut Text = unsafe { cast::transmute(node.raw_object()) };
             node.wrap_object_shared(cx, ptr::null())
         }
+        DocumentFragmentNodeTypeId => {
+            let node: @mut DocumentFragment = unsafe { cast::transmute(node.raw_object()) };
+            node.wrap_object_shared(cx, ptr::null())
+        }
      }
 }
 
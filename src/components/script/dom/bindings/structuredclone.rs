@@ -0,0 +1,163 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Copying script values between script tasks, for `postMessage`. A value is written out to a
+//! `StructuredCloneData`, which is sendable, and read back in as a new value by the receiver.
+//!
+//! FIXME: Only primitives, arrays and plain objects can be copied so far. Values that refer to
+//! the same object more than once are copied once per reference, and cycles can't be copied.
+
+use dom::bindings::utils::{DataClone, Error, domstring_to_jsval, jsval_to_str, str};
+
+use js::glue::{RUST_JSVAL_IS_BOOLEAN, RUST_JSVAL_IS_DOUBLE, RUST_JSVAL_IS_INT};
+use js::glue::{RUST_JSVAL_IS_NULL, RUST_JSVAL_IS_OBJECT, RUST_JSVAL_IS_STRING};
+use js::glue::{RUST_JSVAL_IS_VOID, RUST_JSVAL_TO_BOOLEAN, RUST_JSVAL_TO_DOUBLE};
+use js::glue::{RUST_JSVAL_TO_INT, RUST_JSVAL_TO_OBJECT, RUST_DOUBLE_TO_JSVAL};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSContext, JSObject, JSVal, JS_DestroyIdArray, JS_Enumerate, JS_GetArrayLength};
+use js::jsapi::{JS_GetElement, JS_GetPropertyById, JS_IdArrayGet, JS_IdArrayLength, JS_IdToValue};
+use js::jsapi::{JS_IsArrayObject, JS_NewArrayObject, JS_NewObject, JS_ObjectIsFunction};
+use js::jsapi::JS_SetProperty;
+use js::{JSVAL_FALSE, JSVAL_NULL, JSVAL_TRUE, JSVAL_VOID};
+use servo_msg::constellation_msg::{ArrayData, BooleanData, NullData, NumberData, ObjectData};
+use servo_msg::constellation_msg::{StringData, StructuredCloneData, UndefinedData};
+
+use std::libc::c_int;
+use std::ptr;
+
+/// Copies `value` out of the script engine. Fails with `DataClone` for values that can't be
+/// copied, like functions.
+pub fn write(cx: *JSContext, value: JSVal) -> Result<StructuredCloneData, Error> {
+    let mut ancestors = ~[];
+    write_value(cx, value, &mut ancestors)
+}
+
+/// Makes a new script value from a copy of one.
+pub fn read(cx: *JSContext, data: &StructuredCloneData) -> JSVal {
+    unsafe {
+        match *data {
+            UndefinedData => JSVAL_VOID,
+            NullData => JSVAL_NULL,
+            BooleanData(value) => if value { JSVAL_TRUE } else { JSVAL_FALSE },
+            NumberData(value) => RUST_DOUBLE_TO_JSVAL(value),
+            StringData(ref value) => domstring_to_jsval(cx, &str(value.clone())),
+            ArrayData(ref elements) => {
+                let values = elements.map(|element| read(cx, element));
+                let array = do values.as_imm_buf |values, length| {
+                    JS_NewArrayObject(cx, length as c_int, values)
+                };
+                RUST_OBJECT_TO_JSVAL(array)
+            }
+            ObjectData(ref properties) => {
+                let object = JS_NewObject(cx, ptr::null(), ptr::null(), ptr::null());
+                for &(ref name, ref property) in properties.iter() {
+                    let value = read(cx, property);
+                    do name.to_c_str().with_ref |name| {
+                        JS_SetProperty(cx, object, name, ptr::to_unsafe_ptr(&value));
+                    }
+                }
+                RUST_OBJECT_TO_JSVAL(object)
+            }
+        }
+    }
+}
+
+/// Copies `value`, which is inside the objects in `ancestors`.
+fn write_value(cx: *JSContext, value: JSVal, ancestors: &mut ~[*JSObject])
+               -> Result<StructuredCloneData, Error> {
+    unsafe {
+        if RUST_JSVAL_IS_VOID(value) == 1 {
+            Ok(UndefinedData)
+        } else if RUST_JSVAL_IS_NULL(value) == 1 {
+            Ok(NullData)
+        } else if RUST_JSVAL_IS_BOOLEAN(value) == 1 {
+            Ok(BooleanData(RUST_JSVAL_TO_BOOLEAN(value) != 0))
+        } else if RUST_JSVAL_IS_INT(value) == 1 {
+            Ok(NumberData(RUST_JSVAL_TO_INT(value) as f64))
+        } else if RUST_JSVAL_IS_DOUBLE(value) == 1 {
+            Ok(NumberData(RUST_JSVAL_TO_DOUBLE(value)))
+        } else if RUST_JSVAL_IS_STRING(value) == 1 {
+            match jsval_to_str(cx, value) {
+                Ok(value) => Ok(StringData(value)),
+                Err(()) => Err(DataClone),
+            }
+        } else if RUST_JSVAL_IS_OBJECT(value) == 1 {
+            let object = RUST_JSVAL_TO_OBJECT(value);
+            if JS_ObjectIsFunction(cx, object) != 0 || ancestors.contains(&object) {
+                return Err(DataClone)
+            }
+            ancestors.push(object);
+            let result = if JS_IsArrayObject(cx, object) != 0 {
+                write_array(cx, object, ancestors)
+            } else {
+                write_object(cx, object, ancestors)
+            };
+            ancestors.pop();
+            result
+        } else {
+            Err(DataClone)
+        }
+    }
+}
+
+/// Copies the elements of the array `array`.
+unsafe fn write_array(cx: *JSContext, array: *JSObject, ancestors: &mut ~[*JSObject])
+                      -> Result<StructuredCloneData, Error> {
+    let mut length = 0;
+    if JS_GetArrayLength(cx, array, &mut length) == 0 {
+        return Err(DataClone)
+    }
+    let mut elements = ~[];
+    for index in range(0, length) {
+        let mut element = JSVAL_VOID;
+        if JS_GetElement(cx, array, index, &mut element) == 0 {
+            return Err(DataClone)
+        }
+        match write_value(cx, element, ancestors) {
+            Ok(element) => elements.push(element),
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(ArrayData(elements))
+}
+
+/// Copies the enumerable own properties of `object`.
+unsafe fn write_object(cx: *JSContext, object: *JSObject, ancestors: &mut ~[*JSObject])
+                       -> Result<StructuredCloneData, Error> {
+    let ids = JS_Enumerate(cx, object);
+    if ids.is_null() {
+        return Err(DataClone)
+    }
+    let mut properties = ~[];
+    let mut result = Ok(());
+    for index in range(0, JS_IdArrayLength(cx, ids)) {
+        let id = JS_IdArrayGet(cx, ids, index);
+        let mut name = JSVAL_VOID;
+        let value = JSVAL_VOID;
+        if JS_IdToValue(cx, id, &mut name) == 0 ||
+                JS_GetPropertyById(cx, object, id, ptr::to_unsafe_ptr(&value)) == 0 {
+            result = Err(DataClone);
+            break
+        }
+        let name = match jsval_to_str(cx, name) {
+            Ok(name) => name,
+            Err(()) => {
+                result = Err(DataClone);
+                break
+            }
+        };
+        match write_value(cx, value, ancestors) {
+            Ok(value) => properties.push((name, value)),
+            Err(error) => {
+                result = Err(error);
+                break
+            }
+        }
+    }
+    JS_DestroyIdArray(cx, ids);
+    match result {
+        Ok(()) => Ok(ObjectData(properties)),
+        Err(error) => Err(error),
+    }
+}
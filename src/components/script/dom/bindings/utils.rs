@@ -852,7 +852,17 @@ impl DerivedWrapper for AbstractNode<ScriptView> {
 
 #[deriving(ToStr)]
 pub enum Error {
-    FailureUnknown
+    FailureUnknown,
+    InvalidState,
+    Syntax,
+    NoModificationAllowed,
+    HierarchyRequest,
+    NotFound,
+    InvalidNodeType,
+    IndexSize,
+    Security,
+    DataClone,
+    InvalidAccess,
 }
 
 pub type ErrorResult = Result<(), Error>;
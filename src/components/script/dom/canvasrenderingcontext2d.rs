@@ -0,0 +1,458 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `CanvasRenderingContext2D`. The context keeps the state script can read back,
+//! and sends everything else as messages to the canvas's task, which does the drawing; see
+//! `gfx::canvas_task`.
+
+use dom::bindings::codegen::CanvasRenderingContext2DBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, Error, ErrorResult, IndexSize, Security, str};
+use dom::imagedata::ImageData;
+use dom::node::{AbstractNode, ScriptView};
+use dom::window::Window;
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::canvas_task::{BeginPath, CanvasMsg, CanvasTask, ClearRect, ClosePath, DrawImage, Fill};
+use gfx::canvas_task::{FillRect, FillText, LineTo, MoveTo, RectPath, SetFillStyle, SetFont};
+use gfx::canvas_task::{PutImageData, SetLineWidth, SetStrokeStyle, Stroke, StrokeRect};
+use gfx::canvas_task::StrokeText;
+use gfx::color::RGBA;
+use gfx::font::{FontStyle, FontWeight400, FontWeight700};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+use std::cast;
+use std::float;
+use std::num::Orderable;
+use std::vec;
+
+static DEFAULT_STYLE: &'static str = "#000000";
+static DEFAULT_LINE_WIDTH: f64 = 1.0;
+static DEFAULT_FONT: &'static str = "10px sans-serif";
+
+pub struct CanvasRenderingContext2D {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    /// The canvas element the context draws on.
+    canvas: AbstractNode<ScriptView>,
+    /// The canvas's task, which does the drawing.
+    renderer: CanvasTask,
+    /// The drawing state, as script last set it.
+    fill_style: ~str,
+    stroke_style: ~str,
+    line_width: f64,
+    font: ~str,
+}
+
+impl CanvasRenderingContext2D {
+    pub fn new(window: @mut Window, canvas: AbstractNode<ScriptView>, size: Size2D<i32>)
+               -> @mut CanvasRenderingContext2D {
+        let renderer = CanvasTask(size,
+                                  window.image_cache_task.clone(),
+                                  window.profiler_chan.clone());
+        let context = @mut CanvasRenderingContext2D {
+            wrapper: WrapperCache::new(),
+            window: window,
+            canvas: canvas,
+            renderer: renderer,
+            fill_style: DEFAULT_STYLE.to_owned(),
+            stroke_style: DEFAULT_STYLE.to_owned(),
+            line_width: DEFAULT_LINE_WIDTH,
+            font: DEFAULT_FONT.to_owned(),
+        };
+
+        // TODO(tkuehn): This just handles the top-level page. Need to handle subframes.
+        let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = window.get_wrappercache().get_wrapper();
+        context.wrap_object_shared(cx, scope);
+        context
+    }
+
+    pub fn Canvas(&self) -> AbstractNode<ScriptView> {
+        self.canvas
+    }
+
+    pub fn LineWidth(&self) -> f64 {
+        self.line_width
+    }
+
+    pub fn SetLineWidth(&mut self, width: f64, _rv: &mut ErrorResult) {
+        if !width.is_finite() || width <= 0.0 {
+            return;
+        }
+        self.line_width = width;
+        self.send(SetLineWidth(width as f32));
+    }
+
+    pub fn StrokeStyle(&self) -> DOMString {
+        str(self.stroke_style.clone())
+    }
+
+    pub fn SetStrokeStyle(&mut self, style: &DOMString, _rv: &mut ErrorResult) {
+        let style = style.to_str();
+        match RGBA::parse(style) {
+            Some(color) => {
+                self.stroke_style = style;
+                self.send(SetStrokeStyle(color.to_gfx_color()));
+            }
+            None => {}
+        }
+    }
+
+    pub fn FillStyle(&self) -> DOMString {
+        str(self.fill_style.clone())
+    }
+
+    pub fn SetFillStyle(&mut self, style: &DOMString, _rv: &mut ErrorResult) {
+        let style = style.to_str();
+        match RGBA::parse(style) {
+            Some(color) => {
+                self.fill_style = style;
+                self.send(SetFillStyle(color.to_gfx_color()));
+            }
+            None => {}
+        }
+    }
+
+    pub fn Font(&self) -> DOMString {
+        str(self.font.clone())
+    }
+
+    pub fn SetFont(&mut self, font: &DOMString, _rv: &mut ErrorResult) {
+        let font = font.to_str();
+        match parse_font(font) {
+            Some(style) => {
+                self.font = font;
+                self.send(SetFont(style));
+            }
+            None => {}
+        }
+    }
+
+    pub fn ClearRect(&self, x: f64, y: f64, w: f64, h: f64) {
+        for rect in to_rect(x, y, w, h).iter() {
+            self.draw(ClearRect(*rect));
+        }
+    }
+
+    pub fn FillRect(&self, x: f64, y: f64, w: f64, h: f64) {
+        for rect in to_rect(x, y, w, h).iter() {
+            self.draw(FillRect(*rect));
+        }
+    }
+
+    pub fn StrokeRect(&self, x: f64, y: f64, w: f64, h: f64) {
+        for rect in to_rect(x, y, w, h).iter() {
+            self.draw(StrokeRect(*rect));
+        }
+    }
+
+    pub fn BeginPath(&self) {
+        self.send(BeginPath);
+    }
+
+    pub fn ClosePath(&self) {
+        self.send(ClosePath);
+    }
+
+    pub fn MoveTo(&self, x: f64, y: f64) {
+        for point in to_point(x, y).iter() {
+            self.send(MoveTo(*point));
+        }
+    }
+
+    pub fn LineTo(&self, x: f64, y: f64) {
+        for point in to_point(x, y).iter() {
+            self.send(LineTo(*point));
+        }
+    }
+
+    pub fn Rect(&self, x: f64, y: f64, w: f64, h: f64) {
+        for rect in to_rect(x, y, w, h).iter() {
+            self.send(RectPath(*rect));
+        }
+    }
+
+    pub fn Fill(&self) {
+        self.draw(Fill);
+    }
+
+    pub fn Stroke(&self) {
+        self.draw(Stroke);
+    }
+
+    pub fn FillText(&self, text: &DOMString, x: f64, y: f64) {
+        for point in to_point(x, y).iter() {
+            self.draw(FillText(text.to_str(), *point));
+        }
+    }
+
+    pub fn StrokeText(&self, text: &DOMString, x: f64, y: f64) {
+        for point in to_point(x, y).iter() {
+            self.draw(StrokeText(text.to_str(), *point));
+        }
+    }
+
+    pub fn DrawImage(&self, image: AbstractNode<ScriptView>, dx: f64, dy: f64) {
+        for origin in to_point(dx, dy).iter() {
+            self.draw_image(image, *origin, None, None);
+        }
+    }
+
+    pub fn DrawImage_(&self, image: AbstractNode<ScriptView>, dx: f64, dy: f64, dw: f64,
+                      dh: f64) {
+        for dest in to_rect(dx, dy, dw, dh).iter() {
+            self.draw_image(image, dest.origin, Some(dest.size), None);
+        }
+    }
+
+    pub fn DrawImage__(&self, image: AbstractNode<ScriptView>, sx: f64, sy: f64, sw: f64,
+                       sh: f64, dx: f64, dy: f64, dw: f64, dh: f64) {
+        match (to_rect(sx, sy, sw, sh), to_rect(dx, dy, dw, dh)) {
+            (Some(source), Some(dest)) => {
+                self.draw_image(image, dest.origin, Some(dest.size), Some(source))
+            }
+            _ => {}
+        }
+    }
+
+    pub fn CreateImageData(&self, sw: f64, sh: f64, rv: &mut ErrorResult) -> @mut ImageData {
+        match to_pixel_rect(0.0, 0.0, sw, sh) {
+            Ok(rect) => self.blank_image_data(rect.size.width as u32, rect.size.height as u32),
+            Err(error) => {
+                *rv = Err(error);
+                self.blank_image_data(0, 0)
+            }
+        }
+    }
+
+    pub fn CreateImageData_(&self, imagedata: @mut ImageData, _rv: &mut ErrorResult)
+                            -> @mut ImageData {
+        self.blank_image_data(imagedata.width, imagedata.height)
+    }
+
+    pub fn GetImageData(&self, sx: f64, sy: f64, sw: f64, sh: f64, rv: &mut ErrorResult)
+                        -> @mut ImageData {
+        // Script may not read back images from other origins by drawing them.
+        if !self.canvas.with_imm_canvas_element(|canvas| canvas.is_origin_clean()) {
+            *rv = Err(Security);
+            return self.blank_image_data(0, 0);
+        }
+        let rect = match to_pixel_rect(sx, sy, sw, sh) {
+            Ok(rect) => rect,
+            Err(error) => {
+                *rv = Err(error);
+                return self.blank_image_data(0, 0);
+            }
+        };
+        let pixels = self.canvas.with_imm_canvas_element(|canvas| canvas.get_image_data(&rect));
+        ImageData::new(self.window, rect.size.width as u32, rect.size.height as u32, pixels)
+    }
+
+    pub fn PutImageData(&self, imagedata: @mut ImageData, dx: f64, dy: f64) {
+        self.PutImageData_(imagedata, dx, dy, 0.0, 0.0, imagedata.width as f64,
+                           imagedata.height as f64)
+    }
+
+    pub fn PutImageData_(&self, imagedata: @mut ImageData, dx: f64, dy: f64, dirty_x: f64,
+                         dirty_y: f64, dirty_width: f64, dirty_height: f64) {
+        if !(dx.is_finite() && dy.is_finite() && dirty_x.is_finite() && dirty_y.is_finite() &&
+             dirty_width.is_finite() && dirty_height.is_finite()) {
+            return;
+        }
+
+        // A dirty rect of negative size extends the other way from its origin, and only the part
+        // of it inside the image data is put.
+        let (dirty_x, dirty_width) = normalize(dirty_x, dirty_width);
+        let (dirty_y, dirty_height) = normalize(dirty_y, dirty_height);
+        let left = dirty_x.max(&0.0).floor();
+        let top = dirty_y.max(&0.0).floor();
+        let right = (dirty_x + dirty_width).min(&(imagedata.width as f64)).floor();
+        let bottom = (dirty_y + dirty_height).min(&(imagedata.height as f64)).floor();
+        if right <= left || bottom <= top {
+            return;
+        }
+        let (left, top) = (left as uint, top as uint);
+        let (right, bottom) = (right as uint, bottom as uint);
+
+        let pixels = imagedata.pixels();
+        let row_length = imagedata.width as uint * 4;
+        let mut dirty = vec::with_capacity((right - left) * (bottom - top) * 4);
+        for row in range(top, bottom) {
+            dirty.push_all(pixels.slice(row * row_length + left * 4, row * row_length + right * 4));
+        }
+        let origin = Point2D(dx.floor() as i32 + left as i32, dy.floor() as i32 + top as i32);
+        let size = Size2D((right - left) as i32, (bottom - top) as i32);
+        self.draw(PutImageData(dirty, Rect(origin, size)));
+    }
+}
+
+impl CanvasRenderingContext2D {
+    /// Makes an `ImageData` of transparent black pixels.
+    fn blank_image_data(&self, width: u32, height: u32) -> @mut ImageData {
+        let pixels = vec::from_elem(width as uint * height as uint * 4, 0u8);
+        ImageData::new(self.window, width, height, pixels)
+    }
+
+    fn send(&self, msg: CanvasMsg) {
+        self.renderer.send(msg);
+    }
+
+    /// Sends a message that changes what the canvas shows, so that it gets laid out again.
+    fn draw(&self, msg: CanvasMsg) {
+        self.send(msg);
+        self.window.content_changed();
+    }
+
+    /// Draws the image an `img` element shows, if any, tainting the canvas if the image came
+    /// from another origin.
+    fn draw_image(&self,
+                  image: AbstractNode<ScriptView>,
+                  origin: Point2D<f32>,
+                  size: Option<Size2D<f32>>,
+                  source: Option<Rect<f32>>) {
+        if !image.is_image_element() {
+            return;
+        }
+        let url = match image.with_imm_image_element(|image| image.image.clone()) {
+            Some(url) => url,
+            None => return,
+        };
+        match self.window.origin() {
+            Some(ref document_origin) => {
+                do self.canvas.with_mut_canvas_element |canvas| {
+                    canvas.taint(document_origin, &url);
+                }
+            }
+            None => {}
+        }
+        self.draw(DrawImage(url, origin, size, source));
+    }
+
+    /// Puts the drawing state back as it was when the context was made, as resizing the canvas
+    /// does.
+    pub fn reset(&mut self) {
+        self.fill_style = DEFAULT_STYLE.to_owned();
+        self.stroke_style = DEFAULT_STYLE.to_owned();
+        self.line_width = DEFAULT_LINE_WIDTH;
+        self.font = DEFAULT_FONT.to_owned();
+    }
+}
+
+/// Converts a point script passed, unless either coordinate is infinite or NaN, in which case
+/// the call it was passed to does nothing.
+fn to_point(x: f64, y: f64) -> Option<Point2D<f32>> {
+    if x.is_finite() && y.is_finite() {
+        Some(Point2D(x as f32, y as f32))
+    } else {
+        None
+    }
+}
+
+/// Converts a rect script passed, as `to_point` does.
+fn to_rect(x: f64, y: f64, w: f64, h: f64) -> Option<Rect<f32>> {
+    match (to_point(x, y), to_point(w, h)) {
+        (Some(origin), Some(size)) => Some(Rect(origin, Size2D(size.x, size.y))),
+        _ => None,
+    }
+}
+
+/// Turns an origin and a size of either sign into the origin and positive size of the same span.
+fn normalize(origin: f64, size: f64) -> (f64, f64) {
+    if size < 0.0 {
+        (origin + size, -size)
+    } else {
+        (origin, size)
+    }
+}
+
+/// Converts a rect of pixels script passed to `getImageData()` or `createImageData()`, which may
+/// have a negative size, to the rect of whole pixels it covers.
+fn to_pixel_rect(x: f64, y: f64, w: f64, h: f64) -> Result<Rect<i32>, Error> {
+    // FIXME: Infinite and NaN arguments should throw a `TypeError`, which we have no way to.
+    if !(x.is_finite() && y.is_finite() && w.is_finite() && h.is_finite()) || w == 0.0 ||
+            h == 0.0 {
+        return Err(IndexSize);
+    }
+    let (x, w) = normalize(x, w);
+    let (y, h) = normalize(y, h);
+    let left = x.floor();
+    let top = y.floor();
+    let right = (x + w).ceil();
+    let bottom = (y + h).ceil();
+    Ok(Rect(Point2D(left as i32, top as i32),
+            Size2D((right - left) as i32, (bottom - top) as i32)))
+}
+
+/// Parses the subset of the CSS `font` shorthand canvases understand: an optional `italic` and
+/// `bold`, a size in `px` and the families, as in `italic bold 12px serif`.
+fn parse_font(font: &str) -> Option<FontStyle> {
+    let mut style = FontStyle {
+        pt_size: 10f,
+        weight: FontWeight400,
+        italic: false,
+        oblique: false,
+        families: ~"sans-serif",
+    };
+    let words: ~[&str] = font.word_iter().collect();
+    for (i, word) in words.iter().enumerate() {
+        match *word {
+            "normal" => {}
+            "italic" => style.italic = true,
+            "oblique" => style.oblique = true,
+            "bold" => style.weight = FontWeight700,
+            _ if word.ends_with("px") => {
+                match float::from_str(word.slice_to(word.len() - 2)) {
+                    Some(size) if size > 0.0 => style.pt_size = size,
+                    _ => return None,
+                }
+                if i + 1 == words.len() {
+                    return None;
+                }
+                style.families = words.slice_from(i + 1).connect(" ");
+                return Some(style);
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+impl CacheableWrapper for CanvasRenderingContext2D {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CanvasRenderingContext2DBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CanvasRenderingContext2D {
+    fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        Some(self.window as @mut CacheableWrapper)
+    }
+}
+
+impl DerivedWrapper for CanvasRenderingContext2D {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe {
+                *vp = RUST_OBJECT_TO_JSVAL(obj)
+            };
+            return 1;
+        }
+    }
+}
@@ -6,9 +6,12 @@
 
 use dom::bindings::utils::{DOMString, null_string, str, ErrorResult};
 use dom::bindings::utils::{BindingObject, CacheableWrapper, WrapperCache};
+use dom::mutationobserver::CharacterDataMutation;
 use dom::node::{Node, NodeTypeId, ScriptView};
 use js::jsapi::{JSObject, JSContext};
 
+use std::util::replace;
+
 pub struct CharacterData {
     parent: Node<ScriptView>,
     data: DOMString
@@ -27,7 +30,7 @@ impl CharacterData {
     }
 
     pub fn SetData(&mut self, arg: &DOMString, _rv: &mut ErrorResult) {
-        self.data = (*arg).clone();
+        self.set_data((*arg).clone());
     }
 
     pub fn Length(&self) -> u32 {
@@ -46,7 +49,7 @@ impl CharacterData {
 
     pub fn AppendData(&mut self, arg: &DOMString, _rv: &mut ErrorResult) {
         let s = self.data.to_str();
-        self.data = str(s.append(arg.to_str()));
+        self.set_data(str(s.append(arg.to_str())));
     }
 
     pub fn InsertData(&mut self, _offset: u32, _arg: &DOMString, _rv: &mut ErrorResult) {
@@ -62,6 +65,16 @@ impl CharacterData {
     }
 }
 
+impl CharacterData {
+    /// Replaces the text, and tells the document and its mutation observers.
+    pub fn set_data(&mut self, data: DOMString) {
+        let old_data = replace(&mut self.data, data).to_str();
+        for node in self.parent.abstract.iter() {
+            node.mutated(CharacterDataMutation(old_data.clone()));
+        }
+    }
+}
+
 impl CacheableWrapper for CharacterData {
     fn get_wrappercache(&mut self) -> &mut WrapperCache {
         self.parent.get_wrappercache()
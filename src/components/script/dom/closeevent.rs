@@ -0,0 +1,82 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::CloseEventBinding;
+use dom::bindings::utils::{DOMString, str};
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::event::Event;
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+/// The event fired at a web socket when its connection is closed.
+pub struct CloseEvent {
+    parent: Event,
+    /// Whether the connection was closed with a closing handshake, rather than dropped.
+    was_clean: bool,
+    /// The status code the server gave for closing the connection.
+    code: u16,
+    /// The reason the server gave for closing the connection.
+    reason: ~str,
+}
+
+impl CloseEvent {
+    pub fn new(type_: &DOMString, was_clean: bool, code: u16, reason: ~str) -> CloseEvent {
+        CloseEvent {
+            parent: Event::new(type_),
+            was_clean: was_clean,
+            code: code,
+            reason: reason,
+        }
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn WasClean(&self) -> bool {
+        self.was_clean
+    }
+
+    pub fn Code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn Reason(&self) -> DOMString {
+        str(self.reason.clone())
+    }
+}
+
+impl CacheableWrapper for CloseEvent {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        return self.parent.get_wrappercache()
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CloseEventBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CloseEvent {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        self.parent.GetParentObject(cx)
+    }
+}
+
+impl DerivedWrapper for CloseEvent {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
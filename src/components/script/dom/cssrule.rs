@@ -0,0 +1,89 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::CSSRuleBinding;
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject, DOMString, str};
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext};
+
+use std::ascii::StrAsciiExt;
+use std::cast;
+
+pub static UNKNOWN_RULE: u16 = 0;
+pub static STYLE_RULE: u16 = 1;
+pub static CHARSET_RULE: u16 = 2;
+pub static IMPORT_RULE: u16 = 3;
+pub static MEDIA_RULE: u16 = 4;
+pub static FONT_FACE_RULE: u16 = 5;
+pub static PAGE_RULE: u16 = 6;
+
+pub struct CSSRule {
+    wrapper: WrapperCache,
+    /// The text of the rule, as it was written in the style sheet or inserted.
+    text: ~str,
+}
+
+impl CSSRule {
+    pub fn new(text: ~str, cx: *JSContext, scope: *JSObject) -> @mut CSSRule {
+        let rule = @mut CSSRule {
+            wrapper: WrapperCache::new(),
+            text: text,
+        };
+        rule.wrap_object_shared(cx, scope);
+        rule
+    }
+
+    pub fn Type(&self) -> u16 {
+        rule_type(self.text.as_slice())
+    }
+
+    pub fn CssText(&self) -> DOMString {
+        str(self.text.clone())
+    }
+}
+
+/// The type of the rule with the text `text`, going by its at-keyword.
+pub fn rule_type(text: &str) -> u16 {
+    if !text.starts_with("@") {
+        return STYLE_RULE
+    }
+    let mut name = ~"";
+    for c in text.slice_from(1).iter() {
+        if !(c.is_alphanumeric() || c == '-' || c == '_') {
+            break;
+        }
+        name.push_char(c);
+    }
+    match name.to_ascii_lower().as_slice() {
+        "charset" => CHARSET_RULE,
+        "import" => IMPORT_RULE,
+        "media" => MEDIA_RULE,
+        "font-face" => FONT_FACE_RULE,
+        "page" => PAGE_RULE,
+        _ => UNKNOWN_RULE,
+    }
+}
+
+impl CacheableWrapper for CSSRule {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CSSRuleBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CSSRule {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
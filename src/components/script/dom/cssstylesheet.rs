@@ -0,0 +1,156 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for style sheets, as `document.styleSheets` gives them. Inserting and deleting
+//! rules sends the style sheet to layout again, which restyles the document.
+
+use dom::bindings::codegen::CSSStyleSheetBinding;
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject, DOMString, str};
+use dom::bindings::utils::{ErrorResult, IndexSize, Syntax, null_string};
+use dom::cssrule::{CSSRule, IMPORT_RULE, CHARSET_RULE, rule_type};
+use dom::cssrulelist::CSSRuleList;
+use dom::window::Window;
+use layout_interface::{ReplaceStylesheetMsg, SetStylesheetEnabledMsg};
+use script_task::page_from_context;
+use servo_util::media_queries::{StylesheetSource, split_rules};
+
+use extra::url::Url;
+use js::jsapi::{JSObject, JSContext};
+
+use std::cast;
+
+pub struct CSSStyleSheet {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    /// The index of the style sheet in the order style sheets were given to layout.
+    index: uint,
+    source: StylesheetSource,
+    /// The URL the style sheet was linked from, or `None` for a `style` element's.
+    href: Option<Url>,
+    /// The text of each of the style sheet's own rules, in order. Its `@import` rules were taken
+    /// off when it was loaded, and aren't among them.
+    rules: ~[~str],
+    disabled: bool,
+}
+
+impl CSSStyleSheet {
+    pub fn new(window: @mut Window, index: uint, source: StylesheetSource, href: Option<Url>,
+               cx: *JSContext, scope: *JSObject) -> @mut CSSStyleSheet {
+        let sheet = @mut CSSStyleSheet {
+            wrapper: WrapperCache::new(),
+            window: window,
+            index: index,
+            rules: split_rules(source.text.as_slice()),
+            source: source,
+            href: href,
+            disabled: false,
+        };
+        sheet.wrap_object_shared(cx, scope);
+        sheet
+    }
+
+    pub fn Type(&self) -> DOMString {
+        str(~"text/css")
+    }
+
+    pub fn GetHref(&self) -> DOMString {
+        match self.href {
+            Some(ref url) => str(url.to_str()),
+            None => null_string,
+        }
+    }
+
+    // FIXME: This should be the title of the element the style sheet came from.
+    pub fn GetTitle(&self) -> DOMString {
+        null_string
+    }
+
+    pub fn Disabled(&self) -> bool {
+        self.disabled
+    }
+
+    pub fn SetDisabled(&mut self, disabled: bool) {
+        if disabled == self.disabled {
+            return
+        }
+        self.disabled = disabled;
+        unsafe {
+            (*self.window.page).layout_chan.send(SetStylesheetEnabledMsg(self.index, !disabled));
+        }
+        self.window.content_changed();
+    }
+
+    pub fn CssRules(&self) -> @mut CSSRuleList {
+        let cx = unsafe { (*self.window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = self.window.get_wrappercache().get_wrapper();
+        let rules = do self.rules.iter().map |rule| {
+            CSSRule::new(rule.clone(), cx, scope)
+        }.collect();
+        CSSRuleList::new(rules, cx, scope)
+    }
+
+    pub fn InsertRule(&mut self, rule: &DOMString, index: u32, rv: &mut ErrorResult) -> u32 {
+        if index as uint > self.rules.len() {
+            *rv = Err(IndexSize);
+            return 0
+        }
+        let mut rules = split_rules(rule.to_str().as_slice());
+        if rules.len() != 1 {
+            *rv = Err(Syntax);
+            return 0
+        }
+        let rule = rules.pop();
+        // FIXME: Style sheets are only imported while the document is parsed, so `@import`
+        // rules can't be inserted yet.
+        let kind = rule_type(rule.as_slice());
+        if kind == IMPORT_RULE || kind == CHARSET_RULE || !rule.ends_with("}") {
+            *rv = Err(Syntax);
+            return 0
+        }
+        self.rules.insert(index as uint, rule);
+        self.rules_changed();
+        index
+    }
+
+    pub fn DeleteRule(&mut self, index: u32, rv: &mut ErrorResult) {
+        if index as uint >= self.rules.len() {
+            *rv = Err(IndexSize);
+            return
+        }
+        self.rules.remove(index as uint);
+        self.rules_changed();
+    }
+
+    /// Gives layout the style sheet with its rules as they are now.
+    fn rules_changed(&mut self) {
+        self.source.set_text(self.rules.connect("\n").as_slice());
+        unsafe {
+            let sheet = ReplaceStylesheetMsg(self.index, self.source.clone());
+            (*self.window.page).layout_chan.send(sheet);
+        }
+        self.window.content_changed();
+    }
+}
+
+impl CacheableWrapper for CSSStyleSheet {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CSSStyleSheetBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CSSStyleSheet {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
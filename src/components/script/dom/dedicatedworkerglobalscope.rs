@@ -0,0 +1,195 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `DedicatedWorkerGlobalScope`, what the script of a dedicated worker sees of
+//! the worker as `self`.
+
+use dom::bindings::codegen::DedicatedWorkerGlobalScopeBinding;
+use dom::bindings::structuredclone;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, ErrorResult, str};
+use dom::eventtarget::{EventListeners, invoke_listeners};
+use dom::messageevent::MessageEvent;
+use dom::window::TimerData;
+use dom::worker::WorkerId;
+use script_task::{ScriptChan, WorkerErrorMsg, WorkerPostMessageMsg};
+use worker_task::{FireWorkerTimerMsg, WorkerChan};
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+use js::rust::Compartment;
+
+use servo_msg::constellation_msg::{PipelineId, StructuredCloneData};
+
+use std::cast;
+use std::int;
+use std::rt::io::timer::Timer;
+use std::rt::rtio::RtioTimer;
+
+pub struct DedicatedWorkerGlobalScope {
+    wrapper: WrapperCache,
+    /// The compartment the worker's script runs in.
+    compartment: @mut Compartment,
+    /// The worker task's own channel, for timers to fire on.
+    chan: WorkerChan,
+    /// The script task of the document that started the worker, and the pipeline and id of the
+    /// `Worker` object there, for messages to go back to.
+    owner: ScriptChan,
+    pipeline_id: PipelineId,
+    worker_id: WorkerId,
+    /// The timeouts that are still to fire, by handle.
+    timers: ~[i32],
+    next_timer_handle: i32,
+    /// Set by `close()`. The worker stops once it's done with the task at hand.
+    closing: bool,
+    listeners: EventListeners,
+}
+
+impl DedicatedWorkerGlobalScope {
+    pub fn new(compartment: @mut Compartment,
+               chan: WorkerChan,
+               owner: ScriptChan,
+               pipeline_id: PipelineId,
+               worker_id: WorkerId)
+               -> @mut DedicatedWorkerGlobalScope {
+        let scope = @mut DedicatedWorkerGlobalScope {
+            wrapper: WrapperCache::new(),
+            compartment: compartment,
+            chan: chan,
+            owner: owner,
+            pipeline_id: pipeline_id,
+            worker_id: worker_id,
+            timers: ~[],
+            next_timer_handle: 0,
+            closing: false,
+            listeners: EventListeners::new(),
+        };
+        scope.wrap_object_shared(compartment.cx.ptr, compartment.global_obj.ptr);
+        scope
+    }
+
+    pub fn Close(&mut self) {
+        self.closing = true;
+    }
+
+    pub fn PostMessage(&self, cx: *JSContext, message: JSVal, rv: &mut ErrorResult) {
+        match structuredclone::write(cx, message) {
+            Ok(data) => {
+                self.owner.send(WorkerPostMessageMsg(self.pipeline_id.clone(), self.worker_id,
+                                                     data));
+            }
+            Err(error) => *rv = Err(error),
+        }
+    }
+
+    pub fn Onmessage(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("message")
+    }
+
+    pub fn SetOnmessage(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("message", handler);
+    }
+
+    pub fn AddEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                            _capture: bool) {
+        self.listeners.add(type_, listener);
+    }
+
+    pub fn RemoveEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                               _capture: bool) {
+        self.listeners.remove(type_, listener);
+    }
+
+    pub fn SetTimeout(&mut self, _cx: *JSContext, callback: JSVal, timeout: Option<i32>) -> i32 {
+        let timeout = int::max(0, timeout.unwrap_or(0)) as u64;
+        self.next_timer_handle += 1;
+        let handle = self.next_timer_handle;
+        self.timers.push(handle);
+
+        // Post a delayed message back to the worker task, which checks the timeout hasn't been
+        // cleared before running it.
+        let tm = Timer::new().unwrap();
+        let chan = self.chan.clone();
+        do spawn {
+            tm.sleep(timeout);
+            chan.send(FireWorkerTimerMsg(handle, ~TimerData {
+                funval: callback,
+                args: ~[]
+            }));
+        }
+        handle
+    }
+
+    pub fn ClearTimeout(&mut self, handle: i32) {
+        self.timers.retain(|&timer| timer != handle);
+    }
+}
+
+impl DedicatedWorkerGlobalScope {
+    /// Takes the timeout with the given handle off the list of those still to fire, returning
+    /// whether it was there, i.e. whether it should run.
+    pub fn take_timer(&mut self, handle: i32) -> bool {
+        let pending = self.timers.contains(&handle);
+        self.timers.retain(|&timer| timer != handle);
+        pending
+    }
+
+    /// Fires a `message` event for a message the worker's owner posted.
+    pub fn receive_message(@mut self, data: StructuredCloneData) {
+        let type_ = str(~"message");
+        let listeners = self.listeners.get(&type_);
+        if listeners.is_empty() {
+            return;
+        }
+        let cx = self.compartment.cx.ptr;
+        let this = self.wrapper.get_wrapper();
+        let data = structuredclone::read(cx, &data);
+        let event = @mut MessageEvent::new(&type_, data, ~"", None);
+        event.parent.bubbles = false;
+        event.parent.cancelable = false;
+        event.parent.trusted = true;
+        let event_obj = event.wrap_object_shared(cx, this);
+        invoke_listeners(cx, this, listeners, event_obj);
+    }
+
+    /// Tells the worker's owner that a script of the worker failed.
+    pub fn report_error(&self, message: ~str) {
+        self.owner.send(WorkerErrorMsg(self.pipeline_id.clone(), self.worker_id, message));
+    }
+}
+
+impl CacheableWrapper for DedicatedWorkerGlobalScope {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        DedicatedWorkerGlobalScopeBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for DedicatedWorkerGlobalScope {
+    fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        None
+    }
+}
+
+impl DerivedWrapper for DedicatedWorkerGlobalScope {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe {
+                *vp = RUST_OBJECT_TO_JSVAL(obj)
+            };
+            return 1;
+        }
+    }
+}
@@ -5,24 +5,37 @@
 use dom::bindings::codegen::DocumentBinding;
 use dom::bindings::utils::{DOMString, WrapperCache, ErrorResult, null_string, str};
 use dom::bindings::utils::{BindingObject, CacheableWrapper, rust_box, DerivedWrapper};
-use dom::element::{Element, HTMLHtmlElement};
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::documentfragment::DocumentFragment;
+use dom::element::{Element, ElementState, HTMLHtmlElement};
 use dom::element::{HTMLHtmlElementTypeId, HTMLHeadElementTypeId, HTMLTitleElementTypeId};
+use dom::element::HTMLBaseElementTypeId;
 use dom::event::Event;
 use dom::htmlcollection::HTMLCollection;
 use dom::htmldocument::HTMLDocument;
 use dom::htmlelement::HTMLElement;
+use dom::location::Location;
 use dom::node::{AbstractNode, ScriptView, Node, ElementNodeTypeId, Text};
+use dom::range::{BoundaryPoint, Range};
+use dom::selection;
+use dom::stylesheetlist::StyleSheetList;
 use dom::window::Window;
 use dom::windowproxy::WindowProxy;
 use dom::htmltitleelement::HTMLTitleElement;
+use html::hubbub_html_parser::clone_node;
 
 use js::jsapi::{JS_AddObjectRoot, JS_RemoveObjectRoot, JSObject, JSContext, JSVal};
 use js::glue::RUST_OBJECT_TO_JSVAL;
+use servo_msg::constellation_msg::PipelineWindow;
+use servo_util::media_queries::StylesheetSource;
 use servo_util::tree::TreeNodeRef;
+use servo_util::url::make_url;
+use extra::url::Url;
 
 use std::cast;
 use std::ptr;
 use std::str::eq_slice;
+use std::util::replace;
 
 pub trait WrappableDocument {
     fn init_wrapper(@mut self, cx: *JSContext);
@@ -81,7 +94,25 @@ pub struct Document {
     wrapper: WrapperCache,
     window: Option<@mut Window>,
     doctype: DocumentType,
-    title: ~str
+    title: ~str,
+    /// While the parser runs one of the document's scripts, the text the script writes with
+    /// `document.write`. The parser parses it where the script was once the script is done.
+    script_inserted_text: Option<@mut ~str>,
+    /// The element that keys the user presses go to.
+    focused: Option<AbstractNode<ScriptView>>,
+    /// The element under the pointer.
+    hovered: Option<AbstractNode<ScriptView>>,
+    /// The element the user is pressing the mouse button on.
+    active: Option<AbstractNode<ScriptView>>,
+    /// While a `contenteditable` element has focus, the text node with the caret, and the caret's
+    /// byte offset in its text.
+    caret: Option<(AbstractNode<ScriptView>, uint)>,
+    /// The selection: where the user started selecting, and where the selection extends to.
+    selection: Option<(BoundaryPoint, BoundaryPoint)>,
+    /// The style sheets layout was given, in order, with the URLs of those that were linked.
+    stylesheets: ~[(StylesheetSource, Option<Url>)],
+    /// The `document.styleSheets` list, once script has asked for it.
+    stylesheet_list: Option<@mut StyleSheetList>,
 }
 
 impl Document {
@@ -99,7 +130,114 @@ impl Document {
             wrapper: WrapperCache::new(),
             window: window,
             doctype: doctype,
-            title: ~""
+            title: ~"",
+            script_inserted_text: None,
+            focused: None,
+            hovered: None,
+            active: None,
+            caret: None,
+            selection: None,
+            stylesheets: ~[],
+            stylesheet_list: None,
+        }
+    }
+
+    /// Records a style sheet that was sent to layout, so that script can see it in
+    /// `document.styleSheets`.
+    pub fn add_stylesheet(&mut self, source: StylesheetSource, href: Option<Url>) {
+        self.stylesheets.push((source, href));
+    }
+
+    /// Moves focus to `element`, or takes it away from the focused element.
+    pub fn set_focus(&mut self, element: Option<AbstractNode<ScriptView>>) {
+        if self.focused == element {
+            return
+        }
+        if self.caret.is_some() {
+            self.set_caret(None);
+        }
+        for &old in self.focused.iter() {
+            if old.is_input_element() {
+                old.with_mut_input_element(|input| input.set_focused(false));
+            } else if old.is_textarea_element() {
+                old.with_mut_textarea_element(|textarea| textarea.set_focused(old, false));
+            }
+        }
+        for &new in element.iter() {
+            if new.is_input_element() {
+                new.with_mut_input_element(|input| input.set_focused(true));
+            } else if new.is_textarea_element() {
+                new.with_mut_textarea_element(|textarea| textarea.set_focused(new, true));
+            }
+        }
+        let old = replace(&mut self.focused, element);
+        let changed = move_state(old, element, false, |state, focus| state.focus = focus);
+        self.state_changed(changed);
+    }
+
+    /// Moves the pointer over `element`, or off the document. The element and its ancestors
+    /// match `:hover`.
+    pub fn set_hover(&mut self, element: Option<AbstractNode<ScriptView>>) {
+        if self.hovered == element {
+            return
+        }
+        let old = replace(&mut self.hovered, element);
+        let changed = move_state(old, element, true, |state, hover| state.hover = hover);
+        self.state_changed(changed);
+    }
+
+    /// Notes that the user started pressing on `element`, or stopped pressing. The element and
+    /// its ancestors match `:active`.
+    pub fn set_active(&mut self, element: Option<AbstractNode<ScriptView>>) {
+        if self.active == element {
+            return
+        }
+        let old = replace(&mut self.active, element);
+        let changed = move_state(old, element, true, |state, active| state.active = active);
+        self.state_changed(changed);
+    }
+
+    /// Has the smallest subtree holding all the elements that changed state restyled. Elements
+    /// that left the document since take the whole document with them.
+    fn state_changed(&self, changed: ~[AbstractNode<ScriptView>]) {
+        if changed.is_empty() {
+            return
+        }
+        let mut root = changed[0];
+        for &element in changed.iter() {
+            root = root.common_ancestor(element).unwrap_or_default(self.root);
+        }
+        for window in self.window.iter() {
+            window.restyle_subtree(root)
+        }
+    }
+
+    /// Moves the editing caret, and has layout show it in its new place.
+    pub fn set_caret(&mut self, caret: Option<(AbstractNode<ScriptView>, uint)>) {
+        self.caret = caret;
+        self.content_changed();
+    }
+
+    /// Puts the selected text on the clipboard. Returns whether there was any to copy.
+    pub fn copy(&self) -> bool {
+        let text = match self.selection {
+            Some((ref anchor, ref focus)) => selection::text(anchor, focus),
+            None => return false,
+        };
+        if text.is_empty() {
+            return false
+        }
+        for window in self.window.iter() {
+            window.compositor.set_clipboard_text(text.clone());
+        }
+        true
+    }
+
+    /// Changes the selection, and has layout highlight it in its new place.
+    pub fn set_selection(&mut self, selection: Option<(BoundaryPoint, BoundaryPoint)>) {
+        if self.selection != selection {
+            self.selection = selection;
+            self.content_changed();
         }
     }
 
@@ -243,14 +381,100 @@ impl Document {
         fail!("stub")
     }
 
+    pub fn CreateDocumentFragment(&self) -> AbstractNode<ScriptView> {
+        let (_scope, cx) = self.get_scope_and_cx();
+        let fragment = unsafe { Node::as_abstract_node(cx, @DocumentFragment::new()) };
+        self.adopt(fragment);
+        fragment
+    }
+
+    pub fn CreateTextNode(&self, data: &DOMString) -> AbstractNode<ScriptView> {
+        let (_scope, cx) = self.get_scope_and_cx();
+        let text = unsafe { Node::as_abstract_node(cx, @Text::new(data.to_str())) };
+        self.adopt(text);
+        text
+    }
+
+    pub fn ImportNode(&self,
+                      node: AbstractNode<ScriptView>,
+                      deep: bool,
+                      _rv: &mut ErrorResult)
+                      -> AbstractNode<ScriptView> {
+        let (_scope, cx) = self.get_scope_and_cx();
+        let clone = clone_node(cx, node, deep);
+        self.adopt(clone);
+        clone
+    }
+
+    /// Moves `node` out of wherever it is and into this document. All documents share their
+    /// window's compartment, so the node's reflector stays valid as it is.
+    pub fn AdoptNode(&self,
+                     node: AbstractNode<ScriptView>,
+                     rv: &mut ErrorResult)
+                     -> AbstractNode<ScriptView> {
+        for &parent in node.parent_node().iter() {
+            *rv = parent.pre_remove(node);
+        }
+        self.adopt(node);
+        node
+    }
+
     pub fn CreateEvent(&self, _interface: &DOMString, _rv: &mut ErrorResult) -> @mut Event {
         fail!("stub")
     }
 
+    pub fn CreateRange(&self, _rv: &mut ErrorResult) -> @mut Range {
+        let (scope, cx) = self.get_scope_and_cx();
+        Range::new(*self.window.get_ref(), self.root, cx, scope)
+    }
+
+    /// Makes a node, and its descendants, belong to this document.
+    fn adopt(&self, node: AbstractNode<ScriptView>) {
+        for &doc in self.root.with_base(|root| root.owner_doc).iter() {
+            node.with_mut_base(|base| base.add_to_doc(doc));
+        }
+    }
+
     pub fn GetInputEncoding(&self) -> DOMString {
         null_string
     }
 
+    pub fn GetLocation(&self) -> Option<@mut Location> {
+        self.window.map(|&window| window.Location())
+    }
+
+    /// The URL that relative URLs in the document resolve against: the `href` of its first `base`
+    /// element with one, itself resolved against the document's URL, or else the document's URL.
+    pub fn base_url(&self) -> Option<Url> {
+        let url = self.window.chain(|window| window.url());
+        match self.base_attr("href") {
+            Some(href) => Some(make_url(href, url)),
+            None => url,
+        }
+    }
+
+    /// Where links without a `target` of their own load: the `target` of the document's first
+    /// `base` element with one.
+    pub fn base_target(&self) -> Option<~str> {
+        self.base_attr("target")
+    }
+
+    /// The value of the attribute `name` on the first `base` element that has it.
+    fn base_attr(&self, name: &str) -> Option<~str> {
+        for node in self.root.traverse_preorder() {
+            if node.type_id() != ElementNodeTypeId(HTMLBaseElementTypeId) {
+                loop
+            }
+            let value = node.with_imm_element(|element| element.get_attr(name).map(|value| {
+                value.to_owned()
+            }));
+            if value.is_some() {
+                return value
+            }
+        }
+        None
+    }
+
     pub fn Referrer(&self) -> DOMString {
         null_string
     }
@@ -345,15 +569,19 @@ impl Document {
     }
 
     pub fn GetDefaultView(&self) -> Option<@mut WindowProxy> {
-        None
+        do self.window.map |&window| {
+            let id = unsafe { (*window.page).id };
+            window.proxy(PipelineWindow(id))
+        }
     }
 
+    /// The focused element, or the root element when nothing has focus.
     pub fn GetActiveElement(&self) -> Option<AbstractNode<ScriptView>> {
-        None
+        self.focused.or(Some(self.root))
     }
 
     pub fn HasFocus(&self, _rv: &mut ErrorResult) -> bool {
-        false
+        self.focused.is_some()
     }
 
     pub fn GetCurrentScript(&self) -> Option<AbstractNode<ScriptView>> {
@@ -394,6 +622,27 @@ impl Document {
         self.VisibilityState()
     }
 
+    pub fn StyleSheets(&mut self) -> @mut StyleSheetList {
+        let (scope, cx) = self.get_scope_and_cx();
+        let list = match self.stylesheet_list {
+            Some(list) => list,
+            None => {
+                let list = StyleSheetList::new(cx, scope);
+                self.stylesheet_list = Some(list);
+                list
+            }
+        };
+        // Make objects for the style sheets that were added since the list was last looked at.
+        let window = *self.window.get_ref();
+        for index in range(list.sheets.len(), self.stylesheets.len()) {
+            let (ref source, ref href) = self.stylesheets[index];
+            let sheet = CSSStyleSheet::new(window, index, source.clone(), href.clone(), cx,
+                                           scope);
+            list.sheets.push(sheet);
+        }
+        list
+    }
+
     pub fn GetSelectedStyleSheetSet(&self) -> DOMString {
         null_string
     }
@@ -458,3 +707,46 @@ impl Document {
     }
 }
 
+/// The elements a state applies to when `node` has it: the element itself, and with `ancestors`
+/// the elements it's in as well.
+fn state_chain(node: Option<AbstractNode<ScriptView>>, ancestors: bool)
+               -> ~[AbstractNode<ScriptView>] {
+    let mut chain = ~[];
+    let mut current = node;
+    while current.is_some() {
+        let node = current.unwrap();
+        if node.is_element() {
+            chain.push(node);
+        }
+        if !ancestors {
+            break
+        }
+        current = node.parent_node();
+    }
+    chain
+}
+
+/// Moves a state from the elements it applied to because of `old` to those it applies to because
+/// of `new`, through `set`. Returns the elements whose state changed.
+fn move_state(old: Option<AbstractNode<ScriptView>>,
+              new: Option<AbstractNode<ScriptView>>,
+              ancestors: bool,
+              set: &fn(&mut ElementState, bool))
+              -> ~[AbstractNode<ScriptView>] {
+    let old_chain = state_chain(old, ancestors);
+    let new_chain = state_chain(new, ancestors);
+    let mut changed = ~[];
+    for &element in old_chain.iter() {
+        if !new_chain.contains(&element) {
+            element.as_mut_element(|element| set(&mut element.state, false));
+            changed.push(element);
+        }
+    }
+    for &element in new_chain.iter() {
+        if !old_chain.contains(&element) {
+            element.as_mut_element(|element| set(&mut element.state, true));
+            changed.push(element);
+        }
+    }
+    changed
+}
@@ -0,0 +1,53 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `DocumentFragment`.
+
+use dom::bindings::codegen::DocumentFragmentBinding;
+use dom::bindings::utils::{BindingObject, CacheableWrapper, WrapperCache, ErrorResult};
+use dom::node::{AbstractNode, DocumentFragmentNodeTypeId, Node, ScriptView};
+use dom::window::Window;
+
+use js::jsapi::{JSObject, JSContext};
+
+/// A lightweight container for nodes that aren't in a document. Inserting a fragment inserts its
+/// children instead, and leaves the fragment empty.
+pub struct DocumentFragment {
+    parent: Node<ScriptView>,
+}
+
+impl DocumentFragment {
+    /// Creates a new, empty document fragment.
+    pub fn new() -> DocumentFragment {
+        DocumentFragment {
+            parent: Node::new(DocumentFragmentNodeTypeId)
+        }
+    }
+
+    pub fn Constructor(owner: @mut Window, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
+        let cx = unsafe {(*owner.page).js_info.get_ref().js_compartment.cx.ptr};
+        let fragment = unsafe { Node::as_abstract_node(cx, @DocumentFragment::new()) };
+        do fragment.with_mut_base |base| {
+            base.owner_doc = Some(owner.Document());
+        }
+        fragment
+    }
+}
+
+impl CacheableWrapper for DocumentFragment {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        self.parent.get_wrappercache()
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        DocumentFragmentBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for DocumentFragment {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        self.parent.GetParentObject(cx)
+    }
+}
@@ -0,0 +1,298 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Editing the contents of `contenteditable` elements. The caret is a text node and a byte offset
+//! in its text, which the document keeps while an editing host has focus. Edits change text nodes
+//! and move nodes around through the usual DOM methods, so layout and mutation observers hear
+//! about them as they would about script's changes.
+
+use dom::bindings::utils::str;
+use dom::event::{Key, BackspaceKey, CharacterKey, DeleteKey, EnterKey, LeftKey, RightKey};
+use dom::htmlinputelement::edit_text;
+use dom::node::{AbstractNode, ScriptView, Node, Text};
+use html::hubbub_html_parser::{build_element_from_tag, clone_node};
+
+use gfx::text::util::uncompressed_offset;
+use servo_util::tree::TreeNodeRef;
+use std::ascii::StrAsciiExt;
+
+/// The elements that enter splits in two. Elsewhere, it breaks the line instead.
+static PARAGRAPH_TAGS: &'static [&'static str] = &[
+    "address", "blockquote", "div", "h1", "h2", "h3", "h4", "h5", "h6", "li", "p", "pre",
+];
+
+/// The editing host that `node` is in, or is: the outermost element that its `contenteditable`
+/// attribute makes editable, unless an element inside it turns editing back off.
+pub fn editing_host<View>(node: AbstractNode<View>) -> Option<AbstractNode<View>> {
+    let mut host = None;
+    let mut current = Some(node);
+    loop {
+        let node = match current {
+            Some(node) => node,
+            None => break,
+        };
+        if node.is_element() {
+            let editable = do node.with_imm_element |element| {
+                element.get_attr("contenteditable").map(|value| value.to_ascii_lower())
+            };
+            match editable {
+                Some(ref value) if "false" == *value => break,
+                Some(ref value) if value.is_empty() || "true" == *value => host = Some(node),
+                // Other values inherit.
+                _ => {}
+            }
+        }
+        current = node.parent_node();
+    }
+    host
+}
+
+/// Where the caret goes when the user clicks `host`. Layout found the click `offset` characters
+/// into the text laid out for `text`, if it was on text at all. Clicks elsewhere in the host put
+/// the caret at the end of its text.
+pub fn caret_for_click(host: AbstractNode<ScriptView>,
+                       hit: Option<(AbstractNode<ScriptView>, uint)>)
+                       -> Option<(AbstractNode<ScriptView>, uint)> {
+    match hit {
+        Some((text, offset)) if host.is_inclusive_ancestor_of(text) => {
+            Some((text, uncompressed_offset(data(text), offset)))
+        }
+        _ => {
+            let texts = text_nodes(host);
+            texts.last_opt().map(|&text| (text, data(text).len()))
+        }
+    }
+}
+
+/// Edits `host` as the user pressing `key` with the caret at `caret` asks. Returns where the caret
+/// is afterwards, or `None` if the key doesn't edit text.
+pub fn handle_key(host: AbstractNode<ScriptView>,
+                  caret: Option<(AbstractNode<ScriptView>, uint)>,
+                  key: Key)
+                  -> Option<(AbstractNode<ScriptView>, uint)> {
+    let (text, offset) = match (caret, key) {
+        (Some(caret), _) => caret,
+        // Typing into a host with no text starts some.
+        (None, CharacterKey(_)) => match append_text(host) {
+            Some(text) => (text, 0),
+            None => return None,
+        },
+        (None, _) => return None,
+    };
+    let value = data(text);
+    let offset = offset.min(&value.len());
+
+    match key {
+        EnterKey => return break_paragraph(host, text, offset),
+        BackspaceKey if offset == 0 => {
+            if remove_break_before(host, text) {
+                return Some((text, 0))
+            }
+            return match previous_text(host, text) {
+                Some(previous) => {
+                    let previous_value = data(previous);
+                    let end = previous_value.len();
+                    let (edited, caret) = edit_text(previous_value, end, BackspaceKey).unwrap();
+                    set_data(previous, edited);
+                    Some((previous, caret))
+                }
+                None => Some((text, 0)),
+            }
+        }
+        DeleteKey if offset == value.len() => {
+            for &next in next_text(host, text).iter() {
+                if !remove_break_before(host, next) {
+                    let (edited, _) = edit_text(data(next), 0, DeleteKey).unwrap();
+                    set_data(next, edited);
+                }
+            }
+            return Some((text, offset))
+        }
+        LeftKey if offset == 0 => {
+            return Some(match previous_text(host, text) {
+                Some(previous) => (previous, data(previous).len()),
+                None => (text, 0),
+            })
+        }
+        RightKey if offset == value.len() => {
+            return Some(match next_text(host, text) {
+                Some(next) => (next, 0),
+                None => (text, offset),
+            })
+        }
+        _ => {}
+    }
+
+    let (edited, offset) = match edit_text(value, offset, key) {
+        Some(edited) => edited,
+        None => return None,
+    };
+    if edited != value {
+        set_data(text, edited);
+    }
+    Some((text, offset))
+}
+
+/// Breaks the paragraph `text` is in at `offset`. The text after the caret, and everything after
+/// it in the paragraph, moves into a copy of the paragraph after it. Outside paragraphs, a line
+/// break goes in instead. Returns where the caret goes: at the start of what moved.
+fn break_paragraph(host: AbstractNode<ScriptView>, text: AbstractNode<ScriptView>, offset: uint)
+                   -> Option<(AbstractNode<ScriptView>, uint)> {
+    let cx = match text.with_base(|text| text.script_cx()) {
+        Some(cx) => cx,
+        None => return None,
+    };
+    let mut rv = Ok(());
+    let tail = text.with_mut_text(|text| text.SplitText(offset as u32, &mut rv));
+    if rv.is_err() {
+        return None
+    }
+
+    match paragraph(host, text) {
+        Some(paragraph) => {
+            // Copy each element from the text up to the paragraph, moving what follows the caret
+            // into the copies.
+            let mut moved = tail;
+            let mut parent = tail.parent_node().unwrap();
+            loop {
+                let copy = clone_node(cx, parent, false);
+                let mut next = Some(moved);
+                loop {
+                    let node = match next {
+                        Some(node) => node,
+                        None => break,
+                    };
+                    next = node.next_sibling();
+                    copy.pre_insert(node, None);
+                }
+                let grandparent = parent.parent_node().unwrap();
+                grandparent.pre_insert(copy, parent.next_sibling());
+                if parent == paragraph {
+                    break
+                }
+                moved = copy;
+                parent = grandparent;
+            }
+        }
+        None => {
+            let br = build_element_from_tag(cx, "br");
+            tail.parent_node().unwrap().pre_insert(br, Some(tail));
+        }
+    }
+    Some((tail, 0))
+}
+
+/// Removes the break between `text` and the text before it, if there is one: a line break right
+/// before it, or the start of its paragraph. A paragraph is joined to the one before. Returns
+/// whether there was a break to remove.
+fn remove_break_before(host: AbstractNode<ScriptView>, text: AbstractNode<ScriptView>) -> bool {
+    match text.prev_sibling() {
+        Some(br) if has_tag(br, "br") => {
+            text.parent_node().unwrap().pre_remove(br);
+            return true
+        }
+        _ => {}
+    }
+
+    let previous = match previous_text(host, text) {
+        Some(previous) => previous,
+        None => return false,
+    };
+    let this_paragraph = paragraph(host, text);
+    let previous_paragraph = paragraph(host, previous);
+    if this_paragraph == previous_paragraph {
+        return false
+    }
+    match (this_paragraph, previous_paragraph) {
+        (Some(this_paragraph), _) => {
+            // The paragraph's contents move to the end of the one before, or out of it if the
+            // text before isn't in a paragraph.
+            let parent = this_paragraph.parent_node().unwrap();
+            let children: ~[AbstractNode<ScriptView>] = this_paragraph.children().collect();
+            for &child in children.iter() {
+                match previous_paragraph {
+                    Some(previous_paragraph) => previous_paragraph.pre_insert(child, None),
+                    None => parent.pre_insert(child, Some(this_paragraph)),
+                };
+            }
+            parent.pre_remove(this_paragraph);
+        }
+        (None, Some(previous_paragraph)) => {
+            previous_paragraph.pre_insert(text, None);
+        }
+        (None, None) => return false,
+    }
+    true
+}
+
+/// The paragraph `node` is in, inside `host`.
+fn paragraph(host: AbstractNode<ScriptView>, node: AbstractNode<ScriptView>)
+             -> Option<AbstractNode<ScriptView>> {
+    let mut current = node.parent_node();
+    loop {
+        match current {
+            Some(element) if element != host => {
+                if PARAGRAPH_TAGS.iter().any(|&tag| has_tag(element, tag)) {
+                    return Some(element)
+                }
+                current = element.parent_node();
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Adds an empty text node to the end of `host`, for the user to type into.
+fn append_text(host: AbstractNode<ScriptView>) -> Option<AbstractNode<ScriptView>> {
+    let cx = match host.with_base(|host| host.script_cx()) {
+        Some(cx) => cx,
+        None => return None,
+    };
+    let text = unsafe { Node::as_abstract_node(cx, @Text::new(~"")) };
+    host.pre_insert(text, None);
+    Some(text)
+}
+
+/// The text nodes in `host`, in document order.
+fn text_nodes(host: AbstractNode<ScriptView>) -> ~[AbstractNode<ScriptView>] {
+    let mut texts = ~[];
+    for child in host.children() {
+        if child.is_text() {
+            texts.push(child);
+        } else {
+            texts.push_all_move(text_nodes(child));
+        }
+    }
+    texts
+}
+
+fn previous_text(host: AbstractNode<ScriptView>, text: AbstractNode<ScriptView>)
+                 -> Option<AbstractNode<ScriptView>> {
+    let texts = text_nodes(host);
+    match texts.iter().position(|&other| other == text) {
+        Some(index) if index > 0 => Some(texts[index - 1]),
+        _ => None,
+    }
+}
+
+fn next_text(host: AbstractNode<ScriptView>, text: AbstractNode<ScriptView>)
+             -> Option<AbstractNode<ScriptView>> {
+    let texts = text_nodes(host);
+    match texts.iter().position(|&other| other == text) {
+        Some(index) if index + 1 < texts.len() => Some(texts[index + 1]),
+        _ => None,
+    }
+}
+
+fn has_tag(node: AbstractNode<ScriptView>, tag: &str) -> bool {
+    node.is_element() && node.with_imm_element(|element| tag == element.tag_name)
+}
+
+fn data(text: AbstractNode<ScriptView>) -> ~str {
+    text.with_imm_text(|text| text.parent.data.to_str())
+}
+
+fn set_data(text: AbstractNode<ScriptView>, data: ~str) {
+    text.with_mut_text(|text| text.parent.set_data(str(data.clone())))
+}
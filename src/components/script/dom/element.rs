@@ -5,30 +5,38 @@
 //! Element nodes.
 
 use dom::bindings::codegen::{HTMLAnchorElementBinding, HTMLAppletElementBinding,
-                             HTMLAreaElementBinding, HTMLBaseElementBinding,
+                             HTMLAreaElementBinding, HTMLAudioElementBinding,
+                             HTMLBaseElementBinding,
                              HTMLBodyElementBinding, HTMLBRElementBinding,
                              HTMLCanvasElementBinding, HTMLDataElementBinding,
                              HTMLDListElementBinding, HTMLDivElementBinding,
+                             HTMLFormElementBinding,
                              HTMLHeadElementBinding, HTMLHRElementBinding,
                              HTMLHtmlElementBinding, HTMLIFrameElementBinding,
                              HTMLImageElementBinding, HTMLInputElementBinding,
                              HTMLLIElementBinding,
                              HTMLLinkElementBinding, HTMLMetaElementBinding,
-                             HTMLOListElementBinding, HTMLParagraphElementBinding,
+                             HTMLOListElementBinding, HTMLOptionElementBinding,
+                             HTMLParagraphElementBinding,
                              HTMLProgressElementBinding, HTMLQuoteElementBinding,
-                             HTMLScriptElementBinding, HTMLSourceElementBinding, HTMLSpanElementBinding,
+                             HTMLScriptElementBinding, HTMLSelectElementBinding,
+                             HTMLSourceElementBinding, HTMLSpanElementBinding,
                              HTMLStyleElementBinding, HTMLTableElementBinding,
                              HTMLTableCaptionElementBinding, HTMLTableCellElementBinding,
                              HTMLTableColElementBinding, HTMLTableRowElementBinding,
                              HTMLTableSectionElementBinding, HTMLTextAreaElementBinding,
-                             HTMLTimeElementBinding, HTMLTitleElementBinding, HTMLUListElementBinding};
+                             HTMLTimeElementBinding, HTMLTitleElementBinding, HTMLUListElementBinding,
+                             HTMLVideoElementBinding};
 use dom::bindings::utils::{null_string, str};
+use dom::bindings::utils::{NoModificationAllowed, Syntax};
 use dom::bindings::utils::{BindingObject, CacheableWrapper, DOMString, ErrorResult, WrapperCache};
 use dom::clientrect::ClientRect;
 use dom::clientrectlist::ClientRectList;
+use dom::eventtarget::EventListeners;
 use dom::htmlanchorelement::HTMLAnchorElement;
 use dom::htmlappletelement::HTMLAppletElement;
 use dom::htmlareaelement::HTMLAreaElement;
+use dom::htmlaudioelement::HTMLAudioElement;
 use dom::htmlbaseelement::HTMLBaseElement;
 use dom::htmlbodyelement::HTMLBodyElement;
 use dom::htmlbrelement::HTMLBRElement;
@@ -37,17 +45,22 @@ use dom::htmlcollection::HTMLCollection;
 use dom::htmldataelement::HTMLDataElement;
 use dom::htmldlistelement::HTMLDListElement;
 use dom::htmlelement::HTMLElement;
+use dom::htmlformelement::HTMLFormElement;
 use dom::htmlhrelement::HTMLHRElement;
 use dom::htmliframeelement::HTMLIFrameElement;
 use dom::htmlimageelement::HTMLImageElement;
-use dom::htmlinputelement::HTMLInputElement;
+use dom::htmlinputelement::{HTMLInputElement, is_checkable_type, is_text_field_type};
 use dom::htmllielement::HTMLLIElement;
 use dom::htmllinkelement::HTMLLinkElement;
 use dom::htmlmetaelement::HTMLMetaElement;
 use dom::htmlolistelement::HTMLOListElement;
+use dom::htmloptionelement;
+use dom::htmloptionelement::HTMLOptionElement;
 use dom::htmlprogresselement::HTMLProgressElement;
 use dom::htmlquoteelement::HTMLQuoteElement;
 use dom::htmlscriptelement::HTMLScriptElement;
+use dom::htmlselectelement;
+use dom::htmlselectelement::HTMLSelectElement;
 use dom::htmlsourceelement::HTMLSourceElement;
 use dom::htmlstyleelement::HTMLStyleElement;
 use dom::htmltableelement::HTMLTableElement;
@@ -56,20 +69,29 @@ use dom::htmltablecellelement::HTMLTableCellElement;
 use dom::htmltablecolelement::HTMLTableColElement;
 use dom::htmltablerowelement::HTMLTableRowElement;
 use dom::htmltablesectionelement::HTMLTableSectionElement;
+use dom::htmltextareaelement;
 use dom::htmltextareaelement::HTMLTextAreaElement;
 use dom::htmltimeelement::HTMLTimeElement;
 use dom::htmltitleelement::HTMLTitleElement;
 use dom::htmlulistelement::HTMLUListElement;
+use dom::htmlvideoelement::HTMLVideoElement;
+use dom::mutationobserver::{AttributeMutation, ChildListMutation};
 use dom::node::{ElementNodeTypeId, Node, ScriptView, AbstractNode};
 use layout_interface::{ContentBoxQuery, ContentBoxResponse, ContentBoxesQuery};
-use layout_interface::{ContentBoxesResponse};
+use layout_interface::{ContentBoxesResponse, ScrollArea, ScrollAreaQuery};
 use newcss::stylesheet::Stylesheet;
 
-use js::jsapi::{JSContext, JSObject};
+use js::jsapi::{JSContext, JSObject, JSVal};
+use geom::point::Point2D;
+use geom::size::Size2D;
 
+use html::hubbub_html_parser::parse_fragment;
+use html::serializer;
+use servo_util::tree::TreeNodeRef;
+use std::ascii::StrAsciiExt;
 use std::cell::Cell;
-use std::comm;
 use std::str::eq_slice;
+use std::util::replace;
 use std::FromStr;
 
 pub struct Element {
@@ -77,6 +99,40 @@ pub struct Element {
     tag_name: ~str,     // TODO: This should be an atom, not a ~str.
     attrs: ~[Attr],
     style_attribute: Option<Stylesheet>,
+    /// Which of the dynamic pseudo-classes the element matches.
+    state: ElementState,
+    /// The listeners added with `addEventListener`.
+    listeners: EventListeners,
+    /// How far the element has scrolled its overflow, in px from the top left, when it's a box
+    /// that scrolls its overflow. Layout keeps it within the content.
+    scroll_offset: Point2D<f32>,
+    /// Whether the attributes changed since layout was last sent a snapshot of them.
+    attrs_changed: bool,
+}
+
+/// The states of an element that follow what the user does with it, as the `:hover`, `:active`,
+/// `:focus` and `:visited` pseudo-classes match them. Script keeps them up to date.
+#[deriving(Eq)]
+pub struct ElementState {
+    /// Whether the pointer is over the element or one of its descendants.
+    hover: bool,
+    /// Whether the element or one of its descendants is being pressed on.
+    active: bool,
+    /// Whether the element has focus.
+    focus: bool,
+    /// Whether the element is a link to a page in the history.
+    visited: bool,
+}
+
+impl ElementState {
+    pub fn new() -> ElementState {
+        ElementState {
+            hover: false,
+            active: false,
+            focus: false,
+            visited: false,
+        }
+    }
 }
 
 impl CacheableWrapper for Element {
@@ -101,6 +157,7 @@ pub enum ElementTypeId {
     HTMLAnchorElementTypeId,
     HTMLAppletElementTypeId,
     HTMLAreaElementTypeId,
+    HTMLAudioElementTypeId,
     HTMLBaseElementTypeId,
     HTMLBRElementTypeId,
     HTMLBodyElementTypeId,
@@ -141,6 +198,11 @@ pub enum ElementTypeId {
     HTMLTimeElementTypeId,
     HTMLTitleElementTypeId,
     HTMLUListElementTypeId,
+    HTMLVideoElementTypeId,
+    SVGSVGElementTypeId,
+    SVGRectElementTypeId,
+    SVGCircleElementTypeId,
+    SVGPathElementTypeId,
     UnknownElementTypeId,
 }
 
@@ -150,12 +212,9 @@ pub enum ElementTypeId {
 
 pub struct HTMLDivElement       { parent: HTMLElement }
 pub struct HTMLFontElement      { parent: HTMLElement }
-pub struct HTMLFormElement      { parent: HTMLElement }
 pub struct HTMLHeadElement      { parent: HTMLElement }
 pub struct HTMLHtmlElement      { parent: HTMLElement }
-pub struct HTMLOptionElement    { parent: HTMLElement }
 pub struct HTMLParagraphElement { parent: HTMLElement }
-pub struct HTMLSelectElement    { parent: HTMLElement }
 pub struct HTMLSmallElement     { parent: HTMLElement }
 pub struct HTMLSpanElement      { parent: HTMLElement }
 pub struct UnknownElement       { parent: HTMLElement }
@@ -220,6 +279,8 @@ generate_cacheable_wrapper!(HTMLAppletElement, HTMLAppletElementBinding::Wrap)
 generate_binding_object!(HTMLAppletElement)
 generate_cacheable_wrapper!(HTMLAreaElement, HTMLAreaElementBinding::Wrap)
 generate_binding_object!(HTMLAreaElement)
+generate_cacheable_wrapper!(HTMLAudioElement, HTMLAudioElementBinding::Wrap)
+generate_binding_object!(HTMLAudioElement)
 generate_cacheable_wrapper!(HTMLBaseElement, HTMLBaseElementBinding::Wrap)
 generate_binding_object!(HTMLBaseElement)
 generate_cacheable_wrapper!(HTMLBodyElement, HTMLBodyElementBinding::Wrap)
@@ -238,6 +299,8 @@ generate_cacheable_wrapper!(HTMLDataElement, HTMLDataElementBinding::Wrap)
 generate_binding_object!(HTMLDataElement)
 generate_cacheable_wrapper!(HTMLDivElement, HTMLDivElementBinding::Wrap)
 generate_binding_object!(HTMLDivElement)
+generate_cacheable_wrapper!(HTMLFormElement, HTMLFormElementBinding::Wrap)
+generate_binding_object!(HTMLFormElement)
 generate_cacheable_wrapper!(HTMLIFrameElement, HTMLIFrameElementBinding::Wrap)
 generate_binding_object!(HTMLIFrameElement)
 generate_cacheable_wrapper!(HTMLImageElement, HTMLImageElementBinding::Wrap)
@@ -252,6 +315,8 @@ generate_cacheable_wrapper!(HTMLMetaElement, HTMLMetaElementBinding::Wrap)
 generate_binding_object!(HTMLMetaElement)
 generate_cacheable_wrapper!(HTMLOListElement, HTMLOListElementBinding::Wrap)
 generate_binding_object!(HTMLOListElement)
+generate_cacheable_wrapper!(HTMLOptionElement, HTMLOptionElementBinding::Wrap)
+generate_binding_object!(HTMLOptionElement)
 generate_cacheable_wrapper!(HTMLParagraphElement, HTMLParagraphElementBinding::Wrap)
 generate_binding_object!(HTMLParagraphElement)
 generate_cacheable_wrapper!(HTMLProgressElement, HTMLProgressElementBinding::Wrap)
@@ -260,6 +325,8 @@ generate_cacheable_wrapper!(HTMLQuoteElement, HTMLQuoteElementBinding::Wrap)
 generate_binding_object!(HTMLQuoteElement)
 generate_cacheable_wrapper!(HTMLScriptElement, HTMLScriptElementBinding::Wrap)
 generate_binding_object!(HTMLScriptElement)
+generate_cacheable_wrapper!(HTMLSelectElement, HTMLSelectElementBinding::Wrap)
+generate_binding_object!(HTMLSelectElement)
 generate_cacheable_wrapper!(HTMLSourceElement, HTMLSourceElementBinding::Wrap)
 generate_binding_object!(HTMLSourceElement)
 generate_cacheable_wrapper!(HTMLSpanElement, HTMLSpanElementBinding::Wrap)
@@ -286,6 +353,8 @@ generate_cacheable_wrapper!(HTMLTimeElement, HTMLTimeElementBinding::Wrap)
 generate_binding_object!(HTMLTimeElement)
 generate_cacheable_wrapper!(HTMLUListElement, HTMLUListElementBinding::Wrap)
 generate_binding_object!(HTMLUListElement)
+generate_cacheable_wrapper!(HTMLVideoElement, HTMLVideoElementBinding::Wrap)
+generate_binding_object!(HTMLVideoElement)
 
 //
 // Fancier elements
@@ -307,6 +376,10 @@ impl<'self> Element {
             tag_name: tag_name,
             attrs: ~[],
             style_attribute: None,
+            state: ElementState::new(),
+            listeners: EventListeners::new(),
+            scroll_offset: Point2D(0f32, 0f32),
+            attrs_changed: false,
         }
     }
 
@@ -321,20 +394,30 @@ impl<'self> Element {
         return None;
     }
 
+    /// Copies the attributes for layout, which sees no changes made after this until the next.
+    /// Use `element::snapshot` to copy the state of form controls too.
+    pub fn snapshot(&mut self) -> ElementSnapshot {
+        self.attrs_changed = false;
+        ElementSnapshot {
+            attrs: self.attrs.clone(),
+            control: None,
+        }
+    }
+
     pub fn set_attr(&mut self, name: &DOMString, value: &DOMString) {
         let name = name.to_str();
         let value_cell = Cell::new(value.to_str());
-        let mut found = false;
+        let mut old_value = None;
         for attr in self.attrs.mut_iter() {
             if eq_slice(attr.name, name) {
-                attr.value = value_cell.take().clone();
-                found = true;
+                old_value = Some(replace(&mut attr.value, value_cell.take()));
                 break;
             }
         }
-        if !found {
+        if old_value.is_none() {
             self.attrs.push(Attr::new(name.to_str(), value_cell.take().clone()));
         }
+        self.attrs_changed = true;
 
         if "style" == name {
             self.style_attribute = Some(
@@ -343,10 +426,135 @@ impl<'self> Element {
                     value.get_ref()));
         }
 
-        match self.parent.owner_doc {
-            Some(owner) => do owner.with_base |owner| { owner.content_changed() },
+        match self.parent.abstract {
+            Some(node) => node.mutated(AttributeMutation(name, old_value)),
+            None => {
+                for owner in self.parent.owner_doc.iter() {
+                    owner.with_base(|owner| owner.content_changed());
+                }
+            }
+        }
+    }
+
+    pub fn remove_attr(&mut self, name: &str) {
+        let index = match self.attrs.iter().position(|attr| eq_slice(attr.name, name)) {
+            Some(index) => index,
+            None => return,
+        };
+        let old_value = self.attrs.remove(index).value;
+        self.attrs_changed = true;
+
+        if "style" == name {
+            self.style_attribute = None;
+        }
+
+        match self.parent.abstract {
+            Some(node) => node.mutated(AttributeMutation(name.to_owned(), Some(old_value))),
+            None => {
+                for owner in self.parent.owner_doc.iter() {
+                    owner.with_base(|owner| owner.content_changed());
+                }
+            }
+        }
+    }
+
+    /// Scrolls the element's overflow to `offset`, and has it laid out again. It's sent a
+    /// `scroll` event on the next animation frame.
+    pub fn set_scroll_offset(&mut self, offset: Point2D<f32>) {
+        if self.scroll_offset == offset {
+            return
+        }
+        self.scroll_offset = offset;
+        for owner in self.parent.owner_doc.iter() {
+            owner.with_base(|owner| owner.content_changed());
+            for window in owner.with_base(|owner| owner.window).iter() {
+                window.queue_scroll_event(self.parent.abstract);
+            }
+        }
+    }
+
+    /// What layout made of the element's overflow, if it scrolls it.
+    fn scroll_area(&self) -> Option<ScrollArea> {
+        let node = match self.parent.abstract {
+            Some(node) => node,
+            None => return None,
+        };
+        let page = match self.parent.owner_doc.chain(|doc| doc.with_base(|doc| doc.window)) {
+            Some(win) => win.page,
+            None => return None,
+        };
+        match unsafe { (*page).query_layout(|chan| ScrollAreaQuery(node, chan)) } {
+            Ok(area) => Some(area),
+            Err(()) => None,
+        }
+    }
+
+    /// Scrolls the element's overflow to `offset`, kept within its content. Elements that don't
+    /// scroll their overflow stay put.
+    fn scroll_to(&mut self, offset: Point2D<f32>) {
+        for area in self.scroll_area().iter() {
+            self.set_scroll_offset(area.clamp_offset(offset));
+        }
+    }
+
+    /// The size of what the element scrolls, or of its box if it doesn't scroll its overflow.
+    fn scroll_size(&self) -> Size2D<f32> {
+        match self.scroll_area() {
+            Some(area) => {
+                return Size2D(area.content_size.width.to_f32(), area.content_size.height.to_f32())
+            }
             None => {}
         }
+        let node = match self.parent.abstract {
+            Some(node) => node,
+            None => return Size2D(0f32, 0f32),
+        };
+        let page = match self.parent.owner_doc.chain(|doc| doc.with_base(|doc| doc.window)) {
+            Some(win) => win.page,
+            None => return Size2D(0f32, 0f32),
+        };
+        match unsafe { (*page).query_layout(|chan| ContentBoxQuery(node, chan)) } {
+            Ok(ContentBoxResponse(rect)) => {
+                Size2D(rect.size.width.to_f32(), rect.size.height.to_f32())
+            }
+            Err(()) => Size2D(0f32, 0f32),
+        }
+    }
+
+    /// Parses `markup` in the context of `context`, for the string-based DOM manipulation APIs.
+    /// Returns the nodes, which belong to this element's document but aren't in the tree yet, or
+    /// `None` if this element isn't in a document with a window.
+    fn parse_fragment(&self, context: AbstractNode<ScriptView>, markup: &DOMString)
+                      -> Option<~[AbstractNode<ScriptView>]> {
+        let doc = match self.parent.owner_doc {
+            Some(doc) => doc,
+            None => {
+                debug!("no document");
+                return None
+            }
+        };
+        let page = match doc.with_base(|doc| doc.window) {
+            Some(win) => win.page,
+            None => {
+                debug!("no window");
+                return None
+            }
+        };
+        let (cx, base_url) = unsafe {
+            let cx = (*page).js_info.get_ref().js_compartment.cx.ptr;
+            match (*page).url {
+                Some((ref url, _)) => (cx, url.clone()),
+                None => return None,
+            }
+        };
+
+        let nodes = parse_fragment(cx, context, markup.to_str(), &base_url);
+        for &node in nodes.iter() {
+            do node.with_mut_base |node| {
+                node.add_to_doc(doc);
+            }
+        }
+        Some(nodes)
     }
 
     fn get_scope_and_cx(&self) -> (*JSObject, *JSContext) {
@@ -444,8 +652,7 @@ impl Element {
                         let node = abstract_self;
                         assert!(node.is_element());
                         let page = win.page;
-                        let (port, chan) = comm::stream();
-                        match unsafe {(*page).query_layout(ContentBoxesQuery(node, chan), port)} {
+                        match unsafe {(*page).query_layout(|chan| ContentBoxesQuery(node, chan))} {
                             Ok(ContentBoxesResponse(rects)) => {
                                 let cx = unsafe {(*page).js_info.get_ref().js_compartment.cx.ptr};
                                 let cache = win.get_wrappercache();
@@ -490,8 +697,7 @@ impl Element {
                         let page = win.page;
                         let node = abstract_self;
                         assert!(node.is_element());
-                        let (port, chan) = comm::stream();
-                        match unsafe{(*page).query_layout(ContentBoxQuery(node, chan), port)} {
+                        match unsafe{(*page).query_layout(|chan| ContentBoxQuery(node, chan))} {
                             Ok(ContentBoxResponse(rect)) => {
                                 let cx = unsafe {(*page).js_info.get_ref().js_compartment.cx.ptr};
                                 let cache = win.get_wrappercache();
@@ -518,25 +724,33 @@ impl Element {
     }
 
     pub fn ScrollTop(&self) -> i32 {
-        0
+        match self.scroll_area() {
+            Some(area) => area.clamp_offset(self.scroll_offset).y as i32,
+            None => 0,
+        }
     }
 
-    pub fn SetScrollTop(&mut self, _scroll_top: i32) {
+    pub fn SetScrollTop(&mut self, scroll_top: i32) {
+        self.scroll_to(Point2D(self.scroll_offset.x, scroll_top as f32));
     }
 
     pub fn ScrollLeft(&self) -> i32 {
-        0
+        match self.scroll_area() {
+            Some(area) => area.clamp_offset(self.scroll_offset).x as i32,
+            None => 0,
+        }
     }
 
-    pub fn SetScrollLeft(&mut self, _scroll_left: i32) {
+    pub fn SetScrollLeft(&mut self, scroll_left: i32) {
+        self.scroll_to(Point2D(scroll_left as f32, self.scroll_offset.y));
     }
 
     pub fn ScrollWidth(&self) -> i32 {
-        0
+        self.scroll_size().width as i32
     }
 
     pub fn ScrollHeight(&self) -> i32 {
-        0
+        self.scroll_size().height as i32
     }
 
     pub fn ClientTop(&self) -> i32 {
@@ -555,28 +769,128 @@ impl Element {
         0
     }
 
-    pub fn GetInnerHTML(&self, _rv: &mut ErrorResult) -> DOMString {
-        null_string
+    pub fn GetInnerHTML(&self, abstract_self: AbstractNode<ScriptView>, _rv: &mut ErrorResult)
+                        -> DOMString {
+        str(serializer::serialize_children(abstract_self))
     }
 
-    pub fn SetInnerHTML(&mut self, _value: &DOMString, _rv: &mut ErrorResult) {
-    }
+    pub fn SetInnerHTML(&mut self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        value: &DOMString,
+                        _rv: &mut ErrorResult) {
+        let nodes = match self.parse_fragment(abstract_self, value) {
+            Some(nodes) => nodes,
+            None => return,
+        };
 
-    pub fn GetOuterHTML(&self, _rv: &mut ErrorResult) -> DOMString {
-        null_string
+        let old_children: ~[AbstractNode<ScriptView>] = abstract_self.children().collect();
+        for &child in old_children.iter() {
+            abstract_self.remove_child(child);
+        }
+        for &node in nodes.iter() {
+            abstract_self.add_child(node);
+        }
+        abstract_self.mutated(ChildListMutation(nodes, old_children, None, None));
     }
 
-    pub fn SetOuterHTML(&mut self, _value: &DOMString, _rv: &mut ErrorResult) {
+    pub fn GetOuterHTML(&self, abstract_self: AbstractNode<ScriptView>, _rv: &mut ErrorResult)
+                        -> DOMString {
+        str(serializer::serialize(abstract_self))
     }
 
-    pub fn InsertAdjacentHTML(&mut self, _position: &DOMString, _text: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetOuterHTML(&mut self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        value: &DOMString,
+                        rv: &mut ErrorResult) {
+        let parent = match abstract_self.parent_node() {
+            Some(parent) => parent,
+            None => {
+                *rv = Err(NoModificationAllowed);
+                return
+            }
+        };
+        let nodes = match self.parse_fragment(parent, value) {
+            Some(nodes) => nodes,
+            None => return,
+        };
+
+        let previous_sibling = abstract_self.prev_sibling();
+        let next_sibling = abstract_self.next_sibling();
+        for &node in nodes.iter() {
+            parent.insert_before(node, abstract_self);
+        }
+        parent.remove_child(abstract_self);
+        parent.mutated(ChildListMutation(nodes, ~[abstract_self], previous_sibling, next_sibling));
+    }
+
+    /// Parses `text` and inserts the nodes before this element ("beforebegin"), before its first
+    /// child ("afterbegin"), after its last child ("beforeend") or after it ("afterend").
+    pub fn InsertAdjacentHTML(&mut self,
+                              abstract_self: AbstractNode<ScriptView>,
+                              position: &DOMString,
+                              text: &DOMString,
+                              rv: &mut ErrorResult) {
+        let position = position.to_str().to_ascii_lower();
+        let inside = match position.as_slice() {
+            "afterbegin" | "beforeend" => true,
+            "beforebegin" | "afterend" => false,
+            _ => {
+                *rv = Err(Syntax);
+                return
+            }
+        };
+        let context = if inside {
+            abstract_self
+        } else {
+            match abstract_self.parent_node() {
+                Some(parent) => parent,
+                None => {
+                    *rv = Err(NoModificationAllowed);
+                    return
+                }
+            }
+        };
+        let nodes = match self.parse_fragment(context, text) {
+            Some(nodes) => nodes,
+            None => return,
+        };
+
+        // The node to insert the nodes before, if not at the end of `context`.
+        let before = match position.as_slice() {
+            "beforebegin" => Some(abstract_self),
+            "afterbegin" => abstract_self.first_child(),
+            "beforeend" => None,
+            _ => abstract_self.next_sibling(),
+        };
+        let previous_sibling = match before {
+            Some(before) => before.prev_sibling(),
+            None => context.last_child(),
+        };
+        for &node in nodes.iter() {
+            match before {
+                Some(before) => context.insert_before(node, before),
+                None => context.add_child(node),
+            }
+        }
+        context.mutated(ChildListMutation(nodes, ~[], previous_sibling, before));
     }
 
     pub fn QuerySelector(&self, _selectors: &DOMString, _rv: &mut ErrorResult) -> Option<AbstractNode<ScriptView>> {
         None
     }
+
+    pub fn AddEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                            _capture: bool) {
+        self.listeners.add(type_, listener);
+    }
+
+    pub fn RemoveEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                               _capture: bool) {
+        self.listeners.remove(type_, listener);
+    }
 }
 
+#[deriving(Clone)]
 pub struct Attr {
     name: ~str,
     value: ~str,
@@ -591,6 +905,114 @@ impl Attr {
     }
 }
 
+/// A copy of an element's attributes, taken by script when it asks for a reflow. Layout reads
+/// attributes from these rather than from the element, which script may change while layout runs.
+#[deriving(Clone)]
+pub struct ElementSnapshot {
+    attrs: ~[Attr],
+    /// The state of the form control the element is, if it's one.
+    control: Option<ControlSnapshot>,
+}
+
+/// What layout shows of a form control, copied along with its element's attributes.
+#[deriving(Clone)]
+pub struct ControlSnapshot {
+    /// The type of an input, as `HTMLInputElement::type_` gives it. Empty for other controls.
+    type_: ~str,
+    /// The text a text field or text area shows.
+    value: ~str,
+    /// Where the caret is in `value`, as a byte offset, if the control shows one.
+    caret: Option<uint>,
+    /// Whether a checkbox, radio button or option is checked.
+    checked: bool,
+    /// The labels of the options of a select.
+    labels: ~[~str],
+    /// Which option of a select is selected, if one is.
+    selected: Option<uint>,
+}
+
+impl ControlSnapshot {
+    /// A snapshot of an empty, unchecked control without a caret.
+    pub fn new() -> ControlSnapshot {
+        ControlSnapshot {
+            type_: ~"",
+            value: ~"",
+            caret: None,
+            checked: false,
+            labels: ~[],
+            selected: None,
+        }
+    }
+
+    /// Whether the control is an input the user types text into.
+    pub fn is_text_field(&self) -> bool {
+        is_text_field_type(self.type_.as_slice())
+    }
+
+    /// Whether the control is a checkbox or radio button.
+    pub fn is_checkable(&self) -> bool {
+        is_checkable_type(self.type_.as_slice())
+    }
+}
+
+/// Snapshots `element` for layout: its attributes, and its state if it's a form control.
+pub fn snapshot(element: AbstractNode<ScriptView>) -> ElementSnapshot {
+    let mut snapshot = element.as_mut_element(|element| element.snapshot());
+    snapshot.control = control_snapshot(element);
+    snapshot
+}
+
+/// Whether `element` is a form control, whose snapshot depends on its state and on the nodes
+/// under it as well as on its attributes.
+pub fn is_form_control<View>(element: AbstractNode<View>) -> bool {
+    element.is_input_element() || element.is_textarea_element() || element.is_select_element() ||
+        element.is_option_element()
+}
+
+fn control_snapshot(element: AbstractNode<ScriptView>) -> Option<ControlSnapshot> {
+    let mut control = ControlSnapshot::new();
+    if element.is_input_element() {
+        do element.with_imm_input_element |input| {
+            control.type_ = input.type_();
+            control.value = input.value();
+            control.caret = input.caret();
+            control.checked = input.checked();
+        }
+    } else if element.is_textarea_element() {
+        control.value = htmltextareaelement::value(element);
+        control.caret = element.with_imm_textarea_element(|textarea| textarea.caret());
+    } else if element.is_select_element() {
+        let options = htmlselectelement::options(element);
+        control.labels = options.iter().map(|&option| htmloptionelement::label(option)).collect();
+        control.selected = htmlselectelement::selected_index(element);
+    } else if element.is_option_element() {
+        control.checked = element.with_imm_option_element(|option| option.selected());
+    } else {
+        return None
+    }
+    Some(control)
+}
+
+impl ElementSnapshot {
+    /// A snapshot of an element without attributes.
+    pub fn empty() -> ElementSnapshot {
+        ElementSnapshot {
+            attrs: ~[],
+            control: None,
+        }
+    }
+
+    pub fn get_attr<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.attrs.iter().find(|attr| eq_slice(attr.name, name)).map(|attr| attr.value.as_slice())
+    }
+
+    /// Like `get_attr`, but ignoring the case of the name, as selectors match HTML attributes.
+    pub fn get_attr_ignoring_case<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.attrs.iter().find(|attr| attr.name.eq_ignore_ascii_case(name))
+                         .map(|attr| attr.value.as_slice())
+    }
+}
+
 pub enum HeadingLevel {
     Heading1,
     Heading2,
@@ -23,6 +23,42 @@ pub enum Event_ {
     ClickEvent(uint, Point2D<f32>),
     MouseDownEvent(uint, Point2D<f32>),
     MouseUpEvent(uint, Point2D<f32>),
+    /// The mouse moved while a button was held down.
+    MouseMoveEvent(Point2D<f32>),
+    ScrollEvent(Point2D<f32>),
+    /// A finger touched the page at the given point. The id tells it apart from the other fingers
+    /// on the screen, and stays the same until it's lifted.
+    TouchStartEvent(uint, Point2D<f32>),
+    TouchMoveEvent(uint, Point2D<f32>),
+    TouchEndEvent(uint, Point2D<f32>),
+    /// The system took the touch of the given finger over.
+    TouchCancelEvent(uint, Point2D<f32>),
+    KeyEvent(Key),
+    /// The user asked to copy the selection to the clipboard.
+    CopyEvent,
+    /// The user pasted the given text from the clipboard.
+    PasteEvent(~str),
+    /// The user picked an option from the popup list of the focused `<select>`, or dismissed it.
+    SelectOptionEvent(Option<uint>),
+}
+
+/// A key the user pressed, as far as editing text cares.
+#[deriving(Clone, Eq)]
+pub enum Key {
+    CharacterKey(char),
+    BackspaceKey,
+    DeleteKey,
+    LeftKey,
+    RightKey,
+    HomeKey,
+    EndKey,
+    EnterKey,
+    UpKey,
+    DownKey,
+    /// Tab, which moves focus to the next element in the sequential navigation order.
+    TabKey,
+    /// Shift+Tab, which moves focus to the previous element.
+    ShiftTabKey,
 }
 
 pub struct Event {
@@ -4,12 +4,15 @@
 
 use dom::bindings::codegen::EventTargetBinding;
 use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::DOMString;
 use script_task::page_from_context;
 
+use js::JSVAL_NULL;
 use js::glue::RUST_OBJECT_TO_JSVAL;
-use js::jsapi::{JSObject, JSContext, JSVal};
+use js::jsapi::{JSObject, JSContext, JSVal, JS_CallFunctionValue};
 
 use std::cast;
+use std::hashmap::HashMap;
 
 pub struct EventTarget {
     wrapper: WrapperCache
@@ -27,6 +30,83 @@ impl EventTarget {
     }
 }
 
+/// The JS listeners registered on an object, keyed by event type, along with the handlers set
+/// through its `on*` attributes. Objects that are not yet `EventTarget`s in the bindings (such as
+/// `Window`) embed one of these directly.
+pub struct EventListeners {
+    priv listeners: HashMap<~str, ~[JSVal]>,
+    priv handlers: HashMap<~str, JSVal>,
+}
+
+impl EventListeners {
+    pub fn new() -> EventListeners {
+        EventListeners {
+            listeners: HashMap::new(),
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers a listener. Registering the same listener twice has no effect.
+    pub fn add(&mut self, type_: &DOMString, listener: JSVal) {
+        let listeners = self.listeners.find_or_insert_with(type_.to_str(), |_| ~[]);
+        if !listeners.contains(&listener) {
+            listeners.push(listener);
+        }
+    }
+
+    pub fn remove(&mut self, type_: &DOMString, listener: JSVal) {
+        match self.listeners.find_mut(&type_.to_str()) {
+            Some(listeners) => {
+                let position = listeners.iter().position(|&l| l == listener);
+                for &index in position.iter() {
+                    listeners.remove(index);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// The handler set through the `on*` attribute for the given event type, or null.
+    pub fn handler(&self, type_: &str) -> JSVal {
+        match self.handlers.find_equiv(&type_) {
+            Some(&handler) => handler,
+            None => JSVAL_NULL,
+        }
+    }
+
+    pub fn set_handler(&mut self, type_: &str, handler: JSVal) {
+        self.handlers.insert(type_.to_owned(), handler);
+    }
+
+    /// Returns a copy of the `on*` handler and the listeners for the given event type, in the
+    /// order they're called, so that listeners can add or remove listeners while the event is
+    /// being dispatched.
+    pub fn get(&self, type_: &DOMString) -> ~[JSVal] {
+        let type_ = type_.to_str();
+        let mut listeners = match self.listeners.find(&type_) {
+            Some(listeners) => listeners.clone(),
+            None => ~[],
+        };
+        let handler = self.handler(type_);
+        if handler != JSVAL_NULL {
+            listeners.unshift(handler);
+        }
+        listeners
+    }
+}
+
+/// Calls each of the given listeners with `this` bound to `target` and the event object as the
+/// only argument.
+pub fn invoke_listeners(cx: *JSContext, target: *JSObject, listeners: &[JSVal], event: *JSObject) {
+    let event = RUST_OBJECT_TO_JSVAL(event);
+    for &listener in listeners.iter() {
+        let rval = JSVAL_NULL;
+        unsafe {
+            JS_CallFunctionValue(cx, target, listener, 1, &event, &rval);
+        }
+    }
+}
+
 impl CacheableWrapper for EventTarget {
     fn get_wrappercache(&mut self) -> &mut WrapperCache {
         unsafe { cast::transmute(&self.wrapper) }
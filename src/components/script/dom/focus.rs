@@ -0,0 +1,154 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Keyboard focus: which elements can have it, the order Tab moves it through them in, and moving
+//! it, which fires `blur` at the element that loses it and `focus` at the one that gains it. The
+//! document keeps track of the focused element, which matches `:focus`.
+
+use dom::bindings::utils::{CacheableWrapper, str};
+use dom::document::AbstractDocument;
+use dom::editing;
+use dom::event::Event;
+use dom::eventtarget::invoke_listeners;
+use dom::node::{AbstractNode, ScriptView};
+use dom::window::Window;
+
+use extra::sort::merge_sort;
+use servo_util::tree::TreeNodeRef;
+use std::i32;
+
+/// The `tabindex` of `element`, if it has a valid one.
+pub fn tab_index(element: AbstractNode<ScriptView>) -> Option<i32> {
+    do element.with_imm_element |element| {
+        element.get_attr("tabindex").chain(|value| i32::from_str(value.trim()))
+    }
+}
+
+/// Whether `element` can have focus without a `tabindex`, as links, form controls and editing
+/// hosts can.
+///
+/// FIXME: Elements that aren't rendered, like those with `display: none`, can't have focus.
+pub fn is_focusable_by_default(element: AbstractNode<ScriptView>) -> bool {
+    let disabled = element.with_imm_element(|element| element.get_attr("disabled").is_some());
+    if element.is_anchor_element() {
+        element.with_imm_element(|element| element.get_attr("href").is_some())
+    } else if element.is_input_element() {
+        !disabled && element.with_imm_input_element(|input| "hidden" != input.type_())
+    } else if element.is_select_element() || element.is_textarea_element() {
+        !disabled
+    } else {
+        editing::editing_host(element) == Some(element)
+    }
+}
+
+/// Whether `node` is an element that can have focus.
+pub fn is_focusable(node: AbstractNode<ScriptView>) -> bool {
+    node.is_element() && (tab_index(node).is_some() || is_focusable_by_default(node))
+}
+
+/// The element that clicking `node` focuses: the nearest focusable element among it and its
+/// ancestors.
+pub fn focusable_ancestor(node: AbstractNode<ScriptView>) -> Option<AbstractNode<ScriptView>> {
+    let mut current = Some(node);
+    while current.is_some() {
+        let node = current.unwrap();
+        if is_focusable(node) {
+            return Some(node)
+        }
+        current = node.parent_node();
+    }
+    None
+}
+
+/// The elements under `root` that Tab moves focus through, in the order it does: those with a
+/// positive `tabindex` from the lowest to the highest, then the others in tree order. Elements
+/// with a negative `tabindex` can have focus, but Tab skips them.
+pub fn sequential_navigation_order(root: AbstractNode<ScriptView>)
+                                   -> ~[AbstractNode<ScriptView>] {
+    let mut positive = ~[];
+    let mut rest = ~[];
+    for node in root.traverse_preorder() {
+        if !is_focusable(node) {
+            loop
+        }
+        match tab_index(node) {
+            Some(index) if index > 0 => positive.push((index, node)),
+            Some(index) if index < 0 => {}
+            _ => rest.push(node),
+        }
+    }
+    // The sort is stable, so elements with the same index stay in tree order.
+    let positive = do merge_sort(positive) |&(a, _), &(b, _)| a <= b;
+    let mut order: ~[AbstractNode<ScriptView>] = positive.iter().map(|&(_, node)| node).collect();
+    order.push_all_move(rest);
+    order
+}
+
+/// The element that Tab, or Shift+Tab if `forward` is false, moves focus to from `current`. It
+/// wraps around from one end of the document to the other.
+pub fn next_in_navigation_order(root: AbstractNode<ScriptView>,
+                                current: Option<AbstractNode<ScriptView>>,
+                                forward: bool)
+                                -> Option<AbstractNode<ScriptView>> {
+    let order = sequential_navigation_order(root);
+    if order.is_empty() {
+        return None
+    }
+    let position = current.chain(|current| order.iter().position(|&node| node == current));
+    let next = match position {
+        Some(i) if forward => (i + 1) % order.len(),
+        Some(i) => (i + order.len() - 1) % order.len(),
+        None if forward => 0,
+        None => order.len() - 1,
+    };
+    Some(order[next])
+}
+
+/// Moves focus in `document` to `element`, or takes it away, firing `blur` at the element that
+/// loses it and then `focus` at the one that gains it. An editing host gets the caret at the end
+/// of its text.
+pub fn set_focus(document: AbstractDocument, element: Option<AbstractNode<ScriptView>>) {
+    let old = document.with_base(|doc| doc.focused);
+    if old == element {
+        return
+    }
+    do document.with_mut_base |doc| {
+        doc.set_focus(element);
+        for &host in element.iter() {
+            if editing::editing_host(host) == Some(host) {
+                doc.set_caret(editing::caret_for_click(host, None));
+            }
+        }
+    }
+
+    let window = match document.with_base(|doc| doc.window) {
+        Some(window) => window,
+        None => return,
+    };
+    for &old in old.iter() {
+        fire_focus_event(old, "blur", window);
+    }
+    for &new in element.iter() {
+        fire_focus_event(new, "focus", window);
+    }
+}
+
+/// Fires a `focus` or `blur` event at `element`. Neither bubbles nor can be canceled.
+fn fire_focus_event(element: AbstractNode<ScriptView>, type_: &str, window: @mut Window) {
+    let type_ = str(type_.to_owned());
+    let listeners = element.with_imm_element(|element| element.listeners.get(&type_));
+    if listeners.is_empty() {
+        return
+    }
+
+    // Listeners can only have been added from script, so the element has a wrapper by now.
+    let this = element.with_base(|node| node.wrapper.get_wrapper());
+    let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+    let event = @mut Event::new(&type_);
+    event.bubbles = false;
+    event.cancelable = false;
+    event.trusted = true;
+    let event_obj = event.wrap_object_shared(cx, window.wrapper.get_wrapper());
+    invoke_listeners(cx, this, listeners, event_obj);
+}
@@ -0,0 +1,73 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::HashChangeEventBinding;
+use dom::bindings::utils::{DOMString, str};
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::event::Event;
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+/// The event fired at the window when the fragment of its document's URL changes.
+pub struct HashChangeEvent {
+    parent: Event,
+    old_url: ~str,
+    new_url: ~str,
+}
+
+impl HashChangeEvent {
+    pub fn new(type_: &DOMString, old_url: ~str, new_url: ~str) -> HashChangeEvent {
+        HashChangeEvent {
+            parent: Event::new(type_),
+            old_url: old_url,
+            new_url: new_url,
+        }
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn OldURL(&self) -> DOMString {
+        str(self.old_url.clone())
+    }
+
+    pub fn NewURL(&self) -> DOMString {
+        str(self.new_url.clone())
+    }
+}
+
+impl CacheableWrapper for HashChangeEvent {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        return self.parent.get_wrappercache()
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        HashChangeEventBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for HashChangeEvent {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        self.parent.GetParentObject(cx)
+    }
+}
+
+impl DerivedWrapper for HashChangeEvent {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,138 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `History`, the session history of the window. The constellation keeps the
+//! entries, and the window the state objects of its document's own.
+
+use dom::bindings::codegen::HistoryBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, Error, ErrorResult, Security, null_string, str};
+use dom::window::Window;
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext, JSVal};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use servo_msg::constellation_msg::{GetHistoryLengthMsg, TraverseHistoryMsg};
+use servo_util::url::{is_same_origin, make_url};
+use extra::url::Url;
+
+use std::cast;
+use std::comm;
+
+/// The `window.history` object.
+pub struct History {
+    wrapper: WrapperCache,
+    window: @mut Window,
+}
+
+impl History {
+    pub fn new(window: @mut Window, cx: *JSContext, scope: *JSObject) -> @mut History {
+        let history = @mut History {
+            wrapper: WrapperCache::new(),
+            window: window,
+        };
+        history.init_wrapper(cx, scope);
+        history
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn Length(&self) -> u32 {
+        let (port, chan) = comm::stream();
+        self.window.constellation_chan.send(GetHistoryLengthMsg(chan));
+        port.recv() as u32
+    }
+
+    pub fn State(&self, _cx: *JSContext) -> JSVal {
+        self.window.history_state()
+    }
+
+    pub fn Go(&self, delta: i32) {
+        self.window.constellation_chan.send(TraverseHistoryMsg(delta as int));
+    }
+
+    pub fn Back(&self) {
+        self.Go(-1)
+    }
+
+    pub fn Forward(&self) {
+        self.Go(1)
+    }
+
+    pub fn PushState(&self, _cx: *JSContext, data: JSVal, _title: &DOMString, url: &DOMString,
+                     rv: &mut ErrorResult) {
+        match self.resolve_url(url) {
+            Ok(url) => self.window.push_state(data, url),
+            Err(error) => *rv = Err(error),
+        }
+    }
+
+    pub fn ReplaceState(&self, _cx: *JSContext, data: JSVal, _title: &DOMString, url: &DOMString,
+                        rv: &mut ErrorResult) {
+        match self.resolve_url(url) {
+            Ok(url) => self.window.replace_state(data, url),
+            Err(error) => *rv = Err(error),
+        }
+    }
+}
+
+impl History {
+    /// Resolves the URL given to `pushState` or `replaceState` against the document's. It has to
+    /// be of the same origin, since it ends up in the URL bar. Without one, the URL stays as it
+    /// is.
+    fn resolve_url(&self, url: &DOMString) -> Result<Url, Error> {
+        let current_url = match self.window.url() {
+            Some(current_url) => current_url,
+            None => return Err(Security),
+        };
+        let url = match *url {
+            str(ref url) => make_url(url.clone(), Some(current_url.clone())),
+            null_string => return Ok(current_url),
+        };
+        if !is_same_origin(&url, &current_url) {
+            return Err(Security)
+        }
+        Ok(url)
+    }
+}
+
+impl CacheableWrapper for History {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        HistoryBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for History {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for History {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
@@ -2,26 +2,53 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use dom::bindings::utils::{CacheableWrapper, DOMString, null_string, str, ErrorResult};
+use dom::event::Event;
+use dom::eventtarget::invoke_listeners;
 use dom::htmlelement::HTMLElement;
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::node::{AbstractNode, ScriptView};
+use dom::window::Window;
+use script_task::TriggerLoadMsg;
+
+use js::JSVAL_NULL;
+use js::jsapi::{JSContext, JSVal};
+use servo_msg::constellation_msg::OpenUrlMsg;
+use servo_net::resource_task::{GetVisited, LoadData, ResourceTask};
+use servo_util::tree::TreeNodeRef;
+use servo_util::url::{is_fragment_navigation, make_url};
+use extra::url::Url;
+use std::ascii::StrAsciiExt;
+use std::comm;
 
 pub struct HTMLAnchorElement {
-    parent: HTMLElement
+    parent: HTMLElement,
+    /// The handler set through the `onclick` attribute.
+    onclick: JSVal,
 }
 
 impl HTMLAnchorElement {
+    fn reflect(&self, name: &str) -> DOMString {
+        str(self.parent.parent.get_attr(name).unwrap_or_default("").to_owned())
+    }
+
+    fn set_reflected(&mut self, name: &str, value: &DOMString) {
+        self.parent.parent.set_attr(&str(name.to_owned()), value);
+    }
+
     pub fn Href(&self) -> DOMString {
-        null_string
+        self.reflect("href")
     }
 
-    pub fn SetHref(&mut self, _href: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetHref(&mut self, href: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("href", href)
     }
 
     pub fn Target(&self) -> DOMString {
-        null_string
+        self.reflect("target")
     }
 
-    pub fn SetTarget(&self, _target: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetTarget(&mut self, target: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("target", target)
     }
 
     pub fn Download(&self) -> DOMString {
@@ -100,4 +127,133 @@ impl HTMLAnchorElement {
 
     pub fn SetShape(&mut self, _shape: &DOMString, _rv: &mut ErrorResult) {
     }
+
+    pub fn Onclick(&self, _cx: *JSContext) -> JSVal {
+        self.onclick
+    }
+
+    pub fn SetOnclick(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.onclick = handler;
+    }
+}
+
+/// The link `node` is in, or is: its nearest `a` ancestor with an `href`.
+pub fn link_for(node: AbstractNode<ScriptView>) -> Option<AbstractNode<ScriptView>> {
+    let mut current = Some(node);
+    while current.is_some() {
+        let ancestor = current.unwrap();
+        if ancestor.is_anchor_element() &&
+                ancestor.with_imm_element(|element| element.get_attr("href").is_some()) {
+            return Some(ancestor)
+        }
+        current = ancestor.parent_node();
+    }
+    None
+}
+
+/// Marks the links under `root` that lead to pages in the history as visited, so that they match
+/// `:visited`. Their `href`s are resolved against `base_url`.
+pub fn mark_visited_links(root: AbstractNode<ScriptView>, base_url: Option<Url>,
+                          resource_task: &ResourceTask) {
+    let mut links = ~[];
+    let mut urls = ~[];
+    for node in root.traverse_preorder() {
+        if !node.is_anchor_element() {
+            loop
+        }
+        let href = node.with_imm_element(|element| element.get_attr("href").map(|href| {
+            href.to_owned()
+        }));
+        match href {
+            Some(href) => {
+                links.push(node);
+                urls.push(make_url(href, base_url.clone()));
+            }
+            None => node.as_mut_element(|element| element.state.visited = false),
+        }
+    }
+    if links.is_empty() {
+        return
+    }
+
+    let (port, chan) = comm::stream();
+    resource_task.send(GetVisited(urls, chan));
+    for (&link, &visited) in links.iter().zip(port.recv().iter()) {
+        link.as_mut_element(|element| element.state.visited = visited);
+    }
+}
+
+/// Activates the link `anchor`, as clicking it does: fires a `click` event at it, and unless a
+/// listener prevents its default action, follows the link.
+pub fn activate(anchor: AbstractNode<ScriptView>, window: @mut Window) {
+    if !fire_click_event(anchor, window) {
+        debug!("following the link was canceled");
+        return
+    }
+    follow(anchor, window)
+}
+
+/// Follows the link `anchor`: resolves its `href` against the document's base URL and navigates
+/// to it. A `target` of `_blank`, on the link or else on the document's `base` element, opens it
+/// in a new top-level browsing context.
+///
+/// FIXME: Other targets, like `_top` for a link in a frame, load in the link's own frame.
+pub fn follow(anchor: AbstractNode<ScriptView>, window: @mut Window) {
+    let (href, target) = do anchor.with_imm_element |element| {
+        (element.get_attr("href").map(|href| href.to_owned()),
+         element.get_attr("target").map(|target| target.to_owned()))
+    };
+    let href = match href {
+        Some(href) => href,
+        None => return,
+    };
+    let (base_url, base_target) = do window.Document().with_base |doc| {
+        (doc.base_url(), doc.base_target())
+    };
+    let target = match target {
+        Some(target) => Some(target),
+        None => base_target,
+    };
+    let url = make_url(href, base_url);
+    debug!("following link to %s", url.to_str());
+
+    match target {
+        Some(ref target) if "_blank" == target.to_ascii_lower() => {
+            window.constellation_chan.send(OpenUrlMsg(LoadData::new(url)));
+            return
+        }
+        _ => {}
+    }
+    // Links to a fragment of the page scroll it rather than loading it again.
+    let within_page = do window.url().map_default(false) |current_url| {
+        is_fragment_navigation(current_url, &url)
+    };
+    if within_page {
+        window.navigate_to_fragment(url);
+    } else {
+        let page_id = unsafe { (*window.page).id };
+        window.script_chan.send(TriggerLoadMsg(page_id, LoadData::new(url)));
+    }
+}
+
+/// Fires a `click` event at `anchor`. Returns false if a listener canceled it.
+fn fire_click_event(anchor: AbstractNode<ScriptView>, window: @mut Window) -> bool {
+    let type_ = str(~"click");
+    let handler = anchor.with_imm_anchor_element(|anchor| anchor.onclick);
+    let mut listeners = anchor.with_imm_element(|element| element.listeners.get(&type_));
+    if handler != JSVAL_NULL {
+        listeners.unshift(handler);
+    }
+    if listeners.is_empty() {
+        return true
+    }
+
+    // Listeners can only have been added from script, so the link has a wrapper by now.
+    let this = anchor.with_base(|node| node.wrapper.get_wrapper());
+    let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+    let event = @mut Event::new(&type_);
+    event.trusted = true;
+    let event_obj = event.wrap_object_shared(cx, window.wrapper.get_wrapper());
+    invoke_listeners(cx, this, listeners, event_obj);
+    !event.default_prevented
 }
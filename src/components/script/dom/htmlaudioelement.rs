@@ -0,0 +1,10 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::htmlmediaelement::HTMLMediaElement;
+
+/// An `<audio>`. It's never rendered, since there are no controls to show yet.
+pub struct HTMLAudioElement {
+    parent: HTMLMediaElement,
+}
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{DOMString, str, ErrorResult};
 use dom::htmlelement::HTMLElement;
 
 pub struct HTMLBaseElement {
@@ -10,17 +10,27 @@ pub struct HTMLBaseElement {
 }
 
 impl HTMLBaseElement {
+    fn reflect(&self, name: &str) -> DOMString {
+        str(self.parent.parent.get_attr(name).unwrap_or_default("").to_owned())
+    }
+
+    fn set_reflected(&mut self, name: &str, value: &DOMString) {
+        self.parent.parent.set_attr(&str(name.to_owned()), value);
+    }
+
     pub fn Href(&self) -> DOMString {
-        null_string
+        self.reflect("href")
     }
 
-    pub fn SetHref(&self, _href: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetHref(&mut self, href: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("href", href)
     }
 
     pub fn Target(&self) -> DOMString {
-        null_string
+        self.reflect("target")
     }
 
-    pub fn SetTarget(&self, _target: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetTarget(&mut self, target: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("target", target)
     }
 }
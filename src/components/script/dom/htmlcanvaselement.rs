@@ -2,25 +2,158 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{ErrorResult};
+use dom::bindings::utils::{DOMString, ErrorResult, Security, str};
+use dom::canvasrenderingcontext2d::CanvasRenderingContext2D;
 use dom::htmlelement::HTMLElement;
+use dom::node::{AbstractNode, ScriptView};
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::canvas_task::{CanvasTask, GetImageData, Recreate};
+use servo_net::image::png;
+use servo_util::url::Origin;
+use extra::base64::{STANDARD, ToBase64};
+use extra::url::Url;
+
+use std::comm;
+use std::u32;
+use std::vec;
+
+/// The size of a canvas without `width` and `height` attributes.
+static DEFAULT_WIDTH: u32 = 300;
+static DEFAULT_HEIGHT: u32 = 150;
 
 pub struct HTMLCanvasElement {
     parent: HTMLElement,
+    /// Whether everything drawn on the canvas came from the document's origin. Script may only
+    /// read back the pixels of a canvas that is.
+    origin_clean: bool,
+    /// The 2D context, once script has asked for it.
+    context: Option<@mut CanvasRenderingContext2D>,
+    /// The task the context draws with, which layout asks for the canvas's image.
+    renderer: Option<CanvasTask>,
 }
 
 impl HTMLCanvasElement {
     pub fn Width(&self) -> u32 {
-        0
+        self.size().width
     }
 
-    pub fn SetWidth(&mut self, _width: u32, _rv: &mut ErrorResult) {
+    pub fn SetWidth(&mut self, width: u32, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"width"), &str(width.to_str()));
+        self.recreate();
     }
 
     pub fn Height(&self) -> u32 {
-        0
+        self.size().height
+    }
+
+    pub fn SetHeight(&mut self, height: u32, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"height"), &str(height.to_str()));
+        self.recreate();
+    }
+
+    /// Gets the canvas's 2D context, creating it and the task it draws with the first time. Other
+    /// kinds of context aren't supported.
+    pub fn GetContext(&mut self, abstract_self: AbstractNode<ScriptView>, context_id: &DOMString)
+                      -> Option<@mut CanvasRenderingContext2D> {
+        if context_id.to_str() != ~"2d" {
+            return None;
+        }
+        if self.context.is_none() {
+            // The context's task belongs to the window of the canvas's document.
+            let doc = abstract_self.with_base(|node| node.owner_doc);
+            let window = match doc.chain(|doc| doc.with_base(|doc| doc.window)) {
+                Some(window) => window,
+                None => return None,
+            };
+            let context = CanvasRenderingContext2D::new(window, abstract_self,
+                                                        self.bitmap_size());
+            self.renderer = Some(context.renderer.clone());
+            self.context = Some(context);
+        }
+        self.context
+    }
+
+    /// Encodes what's been drawn on the canvas as a PNG `data:` URL. Other types aren't supported,
+    /// and fall back to PNG as the spec says.
+    pub fn ToDataURL(&self, _type: Option<DOMString>, rv: &mut ErrorResult) -> DOMString {
+        if !self.origin_clean {
+            *rv = Err(Security);
+            return str(~"");
+        }
+        let size = self.size();
+        if size.width == 0 || size.height == 0 {
+            return str(~"data:,");
+        }
+        let rect = Rect(Point2D(0, 0), self.bitmap_size());
+        let pixels = self.get_image_data(&rect);
+        let png = png::encode(size.width as uint, size.height as uint, pixels);
+        str(fmt!("data:image/png;base64,%s", png.to_base64(STANDARD)))
+    }
+}
+
+impl HTMLCanvasElement {
+    /// The size of the canvas's bitmap, in pixels, from its `width` and `height` attributes.
+    pub fn size(&self) -> Size2D<u32> {
+        let dimension = |name: &str, default: u32| {
+            match self.parent.parent.get_attr(name) {
+                Some(value) => u32::from_str(value.trim()).unwrap_or_default(default),
+                None => default,
+            }
+        };
+        Size2D(dimension("width", DEFAULT_WIDTH), dimension("height", DEFAULT_HEIGHT))
+    }
+
+    fn bitmap_size(&self) -> Size2D<i32> {
+        let size = self.size();
+        Size2D(size.width as i32, size.height as i32)
+    }
+
+    /// Clears the canvas and resizes its bitmap, as setting either dimension does.
+    ///
+    /// FIXME: Setting the attributes with `setAttribute` should do this too.
+    fn recreate(&mut self) {
+        for renderer in self.renderer.iter() {
+            renderer.send(Recreate(self.bitmap_size()));
+        }
+        for context in self.context.iter() {
+            context.reset();
+        }
+    }
+
+    /// Notes that an image from `url` was drawn on the canvas of a document of `origin`. One from
+    /// another origin taints the canvas for good.
+    pub fn taint(&mut self, origin: &Origin, url: &Url) {
+        if !origin.is_same_origin(url) {
+            self.origin_clean = false;
+        }
+    }
+
+    /// Reads the pixels of a rect of the canvas as straight RGBA rows, waiting for everything
+    /// script has drawn so far. Pixels outside the canvas, or of a canvas nothing has been drawn
+    /// on, are transparent black.
+    pub fn get_image_data(&self, rect: &Rect<i32>) -> ~[u8] {
+        match self.renderer {
+            Some(ref renderer) => {
+                let (port, chan) = comm::stream();
+                renderer.send(GetImageData(*rect, chan));
+                port.recv()
+            }
+            None => {
+                vec::from_elem(rect.size.width as uint * rect.size.height as uint * 4, 0u8)
+            }
+        }
+    }
+
+    /// Whether script may read the canvas's pixels.
+    pub fn is_origin_clean(&self) -> bool {
+        self.origin_clean
     }
 
-    pub fn SetHeight(&mut self, _height: u32, _rv: &mut ErrorResult) {
+    /// The task drawing the canvas, if script has drawn on it.
+    pub fn renderer(&self) -> Option<CanvasTask> {
+        self.renderer.clone()
     }
 }
@@ -3,7 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::codegen::HTMLDocumentBinding;
-use dom::bindings::utils::{DOMString, ErrorResult, null_string};
+use dom::bindings::utils::{DOMString, ErrorResult, null_string, str};
 use dom::bindings::utils::{CacheableWrapper, BindingObject, WrapperCache};
 use dom::document::{AbstractDocument, Document, WrappableDocument, HTML};
 use dom::element::HTMLHeadElementTypeId;
@@ -13,8 +13,12 @@ use dom::window::Window;
 
 use js::jsapi::{JSObject, JSContext};
 
+use servo_net::cookie::NonHTTP;
+use servo_net::resource_task::{GetCookiesForUrl, SetCookiesForUrl};
 use servo_util::tree::TreeNodeRef;
 
+use std::ascii::StrAsciiExt;
+use std::comm;
 use std::libc;
 use std::ptr;
 use std::str::eq_slice;
@@ -61,10 +65,26 @@ impl HTMLDocument {
     }
 
     pub fn GetCookie(&self, _rv: &mut ErrorResult) -> DOMString {
-        null_string
+        let window = self.parent.window.get_ref();
+        match window.url() {
+            Some(url) => {
+                let (port, chan) = comm::stream();
+                window.resource_task.send(GetCookiesForUrl(url, chan, NonHTTP));
+                match port.recv() {
+                    Some(cookies) => str(cookies),
+                    None => str(~""),
+                }
+            }
+            None => str(~""),
+        }
     }
 
-    pub fn SetCookie(&self, _cookie: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetCookie(&self, cookie: &DOMString, _rv: &mut ErrorResult) {
+        let window = self.parent.window.get_ref();
+        match window.url() {
+            Some(url) => window.resource_task.send(SetCookiesForUrl(url, cookie.to_str(), NonHTTP)),
+            None => {}
+        }
     }
 
     pub fn GetHead(&self) -> Option<AbstractNode<ScriptView>> {
@@ -107,6 +127,19 @@ impl HTMLDocument {
     pub fn Close(&self, _rv: &mut ErrorResult) {
     }
 
+    pub fn Write(&self, text: &DOMString, _rv: &mut ErrorResult) {
+        match self.parent.script_inserted_text {
+            Some(inserted_text) => inserted_text.push_str(text.to_str()),
+            // FIXME: Writing to a document that has been parsed should open it again, which
+            // replaces its contents. We don't support `document.open` yet.
+            None => warn!("document.write() after the document was parsed is ignored"),
+        }
+    }
+
+    pub fn Writeln(&self, text: &DOMString, rv: &mut ErrorResult) {
+        self.Write(&str(text.to_str() + "\n"), rv)
+    }
+
     pub fn DesignMode(&self) -> DOMString {
         null_string
     }
@@ -114,12 +147,23 @@ impl HTMLDocument {
     pub fn SetDesignMode(&self, _mode: &DOMString, _rv: &mut ErrorResult) {
     }
 
-    pub fn ExecCommand(&self, _command_id: &DOMString, _show_ui: bool, _value: &DOMString, _rv: &mut ErrorResult) -> bool {
-        false
+    /// Runs an editing command. Only "copy" is supported so far.
+    pub fn ExecCommand(&self, command_id: &DOMString, _show_ui: bool, _value: &DOMString, _rv: &mut ErrorResult) -> bool {
+        match command(command_id) {
+            Some(CopyCommand) => self.parent.copy(),
+            None => false,
+        }
     }
 
-    pub fn QueryCommandEnabled(&self, _command_id: &DOMString, _rv: &mut ErrorResult) -> bool {
-        false
+    pub fn QueryCommandEnabled(&self, command_id: &DOMString, _rv: &mut ErrorResult) -> bool {
+        match command(command_id) {
+            Some(CopyCommand) => {
+                self.parent.selection.map_default(false, |&(ref anchor, ref focus)| {
+                    anchor != focus
+                })
+            }
+            None => false,
+        }
     }
 
     pub fn QueryCommandIndeterm(&self, _command_id: &DOMString, _rv: &mut ErrorResult) -> bool {
@@ -130,8 +174,8 @@ impl HTMLDocument {
         false
     }
 
-    pub fn QueryCommandSupported(&self, _command_id: &DOMString) -> bool {
-        false
+    pub fn QueryCommandSupported(&self, command_id: &DOMString) -> bool {
+        command(command_id).is_some()
     }
 
     pub fn QueryCommandValue(&self, _command_id: &DOMString, _rv: &mut ErrorResult) -> DOMString {
@@ -191,6 +235,21 @@ impl HTMLDocument {
     }
 }
 
+/// The editing commands `execCommand` can run.
+enum Command {
+    CopyCommand,
+}
+
+/// Looks up a command by its name, ignoring case.
+fn command(command_id: &DOMString) -> Option<Command> {
+    let name = command_id.to_str().to_ascii_lower();
+    if "copy" == name {
+        Some(CopyCommand)
+    } else {
+        None
+    }
+}
+
 impl CacheableWrapper for HTMLDocument {
     fn get_wrappercache(&mut self) -> &mut WrapperCache {
         self.parent.get_wrappercache()
@@ -3,12 +3,15 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::codegen::HTMLElementBinding;
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{DOMString, null_string, str, ErrorResult, Syntax};
 use dom::bindings::utils::{CacheableWrapper, BindingObject, WrapperCache};
+use dom::editing::editing_host;
 use dom::element::{Element, ElementTypeId};
+use dom::focus;
 use dom::node::{AbstractNode, ScriptView};
 use js::jsapi::{JSObject, JSContext, JSVal};
 use js::JSVAL_NULL;
+use std::ascii::StrAsciiExt;
 
 pub struct HTMLElement {
     parent: Element
@@ -61,17 +64,40 @@ impl HTMLElement {
     pub fn Click(&self) {
     }
 
+    /// The element's `tabindex`, or 0 for elements that can have focus without one and -1 for
+    /// those that can't.
     pub fn TabIndex(&self) -> i32 {
-        0
+        let node = self.parent.parent.abstract.unwrap();
+        match focus::tab_index(node) {
+            Some(index) => index,
+            None if focus::is_focusable_by_default(node) => 0,
+            None => -1,
+        }
     }
 
-    pub fn SetTabIndex(&mut self, _index: i32, _rv: &mut ErrorResult) {
+    pub fn SetTabIndex(&mut self, index: i32, _rv: &mut ErrorResult) {
+        self.parent.set_attr(&str(~"tabindex"), &str(index.to_str()))
     }
 
+    /// Focuses the element, if it can have focus and is in its document.
     pub fn Focus(&self, _rv: &mut ErrorResult) {
+        let node = self.parent.parent.abstract.unwrap();
+        for &document in self.parent.parent.owner_doc.iter() {
+            let root = document.with_base(|doc| doc.root);
+            if focus::is_focusable(node) && root.is_inclusive_ancestor_of(node) {
+                focus::set_focus(document, Some(node))
+            }
+        }
     }
 
+    /// Takes focus away from the element, if it has it.
     pub fn Blur(&self, _rv: &mut ErrorResult) {
+        let node = self.parent.parent.abstract;
+        for &document in self.parent.parent.owner_doc.iter() {
+            if document.with_base(|doc| doc.focused) == node {
+                focus::set_focus(document, None)
+            }
+        }
     }
 
     pub fn AccessKey(&self) -> DOMString {
@@ -93,14 +119,25 @@ impl HTMLElement {
     }
 
     pub fn ContentEditable(&self) -> DOMString {
-        null_string
-    }
-
-    pub fn SetContentEditable(&mut self, _val: &DOMString, _rv: &mut ErrorResult) {
+        let value = self.parent.get_attr("contenteditable").map(|value| value.to_ascii_lower());
+        str(match value {
+            Some(ref value) if value.is_empty() || "true" == *value => ~"true",
+            Some(ref value) if "false" == *value => ~"false",
+            _ => ~"inherit",
+        })
+    }
+
+    pub fn SetContentEditable(&mut self, val: &DOMString, rv: &mut ErrorResult) {
+        let value = val.to_str().to_ascii_lower();
+        match value.as_slice() {
+            "inherit" => self.parent.remove_attr("contenteditable"),
+            "true" | "false" => self.parent.set_attr(&str(~"contenteditable"), &str(value.clone())),
+            _ => *rv = Err(Syntax),
+        }
     }
 
     pub fn IsContentEditable(&self) -> bool {
-        false
+        self.parent.parent.abstract.chain(|node| editing_host(node)).is_some()
     }
 
     pub fn Spellcheck(&self) -> bool {
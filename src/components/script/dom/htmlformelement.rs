@@ -0,0 +1,367 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::utils::{CacheableWrapper, DOMString, str, ErrorResult};
+use dom::event::Event;
+use dom::eventtarget::invoke_listeners;
+use dom::htmlelement::HTMLElement;
+use dom::htmloptionelement;
+use dom::htmlselectelement;
+use dom::htmltextareaelement;
+use dom::node::{AbstractNode, ScriptView};
+use dom::window::Window;
+use script_task::TriggerLoadMsg;
+
+use js::JSVAL_NULL;
+use js::jsapi::{JSContext, JSVal};
+use servo_net::resource_task::LoadData;
+use servo_util::tree::TreeNodeRef;
+use servo_util::url::make_url;
+use std::ascii::StrAsciiExt;
+use std::rand;
+
+pub struct HTMLFormElement {
+    parent: HTMLElement,
+    /// The handler set through the `onsubmit` attribute.
+    onsubmit: JSVal,
+}
+
+impl HTMLFormElement {
+    /// The method the form submits with, from its `method` attribute: `get` unless it's `post`.
+    pub fn method(&self) -> ~str {
+        match self.parent.parent.get_attr("method") {
+            Some(method) if "post" == method.to_ascii_lower() => ~"post",
+            _ => ~"get",
+        }
+    }
+
+    /// How the form encodes its entries, from its `enctype` attribute. Missing and unknown
+    /// types mean `application/x-www-form-urlencoded`.
+    pub fn enctype(&self) -> ~str {
+        let enctype = self.parent.parent.get_attr("enctype").unwrap_or_default("").to_ascii_lower();
+        match enctype.as_slice() {
+            "multipart/form-data" | "text/plain" => enctype,
+            _ => ~"application/x-www-form-urlencoded",
+        }
+    }
+
+    fn reflect(&self, name: &str) -> DOMString {
+        str(self.parent.parent.get_attr(name).unwrap_or_default("").to_owned())
+    }
+
+    fn set_reflected(&mut self, name: &str, value: &DOMString) {
+        self.parent.parent.set_attr(&str(name.to_owned()), value);
+    }
+
+    pub fn AcceptCharset(&self) -> DOMString {
+        self.reflect("accept-charset")
+    }
+
+    pub fn SetAcceptCharset(&mut self, accept_charset: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("accept-charset", accept_charset)
+    }
+
+    pub fn Action(&self) -> DOMString {
+        self.reflect("action")
+    }
+
+    pub fn SetAction(&mut self, action: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("action", action)
+    }
+
+    pub fn Autocomplete(&self) -> DOMString {
+        self.reflect("autocomplete")
+    }
+
+    pub fn SetAutocomplete(&mut self, autocomplete: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("autocomplete", autocomplete)
+    }
+
+    pub fn Enctype(&self) -> DOMString {
+        str(self.enctype())
+    }
+
+    pub fn SetEnctype(&mut self, enctype: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("enctype", enctype)
+    }
+
+    pub fn Encoding(&self) -> DOMString {
+        self.Enctype()
+    }
+
+    pub fn SetEncoding(&mut self, encoding: &DOMString, rv: &mut ErrorResult) {
+        self.SetEnctype(encoding, rv)
+    }
+
+    pub fn Method(&self) -> DOMString {
+        str(self.method())
+    }
+
+    pub fn SetMethod(&mut self, method: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("method", method)
+    }
+
+    pub fn Name(&self) -> DOMString {
+        self.reflect("name")
+    }
+
+    pub fn SetName(&mut self, name: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("name", name)
+    }
+
+    pub fn NoValidate(&self) -> bool {
+        self.parent.parent.get_attr("novalidate").is_some()
+    }
+
+    pub fn SetNoValidate(&mut self, no_validate: bool, _rv: &mut ErrorResult) {
+        if no_validate {
+            self.set_reflected("novalidate", &str(~""));
+        } else {
+            self.parent.parent.remove_attr("novalidate");
+        }
+    }
+
+    pub fn Target(&self) -> DOMString {
+        self.reflect("target")
+    }
+
+    pub fn SetTarget(&mut self, target: &DOMString, _rv: &mut ErrorResult) {
+        self.set_reflected("target", target)
+    }
+
+    pub fn Length(&self, abstract_self: AbstractNode<ScriptView>) -> i32 {
+        let controls = do abstract_self.traverse_preorder().count |node| {
+            node.is_input_element() || node.is_select_element() || node.is_textarea_element()
+        };
+        controls as i32
+    }
+
+    /// Submits the form without firing a `submit` event, as the spec asks.
+    pub fn Submit(&self, abstract_self: AbstractNode<ScriptView>) {
+        submit(abstract_self, None, false)
+    }
+
+    pub fn Onsubmit(&self, _cx: *JSContext) -> JSVal {
+        self.onsubmit
+    }
+
+    pub fn SetOnsubmit(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.onsubmit = handler;
+    }
+}
+
+/// The form `node` belongs to: its nearest `form` ancestor.
+pub fn form_owner(node: AbstractNode<ScriptView>) -> Option<AbstractNode<ScriptView>> {
+    let mut current = node.parent_node();
+    while current.is_some() {
+        let ancestor = current.unwrap();
+        if ancestor.is_form_element() {
+            return Some(ancestor)
+        }
+        current = ancestor.parent_node();
+    }
+    None
+}
+
+/// The window of the document `node` is in, if it's in one that has a window.
+fn window_of(node: AbstractNode<ScriptView>) -> Option<@mut Window> {
+    let doc = node.with_base(|node| node.owner_doc);
+    doc.chain(|doc| doc.with_base(|doc| doc.window))
+}
+
+/// Submits `form`, as activating `submitter`, one of its submit buttons, does, or as pressing
+/// enter in one of its fields does if there's no submitter. Unless `fire_event` is false, as it is
+/// for `form.submit()`, a `submit` event is fired at the form first, and a listener can cancel the
+/// submission by preventing its default action.
+pub fn submit(form: AbstractNode<ScriptView>,
+              submitter: Option<AbstractNode<ScriptView>>,
+              fire_event: bool) {
+    let window = match window_of(form) {
+        Some(window) => window,
+        None => return,
+    };
+    if fire_event && !fire_submit_event(form, window) {
+        debug!("form submission canceled");
+        return
+    }
+
+    let document_url = match window.url() {
+        Some(url) => url,
+        None => return,
+    };
+    let (action, method, enctype) = do form.with_imm_form_element |form| {
+        (form.parent.parent.get_attr("action").unwrap_or_default("").to_owned(),
+         form.method(),
+         form.enctype())
+    };
+    let mut url = if action.is_empty() {
+        document_url
+    } else {
+        make_url(action, window.Document().with_base(|doc| doc.base_url()))
+    };
+    let entries = form_entries(form, submitter);
+
+    let load_data = if "post" == method {
+        let (content_type, body) = match enctype.as_slice() {
+            "multipart/form-data" => {
+                let boundary = fmt!("----ServoFormBoundary%016x", rand::random::<u64>());
+                (fmt!("multipart/form-data; boundary=%s", boundary),
+                 encode_multipart(entries, boundary))
+            }
+            "text/plain" => (~"text/plain", encode_text_plain(entries)),
+            _ => (~"application/x-www-form-urlencoded", encode_urlencoded(entries)),
+        };
+        let mut load_data = LoadData::new(url);
+        load_data.method = ~"POST";
+        load_data.headers.push((~"Content-Type", content_type));
+        load_data.data = Some(body.as_bytes().to_owned());
+        load_data
+    } else {
+        // A GET submission replaces the action's query with the entries.
+        url.query = entries;
+        url.fragment = None;
+        LoadData::new(url)
+    };
+
+    debug!("submitting form: %s %s", load_data.method, load_data.url.to_str());
+    let page_id = unsafe { (*window.page).id };
+    window.script_chan.send(TriggerLoadMsg(page_id, load_data));
+}
+
+/// Submits the form `field` is in, as pressing enter in it does. The form's first submit button,
+/// if it has one, counts as the submitter.
+pub fn implicitly_submit(field: AbstractNode<ScriptView>) {
+    for &form in form_owner(field).iter() {
+        let default_button = do form.traverse_preorder().find |&node| {
+            node.is_input_element() && node.with_imm_input_element(|input| {
+                input.is_submit_button()
+            })
+        };
+        submit(form, default_button, true)
+    }
+}
+
+/// Fires a `submit` event at `form`. Returns false if a listener canceled it.
+fn fire_submit_event(form: AbstractNode<ScriptView>, window: @mut Window) -> bool {
+    let type_ = str(~"submit");
+    let handler = form.with_imm_form_element(|form| form.onsubmit);
+    let mut listeners = form.with_imm_element(|element| element.listeners.get(&type_));
+    if handler != JSVAL_NULL {
+        listeners.unshift(handler);
+    }
+    if listeners.is_empty() {
+        return true
+    }
+
+    // Listeners can only have been added from script, so the form has a wrapper by now.
+    let this = form.with_base(|node| node.wrapper.get_wrapper());
+    let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+    let event = @mut Event::new(&type_);
+    event.trusted = true;
+    let event_obj = event.wrap_object_shared(cx, window.wrapper.get_wrapper());
+    invoke_listeners(cx, this, listeners, event_obj);
+    !event.default_prevented
+}
+
+/// The name/value pairs that the enabled, named controls in `form` submit, in tree order. Of the
+/// submit buttons, only `submitter` takes part.
+fn form_entries(form: AbstractNode<ScriptView>, submitter: Option<AbstractNode<ScriptView>>)
+                -> ~[(~str, ~str)] {
+    let mut entries = ~[];
+    for node in form.traverse_preorder() {
+        if !node.is_input_element() && !node.is_select_element() && !node.is_textarea_element() {
+            loop
+        }
+        let (name, disabled) = do node.with_imm_element |element| {
+            (element.get_attr("name").unwrap_or_default("").to_owned(),
+             element.get_attr("disabled").is_some())
+        };
+        if name.is_empty() || disabled {
+            loop
+        }
+
+        // FIXME: A text area with `wrap="hard"` should submit line breaks where it wraps.
+        if node.is_textarea_element() {
+            let value = htmltextareaelement::value(node).replace("\n", "\r\n");
+            entries.push((name, value));
+            loop
+        }
+
+        if node.is_select_element() {
+            let options = htmlselectelement::options(node);
+            if htmlselectelement::is_multiple(node) {
+                for &option in options.iter() {
+                    if option.with_imm_option_element(|option| option.selected()) {
+                        entries.push((name.clone(), htmloptionelement::value(option)));
+                    }
+                }
+            } else {
+                for &index in htmlselectelement::selected_index(node).iter() {
+                    entries.push((name.clone(), htmloptionelement::value(options[index])));
+                }
+            }
+            loop
+        }
+
+        do node.with_imm_input_element |input| {
+            match input.type_().as_slice() {
+                "submit" if submitter == Some(node) => {
+                    entries.push((name.clone(), input.value()));
+                }
+                // FIXME: This should be where the user clicked in the image.
+                "image" if submitter == Some(node) => {
+                    entries.push((name + ".x", ~"0"));
+                    entries.push((name + ".y", ~"0"));
+                }
+                "checkbox" | "radio" if input.checked() => {
+                    let value = input.value();
+                    entries.push((name.clone(), if value.is_empty() { ~"on" } else { value }));
+                }
+                // FIXME: File inputs should submit their files.
+                "submit" | "image" | "reset" | "button" | "file" | "checkbox" | "radio" => {}
+                _ => entries.push((name.clone(), input.value())),
+            }
+        }
+    }
+    entries
+}
+
+/// Percent-encodes `value` as `application/x-www-form-urlencoded` does, with spaces as `+`.
+fn urlencode(value: &str) -> ~str {
+    let mut encoded = ~"";
+    for &byte in value.as_bytes().iter() {
+        match byte as char {
+            'a'..'z' | 'A'..'Z' | '0'..'9' | '*' | '-' | '.' | '_' => encoded.push_char(byte as char),
+            ' ' => encoded.push_char('+'),
+            _ => encoded.push_str(fmt!("%%%02X", byte as uint)),
+        }
+    }
+    encoded
+}
+
+fn encode_urlencoded(entries: &[(~str, ~str)]) -> ~str {
+    let pairs: ~[~str] = do entries.iter().map |&(ref name, ref value)| {
+        fmt!("%s=%s", urlencode(*name), urlencode(*value))
+    }.collect();
+    pairs.connect("&")
+}
+
+fn encode_text_plain(entries: &[(~str, ~str)]) -> ~str {
+    let mut body = ~"";
+    for &(ref name, ref value) in entries.iter() {
+        body.push_str(fmt!("%s=%s\r\n", *name, *value));
+    }
+    body
+}
+
+fn encode_multipart(entries: &[(~str, ~str)], boundary: &str) -> ~str {
+    let mut body = ~"";
+    for &(ref name, ref value) in entries.iter() {
+        let name = name.replace("\"", "%22");
+        body.push_str(fmt!("--%s\r\nContent-Disposition: form-data; name=\"%s\"\r\n\r\n%s\r\n",
+                           boundary, name, *value));
+    }
+    body.push_str(fmt!("--%s--\r\n", boundary));
+    body
+}
@@ -10,6 +10,7 @@ use geom::size::Size2D;
 use geom::rect::Rect;
 
 use servo_msg::constellation_msg::{ConstellationChan, FrameRectMsg, PipelineId, SubpageId};
+use servo_msg::constellation_msg::SubpageWindow;
 
 use std::comm::ChanOne;
 use extra::url::Url;
@@ -91,11 +92,22 @@ impl HTMLIFrameElement {
     }
 
     pub fn GetContentDocument(&self) -> Option<AbstractDocument> {
+        // FIXME: Frames of the document's origin share its script task, so their documents could
+        // be handed out here. Those of other origins must never be; script can only post
+        // messages to their windows.
         None
     }
 
     pub fn GetContentWindow(&self) -> Option<@mut WindowProxy> {
-        None
+        let subpage_id = match self.size {
+            Some(ref size) => size.subpage_id,
+            None => return None,
+        };
+        let window = match self.parent.parent.parent.owner_doc {
+            Some(doc) => doc.with_base(|doc| doc.window),
+            None => None,
+        };
+        window.map_move(|window| window.proxy(SubpageWindow(subpage_id)))
     }
 
     pub fn Align(&self) -> DOMString {
@@ -4,14 +4,232 @@
 
 use dom::bindings::utils::{DOMString, null_string, ErrorResult};
 use dom::htmlelement::HTMLElement;
+use servo_util::url::make_url;
+
 use extra::url::Url;
+use std::ascii::StrAsciiExt;
+use std::float;
 
 pub struct HTMLImageElement {
     parent: HTMLElement,
+    /// The image to show, picked from `srcset` and `src`.
     image: Option<Url>,
+    /// The `src` attribute, resolved against the document's URL.
+    src: Option<Url>,
+    /// The candidates in the `srcset` attribute.
+    srcset: ~[ImageCandidate],
+    /// The entries of the `sizes` attribute.
+    sizes: ~[SourceSize],
+}
+
+/// What an image candidate in a `srcset` is suited to.
+#[deriving(Clone)]
+pub enum CandidateDescriptor {
+    /// The image is meant for this many device pixels per CSS pixel, as in `2x`.
+    DensityDescriptor(f32),
+    /// The image is this many pixels wide, as in `800w`.
+    WidthDescriptor(f32),
+}
+
+#[deriving(Clone)]
+pub struct ImageCandidate {
+    url: Url,
+    descriptor: CandidateDescriptor,
+}
+
+/// One entry of a `sizes` attribute: how wide the image will be laid out, in CSS pixels, when the
+/// viewport width is in a range.
+#[deriving(Clone)]
+pub struct SourceSize {
+    min_viewport_width: Option<f32>,
+    max_viewport_width: Option<f32>,
+    width: SourceSizeWidth,
+}
+
+#[deriving(Clone)]
+pub enum SourceSizeWidth {
+    SourceSizePx(f32),
+    /// Percent of the viewport width.
+    SourceSizeVw(f32),
+}
+
+/// Parses a `srcset` attribute, resolving the candidates' URLs against `base_url`. Candidates
+/// with invalid descriptors are dropped.
+pub fn parse_srcset(srcset: &str, base_url: &Url) -> ~[ImageCandidate] {
+    let mut candidates = ~[];
+    for candidate in srcset.split_iter(',') {
+        let mut words = candidate.word_iter();
+        let url = match words.next() {
+            Some(url) => url,
+            None => loop,
+        };
+        let descriptor = match words.next() {
+            None => Some(DensityDescriptor(1.0)),
+            Some(descriptor) => parse_candidate_descriptor(descriptor),
+        };
+        match descriptor {
+            Some(descriptor) if words.next().is_none() => {
+                candidates.push(ImageCandidate {
+                    url: make_url(url.to_owned(), Some(base_url.clone())),
+                    descriptor: descriptor,
+                })
+            }
+            _ => debug!("dropping invalid srcset candidate: %s", candidate),
+        }
+    }
+    candidates
+}
+
+fn parse_candidate_descriptor(descriptor: &str) -> Option<CandidateDescriptor> {
+    let descriptor = descriptor.to_ascii_lower();
+    if descriptor.len() < 2 {
+        return None
+    }
+    let number = float::from_str(descriptor.slice_to(descriptor.len() - 1));
+    match (number, descriptor.char_at(descriptor.len() - 1)) {
+        (Some(density), 'x') if density > 0.0 => Some(DensityDescriptor(density as f32)),
+        (Some(width), 'w') if width > 0.0 => Some(WidthDescriptor(width as f32)),
+        _ => None,
+    }
+}
+
+/// Parses a `sizes` attribute. Only `(min-width: ...)` and `(max-width: ...)` conditions are
+/// understood; entries with others are dropped.
+///
+/// FIXME: `em` lengths assume a 16px font.
+pub fn parse_sizes(sizes: &str) -> ~[SourceSize] {
+    let mut entries = ~[];
+    for entry in sizes.split_iter(',') {
+        let entry = entry.trim().to_ascii_lower();
+        let (condition, width) = match entry.rfind(' ') {
+            Some(index) => (entry.slice_to(index).trim(), entry.slice_from(index + 1)),
+            None => ("", entry.as_slice()),
+        };
+        let width = match parse_source_size_width(width) {
+            Some(width) => width,
+            None => loop,
+        };
+
+        let mut source_size = SourceSize {
+            min_viewport_width: None,
+            max_viewport_width: None,
+            width: width,
+        };
+        let mut understood = true;
+        for feature in condition.split_str_iter(" and ") {
+            let feature = feature.trim();
+            if feature.is_empty() {
+                loop
+            }
+            if !feature.starts_with("(") || !feature.ends_with(")") {
+                understood = false;
+                break
+            }
+            let mut parts = feature.slice(1, feature.len() - 1).splitn_iter(':', 1);
+            let name = parts.next().map(|name| name.trim());
+            let value = parts.next().and_then(|value| parse_length_px(value.trim()));
+            match (name, value) {
+                (Some("min-width"), Some(value)) => source_size.min_viewport_width = Some(value),
+                (Some("max-width"), Some(value)) => source_size.max_viewport_width = Some(value),
+                _ => {
+                    understood = false;
+                    break
+                }
+            }
+        }
+        if understood {
+            entries.push(source_size);
+        }
+    }
+    entries
+}
+
+fn parse_source_size_width(width: &str) -> Option<SourceSizeWidth> {
+    if width.ends_with("vw") {
+        float::from_str(width.slice_to(width.len() - 2)).map(|vw| SourceSizeVw(*vw as f32))
+    } else {
+        parse_length_px(width).map(|px| SourceSizePx(*px))
+    }
+}
+
+fn parse_length_px(length: &str) -> Option<f32> {
+    if length == "0" {
+        Some(0.0)
+    } else if length.ends_with("px") {
+        float::from_str(length.slice_to(length.len() - 2)).map(|px| *px as f32)
+    } else if length.ends_with("em") {
+        float::from_str(length.slice_to(length.len() - 2)).map(|em| *em as f32 * 16.0)
+    } else {
+        None
+    }
 }
 
 impl HTMLImageElement {
+    /// Picks the image to show out of `srcset`, or `src` if there's no `srcset`, for a viewport
+    /// `viewport_width` CSS pixels wide. Width descriptors are measured against the first entry of
+    /// `sizes` that matches, or failing that `laid_out_width`, how wide the image was last laid
+    /// out, or failing that the viewport. The candidate with the lowest density that's still at
+    /// least `device_pixel_ratio` wins, or the densest one if none are dense enough.
+    pub fn select_source(&self, viewport_width: f32, device_pixel_ratio: f32,
+                         laid_out_width: Option<f32>) -> Option<Url> {
+        if self.srcset.is_empty() {
+            return self.src.clone()
+        }
+
+        let slot_width = self.slot_width(viewport_width).or(laid_out_width)
+                                                        .unwrap_or_default(viewport_width);
+        let slot_width = if slot_width < 1.0 { 1.0 } else { slot_width };
+        let mut best: Option<(f32, &ImageCandidate)> = None;
+        for candidate in self.srcset.iter() {
+            let density = match candidate.descriptor {
+                DensityDescriptor(density) => density,
+                WidthDescriptor(width) => width / slot_width,
+            };
+            best = match best {
+                None => Some((density, candidate)),
+                Some((best_density, best_candidate)) => {
+                    let better = if best_density >= device_pixel_ratio {
+                        density >= device_pixel_ratio && density < best_density
+                    } else {
+                        density > best_density
+                    };
+                    if better {
+                        Some((density, candidate))
+                    } else {
+                        Some((best_density, best_candidate))
+                    }
+                }
+            };
+        }
+        best.map(|&(_, candidate)| candidate.url.clone())
+    }
+
+    /// Whether laying the image out at a different width could change which candidate
+    /// `select_source` picks.
+    pub fn depends_on_layout(&self) -> bool {
+        self.sizes.is_empty() && do self.srcset.iter().any |candidate| {
+            match candidate.descriptor {
+                WidthDescriptor(*) => true,
+                DensityDescriptor(*) => false,
+            }
+        }
+    }
+
+    /// How wide `sizes` says the image will be, if any of its entries match.
+    fn slot_width(&self, viewport_width: f32) -> Option<f32> {
+        for size in self.sizes.iter() {
+            let matches = size.min_viewport_width.map_default(true, |min| viewport_width >= *min) &&
+                size.max_viewport_width.map_default(true, |max| viewport_width <= *max);
+            if matches {
+                return Some(match size.width {
+                    SourceSizePx(px) => px,
+                    SourceSizeVw(vw) => viewport_width * vw / 100.0,
+                })
+            }
+        }
+        None
+    }
+
     pub fn Alt(&self) -> DOMString {
         null_string
     }
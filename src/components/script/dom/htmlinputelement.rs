@@ -2,14 +2,138 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{DOMString, null_string, str, ErrorResult};
+use dom::event::{Key, CharacterKey, BackspaceKey, DeleteKey, LeftKey, RightKey, HomeKey, EndKey};
+use dom::event::{EnterKey, UpKey, DownKey, TabKey, ShiftTabKey};
 use dom::htmlelement::HTMLElement;
+use dom::htmlformelement::form_owner;
+use dom::node::{AbstractNode, ScriptView};
+
+use servo_util::tree::TreeNodeRef;
+use std::ascii::StrAsciiExt;
 
 pub struct HTMLInputElement {
     parent: HTMLElement,
+    /// The value the user or script gave the control. Until it's first changed, the control shows
+    /// its `value` attribute instead, and this is `None`.
+    value: Option<~str>,
+    /// Where the caret is in the value, as a byte offset.
+    caret: uint,
+    /// Whether the control has focus, and so shows its caret.
+    focused: bool,
+    /// Whether the user or script checked the control. Until it's first changed, the control is
+    /// checked if it has a `checked` attribute, and this is `None`.
+    checked: Option<bool>,
 }
 
 impl HTMLInputElement {
+    /// The value the control shows.
+    pub fn value(&self) -> ~str {
+        match self.value {
+            Some(ref value) => value.clone(),
+            None => self.parent.parent.get_attr("value").unwrap_or_default("").to_owned(),
+        }
+    }
+
+    /// The control's type, from its `type` attribute. Missing and unknown types mean `text`.
+    pub fn type_(&self) -> ~str {
+        let type_ = self.parent.parent.get_attr("type").unwrap_or_default("").to_ascii_lower();
+        match type_.as_slice() {
+            "text" | "search" | "tel" | "url" | "email" | "password" | "number" | "hidden" |
+            "checkbox" | "radio" | "file" | "submit" | "image" | "reset" | "button" => type_,
+            _ => ~"text",
+        }
+    }
+
+    /// Whether the control is one the user types text into.
+    pub fn is_text_field(&self) -> bool {
+        is_text_field_type(self.type_().as_slice())
+    }
+
+    /// Whether the control is a checkbox or radio button.
+    pub fn is_checkable(&self) -> bool {
+        is_checkable_type(self.type_().as_slice())
+    }
+
+    /// Whether the control is a button that submits its form.
+    pub fn is_submit_button(&self) -> bool {
+        match self.type_().as_slice() {
+            "submit" | "image" => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the control is checked. Only checkboxes and radio buttons show it.
+    pub fn checked(&self) -> bool {
+        match self.checked {
+            Some(checked) => checked,
+            None => self.parent.parent.get_attr("checked").is_some(),
+        }
+    }
+
+    /// Checks or unchecks the control. Checking a radio button unchecks the others in its group.
+    pub fn set_checked(&mut self, abstract_self: AbstractNode<ScriptView>, checked: bool) {
+        self.checked = Some(checked);
+        if checked && "radio" == self.type_() {
+            let name = self.parent.parent.get_attr("name").unwrap_or_default("").to_owned();
+            uncheck_radio_group(abstract_self, name);
+        }
+        self.changed();
+    }
+
+    /// Does what clicking the control does: toggles a checkbox, or checks a radio button.
+    pub fn activate(&mut self, abstract_self: AbstractNode<ScriptView>) {
+        match self.type_().as_slice() {
+            "checkbox" => {
+                let checked = !self.checked();
+                self.set_checked(abstract_self, checked)
+            }
+            "radio" => self.set_checked(abstract_self, true),
+            _ => {}
+        }
+    }
+
+    /// The caret, if the control shows one.
+    pub fn caret(&self) -> Option<uint> {
+        if self.focused && self.is_text_field() {
+            Some(self.caret)
+        } else {
+            None
+        }
+    }
+
+    /// Gives the control focus, with the caret at the end of its value, or takes it away.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        self.caret = self.value().len();
+        self.changed();
+    }
+
+    /// Edits the value as the user pressing `key` asks. Returns false if the key doesn't edit
+    /// text.
+    pub fn handle_key(&mut self, key: Key) -> bool {
+        let value = self.value();
+        match edit_text(value, self.caret, key) {
+            Some((value, caret)) => {
+                self.value = Some(value);
+                self.caret = caret;
+                self.changed();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks the control for snapshotting, and tells the document it needs laying out again.
+    fn changed(&self) {
+        for node in self.parent.parent.parent.abstract.iter() {
+            node.dirty_snapshots(false);
+        }
+        for doc in self.parent.parent.parent.owner_doc.iter() {
+            doc.with_base(|doc| doc.content_changed());
+        }
+    }
+
     pub fn Accept(&self) -> DOMString {
         null_string
     }
@@ -39,17 +163,23 @@ impl HTMLInputElement {
     }
 
     pub fn DefaultChecked(&self) -> bool {
-        false
+        self.parent.parent.get_attr("checked").is_some()
     }
 
-    pub fn SetDefaultChecked(&mut self, _default_checked: bool, _rv: &mut ErrorResult) {
+    pub fn SetDefaultChecked(&mut self, default_checked: bool, _rv: &mut ErrorResult) {
+        if default_checked {
+            self.parent.parent.set_attr(&str(~"checked"), &str(~""));
+        } else {
+            self.parent.parent.remove_attr("checked");
+        }
     }
 
-    pub fn Checked(&self) -> bool {
-        false
+    pub fn Checked(&self, _abstract_self: AbstractNode<ScriptView>) -> bool {
+        self.checked()
     }
 
-    pub fn SetChecked(&mut self, _checked: bool) {
+    pub fn SetChecked(&mut self, abstract_self: AbstractNode<ScriptView>, checked: bool) {
+        self.set_checked(abstract_self, checked)
     }
 
     pub fn Disabled(&self) -> bool {
@@ -144,10 +274,11 @@ impl HTMLInputElement {
     }
 
     pub fn Name(&self) -> DOMString {
-        null_string
+        str(self.parent.parent.get_attr("name").unwrap_or_default("").to_owned())
     }
 
-    pub fn SetName(&mut self, _name: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetName(&mut self, name: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"name"), name);
     }
 
     pub fn Pattern(&self) -> DOMString {
@@ -200,24 +331,30 @@ impl HTMLInputElement {
     }
 
     pub fn Type(&self) -> DOMString {
-        null_string
+        str(self.type_())
     }
 
-    pub fn SetType(&mut self, _type: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetType(&mut self, type_: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"type"), type_);
     }
 
     pub fn DefaultValue(&self) -> DOMString {
-        null_string
+        str(self.parent.parent.get_attr("value").unwrap_or_default("").to_owned())
     }
 
-    pub fn SetDefaultValue(&mut self, _default_value: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetDefaultValue(&mut self, default_value: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"value"), default_value);
     }
 
     pub fn Value(&self) -> DOMString {
-        null_string
+        str(self.value())
     }
 
-    pub fn SetValue(&mut self, _value: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetValue(&mut self, value: &DOMString, _rv: &mut ErrorResult) {
+        let value = value.to_str();
+        self.caret = value.len();
+        self.value = Some(value);
+        self.changed();
     }
 
     pub fn Width(&self) -> u32 {
@@ -283,3 +420,86 @@ impl HTMLInputElement {
     pub fn SetUseMap(&mut self, _align: &DOMString, _rv: &mut ErrorResult) {
     }
 }
+
+/// Whether controls of type `type_` are ones the user types text into.
+pub fn is_text_field_type(type_: &str) -> bool {
+    match type_ {
+        "text" | "search" | "tel" | "url" | "email" | "password" | "number" => true,
+        _ => false,
+    }
+}
+
+/// Whether controls of type `type_` are checkboxes or radio buttons.
+pub fn is_checkable_type(type_: &str) -> bool {
+    match type_ {
+        "checkbox" | "radio" => true,
+        _ => false,
+    }
+}
+
+/// Edits `value` as pressing `key` with the caret at byte offset `caret` does in a text field or
+/// text area. Returns the new value and caret, or `None` if the key doesn't edit text on one line.
+pub fn edit_text(value: &str, caret: uint, key: Key) -> Option<(~str, uint)> {
+    let caret = caret.min(&value.len());
+    let mut value = value.to_owned();
+    let caret = match key {
+        CharacterKey(character) => {
+            let mut edited = value.slice_to(caret).to_owned();
+            edited.push_char(character);
+            let new_caret = edited.len();
+            edited.push_str(value.slice_from(caret));
+            value = edited;
+            new_caret
+        }
+        BackspaceKey if caret > 0 => {
+            let previous = value.char_range_at_reverse(caret).next;
+            value = value.slice_to(previous) + value.slice_from(caret);
+            previous
+        }
+        DeleteKey if caret < value.len() => {
+            let next = value.char_range_at(caret).next;
+            value = value.slice_to(caret) + value.slice_from(next);
+            caret
+        }
+        BackspaceKey | DeleteKey => caret,
+        LeftKey if caret > 0 => value.char_range_at_reverse(caret).next,
+        RightKey if caret < value.len() => value.char_range_at(caret).next,
+        LeftKey | RightKey => caret,
+        HomeKey => 0,
+        EndKey => value.len(),
+        EnterKey | UpKey | DownKey | TabKey | ShiftTabKey => return None,
+    };
+    Some((value, caret))
+}
+
+/// Unchecks the radio buttons in the same group as `radio`, which is named `name`: the others with
+/// that name in the same form, or in the same tree and in no form if `radio` isn't in one.
+fn uncheck_radio_group(radio: AbstractNode<ScriptView>, name: ~str) {
+    if name.is_empty() {
+        return
+    }
+    let form = form_owner(radio);
+    let mut root = radio;
+    loop {
+        match root.parent_node() {
+            Some(parent) => root = parent,
+            None => break,
+        }
+    }
+    let scope = form.unwrap_or_default(root);
+
+    for node in scope.traverse_preorder() {
+        if node == radio || !node.is_input_element() {
+            loop
+        }
+        let in_group = do node.with_imm_input_element |input| {
+            "radio" == input.type_() &&
+                input.parent.parent.get_attr("name") == Some(name.as_slice())
+        };
+        if in_group && form_owner(node) == form {
+            do node.with_mut_input_element |input| {
+                input.checked = Some(false);
+            }
+        }
+    }
+}
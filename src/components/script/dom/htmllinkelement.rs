@@ -2,23 +2,106 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{DOMString, null_string, str, ErrorResult};
 use dom::htmlelement::HTMLElement;
+use dom::node::{AbstractNode, ScriptView};
+use layout_interface::{LayoutChan, SetStylesheetEnabledMsg};
+use servo_util::tree::TreeNodeRef;
+
+use std::ascii::StrAsciiExt;
 
 pub struct HTMLLinkElement {
     parent: HTMLElement,
+    /// The index of the style sheet this element loaded among those of the document, if it
+    /// loaded one.
+    stylesheet: Option<uint>,
+    /// What script last set `disabled` to, if it set it.
+    disabled: Option<bool>,
+    /// Whether the style sheet applies, as last worked out by `update_stylesheet_sets()`.
+    enabled: bool,
+}
+
+/// Whether a `rel` attribute makes a `<link>` load a style sheet.
+pub fn is_stylesheet_rel(rel: &str) -> bool {
+    rel.word_iter().any(|keyword| keyword.eq_ignore_ascii_case("stylesheet"))
+}
+
+fn is_alternate_rel(rel: &str) -> bool {
+    rel.word_iter().any(|keyword| keyword.eq_ignore_ascii_case("alternate"))
+}
+
+/// Works out which of the style sheets of the `<link>` elements under `root` apply, and tells
+/// layout. Untitled sheets always do, and titled ones do if they're in the preferred style sheet
+/// set, which is named by the first titled sheet that isn't an alternate. Setting `disabled`
+/// overrides either.
+pub fn update_stylesheet_sets(root: AbstractNode<ScriptView>, layout_chan: &LayoutChan) {
+    let links: ~[AbstractNode<ScriptView>] = root.traverse_preorder().filter(|node| {
+        node.is_link_element() && node.with_imm_link_element(|link| link.stylesheet.is_some())
+    }).collect();
+
+    let mut preferred_set = None;
+    for link in links.iter() {
+        let title = do link.with_imm_link_element |link| {
+            if link.is_alternate() { None } else { link.title() }
+        };
+        if title.is_some() {
+            preferred_set = title;
+            break;
+        }
+    }
+
+    for link in links.iter() {
+        do link.with_mut_link_element |link| {
+            link.enabled = match link.disabled {
+                Some(disabled) => !disabled,
+                None => match link.title() {
+                    Some(title) => preferred_set == Some(title),
+                    // An untitled alternate isn't in any set, so it never applies.
+                    None => !link.is_alternate(),
+                },
+            };
+            layout_chan.send(SetStylesheetEnabledMsg(link.stylesheet.unwrap(), link.enabled));
+        }
+    }
 }
 
 impl HTMLLinkElement {
-    pub fn Disabled(&self) -> bool {
-        false
+    fn is_alternate(&self) -> bool {
+        is_alternate_rel(self.parent.parent.get_attr("rel").unwrap_or_default(""))
+    }
+
+    /// The name of the style sheet set this element's style sheet is in, if any.
+    fn title(&self) -> Option<~str> {
+        match self.parent.parent.get_attr("title") {
+            Some(title) if !title.trim().is_empty() => Some(title.trim().to_owned()),
+            _ => None,
+        }
     }
 
-    pub fn SetDisabled(&mut self, _disable: bool) {
+    pub fn Disabled(&self) -> bool {
+        self.stylesheet.is_some() && !self.enabled
+    }
+
+    pub fn SetDisabled(&mut self, disabled: bool) {
+        self.disabled = Some(disabled);
+        if self.stylesheet.is_none() {
+            return;
+        }
+        let doc = match self.parent.parent.parent.owner_doc {
+            Some(doc) => doc,
+            None => return,
+        };
+        let (root, window) = doc.with_base(|doc| (doc.root, doc.window));
+        for window in window.iter() {
+            unsafe {
+                update_stylesheet_sets(root, &(*window.page).layout_chan);
+            }
+            window.content_changed();
+        }
     }
 
     pub fn Href(&self) -> DOMString {
-        null_string
+        str(self.parent.parent.get_attr("href").unwrap_or_default("").to_owned())
     }
 
     pub fn SetHref(&mut self, _href: &DOMString, _rv: &mut ErrorResult) {
@@ -32,14 +115,14 @@ impl HTMLLinkElement {
     }
 
     pub fn Rel(&self) -> DOMString {
-        null_string
+        str(self.parent.parent.get_attr("rel").unwrap_or_default("").to_owned())
     }
 
     pub fn SetRel(&mut self, _rel: &DOMString, _rv: &mut ErrorResult) {
     }
 
     pub fn Media(&self) -> DOMString {
-        null_string
+        str(self.parent.parent.get_attr("media").unwrap_or_default("").to_owned())
     }
 
     pub fn SetMedia(&mut self, _media: &DOMString, _rv: &mut ErrorResult) {
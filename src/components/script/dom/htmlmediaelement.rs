@@ -0,0 +1,472 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! What `<video>` and `<audio>` elements have in common: loading a source, and playing it. Each
+//! source an element loads is played by a media task of its own; see `media_task`.
+//!
+//! Sources are fetched and decoded whole, so an element goes from having nothing to having enough
+//! data to play through in one step, and fires the events in between together.
+//!
+//! FIXME: Sources are only loaded when the document is parsed or when script sets `src` or calls
+//! `load()`, not when `setAttribute()` changes `src` or a `<source>` is added.
+
+use dom::bindings::codegen::HTMLMediaElementBinding;
+use dom::bindings::utils::{DOMString, str};
+use dom::bindings::utils::{CacheableWrapper, BindingObject, WrapperCache};
+use dom::element::ElementTypeId;
+use dom::event::Event;
+use dom::eventtarget::{EventListeners, invoke_listeners};
+use dom::htmlelement::HTMLElement;
+use dom::node::{AbstractNode, ScriptView};
+use dom::window::Window;
+use media_task::{EndedEvent, MediaChan, MediaErrorEvent, MediaEvent, MediaTask};
+use media_task::{MetadataEvent, PauseMsg, PlayMsg, SeekMsg, TimeUpdateEvent};
+
+use js::jsapi::{JSContext, JSObject, JSVal};
+use servo_util::tree::TreeNodeRef;
+use servo_util::url::make_url;
+use extra::url::Url;
+
+use std::f64;
+
+/// Identifies the source a media element is loading or playing within its window, so that what
+/// its media task says can find the element. Each load gets a new one, so that a task that was
+/// replaced can't be heard from any more.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct MediaId(uint);
+
+// The values of `networkState`.
+pub static NETWORK_EMPTY: u16 = 0;
+pub static NETWORK_IDLE: u16 = 1;
+pub static NETWORK_LOADING: u16 = 2;
+pub static NETWORK_NO_SOURCE: u16 = 3;
+
+// The values of `readyState`.
+pub static HAVE_NOTHING: u16 = 0;
+pub static HAVE_METADATA: u16 = 1;
+pub static HAVE_CURRENT_DATA: u16 = 2;
+pub static HAVE_FUTURE_DATA: u16 = 3;
+pub static HAVE_ENOUGH_DATA: u16 = 4;
+
+pub struct HTMLMediaElement {
+    parent: HTMLElement,
+    /// The source being played, the id its media task reports with, and the channel to the task.
+    /// `None` until the element has a source to load.
+    id: Option<MediaId>,
+    chan: Option<MediaChan>,
+    current_src: Option<Url>,
+    network_state: u16,
+    ready_state: u16,
+    paused: bool,
+    ended: bool,
+    /// The position, in seconds, as of the last time the media task said.
+    current_time: f64,
+    /// How long the media lasts, in seconds, or NaN until that's known.
+    duration: f64,
+    listeners: EventListeners,
+}
+
+impl HTMLMediaElement {
+    pub fn new(type_id: ElementTypeId, tag_name: ~str) -> HTMLMediaElement {
+        HTMLMediaElement {
+            parent: HTMLElement::new(type_id, tag_name),
+            id: None,
+            chan: None,
+            current_src: None,
+            network_state: NETWORK_EMPTY,
+            ready_state: HAVE_NOTHING,
+            paused: true,
+            ended: false,
+            current_time: 0.0,
+            duration: f64::NaN,
+            listeners: EventListeners::new(),
+        }
+    }
+
+    pub fn Src(&self, _abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        str(self.parent.parent.get_attr("src").unwrap_or_default("").to_owned())
+    }
+
+    pub fn SetSrc(&mut self, abstract_self: AbstractNode<ScriptView>, src: &DOMString) {
+        self.parent.parent.set_attr(&str(~"src"), src);
+        self.load(abstract_self);
+    }
+
+    pub fn CurrentSrc(&self, _abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        match self.current_src {
+            Some(ref url) => str(url.to_str()),
+            None => str(~""),
+        }
+    }
+
+    pub fn Load(&mut self, abstract_self: AbstractNode<ScriptView>) {
+        self.load(abstract_self);
+    }
+
+    pub fn NetworkState(&self) -> u16 {
+        self.network_state
+    }
+
+    pub fn ReadyState(&self) -> u16 {
+        self.ready_state
+    }
+
+    pub fn CurrentTime(&self) -> f64 {
+        self.current_time
+    }
+
+    pub fn SetCurrentTime(&mut self, time: f64) {
+        if !time.is_finite() {
+            return;
+        }
+        let time = if self.duration.is_nan() { time } else { time.max(&0.0).min(&self.duration) };
+        self.current_time = time;
+        self.ended = false;
+        for chan in self.chan.iter() {
+            chan.send(SeekMsg(time));
+        }
+    }
+
+    pub fn Duration(&self) -> f64 {
+        self.duration
+    }
+
+    pub fn Paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn Ended(&self) -> bool {
+        self.ended
+    }
+
+    pub fn Autoplay(&self) -> bool {
+        self.parent.parent.get_attr("autoplay").is_some()
+    }
+
+    pub fn SetAutoplay(&mut self, autoplay: bool) {
+        if autoplay {
+            self.parent.parent.set_attr(&str(~"autoplay"), &str(~""));
+        } else {
+            self.parent.parent.remove_attr("autoplay");
+        }
+    }
+
+    pub fn Play(&mut self, abstract_self: AbstractNode<ScriptView>) {
+        if self.chan.is_none() {
+            self.load(abstract_self);
+        }
+        if !self.paused {
+            return;
+        }
+        self.paused = false;
+        self.ended = false;
+        for chan in self.chan.iter() {
+            chan.send(PlayMsg);
+        }
+        fire_event(abstract_self, "play");
+        if self.ready_state >= HAVE_FUTURE_DATA {
+            fire_event(abstract_self, "playing");
+        }
+    }
+
+    pub fn Pause(&mut self) {
+        if self.paused {
+            return;
+        }
+        self.paused = true;
+        for chan in self.chan.iter() {
+            chan.send(PauseMsg);
+        }
+        // The media task answers with the position it stopped at, and the `pause` event goes
+        // with the `timeupdate` for that.
+    }
+
+    pub fn Onloadstart(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("loadstart")
+    }
+
+    pub fn SetOnloadstart(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("loadstart", handler);
+    }
+
+    pub fn Onemptied(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("emptied")
+    }
+
+    pub fn SetOnemptied(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("emptied", handler);
+    }
+
+    pub fn Ondurationchange(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("durationchange")
+    }
+
+    pub fn SetOndurationchange(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("durationchange", handler);
+    }
+
+    pub fn Onloadedmetadata(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("loadedmetadata")
+    }
+
+    pub fn SetOnloadedmetadata(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("loadedmetadata", handler);
+    }
+
+    pub fn Onloadeddata(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("loadeddata")
+    }
+
+    pub fn SetOnloadeddata(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("loadeddata", handler);
+    }
+
+    pub fn Oncanplay(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("canplay")
+    }
+
+    pub fn SetOncanplay(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("canplay", handler);
+    }
+
+    pub fn Oncanplaythrough(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("canplaythrough")
+    }
+
+    pub fn SetOncanplaythrough(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("canplaythrough", handler);
+    }
+
+    pub fn Ontimeupdate(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("timeupdate")
+    }
+
+    pub fn SetOntimeupdate(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("timeupdate", handler);
+    }
+
+    pub fn Onplay(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("play")
+    }
+
+    pub fn SetOnplay(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("play", handler);
+    }
+
+    pub fn Onplaying(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("playing")
+    }
+
+    pub fn SetOnplaying(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("playing", handler);
+    }
+
+    pub fn Onpause(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("pause")
+    }
+
+    pub fn SetOnpause(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("pause", handler);
+    }
+
+    pub fn Onended(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("ended")
+    }
+
+    pub fn SetOnended(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("ended", handler);
+    }
+
+    pub fn Onerror(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("error")
+    }
+
+    pub fn SetOnerror(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("error", handler);
+    }
+
+    pub fn AddEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                            _capture: bool) {
+        self.listeners.add(type_, listener);
+    }
+
+    pub fn RemoveEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                               _capture: bool) {
+        self.listeners.remove(type_, listener);
+    }
+}
+
+impl HTMLMediaElement {
+    /// The URL to load: the `src` attribute, or failing that that of the first `<source>` child
+    /// that has one.
+    fn source(&self, abstract_self: AbstractNode<ScriptView>) -> Option<~str> {
+        match self.parent.parent.get_attr("src") {
+            Some(src) => return Some(src.to_owned()),
+            None => {}
+        }
+        for child in abstract_self.children() {
+            if !child.is_source_element() {
+                loop
+            }
+            let src = do child.with_imm_element |element| {
+                element.get_attr("src").map(|src| src.to_owned())
+            };
+            if src.is_some() {
+                return src;
+            }
+        }
+        None
+    }
+
+    /// Stops playing whatever source was loaded, and starts a media task loading the current one.
+    /// The media starts playing as soon as it can if the element has `autoplay`.
+    pub fn load(&mut self, abstract_self: AbstractNode<ScriptView>) {
+        let window = match window_of(abstract_self) {
+            Some(window) => window,
+            None => return,
+        };
+        let mut events = ~[];
+        if self.network_state != NETWORK_EMPTY {
+            events.push("emptied");
+        }
+        for &id in self.id.iter() {
+            window.remove_media(id);
+        }
+        self.id = None;
+        self.chan = None;
+        self.current_src = None;
+        self.network_state = NETWORK_EMPTY;
+        self.ready_state = HAVE_NOTHING;
+        self.paused = true;
+        self.ended = false;
+        self.current_time = 0.0;
+        self.duration = f64::NaN;
+
+        // Layout drops the old video layer, and later shows the new one.
+        window.content_changed();
+
+        match self.source(abstract_self) {
+            Some(src) => {
+                let url = make_url(src, window.url());
+                let id = window.next_media_id();
+                let pipeline_id = unsafe { (*window.page).id.clone() };
+                let chan = MediaTask::create(url.clone(),
+                                             window.script_chan.clone(),
+                                             pipeline_id,
+                                             id,
+                                             window.compositor.video_frame_sink(),
+                                             window.resource_task.clone());
+                window.add_media(id, abstract_self, chan.clone());
+                self.network_state = NETWORK_LOADING;
+                events.push("loadstart");
+                if self.Autoplay() {
+                    self.paused = false;
+                    chan.send(PlayMsg);
+                    events.push("play");
+                }
+                self.id = Some(id);
+                self.chan = Some(chan);
+                self.current_src = Some(url);
+            }
+            None => self.network_state = NETWORK_NO_SOURCE,
+        }
+
+        for &type_ in events.iter() {
+            fire_event(abstract_self, type_);
+        }
+    }
+}
+
+impl CacheableWrapper for HTMLMediaElement {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        self.parent.get_wrappercache()
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        HTMLMediaElementBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for HTMLMediaElement {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        self.parent.GetParentObject(cx)
+    }
+}
+
+/// Takes in what the media task of `node` said, and fires the events that calls for.
+pub fn process_event(node: AbstractNode<ScriptView>, event: MediaEvent) {
+    match event {
+        MetadataEvent(_, size) if node.is_video_element() => {
+            do node.with_mut_video_element |video| {
+                video.video_size = size;
+            }
+        }
+        _ => {}
+    }
+    let events = do node.with_mut_media_element |media| {
+        match event {
+            MetadataEvent(duration, _) => {
+                // The whole source is in memory by now.
+                media.duration = duration;
+                media.network_state = NETWORK_IDLE;
+                media.ready_state = HAVE_ENOUGH_DATA;
+                let mut events = ~["durationchange", "loadedmetadata", "loadeddata", "canplay",
+                                   "canplaythrough"];
+                if !media.paused {
+                    events.push("playing");
+                }
+                events
+            }
+            TimeUpdateEvent(time) => {
+                media.current_time = time;
+                if media.paused { ~["timeupdate", "pause"] } else { ~["timeupdate"] }
+            }
+            EndedEvent(time) => {
+                media.current_time = time;
+                media.paused = true;
+                media.ended = true;
+                ~["timeupdate", "pause", "ended"]
+            }
+            MediaErrorEvent => {
+                media.network_state = NETWORK_NO_SOURCE;
+                ~["error"]
+            }
+        }
+    };
+    for &type_ in events.iter() {
+        fire_event(node, type_);
+    }
+}
+
+/// Fires a simple event of the given type at `node`.
+fn fire_event(node: AbstractNode<ScriptView>, type_: &str) {
+    let type_ = str(type_.to_owned());
+    let listeners = do node.with_imm_media_element |media| {
+        media.listeners.get(&type_)
+    };
+    if listeners.is_empty() {
+        return;
+    }
+    let window = match window_of(node) {
+        Some(window) => window,
+        None => return,
+    };
+
+    // Listeners can only have been added from script, so the element has a wrapper by now.
+    let this = node.with_base(|node| node.wrapper.get_wrapper());
+    let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+    let event = @mut Event::new(&type_);
+    event.bubbles = false;
+    event.cancelable = false;
+    event.trusted = true;
+    let event_obj = event.wrap_object_shared(cx, window.wrapper.get_wrapper());
+    invoke_listeners(cx, this, listeners, event_obj);
+}
+
+/// The window of the document `node` is in, if it's in one that has a window.
+fn window_of(node: AbstractNode<ScriptView>) -> Option<@mut Window> {
+    let doc = node.with_base(|node| node.owner_doc);
+    doc.chain(|doc| doc.with_base(|doc| doc.window))
+}
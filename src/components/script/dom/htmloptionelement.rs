@@ -0,0 +1,163 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::utils::{DOMString, str, ErrorResult};
+use dom::htmlelement::HTMLElement;
+use dom::htmlselectelement;
+use dom::node::{AbstractNode, ScriptView};
+
+use servo_util::tree::TreeNodeRef;
+
+pub struct HTMLOptionElement {
+    parent: HTMLElement,
+    /// Whether the user or script selected the option. Until it's first changed, the option is
+    /// selected if it has a `selected` attribute, and this is `None`.
+    selected: Option<bool>,
+}
+
+impl HTMLOptionElement {
+    /// Whether the option is selected.
+    pub fn selected(&self) -> bool {
+        match self.selected {
+            Some(selected) => selected,
+            None => self.parent.parent.get_attr("selected").is_some(),
+        }
+    }
+
+    /// Marks the option for snapshotting, and tells the document its select needs laying out
+    /// again.
+    fn changed(&self) {
+        for node in self.parent.parent.parent.abstract.iter() {
+            node.dirty_snapshots(false);
+        }
+        for doc in self.parent.parent.parent.owner_doc.iter() {
+            doc.with_base(|doc| doc.content_changed());
+        }
+    }
+
+    fn set_bool_attr(&mut self, name: &str, value: bool) {
+        if value {
+            self.parent.parent.set_attr(&str(name.to_owned()), &str(~""));
+        } else {
+            self.parent.parent.remove_attr(name);
+        }
+    }
+
+    pub fn Disabled(&self) -> bool {
+        self.parent.parent.get_attr("disabled").is_some()
+    }
+
+    pub fn SetDisabled(&mut self, disabled: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("disabled", disabled)
+    }
+
+    pub fn Label(&self, abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        str(label(abstract_self))
+    }
+
+    pub fn SetLabel(&mut self,
+                    _abstract_self: AbstractNode<ScriptView>,
+                    label: &DOMString,
+                    _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"label"), label);
+    }
+
+    pub fn DefaultSelected(&self) -> bool {
+        self.parent.parent.get_attr("selected").is_some()
+    }
+
+    pub fn SetDefaultSelected(&mut self, default_selected: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("selected", default_selected)
+    }
+
+    pub fn Selected(&self, _abstract_self: AbstractNode<ScriptView>) -> bool {
+        self.selected()
+    }
+
+    pub fn SetSelected(&mut self, abstract_self: AbstractNode<ScriptView>, selected: bool) {
+        self.selected = Some(selected);
+        match htmlselectelement::select_owner(abstract_self) {
+            Some(select) if selected && !htmlselectelement::is_multiple(select) => {
+                let index = htmlselectelement::options(select).iter().position(|&option| {
+                    option == abstract_self
+                });
+                htmlselectelement::select_index(select, index)
+            }
+            _ => self.changed(),
+        }
+    }
+
+    pub fn Value(&self, abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        str(value(abstract_self))
+    }
+
+    pub fn SetValue(&mut self,
+                    _abstract_self: AbstractNode<ScriptView>,
+                    value: &DOMString,
+                    _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"value"), value);
+    }
+
+    pub fn Text(&self, abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        str(text(abstract_self))
+    }
+
+    pub fn SetText(&mut self,
+                   abstract_self: AbstractNode<ScriptView>,
+                   text: &DOMString,
+                   rv: &mut ErrorResult) {
+        do abstract_self.with_mut_base |node| {
+            node.SetTextContent(abstract_self, text, rv)
+        }
+    }
+
+    pub fn Index(&self, abstract_self: AbstractNode<ScriptView>) -> i32 {
+        match htmlselectelement::select_owner(abstract_self) {
+            Some(select) => {
+                let options = htmlselectelement::options(select);
+                match options.iter().position(|&option| option == abstract_self) {
+                    Some(index) => index as i32,
+                    None => 0,
+                }
+            }
+            None => 0,
+        }
+    }
+}
+
+/// The option's text, with runs of whitespace collapsed to single spaces and trimmed.
+pub fn text<View>(option: AbstractNode<View>) -> ~str {
+    let mut data = ~"";
+    for node in option.traverse_preorder() {
+        if node.is_text() {
+            do node.with_imm_text |text| {
+                data.push_str(text.parent.data.to_str());
+            }
+        }
+    }
+    let words: ~[&str] = data.word_iter().collect();
+    words.connect(" ")
+}
+
+/// What the option shows in its select: its `label` attribute, or else its text.
+pub fn label<View>(option: AbstractNode<View>) -> ~str {
+    let label = do option.with_imm_element |element| {
+        element.get_attr("label").map(|label| label.to_owned())
+    };
+    match label {
+        Some(label) if !label.is_empty() => label,
+        _ => text(option),
+    }
+}
+
+/// The value the option gives its select: its `value` attribute, or else its text.
+pub fn value<View>(option: AbstractNode<View>) -> ~str {
+    let value = do option.with_imm_element |element| {
+        element.get_attr("value").map(|value| value.to_owned())
+    };
+    match value {
+        Some(value) => value,
+        None => text(option),
+    }
+}
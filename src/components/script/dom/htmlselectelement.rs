@@ -0,0 +1,180 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::utils::{DOMString, str, ErrorResult};
+use dom::htmlelement::HTMLElement;
+use dom::htmloptionelement;
+use dom::node::{AbstractNode, ScriptView};
+
+use servo_util::tree::TreeNodeRef;
+use std::uint;
+
+pub struct HTMLSelectElement {
+    parent: HTMLElement,
+}
+
+impl HTMLSelectElement {
+    fn set_bool_attr(&mut self, name: &str, value: bool) {
+        if value {
+            self.parent.parent.set_attr(&str(name.to_owned()), &str(~""));
+        } else {
+            self.parent.parent.remove_attr(name);
+        }
+    }
+
+    pub fn Autofocus(&self) -> bool {
+        self.parent.parent.get_attr("autofocus").is_some()
+    }
+
+    pub fn SetAutofocus(&mut self, autofocus: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("autofocus", autofocus)
+    }
+
+    pub fn Disabled(&self) -> bool {
+        self.parent.parent.get_attr("disabled").is_some()
+    }
+
+    pub fn SetDisabled(&mut self, disabled: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("disabled", disabled)
+    }
+
+    pub fn Multiple(&self) -> bool {
+        self.parent.parent.get_attr("multiple").is_some()
+    }
+
+    pub fn SetMultiple(&mut self, multiple: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("multiple", multiple)
+    }
+
+    pub fn Name(&self) -> DOMString {
+        str(self.parent.parent.get_attr("name").unwrap_or_default("").to_owned())
+    }
+
+    pub fn SetName(&mut self, name: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"name"), name);
+    }
+
+    pub fn Required(&self) -> bool {
+        self.parent.parent.get_attr("required").is_some()
+    }
+
+    pub fn SetRequired(&mut self, required: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("required", required)
+    }
+
+    pub fn Size(&self) -> u32 {
+        let size = self.parent.parent.get_attr("size").chain(|size| uint::from_str(size));
+        size.unwrap_or_default(0) as u32
+    }
+
+    pub fn SetSize(&mut self, size: u32, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"size"), &str(size.to_str()));
+    }
+
+    pub fn Type(&self) -> DOMString {
+        if self.Multiple() {
+            str(~"select-multiple")
+        } else {
+            str(~"select-one")
+        }
+    }
+
+    pub fn Length(&self, abstract_self: AbstractNode<ScriptView>) -> u32 {
+        options(abstract_self).len() as u32
+    }
+
+    pub fn SelectedIndex(&self, abstract_self: AbstractNode<ScriptView>) -> i32 {
+        match selected_index(abstract_self) {
+            Some(index) => index as i32,
+            None => -1,
+        }
+    }
+
+    pub fn SetSelectedIndex(&mut self, abstract_self: AbstractNode<ScriptView>, index: i32) {
+        let index = if index < 0 { None } else { Some(index as uint) };
+        select_index(abstract_self, index)
+    }
+
+    pub fn Value(&self, abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        match selected_index(abstract_self) {
+            Some(index) => str(htmloptionelement::value(options(abstract_self)[index])),
+            None => str(~""),
+        }
+    }
+
+    pub fn SetValue(&mut self, abstract_self: AbstractNode<ScriptView>, value: &DOMString) {
+        let value = value.to_str();
+        let index = options(abstract_self).iter().position(|&option| {
+            htmloptionelement::value(option) == value
+        });
+        select_index(abstract_self, index)
+    }
+}
+
+/// The options in `select`, in tree order, including those in option groups.
+pub fn options<View>(select: AbstractNode<View>) -> ~[AbstractNode<View>] {
+    select.traverse_preorder().filter(|node| node.is_option_element()).collect()
+}
+
+/// Whether `select` lets the user select more than one option.
+pub fn is_multiple<View>(select: AbstractNode<View>) -> bool {
+    select.with_imm_element(|element| element.get_attr("multiple").is_some())
+}
+
+/// The select `option` belongs to, if it's in one.
+pub fn select_owner<View>(option: AbstractNode<View>) -> Option<AbstractNode<View>> {
+    let mut current = option.parent_node();
+    while current.is_some() {
+        let ancestor = current.unwrap();
+        if ancestor.is_select_element() {
+            return Some(ancestor)
+        }
+        current = ancestor.parent_node();
+    }
+    None
+}
+
+/// The index of the first selected option in `select`. A select that shows one option at a time
+/// always has one selected while it has options, so if none is, the first option counts.
+pub fn selected_index<View>(select: AbstractNode<View>) -> Option<uint> {
+    let options = options(select);
+    let selected = do options.iter().position |&option| {
+        option.with_imm_option_element(|option| option.selected())
+    };
+    match selected {
+        None if !options.is_empty() && !is_multiple(select) => Some(0),
+        selected => selected,
+    }
+}
+
+/// Selects the option at `index` in `select`, and deselects the others. `None` deselects them
+/// all.
+pub fn select_index(select: AbstractNode<ScriptView>, index: Option<uint>) {
+    for (i, &option) in options(select).iter().enumerate() {
+        do option.with_mut_option_element |option| {
+            option.selected = Some(Some(i) == index);
+        }
+        option.dirty_snapshots(false);
+    }
+    do select.with_base |node| {
+        for doc in node.owner_doc.iter() {
+            doc.with_base(|doc| doc.content_changed());
+        }
+    }
+}
+
+/// Moves the selection in `select` by `delta` options, as the arrow keys do, stopping at the first
+/// and last options.
+pub fn step_selection(select: AbstractNode<ScriptView>, delta: int) {
+    let count = options(select).len();
+    if count == 0 {
+        return
+    }
+    let index = match selected_index(select) {
+        Some(index) => (index as int + delta).max(&0).min(&(count as int - 1)) as uint,
+        None if delta < 0 => count - 1,
+        None => 0,
+    };
+    select_index(select, Some(index))
+}
@@ -2,33 +2,144 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{DOMString, null_string, str, ErrorResult};
+use dom::element::ElementSnapshot;
+use dom::event::{Key, DownKey, EndKey, EnterKey, CharacterKey, HomeKey, UpKey};
 use dom::htmlelement::HTMLElement;
+use dom::htmlinputelement::edit_text;
+use dom::node::{AbstractNode, ScriptView};
+
+use servo_util::tree::TreeNodeRef;
+use std::ascii::StrAsciiExt;
+use std::uint;
 
 pub struct HTMLTextAreaElement {
     parent: HTMLElement,
+    /// The value the user or script gave the text area. Until it's first changed, the text area
+    /// shows its text content instead, and this is `None`.
+    value: Option<~str>,
+    /// Where the caret is in the value, as a byte offset.
+    caret: uint,
+    /// Whether the text area has focus, and so shows its caret.
+    focused: bool,
 }
 
 impl HTMLTextAreaElement {
+    /// The caret, if the text area has focus.
+    pub fn caret(&self) -> Option<uint> {
+        if self.focused {
+            Some(self.caret)
+        } else {
+            None
+        }
+    }
+
+    /// Gives the text area focus, with the caret at the end of its value, or takes it away.
+    pub fn set_focused(&mut self, abstract_self: AbstractNode<ScriptView>, focused: bool) {
+        self.focused = focused;
+        self.caret = value(abstract_self).len();
+        self.changed();
+    }
+
+    /// Edits the value as the user pressing `key` asks. Enter starts a new line, and the up and
+    /// down arrows and home and end keys move within and between lines. Returns false if the key
+    /// doesn't edit text.
+    pub fn handle_key(&mut self, abstract_self: AbstractNode<ScriptView>, key: Key) -> bool {
+        let value = value(abstract_self);
+        let caret = self.caret.min(&value.len());
+        let line_start = match value.slice_to(caret).rfind('\n') {
+            Some(newline) => newline + 1,
+            None => 0,
+        };
+        let line_end = match value.slice_from(caret).find('\n') {
+            Some(newline) => caret + newline,
+            None => value.len(),
+        };
+        let edited = match key {
+            EnterKey => edit_text(value, caret, CharacterKey('\n')),
+            HomeKey => Some((value.clone(), line_start)),
+            EndKey => Some((value.clone(), line_end)),
+            UpKey | DownKey => {
+                // Keep the caret as many characters into the line it moves to, or at its end.
+                let column = value.slice(line_start, caret).char_len();
+                let (start, end) = if key == UpKey {
+                    if line_start == 0 {
+                        (0, 0)
+                    } else {
+                        let end = line_start - 1;
+                        (value.slice_to(end).rfind('\n').map_default(0, |&newline| newline + 1),
+                         end)
+                    }
+                } else if line_end == value.len() {
+                    (value.len(), value.len())
+                } else {
+                    let start = line_end + 1;
+                    (start,
+                     value.slice_from(start).find('\n').map_default(value.len(),
+                                                                     |&newline| start + newline))
+                };
+                let mut caret = start;
+                for _ in range(0, column) {
+                    if caret >= end {
+                        break
+                    }
+                    caret = value.char_range_at(caret).next;
+                }
+                Some((value.clone(), caret))
+            }
+            _ => edit_text(value, caret, key),
+        };
+        match edited {
+            Some((value, caret)) => {
+                self.value = Some(value);
+                self.caret = caret;
+                self.changed();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks the text area for snapshotting, and tells the document it needs laying out again.
+    fn changed(&self) {
+        for node in self.parent.parent.parent.abstract.iter() {
+            node.dirty_snapshots(false);
+        }
+        for doc in self.parent.parent.parent.owner_doc.iter() {
+            doc.with_base(|doc| doc.content_changed());
+        }
+    }
+
+    fn set_bool_attr(&mut self, name: &str, value: bool) {
+        if value {
+            self.parent.parent.set_attr(&str(name.to_owned()), &str(~""));
+        } else {
+            self.parent.parent.remove_attr(name);
+        }
+    }
+
     pub fn Autofocus(&self) -> bool {
-        false
+        self.parent.parent.get_attr("autofocus").is_some()
     }
 
-    pub fn SetAutofocus(&mut self, _autofocus: bool, _rv: &mut ErrorResult) {
+    pub fn SetAutofocus(&mut self, autofocus: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("autofocus", autofocus)
     }
 
     pub fn Cols(&self) -> u32 {
-        0
+        cols(self) as u32
     }
 
-    pub fn SetCols(&self, _cols: u32, _rv: &mut ErrorResult) {
+    pub fn SetCols(&mut self, cols: u32, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"cols"), &str(cols.to_str()));
     }
 
     pub fn Disabled(&self) -> bool {
-        false
+        self.parent.parent.get_attr("disabled").is_some()
     }
 
-    pub fn SetDisabled(&mut self, _disabled: bool, _rv: &mut ErrorResult) {
+    pub fn SetDisabled(&mut self, disabled: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("disabled", disabled)
     }
 
     pub fn MaxLength(&self) -> i32 {
@@ -39,10 +150,11 @@ impl HTMLTextAreaElement {
     }
 
     pub fn Name(&self) -> DOMString {
-        null_string
+        str(self.parent.parent.get_attr("name").unwrap_or_default("").to_owned())
     }
 
-    pub fn SetName(&mut self, _name: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetName(&mut self, name: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"name"), name);
     }
 
     pub fn Placeholder(&self) -> DOMString {
@@ -53,56 +165,71 @@ impl HTMLTextAreaElement {
     }
 
     pub fn ReadOnly(&self) -> bool {
-        false
+        self.parent.parent.get_attr("readonly").is_some()
     }
 
-    pub fn SetReadOnly(&mut self, _read_only: bool, _rv: &mut ErrorResult) {
+    pub fn SetReadOnly(&mut self, read_only: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("readonly", read_only)
     }
 
     pub fn Required(&self) -> bool {
-        false
+        self.parent.parent.get_attr("required").is_some()
     }
 
-    pub fn SetRequired(&mut self, _required: bool, _rv: &mut ErrorResult) {
+    pub fn SetRequired(&mut self, required: bool, _rv: &mut ErrorResult) {
+        self.set_bool_attr("required", required)
     }
 
     pub fn Rows(&self) -> u32 {
-        0
+        rows(self) as u32
     }
 
-    pub fn SetRows(&self, _rows: u32, _rv: &mut ErrorResult) {
+    pub fn SetRows(&mut self, rows: u32, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"rows"), &str(rows.to_str()));
     }
 
     pub fn Wrap(&self) -> DOMString {
-        null_string
+        str(self.parent.parent.get_attr("wrap").unwrap_or_default("").to_owned())
     }
 
-    pub fn SetWrap(&mut self, _wrap: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetWrap(&mut self, wrap: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"wrap"), wrap);
     }
 
     pub fn Type(&self) -> DOMString {
-        null_string
+        str(~"textarea")
     }
 
     pub fn SetType(&mut self, _type: &DOMString) {
     }
 
-    pub fn DefaultValue(&self) -> DOMString {
-        null_string
+    pub fn DefaultValue(&self, abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        str(default_value(abstract_self))
     }
 
-    pub fn SetDefaultValue(&mut self, _default_value: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetDefaultValue(&mut self,
+                           abstract_self: AbstractNode<ScriptView>,
+                           default_value: &DOMString,
+                           rv: &mut ErrorResult) {
+        do abstract_self.with_mut_base |node| {
+            node.SetTextContent(abstract_self, default_value, rv)
+        }
     }
 
-    pub fn Value(&self) -> DOMString {
-        null_string
+    pub fn Value(&self, abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        str(value(abstract_self))
     }
 
-    pub fn SetValue(&mut self, _value: &DOMString) {
+    /// Setting the value moves the caret to its end.
+    pub fn SetValue(&mut self, _abstract_self: AbstractNode<ScriptView>, value: &DOMString) {
+        let value = value.to_str().replace("\r\n", "\n").replace("\r", "\n");
+        self.caret = value.len();
+        self.value = Some(value);
+        self.changed();
     }
 
-    pub fn TextLength(&self) -> u32 {
-        0
+    pub fn TextLength(&self, abstract_self: AbstractNode<ScriptView>) -> u32 {
+        value(abstract_self).char_len() as u32
     }
 
     pub fn SetTextLength(&self, _text_length: u32, _rv: &mut ErrorResult) {
@@ -153,3 +280,52 @@ impl HTMLTextAreaElement {
     pub fn SetRangeText(&self, _replacement: &DOMString) {
     }
 }
+
+/// The text area's text content, which is its value until the user or script changes it.
+pub fn default_value<View>(textarea: AbstractNode<View>) -> ~str {
+    let mut data = ~"";
+    for child in textarea.children() {
+        if child.is_text() {
+            do child.with_imm_text |text| {
+                data.push_str(text.parent.data.to_str());
+            }
+        }
+    }
+    data
+}
+
+/// The text the text area shows, with its lines separated by `\n`.
+pub fn value<View>(textarea: AbstractNode<View>) -> ~str {
+    let value = textarea.with_imm_textarea_element(|textarea| textarea.value.clone());
+    match value {
+        Some(value) => value,
+        None => default_value(textarea),
+    }
+}
+
+/// How many characters wide a text area is, from the `cols` attribute in its snapshot.
+pub fn cols(textarea: &ElementSnapshot) -> uint {
+    let cols = textarea.get_attr("cols").chain(|cols| uint::from_str(cols));
+    match cols {
+        Some(cols) if cols > 0 => cols,
+        _ => 20,
+    }
+}
+
+/// How many lines tall a text area is, from the `rows` attribute in its snapshot.
+pub fn rows(textarea: &ElementSnapshot) -> uint {
+    let rows = textarea.get_attr("rows").chain(|rows| uint::from_str(rows));
+    match rows {
+        Some(rows) if rows > 0 => rows,
+        _ => 2,
+    }
+}
+
+/// Whether a text area wraps lines too long for it, from the `wrap` attribute in its snapshot:
+/// `soft` and `hard` do, and `off` doesn't.
+pub fn wraps(textarea: &ElementSnapshot) -> bool {
+    match textarea.get_attr("wrap") {
+        Some(wrap) => "off" != wrap.to_ascii_lower(),
+        None => true,
+    }
+}
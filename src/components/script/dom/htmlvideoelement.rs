@@ -0,0 +1,51 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::utils::str;
+use dom::htmlmediaelement::HTMLMediaElement;
+
+use geom::size::Size2D;
+
+use std::u32;
+
+pub struct HTMLVideoElement {
+    parent: HTMLMediaElement,
+    /// The size of the video in pixels, once the media task has said.
+    video_size: Size2D<uint>,
+}
+
+impl HTMLVideoElement {
+    pub fn Width(&self) -> u32 {
+        self.dimension("width")
+    }
+
+    pub fn SetWidth(&mut self, width: u32) {
+        self.parent.parent.parent.set_attr(&str(~"width"), &str(width.to_str()));
+    }
+
+    pub fn Height(&self) -> u32 {
+        self.dimension("height")
+    }
+
+    pub fn SetHeight(&mut self, height: u32) {
+        self.parent.parent.parent.set_attr(&str(~"height"), &str(height.to_str()));
+    }
+
+    pub fn VideoWidth(&self) -> u32 {
+        self.video_size.width as u32
+    }
+
+    pub fn VideoHeight(&self) -> u32 {
+        self.video_size.height as u32
+    }
+}
+
+impl HTMLVideoElement {
+    fn dimension(&self, name: &str) -> u32 {
+        match self.parent.parent.parent.get_attr(name) {
+            Some(value) => u32::from_str(value.trim()).unwrap_or_default(0),
+            None => 0,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `ImageData`, the pixels `getImageData()` reads out of a canvas and
+//! `putImageData()` writes back.
+//!
+//! FIXME: `data` should be a `Uint8ClampedArray`, but the bindings don't have typed arrays, so
+//! it's a plain array of numbers, clamped when it's read back.
+
+use dom::bindings::codegen::ImageDataBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::window::Window;
+
+use js::JSVAL_VOID;
+use js::glue::{RUST_JSVAL_IS_DOUBLE, RUST_JSVAL_IS_INT, RUST_JSVAL_TO_DOUBLE, RUST_JSVAL_TO_INT};
+use js::glue::{RUST_JSVAL_TO_OBJECT, RUST_OBJECT_TO_JSVAL, RUST_UINT_TO_JSVAL};
+use js::jsapi::{JSObject, JSContext, JSVal, JS_GetElement, JS_NewArrayObject};
+
+use std::cast;
+use std::libc::c_int;
+use std::vec;
+
+pub struct ImageData {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    width: u32,
+    height: u32,
+    /// The array of the pixels' red, green, blue and alpha values, row by row.
+    data: JSVal,
+}
+
+impl ImageData {
+    /// Makes an `ImageData` holding `pixels`, given as straight RGBA rows.
+    pub fn new(window: @mut Window, width: u32, height: u32, pixels: &[u8]) -> @mut ImageData {
+        assert!(pixels.len() == width as uint * height as uint * 4);
+
+        // TODO(tkuehn): This just handles the top-level page. Need to handle subframes.
+        let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = window.get_wrappercache().get_wrapper();
+        let data = unsafe {
+            let values = pixels.map(|&value| RUST_UINT_TO_JSVAL(value as u32));
+            let array = do values.as_imm_buf |values, length| {
+                JS_NewArrayObject(cx, length as c_int, values)
+            };
+            RUST_OBJECT_TO_JSVAL(array)
+        };
+        let image_data = @mut ImageData {
+            wrapper: WrapperCache::new(),
+            window: window,
+            width: width,
+            height: height,
+            data: data,
+        };
+        image_data.wrap_object_shared(cx, scope);
+        image_data
+    }
+
+    pub fn Width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn Height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn Data(&self, _cx: *JSContext) -> JSVal {
+        self.data
+    }
+}
+
+impl ImageData {
+    /// Reads the pixels back out of `data`, which script may have changed, as straight RGBA rows.
+    /// Values are clamped and rounded as a `Uint8ClampedArray` would store them, and anything
+    /// that isn't a number counts as 0.
+    pub fn pixels(&self) -> ~[u8] {
+        let cx = unsafe { (*self.window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let length = self.width as uint * self.height as uint * 4;
+        let mut pixels = vec::with_capacity(length);
+        unsafe {
+            let array = RUST_JSVAL_TO_OBJECT(self.data);
+            for index in range(0, length) {
+                let mut value = JSVAL_VOID;
+                if JS_GetElement(cx, array, index as u32, &mut value) == 0 {
+                    value = JSVAL_VOID;
+                }
+                let value = if RUST_JSVAL_IS_INT(value) == 1 {
+                    RUST_JSVAL_TO_INT(value) as f64
+                } else if RUST_JSVAL_IS_DOUBLE(value) == 1 {
+                    RUST_JSVAL_TO_DOUBLE(value)
+                } else {
+                    0.0
+                };
+                pixels.push(clamp_to_u8(value));
+            }
+        }
+        pixels
+    }
+}
+
+/// Converts a number to a byte the way a `Uint8ClampedArray` does: clamped, and rounded half to
+/// even.
+fn clamp_to_u8(value: f64) -> u8 {
+    if !(value > 0.0) {
+        return 0;
+    }
+    if value >= 255.0 {
+        return 255;
+    }
+    let floor = value.floor();
+    let fraction = value - floor;
+    let rounded = if fraction > 0.5 || (fraction == 0.5 && floor % 2.0 == 1.0) {
+        floor + 1.0
+    } else {
+        floor
+    };
+    rounded as u8
+}
+
+impl CacheableWrapper for ImageData {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        ImageDataBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for ImageData {
+    fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        Some(self.window as @mut CacheableWrapper)
+    }
+}
+
+impl DerivedWrapper for ImageData {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe {
+                *vp = RUST_OBJECT_TO_JSVAL(obj)
+            };
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,224 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `Location`, the URL of the window's document. Changing any part of it loads
+//! the URL it makes in the window.
+
+use dom::bindings::codegen::LocationBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, str};
+use dom::window::Window;
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext, JSVal};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use servo_msg::constellation_msg::{LoadUrlMsg, ReplaceUrlMsg};
+use servo_net::resource_task::LoadData;
+use servo_util::url::{is_fragment_navigation, make_url};
+use extra::future::from_value;
+use extra::url::{Url, query_from_str, query_to_str};
+
+use std::cast;
+
+/// The `window.location` and `document.location` object.
+pub struct Location {
+    wrapper: WrapperCache,
+    window: @mut Window,
+}
+
+impl Location {
+    pub fn new(window: @mut Window, cx: *JSContext, scope: *JSObject) -> @mut Location {
+        let location = @mut Location {
+            wrapper: WrapperCache::new(),
+            window: window,
+        };
+        location.init_wrapper(cx, scope);
+        location
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn Href(&self) -> DOMString {
+        str(self.url().to_str())
+    }
+
+    pub fn SetHref(&self, href: &DOMString) {
+        self.Assign(href)
+    }
+
+    pub fn Assign(&self, url: &DOMString) {
+        let url = make_url(url.to_str(), Some(self.url()));
+        self.navigate(url, false)
+    }
+
+    pub fn Replace(&self, url: &DOMString) {
+        let url = make_url(url.to_str(), Some(self.url()));
+        self.navigate(url, true)
+    }
+
+    pub fn Reload(&self) {
+        self.navigate(self.url(), true)
+    }
+
+    pub fn Protocol(&self) -> DOMString {
+        str(fmt!("%s:", self.url().scheme))
+    }
+
+    pub fn SetProtocol(&self, protocol: &DOMString) {
+        let mut url = self.url();
+        url.scheme = protocol.to_str().trim_right_chars(&':').to_owned();
+        self.navigate(url, false)
+    }
+
+    pub fn Host(&self) -> DOMString {
+        let url = self.url();
+        match url.port {
+            Some(ref port) => str(fmt!("%s:%s", url.host, *port)),
+            None => str(url.host.clone()),
+        }
+    }
+
+    pub fn SetHost(&self, host: &DOMString) {
+        let mut url = self.url();
+        let host = host.to_str();
+        match host.find(':') {
+            Some(index) => {
+                url.host = host.slice_to(index).to_owned();
+                url.port = Some(host.slice_from(index + 1).to_owned());
+            }
+            None => url.host = host,
+        }
+        self.navigate(url, false)
+    }
+
+    pub fn Hostname(&self) -> DOMString {
+        str(self.url().host)
+    }
+
+    pub fn SetHostname(&self, hostname: &DOMString) {
+        let mut url = self.url();
+        url.host = hostname.to_str();
+        self.navigate(url, false)
+    }
+
+    pub fn Port(&self) -> DOMString {
+        match self.url().port {
+            Some(port) => str(port),
+            None => str(~""),
+        }
+    }
+
+    pub fn SetPort(&self, port: &DOMString) {
+        let mut url = self.url();
+        let port = port.to_str();
+        url.port = if port.is_empty() { None } else { Some(port) };
+        self.navigate(url, false)
+    }
+
+    pub fn Pathname(&self) -> DOMString {
+        str(self.url().path)
+    }
+
+    pub fn SetPathname(&self, pathname: &DOMString) {
+        let mut url = self.url();
+        let pathname = pathname.to_str();
+        url.path = if pathname.starts_with("/") { pathname } else { fmt!("/%s", pathname) };
+        self.navigate(url, false)
+    }
+
+    pub fn Search(&self) -> DOMString {
+        let url = self.url();
+        if url.query.is_empty() {
+            str(~"")
+        } else {
+            str(fmt!("?%s", query_to_str(&url.query)))
+        }
+    }
+
+    pub fn SetSearch(&self, search: &DOMString) {
+        let mut url = self.url();
+        url.query = query_from_str(search.to_str().trim_left_chars(&'?'));
+        self.navigate(url, false)
+    }
+
+    pub fn Hash(&self) -> DOMString {
+        match self.url().fragment {
+            Some(fragment) => str(fmt!("#%s", fragment)),
+            None => str(~""),
+        }
+    }
+
+    pub fn SetHash(&self, hash: &DOMString) {
+        let mut url = self.url();
+        url.fragment = Some(hash.to_str().trim_left_chars(&'#').to_owned());
+        self.navigate(url, false)
+    }
+}
+
+impl Location {
+    fn url(&self) -> Url {
+        self.window.url().expect("Location: the window has no document loaded")
+    }
+
+    /// Asks the constellation to load `url` in the window's pipeline. With `replace`, the page
+    /// takes the place of the current session history entry. A URL that only changes the
+    /// fragment stays on the document, which scrolls there instead.
+    fn navigate(&self, url: Url, replace: bool) {
+        debug!("Location: navigating to %s", url.to_str());
+        if !replace && is_fragment_navigation(&self.url(), &url) {
+            self.window.navigate_to_fragment(url);
+            return
+        }
+        let (id, size) = unsafe {
+            ((*self.window.page).id, (*self.window.page).window_size.get())
+        };
+        let load_data = LoadData::new(url);
+        let msg = if replace {
+            ReplaceUrlMsg(id, load_data, from_value(size))
+        } else {
+            LoadUrlMsg(id, load_data, from_value(size))
+        };
+        self.window.constellation_chan.send(msg);
+    }
+}
+
+impl CacheableWrapper for Location {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        LocationBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for Location {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for Location {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
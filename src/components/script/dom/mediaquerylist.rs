@@ -0,0 +1,133 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `MediaQueryList`, as returned by `window.matchMedia()`. Queries are evaluated
+//! the same way as the `@media` rules of style sheets, and again whenever the window is resized.
+
+use dom::bindings::codegen::MediaQueryListBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, str};
+use dom::window::Window;
+use script_task::page_from_context;
+use servo_util::media_queries::Device;
+use servo_util::media_queries;
+
+use js::JSVAL_NULL;
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal, JS_CallFunctionValue};
+
+use std::cast;
+
+pub struct MediaQueryList {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    /// The text of the query list, as it was given.
+    media: ~str,
+    queries: media_queries::MediaQueryList,
+    /// Whether the queries matched when they were last evaluated.
+    matches: bool,
+    listeners: ~[JSVal],
+}
+
+impl MediaQueryList {
+    pub fn new(window: @mut Window, media: ~str, device: &Device, cx: *JSContext,
+               scope: *JSObject) -> @mut MediaQueryList {
+        let queries = media_queries::MediaQueryList::parse(media.as_slice());
+        let list = @mut MediaQueryList {
+            wrapper: WrapperCache::new(),
+            window: window,
+            matches: queries.evaluate(device),
+            media: media,
+            queries: queries,
+            listeners: ~[],
+        };
+        list.wrap_object_shared(cx, scope);
+        list
+    }
+
+    pub fn Media(&self) -> DOMString {
+        str(self.media.clone())
+    }
+
+    pub fn Matches(&self) -> bool {
+        self.matches
+    }
+
+    // FIXME: The listener should be a `MediaQueryListListener`, but the bindings don't support
+    // callback interfaces yet.
+    pub fn AddListener(&mut self, _cx: *JSContext, listener: JSVal) {
+        if !self.listeners.contains(&listener) {
+            self.listeners.push(listener);
+        }
+    }
+
+    pub fn RemoveListener(&mut self, _cx: *JSContext, listener: JSVal) {
+        let position = self.listeners.iter().position(|&l| l == listener);
+        for &index in position.iter() {
+            self.listeners.remove(index);
+        }
+    }
+
+    /// Evaluates the queries against `device` again. If that changes whether they match, calls
+    /// the listeners with this list.
+    pub fn evaluate(@mut self, cx: *JSContext, device: &Device) {
+        let matches = self.queries.evaluate(device);
+        if matches == self.matches {
+            return
+        }
+        self.matches = matches;
+
+        // Listeners can add and remove listeners; the ones called are those there were before.
+        let listeners = self.listeners.clone();
+        let this = self.wrapper.get_wrapper();
+        let argv = [RUST_OBJECT_TO_JSVAL(this)];
+        let scope = self.window.get_wrappercache().get_wrapper();
+        for &listener in listeners.iter() {
+            let rval = JSVAL_NULL;
+            unsafe {
+                do argv.as_imm_buf |argv, argc| {
+                    JS_CallFunctionValue(cx, scope, listener, argc as u32, argv, &rval);
+                }
+            }
+        }
+    }
+}
+
+impl CacheableWrapper for MediaQueryList {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        MediaQueryListBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for MediaQueryList {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for MediaQueryList {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
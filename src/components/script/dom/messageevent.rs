@@ -0,0 +1,88 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::MessageEventBinding;
+use dom::bindings::utils::{DOMString, str};
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::event::Event;
+use dom::windowproxy::WindowProxy;
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+/// The event fired at a window when another window posts a message to it.
+pub struct MessageEvent {
+    parent: Event,
+    /// The copy of the message.
+    data: JSVal,
+    /// The serialized origin of the document that posted the message.
+    origin: ~str,
+    /// The window that posted the message.
+    source: Option<@mut WindowProxy>,
+}
+
+impl MessageEvent {
+    pub fn new(type_: &DOMString, data: JSVal, origin: ~str, source: Option<@mut WindowProxy>)
+               -> MessageEvent {
+        MessageEvent {
+            parent: Event::new(type_),
+            data: data,
+            origin: origin,
+            source: source,
+        }
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn Data(&self, _cx: *JSContext) -> JSVal {
+        self.data
+    }
+
+    pub fn Origin(&self) -> DOMString {
+        str(self.origin.clone())
+    }
+
+    pub fn LastEventId(&self) -> DOMString {
+        str(~"")
+    }
+
+    pub fn GetSource(&self) -> Option<@mut WindowProxy> {
+        self.source
+    }
+}
+
+impl CacheableWrapper for MessageEvent {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        return self.parent.get_wrappercache()
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        MessageEventBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for MessageEvent {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        self.parent.GetParentObject(cx)
+    }
+}
+
+impl DerivedWrapper for MessageEvent {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,251 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `MutationObserver`.
+//!
+//! Every change that script makes to the tree goes through `AbstractNode::mutated`, which marks
+//! the document as needing layout and hands a description of the change to the window's
+//! observers. The records they queue are delivered at the end of the current task, and after
+//! each script the parser runs.
+
+use dom::bindings::codegen::MutationObserverBinding;
+use dom::bindings::codegen::MutationObserverBinding::MutationObserverInit;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{ErrorResult, Syntax};
+use dom::mutationrecord::MutationRecord;
+use dom::node::{AbstractNode, ScriptView};
+use dom::window::Window;
+use script_task::page_from_context;
+
+use js::JSVAL_NULL;
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal, JS_CallFunctionValue, JS_NewArrayObject};
+
+use std::cast;
+use std::libc::c_int;
+use std::util::replace;
+
+/// A change to a node, described for mutation observers.
+pub enum Mutation {
+    /// Nodes were added to and/or removed from the node's children. Holds the added nodes, the
+    /// removed nodes, and the siblings either side of where they were.
+    ChildListMutation(~[AbstractNode<ScriptView>],
+                      ~[AbstractNode<ScriptView>],
+                      Option<AbstractNode<ScriptView>>,
+                      Option<AbstractNode<ScriptView>>),
+    /// An attribute of the node was set. Holds the attribute's name and old value.
+    AttributeMutation(~str, Option<~str>),
+    /// The text of a text node or comment was changed. Holds the old text.
+    CharacterDataMutation(~str),
+}
+
+/// The kinds of changes an observer asked to hear about for a node.
+struct ObserveOptions {
+    child_list: bool,
+    attributes: bool,
+    character_data: bool,
+    subtree: bool,
+    attribute_old_value: bool,
+    character_data_old_value: bool,
+}
+
+/// A node an observer is observing.
+struct Registration {
+    node: AbstractNode<ScriptView>,
+    options: ObserveOptions,
+}
+
+impl Registration {
+    /// Returns whether this registration asks for `mutation` to `target` to be reported, and
+    /// whether with the old value.
+    fn wants(&self, target: AbstractNode<ScriptView>, mutation: &Mutation) -> (bool, bool) {
+        let observed = self.node == target ||
+            (self.options.subtree && self.node.is_inclusive_ancestor_of(target));
+        if !observed {
+            return (false, false)
+        }
+        match *mutation {
+            ChildListMutation(*) => (self.options.child_list, false),
+            AttributeMutation(*) => (self.options.attributes, self.options.attribute_old_value),
+            CharacterDataMutation(*) => {
+                (self.options.character_data, self.options.character_data_old_value)
+            }
+        }
+    }
+}
+
+pub struct MutationObserver {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    callback: JSVal,
+    registrations: ~[Registration],
+    records: ~[@mut MutationRecord],
+}
+
+impl MutationObserver {
+    // FIXME: The callback should be a `MutationCallback`, but the bindings don't support
+    // callback types yet.
+    pub fn Constructor(cx: *JSContext,
+                       owner: @mut Window,
+                       callback: JSVal,
+                       _rv: &mut ErrorResult)
+                       -> @mut MutationObserver {
+        let observer = @mut MutationObserver {
+            wrapper: WrapperCache::new(),
+            window: owner,
+            callback: callback,
+            registrations: ~[],
+            records: ~[],
+        };
+        observer.wrap_object_shared(cx, owner.get_wrappercache().get_wrapper());
+        owner.mutation_observers.push(observer);
+        observer
+    }
+
+    pub fn Observe(&mut self,
+                   target: AbstractNode<ScriptView>,
+                   options: &MutationObserverInit,
+                   rv: &mut ErrorResult) {
+        let options = ObserveOptions {
+            child_list: options.childList,
+            attributes: options.attributes || options.attributeOldValue,
+            character_data: options.characterData || options.characterDataOldValue,
+            subtree: options.subtree,
+            attribute_old_value: options.attributeOldValue,
+            character_data_old_value: options.characterDataOldValue,
+        };
+        if !options.child_list && !options.attributes && !options.character_data {
+            // FIXME: This should be a TypeError, which the bindings can't throw yet.
+            *rv = Err(Syntax);
+            return
+        }
+
+        // Observing the same node again replaces the options it was observed with.
+        for registration in self.registrations.mut_iter() {
+            if registration.node == target {
+                registration.options = options;
+                return
+            }
+        }
+        self.registrations.push(Registration {
+            node: target,
+            options: options,
+        });
+    }
+
+    pub fn Disconnect(&mut self) {
+        self.registrations = ~[];
+        self.records = ~[];
+    }
+
+    // FIXME: This should return a `sequence<MutationRecord>`, but the bindings can't return
+    // sequences yet, so it builds the array itself.
+    pub fn TakeRecords(&mut self, cx: *JSContext) -> JSVal {
+        self.take_records_as_array(cx)
+    }
+
+    /// Queues a record of `mutation` to `target` if it's a change this observer asked about.
+    pub fn queue_record(&mut self, target: AbstractNode<ScriptView>, mutation: &Mutation) {
+        let mut interested = false;
+        let mut wants_old_value = false;
+        for registration in self.registrations.iter() {
+            let (wants, wants_old) = registration.wants(target, mutation);
+            interested = interested || wants;
+            wants_old_value = wants_old_value || (wants && wants_old);
+        }
+        if !interested {
+            return
+        }
+
+        let cx = unsafe { (*self.window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = self.window.get_wrappercache().get_wrapper();
+        let record = match *mutation {
+            ChildListMutation(ref added, ref removed, previous_sibling, next_sibling) => {
+                MutationRecord::new(~"childList", target, added.clone(), removed.clone(),
+                                    previous_sibling, next_sibling, None, None, cx, scope)
+            }
+            AttributeMutation(ref name, ref old_value) => {
+                let old_value = if wants_old_value { old_value.clone() } else { None };
+                MutationRecord::new(~"attributes", target, ~[], ~[], None, None,
+                                    Some(name.clone()), old_value, cx, scope)
+            }
+            CharacterDataMutation(ref old_value) => {
+                let old_value = if wants_old_value { Some(old_value.clone()) } else { None };
+                MutationRecord::new(~"characterData", target, ~[], ~[], None, None, None,
+                                    old_value, cx, scope)
+            }
+        };
+        self.records.push(record);
+    }
+
+    pub fn has_records(&self) -> bool {
+        !self.records.is_empty()
+    }
+
+    /// Calls the callback with the queued records, and this observer as the second argument.
+    pub fn notify(&mut self, cx: *JSContext) {
+        let records = self.take_records_as_array(cx);
+        let this = self.wrapper.get_wrapper();
+        let argv = [records, RUST_OBJECT_TO_JSVAL(this)];
+        let rval = JSVAL_NULL;
+        unsafe {
+            do argv.as_imm_buf |argv, argc| {
+                JS_CallFunctionValue(cx, this, self.callback, argc as u32, argv, &rval);
+            }
+        }
+    }
+
+    /// Empties the record queue into a new JS array.
+    fn take_records_as_array(&mut self, cx: *JSContext) -> JSVal {
+        let scope = self.window.get_wrappercache().get_wrapper();
+        let records = replace(&mut self.records, ~[]);
+        let values = do records.map |&record| {
+            RUST_OBJECT_TO_JSVAL(record.wrap_object_shared(cx, scope))
+        };
+        unsafe {
+            let array = do values.as_imm_buf |values, length| {
+                JS_NewArrayObject(cx, length as c_int, values)
+            };
+            RUST_OBJECT_TO_JSVAL(array)
+        }
+    }
+}
+
+impl CacheableWrapper for MutationObserver {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        MutationObserverBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for MutationObserver {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for MutationObserver {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
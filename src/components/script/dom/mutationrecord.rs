@@ -0,0 +1,132 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `MutationRecord`.
+
+use dom::bindings::codegen::MutationRecordBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, null_string, str};
+use dom::htmlcollection::HTMLCollection;
+use dom::node::{AbstractNode, ScriptView};
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext, JSVal};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+
+use std::cast;
+
+/// A change to the tree, as reported to a `MutationObserver`.
+pub struct MutationRecord {
+    wrapper: WrapperCache,
+    type_: ~str,
+    target: AbstractNode<ScriptView>,
+    added_nodes: @mut HTMLCollection,
+    removed_nodes: @mut HTMLCollection,
+    previous_sibling: Option<AbstractNode<ScriptView>>,
+    next_sibling: Option<AbstractNode<ScriptView>>,
+    attribute_name: Option<~str>,
+    old_value: Option<~str>,
+}
+
+impl MutationRecord {
+    pub fn new(type_: ~str,
+               target: AbstractNode<ScriptView>,
+               added_nodes: ~[AbstractNode<ScriptView>],
+               removed_nodes: ~[AbstractNode<ScriptView>],
+               previous_sibling: Option<AbstractNode<ScriptView>>,
+               next_sibling: Option<AbstractNode<ScriptView>>,
+               attribute_name: Option<~str>,
+               old_value: Option<~str>,
+               cx: *JSContext,
+               scope: *JSObject)
+               -> @mut MutationRecord {
+        @mut MutationRecord {
+            wrapper: WrapperCache::new(),
+            type_: type_,
+            target: target,
+            added_nodes: HTMLCollection::new(added_nodes, cx, scope),
+            removed_nodes: HTMLCollection::new(removed_nodes, cx, scope),
+            previous_sibling: previous_sibling,
+            next_sibling: next_sibling,
+            attribute_name: attribute_name,
+            old_value: old_value,
+        }
+    }
+
+    pub fn Type(&self) -> DOMString {
+        str(self.type_.clone())
+    }
+
+    pub fn Target(&self) -> AbstractNode<ScriptView> {
+        self.target
+    }
+
+    pub fn AddedNodes(&self) -> @mut HTMLCollection {
+        self.added_nodes
+    }
+
+    pub fn RemovedNodes(&self) -> @mut HTMLCollection {
+        self.removed_nodes
+    }
+
+    pub fn GetPreviousSibling(&self) -> Option<AbstractNode<ScriptView>> {
+        self.previous_sibling
+    }
+
+    pub fn GetNextSibling(&self) -> Option<AbstractNode<ScriptView>> {
+        self.next_sibling
+    }
+
+    pub fn GetAttributeName(&self) -> DOMString {
+        match self.attribute_name {
+            Some(ref name) => str(name.clone()),
+            None => null_string,
+        }
+    }
+
+    pub fn GetOldValue(&self) -> DOMString {
+        match self.old_value {
+            Some(ref value) => str(value.clone()),
+            None => null_string,
+        }
+    }
+}
+
+impl CacheableWrapper for MutationRecord {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        MutationRecordBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for MutationRecord {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for MutationRecord {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
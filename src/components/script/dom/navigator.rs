@@ -0,0 +1,117 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, str};
+use dom::bindings::codegen::NavigatorBinding;
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext, JSVal};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+
+use std::cast;
+
+/// The `window.navigator` object. Most of what it says is fixed by the spec, for the sake of
+/// pages that sniff for particular browsers.
+pub struct Navigator {
+    wrapper: WrapperCache,
+    user_agent: ~str,
+}
+
+impl Navigator {
+    pub fn new(user_agent: ~str, cx: *JSContext, scope: *JSObject) -> @mut Navigator {
+        let navigator = @mut Navigator {
+            wrapper: WrapperCache::new(),
+            user_agent: user_agent,
+        };
+        navigator.init_wrapper(cx, scope);
+        navigator
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn AppCodeName(&self) -> DOMString {
+        str(~"Mozilla")
+    }
+
+    pub fn AppName(&self) -> DOMString {
+        str(~"Netscape")
+    }
+
+    pub fn AppVersion(&self) -> DOMString {
+        str(~"4.0")
+    }
+
+    pub fn Platform(&self) -> DOMString {
+        str(platform().to_owned())
+    }
+
+    pub fn Product(&self) -> DOMString {
+        str(~"Gecko")
+    }
+
+    pub fn UserAgent(&self) -> DOMString {
+        str(self.user_agent.clone())
+    }
+
+    pub fn Language(&self) -> DOMString {
+        // FIXME: This should be the user's preferred language.
+        str(~"en-US")
+    }
+}
+
+#[cfg(target_os="macos")]
+fn platform() -> &'static str {
+    "MacIntel"
+}
+
+#[cfg(target_os="linux")]
+fn platform() -> &'static str {
+    "Linux"
+}
+
+#[cfg(target_os="android")]
+fn platform() -> &'static str {
+    "Linux armv7l"
+}
+
+impl CacheableWrapper for Navigator {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        NavigatorBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for Navigator {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for Navigator {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
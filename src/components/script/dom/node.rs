@@ -6,15 +6,33 @@
 
 use dom::bindings::codegen::TextBinding;
 use dom::bindings::node;
-use dom::bindings::utils::{WrapperCache, DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{WrapperCache, DOMString, null_string, str, ErrorResult};
+use dom::bindings::utils::{HierarchyRequest, IndexSize, InvalidState, NotFound};
 use dom::bindings::utils::{BindingObject, CacheableWrapper, rust_box};
 use dom::bindings;
 use dom::characterdata::CharacterData;
 use dom::document::AbstractDocument;
+use dom::documentfragment::DocumentFragment;
 use dom::element::{Element, ElementTypeId, HTMLImageElementTypeId, HTMLIframeElementTypeId};
-use dom::element::{HTMLStyleElementTypeId};
+use dom::element::{HTMLAnchorElementTypeId, HTMLCanvasElementTypeId};
+use dom::element::{HTMLFormElementTypeId, HTMLInputElementTypeId, HTMLOptionElementTypeId};
+use dom::element::{HTMLSelectElementTypeId, HTMLStyleElementTypeId, HTMLTextAreaElementTypeId};
+use dom::element::{HTMLAudioElementTypeId, HTMLSourceElementTypeId, HTMLVideoElementTypeId};
+use dom::element::{HTMLLinkElementTypeId, SVGSVGElementTypeId};
+use dom::htmlanchorelement::HTMLAnchorElement;
+use dom::htmlcanvaselement::HTMLCanvasElement;
+use dom::htmlformelement::HTMLFormElement;
 use dom::htmlimageelement::HTMLImageElement;
+use dom::htmlinputelement::HTMLInputElement;
+use dom::htmlmediaelement::HTMLMediaElement;
 use dom::htmliframeelement::HTMLIFrameElement;
+use dom::htmllinkelement::HTMLLinkElement;
+use dom::htmloptionelement::HTMLOptionElement;
+use dom::htmlselectelement::HTMLSelectElement;
+use dom::htmltextareaelement::HTMLTextAreaElement;
+use dom::htmlvideoelement::HTMLVideoElement;
+use dom::mutationobserver::{ChildListMutation, Mutation};
+use html::hubbub_html_parser::clone_node;
 use dom::window::Window;
 
 use std::cast;
@@ -87,7 +105,15 @@ pub struct Node<View> {
     owner_doc: Option<AbstractDocument>,
 
     /// Layout information. Only the layout task may touch this data.
-    priv layout_data: Option<@mut ()>
+    priv layout_data: Option<*()>,
+
+    /// Whether layout has to be sent new snapshots of all the elements in this subtree, because
+    /// it was inserted since the last reflow.
+    priv snapshot_subtree: bool,
+
+    /// Whether this node or one under it has to be snapshotted for layout again. Script only
+    /// looks for elements to snapshot under nodes with this set.
+    priv snapshot_dirty: bool,
 }
 
 /// The different types of nodes.
@@ -95,6 +121,7 @@ pub struct Node<View> {
 pub enum NodeTypeId {
     DoctypeNodeTypeId,
     CommentNodeTypeId,
+    DocumentFragmentNodeTypeId,
     ElementNodeTypeId(ElementTypeId),
     TextNodeTypeId,
 }
@@ -161,8 +188,35 @@ impl Text {
         unsafe { Node::as_abstract_node(cx, @Text::new(text.to_str())) }
     }
 
-    pub fn SplitText(&self, _offset: u32, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
-        fail!("unimplemented")
+    /// Moves the text after `offset`, a byte offset, into a new text node, which goes after this
+    /// one if this one has a parent.
+    pub fn SplitText(&mut self, offset: u32, rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
+        let abstract_self = self.parent.parent.abstract.unwrap();
+        let data = self.parent.data.to_str();
+        let offset = offset as uint;
+        if offset > data.len() || !data.is_char_boundary(offset) {
+            *rv = Err(IndexSize);
+            return abstract_self
+        }
+        let cx = match self.parent.parent.script_cx() {
+            Some(cx) => cx,
+            None => {
+                *rv = Err(InvalidState);
+                return abstract_self
+            }
+        };
+
+        let new_text = unsafe {
+            Node::as_abstract_node(cx, @Text::new(data.slice_from(offset).to_owned()))
+        };
+        do new_text.with_mut_base |new_text| {
+            new_text.owner_doc = self.parent.parent.owner_doc;
+        }
+        self.parent.set_data(str(data.slice_to(offset).to_owned()));
+        for parent in abstract_self.parent_node().iter() {
+            parent.pre_insert(new_text, abstract_self.next_sibling());
+        }
+        new_text
     }
 
     pub fn GetWholeText(&self, _rv: &mut ErrorResult) -> DOMString {
@@ -217,6 +271,10 @@ impl<View> TreeNodeRef<Node<View>> for AbstractNode<View> {
     fn with_mut_base<R>(&self, callback: &fn(&mut Node<View>) -> R) -> R {
         self.transmute_mut(callback)
     }
+
+    fn child_inserted(&self, new_child: &AbstractNode<View>) {
+        new_child.dirty_snapshots(true)
+    }
 }
 
 impl<View> TreeNode<AbstractNode<View>> for Node<View> { }
@@ -230,6 +288,10 @@ impl<'self, View> AbstractNode<View> {
                 let node: @mut Text = cast::transmute(self.obj);
                 node as @mut CacheableWrapper
             }
+            DocumentFragmentNodeTypeId => {
+                let node: @mut DocumentFragment = cast::transmute(self.obj);
+                node as @mut CacheableWrapper
+            }
             _ => {
                 fail!("unsupported node type")
             }
@@ -249,7 +311,8 @@ impl<'self, View> AbstractNode<View> {
     /// allowed to call this. This is wildly unsafe and is therefore marked as such.
     pub unsafe fn unsafe_layout_data<T>(self) -> @mut T {
         do self.with_base |base| {
-            transmute(base.layout_data.unwrap())
+            let data: &@mut T = transmute(&base.layout_data.unwrap());
+            *data
         }
     }
     /// Returns true if this node has layout data and false otherwise.
@@ -258,15 +321,45 @@ impl<'self, View> AbstractNode<View> {
             base.layout_data.is_some()
         }
     }
+
+    /// Notes that layout has to be sent a new snapshot of this node, if it's an element, and of
+    /// the elements under it if `subtree` is set. Marks its ancestors, so that it's found.
+    pub fn dirty_snapshots(self, subtree: bool) {
+        do self.with_mut_base |base| {
+            base.snapshot_dirty = true;
+            base.snapshot_subtree = base.snapshot_subtree || subtree;
+        }
+        let mut current = self.parent_node();
+        while current.is_some() {
+            let ancestor = current.unwrap();
+            // The ancestors of a marked node are marked already.
+            if ancestor.with_base(|base| base.snapshot_dirty) {
+                break
+            }
+            ancestor.with_mut_base(|base| base.snapshot_dirty = true);
+            current = ancestor.parent_node();
+        }
+    }
+
+    /// Returns whether this subtree has nodes to snapshot, and whether all of its elements are
+    /// to be snapshotted, and clears both.
+    pub fn take_snapshot_dirtiness(self) -> (bool, bool) {
+        do self.with_mut_base |base| {
+            let dirtiness = (base.snapshot_dirty, base.snapshot_subtree);
+            base.snapshot_dirty = false;
+            base.snapshot_subtree = false;
+            dirtiness
+        }
+    }
+
     /// Sets the layout data, unsafely casting the type as layout wishes. Only layout is allowed
     /// to call this. This is wildly unsafe and is therefore marked as such.
+    ///
+    /// The node doesn't hold a reference to the data: layout has to keep it alive for as long as
+    /// the node points at it.
     pub unsafe fn unsafe_set_layout_data<T>(self, data: @mut T) {
-        // Don't decrement the refcount on data, since we're giving it to the
-        // base structure.
-        cast::forget(data);
-
         do self.with_mut_base |base| {
-            base.layout_data = Some(transmute(data))
+            base.layout_data = Some(cast::transmute_copy(&data))
         }
     }
 
@@ -356,6 +449,10 @@ impl<'self, View> AbstractNode<View> {
         self.transmute_mut(f)
     }
 
+    pub fn is_document_fragment(self) -> bool {
+        self.type_id() == DocumentFragmentNodeTypeId
+    }
+
     pub fn is_element(self) -> bool {
         match self.type_id() {
             ElementNodeTypeId(*) => true,
@@ -397,6 +494,160 @@ impl<'self, View> AbstractNode<View> {
         self.transmute_mut(f)
     }
 
+    pub fn is_canvas_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLCanvasElementTypeId)
+    }
+
+    pub fn with_imm_canvas_element<R>(self, f: &fn(&HTMLCanvasElement) -> R) -> R {
+        if !self.is_canvas_element() {
+            fail!(~"node is not a canvas element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_canvas_element<R>(self, f: &fn(&mut HTMLCanvasElement) -> R) -> R {
+        if !self.is_canvas_element() {
+            fail!(~"node is not a canvas element");
+        }
+        self.transmute_mut(f)
+    }
+
+    /// Whether this is an element that plays media: a `<video>` or an `<audio>`.
+    pub fn is_media_element(self) -> bool {
+        self.is_video_element() || self.is_audio_element()
+    }
+
+    pub fn with_imm_media_element<R>(self, f: &fn(&HTMLMediaElement) -> R) -> R {
+        if !self.is_media_element() {
+            fail!(~"node is not a media element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_media_element<R>(self, f: &fn(&mut HTMLMediaElement) -> R) -> R {
+        if !self.is_media_element() {
+            fail!(~"node is not a media element");
+        }
+        self.transmute_mut(f)
+    }
+
+    pub fn is_audio_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLAudioElementTypeId)
+    }
+
+    pub fn is_video_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLVideoElementTypeId)
+    }
+
+    pub fn with_imm_video_element<R>(self, f: &fn(&HTMLVideoElement) -> R) -> R {
+        if !self.is_video_element() {
+            fail!(~"node is not a video element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_video_element<R>(self, f: &fn(&mut HTMLVideoElement) -> R) -> R {
+        if !self.is_video_element() {
+            fail!(~"node is not a video element");
+        }
+        self.transmute_mut(f)
+    }
+
+    pub fn is_source_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLSourceElementTypeId)
+    }
+
+    pub fn is_svg_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(SVGSVGElementTypeId)
+    }
+
+    pub fn is_input_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLInputElementTypeId)
+    }
+
+    pub fn with_imm_input_element<R>(self, f: &fn(&HTMLInputElement) -> R) -> R {
+        if !self.is_input_element() {
+            fail!(~"node is not an input element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_input_element<R>(self, f: &fn(&mut HTMLInputElement) -> R) -> R {
+        if !self.is_input_element() {
+            fail!(~"node is not an input element");
+        }
+        self.transmute_mut(f)
+    }
+
+    pub fn is_anchor_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLAnchorElementTypeId)
+    }
+
+    pub fn with_imm_anchor_element<R>(self, f: &fn(&HTMLAnchorElement) -> R) -> R {
+        if !self.is_anchor_element() {
+            fail!(~"node is not an anchor element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn is_form_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLFormElementTypeId)
+    }
+
+    pub fn with_imm_form_element<R>(self, f: &fn(&HTMLFormElement) -> R) -> R {
+        if !self.is_form_element() {
+            fail!(~"node is not a form element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn is_textarea_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLTextAreaElementTypeId)
+    }
+
+    pub fn with_imm_textarea_element<R>(self, f: &fn(&HTMLTextAreaElement) -> R) -> R {
+        if !self.is_textarea_element() {
+            fail!(~"node is not a textarea element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_textarea_element<R>(self, f: &fn(&mut HTMLTextAreaElement) -> R) -> R {
+        if !self.is_textarea_element() {
+            fail!(~"node is not a textarea element");
+        }
+        self.transmute_mut(f)
+    }
+
+    pub fn is_select_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLSelectElementTypeId)
+    }
+
+    pub fn with_imm_select_element<R>(self, f: &fn(&HTMLSelectElement) -> R) -> R {
+        if !self.is_select_element() {
+            fail!(~"node is not a select element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn is_option_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLOptionElementTypeId)
+    }
+
+    pub fn with_imm_option_element<R>(self, f: &fn(&HTMLOptionElement) -> R) -> R {
+        if !self.is_option_element() {
+            fail!(~"node is not an option element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_option_element<R>(self, f: &fn(&mut HTMLOptionElement) -> R) -> R {
+        if !self.is_option_element() {
+            fail!(~"node is not an option element");
+        }
+        self.transmute_mut(f)
+    }
+
     pub fn is_iframe_element(self) -> bool {
         self.type_id() == ElementNodeTypeId(HTMLIframeElementTypeId)
     }
@@ -415,6 +666,24 @@ impl<'self, View> AbstractNode<View> {
         self.transmute_mut(f)
     }
 
+    pub fn is_link_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLLinkElementTypeId)
+    }
+
+    pub fn with_imm_link_element<R>(self, f: &fn(&HTMLLinkElement) -> R) -> R {
+        if !self.is_link_element() {
+            fail!(~"node is not a link element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_link_element<R>(self, f: &fn(&mut HTMLLinkElement) -> R) -> R {
+        if !self.is_link_element() {
+            fail!(~"node is not a link element");
+        }
+        self.transmute_mut(f)
+    }
+
     pub fn is_style_element(self) -> bool {
         self.type_id() == ElementNodeTypeId(HTMLStyleElementTypeId)
     }
@@ -470,6 +739,141 @@ impl<View> Iterator<AbstractNode<View>> for AbstractNodeChildrenIterator<View> {
     }
 }
 
+impl AbstractNode<ScriptView> {
+    /// Returns whether this node is `other` or one of its ancestors.
+    pub fn is_inclusive_ancestor_of(self, other: AbstractNode<ScriptView>) -> bool {
+        let mut current = Some(other);
+        while current.is_some() {
+            if current == Some(self) {
+                return true
+            }
+            current = current.unwrap().parent_node();
+        }
+        false
+    }
+
+    /// Returns the nearest node that is an inclusive ancestor of both this node and `other`, or
+    /// `None` if they're in different trees.
+    pub fn common_ancestor(self, other: AbstractNode<ScriptView>)
+                           -> Option<AbstractNode<ScriptView>> {
+        let mut current = Some(self);
+        while current.is_some() {
+            let node = current.unwrap();
+            if node.is_inclusive_ancestor_of(other) {
+                return current
+            }
+            current = node.parent_node();
+        }
+        None
+    }
+
+    /// Returns the number of siblings before this node.
+    pub fn index(self) -> uint {
+        let mut index = 0;
+        let mut current = self.prev_sibling();
+        while current.is_some() {
+            index += 1;
+            current = current.unwrap().prev_sibling();
+        }
+        index
+    }
+
+    /// Checks that `node` may be inserted into this node's children.
+    pub fn check_pre_insert(self, node: AbstractNode<ScriptView>) -> ErrorResult {
+        match self.type_id() {
+            ElementNodeTypeId(*) | DocumentFragmentNodeTypeId => {}
+            _ => return Err(HierarchyRequest),
+        }
+        if node.type_id() == DoctypeNodeTypeId || node.is_inclusive_ancestor_of(self) {
+            return Err(HierarchyRequest)
+        }
+        Ok(())
+    }
+
+    /// Inserts `node` into this node's children before `child`, or at the end if `child` is
+    /// `None`. `node` is removed from wherever it was first. If `node` is a document fragment,
+    /// its children are moved over instead, leaving it empty.
+    pub fn pre_insert(self, node: AbstractNode<ScriptView>, child: Option<AbstractNode<ScriptView>>)
+                      -> ErrorResult {
+        match self.check_pre_insert(node) {
+            Ok(()) => {}
+            error => return error,
+        }
+        for child in child.iter() {
+            if child.parent_node() != Some(self) {
+                return Err(NotFound)
+            }
+        }
+
+        // Inserting a node before itself leaves it where it is.
+        let child = if child == Some(node) {
+            node.next_sibling()
+        } else {
+            child
+        };
+
+        let nodes = if node.is_document_fragment() {
+            let children: ~[AbstractNode<ScriptView>] = node.children().collect();
+            for &kid in children.iter() {
+                node.remove_child(kid);
+            }
+            node.mutated(ChildListMutation(~[], children.clone(), None, None));
+            children
+        } else {
+            for parent in node.parent_node().iter() {
+                parent.pre_remove(node);
+            }
+            ~[node]
+        };
+        if nodes.is_empty() {
+            return Ok(())
+        }
+
+        let previous_sibling = match child {
+            Some(child) => child.prev_sibling(),
+            None => self.last_child(),
+        };
+        let doc = self.with_base(|base| base.owner_doc);
+        for &kid in nodes.iter() {
+            match child {
+                Some(child) => self.insert_before(kid, child),
+                None => self.add_child(kid),
+            }
+            for &doc in doc.iter() {
+                kid.with_mut_base(|base| base.add_to_doc(doc));
+            }
+        }
+        self.mutated(ChildListMutation(nodes, ~[], previous_sibling, child));
+        Ok(())
+    }
+
+    /// Removes `child` from this node's children.
+    pub fn pre_remove(self, child: AbstractNode<ScriptView>) -> ErrorResult {
+        if child.parent_node() != Some(self) {
+            return Err(NotFound)
+        }
+        let (previous_sibling, next_sibling) = (child.prev_sibling(), child.next_sibling());
+        self.remove_child(child);
+        self.mutated(ChildListMutation(~[], ~[child], previous_sibling, next_sibling));
+        Ok(())
+    }
+
+    /// Notes a change to this node: marks it for snapshotting and its document as needing layout,
+    /// and queues records of the change for the mutation observers interested in it.
+    pub fn mutated(self, mutation: Mutation) {
+        self.dirty_snapshots(false);
+        let doc = match self.with_base(|base| base.owner_doc) {
+            Some(doc) => doc,
+            None => return,
+        };
+        doc.with_base(|doc| doc.content_changed());
+        for window in doc.with_base(|doc| doc.window).iter() {
+            window.queue_mutation_records(self, &mutation);
+        }
+    }
+
+}
+
 impl Node<ScriptView> {
     pub unsafe fn as_abstract_node<N>(cx: *JSContext, node: @N) -> AbstractNode<ScriptView> {
         // This surrenders memory management of the node!
@@ -480,19 +884,45 @@ impl Node<ScriptView> {
         node
     }
 
+    /// Makes this node and its descendants belong to `doc`.
     pub fn add_to_doc(&mut self, doc: AbstractDocument) {
-        self.owner_doc = Some(doc);
+        self.set_owner_doc(doc);
         let mut cur_node = self.first_child;
         while cur_node.is_some() {
             for node in cur_node.unwrap().traverse_preorder() {
                 do node.with_mut_base |node_base| {
-                    node_base.owner_doc = Some(doc);
+                    node_base.set_owner_doc(doc);
                 }
             };
             cur_node = cur_node.unwrap().next_sibling();
         }
     }
 
+    /// Makes this node belong to `doc`. A node moving between documents forgets its layout data,
+    /// which belongs to the old document's layout; the old layout task still holds the data
+    /// itself, so it's dropped with that task's other roots rather than here.
+    fn set_owner_doc(&mut self, doc: AbstractDocument) {
+        let moved = match self.owner_doc {
+            Some(old_doc) => old_doc.document != doc.document,
+            None => false,
+        };
+        if moved {
+            self.layout_data = None;
+        }
+        self.owner_doc = Some(doc);
+    }
+
+    /// Returns the JS context of the window this node's document is in, if it's in one.
+    pub fn script_cx(&self) -> Option<*JSContext> {
+        let doc = match self.owner_doc {
+            Some(doc) => doc,
+            None => return None,
+        };
+        do doc.with_base(|doc| doc.window).map_move |win| {
+            unsafe { (*win.page).js_info.get_ref().js_compartment.cx.ptr }
+        }
+    }
+
     pub fn new(type_id: NodeTypeId) -> Node<ScriptView> {
         Node {
             wrapper: WrapperCache::new(),
@@ -509,6 +939,9 @@ impl Node<ScriptView> {
             owner_doc: None,
 
             layout_data: None,
+
+            snapshot_subtree: false,
+            snapshot_dirty: false,
         }
     }
 
@@ -517,7 +950,8 @@ impl Node<ScriptView> {
             ElementNodeTypeId(_) => 1,
             TextNodeTypeId       => 3,
             CommentNodeTypeId    => 8,
-            DoctypeNodeTypeId    => 10
+            DoctypeNodeTypeId    => 10,
+            DocumentFragmentNodeTypeId => 11,
         }
     }
 
@@ -592,34 +1026,136 @@ impl Node<ScriptView> {
     pub fn SetNodeValue(&mut self, _val: &DOMString, _rv: &mut ErrorResult) {
     }
 
-    pub fn GetTextContent(&self) -> DOMString {
-        null_string
+    pub fn GetTextContent(&self, abstract_self: AbstractNode<ScriptView>) -> DOMString {
+        match self.type_id {
+            ElementNodeTypeId(*) | DocumentFragmentNodeTypeId => {
+                let mut content = ~"";
+                for node in abstract_self.traverse_preorder() {
+                    if node.is_text() {
+                        do node.with_imm_text |text| {
+                            content.push_str(text.parent.data.to_str());
+                        }
+                    }
+                }
+                str(content)
+            }
+            CommentNodeTypeId | TextNodeTypeId => {
+                do abstract_self.transmute |characterdata: &CharacterData| {
+                    characterdata.Data()
+                }
+            }
+            DoctypeNodeTypeId => null_string,
+        }
     }
 
-    pub fn SetTextContent(&mut self, _val: &DOMString, _rv: &mut ErrorResult) {
+    /// Replaces the children of an element with a single text node holding `value`, or the text
+    /// of a text node or comment with `value`.
+    pub fn SetTextContent(&mut self,
+                          abstract_self: AbstractNode<ScriptView>,
+                          value: &DOMString,
+                          _rv: &mut ErrorResult) {
+        let value = value.to_str();
+        match self.type_id {
+            ElementNodeTypeId(*) | DocumentFragmentNodeTypeId => {
+                let cx = match self.script_cx() {
+                    Some(cx) => cx,
+                    None => return,
+                };
+
+                let old_children: ~[AbstractNode<ScriptView>] = abstract_self.children().collect();
+                for &child in old_children.iter() {
+                    abstract_self.remove_child(child);
+                }
+                let mut added = ~[];
+                if !value.is_empty() {
+                    let text = unsafe { Node::as_abstract_node(cx, @Text::new(value)) };
+                    abstract_self.add_child(text);
+                    do text.with_mut_base |text| {
+                        text.owner_doc = self.owner_doc;
+                    }
+                    added.push(text);
+                }
+                if !added.is_empty() || !old_children.is_empty() {
+                    abstract_self.mutated(ChildListMutation(added, old_children, None, None));
+                }
+            }
+            CommentNodeTypeId | TextNodeTypeId => {
+                do abstract_self.transmute_mut |characterdata: &mut CharacterData| {
+                    characterdata.set_data(str(value.clone()));
+                }
+            }
+            DoctypeNodeTypeId => {}
+        }
     }
 
-    pub fn InsertBefore(&mut self, _node: AbstractNode<ScriptView>, _child: Option<AbstractNode<ScriptView>>, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
-        fail!("stub")
+    pub fn InsertBefore(&mut self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        node: AbstractNode<ScriptView>,
+                        child: Option<AbstractNode<ScriptView>>,
+                        rv: &mut ErrorResult)
+                        -> AbstractNode<ScriptView> {
+        *rv = abstract_self.pre_insert(node, child);
+        node
     }
 
-    pub fn AppendChild(&mut self, _node: AbstractNode<ScriptView>, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
-        fail!("stub")
+    pub fn AppendChild(&mut self,
+                       abstract_self: AbstractNode<ScriptView>,
+                       node: AbstractNode<ScriptView>,
+                       rv: &mut ErrorResult)
+                       -> AbstractNode<ScriptView> {
+        *rv = abstract_self.pre_insert(node, None);
+        node
     }
 
-    pub fn ReplaceChild(&mut self, _node: AbstractNode<ScriptView>, _child: AbstractNode<ScriptView>, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
-        fail!("stub")
+    pub fn ReplaceChild(&mut self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        node: AbstractNode<ScriptView>,
+                        child: AbstractNode<ScriptView>,
+                        rv: &mut ErrorResult)
+                        -> AbstractNode<ScriptView> {
+        if child.parent_node() != Some(abstract_self) {
+            *rv = Err(NotFound);
+            return child
+        }
+        if node == child {
+            return child
+        }
+        let mut reference = child.next_sibling();
+        if reference == Some(node) {
+            reference = node.next_sibling();
+        }
+        *rv = abstract_self.check_pre_insert(node);
+        if rv.is_ok() {
+            abstract_self.remove_child(child);
+            *rv = abstract_self.pre_insert(node, reference);
+        }
+        child
     }
 
-    pub fn RemoveChild(&mut self, _node: AbstractNode<ScriptView>, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
-        fail!("stub")
+    pub fn RemoveChild(&mut self,
+                       abstract_self: AbstractNode<ScriptView>,
+                       child: AbstractNode<ScriptView>,
+                       rv: &mut ErrorResult)
+                       -> AbstractNode<ScriptView> {
+        *rv = abstract_self.pre_remove(child);
+        child
     }
 
     pub fn Normalize(&mut self) {
     }
 
-    pub fn CloneNode(&self, _deep: bool, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
-        fail!("stub")
+    pub fn CloneNode(&self,
+                     abstract_self: AbstractNode<ScriptView>,
+                     deep: bool,
+                     rv: &mut ErrorResult)
+                     -> AbstractNode<ScriptView> {
+        match self.script_cx() {
+            Some(cx) => clone_node(cx, abstract_self, deep),
+            None => {
+                *rv = Err(InvalidState);
+                abstract_self
+            }
+        }
     }
 
     pub fn IsEqualNode(&self, _node: Option<AbstractNode<ScriptView>>) -> bool {
@@ -684,6 +1220,7 @@ pub fn define_bindings(compartment: @mut Compartment) {
     bindings::node::init(compartment);
     bindings::element::init(compartment);
     bindings::text::init(compartment);
+    bindings::console::init(compartment);
     bindings::utils::initialize_global(compartment.global_obj.ptr);
     bindings::codegen::RegisterBindings::Register(compartment);
 }
@@ -0,0 +1,69 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::PopStateEventBinding;
+use dom::bindings::utils::DOMString;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::event::Event;
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+/// The event fired at the window when the user moves to another of its document's session
+/// history entries.
+pub struct PopStateEvent {
+    parent: Event,
+    /// The state object of the entry moved to.
+    state: JSVal,
+}
+
+impl PopStateEvent {
+    pub fn new(type_: &DOMString, state: JSVal) -> PopStateEvent {
+        PopStateEvent {
+            parent: Event::new(type_),
+            state: state,
+        }
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn State(&self, _cx: *JSContext) -> JSVal {
+        self.state
+    }
+}
+
+impl CacheableWrapper for PopStateEvent {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        return self.parent.get_wrappercache()
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        PopStateEventBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for PopStateEvent {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        self.parent.GetParentObject(cx)
+    }
+}
+
+impl DerivedWrapper for PopStateEvent {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,101 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::ProgressEventBinding;
+use dom::bindings::utils::{ErrorResult, DOMString};
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::event::Event;
+use dom::window::Window;
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+use servo_net::resource_task::LoadProgress;
+
+pub struct ProgressEvent {
+    parent: Event,
+    length_computable: bool,
+    loaded: u64,
+    total: u64,
+}
+
+impl ProgressEvent {
+    pub fn new(type_: &DOMString, length_computable: bool, loaded: u64, total: u64)
+               -> ProgressEvent {
+        ProgressEvent {
+            parent: Event::new(type_),
+            length_computable: length_computable,
+            loaded: loaded,
+            total: total,
+        }
+    }
+
+    /// Creates an event describing the progress of a load from the resource task.
+    pub fn from_load_progress(type_: &DOMString, progress: &LoadProgress) -> ProgressEvent {
+        match progress.total {
+            Some(total) => ProgressEvent::new(type_, true, progress.loaded as u64, total as u64),
+            None => ProgressEvent::new(type_, false, progress.loaded as u64, 0),
+        }
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn Constructor(_owner: @mut Window,
+                       type_: &DOMString,
+                       init: &ProgressEventBinding::ProgressEventInit,
+                       _rv: &mut ErrorResult) -> @mut ProgressEvent {
+        let event = @mut ProgressEvent::new(type_, init.lengthComputable, init.loaded,
+                                            init.total);
+        event.parent.bubbles = init.parent.bubbles;
+        event.parent.cancelable = init.parent.cancelable;
+        event
+    }
+
+    pub fn LengthComputable(&self) -> bool {
+        self.length_computable
+    }
+
+    pub fn Loaded(&self) -> u64 {
+        self.loaded
+    }
+
+    pub fn Total(&self) -> u64 {
+        self.total
+    }
+}
+
+impl CacheableWrapper for ProgressEvent {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        return self.parent.get_wrappercache()
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        ProgressEventBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for ProgressEvent {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        self.parent.GetParentObject(cx)
+    }
+}
+
+impl DerivedWrapper for ProgressEvent {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,548 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `Range`.
+//!
+//! FIXME: Ranges don't yet follow the mutations of the tree they're in, so a range over nodes
+//! that are later moved or removed by something other than the range itself may end up pointing
+//! at the wrong place.
+
+use dom::bindings::codegen::RangeBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{Error, ErrorResult, HierarchyRequest, IndexSize, InvalidNodeType, str};
+use dom::characterdata::CharacterData;
+use dom::documentfragment::DocumentFragment;
+use dom::node::{AbstractNode, CommentNodeTypeId, DoctypeNodeTypeId, Node, ScriptView};
+use dom::node::TextNodeTypeId;
+use dom::window::Window;
+use html::hubbub_html_parser::clone_node;
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext, JSVal};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use servo_util::tree::TreeNodeRef;
+
+use std::cast;
+
+/// A position in the tree: between two children of `node`, or, if `node` holds text, between two
+/// of its characters.
+#[deriving(Clone, Eq)]
+pub struct BoundaryPoint {
+    node: AbstractNode<ScriptView>,
+    offset: uint,
+}
+
+impl BoundaryPoint {
+    /// Returns the index of each of the node's ancestors among its siblings, from the root down,
+    /// followed by the offset. Points compare in tree order by comparing their paths.
+    fn path(&self) -> ~[uint] {
+        let mut path = ~[self.offset];
+        let mut node = self.node;
+        loop {
+            match node.parent_node() {
+                Some(parent) => {
+                    path.push(node.index());
+                    node = parent;
+                }
+                None => break,
+            }
+        }
+        path.reverse();
+        path
+    }
+
+    pub fn is_before(&self, other: &BoundaryPoint) -> bool {
+        self.path() < other.path()
+    }
+}
+
+/// A range of a document, from one boundary point to another.
+pub struct Range {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    start: BoundaryPoint,
+    end: BoundaryPoint,
+}
+
+impl Range {
+    /// Creates a range collapsed at the start of `node`.
+    pub fn new(window: @mut Window, node: AbstractNode<ScriptView>, cx: *JSContext,
+               scope: *JSObject) -> @mut Range {
+        let point = BoundaryPoint {
+            node: node,
+            offset: 0,
+        };
+        let range = @mut Range {
+            wrapper: WrapperCache::new(),
+            window: window,
+            start: point.clone(),
+            end: point,
+        };
+        range.init_wrapper(cx, scope);
+        range
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn StartContainer(&self) -> AbstractNode<ScriptView> {
+        self.start.node
+    }
+
+    pub fn StartOffset(&self) -> u32 {
+        self.start.offset as u32
+    }
+
+    pub fn EndContainer(&self) -> AbstractNode<ScriptView> {
+        self.end.node
+    }
+
+    pub fn EndOffset(&self) -> u32 {
+        self.end.offset as u32
+    }
+
+    pub fn Collapsed(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn CommonAncestorContainer(&self) -> AbstractNode<ScriptView> {
+        common_ancestor(self.start.node, self.end.node)
+    }
+
+    pub fn SetStart(&mut self, node: AbstractNode<ScriptView>, offset: u32, rv: &mut ErrorResult) {
+        *rv = self.set_start(node, offset as uint);
+    }
+
+    pub fn SetEnd(&mut self, node: AbstractNode<ScriptView>, offset: u32, rv: &mut ErrorResult) {
+        *rv = self.set_end(node, offset as uint);
+    }
+
+    pub fn SetStartBefore(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        *rv = match node.parent_node() {
+            Some(parent) => self.set_start(parent, node.index()),
+            None => Err(InvalidNodeType),
+        };
+    }
+
+    pub fn SetStartAfter(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        *rv = match node.parent_node() {
+            Some(parent) => self.set_start(parent, node.index() + 1),
+            None => Err(InvalidNodeType),
+        };
+    }
+
+    pub fn SetEndBefore(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        *rv = match node.parent_node() {
+            Some(parent) => self.set_end(parent, node.index()),
+            None => Err(InvalidNodeType),
+        };
+    }
+
+    pub fn SetEndAfter(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        *rv = match node.parent_node() {
+            Some(parent) => self.set_end(parent, node.index() + 1),
+            None => Err(InvalidNodeType),
+        };
+    }
+
+    pub fn Collapse(&mut self, to_start: bool) {
+        if to_start {
+            self.end = self.start.clone();
+        } else {
+            self.start = self.end.clone();
+        }
+    }
+
+    pub fn SelectNode(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        let parent = match node.parent_node() {
+            Some(parent) => parent,
+            None => {
+                *rv = Err(InvalidNodeType);
+                return
+            }
+        };
+        let index = node.index();
+        self.start = BoundaryPoint { node: parent, offset: index };
+        self.end = BoundaryPoint { node: parent, offset: index + 1 };
+    }
+
+    pub fn SelectNodeContents(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        if node.type_id() == DoctypeNodeTypeId {
+            *rv = Err(InvalidNodeType);
+            return
+        }
+        self.start = BoundaryPoint { node: node, offset: 0 };
+        self.end = BoundaryPoint { node: node, offset: length(node) };
+    }
+
+    pub fn DeleteContents(&mut self, rv: &mut ErrorResult) {
+        match self.extract() {
+            Ok(_) => {}
+            Err(error) => *rv = Err(error),
+        }
+    }
+
+    pub fn ExtractContents(&mut self, rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
+        match self.extract() {
+            Ok(fragment) => fragment,
+            Err(error) => {
+                *rv = Err(error);
+                self.new_fragment()
+            }
+        }
+    }
+
+    pub fn CloneContents(&self, rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
+        match self.clone_contents(&self.start, &self.end) {
+            Ok(fragment) => fragment,
+            Err(error) => {
+                *rv = Err(error);
+                self.new_fragment()
+            }
+        }
+    }
+
+    pub fn Detach(&self) {
+    }
+}
+
+impl Range {
+    fn set_start(&mut self, node: AbstractNode<ScriptView>, offset: uint) -> ErrorResult {
+        let point = match boundary_point(node, offset) {
+            Ok(point) => point,
+            Err(error) => return Err(error),
+        };
+        if root(node) != root(self.end.node) || self.end.is_before(&point) {
+            self.end = point.clone();
+        }
+        self.start = point;
+        Ok(())
+    }
+
+    fn set_end(&mut self, node: AbstractNode<ScriptView>, offset: uint) -> ErrorResult {
+        let point = match boundary_point(node, offset) {
+            Ok(point) => point,
+            Err(error) => return Err(error),
+        };
+        if root(node) != root(self.start.node) || point.is_before(&self.start) {
+            self.start = point.clone();
+        }
+        self.end = point;
+        Ok(())
+    }
+
+    fn cx(&self) -> *JSContext {
+        unsafe {
+            (*self.window.page).js_info.get_ref().js_compartment.cx.ptr
+        }
+    }
+
+    /// Creates an empty document fragment in the range's document.
+    fn new_fragment(&self) -> AbstractNode<ScriptView> {
+        let fragment = unsafe { Node::as_abstract_node(self.cx(), @DocumentFragment::new()) };
+        let doc = self.window.Document();
+        do fragment.with_mut_base |base| {
+            base.add_to_doc(doc);
+        }
+        fragment
+    }
+
+    /// Copies `node`, without its children, but with its text replaced by `data` if it has any.
+    fn clone_with_data(&self, node: AbstractNode<ScriptView>, data: Option<~str>)
+                       -> AbstractNode<ScriptView> {
+        let clone = clone_node(self.cx(), node, false);
+        for data in data.iter() {
+            set_data(clone, data.clone());
+        }
+        clone
+    }
+
+    /// Returns a fragment holding a copy of what's between `start` and `end`. Nodes that are only
+    /// partly in the range are copied without the parts that aren't.
+    fn clone_contents(&self, start: &BoundaryPoint, end: &BoundaryPoint)
+                      -> Result<AbstractNode<ScriptView>, Error> {
+        let fragment = self.new_fragment();
+        if start == end {
+            return Ok(fragment)
+        }
+
+        if start.node == end.node && is_character_data(start.node) {
+            let text = data(start.node).slice(start.offset, end.offset).to_owned();
+            append(fragment, self.clone_with_data(start.node, Some(text)));
+            return Ok(fragment)
+        }
+
+        let (first, last, contained) = match split(start, end) {
+            Ok(split) => split,
+            Err(error) => return Err(error),
+        };
+
+        for &first in first.iter() {
+            if is_character_data(first) {
+                let text = data(start.node).slice_from(start.offset).to_owned();
+                append(fragment, self.clone_with_data(start.node, Some(text)));
+            } else {
+                let clone = self.clone_with_data(first, None);
+                append(fragment, clone);
+                let end = BoundaryPoint { node: first, offset: length(first) };
+                match self.clone_contents(start, &end) {
+                    Ok(subfragment) => append(clone, subfragment),
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+
+        for &child in contained.iter() {
+            append(fragment, clone_node(self.cx(), child, true));
+        }
+
+        for &last in last.iter() {
+            if is_character_data(last) {
+                let text = data(end.node).slice_to(end.offset).to_owned();
+                append(fragment, self.clone_with_data(end.node, Some(text)));
+            } else {
+                let clone = self.clone_with_data(last, None);
+                append(fragment, clone);
+                let start = BoundaryPoint { node: last, offset: 0 };
+                match self.clone_contents(&start, end) {
+                    Ok(subfragment) => append(clone, subfragment),
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+
+        Ok(fragment)
+    }
+
+    /// Moves what's in the range into a new fragment, and collapses the range to where its
+    /// contents were.
+    fn extract(&mut self) -> Result<AbstractNode<ScriptView>, Error> {
+        let (start, end) = (self.start.clone(), self.end.clone());
+        let (new_node, new_offset) = if start.node.is_inclusive_ancestor_of(end.node) {
+            (start.node, start.offset)
+        } else {
+            let mut reference = start.node;
+            while !reference.parent_node().unwrap().is_inclusive_ancestor_of(end.node) {
+                reference = reference.parent_node().unwrap();
+            }
+            (reference.parent_node().unwrap(), reference.index() + 1)
+        };
+
+        match self.extract_contents(&start, &end) {
+            Ok(fragment) => {
+                self.start = BoundaryPoint { node: new_node, offset: new_offset };
+                self.end = self.start.clone();
+                Ok(fragment)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Returns a fragment holding what's between `start` and `end`, removing it from the tree.
+    /// Nodes that are only partly in the range are split: the part in the range is copied into
+    /// the fragment, and the rest stays where it was.
+    fn extract_contents(&self, start: &BoundaryPoint, end: &BoundaryPoint)
+                        -> Result<AbstractNode<ScriptView>, Error> {
+        let fragment = self.new_fragment();
+        if start == end {
+            return Ok(fragment)
+        }
+
+        if start.node == end.node && is_character_data(start.node) {
+            let text = data(start.node);
+            let extracted = text.slice(start.offset, end.offset).to_owned();
+            append(fragment, self.clone_with_data(start.node, Some(extracted)));
+            set_data(start.node, text.slice_to(start.offset) + text.slice_from(end.offset));
+            return Ok(fragment)
+        }
+
+        let (first, last, contained) = match split(start, end) {
+            Ok(split) => split,
+            Err(error) => return Err(error),
+        };
+
+        for &first in first.iter() {
+            if is_character_data(first) {
+                let text = data(start.node);
+                let extracted = text.slice_from(start.offset).to_owned();
+                append(fragment, self.clone_with_data(start.node, Some(extracted)));
+                set_data(start.node, text.slice_to(start.offset).to_owned());
+            } else {
+                let clone = self.clone_with_data(first, None);
+                append(fragment, clone);
+                let end = BoundaryPoint { node: first, offset: length(first) };
+                match self.extract_contents(start, &end) {
+                    Ok(subfragment) => append(clone, subfragment),
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+
+        for &child in contained.iter() {
+            append(fragment, child);
+        }
+
+        for &last in last.iter() {
+            if is_character_data(last) {
+                let text = data(end.node);
+                let extracted = text.slice_to(end.offset).to_owned();
+                append(fragment, self.clone_with_data(end.node, Some(extracted)));
+                set_data(end.node, text.slice_from(end.offset).to_owned());
+            } else {
+                let clone = self.clone_with_data(last, None);
+                append(fragment, clone);
+                let start = BoundaryPoint { node: last, offset: 0 };
+                match self.extract_contents(&start, end) {
+                    Ok(subfragment) => append(clone, subfragment),
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+
+        Ok(fragment)
+    }
+}
+
+/// Checks that `offset` is a position in `node`.
+pub fn boundary_point(node: AbstractNode<ScriptView>, offset: uint)
+                      -> Result<BoundaryPoint, Error> {
+    if node.type_id() == DoctypeNodeTypeId {
+        return Err(InvalidNodeType)
+    }
+    if offset > length(node) {
+        return Err(IndexSize)
+    }
+    Ok(BoundaryPoint {
+        node: node,
+        offset: offset,
+    })
+}
+
+/// Splits the children of the closest common ancestor of `start` and `end` into the ones that
+/// hold the start and the end, if they're only partly in the range, and the ones wholly in it.
+fn split(start: &BoundaryPoint, end: &BoundaryPoint)
+         -> Result<(Option<AbstractNode<ScriptView>>,
+                    Option<AbstractNode<ScriptView>>,
+                    ~[AbstractNode<ScriptView>]), Error> {
+    let common = common_ancestor(start.node, end.node);
+    let first = if start.node.is_inclusive_ancestor_of(end.node) {
+        None
+    } else {
+        common.children().find(|child| child.is_inclusive_ancestor_of(start.node))
+    };
+    let last = if end.node.is_inclusive_ancestor_of(start.node) {
+        None
+    } else {
+        common.children().find(|child| child.is_inclusive_ancestor_of(end.node))
+    };
+    let contained: ~[AbstractNode<ScriptView>] = common.children().filter(|child| {
+        let child_start = BoundaryPoint { node: *child, offset: 0 };
+        let child_end = BoundaryPoint { node: *child, offset: length(*child) };
+        start.is_before(&child_start) && child_end.is_before(end)
+    }).collect();
+
+    if contained.iter().any(|child| child.type_id() == DoctypeNodeTypeId) {
+        return Err(HierarchyRequest)
+    }
+    Ok((first, last, contained))
+}
+
+/// Moves `node` to the end of `parent`'s children.
+fn append(parent: AbstractNode<ScriptView>, node: AbstractNode<ScriptView>) {
+    // The callers have already checked that this can't fail.
+    let _ = parent.pre_insert(node, None);
+}
+
+pub fn common_ancestor(a: AbstractNode<ScriptView>, b: AbstractNode<ScriptView>)
+                   -> AbstractNode<ScriptView> {
+    let mut ancestor = a;
+    while !ancestor.is_inclusive_ancestor_of(b) {
+        ancestor = ancestor.parent_node().unwrap();
+    }
+    ancestor
+}
+
+fn root(node: AbstractNode<ScriptView>) -> AbstractNode<ScriptView> {
+    let mut root = node;
+    loop {
+        match root.parent_node() {
+            Some(parent) => root = parent,
+            None => return root,
+        }
+    }
+}
+
+fn is_character_data(node: AbstractNode<ScriptView>) -> bool {
+    match node.type_id() {
+        TextNodeTypeId | CommentNodeTypeId => true,
+        _ => false,
+    }
+}
+
+fn data(node: AbstractNode<ScriptView>) -> ~str {
+    do node.transmute |characterdata: &CharacterData| {
+        characterdata.data.to_str()
+    }
+}
+
+fn set_data(node: AbstractNode<ScriptView>, data: ~str) {
+    do node.transmute_mut |characterdata: &mut CharacterData| {
+        characterdata.set_data(str(data.clone()));
+    }
+}
+
+/// Returns the number of positions in `node`, which is the length of its text if it has text and
+/// the number of its children otherwise.
+pub fn length(node: AbstractNode<ScriptView>) -> uint {
+    match node.type_id() {
+        TextNodeTypeId | CommentNodeTypeId => data(node).len(),
+        DoctypeNodeTypeId => 0,
+        _ => {
+            let mut length = 0;
+            for _ in node.children() {
+                length += 1;
+            }
+            length
+        }
+    }
+}
+
+impl CacheableWrapper for Range {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        RangeBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for Range {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for Range {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
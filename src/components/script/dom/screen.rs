@@ -0,0 +1,100 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::codegen::ScreenBinding;
+use script_task::page_from_context;
+use servo_msg::compositor_msg::WindowMetrics;
+
+use js::jsapi::{JSObject, JSContext, JSVal};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+
+use std::cast;
+
+/// The `window.screen` object. Sizes are reported in CSS pixels.
+pub struct Screen {
+    wrapper: WrapperCache,
+    metrics: WindowMetrics,
+}
+
+impl Screen {
+    pub fn new(metrics: WindowMetrics, cx: *JSContext, scope: *JSObject) -> @mut Screen {
+        let screen = @mut Screen {
+            wrapper: WrapperCache::new(),
+            metrics: metrics,
+        };
+        screen.init_wrapper(cx, scope);
+        screen
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    fn to_css_px(&self, device_px: uint) -> i32 {
+        (device_px as f32 / self.metrics.device_pixel_ratio) as i32
+    }
+
+    pub fn AvailWidth(&self) -> i32 {
+        self.to_css_px(self.metrics.available_screen_size.width)
+    }
+
+    pub fn AvailHeight(&self) -> i32 {
+        self.to_css_px(self.metrics.available_screen_size.height)
+    }
+
+    pub fn Width(&self) -> i32 {
+        self.to_css_px(self.metrics.screen_size.width)
+    }
+
+    pub fn Height(&self) -> i32 {
+        self.to_css_px(self.metrics.screen_size.height)
+    }
+
+    pub fn ColorDepth(&self) -> u32 {
+        self.metrics.color_depth as u32
+    }
+
+    pub fn PixelDepth(&self) -> u32 {
+        self.metrics.color_depth as u32
+    }
+}
+
+impl CacheableWrapper for Screen {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        ScreenBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for Screen {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for Screen {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,245 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `Selection`, the text the user selected. The document keeps the selection as
+//! the boundary point where the user started selecting, the anchor, and the one the selection
+//! extends to, the focus. This object is script's view of it.
+//!
+//! FIXME: Like ranges, the selection doesn't yet follow mutations of the tree it's in.
+
+use dom::bindings::codegen::SelectionBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, ErrorResult, IndexSize, InvalidNodeType, InvalidState, str};
+use dom::node::{AbstractNode, DoctypeNodeTypeId, ScriptView};
+use dom::range::{BoundaryPoint, Range, boundary_point, common_ancestor, length};
+use dom::window::Window;
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext, JSVal};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use servo_util::tree::TreeNodeRef;
+
+use std::cast;
+
+/// The `window.getSelection()` object.
+pub struct Selection {
+    wrapper: WrapperCache,
+    window: @mut Window,
+}
+
+impl Selection {
+    pub fn new(window: @mut Window, cx: *JSContext, scope: *JSObject) -> @mut Selection {
+        let selection = @mut Selection {
+            wrapper: WrapperCache::new(),
+            window: window,
+        };
+        selection.init_wrapper(cx, scope);
+        selection
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn GetAnchorNode(&self) -> Option<AbstractNode<ScriptView>> {
+        self.get().map(|&(ref anchor, _)| anchor.node)
+    }
+
+    pub fn AnchorOffset(&self) -> u32 {
+        self.get().map_default(0, |&(ref anchor, _)| anchor.offset as u32)
+    }
+
+    pub fn GetFocusNode(&self) -> Option<AbstractNode<ScriptView>> {
+        self.get().map(|&(_, ref focus)| focus.node)
+    }
+
+    pub fn FocusOffset(&self) -> u32 {
+        self.get().map_default(0, |&(_, ref focus)| focus.offset as u32)
+    }
+
+    pub fn IsCollapsed(&self) -> bool {
+        match self.get() {
+            Some((anchor, focus)) => anchor == focus,
+            None => true,
+        }
+    }
+
+    pub fn Collapse(&self, node: AbstractNode<ScriptView>, offset: u32, rv: &mut ErrorResult) {
+        match boundary_point(node, offset as uint) {
+            Ok(point) => self.set(Some((point.clone(), point))),
+            Err(error) => *rv = Err(error),
+        }
+    }
+
+    pub fn CollapseToStart(&self, rv: &mut ErrorResult) {
+        match self.get() {
+            Some((anchor, focus)) => {
+                let start = if focus.is_before(&anchor) { focus } else { anchor };
+                self.set(Some((start.clone(), start)))
+            }
+            None => *rv = Err(InvalidState),
+        }
+    }
+
+    pub fn CollapseToEnd(&self, rv: &mut ErrorResult) {
+        match self.get() {
+            Some((anchor, focus)) => {
+                let end = if focus.is_before(&anchor) { anchor } else { focus };
+                self.set(Some((end.clone(), end)))
+            }
+            None => *rv = Err(InvalidState),
+        }
+    }
+
+    pub fn Extend(&self, node: AbstractNode<ScriptView>, offset: u32, rv: &mut ErrorResult) {
+        let anchor = match self.get() {
+            Some((anchor, _)) => anchor,
+            None => {
+                *rv = Err(InvalidState);
+                return
+            }
+        };
+        match boundary_point(node, offset as uint) {
+            Ok(focus) => self.set(Some((anchor, focus))),
+            Err(error) => *rv = Err(error),
+        }
+    }
+
+    pub fn SelectAllChildren(&self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        if node.type_id() == DoctypeNodeTypeId {
+            *rv = Err(InvalidNodeType);
+            return
+        }
+        self.set(Some((BoundaryPoint { node: node, offset: 0 },
+                       BoundaryPoint { node: node, offset: length(node) })))
+    }
+
+    pub fn RangeCount(&self) -> u32 {
+        if self.get().is_some() { 1 } else { 0 }
+    }
+
+    pub fn GetRangeAt(&self, index: u32, rv: &mut ErrorResult) -> @mut Range {
+        let cx = unsafe { (*self.window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = self.window.wrapper.get_wrapper();
+        match self.get() {
+            Some((anchor, focus)) if index == 0 => {
+                let range = Range::new(self.window, anchor.node, cx, scope);
+                if focus.is_before(&anchor) {
+                    range.start = focus;
+                    range.end = anchor;
+                } else {
+                    range.start = anchor;
+                    range.end = focus;
+                }
+                range
+            }
+            _ => {
+                *rv = Err(IndexSize);
+                let root = self.window.Document().with_base(|doc| doc.root);
+                Range::new(self.window, root, cx, scope)
+            }
+        }
+    }
+
+    pub fn RemoveAllRanges(&self) {
+        self.set(None)
+    }
+
+    pub fn ToString(&self) -> DOMString {
+        match self.get() {
+            Some((anchor, focus)) => str(text(&anchor, &focus)),
+            None => str(~""),
+        }
+    }
+}
+
+impl Selection {
+    fn get(&self) -> Option<(BoundaryPoint, BoundaryPoint)> {
+        self.window.Document().with_base(|doc| doc.selection.clone())
+    }
+
+    fn set(&self, selection: Option<(BoundaryPoint, BoundaryPoint)>) {
+        self.window.Document().with_mut_base(|doc| doc.set_selection(selection.clone()))
+    }
+}
+
+/// The text between `anchor` and `focus`, whichever comes first: each text node with text between
+/// them, in tree order, and the byte range of its text that is.
+pub fn selected_text(anchor: &BoundaryPoint, focus: &BoundaryPoint)
+                     -> ~[(AbstractNode<ScriptView>, uint, uint)] {
+    let (start, end) = if focus.is_before(anchor) { (focus, anchor) } else { (anchor, focus) };
+    let mut texts = ~[];
+    for node in common_ancestor(start.node, end.node).traverse_preorder() {
+        if !node.is_text() {
+            loop
+        }
+        let node_length = length(node);
+        let begin = if node == start.node {
+            start.offset
+        } else if start.is_before(&BoundaryPoint { node: node, offset: 0 }) {
+            0
+        } else {
+            loop
+        };
+        let finish = if node == end.node {
+            end.offset
+        } else if (BoundaryPoint { node: node, offset: node_length }).is_before(end) {
+            node_length
+        } else {
+            loop
+        };
+        if begin < finish {
+            texts.push((node, begin, finish));
+        }
+    }
+    texts
+}
+
+/// The text between `anchor` and `focus`, as it's copied to the clipboard.
+pub fn text(anchor: &BoundaryPoint, focus: &BoundaryPoint) -> ~str {
+    let mut text = ~"";
+    for &(node, begin, end) in selected_text(anchor, focus).iter() {
+        let data = node.with_imm_text(|node| node.parent.data.to_str());
+        text.push_str(data.slice(begin, end));
+    }
+    text
+}
+
+impl CacheableWrapper for Selection {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        SelectionBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for Selection {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+impl DerivedWrapper for Selection {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::StyleSheetListBinding;
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject};
+use dom::cssstylesheet::CSSStyleSheet;
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext};
+
+use std::cast;
+
+/// The style sheets of a document, in the order they apply in. The document adds to it.
+pub struct StyleSheetList {
+    wrapper: WrapperCache,
+    sheets: ~[@mut CSSStyleSheet]
+}
+
+impl StyleSheetList {
+    pub fn new(cx: *JSContext, scope: *JSObject) -> @mut StyleSheetList {
+        let list = @mut StyleSheetList {
+            wrapper: WrapperCache::new(),
+            sheets: ~[]
+        };
+        list.wrap_object_shared(cx, scope);
+        list
+    }
+
+    pub fn Length(&self) -> u32 {
+        self.sheets.len() as u32
+    }
+
+    pub fn Item(&self, index: u32) -> Option<@mut CSSStyleSheet> {
+        if index < self.sheets.len() as u32 {
+            Some(self.sheets[index])
+        } else {
+            None
+        }
+    }
+
+    pub fn IndexedGetter(&self, index: u32, found: &mut bool) -> Option<@mut CSSStyleSheet> {
+        *found = index < self.sheets.len() as u32;
+        self.Item(index)
+    }
+}
+
+impl CacheableWrapper for StyleSheetList {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        StyleSheetListBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for StyleSheetList {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
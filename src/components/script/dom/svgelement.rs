@@ -0,0 +1,36 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! SVG elements. Layout draws an inline `<svg>` from the geometry of the shapes in it; to script
+//! they're plain elements, with nothing of the SVG DOM.
+
+use dom::element::{Element, ElementTypeId};
+
+pub struct SVGElement {
+    parent: Element
+}
+
+impl SVGElement {
+    pub fn new(type_id: ElementTypeId, tag_name: ~str) -> SVGElement {
+        SVGElement {
+            parent: Element::new(type_id, tag_name)
+        }
+    }
+}
+
+pub struct SVGSVGElement {
+    parent: SVGElement
+}
+
+pub struct SVGRectElement {
+    parent: SVGElement
+}
+
+pub struct SVGCircleElement {
+    parent: SVGElement
+}
+
+pub struct SVGPathElement {
+    parent: SVGElement
+}
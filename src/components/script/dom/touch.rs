@@ -0,0 +1,97 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::TouchBinding;
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject};
+use dom::node::{AbstractNode, ScriptView};
+use script_task::page_from_context;
+
+use geom::point::Point2D;
+use js::jsapi::{JSObject, JSContext};
+
+use std::cast;
+
+/// A finger on the screen, as it was when a touch event was fired.
+pub struct Touch {
+    wrapper: WrapperCache,
+    identifier: i32,
+    /// The element the finger first touched, which stays the target of its events wherever it
+    /// moves.
+    target: AbstractNode<ScriptView>,
+    /// Where the finger is, relative to the viewport.
+    client_point: Point2D<i32>,
+    /// Where the finger is, relative to the document.
+    page_point: Point2D<i32>,
+}
+
+impl Touch {
+    pub fn new(identifier: i32, target: AbstractNode<ScriptView>, client_point: Point2D<i32>,
+               page_point: Point2D<i32>, cx: *JSContext, scope: *JSObject) -> @mut Touch {
+        let touch = @mut Touch {
+            wrapper: WrapperCache::new(),
+            identifier: identifier,
+            target: target,
+            client_point: client_point,
+            page_point: page_point,
+        };
+        touch.wrap_object_shared(cx, scope);
+        touch
+    }
+
+    pub fn Identifier(&self) -> i32 {
+        self.identifier
+    }
+
+    pub fn Target(&self) -> AbstractNode<ScriptView> {
+        self.target
+    }
+
+    // FIXME: Script doesn't know where the window is on the screen, so these are relative to the
+    // viewport.
+    pub fn ScreenX(&self) -> i32 {
+        self.client_point.x
+    }
+
+    pub fn ScreenY(&self) -> i32 {
+        self.client_point.y
+    }
+
+    pub fn ClientX(&self) -> i32 {
+        self.client_point.x
+    }
+
+    pub fn ClientY(&self) -> i32 {
+        self.client_point.y
+    }
+
+    pub fn PageX(&self) -> i32 {
+        self.page_point.x
+    }
+
+    pub fn PageY(&self) -> i32 {
+        self.page_point.y
+    }
+}
+
+impl CacheableWrapper for Touch {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        TouchBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for Touch {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
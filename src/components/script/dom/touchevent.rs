@@ -0,0 +1,136 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::TouchEventBinding;
+use dom::bindings::utils::{DOMString, CacheableWrapper, WrapperCache, BindingObject};
+use dom::bindings::utils::DerivedWrapper;
+use dom::eventtarget::invoke_listeners;
+use dom::node::{AbstractNode, ScriptView};
+use dom::touchlist::TouchList;
+use dom::uievent::UIEvent;
+use dom::window::Window;
+use dom::windowproxy::WindowProxy;
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+use servo_util::tree::TreeNodeRef;
+
+pub struct TouchEvent {
+    parent: UIEvent,
+    /// Every finger on the screen.
+    touches: @mut TouchList,
+    /// The fingers on the screen that first touched the target of the event.
+    target_touches: @mut TouchList,
+    /// The fingers whose touching, moving or lifting the event is about.
+    changed_touches: @mut TouchList,
+}
+
+impl TouchEvent {
+    pub fn new(type_: &DOMString, cancelable: bool, view: Option<@mut WindowProxy>,
+               touches: @mut TouchList, target_touches: @mut TouchList,
+               changed_touches: @mut TouchList) -> TouchEvent {
+        let mut parent = UIEvent::new(type_, true, cancelable, view, 0);
+        // `UIEvent::new` leaves the underlying event cancelable.
+        parent.parent.cancelable = cancelable;
+        TouchEvent {
+            parent: parent,
+            touches: touches,
+            target_touches: target_touches,
+            changed_touches: changed_touches,
+        }
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn Touches(&self) -> @mut TouchList {
+        self.touches
+    }
+
+    pub fn TargetTouches(&self) -> @mut TouchList {
+        self.target_touches
+    }
+
+    pub fn ChangedTouches(&self) -> @mut TouchList {
+        self.changed_touches
+    }
+
+    // FIXME: The compositor doesn't tell script which modifier keys are held down.
+    pub fn AltKey(&self) -> bool {
+        false
+    }
+
+    pub fn MetaKey(&self) -> bool {
+        false
+    }
+
+    pub fn CtrlKey(&self) -> bool {
+        false
+    }
+
+    pub fn ShiftKey(&self) -> bool {
+        false
+    }
+}
+
+/// Fires `event` at `target`: the listeners of the target hear of it, then those of each of its
+/// ancestors, then those of the window. Returns false if a listener canceled it.
+pub fn dispatch(event: @mut TouchEvent, target: AbstractNode<ScriptView>,
+                window: @mut Window) -> bool {
+    let type_ = event.parent.parent.type_.clone();
+    let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+    let event_obj = event.wrap_object_shared(cx, window.wrapper.get_wrapper());
+
+    let mut current = Some(target);
+    while current.is_some() {
+        let node = current.unwrap();
+        if node.is_element() {
+            let listeners = node.with_imm_element(|element| element.listeners.get(&type_));
+            // Listeners can only have been added from script, so the node has a wrapper by now.
+            if !listeners.is_empty() {
+                let this = node.with_base(|node| node.wrapper.get_wrapper());
+                invoke_listeners(cx, this, listeners, event_obj);
+            }
+        }
+        current = node.parent_node();
+    }
+    let listeners = window.listeners.get(&type_);
+    invoke_listeners(cx, window.wrapper.get_wrapper(), listeners, event_obj);
+
+    !event.parent.parent.default_prevented
+}
+
+impl CacheableWrapper for TouchEvent {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        return self.parent.get_wrappercache()
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        TouchEventBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for TouchEvent {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        self.parent.GetParentObject(cx)
+    }
+}
+
+impl DerivedWrapper for TouchEvent {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
+    }
+}
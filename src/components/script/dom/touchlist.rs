@@ -0,0 +1,67 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::TouchListBinding;
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject};
+use dom::touch::Touch;
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext};
+
+use std::cast;
+
+pub struct TouchList {
+    wrapper: WrapperCache,
+    touches: ~[@mut Touch]
+}
+
+impl TouchList {
+    pub fn new(touches: ~[@mut Touch], cx: *JSContext, scope: *JSObject) -> @mut TouchList {
+        let list = @mut TouchList {
+            wrapper: WrapperCache::new(),
+            touches: touches
+        };
+        list.wrap_object_shared(cx, scope);
+        list
+    }
+
+    pub fn Length(&self) -> u32 {
+        self.touches.len() as u32
+    }
+
+    pub fn Item(&self, index: u32) -> Option<@mut Touch> {
+        if index < self.touches.len() as u32 {
+            Some(self.touches[index])
+        } else {
+            None
+        }
+    }
+
+    pub fn IndexedGetter(&self, index: u32, found: &mut bool) -> Option<@mut Touch> {
+        *found = index < self.touches.len() as u32;
+        self.Item(index)
+    }
+}
+
+impl CacheableWrapper for TouchList {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        TouchListBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for TouchList {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
@@ -0,0 +1,388 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `WebSocket`. The connection itself is run by the net crate, whose events are
+//! routed back to the socket through the script task.
+
+use dom::bindings::codegen::WebSocketBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, ErrorResult, InvalidAccess, InvalidState, Security};
+use dom::bindings::utils::{Syntax, domstring_to_jsval, null_string, str};
+use dom::closeevent::CloseEvent;
+use dom::event::Event;
+use dom::eventtarget::{EventListeners, invoke_listeners};
+use dom::messageevent::MessageEvent;
+use dom::window::Window;
+use script_task::WebSocketEventMsg;
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+use servo_net::websocket::{BinaryMessage, Close, Closed, Failed, Opened, SendText, TextMessage};
+use servo_net::websocket::{WebSocketEvent, WebSocketMsg, connect, NORMAL_CLOSURE};
+use servo_util::url::Origin;
+use extra::url;
+use extra::url::Url;
+
+use std::cast;
+use std::cell::Cell;
+use std::comm;
+use std::comm::SharedChan;
+
+/// Identifies a `WebSocket` within its window, so that events coming back from its connection
+/// can find it.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct WebSocketId(uint);
+
+#[deriving(Eq)]
+pub enum WebSocketState {
+    Connecting = 0,
+    Open = 1,
+    Closing = 2,
+    Closed = 3,
+}
+
+/// The longest reason that can be given for closing a connection, in bytes, so that it fits in
+/// a control frame along with the status code.
+static MAX_REASON_LENGTH: uint = 123;
+
+/// An event that is due after the socket processes what happened to its connection.
+pub enum WebSocketDOMEvent {
+    OpenEvent,
+    TextMessageEvent(~str),
+    ErrorEvent,
+    CloseEvent_(bool, u16, ~str),
+}
+
+pub struct WebSocket {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    id: WebSocketId,
+    url: Url,
+    ready_state: WebSocketState,
+    /// The subprotocol the server chose, once the connection is open.
+    protocol: ~str,
+    /// Where to send messages for the connection. `None` if none was ever made.
+    chan: Option<SharedChan<WebSocketMsg>>,
+    listeners: EventListeners,
+}
+
+impl WebSocket {
+    pub fn new(window: @mut Window, url: Url) -> @mut WebSocket {
+        let socket = @mut WebSocket {
+            wrapper: WrapperCache::new(),
+            window: window,
+            id: window.next_websocket_id(),
+            url: url,
+            ready_state: Connecting,
+            protocol: ~"",
+            chan: None,
+            listeners: EventListeners::new(),
+        };
+
+        // TODO(tkuehn): This just handles the top-level page. Need to handle subframes.
+        let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = window.get_wrappercache().get_wrapper();
+        socket.wrap_object_shared(cx, scope);
+        socket
+    }
+
+    pub fn Constructor(owner: @mut Window, url: &DOMString, protocols: Option<DOMString>,
+                       rv: &mut ErrorResult) -> @mut WebSocket {
+        // Only absolute ws: and wss: URLs without a fragment can be connected to.
+        let parsed = match url::from_str(url.to_str()) {
+            Ok(url) => {
+                if (url.scheme == ~"ws" || url.scheme == ~"wss") && url.fragment.is_none() {
+                    Some(url)
+                } else {
+                    None
+                }
+            }
+            Err(_) => None,
+        };
+        let parsed = match parsed {
+            Some(url) => url,
+            None => {
+                *rv = Err(Syntax);
+                let socket = WebSocket::new(owner, url::from_str("ws://invalid/").unwrap());
+                socket.ready_state = Closed;
+                return socket;
+            }
+        };
+
+        let socket = WebSocket::new(owner, parsed.clone());
+        let origin = match owner.origin() {
+            Some(origin) => origin,
+            None => {
+                *rv = Err(Security);
+                socket.ready_state = Closed;
+                return socket;
+            }
+        };
+        // FIXME: Only a single subprotocol can be asked for, as the bindings can't take a
+        // sequence of them yet.
+        let protocols = match protocols {
+            Some(str(protocol)) => ~[protocol],
+            Some(null_string) | None => ~[],
+        };
+        if !protocols.iter().all(|protocol| is_valid_protocol(protocol.as_slice())) {
+            *rv = Err(Syntax);
+            socket.ready_state = Closed;
+            return socket;
+        }
+
+        // Forward what happens to the connection to the script task, which hands it back to us.
+        let (event_port, event_chan) = comm::stream();
+        socket.chan = Some(connect(parsed, protocols, origin, event_chan));
+        let script_chan = owner.script_chan.clone();
+        let pipeline_id = unsafe { (*owner.page).id.clone() };
+        let id = socket.id;
+        let event_port = Cell::new(event_port);
+        do spawn {
+            let event_port = event_port.take();
+            loop {
+                let event = event_port.recv();
+                let done = match event { Closed(*) => true, _ => false };
+                script_chan.send(WebSocketEventMsg(pipeline_id.clone(), id, event));
+                if done {
+                    break;
+                }
+            }
+        }
+
+        // FIXME: The window keeps every socket alive for as long as it lives, even once it's
+        // closed, since nothing tells us when the JS object is collected.
+        owner.add_websocket(socket);
+        socket
+    }
+
+    pub fn Url(&self) -> DOMString {
+        str(self.url.to_str())
+    }
+
+    pub fn ReadyState(&self) -> u16 {
+        self.ready_state as u16
+    }
+
+    pub fn BufferedAmount(&self) -> u32 {
+        // FIXME: Messages are handed to the connection's task straight away, so we don't know
+        // how much of them is still waiting to go out.
+        0
+    }
+
+    pub fn Onopen(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("open")
+    }
+
+    pub fn SetOnopen(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("open", handler);
+    }
+
+    pub fn Onerror(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("error")
+    }
+
+    pub fn SetOnerror(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("error", handler);
+    }
+
+    pub fn Onclose(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("close")
+    }
+
+    pub fn SetOnclose(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("close", handler);
+    }
+
+    pub fn Onmessage(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("message")
+    }
+
+    pub fn SetOnmessage(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("message", handler);
+    }
+
+    pub fn Extensions(&self) -> DOMString {
+        str(~"")
+    }
+
+    pub fn Protocol(&self) -> DOMString {
+        str(self.protocol.clone())
+    }
+
+    pub fn Close(&mut self, code: Option<u16>, reason: Option<DOMString>, rv: &mut ErrorResult) {
+        for &code in code.iter() {
+            if code != NORMAL_CLOSURE && (code < 3000 || code > 4999) {
+                *rv = Err(InvalidAccess);
+                return;
+            }
+        }
+        let reason = match reason {
+            Some(str(reason)) => reason,
+            Some(null_string) | None => ~"",
+        };
+        if reason.len() > MAX_REASON_LENGTH {
+            *rv = Err(Syntax);
+            return;
+        }
+
+        if self.ready_state == Closing || self.ready_state == Closed {
+            return;
+        }
+        // FIXME: A connection that isn't open yet should be failed straight away, rather than
+        // closed once the opening handshake is done.
+        for chan in self.chan.iter() {
+            chan.send(Close(code, reason.clone()));
+        }
+        self.ready_state = Closing;
+    }
+
+    pub fn Send(&mut self, data: &DOMString, rv: &mut ErrorResult) {
+        match self.ready_state {
+            Connecting => *rv = Err(InvalidState),
+            Open => {
+                for chan in self.chan.iter() {
+                    chan.send(SendText(data.to_str()));
+                }
+            }
+            // Messages sent once closing has started are dropped.
+            Closing | Closed => {}
+        }
+    }
+
+    pub fn AddEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                            _capture: bool) {
+        self.listeners.add(type_, listener);
+    }
+
+    pub fn RemoveEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                               _capture: bool) {
+        self.listeners.remove(type_, listener);
+    }
+}
+
+impl WebSocket {
+    /// Updates the socket with what happened to its connection, returning the events that are
+    /// now due.
+    pub fn process_event(&mut self, event: WebSocketEvent) -> ~[WebSocketDOMEvent] {
+        match event {
+            Opened(protocol) => {
+                // The socket may have been closed while the handshake was under way.
+                if self.ready_state != Connecting {
+                    return ~[];
+                }
+                self.ready_state = Open;
+                self.protocol = protocol.unwrap_or(~"");
+                ~[OpenEvent]
+            }
+            TextMessage(message) => {
+                if self.ready_state != Open {
+                    return ~[];
+                }
+                ~[TextMessageEvent(message)]
+            }
+            BinaryMessage(_) => {
+                // FIXME: There's no Blob or ArrayBuffer to hand binary messages to script as.
+                debug!("WebSocket: dropping a binary message");
+                ~[]
+            }
+            Failed => ~[ErrorEvent],
+            Closed(was_clean, code, reason) => {
+                self.ready_state = Closed;
+                self.chan = None;
+                ~[CloseEvent_(was_clean, code, reason)]
+            }
+        }
+    }
+
+    /// Fires the given events at the `on*` handlers and listeners for their types.
+    pub fn dispatch_events(@mut self, events: ~[WebSocketDOMEvent]) {
+        let cx = unsafe { (*self.window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let this = self.wrapper.get_wrapper();
+
+        for event in events.move_iter() {
+            let type_ = match event {
+                OpenEvent => ~"open",
+                TextMessageEvent(_) => ~"message",
+                ErrorEvent => ~"error",
+                CloseEvent_(*) => ~"close",
+            };
+            let type_ = str(type_);
+            let listeners = self.listeners.get(&type_);
+            if listeners.is_empty() {
+                loop;
+            }
+
+            let event_obj = match event {
+                TextMessageEvent(message) => {
+                    let data = unsafe { domstring_to_jsval(cx, &str(message)) };
+                    let origin = Origin::new(&self.url).to_str();
+                    let event = @mut MessageEvent::new(&type_, data, origin, None);
+                    event.parent.bubbles = false;
+                    event.parent.cancelable = false;
+                    event.parent.trusted = true;
+                    event.wrap_object_shared(cx, this)
+                }
+                CloseEvent_(was_clean, code, reason) => {
+                    let event = @mut CloseEvent::new(&type_, was_clean, code, reason);
+                    event.parent.bubbles = false;
+                    event.parent.cancelable = false;
+                    event.parent.trusted = true;
+                    event.wrap_object_shared(cx, this)
+                }
+                OpenEvent | ErrorEvent => {
+                    let event = @mut Event::new(&type_);
+                    event.bubbles = false;
+                    event.cancelable = false;
+                    event.trusted = true;
+                    event.wrap_object_shared(cx, this)
+                }
+            };
+            invoke_listeners(cx, this, listeners, event_obj);
+        }
+    }
+}
+
+/// Whether `protocol` can be asked for as a subprotocol: a token of printable ASCII characters
+/// other than the separators of HTTP headers.
+fn is_valid_protocol(protocol: &str) -> bool {
+    !protocol.is_empty() && do protocol.iter().all |c| {
+        c >= '!' && c <= '~' && !"()<>@,;:\\\"/[]?={}".contains_char(c)
+    }
+}
+
+impl CacheableWrapper for WebSocket {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        WebSocketBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for WebSocket {
+    fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        Some(self.window as @mut CacheableWrapper)
+    }
+}
+
+impl DerivedWrapper for WebSocket {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe {
+                *vp = RUST_OBJECT_TO_JSVAL(obj)
+            };
+            return 1;
+        }
+    }
+}
@@ -3,28 +3,64 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::codegen::WindowBinding;
-use dom::bindings::utils::{WrapperCache, DOMString, null_string};
+use dom::bindings::structuredclone;
+use dom::bindings::utils::{WrapperCache, DOMString, Error, ErrorResult, Syntax, null_string, str};
 use dom::bindings::utils::{CacheableWrapper, BindingObject};
 use dom::document::AbstractDocument;
+use dom::event::Event;
+use dom::eventtarget::{EventListeners, invoke_listeners};
+use dom::hashchangeevent::HashChangeEvent;
+use dom::history::History;
+use dom::htmlmediaelement::MediaId;
+use dom::location::Location;
+use dom::mediaquerylist::MediaQueryList;
+use dom::messageevent::MessageEvent;
+use dom::mutationobserver::{Mutation, MutationObserver};
 use dom::node::{AbstractNode, ScriptView};
-
-use layout_interface::ReflowForScriptQuery;
+use dom::navigator::Navigator;
+use dom::popstateevent::PopStateEvent;
+use dom::screen::Screen;
+use dom::selection::Selection;
+use dom::websocket::{WebSocket, WebSocketId};
+use dom::windowproxy::WindowProxy;
+use dom::worker::{Worker, WorkerId};
+use dom::xmlhttprequest::{XHRId, XMLHttpRequest};
+
+use layout_interface::{ContentBoxQuery, ContentBoxResponse, MatchSelectorsDocumentDamage};
+use media_task::{CloseMsg, MediaChan};
 use script_task::{ExitMsg, FireTimerMsg, Page, ScriptChan};
-use servo_msg::compositor_msg::ScriptListener;
+use servo_msg::compositor_msg::{ScriptListener, WindowMetrics};
+use servo_msg::constellation_msg::{ConstellationChan, ParentWindow, PipelineId, PipelineWindow};
+use servo_msg::constellation_msg::{PostMessageMsg, PushStateMsg, ReplaceStateMsg};
+use servo_msg::constellation_msg::{StructuredCloneData, TopWindow, WindowTarget};
 
 use js::glue::*;
 use js::jsapi::{JSObject, JSContext};
 use js::{JSVAL_NULL, JSPROP_ENUMERATE};
-
+use geom::point::Point2D;
+use geom::size::Size2D;
+use gfx::geometry::to_frac_px;
+use servo_util::media_queries::Device;
+use servo_util::time::ProfilerChan;
+use servo_util::tree::TreeNodeRef;
+use servo_util::url::Origin;
+
+use std::ascii::StrAsciiExt;
 use std::cast;
 use std::comm;
 use std::comm::SharedChan;
+use std::hashmap::HashMap;
 use std::io;
 use std::ptr;
 use std::int;
 use std::rt::rtio::RtioTimer;
 use std::rt::io::timer::Timer;
+use std::util::replace;
 use js::jsapi::JSVal;
+use servo_net::image_cache_task::ImageCacheTask;
+use servo_net::resource_task::ResourceTask;
+use extra::url;
+use extra::url::Url;
 
 pub enum TimerControlMsg {
     TimerMessage_Fire(~TimerData),
@@ -37,14 +73,77 @@ pub struct Window {
     page: *mut Page,
     script_chan: ScriptChan,
     compositor: @ScriptListener,
+    constellation_chan: ConstellationChan,
     wrapper: WrapperCache,
     timer_chan: SharedChan<TimerControlMsg>,
+    /// Callbacks registered with `requestAnimationFrame`, run on the next compositor tick.
+    animation_frame_callbacks: ~[(i32, JSVal)],
+    next_animation_frame_handle: i32,
+    /// What has scrolled since the last animation frame: elements, or `None` for the viewport.
+    /// Each gets a single `scroll` event on the next tick, however often it scrolled.
+    scroll_event_targets: ~[Option<AbstractNode<ScriptView>>],
+    /// Whether the window has been resized since the last animation frame, so that it's sent a
+    /// `resize` event on the next tick.
+    resize_event_pending: bool,
+    /// The most recent metrics of the native window, as reported by the compositor.
+    metrics: WindowMetrics,
+    /// The `window.screen` object, created on first access.
+    screen: Option<@mut Screen>,
+    /// The `window.getSelection()` object, created on first access.
+    selection: Option<@mut Selection>,
+    /// The `window.history` object, created on first access.
+    history: Option<@mut History>,
+    /// The `window.location` object, created on first access.
+    location: Option<@mut Location>,
+    /// The `window.navigator` object, created on first access.
+    navigator: Option<@mut Navigator>,
+    /// The user agent string, for `navigator.userAgent`.
+    user_agent: ~str,
+    /// The state objects of the document's session history entries, and the index of the one
+    /// it's at. The constellation keeps the rest of each entry.
+    history_states: ~[JSVal],
+    history_index: uint,
+    listeners: EventListeners,
+    resource_task: ResourceTask,
+    /// For the tasks of canvases to load the images drawn on them, and report to the profiler.
+    image_cache_task: ImageCacheTask,
+    profiler_chan: ProfilerChan,
+    /// The `XMLHttpRequest`s created in this window, so that their progress messages can be
+    /// routed back to them.
+    xhrs: HashMap<XHRId, @mut XMLHttpRequest>,
+    next_xhr_id: uint,
+    /// The `WebSocket`s created in this window, so that what happens to their connections can
+    /// be routed back to them.
+    websockets: HashMap<WebSocketId, @mut WebSocket>,
+    next_websocket_id: uint,
+    /// The `Worker`s started in this window, so that the messages they post can be routed back
+    /// to them.
+    workers: HashMap<WorkerId, @mut Worker>,
+    next_worker_id: uint,
+    /// The media elements playing a source in this window, by the id of the source, and the
+    /// channels to the tasks playing them.
+    media: HashMap<MediaId, (AbstractNode<ScriptView>, MediaChan)>,
+    next_media_id: uint,
+    /// The `MutationObserver`s created in this window.
+    mutation_observers: ~[@mut MutationObserver],
+    /// The lists returned by `matchMedia()`, to be evaluated again when the window is resized.
+    ///
+    /// FIXME: These live as long as the window does, whether script still has them or not.
+    media_query_lists: ~[@mut MediaQueryList],
 }
 
 #[unsafe_destructor]
 impl Drop for Window {
     fn drop(&self) {
         self.timer_chan.send(TimerMessage_Close);
+        // Workers don't outlive the document that started them.
+        for (_, &worker) in self.workers.iter() {
+            worker.Terminate();
+        }
+        // Nor does media.
+        for (_, &(_, ref chan)) in self.media.iter() {
+            chan.send(CloseMsg);
+        }
     }
 }
 
@@ -121,6 +220,163 @@ impl Window {
     pub fn NamedGetter(&self, _cx: *JSContext, _name: &DOMString, _found: &mut bool) -> *JSObject {
         ptr::null()
     }
+
+    pub fn AddEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                            _capture: bool) {
+        self.listeners.add(type_, listener);
+    }
+
+    pub fn RemoveEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                               _capture: bool) {
+        self.listeners.remove(type_, listener);
+    }
+
+    pub fn Navigator(&mut self) -> @mut Navigator {
+        match self.navigator {
+            Some(navigator) => navigator,
+            None => {
+                let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+                let scope = self.wrapper.get_wrapper();
+                let navigator = Navigator::new(self.user_agent.clone(), cx, scope);
+                self.navigator = Some(navigator);
+                navigator
+            }
+        }
+    }
+
+    pub fn Screen(&mut self) -> @mut Screen {
+        match self.screen {
+            Some(screen) => screen,
+            None => {
+                let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+                let scope = self.wrapper.get_wrapper();
+                let screen = Screen::new(self.metrics.clone(), cx, scope);
+                self.screen = Some(screen);
+                screen
+            }
+        }
+    }
+
+    pub fn GetSelection(&mut self) -> @mut Selection {
+        match self.selection {
+            Some(selection) => selection,
+            None => {
+                let (window, cx) = unsafe {
+                    ((*self.page).frame.get_ref().window,
+                     (*self.page).js_info.get_ref().js_compartment.cx.ptr)
+                };
+                let scope = self.wrapper.get_wrapper();
+                let selection = Selection::new(window, cx, scope);
+                self.selection = Some(selection);
+                selection
+            }
+        }
+    }
+
+    pub fn History(&mut self) -> @mut History {
+        match self.history {
+            Some(history) => history,
+            None => {
+                let (window, cx) = unsafe {
+                    ((*self.page).frame.get_ref().window,
+                     (*self.page).js_info.get_ref().js_compartment.cx.ptr)
+                };
+                let scope = self.wrapper.get_wrapper();
+                let history = History::new(window, cx, scope);
+                self.history = Some(history);
+                history
+            }
+        }
+    }
+
+    pub fn Location(&mut self) -> @mut Location {
+        match self.location {
+            Some(location) => location,
+            None => {
+                let (window, cx) = unsafe {
+                    ((*self.page).frame.get_ref().window,
+                     (*self.page).js_info.get_ref().js_compartment.cx.ptr)
+                };
+                let scope = self.wrapper.get_wrapper();
+                let location = Location::new(window, cx, scope);
+                self.location = Some(location);
+                location
+            }
+        }
+    }
+
+    pub fn Parent(&self) -> @mut WindowProxy {
+        self.proxy(ParentWindow)
+    }
+
+    pub fn Top(&self) -> @mut WindowProxy {
+        self.proxy(TopWindow)
+    }
+
+    pub fn PostMessage(&self, cx: *JSContext, message: JSVal, target_origin: &DOMString,
+                       rv: &mut ErrorResult) {
+        let id = unsafe { (*self.page).id };
+        match self.post_message(cx, PipelineWindow(id), message, target_origin) {
+            Ok(()) => {}
+            Err(error) => *rv = Err(error),
+        }
+    }
+
+    pub fn InnerWidth(&self) -> i32 {
+        unsafe { (*self.page).window_size.get().width as i32 }
+    }
+
+    pub fn InnerHeight(&self) -> i32 {
+        unsafe { (*self.page).window_size.get().height as i32 }
+    }
+
+    pub fn OuterWidth(&self) -> i32 {
+        (self.metrics.window_size.width as f32 / self.metrics.device_pixel_ratio) as i32
+    }
+
+    pub fn OuterHeight(&self) -> i32 {
+        (self.metrics.window_size.height as f32 / self.metrics.device_pixel_ratio) as i32
+    }
+
+    pub fn DevicePixelRatio(&self) -> f64 {
+        self.metrics.device_pixel_ratio as f64
+    }
+
+    pub fn ScrollX(&self) -> f64 {
+        unsafe { (*self.page).scroll_offset.x as f64 }
+    }
+
+    pub fn ScrollY(&self) -> f64 {
+        unsafe { (*self.page).scroll_offset.y as f64 }
+    }
+
+    pub fn PageXOffset(&self) -> f64 {
+        self.ScrollX()
+    }
+
+    pub fn PageYOffset(&self) -> f64 {
+        self.ScrollY()
+    }
+
+    pub fn ScrollTo(&mut self, x: f64, y: f64) {
+        self.scroll_viewport_to(Point2D(x as f32, y as f32));
+    }
+
+    pub fn ScrollBy(&mut self, x: f64, y: f64) {
+        let offset = unsafe { (*self.page).scroll_offset };
+        self.scroll_viewport_to(Point2D(offset.x + x as f32, offset.y + y as f32));
+    }
+
+    pub fn MatchMedia(&mut self, query: &DOMString) -> @mut MediaQueryList {
+        let (window, cx) = unsafe {
+            ((*self.page).frame.get_ref().window,
+             (*self.page).js_info.get_ref().js_compartment.cx.ptr)
+        };
+        let scope = self.wrapper.get_wrapper();
+        let list = MediaQueryList::new(window, query.to_str(), &self.device(), cx, scope);
+        self.media_query_lists.push(list);
+        list
+    }
 }
 
 impl CacheableWrapper for Window {
@@ -141,8 +397,8 @@ impl BindingObject for Window {
 }
 
 impl Window {
-    pub fn SetTimeout(&self, _cx: *JSContext, callback: JSVal, timeout: i32) -> i32 {
-        let timeout = int::max(0, timeout) as u64;
+    pub fn SetTimeout(&self, _cx: *JSContext, callback: JSVal, timeout: Option<i32>) -> i32 {
+        let timeout = int::max(0, timeout.unwrap_or(0)) as u64;
 
         // Post a delayed message to the per-window timer task; it will dispatch it
         // to the relevant script handler that will deal with it.
@@ -158,19 +414,524 @@ impl Window {
         return 0; //TODO return handle into list of active timers
     }
 
+    pub fn RequestAnimationFrame(&mut self, _cx: *JSContext, callback: JSVal) -> i32 {
+        self.request_animation_frame_tick();
+        self.next_animation_frame_handle += 1;
+        let handle = self.next_animation_frame_handle;
+        self.animation_frame_callbacks.push((handle, callback));
+        handle
+    }
+
+    pub fn CancelAnimationFrame(&mut self, handle: i32) {
+        let position = self.animation_frame_callbacks.iter().position(|&(h, _)| h == handle);
+        for &index in position.iter() {
+            self.animation_frame_callbacks.remove(index);
+        }
+    }
+
+    /// Asks the compositor to scroll the viewport to `point`, kept within the document. The
+    /// compositor scrolls asynchronously, so the new position is recorded here straight away for
+    /// `scrollX` and `scrollY` to report.
+    fn scroll_viewport_to(&mut self, point: Point2D<f32>) {
+        let max = self.max_scroll_offset();
+        let point = Point2D(point.x.min(&max.x).max(&0.0), point.y.min(&max.y).max(&0.0));
+        unsafe {
+            if (*self.page).scroll_offset == point {
+                return
+            }
+            (*self.page).scroll_offset = point;
+            self.compositor.scroll_fragment_point((*self.page).id, point);
+        }
+        self.queue_scroll_event(None);
+    }
+
+    /// How far the viewport can scroll before it runs off the end of the document.
+    fn max_scroll_offset(&self) -> Point2D<f32> {
+        let root = self.Document().with_base(|doc| doc.root);
+        match unsafe { (*self.page).query_layout(|chan| ContentBoxQuery(root, chan)) } {
+            Ok(ContentBoxResponse(rect)) => {
+                let window_size = unsafe { (*self.page).window_size.get() };
+                let width = to_frac_px(rect.origin.x + rect.size.width) as f32;
+                let height = to_frac_px(rect.origin.y + rect.size.height) as f32;
+                Point2D((width - window_size.width as f32).max(&0.0),
+                        (height - window_size.height as f32).max(&0.0))
+            }
+            Err(()) => Point2D(0f32, 0f32),
+        }
+    }
+
+    /// Notes that `target`, an element or `None` for the viewport, has scrolled, so that it's
+    /// sent a `scroll` event on the next animation frame.
+    pub fn queue_scroll_event(&mut self, target: Option<AbstractNode<ScriptView>>) {
+        if self.scroll_event_targets.contains(&target) {
+            return
+        }
+        self.request_animation_frame_tick();
+        self.scroll_event_targets.push(target);
+    }
+
+    /// Notes that the window has been resized, so that it's sent a `resize` event on the next
+    /// animation frame.
+    pub fn queue_resize_event(&mut self) {
+        if !self.resize_event_pending {
+            self.request_animation_frame_tick();
+            self.resize_event_pending = true;
+        }
+    }
+
+    /// Asks the compositor for an animation frame tick, unless one has been asked for already.
+    /// Callbacks and events that come in before it's sent are batched along with the first.
+    fn request_animation_frame_tick(&self) {
+        if self.animation_frame_callbacks.is_empty() && self.scroll_event_targets.is_empty() &&
+                !self.resize_event_pending {
+            unsafe {
+                self.compositor.request_animation_frame((*self.page).id.clone());
+            }
+        }
+    }
+
+    /// Sends the window a `resize` event if it's been resized since the last animation frame,
+    /// however many times that was. Returns whether it had been.
+    pub fn fire_resize_event(@mut self) -> bool {
+        if !self.resize_event_pending {
+            return false
+        }
+        self.resize_event_pending = false;
+        let event = @mut Event::new(&str(~"resize"));
+        event.bubbles = false;
+        event.cancelable = false;
+        event.trusted = true;
+        self.dispatch_event(event);
+        true
+    }
+
+    /// Sends a `scroll` event to everything that has scrolled since the last animation frame.
+    /// Returns whether there was anything to send one to.
+    pub fn fire_scroll_events(@mut self) -> bool {
+        let targets = replace(&mut self.scroll_event_targets, ~[]);
+        for &target in targets.iter() {
+            let event = @mut Event::new(&str(~"scroll"));
+            event.bubbles = false;
+            event.cancelable = false;
+            event.trusted = true;
+            match target {
+                None => self.dispatch_event(event),
+                Some(element) => {
+                    let listeners = element.with_imm_element(|element| {
+                        element.listeners.get(&event.type_)
+                    });
+                    if listeners.is_empty() {
+                        loop
+                    }
+                    // Listeners can only have been added from script, so the element has a
+                    // wrapper by now.
+                    let this = element.with_base(|node| node.wrapper.get_wrapper());
+                    let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+                    let event_obj = event.wrap_object_shared(cx, self.wrapper.get_wrapper());
+                    invoke_listeners(cx, this, listeners, event_obj);
+                }
+            }
+        }
+        !targets.is_empty()
+    }
+
+    /// Takes the list of pending animation frame callbacks. Callbacks registered while these
+    /// run will be deferred until the following tick, as the spec requires.
+    pub fn take_animation_frame_callbacks(&mut self) -> ~[(i32, JSVal)] {
+        replace(&mut self.animation_frame_callbacks, ~[])
+    }
+
+    /// Asks the compositor for the current window and screen metrics, e.g. after a resize.
+    pub fn update_metrics(&mut self) {
+        self.metrics = self.compositor.get_window_metrics();
+        for screen in self.screen.iter() {
+            screen.metrics = self.metrics.clone();
+        }
+    }
+
+    /// What media queries are evaluated against: the viewport of this window, on the screen.
+    pub fn device(&self) -> Device {
+        let size = unsafe { (*self.page).window_size.get() };
+        Device::screen(Size2D(size.width as f32, size.height as f32),
+                       self.metrics.device_pixel_ratio)
+    }
+
+    /// Evaluates the lists returned by `matchMedia()` again, calling the listeners of those that
+    /// started or stopped matching.
+    pub fn evaluate_media_query_lists(&self) {
+        let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+        let device = self.device();
+        // Listeners can call `matchMedia()`; lists created by them are already up to date.
+        let lists = self.media_query_lists.clone();
+        for &list in lists.iter() {
+            list.evaluate(cx, &device);
+        }
+    }
+
+    /// Dispatches an event to the listeners registered on this window for its type.
+    pub fn dispatch_event(@mut self, event: @mut Event) {
+        let listeners = self.listeners.get(&event.type_);
+        if listeners.is_empty() {
+            return;
+        }
+        let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+        let this = self.wrapper.get_wrapper();
+        let event_obj = event.wrap_object_shared(cx, this);
+        invoke_listeners(cx, this, listeners, event_obj);
+    }
+
+    /// The URL of the document loaded in this window, if there is one yet.
+    pub fn url(&self) -> Option<Url> {
+        unsafe {
+            match (*self.page).url {
+                Some((ref url, _)) => Some(url.clone()),
+                None => None,
+            }
+        }
+    }
+
+    /// The origin of the document loaded in this window, if there is one yet.
+    pub fn origin(&self) -> Option<Origin> {
+        self.url().map(|url| Origin::new(url))
+    }
+
+    /// Adds a session history entry for the document after the one it's at, dropping those that
+    /// followed, and moves to it.
+    pub fn push_state(&mut self, state: JSVal, url: Url) {
+        self.history_states.truncate(self.history_index + 1);
+        self.history_states.push(state);
+        self.history_index += 1;
+        self.set_url(url.clone());
+        unsafe {
+            self.constellation_chan.send(PushStateMsg((*self.page).id.clone(), url));
+        }
+    }
+
+    /// Changes the state object and URL of the session history entry the document is at.
+    pub fn replace_state(&mut self, state: JSVal, url: Url) {
+        self.history_states[self.history_index] = state;
+        self.set_url(url.clone());
+        unsafe {
+            self.constellation_chan.send(ReplaceStateMsg((*self.page).id.clone(), url));
+        }
+    }
+
+    /// The state object of the session history entry the document is at.
+    pub fn history_state(&self) -> JSVal {
+        self.history_states[self.history_index]
+    }
+
+    /// Moves the document to another of its session history entries, as the user traversing the
+    /// session history did, and fires `popstate` with the entry's state object. If that changes
+    /// the fragment, it scrolls there and fires `hashchange` too.
+    pub fn pop_state(@mut self, index: uint, url: Url) {
+        let old_url = self.url();
+        // A document loaded again has lost the states of the entries it had.
+        while self.history_states.len() <= index {
+            self.history_states.push(JSVAL_NULL);
+        }
+        self.history_index = index;
+        self.set_url(url.clone());
+
+        let type_ = str(~"popstate");
+        let listeners = self.listeners.get(&type_);
+        if !listeners.is_empty() {
+            let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+            let this = self.wrapper.get_wrapper();
+            let event = @mut PopStateEvent::new(&type_, self.history_state());
+            event.parent.bubbles = false;
+            event.parent.cancelable = false;
+            event.parent.trusted = true;
+            let event_obj = event.wrap_object_shared(cx, this);
+            invoke_listeners(cx, this, listeners, event_obj);
+        }
+
+        match old_url {
+            Some(old_url) if old_url.fragment != url.fragment => {
+                self.scroll_to_fragment(fragment(&url).as_slice());
+                self.fire_hash_change(&old_url, &url);
+            }
+            _ => {}
+        }
+    }
+
+    /// Navigates to `url`, which only differs from the document's URL in its fragment: adds a
+    /// session history entry for it, scrolls to the part of the document it names and fires
+    /// `hashchange`. Navigating to the URL the document is at just scrolls again.
+    pub fn navigate_to_fragment(@mut self, url: Url) {
+        let old_url = match self.url() {
+            Some(old_url) => old_url,
+            None => return,
+        };
+        if url != old_url {
+            self.push_state(JSVAL_NULL, url.clone());
+        }
+        self.scroll_to_fragment(fragment(&url).as_slice());
+        if url.fragment != old_url.fragment {
+            self.fire_hash_change(&old_url, &url);
+        }
+    }
+
+    /// Asks the compositor to scroll to the part of the document `fragment` names: the element
+    /// with that ID, or else the `a` element with that name. An empty fragment, or `top`, names
+    /// the top of the document. Fragments that name nothing leave the scroll position alone.
+    pub fn scroll_to_fragment(&self, fragment: &str) {
+        let point = if fragment.is_empty() || "top" == fragment.to_ascii_lower() {
+            Point2D(0f32, 0f32)
+        } else {
+            let target = match self.fragment_target(fragment) {
+                Some(target) => target,
+                None => {
+                    debug!("Window: no element for the fragment %s", fragment);
+                    return
+                }
+            };
+            match unsafe { (*self.page).query_layout(|chan| ContentBoxQuery(target, chan)) } {
+                Ok(ContentBoxResponse(rect)) => {
+                    Point2D(to_frac_px(rect.origin.x) as f32, to_frac_px(rect.origin.y) as f32)
+                }
+                Err(()) => {
+                    debug!("Window: the element for the fragment %s isn't laid out", fragment);
+                    return
+                }
+            }
+        };
+        unsafe {
+            self.compositor.scroll_fragment_point((*self.page).id, point);
+        }
+    }
+
+    /// The element `fragment` names, in tree order: one with that ID, or else an `a` element with
+    /// that name.
+    fn fragment_target(&self, fragment: &str) -> Option<AbstractNode<ScriptView>> {
+        let root = self.Document().with_base(|doc| doc.root);
+        let mut named_anchor = None;
+        for node in root.traverse_preorder() {
+            if !node.is_element() {
+                loop
+            }
+            let (has_id, has_name) = do node.with_imm_element |element| {
+                (element.get_attr("id") == Some(fragment),
+                 "a" == element.tag_name && element.get_attr("name") == Some(fragment))
+            };
+            if has_id {
+                return Some(node)
+            }
+            if has_name && named_anchor.is_none() {
+                named_anchor = Some(node);
+            }
+        }
+        named_anchor
+    }
+
+    /// Fires `hashchange` at the window, for the fragment of its document's URL changing.
+    fn fire_hash_change(@mut self, old_url: &Url, new_url: &Url) {
+        let type_ = str(~"hashchange");
+        let listeners = self.listeners.get(&type_);
+        if listeners.is_empty() {
+            return;
+        }
+        let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+        let this = self.wrapper.get_wrapper();
+        let event = @mut HashChangeEvent::new(&type_, old_url.to_str(), new_url.to_str());
+        event.parent.bubbles = false;
+        event.parent.cancelable = false;
+        event.parent.trusted = true;
+        let event_obj = event.wrap_object_shared(cx, this);
+        invoke_listeners(cx, this, listeners, event_obj);
+    }
+
+    /// A proxy for the window `target` names, relative to this one.
+    pub fn proxy(&self, target: WindowTarget) -> @mut WindowProxy {
+        let (window, cx) = unsafe {
+            ((*self.page).frame.get_ref().window,
+             (*self.page).js_info.get_ref().js_compartment.cx.ptr)
+        };
+        WindowProxy::new(window, target, cx, self.wrapper.get_wrapper())
+    }
+
+    /// Copies `message` and asks the constellation to deliver it to the window `target` names.
+    /// The target only gets it if its document's origin is `target_origin`; `*` matches any
+    /// origin and `/` this document's.
+    pub fn post_message(&self, cx: *JSContext, target: WindowTarget, message: JSVal,
+                        target_origin: &DOMString) -> Result<(), Error> {
+        let origin = match self.origin() {
+            Some(origin) => origin.to_str(),
+            None => return Err(Syntax),
+        };
+        let target_origin = target_origin.to_str();
+        let target_origin = if "*" == target_origin {
+            None
+        } else if "/" == target_origin {
+            Some(origin.clone())
+        } else {
+            match url::from_str(target_origin) {
+                Ok(url) => Some(Origin::new(&url).to_str()),
+                Err(_) => return Err(Syntax),
+            }
+        };
+        let data = match structuredclone::write(cx, message) {
+            Ok(data) => data,
+            Err(error) => return Err(error),
+        };
+        let id = unsafe { (*self.page).id };
+        self.constellation_chan.send(PostMessageMsg(id, target, data, origin, target_origin));
+        Ok(())
+    }
+
+    /// Fires `message` at the window, for a message the window of the pipeline `source`, whose
+    /// document's origin is `origin`, posted to it. Messages meant for another origin are
+    /// dropped, since the document here may have changed since they were posted.
+    pub fn receive_message(@mut self, source: PipelineId, origin: ~str,
+                           target_origin: Option<~str>, data: StructuredCloneData) {
+        let own_origin = self.origin().map(|origin| origin.to_str());
+        if target_origin.is_some() && target_origin != own_origin {
+            debug!("Window: dropping a message for %s", target_origin.unwrap());
+            return
+        }
+
+        let type_ = str(~"message");
+        let listeners = self.listeners.get(&type_);
+        if listeners.is_empty() {
+            return;
+        }
+        let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+        let this = self.wrapper.get_wrapper();
+        let data = structuredclone::read(cx, &data);
+        let source = self.proxy(PipelineWindow(source));
+        let event = @mut MessageEvent::new(&type_, data, origin, Some(source));
+        event.parent.bubbles = false;
+        event.parent.cancelable = false;
+        event.parent.trusted = true;
+        let event_obj = event.wrap_object_shared(cx, this);
+        invoke_listeners(cx, this, listeners, event_obj);
+    }
+
+    /// Changes the URL of the document without loading anything.
+    fn set_url(&self, url: Url) {
+        unsafe {
+            let needs_reflow = (*self.page).url.map_default(false, |&(_, needs_reflow)| needs_reflow);
+            (*self.page).url = Some((url, needs_reflow));
+        }
+    }
+
+    pub fn next_xhr_id(&mut self) -> XHRId {
+        self.next_xhr_id += 1;
+        XHRId(self.next_xhr_id)
+    }
+
+    pub fn add_xhr(&mut self, xhr: @mut XMLHttpRequest) {
+        self.xhrs.insert(xhr.id, xhr);
+    }
+
+    pub fn find_xhr(&self, id: XHRId) -> Option<@mut XMLHttpRequest> {
+        self.xhrs.find(&id).map(|&xhr| xhr)
+    }
+
+    pub fn next_websocket_id(&mut self) -> WebSocketId {
+        self.next_websocket_id += 1;
+        WebSocketId(self.next_websocket_id)
+    }
+
+    pub fn add_websocket(&mut self, socket: @mut WebSocket) {
+        self.websockets.insert(socket.id, socket);
+    }
+
+    pub fn find_websocket(&self, id: WebSocketId) -> Option<@mut WebSocket> {
+        self.websockets.find(&id).map(|&socket| socket)
+    }
+
+    pub fn next_worker_id(&mut self) -> WorkerId {
+        self.next_worker_id += 1;
+        WorkerId(self.next_worker_id)
+    }
+
+    pub fn add_worker(&mut self, worker: @mut Worker) {
+        self.workers.insert(worker.id, worker);
+    }
+
+    pub fn find_worker(&self, id: WorkerId) -> Option<@mut Worker> {
+        self.workers.find(&id).map(|&worker| worker)
+    }
+
+    pub fn next_media_id(&mut self) -> MediaId {
+        self.next_media_id += 1;
+        MediaId(self.next_media_id)
+    }
+
+    pub fn add_media(&mut self, id: MediaId, element: AbstractNode<ScriptView>, chan: MediaChan) {
+        self.media.insert(id, (element, chan));
+    }
+
+    pub fn find_media(&self, id: MediaId) -> Option<AbstractNode<ScriptView>> {
+        self.media.find(&id).map(|&(element, _)| element)
+    }
+
+    /// Stops the task playing the source with the given id, and forgets about it.
+    pub fn remove_media(&mut self, id: MediaId) {
+        match self.media.pop(&id) {
+            Some((_, chan)) => chan.send(CloseMsg),
+            None => {}
+        }
+    }
+
+    /// Queues records of a change to `target` for the mutation observers that asked about it.
+    pub fn queue_mutation_records(&self, target: AbstractNode<ScriptView>, mutation: &Mutation) {
+        for &observer in self.mutation_observers.iter() {
+            observer.queue_record(target, mutation);
+        }
+    }
+
+    /// Calls back the mutation observers that have records queued. Callbacks can change the tree
+    /// again, so this goes on until no records are left.
+    pub fn notify_mutation_observers(&self) {
+        let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+        loop {
+            let observers: ~[@mut MutationObserver] = self.mutation_observers.iter()
+                .filter(|observer| observer.has_records())
+                .map(|&observer| observer)
+                .collect();
+            if observers.is_empty() {
+                break
+            }
+            for &observer in observers.iter() {
+                observer.notify(cx);
+            }
+        }
+    }
+
+    /// Notes that script changed the document. Layout hears about it at the end of the turn, or
+    /// before the next layout query, so that a run of changes costs a single reflow.
     pub fn content_changed(&self) {
         unsafe {
-            (*self.page).reflow_all(ReflowForScriptQuery, self.script_chan.clone(), self.compositor);
+            (*self.page).damage_all();
         }
     }
 
-    pub fn new(page: *mut Page, script_chan: ScriptChan, compositor: @ScriptListener)
+    /// Notes that the styles of the subtree at `root` may have changed, without anything else in
+    /// the document changing, as when an element's `:hover` state does. Layout only matches
+    /// selectors again for that subtree.
+    pub fn restyle_subtree(&self, root: AbstractNode<ScriptView>) {
+        unsafe {
+            (*self.page).damage_subtree(root, MatchSelectorsDocumentDamage);
+        }
+    }
+
+    pub fn new(page: *mut Page,
+               script_chan: ScriptChan,
+               compositor: @ScriptListener,
+               constellation_chan: ConstellationChan,
+               resource_task: ResourceTask,
+               image_cache_task: ImageCacheTask,
+               profiler_chan: ProfilerChan,
+               user_agent: ~str)
                -> @mut Window {
         let script_chan_clone = script_chan.clone();
         let win = @mut Window {
             page: page,
             script_chan: script_chan,
             compositor: compositor,
+            constellation_chan: constellation_chan,
             wrapper: WrapperCache::new(),
             timer_chan: {
                 let (timer_port, timer_chan) = comm::stream::<TimerControlMsg>();
@@ -185,6 +946,33 @@ impl Window {
                 }
                 SharedChan::new(timer_chan)
             },
+            animation_frame_callbacks: ~[],
+            next_animation_frame_handle: 0,
+            scroll_event_targets: ~[],
+            resize_event_pending: false,
+            metrics: compositor.get_window_metrics(),
+            screen: None,
+            selection: None,
+            history: None,
+            location: None,
+            navigator: None,
+            user_agent: user_agent,
+            history_states: ~[JSVAL_NULL],
+            history_index: 0,
+            listeners: EventListeners::new(),
+            resource_task: resource_task,
+            image_cache_task: image_cache_task,
+            profiler_chan: profiler_chan,
+            xhrs: HashMap::new(),
+            next_xhr_id: 0,
+            websockets: HashMap::new(),
+            next_websocket_id: 0,
+            workers: HashMap::new(),
+            next_worker_id: 0,
+            media: HashMap::new(),
+            next_media_id: 0,
+            mutation_observers: ~[],
+            media_query_lists: ~[],
         };
 
         unsafe {
@@ -202,3 +990,10 @@ impl Window {
     }
 }
 
+/// The fragment of `url`, without the `#`.
+fn fragment(url: &Url) -> ~str {
+    match url.fragment {
+        Some(ref fragment) => fragment.clone(),
+        None => ~"",
+    }
+}
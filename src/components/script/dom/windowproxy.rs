@@ -2,25 +2,53 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject};
+//! DOM bindings for `WindowProxy`, another window as script sees it: the window in a frame, the
+//! window a frame is in, or the one a message came from. It can be in another script task, so
+//! all script can do with it is post messages to it.
+
+use dom::bindings::codegen::WindowProxyBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, ErrorResult};
+use dom::window::Window;
 use script_task::page_from_context;
 
-use js::jsapi::{JSContext, JSObject};
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSContext, JSObject, JSVal};
+use servo_msg::constellation_msg::WindowTarget;
+
+use std::cast;
 
 pub struct WindowProxy {
-    wrapper: WrapperCache
+    wrapper: WrapperCache,
+    /// The window of the script that has this proxy.
+    window: @mut Window,
+    /// The window this is a proxy for, as the constellation finds it from `window`'s pipeline.
+    target: WindowTarget,
 }
 
 impl WindowProxy {
-    pub fn new() -> @mut WindowProxy {
-        @mut WindowProxy {
-            wrapper: WrapperCache::new()
-        }
+    pub fn new(window: @mut Window, target: WindowTarget, cx: *JSContext, scope: *JSObject)
+               -> @mut WindowProxy {
+        let proxy = @mut WindowProxy {
+            wrapper: WrapperCache::new(),
+            window: window,
+            target: target,
+        };
+        proxy.init_wrapper(cx, scope);
+        proxy
     }
 
     pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
         self.wrap_object_shared(cx, scope);
     }
+
+    pub fn PostMessage(&self, cx: *JSContext, message: JSVal, target_origin: &DOMString,
+                       rv: &mut ErrorResult) {
+        match self.window.post_message(cx, self.target.clone(), message, target_origin) {
+            Ok(()) => {}
+            Err(error) => *rv = Err(error),
+        }
+    }
 }
 
 impl BindingObject for WindowProxy {
@@ -34,10 +62,29 @@ impl BindingObject for WindowProxy {
 
 impl CacheableWrapper for WindowProxy {
     fn get_wrappercache(&mut self) -> &mut WrapperCache {
-        return self.get_wrappercache()
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        WindowProxyBinding::Wrap(cx, scope, self, &mut unused)
     }
+}
 
-    fn wrap_object_shared(@mut self, _cx: *JSContext, _scope: *JSObject) -> *JSObject {
-        fail!("not yet implemented")
+impl DerivedWrapper for WindowProxy {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe { *vp = RUST_OBJECT_TO_JSVAL(obj) };
+            return 1;
+        }
     }
 }
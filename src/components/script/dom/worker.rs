@@ -0,0 +1,209 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! DOM bindings for `Worker`, the handle a document has on a dedicated worker. The worker runs
+//! its script in a task of its own; see `worker_task`.
+
+use dom::bindings::codegen::WorkerBinding;
+use dom::bindings::structuredclone;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, ErrorResult, Security, str};
+use dom::event::Event;
+use dom::eventtarget::{EventListeners, invoke_listeners};
+use dom::messageevent::MessageEvent;
+use dom::window::Window;
+use worker_task::{PostMessageToWorkerMsg, TerminateMsg, WorkerChan, WorkerTask};
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+use servo_msg::constellation_msg::StructuredCloneData;
+use servo_util::url::make_url;
+
+use std::cast;
+
+/// Identifies a `Worker` within its window, so that messages coming back from the worker can
+/// find it.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct WorkerId(uint);
+
+pub struct Worker {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    id: WorkerId,
+    /// Where to send messages for the worker. `None` once it has been terminated, or if it was
+    /// never started.
+    chan: Option<WorkerChan>,
+    listeners: EventListeners,
+}
+
+impl Worker {
+    pub fn new(window: @mut Window) -> @mut Worker {
+        let worker = @mut Worker {
+            wrapper: WrapperCache::new(),
+            window: window,
+            id: window.next_worker_id(),
+            chan: None,
+            listeners: EventListeners::new(),
+        };
+
+        // TODO(tkuehn): This just handles the top-level page. Need to handle subframes.
+        let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = window.get_wrappercache().get_wrapper();
+        worker.wrap_object_shared(cx, scope);
+        worker
+    }
+
+    pub fn Constructor(owner: @mut Window, script_url: &DOMString, rv: &mut ErrorResult)
+                       -> @mut Worker {
+        let worker = Worker::new(owner);
+        let url = make_url(script_url.to_str(), owner.url());
+        // Workers run with the origin of the document that starts them, so they can only run
+        // scripts from it.
+        match owner.origin() {
+            Some(ref origin) if origin.is_same_origin(&url) => {}
+            _ => {
+                *rv = Err(Security);
+                return worker;
+            }
+        }
+
+        let pipeline_id = unsafe { (*owner.page).id.clone() };
+        worker.chan = Some(WorkerTask::create(url,
+                                              owner.script_chan.clone(),
+                                              pipeline_id,
+                                              worker.id,
+                                              owner.resource_task.clone()));
+        owner.add_worker(worker);
+        worker
+    }
+
+    pub fn Terminate(&mut self) {
+        for chan in self.chan.iter() {
+            chan.send(TerminateMsg);
+        }
+        self.chan = None;
+    }
+
+    pub fn PostMessage(&self, cx: *JSContext, message: JSVal, rv: &mut ErrorResult) {
+        let data = match structuredclone::write(cx, message) {
+            Ok(data) => data,
+            Err(error) => {
+                *rv = Err(error);
+                return;
+            }
+        };
+        // Messages to a terminated worker are dropped.
+        for chan in self.chan.iter() {
+            chan.send(PostMessageToWorkerMsg(data.clone()));
+        }
+    }
+
+    pub fn Onmessage(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("message")
+    }
+
+    pub fn SetOnmessage(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("message", handler);
+    }
+
+    pub fn Onerror(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("error")
+    }
+
+    pub fn SetOnerror(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("error", handler);
+    }
+
+    pub fn AddEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                            _capture: bool) {
+        self.listeners.add(type_, listener);
+    }
+
+    pub fn RemoveEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                               _capture: bool) {
+        self.listeners.remove(type_, listener);
+    }
+}
+
+impl Worker {
+    /// Fires a `message` event for a message the worker posted.
+    pub fn receive_message(@mut self, data: StructuredCloneData) {
+        if self.chan.is_none() {
+            return;
+        }
+        let type_ = str(~"message");
+        let listeners = self.listeners.get(&type_);
+        if listeners.is_empty() {
+            return;
+        }
+        let cx = unsafe { (*self.window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let this = self.wrapper.get_wrapper();
+        let data = structuredclone::read(cx, &data);
+        let event = @mut MessageEvent::new(&type_, data, ~"", None);
+        event.parent.bubbles = false;
+        event.parent.cancelable = false;
+        event.parent.trusted = true;
+        let event_obj = event.wrap_object_shared(cx, this);
+        invoke_listeners(cx, this, listeners, event_obj);
+    }
+
+    /// Fires an `error` event for a script of the worker that failed to load or run.
+    ///
+    /// FIXME: There's no `ErrorEvent` to say what went wrong yet, so `message` is only logged.
+    pub fn report_error(@mut self, message: ~str) {
+        debug!("Worker: %s", message);
+        if self.chan.is_none() {
+            return;
+        }
+        let type_ = str(~"error");
+        let listeners = self.listeners.get(&type_);
+        if listeners.is_empty() {
+            return;
+        }
+        let cx = unsafe { (*self.window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let this = self.wrapper.get_wrapper();
+        let event = @mut Event::new(&type_);
+        event.bubbles = false;
+        event.cancelable = true;
+        event.trusted = true;
+        let event_obj = event.wrap_object_shared(cx, this);
+        invoke_listeners(cx, this, listeners, event_obj);
+    }
+}
+
+impl CacheableWrapper for Worker {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        WorkerBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for Worker {
+    fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        Some(self.window as @mut CacheableWrapper)
+    }
+}
+
+impl DerivedWrapper for Worker {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe {
+                *vp = RUST_OBJECT_TO_JSVAL(obj)
+            };
+            return 1;
+        }
+    }
+}
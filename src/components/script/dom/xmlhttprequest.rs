@@ -0,0 +1,441 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::XMLHttpRequestBinding;
+use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
+use dom::bindings::utils::{DOMString, ErrorResult, InvalidState, Syntax, null_string, str};
+use dom::event::Event;
+use dom::eventtarget::{EventListeners, invoke_listeners};
+use dom::progressevent::ProgressEvent;
+use dom::window::Window;
+use script_task::XHRProgressMsg;
+
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSObject, JSContext, JSVal};
+
+use servo_net::resource_task::{Done, Headers, Load, LoadData, LoadProgress, Payload};
+use servo_net::resource_task::ProgressMsg;
+use servo_util::url::make_url;
+use extra::url::Url;
+
+use std::ascii::StrAsciiExt;
+use std::cast;
+use std::comm;
+use std::str::from_utf8;
+
+/// Identifies an `XMLHttpRequest` within its window, so that progress messages coming back from
+/// the resource task can find it.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct XHRId(uint);
+
+#[deriving(Eq)]
+pub enum XMLHttpRequestState {
+    Unsent = 0,
+    Opened = 1,
+    HeadersReceived = 2,
+    Loading = 3,
+    XHRDone = 4,
+}
+
+/// An event that is due after the request processes a message from the resource task.
+pub enum XHREvent {
+    ReadyStateChangeEvent,
+    /// A `ProgressEvent` of the given type, e.g. "progress" or "load".
+    XHRProgressEvent(~str),
+}
+
+pub struct XMLHttpRequest {
+    wrapper: WrapperCache,
+    window: @mut Window,
+    id: XHRId,
+    ready_state: XMLHttpRequestState,
+    method: ~str,
+    url: Option<Url>,
+    async: bool,
+    request_headers: ~[(~str, ~str)],
+    /// Whether a cross-origin request sends and stores cookies.
+    with_credentials: bool,
+    /// Set between `send()` and the end of the request.
+    send_flag: bool,
+    status: u16,
+    status_text: ~str,
+    response_headers: ~[(~str, ~str)],
+    response: ~[u8],
+    /// How much of the response body has arrived.
+    progress: LoadProgress,
+    listeners: EventListeners,
+    /// Bumped by every `open()` and `abort()`, so that progress from a request that has since
+    /// been cancelled can be told apart from that of the current one.
+    generation: uint,
+}
+
+impl XMLHttpRequest {
+    pub fn new(window: @mut Window) -> @mut XMLHttpRequest {
+        let xhr = @mut XMLHttpRequest {
+            wrapper: WrapperCache::new(),
+            window: window,
+            id: window.next_xhr_id(),
+            ready_state: Unsent,
+            method: ~"GET",
+            url: None,
+            async: true,
+            request_headers: ~[],
+            with_credentials: false,
+            send_flag: false,
+            status: 0,
+            status_text: ~"",
+            response_headers: ~[],
+            response: ~[],
+            progress: LoadProgress::new(),
+            listeners: EventListeners::new(),
+            generation: 0,
+        };
+
+        // TODO(tkuehn): This just handles the top-level page. Need to handle subframes.
+        let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = window.get_wrappercache().get_wrapper();
+        xhr.wrap_object_shared(cx, scope);
+        // FIXME: The window keeps every request alive for as long as it lives, since nothing
+        // tells us when the JS object is collected.
+        window.add_xhr(xhr);
+        xhr
+    }
+
+    pub fn Constructor(owner: @mut Window, _rv: &mut ErrorResult) -> @mut XMLHttpRequest {
+        XMLHttpRequest::new(owner)
+    }
+
+    pub fn Onreadystatechange(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("readystatechange")
+    }
+
+    pub fn SetOnreadystatechange(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("readystatechange", handler);
+    }
+
+    pub fn Onprogress(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("progress")
+    }
+
+    pub fn SetOnprogress(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("progress", handler);
+    }
+
+    pub fn Onload(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("load")
+    }
+
+    pub fn SetOnload(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("load", handler);
+    }
+
+    pub fn Onerror(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("error")
+    }
+
+    pub fn SetOnerror(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("error", handler);
+    }
+
+    pub fn Onloadend(&self, _cx: *JSContext) -> JSVal {
+        self.listeners.handler("loadend")
+    }
+
+    pub fn SetOnloadend(&mut self, _cx: *JSContext, handler: JSVal) {
+        self.listeners.set_handler("loadend", handler);
+    }
+
+    pub fn ReadyState(&self) -> u16 {
+        self.ready_state as u16
+    }
+
+    pub fn Open(&mut self, method: &DOMString, url: &DOMString, async: bool,
+                rv: &mut ErrorResult) {
+        let method = method.to_str();
+        if method.is_empty() || method.iter().any(|c| c == ' ' || c == ':') {
+            *rv = Err(Syntax);
+            return;
+        }
+
+        let base = self.window.url();
+
+        self.generation += 1;
+        self.method = method.to_ascii_upper();
+        self.url = Some(make_url(url.to_str(), base));
+        self.async = async;
+        self.request_headers = ~[];
+        self.send_flag = false;
+        self.reset_response();
+        if self.ready_state != Opened {
+            self.ready_state = Opened;
+            // The handlers run before `open()` returns.
+            match self.window.find_xhr(self.id) {
+                Some(xhr) => xhr.dispatch_events(~[ReadyStateChangeEvent]),
+                None => {}
+            }
+        }
+    }
+
+    pub fn SetRequestHeader(&mut self, header: &DOMString, value: &DOMString,
+                            rv: &mut ErrorResult) {
+        if self.ready_state != Opened || self.send_flag {
+            *rv = Err(InvalidState);
+            return;
+        }
+        let header = header.to_str();
+        if header.is_empty() {
+            *rv = Err(Syntax);
+            return;
+        }
+        self.request_headers.push((header, value.to_str()));
+    }
+
+    pub fn WithCredentials(&self) -> bool {
+        self.with_credentials
+    }
+
+    pub fn SetWithCredentials(&mut self, with_credentials: bool, rv: &mut ErrorResult) {
+        if (self.ready_state != Unsent && self.ready_state != Opened) || self.send_flag {
+            *rv = Err(InvalidState);
+            return;
+        }
+        self.with_credentials = with_credentials;
+    }
+
+    pub fn Send(&mut self, data: &DOMString, rv: &mut ErrorResult) {
+        if self.ready_state != Opened || self.send_flag {
+            *rv = Err(InvalidState);
+            return;
+        }
+
+        let mut load_data = LoadData::new(self.url.get_ref().clone());
+        load_data.method = self.method.clone();
+        load_data.headers = self.request_headers.clone();
+        // Responses from other origins can only be read if they allow it.
+        load_data.origin = self.window.origin();
+        load_data.credentials = self.with_credentials;
+        // GET and HEAD requests never carry a body.
+        if self.method != ~"GET" && self.method != ~"HEAD" {
+            match *data {
+                str(ref s) => load_data.data = Some(s.as_bytes().to_owned()),
+                null_string => {}
+            }
+        }
+
+        let (progress_port, progress_chan) = comm::stream();
+        self.window.resource_task.send(Load(load_data, progress_chan));
+
+        if !self.async {
+            // Synchronous requests block the script task until the whole response has arrived,
+            // and fire no events along the way.
+            let generation = self.generation;
+            loop {
+                let progress = progress_port.recv();
+                let done = match progress { Done(_) => true, _ => false };
+                self.process_progress(generation, progress);
+                if done {
+                    break;
+                }
+            }
+            return;
+        }
+
+        self.send_flag = true;
+
+        // Forward the progress messages to the script task, which hands them back to us.
+        let script_chan = self.window.script_chan.clone();
+        let pipeline_id = unsafe { (*self.window.page).id.clone() };
+        let id = self.id;
+        let generation = self.generation;
+        do spawn {
+            loop {
+                let progress = progress_port.recv();
+                let done = match progress { Done(_) => true, _ => false };
+                script_chan.send(XHRProgressMsg(pipeline_id.clone(), id, generation, progress));
+                if done {
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn Abort(&mut self) {
+        self.generation += 1;
+        self.send_flag = false;
+        self.reset_response();
+        self.ready_state = Unsent;
+    }
+
+    pub fn Status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn StatusText(&self) -> DOMString {
+        str(self.status_text.clone())
+    }
+
+    pub fn GetResponseHeader(&self, header: &DOMString) -> DOMString {
+        if self.ready_state == Unsent || self.ready_state == Opened {
+            return null_string;
+        }
+        let header = header.to_str().to_ascii_lower();
+        let values: ~[~str] = self.response_headers.iter()
+            .filter(|&&(ref name, _)| name.to_ascii_lower() == header)
+            .map(|&(_, ref value)| value.clone())
+            .collect();
+        if values.is_empty() {
+            null_string
+        } else {
+            str(values.connect(", "))
+        }
+    }
+
+    pub fn GetAllResponseHeaders(&self) -> DOMString {
+        if self.ready_state == Unsent || self.ready_state == Opened {
+            return str(~"");
+        }
+        let mut headers = ~"";
+        for &(ref name, ref value) in self.response_headers.iter() {
+            headers.push_str(fmt!("%s: %s\r\n", *name, *value));
+        }
+        str(headers)
+    }
+
+    pub fn ResponseText(&self) -> DOMString {
+        if self.ready_state != Loading && self.ready_state != XHRDone {
+            return str(~"");
+        }
+        // FIXME: Honour the charset of the response instead of assuming UTF-8.
+        str(from_utf8(self.response))
+    }
+
+    pub fn AddEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                            _capture: bool) {
+        self.listeners.add(type_, listener);
+    }
+
+    pub fn RemoveEventListener(&mut self, _cx: *JSContext, type_: &DOMString, listener: JSVal,
+                               _capture: bool) {
+        self.listeners.remove(type_, listener);
+    }
+}
+
+impl XMLHttpRequest {
+    fn reset_response(&mut self) {
+        self.status = 0;
+        self.status_text = ~"";
+        self.response_headers = ~[];
+        self.response = ~[];
+        self.progress = LoadProgress::new();
+    }
+
+    /// Updates the request with a message from the resource task, returning the events that are
+    /// now due. The body is appended to `responseText` chunk by chunk as it streams in, with a
+    /// `progress` event for each. Messages for a previous generation of the request are dropped.
+    pub fn process_progress(&mut self, generation: uint, progress: ProgressMsg) -> ~[XHREvent] {
+        if generation != self.generation {
+            return ~[];
+        }
+
+        self.progress.update(&progress);
+        match progress {
+            Headers(metadata) => {
+                self.status = metadata.status as u16;
+                self.status_text = metadata.status_text;
+                self.response_headers = metadata.headers;
+                self.ready_state = HeadersReceived;
+                ~[ReadyStateChangeEvent]
+            }
+            Payload(data) => {
+                self.response.push_all_move(data);
+                let mut events = ~[];
+                if self.ready_state != Loading {
+                    self.ready_state = Loading;
+                    events.push(ReadyStateChangeEvent);
+                }
+                events.push(XHRProgressEvent(~"progress"));
+                events
+            }
+            Done(result) => {
+                self.send_flag = false;
+                self.ready_state = XHRDone;
+                if result.is_ok() {
+                    ~[ReadyStateChangeEvent, XHRProgressEvent(~"load"), XHRProgressEvent(~"loadend")]
+                } else {
+                    // Network errors leave no trace of the response behind.
+                    self.reset_response();
+                    ~[ReadyStateChangeEvent, XHRProgressEvent(~"error"), XHRProgressEvent(~"loadend")]
+                }
+            }
+        }
+    }
+
+    /// Fires the given events at the `on*` handlers and listeners for their types.
+    pub fn dispatch_events(@mut self, events: ~[XHREvent]) {
+        let cx = unsafe { (*self.window.page).js_info.get_ref().js_compartment.cx.ptr };
+        let this = self.wrapper.get_wrapper();
+
+        for event in events.move_iter() {
+            let (type_, is_progress) = match event {
+                ReadyStateChangeEvent => (~"readystatechange", false),
+                XHRProgressEvent(type_) => (type_, true),
+            };
+            let type_ = str(type_);
+            let listeners = self.listeners.get(&type_);
+            if listeners.is_empty() {
+                loop;
+            }
+
+            let event_obj = if is_progress {
+                let event = @mut ProgressEvent::from_load_progress(&type_, &self.progress);
+                event.parent.bubbles = false;
+                event.parent.cancelable = false;
+                event.parent.trusted = true;
+                event.wrap_object_shared(cx, this)
+            } else {
+                let event = @mut Event::new(&type_);
+                event.bubbles = false;
+                event.cancelable = false;
+                event.trusted = true;
+                event.wrap_object_shared(cx, this)
+            };
+            invoke_listeners(cx, this, listeners, event_obj);
+        }
+    }
+}
+
+impl CacheableWrapper for XMLHttpRequest {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        XMLHttpRequestBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for XMLHttpRequest {
+    fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        Some(self.window as @mut CacheableWrapper)
+    }
+}
+
+impl DerivedWrapper for XMLHttpRequest {
+    fn wrap(&mut self, _cx: *JSContext, _scope: *JSObject, _vp: *mut JSVal) -> i32 {
+        fail!(~"nyi")
+    }
+
+    fn wrap_shared(@mut self, cx: *JSContext, scope: *JSObject, vp: *mut JSVal) -> i32 {
+        let obj = self.wrap_object_shared(cx, scope);
+        if obj.is_null() {
+            return 0;
+        } else {
+            unsafe {
+                *vp = RUST_OBJECT_TO_JSVAL(obj)
+            };
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,125 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An entry point for fuzzers that runs arbitrary bytes through the HTML parser, without a
+//! window, layout or the network. Bugs show up as task failure.
+
+use dom::node::define_bindings;
+use html::hubbub_html_parser::{HtmlDiscoveredScript, HtmlDiscoveredStyle};
+use html::hubbub_html_parser::{HtmlDiscoveredIFrame, HtmlParserResult};
+use html::hubbub_html_parser;
+use layout_interface::LayoutChan;
+use script_task::{ScriptChan, ScriptTask};
+
+use std::comm;
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::opts::DEFAULT_USER_AGENT;
+use servo_msg::compositor_msg::{ReadyState, ScriptListener, SelectPopup, TouchEventResult};
+use servo_msg::compositor_msg::{VideoFrame, VideoFrameSink, VideoLayerId, WindowMetrics};
+use servo_msg::constellation_msg::{ConstellationChan, PipelineId};
+use servo_net::image_cache_task::{ImageCacheTask, ImageCacheTaskClient};
+use servo_net::resource_task::{Exit, LoadData, ResourceTaskWithData};
+use servo_util::time::ProfilerChan;
+use servo_util::url::make_url;
+use extra::future::from_value;
+
+/// The URL the fuzzed document is served from. Every other load fails.
+pub static FUZZ_URL: &'static str = "fuzz:///input.html";
+
+/// The size of the pretend window documents are loaded into.
+pub static FUZZ_WINDOW_SIZE: Size2D<uint> = Size2D { width: 800, height: 600 };
+
+/// A script listener for running without a compositor.
+#[deriving(Clone)]
+pub struct HeadlessScriptListener;
+
+impl ScriptListener for HeadlessScriptListener {
+    fn set_ready_state(&self, _: ReadyState) {}
+    fn set_title(&self, _: PipelineId, _: ~str) {}
+    fn invalidate_rect(&self, _: PipelineId, _: Rect<uint>) {}
+    fn request_animation_frame(&self, _: PipelineId) {}
+    fn show_select_popup(&self, _: PipelineId, _: SelectPopup) {}
+    fn set_clipboard_text(&self, _: ~str) {}
+    fn scroll_fragment_point(&self, _: PipelineId, _: Point2D<f32>) {}
+    fn touch_event_processed(&self, _: TouchEventResult) {}
+    fn video_frame_sink(&self) -> ~VideoFrameSink:Send {
+        ~HeadlessVideoFrameSink as ~VideoFrameSink:Send
+    }
+    fn get_window_metrics(&self) -> WindowMetrics {
+        WindowMetrics {
+            screen_size: FUZZ_WINDOW_SIZE,
+            available_screen_size: FUZZ_WINDOW_SIZE,
+            window_size: FUZZ_WINDOW_SIZE,
+            device_pixel_ratio: 1f32,
+            color_depth: 24,
+        }
+    }
+}
+
+/// Throws away the frames of videos, for running without a compositor.
+pub struct HeadlessVideoFrameSink;
+
+impl VideoFrameSink for HeadlessVideoFrameSink {
+    fn show_video_frame(&self, _: PipelineId, _: VideoLayerId, _: ~VideoFrame) {}
+}
+
+/// Parses `data` as an HTML document, along with any inline style sheets and scripts it
+/// contains. The scripts aren't run.
+pub fn fuzz_html(data: &[u8]) {
+    let url = make_url(FUZZ_URL.to_owned(), None);
+    let resource_task = ResourceTaskWithData(url.clone(), data.to_owned());
+    let image_cache_task = ImageCacheTask(resource_task.clone());
+
+    // Nothing listens on the other ends of these, but they have to stay open for the parser to
+    // be able to send on them.
+    let (script_port, script_chan) = comm::stream();
+    let (_layout_port, layout_chan) = comm::stream();
+    let (_constellation_port, constellation_chan) = comm::stream();
+    let constellation_chan = ConstellationChan::new(constellation_chan);
+    let (_profiler_port, profiler_chan) = comm::stream();
+
+    let script_task = ScriptTask::new(PipelineId(0),
+                                      @HeadlessScriptListener as @ScriptListener,
+                                      LayoutChan::new(layout_chan),
+                                      script_port,
+                                      ScriptChan::new(script_chan),
+                                      constellation_chan.clone(),
+                                      resource_task.clone(),
+                                      image_cache_task.clone(),
+                                      ProfilerChan::new(profiler_chan),
+                                      from_value(FUZZ_WINDOW_SIZE),
+                                      None,
+                                      DEFAULT_USER_AGENT.to_owned());
+
+    let page = script_task.page_tree.page;
+    let js_info = page.js_info.get_mut_ref();
+    define_bindings(js_info.js_compartment);
+    js_info.bindings_initialized = true;
+
+    // The page has no frame, so the parser doesn't run the scripts it finds.
+    let cx = js_info.js_compartment.cx.ptr;
+    let root = hubbub_html_parser::create_document_root(cx);
+    let HtmlParserResult { discovery_port } =
+        hubbub_html_parser::parse_html(cx,
+                                       root,
+                                       LoadData::new(url),
+                                       resource_task.clone(),
+                                       image_cache_task.clone(),
+                                       page.next_subpage_id.clone(),
+                                       constellation_chan);
+
+    // Parsing is done by now, style sheets and scripts included; drop what was found.
+    loop {
+        match discovery_port.try_recv() {
+            Some(HtmlDiscoveredScript(_)) | Some(HtmlDiscoveredStyle(*)) => {}
+            Some(HtmlDiscoveredIFrame(_)) => {}
+            None => break,
+        }
+    }
+
+    image_cache_task.exit();
+    resource_task.send(Exit);
+}
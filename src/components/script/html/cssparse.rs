@@ -6,11 +6,14 @@
 
 use std::cell::Cell;
 use std::comm;
-use std::comm::Port;
+use std::comm::{Port, SharedChan};
+use std::rt::io::timer::Timer;
+use std::rt::rtio::RtioTimer;
+use std::str;
 use std::task;
-use newcss::stylesheet::Stylesheet;
-use newcss::util::DataStream;
-use servo_net::resource_task::{ResourceTask, ProgressMsg, Load, Payload, Done};
+use servo_net::resource_task::{ResourceTask, Load, LoadData, Headers, Payload, Done};
+use servo_util::media_queries::{MediaQueryList, StylesheetSource, take_imports};
+use servo_util::url::make_url;
 use extra::url::Url;
 
 /// Where a style sheet comes from.
@@ -19,61 +22,126 @@ pub enum StylesheetProvenance {
     InlineProvenance(Url, ~str),
 }
 
+/// How long a style sheet waits for each of its imports before going on without it, in
+/// milliseconds.
+static IMPORT_TIMEOUT: u64 = 5000;
+
+/// How deeply imports can nest.
+static MAX_IMPORT_DEPTH: uint = 16;
+
+/// Loads a style sheet and those it imports in a task of its own, and splits them up at their
+/// `@media` rules. It's parsed by layout, once it knows which of those rules apply.
+///
+/// The style sheet only applies where `media` matches. It isn't sent until its imports have
+/// loaded or timed out, so layout never sees it without the rules it imports.
 pub fn spawn_css_parser(provenance: StylesheetProvenance,
+                        media: MediaQueryList,
                         resource_task: ResourceTask)
-                     -> Port<Stylesheet> {
+                     -> Port<StylesheetSource> {
     let (result_port, result_chan) = comm::stream();
 
     let provenance_cell = Cell::new(provenance);
+    let media_cell = Cell::new(media);
     do task::spawn {
-        let url = do provenance_cell.with_ref |p| {
-            match *p {
-                UrlProvenance(ref the_url) => (*the_url).clone(),
-                InlineProvenance(ref the_url, _) => (*the_url).clone()
+        let (url, text) = match provenance_cell.take() {
+            UrlProvenance(url) => {
+                let text = load_stylesheet(&url, &resource_task);
+                (url, text)
             }
+            InlineProvenance(url, data) => (url, data),
         };
-
-        let sheet = Stylesheet::new(url, data_stream(provenance_cell.take(),
-                                                     resource_task.clone()));
-        result_chan.send(sheet);
+        let mut source = stylesheet_source(url, text, &resource_task, []);
+        source.restrict_to(&media_cell.take());
+        result_chan.send(source);
     }
 
     return result_port;
 }
 
-fn data_stream(provenance: StylesheetProvenance, resource_task: ResourceTask) -> DataStream {
-    match provenance {
-        UrlProvenance(url) => {
-            debug!("cssparse: loading style sheet at %s", url.to_str());
-            let (input_port, input_chan) = comm::stream();
-            resource_task.send(Load(url, input_chan));
-            resource_port_to_data_stream(input_port)
+/// Splits up a style sheet after loading its imports, which load in parallel. `ancestors` are
+/// the style sheets that import this one, directly or not; importing any of them again is
+/// ignored, as otherwise the imports would never end.
+fn stylesheet_source(url: Url, text: ~str, resource_task: &ResourceTask, ancestors: &[Url])
+                     -> StylesheetSource {
+    let (imports, rest) = take_imports(text.as_slice());
+
+    let mut ancestors = ancestors.to_owned();
+    ancestors.push(url.clone());
+    let mut pending = ~[];
+    for import in imports.move_iter() {
+        let import_url = make_url(import.href.clone(), Some(url.clone()));
+        if ancestors.len() >= MAX_IMPORT_DEPTH || ancestors.contains(&import_url) {
+            debug!("cssparse: not importing %s again", import_url.to_str());
+            loop;
         }
-        InlineProvenance(_, data) => {
-            data_to_data_stream(data)
+        pending.push((spawn_import_loader(import_url, resource_task.clone(), ancestors.clone()),
+                      import.media));
+    }
+
+    let mut imported = ~[];
+    for (port, media) in pending.move_iter() {
+        match port.recv() {
+            Some(source) => imported.push((source, media)),
+            None => {}
         }
     }
+    StylesheetSource::with_imports(url, imported, rest.as_slice())
 }
 
-fn resource_port_to_data_stream(input_port: Port<ProgressMsg>) -> DataStream {
-    return || {
-        match input_port.recv() {
-            Payload(data) => Some(data),
-            Done(*) => None
+/// Loads an imported style sheet, and those it imports in turn. `None` is sent instead if it
+/// takes longer than `IMPORT_TIMEOUT`.
+fn spawn_import_loader(url: Url, resource_task: ResourceTask, ancestors: ~[Url])
+                       -> Port<Option<StylesheetSource>> {
+    let (port, chan) = comm::stream();
+    let chan = SharedChan::new(chan);
+
+    let url = Cell::new(url);
+    let ancestors = Cell::new(ancestors);
+    let load_chan = chan.clone();
+    do task::spawn {
+        let url = url.take();
+        let text = load_stylesheet(&url, &resource_task);
+        let source = stylesheet_source(url, text, &resource_task, ancestors.take());
+        load_chan.try_send(Some(source));
+    }
+
+    do task::spawn {
+        let timer = Timer::new().unwrap();
+        timer.sleep(IMPORT_TIMEOUT);
+        if chan.try_send(None) {
+            debug!("cssparse: an import timed out");
         }
     }
+
+    port
 }
 
-fn data_to_data_stream(data: ~str) -> DataStream {
-    let data_cell = Cell::new(data);
-    return || {
-        if data_cell.is_empty() {
-            None
-        } else {
-            // FIXME: Blech, a copy.
-            let data = data_cell.take();
-            Some(data.as_bytes().to_owned())
+/// Loads the text of the style sheet at `url`, blocking until it's all there. A style sheet that
+/// fails to load is empty.
+///
+/// FIXME: Honour `@charset` and the charset in the headers. Style sheets that aren't UTF-8 are
+/// read as Latin-1 for now.
+fn load_stylesheet(url: &Url, resource_task: &ResourceTask) -> ~str {
+    debug!("cssparse: loading style sheet at %s", url.to_str());
+    let (input_port, input_chan) = comm::stream();
+    resource_task.send(Load(LoadData::new(url.clone()), input_chan));
+
+    let mut data = ~[];
+    loop {
+        match input_port.recv() {
+            Headers(*) => {}
+            Payload(bytes) => data.push_all(bytes),
+            Done(Ok(*)) => break,
+            Done(Err(*)) => {
+                error!("error loading style sheet %s", url.to_str());
+                return ~"";
+            }
         }
     }
-}
 
+    if str::is_utf8(data) {
+        str::from_utf8(data)
+    } else {
+        data.iter().map(|&byte| byte as char).collect()
+    }
+}
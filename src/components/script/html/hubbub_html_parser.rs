@@ -4,7 +4,8 @@
 
 use dom::element::{HTMLElementTypeId,
                    HTMLAnchorElementTypeId, HTMLAppletElementTypeId, 
-                   HTMLAreaElementTypeId,  HTMLBaseElementTypeId, HTMLBodyElementTypeId,
+                   HTMLAreaElementTypeId, HTMLAudioElementTypeId, HTMLBaseElementTypeId,
+                   HTMLBodyElementTypeId,
                    HTMLBRElementTypeId, HTMLCanvasElementTypeId, HTMLDataElementTypeId,
                    HTMLDivElementTypeId, HTMLDListElementTypeId,
                    HTMLFontElementTypeId, HTMLFormElementTypeId, HTMLHRElementTypeId,
@@ -20,34 +21,38 @@ use dom::element::{HTMLElementTypeId,
                    HTMLTableCaptionElementTypeId, HTMLTableColElementTypeId,
                    HTMLTableRowElementTypeId, HTMLTextAreaElementTypeId,
                    HTMLTimeElementTypeId, HTMLTitleElementTypeId, HTMLUListElementTypeId,
-                   UnknownElementTypeId};
-use dom::element::{HTMLDivElement, HTMLFontElement, HTMLFormElement,
+                   HTMLVideoElementTypeId, SVGCircleElementTypeId, SVGPathElementTypeId, SVGRectElementTypeId,
+                   SVGSVGElementTypeId, UnknownElementTypeId};
+use dom::element::{HTMLDivElement, HTMLFontElement,
                    HTMLHeadElement, HTMLHeadingElement, HTMLHtmlElement,
-                   HTMLOptionElement, HTMLParagraphElement,
-                   HTMLSelectElement, HTMLSmallElement,
-                   HTMLSpanElement};
+                   HTMLParagraphElement, HTMLSmallElement, HTMLSpanElement};
 use dom::element::{HTMLHeadingElementTypeId, Heading1, Heading2, Heading3, Heading4, Heading5,
                    Heading6};
 use dom::htmlbrelement::HTMLBRElement;
 use dom::htmlanchorelement::HTMLAnchorElement;
 use dom::htmlappletelement::HTMLAppletElement;
 use dom::htmlareaelement::HTMLAreaElement;
+use dom::htmlaudioelement::HTMLAudioElement;
 use dom::htmlbaseelement::HTMLBaseElement;
 use dom::htmlbodyelement::HTMLBodyElement;
 use dom::htmlcanvaselement::HTMLCanvasElement;
 use dom::htmldataelement::HTMLDataElement;
 use dom::htmldlistelement::HTMLDListElement;
+use dom::htmlformelement::HTMLFormElement;
 use dom::htmlhrelement::HTMLHRElement;
 use dom::htmliframeelement::{IFrameSize, HTMLIFrameElement};
-use dom::htmlimageelement::HTMLImageElement;
+use dom::htmlimageelement::{HTMLImageElement, parse_sizes, parse_srcset};
 use dom::htmlinputelement::HTMLInputElement;
 use dom::htmllielement::HTMLLIElement;
-use dom::htmllinkelement::HTMLLinkElement;
+use dom::htmllinkelement::{HTMLLinkElement, is_stylesheet_rel};
+use dom::htmlmediaelement::HTMLMediaElement;
 use dom::htmlmetaelement::HTMLMetaElement;
 use dom::htmlolistelement::HTMLOListElement;
+use dom::htmloptionelement::HTMLOptionElement;
 use dom::htmlprogresselement::HTMLProgressElement;
 use dom::htmlquoteelement::HTMLQuoteElement;
 use dom::htmlscriptelement::HTMLScriptElement;
+use dom::htmlselectelement::HTMLSelectElement;
 use dom::htmlsourceelement::HTMLSourceElement;
 use dom::htmlstyleelement::HTMLStyleElement;
 use dom::htmltableelement::HTMLTableElement;
@@ -60,20 +65,27 @@ use dom::htmltextareaelement::HTMLTextAreaElement;
 use dom::htmltimeelement::HTMLTimeElement;
 use dom::htmltitleelement::HTMLTitleElement;
 use dom::htmlulistelement::HTMLUListElement;
+use dom::htmlvideoelement::HTMLVideoElement;
+use dom::svgelement::{SVGCircleElement, SVGElement, SVGPathElement, SVGRectElement};
+use dom::svgelement::SVGSVGElement;
+use dom::documentfragment::DocumentFragment;
 use dom::element::Element;
 use dom::htmlelement::HTMLElement;
 use dom::node::{AbstractNode, Comment, Doctype, ElementNodeTypeId, Node, ScriptView};
-use dom::node::{Text};
+use dom::node::{CommentNodeTypeId, DoctypeNodeTypeId, DocumentFragmentNodeTypeId, Text};
+use dom::node::TextNodeTypeId;
 use dom::bindings::utils::str;
 use html::cssparse::{InlineProvenance, StylesheetProvenance, UrlProvenance, spawn_css_parser};
+use js::JSVAL_NULL;
 use js::jsapi::JSContext;
-use newcss::stylesheet::Stylesheet;
 use script_task::page_from_context;
 
+use std::ascii::StrAsciiExt;
 use std::cast;
 use std::cell::Cell;
 use std::comm;
 use std::comm::{Port, SharedChan};
+use std::ptr;
 use std::str::eq_slice;
 use std::task;
 use std::from_str::FromStr;
@@ -81,7 +93,9 @@ use hubbub::hubbub;
 use servo_msg::constellation_msg::{ConstellationChan, SubpageId};
 use servo_net::image_cache_task::ImageCacheTask;
 use servo_net::image_cache_task;
-use servo_net::resource_task::{Done, Load, Payload, ResourceTask};
+use servo_net::resource_task::{Done, Headers, Load, LoadData, LoadProgress, Payload};
+use servo_net::resource_task::ResourceTask;
+use servo_util::media_queries::{MediaQueryList, StylesheetSource};
 use servo_util::tree::TreeNodeRef;
 use servo_util::url::make_url;
 use extra::url::Url;
@@ -103,6 +117,33 @@ macro_rules! handle_element(
         }
     )
 )
+macro_rules! handle_mediaelement(
+    ($cx: expr, $tag:expr, $string:expr, $type_id:expr, $ctor:ident, [ $(($field:ident : $field_init:expr)),* ]) => (
+        if eq_slice($tag, $string) {
+            let _element = @$ctor {
+                parent: HTMLMediaElement::new($type_id, ($tag).to_str()),
+                $(
+                    $field: $field_init,
+                )*
+            };
+            unsafe {
+                return Node::as_abstract_node(cx, _element);
+            }
+        }
+    )
+)
+macro_rules! handle_svgelement(
+    ($cx: expr, $tag:expr, $string:expr, $type_id:expr, $ctor:ident) => (
+        if eq_slice($tag, $string) {
+            let _element = @$ctor {
+                parent: SVGElement::new($type_id, ($tag).to_str())
+            };
+            unsafe {
+                return Node::as_abstract_node(cx, _element);
+            }
+        }
+    )
+)
 macro_rules! handle_htmlelement(
     ($cx: expr, $tag:expr, $string:expr, $type_id:expr, $ctor:ident) => (
         if eq_slice($tag, $string) {
@@ -117,7 +158,7 @@ macro_rules! handle_htmlelement(
 type JSResult = ~[~[u8]];
 
 enum CSSMessage {
-    CSSTaskNewFile(StylesheetProvenance),
+    CSSTaskNewFile(StylesheetProvenance, MediaQueryList),
     CSSTaskExit   
 }
 
@@ -128,13 +169,13 @@ enum JSMessage {
 
 /// Messages generated by the HTML parser upon discovery of additional resources
 pub enum HtmlDiscoveryMessage {
-    HtmlDiscoveredStyle(Stylesheet),
+    /// A style sheet, with the URL it was linked from if it isn't in a `style` element.
+    HtmlDiscoveredStyle(StylesheetSource, Option<Url>),
     HtmlDiscoveredIFrame((Url, SubpageId, Future<Size2D<uint>>)),
     HtmlDiscoveredScript(JSResult)
 }
 
 pub struct HtmlParserResult {
-    root: AbstractNode<ScriptView>,
     discovery_port: Port<HtmlDiscoveryMessage>,
 }
 
@@ -174,8 +215,13 @@ fn css_link_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
 
     loop {
         match from_parent.recv() {
-            CSSTaskNewFile(provenance) => {
-                result_vec.push(spawn_css_parser(provenance, resource_task.clone()));
+            CSSTaskNewFile(provenance, media) => {
+                let href = match provenance {
+                    UrlProvenance(ref url) => Some(url.clone()),
+                    InlineProvenance(*) => None,
+                };
+                let port = spawn_css_parser(provenance, media, resource_task.clone());
+                result_vec.push((port, href));
             }
             CSSTaskExit => {
                 break;
@@ -185,8 +231,8 @@ fn css_link_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
 
     // Send the sheets back in order
     // FIXME: Shouldn't wait until after we've recieved CSSTaskExit to start sending these
-    for port in result_vec.iter() {
-        to_parent.send(HtmlDiscoveredStyle(port.recv()));
+    for (port, href) in result_vec.move_iter() {
+        to_parent.send(HtmlDiscoveredStyle(port.recv(), href));
     }
 }
 
@@ -201,27 +247,7 @@ fn js_script_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
                 let (result_port, result_chan) = comm::stream();
                 let resource_task = resource_task.clone();
                 do task::spawn {
-                    let (input_port, input_chan) = comm::stream();
-                    // TODO: change copy to move once we can move into closures
-                    resource_task.send(Load(url.clone(), input_chan));
-
-                    let mut buf = ~[];
-                    loop {
-                        match input_port.recv() {
-                            Payload(data) => {
-                                buf.push_all(data);
-                            }
-                            Done(Ok(*)) => {
-                                result_chan.send(Some(buf));
-                                break;
-                            }
-                            Done(Err(*)) => {
-                                error!("error loading script %s", url.to_str());
-                                result_chan.send(None);
-                                break;
-                            }
-                        }
-                    }
+                    result_chan.send(load_script(&url, &resource_task));
                 }
                 result_vec.push(result_port);
             }
@@ -235,32 +261,61 @@ fn js_script_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
     to_parent.send(HtmlDiscoveredScript(js_scripts));
 }
 
+/// Loads the script at `url`, blocking until it's all there. Returns `None` if it fails to load.
+pub fn load_script(url: &Url, resource_task: &ResourceTask) -> Option<~[u8]> {
+    let (input_port, input_chan) = comm::stream();
+    resource_task.send(Load(LoadData::new(url.clone()), input_chan));
+
+    let mut buf = ~[];
+    loop {
+        match input_port.recv() {
+            Headers(*) => {}
+            Payload(data) => {
+                buf.push_all(data);
+            }
+            Done(Ok(*)) => {
+                return Some(buf);
+            }
+            Done(Err(*)) => {
+                error!("error loading script %s", url.to_str());
+                return None;
+            }
+        }
+    }
+}
+
 // Silly macros to handle constructing      DOM nodes. This produces bad code and should be optimized
 // via atomization (issue #85).
 
-fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView> {
+pub fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView> {
     // TODO (Issue #85): use atoms
-    handle_element!(cx, tag, "a",       HTMLAnchorElementTypeId, HTMLAnchorElement, []);
+    handle_element!(cx, tag, "a",       HTMLAnchorElementTypeId, HTMLAnchorElement,
+                    [(onclick: JSVAL_NULL)]);
     handle_element!(cx, tag, "applet",  HTMLAppletElementTypeId, HTMLAppletElement, []);
     handle_element!(cx, tag, "area",    HTMLAreaElementTypeId, HTMLAreaElement, []);
     handle_element!(cx, tag, "base",    HTMLBaseElementTypeId, HTMLBaseElement, []);
     handle_element!(cx, tag, "br",      HTMLBRElementTypeId, HTMLBRElement, []);
     handle_element!(cx, tag, "body",    HTMLBodyElementTypeId, HTMLBodyElement, []);
-    handle_element!(cx, tag, "canvas",  HTMLCanvasElementTypeId, HTMLCanvasElement, []);
+    handle_element!(cx, tag, "canvas",  HTMLCanvasElementTypeId, HTMLCanvasElement,
+                    [(origin_clean: true), (context: None), (renderer: None)]);
     handle_element!(cx, tag, "data",    HTMLDataElementTypeId, HTMLDataElement, []);
     handle_element!(cx, tag, "div",     HTMLDivElementTypeId, HTMLDivElement, []);
     handle_element!(cx, tag, "dl",      HTMLDListElementTypeId, HTMLDListElement, []);
     handle_element!(cx, tag, "font",    HTMLFontElementTypeId, HTMLFontElement, []);
-    handle_element!(cx, tag, "form",    HTMLFormElementTypeId, HTMLFormElement, []);
+    handle_element!(cx, tag, "form",    HTMLFormElementTypeId, HTMLFormElement,
+                    [(onsubmit: JSVAL_NULL)]);
     handle_element!(cx, tag, "hr",      HTMLHRElementTypeId, HTMLHRElement, []);
     handle_element!(cx, tag, "head",    HTMLHeadElementTypeId, HTMLHeadElement, []);
     handle_element!(cx, tag, "html",    HTMLHtmlElementTypeId, HTMLHtmlElement, []);
-    handle_element!(cx, tag, "input",   HTMLInputElementTypeId, HTMLInputElement, []);
-    handle_element!(cx, tag, "link",    HTMLLinkElementTypeId, HTMLLinkElement, []);
+    handle_element!(cx, tag, "input",   HTMLInputElementTypeId, HTMLInputElement,
+                    [(value: None), (caret: 0), (focused: false), (checked: None)]);
+    handle_element!(cx, tag, "link",    HTMLLinkElementTypeId, HTMLLinkElement,
+                    [(stylesheet: None), (disabled: None), (enabled: true)]);
     handle_element!(cx, tag, "li",      HTMLLIElementTypeId, HTMLLIElement, []);
     handle_element!(cx, tag, "meta",    HTMLMetaElementTypeId, HTMLMetaElement, []);
     handle_element!(cx, tag, "ol",      HTMLOListElementTypeId, HTMLOListElement, []);
-    handle_element!(cx, tag, "option",  HTMLOptionElementTypeId, HTMLOptionElement, []);
+    handle_element!(cx, tag, "option",  HTMLOptionElementTypeId, HTMLOptionElement,
+                    [(selected: None)]);
     handle_element!(cx, tag, "p",       HTMLParagraphElementTypeId, HTMLParagraphElement, []);
     handle_element!(cx, tag, "progress",HTMLProgressElementTypeId, HTMLProgressElement, []);
     handle_element!(cx, tag, "q",       HTMLQuoteElementTypeId, HTMLQuoteElement, []);
@@ -273,16 +328,21 @@ fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView>
     handle_element!(cx, tag, "table",   HTMLTableElementTypeId, HTMLTableElement, []);
     handle_element!(cx, tag, "caption", HTMLTableCaptionElementTypeId, HTMLTableCaptionElement, []);
     handle_element!(cx, tag, "td",      HTMLTableCellElementTypeId, HTMLTableCellElement, []);
+    handle_element!(cx, tag, "th",      HTMLTableCellElementTypeId, HTMLTableCellElement, []);
     handle_element!(cx, tag, "col",     HTMLTableColElementTypeId, HTMLTableColElement, []);
     handle_element!(cx, tag, "colgroup",HTMLTableColElementTypeId, HTMLTableColElement, []);
     handle_element!(cx, tag, "tbody",   HTMLTableSectionElementTypeId, HTMLTableSectionElement, []);
-    handle_element!(cx, tag, "textarea",HTMLTextAreaElementTypeId, HTMLTextAreaElement, []);
+    handle_element!(cx, tag, "thead",   HTMLTableSectionElementTypeId, HTMLTableSectionElement, []);
+    handle_element!(cx, tag, "tfoot",   HTMLTableSectionElementTypeId, HTMLTableSectionElement, []);
+    handle_element!(cx, tag, "textarea",HTMLTextAreaElementTypeId, HTMLTextAreaElement,
+                    [(value: None), (caret: 0), (focused: false)]);
     handle_element!(cx, tag, "time",    HTMLTimeElementTypeId, HTMLTimeElement, []);
     handle_element!(cx, tag, "title",   HTMLTitleElementTypeId, HTMLTitleElement, []);
     handle_element!(cx, tag, "tr",      HTMLTableRowElementTypeId, HTMLTableRowElement, []);
     handle_element!(cx, tag, "ul",      HTMLUListElementTypeId, HTMLUListElement, []);
 
-    handle_element!(cx, tag, "img", HTMLImageElementTypeId, HTMLImageElement, [(image: None)]);
+    handle_element!(cx, tag, "img", HTMLImageElementTypeId, HTMLImageElement,
+                    [(image: None), (src: None), (srcset: ~[]), (sizes: ~[])]);
     handle_element!(cx, tag, "iframe",  HTMLIframeElementTypeId, HTMLIFrameElement, [(frame: None), (size: None)]);
 
     handle_element!(cx, tag, "h1", HTMLHeadingElementTypeId, HTMLHeadingElement, [(level: Heading1)]);
@@ -293,22 +353,424 @@ fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView>
     handle_element!(cx, tag, "h6", HTMLHeadingElementTypeId, HTMLHeadingElement, [(level: Heading6)]);
 
 
+    handle_mediaelement!(cx, tag, "audio", HTMLAudioElementTypeId, HTMLAudioElement, []);
+    handle_mediaelement!(cx, tag, "video", HTMLVideoElementTypeId, HTMLVideoElement,
+                         [(video_size: Size2D(0, 0))]);
+
     handle_htmlelement!(cx, tag, "aside",   HTMLElementTypeId, HTMLElement);
     handle_htmlelement!(cx, tag, "b",       HTMLElementTypeId, HTMLElement);
     handle_htmlelement!(cx, tag, "i",       HTMLElementTypeId, HTMLElement);
     handle_htmlelement!(cx, tag, "section", HTMLElementTypeId, HTMLElement);
 
+    // FIXME: The tree builder knows which elements are in the SVG namespace, but we only get
+    // their names, so a `<rect>` outside an `<svg>` is an SVG element too. Layout only draws the
+    // ones in an `<svg>`.
+    handle_svgelement!(cx, tag, "svg",    SVGSVGElementTypeId, SVGSVGElement);
+    handle_svgelement!(cx, tag, "rect",   SVGRectElementTypeId, SVGRectElement);
+    handle_svgelement!(cx, tag, "circle", SVGCircleElementTypeId, SVGCircleElement);
+    handle_svgelement!(cx, tag, "path",   SVGPathElementTypeId, SVGPathElement);
+
     unsafe {
         Node::as_abstract_node(cx, @Element::new(UnknownElementTypeId, tag.to_str()))
     }
 }
 
+// Tree operations that hubbub's tree builder asks for while it constructs the document. It
+// implements the tree construction algorithm itself (implied tags, foster parenting, the adoption
+// agency algorithm and so on) in terms of these.
+
+/// Appends `child` to `parent`. The tree builder leaves it to us to merge adjacent text, so text
+/// following a text node is appended to that node instead. Returns the node that ended up in the
+/// tree.
+fn append_child(parent: AbstractNode<ScriptView>, child: AbstractNode<ScriptView>)
+                -> AbstractNode<ScriptView> {
+    match parent.last_child() {
+        Some(last_child) if last_child.is_text() && child.is_text() => {
+            merge_text(last_child, child);
+            last_child
+        }
+        _ => {
+            parent.add_child(child);
+            child
+        }
+    }
+}
+
+/// Inserts `child` into `parent` right before `before`, merging text like `append_child`. This is
+/// how content misnested in tables gets foster parented. Returns the node that ended up in the
+/// tree.
+fn insert_before(parent: AbstractNode<ScriptView>,
+                 child: AbstractNode<ScriptView>,
+                 before: AbstractNode<ScriptView>)
+                 -> AbstractNode<ScriptView> {
+    match before.prev_sibling() {
+        Some(prev_sibling) if prev_sibling.is_text() && child.is_text() => {
+            merge_text(prev_sibling, child);
+            prev_sibling
+        }
+        _ => {
+            parent.insert_before(child, before);
+            child
+        }
+    }
+}
+
+/// Appends the text of the text node `from` to the text node `to`.
+fn merge_text(to: AbstractNode<ScriptView>, from: AbstractNode<ScriptView>) {
+    let data = do from.with_imm_text |text| {
+        text.parent.data.to_str()
+    };
+    do to.with_mut_text |text| {
+        let merged = text.parent.data.to_str() + data;
+        text.parent.data = str(merged);
+    }
+}
+
+/// Moves all children of `node` to the end of `new_parent`.
+fn reparent_children(node: AbstractNode<ScriptView>, new_parent: AbstractNode<ScriptView>) {
+    let children: ~[AbstractNode<ScriptView>] = node.children().collect();
+    for &child in children.iter() {
+        node.remove_child(child);
+        new_parent.add_child(child);
+    }
+}
+
+/// Copies `node`, and its descendants if `deep` is set. The copy belongs to the same document as
+/// `node`. The adoption agency algorithm uses this to reopen formatting elements that a misnested
+/// end tag closed, ranges to copy their contents, and `cloneNode` and `importNode`.
+///
+/// Elements are copied with their attributes. Inputs also keep the value the user or script gave
+/// them, but not their focus.
+pub fn clone_node(cx: *JSContext, node: AbstractNode<ScriptView>, deep: bool)
+              -> AbstractNode<ScriptView> {
+    let clone = match node.type_id() {
+        ElementNodeTypeId(_) => {
+            let (tag_name, attrs) = do node.with_imm_element |element| {
+                (element.tag_name.clone(),
+                 element.attrs.map(|attr| (attr.name.clone(), attr.value.clone())))
+            };
+            let clone = build_element_from_tag(cx, tag_name);
+            do clone.as_mut_element |element| {
+                for &(ref name, ref value) in attrs.iter() {
+                    element.set_attr(&str(name.clone()), &str(value.clone()));
+                }
+            }
+            if node.is_input_element() {
+                let (value, caret, checked) = do node.with_imm_input_element |input| {
+                    (input.value.clone(), input.caret, input.checked)
+                };
+                do clone.with_mut_input_element |input| {
+                    input.value = value.clone();
+                    input.caret = caret;
+                    input.checked = checked;
+                }
+            }
+            if node.is_option_element() {
+                let selected = node.with_imm_option_element(|option| option.selected);
+                do clone.with_mut_option_element |option| {
+                    option.selected = selected;
+                }
+            }
+            if node.is_textarea_element() {
+                let value = node.with_imm_textarea_element(|textarea| textarea.value.clone());
+                do clone.with_mut_textarea_element |textarea| {
+                    textarea.value = value.clone();
+                }
+            }
+            clone
+        }
+        TextNodeTypeId => {
+            let data = do node.with_imm_text |text| {
+                text.parent.data.to_str()
+            };
+            unsafe { Node::as_abstract_node(cx, @Text::new(data)) }
+        }
+        CommentNodeTypeId => {
+            let data = do node.transmute |comment: &Comment| {
+                comment.parent.data.to_str()
+            };
+            unsafe { Node::as_abstract_node(cx, @Comment::new(data)) }
+        }
+        DoctypeNodeTypeId => {
+            let doctype = do node.transmute |doctype: &Doctype<ScriptView>| {
+                Doctype::new(doctype.name.clone(),
+                             doctype.public_id.clone(),
+                             doctype.system_id.clone(),
+                             doctype.force_quirks)
+            };
+            unsafe { Node::as_abstract_node(cx, @doctype) }
+        }
+        DocumentFragmentNodeTypeId => {
+            unsafe { Node::as_abstract_node(cx, @DocumentFragment::new()) }
+        }
+    };
+    let owner_doc = node.with_base(|base| base.owner_doc);
+    do clone.with_mut_base |base| {
+        base.owner_doc = owner_doc;
+    }
+
+    if deep {
+        for child in node.children() {
+            clone.add_child(clone_node(cx, child, true));
+        }
+    }
+    clone
+}
+
+/// Creates the element for `tag`, with its attributes. Resolves the URLs of images against
+/// `base_url`, but doesn't start any loads.
+fn create_element(cx: *JSContext, tag: &hubbub::Tag, base_url: &Url) -> AbstractNode<ScriptView> {
+    let node = build_element_from_tag(cx, tag.name);
+
+    debug!("-- attach attrs");
+    do node.as_mut_element |element| {
+        for attr in tag.attributes.iter() {
+            element.set_attr(&str(attr.name.clone()), &str(attr.value.clone()));
+        }
+    }
+
+    if node.is_image_element() {
+        do node.with_mut_image_element |image_element| {
+            let (src_opt, srcset_opt, sizes_opt) = {
+                let elem = &image_element.parent.parent;
+                (elem.get_attr("src").map(|x| x.to_str()),
+                 elem.get_attr("srcset").map(|x| x.to_str()),
+                 elem.get_attr("sizes").map(|x| x.to_str()))
+            };
+            image_element.src = do src_opt.map_move |src| {
+                make_url(src, Some(base_url.clone()))
+            };
+            for srcset in srcset_opt.iter() {
+                image_element.srcset = parse_srcset(*srcset, base_url);
+            }
+            for sizes in sizes_opt.iter() {
+                image_element.sizes = parse_sizes(*sizes);
+            }
+            if image_element.srcset.is_empty() {
+                image_element.image = image_element.src.clone();
+            }
+        }
+    }
+    node
+}
+
+/// Returns a tree handler that builds nodes and puts them together, without running scripts or
+/// loading anything. The document parser overrides the parts that kick off loads.
+fn tree_handler(cx: *JSContext, base_url: Url) -> hubbub::TreeHandler {
+    hubbub::TreeHandler {
+        create_comment: |data: ~str| {
+            debug!("create comment");
+            unsafe {
+                Node::as_abstract_node(cx, @Comment::new(data)).to_hubbub_node()
+            }
+        },
+        create_doctype: |doctype: ~hubbub::Doctype| {
+            debug!("create doctype");
+            let ~hubbub::Doctype {name: name,
+                                public_id: public_id,
+                                system_id: system_id,
+                                force_quirks: force_quirks } = doctype;
+            let node = @Doctype::new(name,
+                                     public_id,
+                                     system_id,
+                                     force_quirks);
+            unsafe {
+                Node::as_abstract_node(cx, node).to_hubbub_node()
+            }
+        },
+        create_element: |tag: ~hubbub::Tag| {
+            debug!("create element");
+            unsafe { create_element(cx, tag, &base_url).to_hubbub_node() }
+        },
+        create_text: |data: ~str| {
+            debug!("create text");
+            unsafe {
+                Node::as_abstract_node(cx, @Text::new(data)).to_hubbub_node()
+            }
+        },
+        ref_node: |_| {},
+        unref_node: |_| {},
+        append_child: |parent: hubbub::NodeDataPtr, child: hubbub::NodeDataPtr| {
+            unsafe {
+                debug!("append child %x %x", cast::transmute(parent), cast::transmute(child));
+                let parent: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(parent);
+                let child: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(child);
+                append_child(parent, child).to_hubbub_node()
+            }
+        },
+        insert_before: |parent: hubbub::NodeDataPtr,
+                        child: hubbub::NodeDataPtr,
+                        before: hubbub::NodeDataPtr| {
+            debug!("insert before");
+            unsafe {
+                let parent: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(parent);
+                let child: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(child);
+                let before: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(before);
+                insert_before(parent, child, before).to_hubbub_node()
+            }
+        },
+        remove_child: |parent: hubbub::NodeDataPtr, child: hubbub::NodeDataPtr| {
+            debug!("remove child");
+            unsafe {
+                let parent: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(parent);
+                let child: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(child);
+                parent.remove_child(child);
+            }
+            child
+        },
+        clone_node: |node: hubbub::NodeDataPtr, deep: bool| {
+            debug!("clone node");
+            unsafe {
+                let node: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(node);
+                clone_node(cx, node, deep).to_hubbub_node()
+            }
+        },
+        reparent_children: |node: hubbub::NodeDataPtr, new_parent: hubbub::NodeDataPtr| {
+            debug!("reparent children");
+            unsafe {
+                let node: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(node);
+                let new_parent: AbstractNode<ScriptView> =
+                    NodeWrapping::from_hubbub_node(new_parent);
+                reparent_children(node, new_parent);
+            }
+            new_parent
+        },
+        get_parent: |node: hubbub::NodeDataPtr, element_only: bool| {
+            debug!("get parent");
+            unsafe {
+                let node: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(node);
+                match node.parent_node() {
+                    Some(parent) if !element_only || parent.is_element() => {
+                        parent.to_hubbub_node()
+                    }
+                    _ => 0u,
+                }
+            }
+        },
+        has_children: |node: hubbub::NodeDataPtr| {
+            debug!("has children");
+            unsafe {
+                let node: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(node);
+                node.first_child().is_some()
+            }
+        },
+        form_associate: |_form, _node| {
+            debug!("form associate");
+        },
+        add_attributes: |node: hubbub::NodeDataPtr, attributes: ~[hubbub::Attribute]| {
+            // Attributes of a repeated `<html>` or `<body>` tag go to the first one, unless it
+            // already has them.
+            debug!("add attributes");
+            unsafe {
+                let node: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(node);
+                do node.as_mut_element |element| {
+                    for attr in attributes.iter() {
+                        if element.get_attr(attr.name).is_none() {
+                            element.set_attr(&str(attr.name.clone()), &str(attr.value.clone()));
+                        }
+                    }
+                }
+            }
+        },
+        set_quirks_mode: |_mode| {
+            debug!("set quirks mode");
+        },
+        encoding_change: |_encname| {
+            debug!("encoding change");
+        },
+        complete_script: |_script| {
+            debug!("complete script");
+        },
+        complete_style: |_style| {
+            debug!("complete style");
+        },
+    }
+}
+
+/// Parses `markup` as the contents of `context` and returns the nodes it makes up, for
+/// `innerHTML` and friends. Scripts in the markup aren't run.
+///
+/// FIXME: Hubbub doesn't expose its fragment parsing mode, so this parses a document in which the
+/// markup appears inside elements like `context` and takes the nodes out of the innermost one.
+/// Stylesheets and subframes in the markup aren't loaded.
+pub fn parse_fragment(cx: *JSContext,
+                      context: AbstractNode<ScriptView>,
+                      markup: &str,
+                      base_url: &Url)
+                      -> ~[AbstractNode<ScriptView>] {
+    let context_name = do context.with_imm_element |element| {
+        element.tag_name.to_ascii_lower()
+    };
+    // The elements to put the markup in, innermost last, under `<body>`.
+    let wrappers: ~[&str] = match context_name.as_slice() {
+        // The contents of these aren't markup.
+        "script" | "style" | "textarea" | "title" | "xmp" | "iframe" | "noembed" | "noframes" |
+        "noscript" | "plaintext" => {
+            let text = unsafe { Node::as_abstract_node(cx, @Text::new(markup.to_str())) };
+            return ~[text]
+        }
+        "table" => ~["table"],
+        "caption" | "colgroup" | "tbody" | "thead" | "tfoot" => {
+            ~["table", context_name.as_slice()]
+        }
+        "tr" => ~["table", "tbody", "tr"],
+        "td" | "th" => ~["table", "tbody", "tr", context_name.as_slice()],
+        "select" => ~["select"],
+        _ => ~[],
+    };
+
+    let mut source = ~"";
+    for wrapper in wrappers.iter() {
+        source.push_str(fmt!("<%s>", *wrapper));
+    }
+    source.push_str(markup);
+
+    let root = create_document_root(cx);
+    let mut parser = hubbub::Parser("UTF-8", false);
+    parser.set_document_node(unsafe { root.to_hubbub_node() });
+    parser.enable_scripting(true);
+    parser.set_tree_handler(~tree_handler(cx, base_url.clone()));
+    parser.parse_chunk(source.as_bytes());
+
+    fn is_element_named(node: &AbstractNode<ScriptView>, name: &str) -> bool {
+        node.is_element() && do node.with_imm_element |element| {
+            element.tag_name.eq_ignore_ascii_case(name)
+        }
+    }
+    let mut container = match root.traverse_preorder().find(|node| is_element_named(node, "body")) {
+        Some(body) => body,
+        None => return ~[],
+    };
+    for wrapper in wrappers.iter() {
+        match container.children().find(|child| is_element_named(child, *wrapper)) {
+            Some(child) => container = child,
+            None => return ~[],
+        }
+    }
+
+    let nodes: ~[AbstractNode<ScriptView>] = container.children().collect();
+    for &node in nodes.iter() {
+        container.remove_child(node);
+    }
+    nodes
+}
+
+/// Creates the root element of a document, for `parse_html` to parse the document into.
+pub fn create_document_root(cx: *JSContext) -> AbstractNode<ScriptView> {
+    let root = @HTMLHtmlElement { parent: HTMLElement::new(HTMLHtmlElementTypeId, ~"html") };
+    unsafe { Node::as_abstract_node(cx, root) }
+}
+
+/// Parses the document `load_data` asks for into `root`. Scripts run as the parser reaches them
+/// if the page of `cx` has a document to run them in; otherwise they aren't run.
 pub fn parse_html(cx: *JSContext,
-                  url: Url,
+                  root: AbstractNode<ScriptView>,
+                  load_data: LoadData,
                   resource_task: ResourceTask,
                   image_cache_task: ImageCacheTask,
                   next_subpage_id: SubpageId,
                   constellation_chan: ConstellationChan) -> HtmlParserResult {
+    let url = load_data.url.clone();
     debug!("Hubbub: parsing %?", url);
     // Spawn a CSS parser to receive links to CSS style sheets.
     let resource_task2 = resource_task.clone();
@@ -338,65 +800,51 @@ pub fn parse_html(cx: *JSContext,
     let url2 = url.clone();
     let url3 = url.clone();
 
-    // Build the root node.
-    let root = @HTMLHtmlElement { parent: HTMLElement::new(HTMLHtmlElementTypeId, ~"html") };
-    let root = unsafe { Node::as_abstract_node(cx, root) };
-    debug!("created new node");
     let mut parser = hubbub::Parser("UTF-8", false);
     debug!("created parser");
+    // Scripts insert the text they write into the document through this while the parser is
+    // waiting on them.
+    let parser_ptr = ptr::to_mut_unsafe_ptr(&mut parser);
+    // Style sheets are numbered in the order they're found, which is the order layout gets them
+    // in, so that `<link>` elements can enable and disable theirs later.
+    let mut stylesheet_count = 0u;
+    let stylesheet_count_ptr = ptr::to_mut_unsafe_ptr(&mut stylesheet_count);
     parser.set_document_node(unsafe { root.to_hubbub_node() });
     parser.enable_scripting(true);
     parser.enable_styling(true);
 
     let (css_chan2, css_chan3, js_chan2) = (css_chan.clone(), css_chan.clone(), js_chan.clone());
+    let resource_task3 = resource_task.clone();
     let next_subpage_id = Cell::new(next_subpage_id);
     
     parser.set_tree_handler(~hubbub::TreeHandler {
-        create_comment: |data: ~str| {
-            debug!("create comment");
-            unsafe {
-                Node::as_abstract_node(cx, @Comment::new(data)).to_hubbub_node()
-            }
-        },
-        create_doctype: |doctype: ~hubbub::Doctype| {
-            debug!("create doctype");
-            let ~hubbub::Doctype {name: name,
-                                public_id: public_id,
-                                system_id: system_id,
-                                force_quirks: force_quirks } = doctype;
-            let node = @Doctype::new(name,
-                                     public_id,
-                                     system_id,
-                                     force_quirks);
-            unsafe {
-                Node::as_abstract_node(cx, node).to_hubbub_node()
-            }
-        },
         create_element: |tag: ~hubbub::Tag| {
             debug!("create element");
-            let node = build_element_from_tag(cx, tag.name);
-
-            debug!("-- attach attrs");
-            do node.as_mut_element |element| {
-                for attr in tag.attributes.iter() {
-                    element.set_attr(&str(attr.name.clone()), &str(attr.value.clone()));
-                }
-            }
+            let node = create_element(cx, tag, &url2);
 
             // Spawn additional parsing, network loads, etc. from tag and attrs
             match node.type_id() {
                 // Handle CSS style sheets from <link> elements
                 ElementNodeTypeId(HTMLLinkElementTypeId) => {
-                    do node.with_imm_element |element| {
+                    let found = do node.with_imm_element |element| {
                         match (element.get_attr("rel"), element.get_attr("href")) {
-                            (Some(rel), Some(href)) => {
-                                if rel == "stylesheet" {
-                                    debug!("found CSS stylesheet: %s", href);
-                                    let url = make_url(href.to_str(), Some(url2.clone()));
-                                    css_chan2.send(CSSTaskNewFile(UrlProvenance(url)));
-                                }
+                            (Some(rel), Some(href)) if is_stylesheet_rel(rel) => {
+                                debug!("found CSS stylesheet: %s", href);
+                                let url = make_url(href.to_str(), Some(url2.clone()));
+                                let media = element.get_attr("media").unwrap_or_default("");
+                                let media = MediaQueryList::parse(media);
+                                css_chan2.send(CSSTaskNewFile(UrlProvenance(url), media));
+                                true
+                            }
+                            _ => false
+                        }
+                    };
+                    if found {
+                        do node.with_mut_link_element |link| {
+                            unsafe {
+                                link.stylesheet = Some(*stylesheet_count_ptr);
+                                *stylesheet_count_ptr += 1;
                             }
-                            _ => {}
                         }
                     }
                 }
@@ -437,19 +885,14 @@ pub fn parse_html(cx: *JSContext,
                 }
 
                 ElementNodeTypeId(HTMLImageElementTypeId) => {
-                    do node.with_mut_image_element |image_element| {
-                        let elem = &mut image_element.parent.parent;
-                        let src_opt = elem.get_attr("src").map(|x| x.to_str());
-                        match src_opt {
-                            None => {}
-                            Some(src) => {
-                                let img_url = make_url(src, Some(url2.clone()));
-                                image_element.image = Some(img_url.clone());
-                                // inform the image cache to load this, but don't store a handle.
-                                // TODO (Issue #84): don't prefetch if we are within a <noscript>
-                                // tag.
-                                image_cache_task.send(image_cache_task::Prefetch(img_url));
-                            }
+                    do node.with_imm_image_element |image_element| {
+                        // Which of the `srcset` candidates to load depends on the window, so the
+                        // script task picks one once there is a window.
+                        for img_url in image_element.image.iter() {
+                            // inform the image cache to load this, but don't store a handle.
+                            // TODO (Issue #84): don't prefetch if we are within a <noscript>
+                            // tag.
+                            image_cache_task.send(image_cache_task::Prefetch(img_url.clone()));
                         }
                     }
                 }
@@ -459,80 +902,59 @@ pub fn parse_html(cx: *JSContext,
 
             unsafe { node.to_hubbub_node() }
         },
-        create_text: |data: ~str| {
-            debug!("create text");
-            unsafe {
-                Node::as_abstract_node(cx, @Text::new(data)).to_hubbub_node()
-            }
-        },
-        ref_node: |_| {},
-        unref_node: |_| {},
-        append_child: |parent: hubbub::NodeDataPtr, child: hubbub::NodeDataPtr| {
-            unsafe {
-                debug!("append child %x %x", cast::transmute(parent), cast::transmute(child));
-                let parent: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(parent);
-                let child: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(child);
-                parent.add_child(child);
-            }
-            child
-        },
-        insert_before: |_parent, _child| {
-            debug!("insert before");
-            0u
-        },
-        remove_child: |_parent, _child| {
-            debug!("remove child");
-            0u
-        },
-        clone_node: |_node, deep| {
-            debug!("clone node");
-            if deep { error!("-- deep clone unimplemented"); }
-            fail!(~"clone node unimplemented")
-        },
-        reparent_children: |_node, _new_parent| {
-            debug!("reparent children");
-            0u
-        },
-        get_parent: |_node, _element_only| {
-            debug!("get parent");
-            0u
-        },
-        has_children: |_node| {
-            debug!("has children");
-            false
-        },
-        form_associate: |_form, _node| {
-            debug!("form associate");
-        },
-        add_attributes: |_node, _attributes| {
-            debug!("add attributes");
-        },
-        set_quirks_mode: |_mode| {
-            debug!("set quirks mode");
-        },
-        encoding_change: |_encname| {
-            debug!("encoding change");
-        },
         complete_script: |script| {
-            // A little function for holding this lint attr
+            // Scripts that aren't `async` or `defer` block the parser: they run as soon as it
+            // reaches their end tags, and what they write into the document is parsed right
+            // after them. Returns the source of the script to run now, if any, and its URL.
             fn complete_script(script: hubbub::NodeDataPtr,
                                url: Url,
-                               js_chan: SharedChan<JSMessage>) {
+                               js_chan: SharedChan<JSMessage>,
+                               resource_task: ResourceTask)
+                               -> Option<(~[u8], Url)> {
                 unsafe {
                     let script: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(script);
-                    do script.with_imm_element |script| {
-                        match script.get_attr("src") {
-                            Some(src) => {
-                                debug!("found script: %s", src);
-                                let new_url = make_url(src.to_str(), Some(url.clone()));
+                    let (src, deferred) = do script.with_imm_element |script| {
+                        (script.get_attr("src").map(|src| src.to_str()),
+                         script.get_attr("async").is_some() || script.get_attr("defer").is_some())
+                    };
+                    match src {
+                        Some(src) => {
+                            debug!("found script: %s", src);
+                            let new_url = make_url(src, Some(url.clone()));
+                            if deferred {
                                 js_chan.send(JSTaskNewFile(new_url));
+                                None
+                            } else {
+                                do load_script(&new_url, &resource_task).map_move |source| {
+                                    (source, new_url.clone())
+                                }
                             }
-                            None => {}
                         }
+                        None => {
+                            let mut source = ~"";
+                            for child in script.children().filter(|child| child.is_text()) {
+                                do child.with_imm_text |text| {
+                                    source.push_str(text.parent.data.to_str());
+                                }
+                            }
+                            Some((source.into_bytes(), url))
+                        }
+                    }
+                }
+            }
+            let to_run = complete_script(script,
+                                         url3.clone(),
+                                         js_chan2.clone(),
+                                         resource_task3.clone());
+            for &(ref source, ref script_url) in to_run.iter() {
+                unsafe {
+                    let inserted_text = (*page_from_context(cx)).run_parser_script(source.clone(),
+                                                                                 script_url);
+                    if !inserted_text.is_empty() {
+                        (*parser_ptr).insert_chunk(inserted_text.as_bytes());
                     }
                 }
             }
-            complete_script(script, url3.clone(), js_chan2.clone());
             debug!("complete script");
         },
         complete_style: |style| {
@@ -552,20 +974,31 @@ pub fn parse_html(cx: *JSContext,
                 }
 
                 debug!("data = %?", data);
+                let media = do style.with_imm_element |element| {
+                    MediaQueryList::parse(element.get_attr("media").unwrap_or_default(""))
+                };
                 let provenance = InlineProvenance(url_cell.take().unwrap(), data.concat());
-                css_chan3.send(CSSTaskNewFile(provenance));
+                css_chan3.send(CSSTaskNewFile(provenance, media));
+                *stylesheet_count_ptr += 1;
             }
         },
+        .. tree_handler(cx, url.clone())
     });
     debug!("set tree handler");
 
     let (input_port, input_chan) = comm::stream();
-    resource_task.send(Load(url.clone(), input_chan));
+    resource_task.send(Load(load_data, input_chan));
     debug!("loaded page");
+    // Hand each chunk to the parser as soon as it arrives, so that parsing (and the loads it
+    // kicks off for stylesheets, scripts and subframes) overlaps with the download.
+    let mut progress = LoadProgress::new();
     loop {
-        match input_port.recv() {
+        let msg = input_port.recv();
+        progress.update(&msg);
+        match msg {
+            Headers(*) => {}
             Payload(data) => {
-                debug!("received data");
+                debug!("received data: %u of %? bytes", progress.loaded, progress.total);
                 parser.parse_chunk(data);
             }
             Done(Err(*)) => {
@@ -581,7 +1014,6 @@ pub fn parse_html(cx: *JSContext,
     js_chan.send(JSTaskExit);
 
     HtmlParserResult {
-        root: root,
         discovery_port: discovery_port,
     }
 }
@@ -0,0 +1,133 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Serializes DOM trees back to HTML, for `innerHTML` and `outerHTML`.
+
+use dom::node::{AbstractNode, Comment, CommentNodeTypeId, Doctype, DoctypeNodeTypeId};
+use dom::node::{DocumentFragmentNodeTypeId, ElementNodeTypeId, ScriptView, TextNodeTypeId};
+
+use std::ascii::StrAsciiExt;
+use servo_util::tree::TreeNodeRef;
+
+/// Returns the HTML for the children of `node`.
+pub fn serialize_children(node: AbstractNode<ScriptView>) -> ~str {
+    let mut html = ~"";
+    for child in node.children() {
+        serialize_node(child, &mut html);
+    }
+    html
+}
+
+/// Returns the HTML for `node` and its descendants.
+pub fn serialize(node: AbstractNode<ScriptView>) -> ~str {
+    let mut html = ~"";
+    serialize_node(node, &mut html);
+    html
+}
+
+fn serialize_node(node: AbstractNode<ScriptView>, html: &mut ~str) {
+    match node.type_id() {
+        ElementNodeTypeId(_) => serialize_element(node, html),
+        TextNodeTypeId => {
+            let data = do node.with_imm_text |text| {
+                text.parent.data.to_str()
+            };
+            let parent_is_raw_text = do node.parent_node().map_default(false) |parent| {
+                parent.is_element() && do parent.with_imm_element |element| {
+                    is_raw_text_element(element.tag_name)
+                }
+            };
+            if parent_is_raw_text {
+                html.push_str(data);
+            } else {
+                html.push_str(escape(data, false));
+            }
+        }
+        CommentNodeTypeId => {
+            do node.transmute |comment: &Comment| {
+                html.push_str(fmt!("<!--%s-->", comment.parent.data.to_str()));
+            }
+        }
+        DoctypeNodeTypeId => {
+            do node.transmute |doctype: &Doctype<ScriptView>| {
+                html.push_str(fmt!("<!DOCTYPE %s>", doctype.name));
+            }
+        }
+        DocumentFragmentNodeTypeId => {
+            for child in node.children() {
+                serialize_node(child, html);
+            }
+        }
+    }
+}
+
+fn serialize_element(node: AbstractNode<ScriptView>, html: &mut ~str) {
+    let tag_name = do node.with_imm_element |element| {
+        html.push_char('<');
+        html.push_str(element.tag_name.to_ascii_lower());
+        for attr in element.attrs.iter() {
+            html.push_str(fmt!(" %s=\"%s\"", attr.name, escape(attr.value, true)));
+        }
+        html.push_char('>');
+        element.tag_name.to_ascii_lower()
+    };
+
+    if is_void_element(tag_name) {
+        return
+    }
+
+    // A newline right after these start tags is dropped by the parser, so one at the start of the
+    // content has to be doubled to survive.
+    if "pre" == tag_name || "textarea" == tag_name || "listing" == tag_name {
+        let starts_with_newline = do node.first_child().map_default(false) |child| {
+            child.is_text() && do child.with_imm_text |text| {
+                text.parent.data.to_str().starts_with("\n")
+            }
+        };
+        if starts_with_newline {
+            html.push_char('\n');
+        }
+    }
+
+    for child in node.children() {
+        serialize_node(child, html);
+    }
+    html.push_str(fmt!("</%s>", tag_name));
+}
+
+/// Escapes `text` for text content, or for a double-quoted attribute value if `attribute` is set.
+fn escape(text: &str, attribute: bool) -> ~str {
+    let mut escaped = ~"";
+    for c in text.iter() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '\u00a0' => escaped.push_str("&nbsp;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '<' if !attribute => escaped.push_str("&lt;"),
+            '>' if !attribute => escaped.push_str("&gt;"),
+            c => escaped.push_char(c),
+        }
+    }
+    escaped
+}
+
+/// Returns whether elements named `tag_name` can't have content, so have no end tag.
+fn is_void_element(tag_name: &str) -> bool {
+    match tag_name {
+        "area" | "base" | "basefont" | "bgsound" | "br" | "col" | "embed" | "frame" | "hr" |
+        "img" | "input" | "keygen" | "link" | "meta" | "param" | "source" | "track" | "wbr" => {
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether the text in elements named `tag_name` is written out as it is.
+fn is_raw_text_element(tag_name: &str) -> bool {
+    match tag_name.to_ascii_lower().as_slice() {
+        "style" | "script" | "xmp" | "iframe" | "noembed" | "noframes" | "plaintext" |
+        "noscript" => true,
+        _ => false,
+    }
+}
@@ -6,14 +6,18 @@
 /// coupling between these two components, and enables the DOM to be placed in a separate crate
 /// from layout.
 
+use dom::element::ElementSnapshot;
 use dom::node::{AbstractNode, ScriptView, LayoutView};
 use script_task::{ScriptChan};
 use std::comm::{Chan, SharedChan};
 use geom::rect::Rect;
 use geom::size::Size2D;
 use geom::point::Point2D;
-use gfx::geometry::Au;
-use newcss::stylesheet::Stylesheet;
+use gfx::geometry::{Au, to_frac_px};
+use servo_msg::compositor_msg::NativeOverlay;
+use servo_util::media_queries::StylesheetSource;
+use servo_util::memory::MemoryReport;
+use servo_util::scrollbar::{SCROLLBAR_WIDTH, Scrollbar};
 use extra::url::Url;
 
 /// Asynchronous messages that script can send to layout.
@@ -21,21 +25,36 @@ use extra::url::Url;
 /// FIXME(pcwalton): I think this should probably be merged with `LayoutQuery` below.
 pub enum Msg {
     /// Adds the given stylesheet to the document.
-    AddStylesheetMsg(Stylesheet),
+    AddStylesheetMsg(StylesheetSource),
+
+    /// Enables or disables a stylesheet, given by its index in the order they were added. Sheets
+    /// start out enabled.
+    SetStylesheetEnabledMsg(uint, bool),
+
+    /// Replaces a stylesheet, given by its index in the order they were added, after script
+    /// changed its rules.
+    ReplaceStylesheetMsg(uint, StylesheetSource),
 
     /// Requests a reflow.
     ReflowMsg(~Reflow),
 
     /// Performs a synchronous layout request.
-    ///
-    /// FIXME(pcwalton): As noted below, this isn't very type safe.
     QueryMsg(LayoutQuery),
 
+    /// Replaces the native overlays the embedder registered. Layout reports the geometry of their
+    /// elements to the compositor along with every display list it builds.
+    SetNativeOverlaysMsg(~[NativeOverlay]),
+
+    /// Asks how much layout is holding on to, for the memory profiler.
+    ReportMemoryMsg(Chan<~[MemoryReport]>),
+
     /// Requests that the layout task shut down and exit.
     ExitMsg,
 }
 
-/// Synchronous messages that script can send to layout.
+/// Synchronous messages that script can send to layout. Each carries the channel layout answers
+/// on, whose type is that of the answer; script's `query_layout` makes the channel, so that the
+/// port it waits on always matches the query.
 pub enum LayoutQuery {
     /// Requests the dimensions of the content box, as in the `getBoundingClientRect()` call.
     ContentBoxQuery(AbstractNode<ScriptView>, Chan<Result<ContentBoxResponse, ()>>),
@@ -43,11 +62,76 @@ pub enum LayoutQuery {
     ContentBoxesQuery(AbstractNode<ScriptView>, Chan<Result<ContentBoxesResponse, ()>>),
     /// Requests the node containing the point of interest
     HitTestQuery(AbstractNode<ScriptView>, Point2D<f32>, Chan<Result<HitTestResponse, ()>>),
+    /// Requests the text node under the point of interest, and where in its text a caret placed
+    /// there goes.
+    CaretPositionQuery(AbstractNode<ScriptView>,
+                       Point2D<f32>,
+                       Chan<Result<CaretPositionResponse, ()>>),
+    /// Requests the geometry of an element that scrolls its overflow, for script to drive its
+    /// scrollbars. Fails for elements that don't.
+    ScrollAreaQuery(AbstractNode<ScriptView>, Chan<Result<ScrollArea, ()>>),
+    /// Requests the computed values of the CSS properties of an element, for the developer tools.
+    /// Fails for nodes that haven't been styled.
+    ComputedStyleQuery(AbstractNode<ScriptView>, Chan<Result<ComputedStyleResponse, ()>>),
 }
 
 pub struct ContentBoxResponse(Rect<Au>);
 pub struct ContentBoxesResponse(~[Rect<Au>]);
 pub struct HitTestResponse(AbstractNode<LayoutView>);
+/// A text node, and how many of the characters laid out for it come before the caret. These are
+/// characters of its text after whitespace compression.
+pub struct CaretPositionResponse(AbstractNode<LayoutView>, uint);
+/// The names of CSS properties and descriptions of their computed values.
+pub struct ComputedStyleResponse(~[(~str, ~str)]);
+
+/// The geometry of an element that scrolls its overflow, as of the last display list built.
+#[deriving(Clone, Eq)]
+pub struct ScrollArea {
+    /// The part of the padding box the content shows through, in page coordinates. The scrollbars
+    /// go along its right and bottom edges.
+    view: Rect<Au>,
+    /// How big the content is, including what's scrolled out of view.
+    content_size: Size2D<Au>,
+    vertical_scrollbar: bool,
+    horizontal_scrollbar: bool,
+}
+
+impl ScrollArea {
+    /// The vertical scrollbar, whose track runs down the right edge of the view.
+    pub fn vertical(&self) -> Scrollbar {
+        let view = to_frac_px(self.view.size.height) as f32;
+        Scrollbar::new(view, view, to_frac_px(self.content_size.height) as f32)
+    }
+
+    /// The horizontal scrollbar, whose track runs along the bottom edge of the view.
+    pub fn horizontal(&self) -> Scrollbar {
+        let view = to_frac_px(self.view.size.width) as f32;
+        Scrollbar::new(view, view, to_frac_px(self.content_size.width) as f32)
+    }
+
+    /// Keeps a scroll offset, in px, within the content.
+    pub fn clamp_offset(&self, offset: Point2D<f32>) -> Point2D<f32> {
+        Point2D(self.horizontal().clamp_offset(offset.x), self.vertical().clamp_offset(offset.y))
+    }
+
+    /// The scrollbar at `point`, in page coordinates, if there's one there: whether it's the
+    /// vertical one, and how far along its track the point is.
+    pub fn scrollbar_at(&self, point: Point2D<f32>) -> Option<(bool, f32)> {
+        let left = to_frac_px(self.view.origin.x) as f32;
+        let top = to_frac_px(self.view.origin.y) as f32;
+        let right = left + to_frac_px(self.view.size.width) as f32;
+        let bottom = top + to_frac_px(self.view.size.height) as f32;
+        if self.vertical_scrollbar && point.x >= right && point.x < right + SCROLLBAR_WIDTH &&
+                point.y >= top && point.y < bottom {
+            Some((true, point.y - top))
+        } else if self.horizontal_scrollbar && point.y >= bottom &&
+                point.y < bottom + SCROLLBAR_WIDTH && point.x >= left && point.x < right {
+            Some((false, point.x - left))
+        } else {
+            None
+        }
+    }
+}
 
 /// Determines which part of the 
 pub enum DocumentDamageLevel {
@@ -74,7 +158,8 @@ impl DocumentDamageLevel {
 ///
 /// Note that this is fairly coarse-grained and is separate from layout's notion of the document
 pub struct DocumentDamage {
-    /// The topmost node in the tree that has changed.
+    /// The topmost node in the tree that has changed. With `MatchSelectorsDocumentDamage`,
+    /// selectors are only matched again for its subtree.
     root: AbstractNode<ScriptView>,
     /// The amount of damage that occurred.
     level: DocumentDamageLevel,
@@ -101,10 +186,21 @@ pub struct Reflow {
     url: Url,
     /// The channel through which messages can be sent back to the script task.
     script_chan: ScriptChan,
-    /// The current window size.
+    /// The current window size, in device pixels.
     window_size: Size2D<uint>,
+    /// The number of device pixels per CSS pixel. Layout lays the page out in CSS pixels.
+    device_pixel_ratio: f32,
+    /// The current scroll position of the page, in pixels.
+    scroll_offset: Point2D<f32>,
+    /// The text node with the editing caret, and the caret's byte offset in its text.
+    caret: Option<(AbstractNode<ScriptView>, uint)>,
+    /// Each text node with selected text, and the byte range of its text that's selected.
+    selection: ~[(AbstractNode<ScriptView>, uint, uint)],
     /// The channel that we send a notification to.
     script_join_chan: Chan<()>,
+    /// The attributes of each element whose attributes changed since the last reflow, or that
+    /// layout hasn't seen yet.
+    snapshots: ~[(AbstractNode<ScriptView>, ElementSnapshot)],
 }
 
 /// Encapsulates a channel to the layout task.
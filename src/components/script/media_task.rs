@@ -0,0 +1,485 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The task that plays the source of a media element. It fetches the whole file, decodes it, and
+//! shows its frames and plays its audio at the pace of a clock of its own. Script controls it
+//! with `MediaMsg`s and hears back about its progress through `MediaEventMsg`s; the frames it
+//! decodes go straight to the compositor, to be shown in the video's layer, and the audio to the
+//! platform's audio output.
+//!
+//! Only WebM files with VP8 video, and WAV and Ogg Vorbis audio files, are supported. The audio
+//! tracks of WebM files aren't played.
+
+use dom::htmlmediaelement::MediaId;
+use script_task::{MediaEventMsg, ScriptChan};
+
+use geom::size::Size2D;
+use servo_msg::compositor_msg::{VideoFrame, VideoFrameSink, VideoLayerId};
+use servo_msg::constellation_msg::PipelineId;
+use servo_net::image::base::Image;
+use servo_net::media::audio;
+use servo_net::media::audio::{AudioSink, Samples};
+use servo_net::media::vpx::Decoder;
+use servo_net::media::webm;
+use servo_net::media::webm::WebM;
+use servo_net::resource_task::{Done, Headers, Load, LoadData, Payload, ResourceTask};
+use extra::time::precise_time_s;
+use extra::url::Url;
+
+use std::cell::Cell;
+use std::comm;
+use std::comm::{Port, SharedChan};
+use std::num::Orderable;
+use std::rt::rtio::RtioTimer;
+use std::rt::io::timer::Timer;
+use std::task::{SingleThreaded, task};
+
+/// How often, in seconds, script hears about the position while the media plays. The spec asks
+/// for every 15 to 250 milliseconds.
+static TIME_UPDATE_INTERVAL: f64 = 0.25;
+
+/// The longest the task sleeps between frames, in milliseconds, so that it answers script
+/// promptly.
+static MAX_SLEEP: f64 = 20.0;
+
+/// How far ahead of the clock, in seconds, audio is written to the audio output, so that it
+/// doesn't run dry between wakeups.
+static AUDIO_LEAD: f64 = 0.2;
+
+/// Messages to a media task.
+pub enum MediaMsg {
+    /// Starts playing from the current position, or from the start if playback ended.
+    PlayMsg,
+    /// Stops playing, keeping the current frame on the screen.
+    PauseMsg,
+    /// Moves to the given position, in seconds.
+    SeekMsg(f64),
+    /// Stops the task.
+    CloseMsg,
+}
+
+/// What a media task tells script.
+pub enum MediaEvent {
+    /// The file was loaded and demuxed: how long it lasts in seconds, and the size of its video.
+    MetadataEvent(f64, Size2D<uint>),
+    /// The position moved, in the course of playing or because of a seek.
+    TimeUpdateEvent(f64),
+    /// Playback stopped at the end, at the given position.
+    EndedEvent(f64),
+    /// The file couldn't be loaded or isn't one that can be played.
+    MediaErrorEvent,
+}
+
+/// Encapsulates a channel to a media task.
+#[deriving(Clone)]
+pub struct MediaChan {
+    chan: SharedChan<MediaMsg>,
+}
+
+impl MediaChan {
+    pub fn new(chan: Chan<MediaMsg>) -> MediaChan {
+        MediaChan {
+            chan: SharedChan::new(chan)
+        }
+    }
+
+    pub fn send(&self, msg: MediaMsg) {
+        self.chan.send(msg);
+    }
+}
+
+/// The video of a WebM file.
+struct VideoStream {
+    webm: WebM,
+    /// The indices of the frames of the video track in `webm`, in order.
+    frames: ~[uint],
+    decoder: Decoder,
+    /// The index into `frames` of the next frame to decode.
+    next_frame: uint,
+}
+
+/// Decoded audio, and where it's played.
+struct AudioStream {
+    samples: Samples,
+    sink: ~AudioSink,
+    /// The next frame of samples to write to the sink.
+    next_frame: uint,
+}
+
+pub struct MediaTask {
+    port: Port<MediaMsg>,
+    owner: ScriptChan,
+    pipeline_id: PipelineId,
+    media_id: MediaId,
+    /// Where to show the frames of the video.
+    video_sink: ~VideoFrameSink:Send,
+    video: Option<VideoStream>,
+    audio: Option<AudioStream>,
+    duration: f64,
+    /// The position, in seconds, as of when the clock last started or stopped.
+    position: f64,
+    /// When the clock last started, if the media is playing.
+    clock_start: Option<f64>,
+    /// The position script last heard about.
+    reported_position: f64,
+}
+
+impl MediaTask {
+    /// Starts a task playing the media at `url`, for the media element with the id `media_id` in
+    /// the given pipeline of the script task `owner`. Returns the channel to the task.
+    pub fn create(url: Url,
+                  owner: ScriptChan,
+                  pipeline_id: PipelineId,
+                  media_id: MediaId,
+                  video_sink: ~VideoFrameSink:Send,
+                  resource_task: ResourceTask)
+                  -> MediaChan {
+        let (port, chan) = comm::stream();
+        let chan = MediaChan::new(chan);
+        let port = Cell::new(port);
+        let url = Cell::new(url);
+        let owner = Cell::new(owner);
+        let pipeline_id = Cell::new(pipeline_id);
+        let video_sink = Cell::new(video_sink);
+        let resource_task = Cell::new(resource_task);
+        let start: ~fn() = || {
+            let owner = owner.take();
+            let pipeline_id = pipeline_id.take();
+            let url = url.take();
+            let media_task = load(&url, &resource_task.take()).chain(|data| {
+                MediaTask::new(port.take(), owner.clone(), pipeline_id.clone(), media_id,
+                               video_sink.take(), &url, data)
+            });
+            match media_task {
+                Some(media_task) => {
+                    let mut media_task = media_task;
+                    media_task.start();
+                }
+                None => owner.send(MediaEventMsg(pipeline_id, media_id, MediaErrorEvent)),
+            }
+        };
+
+        // Media that fails to decode doesn't take the document down with it.
+        let mut the_task = task();
+        the_task.sched_mode(SingleThreaded);
+        the_task.unlinked();
+        the_task.spawn(start);
+        chan
+    }
+
+    /// Decodes `data`, or returns `None` if it can't be played.
+    fn new(port: Port<MediaMsg>,
+           owner: ScriptChan,
+           pipeline_id: PipelineId,
+           media_id: MediaId,
+           video_sink: ~VideoFrameSink:Send,
+           url: &Url,
+           data: ~[u8])
+           -> Option<MediaTask> {
+        let (video, audio, size) = if webm::is_webm(data) {
+            match VideoStream::new(data) {
+                Some((video, size)) => (Some(video), None, size),
+                None => return None,
+            }
+        } else {
+            match audio::decode(data) {
+                Some(samples) => {
+                    let sink = audio::open_sink(url.to_str(), samples.channels, samples.rate);
+                    let audio = AudioStream {
+                        samples: samples,
+                        sink: sink,
+                        next_frame: 0,
+                    };
+                    (None, Some(audio), Size2D(0, 0))
+                }
+                None => return None,
+            }
+        };
+        let duration = match (&video, &audio) {
+            (&Some(ref video), _) => video.duration(),
+            (_, &Some(ref audio)) => audio.samples.duration(),
+            _ => 0.0,
+        };
+
+        owner.send(MediaEventMsg(pipeline_id.clone(), media_id, MetadataEvent(duration, size)));
+        let mut media_task = MediaTask {
+            port: port,
+            owner: owner,
+            pipeline_id: pipeline_id,
+            media_id: media_id,
+            video_sink: video_sink,
+            video: video,
+            audio: audio,
+            duration: duration,
+            position: 0.0,
+            clock_start: None,
+            reported_position: 0.0,
+        };
+        // Show the first frame while the media waits to be played.
+        media_task.show_frames_until(0.0);
+        Some(media_task)
+    }
+
+    fn start(&mut self) {
+        let timer = Timer::new().unwrap();
+        loop {
+            // While paused there's nothing to do but wait for script.
+            if self.clock_start.is_none() {
+                let msg = self.port.recv();
+                if !self.handle_msg(msg) {
+                    return
+                }
+                loop
+            }
+            while self.port.peek() {
+                let msg = self.port.recv();
+                if !self.handle_msg(msg) {
+                    return
+                }
+            }
+            if self.clock_start.is_none() {
+                loop
+            }
+
+            let position = self.clock();
+            self.show_frames_until(position);
+            self.play_audio_until(position + AUDIO_LEAD);
+            let video_done = self.video.map_default(true, |video| video.is_done());
+            if position >= self.duration && video_done {
+                self.position = self.duration;
+                self.clock_start = None;
+                self.report(EndedEvent(self.duration));
+                loop
+            }
+            if position - self.reported_position >= TIME_UPDATE_INTERVAL {
+                self.report(TimeUpdateEvent(position));
+            }
+
+            // Sleep until the next frame is due.
+            let due = match self.video {
+                Some(ref video) => video.next_frame_time().unwrap_or_default(self.duration),
+                None => self.duration,
+            };
+            let wait = ((due - position) * 1000.0).max(&1.0).min(&MAX_SLEEP);
+            timer.sleep(wait as u64);
+        }
+    }
+
+    /// Handles a message from script. Returns false if the task should stop.
+    fn handle_msg(&mut self, msg: MediaMsg) -> bool {
+        match msg {
+            PlayMsg => {
+                if self.clock_start.is_none() {
+                    if self.position >= self.duration {
+                        self.seek(0.0);
+                    }
+                    self.clock_start = Some(precise_time_s());
+                }
+            }
+            PauseMsg => {
+                if self.clock_start.is_some() {
+                    self.position = self.clock();
+                    self.clock_start = None;
+                    // The audio written ahead is dropped, and written again on playing.
+                    let position = self.position;
+                    for audio in self.audio.mut_iter() {
+                        audio.seek(position);
+                    }
+                    self.report(TimeUpdateEvent(self.position));
+                }
+            }
+            SeekMsg(position) => {
+                self.seek(position);
+                self.report(TimeUpdateEvent(self.position));
+            }
+            CloseMsg => return false,
+        }
+        true
+    }
+
+    /// The current position, in seconds.
+    fn clock(&self) -> f64 {
+        match self.clock_start {
+            Some(start) => (self.position + precise_time_s() - start).min(&self.duration),
+            None => self.position,
+        }
+    }
+
+    /// Moves to `position`, decoding from the keyframe before it.
+    fn seek(&mut self, position: f64) {
+        let position = position.max(&0.0).min(&self.duration);
+        for video in self.video.mut_iter() {
+            video.seek(position);
+        }
+        for audio in self.audio.mut_iter() {
+            audio.seek(position);
+        }
+        self.show_frames_until(position);
+        self.position = position;
+        if self.clock_start.is_some() {
+            self.clock_start = Some(precise_time_s());
+        }
+    }
+
+    /// Decodes the frames due by `position`, and shows the last of them.
+    fn show_frames_until(&mut self, position: f64) {
+        let image = match self.video {
+            Some(ref mut video) => video.decode_until(position),
+            None => None,
+        };
+        match image {
+            Some(image) => {
+                let frame = ~VideoFrame {
+                    size: Size2D(image.width, image.height),
+                    data: image.data,
+                };
+                self.video_sink.show_video_frame(self.pipeline_id.clone(),
+                                                 VideoLayerId(*self.media_id),
+                                                 frame);
+            }
+            None => {}
+        }
+    }
+
+    /// Writes the audio due by `position` to the audio output.
+    fn play_audio_until(&mut self, position: f64) {
+        for audio in self.audio.mut_iter() {
+            audio.write_until(position);
+        }
+    }
+
+    fn report(&mut self, event: MediaEvent) {
+        match event {
+            TimeUpdateEvent(position) | EndedEvent(position) => self.reported_position = position,
+            _ => {}
+        }
+        self.owner.send(MediaEventMsg(self.pipeline_id.clone(), self.media_id, event));
+    }
+}
+
+impl VideoStream {
+    /// Demuxes a WebM file and makes a decoder for its video, which has to be VP8. Returns the
+    /// stream and the size of the video.
+    fn new(data: ~[u8]) -> Option<(VideoStream, Size2D<uint>)> {
+        let webm = match webm::demux(data) {
+            Some(webm) => webm,
+            None => return None,
+        };
+        let (track, size) = match webm.video_track() {
+            Some(track) if "V_VP8" == track.codec => {
+                (track.number, Size2D(track.width, track.height))
+            }
+            _ => return None,
+        };
+        let decoder = match Decoder::new() {
+            Some(decoder) => decoder,
+            None => return None,
+        };
+
+        let frames: ~[uint] = webm.frames.iter().enumerate()
+            .filter(|&(_, frame)| frame.track == track)
+            .map(|(index, _)| index)
+            .collect();
+        let video = VideoStream {
+            webm: webm,
+            frames: frames,
+            decoder: decoder,
+            next_frame: 0,
+        };
+        Some((video, size))
+    }
+
+    /// How long the video lasts. Files that don't say last until their last frame.
+    fn duration(&self) -> f64 {
+        match self.webm.duration {
+            Some(duration) => duration,
+            None => self.frames.last_opt().map_default(0.0, |&index| self.webm.frames[index].time),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.next_frame == self.frames.len()
+    }
+
+    /// When the next frame to decode is due, if there is one.
+    fn next_frame_time(&self) -> Option<f64> {
+        if self.is_done() {
+            None
+        } else {
+            Some(self.webm.frames[self.frames[self.next_frame]].time)
+        }
+    }
+
+    /// Moves back to the keyframe before `position`.
+    fn seek(&mut self, position: f64) {
+        let mut keyframe = 0;
+        for (i, &index) in self.frames.iter().enumerate() {
+            let frame = &self.webm.frames[index];
+            if frame.time > position {
+                break
+            }
+            if frame.keyframe {
+                keyframe = i;
+            }
+        }
+        self.next_frame = keyframe;
+    }
+
+    /// Decodes the frames due by `position`, returning the last of them. Frames depend on the
+    /// ones before them, so the frames skipped over still have to be decoded.
+    fn decode_until(&mut self, position: f64) -> Option<Image> {
+        let mut latest = None;
+        while self.next_frame < self.frames.len() {
+            let frame = &self.webm.frames[self.frames[self.next_frame]];
+            if frame.time > position {
+                break
+            }
+            match self.decoder.decode(frame.data) {
+                Some(image) => latest = Some(image),
+                None => debug!("media: couldn't decode the frame at %?", frame.time),
+            }
+            self.next_frame += 1;
+        }
+        latest
+    }
+}
+
+impl AudioStream {
+    /// Drops the audio queued in the sink, and moves to `position`.
+    fn seek(&mut self, position: f64) {
+        self.sink.flush();
+        let frame = (position * self.samples.rate as f64) as uint;
+        self.next_frame = frame.min(&self.samples.frames());
+    }
+
+    /// Writes the samples due by `position` to the sink.
+    fn write_until(&mut self, position: f64) {
+        let end = ((position * self.samples.rate as f64) as uint).min(&self.samples.frames());
+        if end <= self.next_frame {
+            return;
+        }
+        let channels = self.samples.channels;
+        self.sink.write(self.samples.data.slice(self.next_frame * channels, end * channels));
+        self.next_frame = end;
+    }
+}
+
+/// Fetches the whole of the media at `url`.
+fn load(url: &Url, resource_task: &ResourceTask) -> Option<~[u8]> {
+    let (input_port, input_chan) = comm::stream();
+    resource_task.send(Load(LoadData::new(url.clone()), input_chan));
+
+    let mut data = ~[];
+    loop {
+        match input_port.recv() {
+            Headers(*) => {}
+            Payload(payload) => data.push_all(payload),
+            Done(Ok(*)) => return Some(data),
+            Done(Err(*)) => {
+                debug!("media: error loading %s", url.to_str());
+                return None
+            }
+        }
+    }
+}
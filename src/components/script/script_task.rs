@@ -6,65 +6,127 @@
 /// and layout tasks.
 
 use servo_msg::compositor_msg::{ScriptListener, Loading, PerformingLayout};
-use servo_msg::compositor_msg::FinishedLoading;
-use dom::bindings::utils::GlobalStaticData;
-use dom::document::AbstractDocument;
-use dom::element::Element;
+use servo_msg::compositor_msg::{FinishedLoading, SelectPopup, TouchDefaultAllowed};
+use servo_msg::compositor_msg::TouchDefaultPrevented;
+use devtools;
+use dom::bindings::utils::{GlobalStaticData, str};
+use dom::document::{AbstractDocument, SVG};
+use dom::editing;
+use dom::element;
+use dom::element::ElementSnapshot;
 use dom::event::{Event_, ResizeEvent, ReflowEvent, ClickEvent, MouseDownEvent, MouseUpEvent};
+use dom::event::{ScrollEvent, KeyEvent, SelectOptionEvent, BackspaceKey, EnterKey, UpKey};
+use dom::event::{DownKey, Key, CharacterKey, MouseMoveEvent, CopyEvent, PasteEvent};
+use dom::event::{TouchStartEvent, TouchMoveEvent, TouchEndEvent, TouchCancelEvent};
+use dom::event::{TabKey, ShiftTabKey};
+use dom::focus;
+use dom::htmlanchorelement;
+use dom::htmlformelement;
+use dom::htmllinkelement::update_stylesheet_sets;
+use dom::htmloptionelement;
+use dom::htmlselectelement;
 use dom::htmldocument::HTMLDocument;
-use dom::node::{define_bindings};
+use dom::htmlmediaelement;
+use dom::htmlmediaelement::MediaId;
+use dom::node::{AbstractNode, ScriptView, define_bindings};
+use dom::range::BoundaryPoint;
+use dom::selection;
+use dom::touch::Touch;
+use dom::touchevent::TouchEvent;
+use dom::touchevent;
+use dom::touchlist::TouchList;
+use dom::websocket::WebSocketId;
 use dom::window::Window;
-use layout_interface::{AddStylesheetMsg, DocumentDamage};
+use dom::worker::WorkerId;
+use dom::xmlhttprequest::XHRId;
+use layout_interface::{AddStylesheetMsg, CaretPositionQuery, CaretPositionResponse};
+use layout_interface::{ContentBoxQuery, ContentBoxResponse, DocumentDamage};
 use layout_interface::{DocumentDamageLevel, HitTestQuery, HitTestResponse, LayoutQuery};
 use layout_interface::{LayoutChan, MatchSelectorsDocumentDamage, QueryMsg, Reflow};
-use layout_interface::{ReflowDocumentDamage, ReflowForDisplay, ReflowGoal};
-use layout_interface::ReflowMsg;
+use layout_interface::{ReflowDocumentDamage, ReflowForDisplay, ReflowForScriptQuery, ReflowGoal};
+use layout_interface::{ReflowMsg, ScrollArea, ScrollAreaQuery};
 use layout_interface;
+use media_task::MediaEvent;
 use servo_msg::constellation_msg::{ConstellationChan, LoadUrlMsg, NavigationDirection};
 use servo_msg::constellation_msg::{PipelineId, SubpageId, RendererReadyMsg};
-use servo_msg::constellation_msg::{LoadIframeUrlMsg};
+use servo_msg::constellation_msg::{LoadIframeUrlMsg, StructuredCloneData};
 use servo_msg::constellation_msg;
+use servo_msg::devtools_msg::{DevtoolScriptControlMsg, EvaluateJSMsg, GetComputedStyleMsg};
+use servo_msg::devtools_msg::GetDocumentMsg;
 
+use std::cast::transmute;
 use std::cell::Cell;
 use std::comm;
-use std::comm::{Port, SharedChan};
+use std::comm::{Chan, Port, SharedChan};
 use std::io::read_whole_file;
 use std::ptr::null;
+use std::rt::io::timer::Timer;
+use std::rt::rtio::RtioTimer;
 use std::task::{SingleThreaded, task};
 use std::util::replace;
 use dom::window::TimerData;
+use geom::point::Point2D;
+use geom::rect::Rect;
 use geom::size::Size2D;
+use gfx::text::util::uncompressed_offset;
 use html::hubbub_html_parser::HtmlParserResult;
 use html::hubbub_html_parser::{HtmlDiscoveredStyle, HtmlDiscoveredIFrame, HtmlDiscoveredScript};
 use html::hubbub_html_parser;
 use js::JSVAL_NULL;
 use js::global::{global_class, debug_fns};
-use js::glue::RUST_JSVAL_TO_OBJECT;
+use js::glue::{RUST_JSVAL_TO_OBJECT, RUST_DOUBLE_TO_JSVAL};
 use js::jsapi::JSContext;
 use js::jsapi::{JS_CallFunctionValue, JS_GetContextPrivate};
 use js::rust::{Compartment, Cx};
 use js;
-use servo_net::image_cache_task::ImageCacheTask;
-use servo_net::resource_task::ResourceTask;
+use servo_net::image_cache_task::{ImageCacheTask, Prefetch};
+use servo_net::resource_task::{AddVisit, LoadData, ProgressMsg, ResourceTask};
+use servo_net::websocket::WebSocketEvent;
+use servo_util::scrollbar::Thumb;
+use servo_util::time::ProfilerChan;
 use servo_util::tree::TreeNodeRef;
-use servo_util::url::make_url;
 use extra::url::Url;
 use extra::future::{from_value, Future};
+use extra::time::precise_time_ns;
 
 /// Messages used to control the script task.
 pub enum ScriptMsg {
     /// Loads a new URL on the specified pipeline.
-    LoadMsg(PipelineId, Url),
+    LoadMsg(PipelineId, LoadData),
     /// Gives a channel and ID to a layout task, as well as the ID of that layout's parent
     AttachLayoutMsg(NewLayoutInfo),
     /// Executes a standalone script.
     ExecuteMsg(PipelineId, Url),
     /// Instructs the script task to send a navigate message to the constellation.
     NavigateMsg(NavigationDirection),
+    /// Asks the script task to load a new page in place of the given pipeline's, e.g. to submit a
+    /// form.
+    TriggerLoadMsg(PipelineId, LoadData),
     /// Sends a DOM event.
     SendEventMsg(PipelineId, Event_),
     /// Fires a JavaScript timeout.
     FireTimerMsg(PipelineId, ~TimerData),
+    /// Runs the pending `requestAnimationFrame` callbacks for the given pipeline.
+    TickAnimationFrameMsg(PipelineId),
+    /// Delivers progress from the resource task to an `XMLHttpRequest`, along with the
+    /// generation of the request it belongs to.
+    XHRProgressMsg(PipelineId, XHRId, uint, ProgressMsg),
+    /// Delivers what happened to the connection of a `WebSocket`.
+    WebSocketEventMsg(PipelineId, WebSocketId, WebSocketEvent),
+    /// Delivers what the task playing the source of a media element found out or did.
+    MediaEventMsg(PipelineId, MediaId, MediaEvent),
+    /// Delivers a message a worker posted to its `Worker` object in the given pipeline.
+    WorkerPostMessageMsg(PipelineId, WorkerId, StructuredCloneData),
+    /// Tells a `Worker` object in the given pipeline that a script of its worker failed.
+    WorkerErrorMsg(PipelineId, WorkerId, ~str),
+    /// Moves the page in the specified pipeline to the session history entry with the given index
+    /// and URL, for the user traversing the session history.
+    PopStateMsg(PipelineId, uint, Url),
+    /// Delivers a message posted to the window of the first pipeline by that of the second, along
+    /// with the sender's origin and the origin it was meant for, if it was restricted to one.
+    PostMessageMsg(PipelineId, PipelineId, ~str, Option<~str>, StructuredCloneData),
+    /// Passes on a request of the developer tools about the given pipeline's page.
+    DevtoolsMsg(PipelineId, DevtoolScriptControlMsg),
     /// Notifies script that reflow is finished.
     ReflowCompleteMsg(PipelineId),
     /// Notifies script that window has been resized but to not take immediate action.
@@ -113,12 +175,37 @@ pub struct Page {
     /// The port that we will use to join layout. If this is `None`, then layout is not running.
     layout_join_port: Option<Port<()>>,
 
-    /// What parts of the document are dirty, if any.
+    /// What parts of the document are dirty, if any. Changes made by script pile up here and are
+    /// sent to layout once, at the end of the script turn or when script queries layout.
     damage: Option<DocumentDamage>,
 
+    /// Whether layout has reflowed for a script query since it last built a display list, so that
+    /// what's on screen is out of date.
+    display_stale: bool,
+
     /// The current size of the window, in pixels.
     window_size: Future<Size2D<uint>>,
 
+    /// The current scroll position of the page, in pixels.
+    scroll_offset: Point2D<f32>,
+
+    /// The scroll position of the page as of the last reflow.
+    reflow_scroll_offset: Point2D<f32>,
+
+    /// Whether the user is dragging out a selection with the mouse.
+    selecting: bool,
+
+    /// The fingers on the page, with the element each first touched and where it is now.
+    touches: ~[(uint, AbstractNode<ScriptView>, Point2D<f32>)],
+
+    /// Whether the mouse button was pressed on the scrollbar of an element that scrolls its
+    /// overflow, and hasn't been released yet.
+    pressing_scrollbar: bool,
+
+    /// The scrollbar thumb being dragged, if any: the element it scrolls, whether it's the
+    /// vertical scrollbar's, and how far along the thumb it was grabbed.
+    scrollbar_drag: Option<(AbstractNode<ScriptView>, bool, f32)>,
+
     js_info: Option<JSPageInfo>,
 
     /// Cached copy of the most recent url loaded by the script
@@ -127,6 +214,9 @@ pub struct Page {
     /// when reloading.
     url: Option<(Url, bool)>,
 
+    /// The title of the document as the compositor was last told it.
+    title: Option<~str>,
+
     next_subpage_id: SubpageId,
 }
 
@@ -148,9 +238,17 @@ impl PageTree {
                 layout_chan: layout_chan,
                 layout_join_port: None,
                 damage: None,
+                display_stale: false,
                 window_size: size_future,
+                scroll_offset: Point2D(0f32, 0f32),
+                reflow_scroll_offset: Point2D(0f32, 0f32),
+                selecting: false,
+                touches: ~[],
+                pressing_scrollbar: false,
+                scrollbar_drag: None,
                 js_info: None,
                 url: None,
+                title: None,
                 next_subpage_id: SubpageId(0),
             },
             inner: ~[],
@@ -192,18 +290,38 @@ impl<'self> Iterator<@mut Page> for PageTreeIterator<'self> {
 impl Page {
     /// Adds the given damage.
     fn damage(&mut self, level: DocumentDamageLevel) {
+        let root = do self.frame.get_ref().document.with_base |doc| { doc.root };
+        self.damage_subtree(root, level)
+    }
+
+    /// Adds the given damage to the subtree at `root`. Selectors are only matched again for the
+    /// smallest subtree holding all the damaged ones; reflow always covers the whole document.
+    pub fn damage_subtree(&mut self, root: AbstractNode<ScriptView>, level: DocumentDamageLevel) {
         match self.damage {
             None => {}
             Some(ref mut damage) => {
-                // FIXME(pcwalton): This is wrong. We should trace up to the nearest ancestor.
-                damage.root = do self.frame.get_ref().document.with_base |doc| { doc.root };
+                match (damage.level, level) {
+                    (_, ReflowDocumentDamage) => {}
+                    (ReflowDocumentDamage, MatchSelectorsDocumentDamage) => damage.root = root,
+                    (MatchSelectorsDocumentDamage, MatchSelectorsDocumentDamage) => {
+                        // Subtrees in different trees can only be covered by the whole
+                        // document.
+                        damage.root = match damage.root.common_ancestor(root) {
+                            Some(ancestor) => ancestor,
+                            None => match self.frame {
+                                Some(ref frame) => frame.document.with_base(|doc| doc.root),
+                                None => root,
+                            },
+                        };
+                    }
+                }
                 damage.level.add(level);
                 return
             }
         }
 
         self.damage = Some(DocumentDamage {
-            root: do self.frame.get_ref().document.with_base |doc| { doc.root },
+            root: root,
             level: level,
         })
     }
@@ -228,16 +346,33 @@ impl Page {
         }
     }
 
-    /// Sends the given query to layout.
-    pub fn query_layout<T: Send>(&mut self,
-                                 query: LayoutQuery,
-                                 response_port: Port<Result<T, ()>>)
-                                 -> Result<T,()> {
+    /// Sends layout the query `query` makes with the channel for its answer, and waits for the
+    /// answer. The type of the answer is the one the query asks for.
+    pub fn query_layout<T: Send>(&mut self, query: &fn(Chan<Result<T, ()>>) -> LayoutQuery)
+                                 -> Result<T, ()> {
+        self.flush_damage_for_query();
         self.join_layout();
-        self.layout_chan.send(QueryMsg(query));
+        let (response_port, response_chan) = comm::stream();
+        self.layout_chan.send(QueryMsg(query(response_chan)));
         response_port.recv()
     }
 
+    /// Sends layout the changes script has made since the last reflow, if any, so that a query
+    /// sees them. The display list is left for the end of the turn.
+    fn flush_damage_for_query(&mut self) {
+        if self.damage.is_none() {
+            return
+        }
+        let window = self.frame.get_ref().window;
+        self.reflow(ReflowForScriptQuery, window.script_chan.clone(), window.compositor);
+        self.display_stale = true;
+    }
+
+    /// Whether script has changed the page since layout last built a display list for it.
+    pub fn needs_display(&self) -> bool {
+        self.frame.is_some() && (self.damage.is_some() || self.display_stale)
+    }
+
     /// This method will wait until the layout task has completed its current action, join the
     /// layout task, and then request a new layout run. It won't wait for the new layout
     /// computation to finish.
@@ -261,23 +396,150 @@ impl Page {
             None => fail!(~"Tried to relayout with no root frame!"),
             Some(ref frame) => {
                 // Send new document and relevant styles to layout.
+                let document_root = do frame.document.with_base |doc| { doc.root };
                 let reflow = ~Reflow {
-                    document_root: do frame.document.with_base |doc| { doc.root },
+                    document_root: document_root,
                     url: self.url.get_ref().first().clone(),
                     goal: goal,
                     window_size: self.window_size.get(),
+                    device_pixel_ratio: frame.window.metrics.device_pixel_ratio,
+                    scroll_offset: self.scroll_offset,
+                    caret: frame.document.with_base(|doc| doc.caret),
+                    selection: match frame.document.with_base(|doc| doc.selection.clone()) {
+                        Some((ref anchor, ref focus)) => selection::selected_text(anchor, focus),
+                        None => ~[],
+                    },
                     script_chan: script_chan,
                     script_join_chan: join_chan,
                     damage: replace(&mut self.damage, None).unwrap(),
+                    snapshots: snapshot_elements(document_root),
                 };
 
-                self.layout_chan.send(ReflowMsg(reflow))
+                self.layout_chan.send(ReflowMsg(reflow));
+                self.reflow_scroll_offset = self.scroll_offset;
+                if goal == ReflowForDisplay {
+                    self.display_stale = false;
+                }
             }
         }
 
         debug!("script: layout forked")
     }
 
+    /// Marks the entire document dirty. It's reflowed at the end of the script turn.
+    pub fn damage_all(&mut self) {
+        if self.frame.is_some() {
+            self.damage(MatchSelectorsDocumentDamage);
+        }
+    }
+
+    /// Starts loading the sources of the media elements the parser made, unless script already
+    /// did.
+    pub fn load_media(&mut self) {
+        let root = match self.frame {
+            Some(ref frame) => frame.document.with_base(|doc| doc.root),
+            None => return,
+        };
+        for node in root.traverse_preorder() {
+            if !node.is_media_element() {
+                loop
+            }
+            do node.with_mut_media_element |media| {
+                if media.id.is_none() {
+                    media.load(node);
+                }
+            }
+        }
+    }
+
+    /// Picks the image each `<img>` with a `srcset` shows, for the window's current width and
+    /// pixel ratio, and starts loading the ones that changed. If `use_layout` is set, images
+    /// whose pick depends on how wide they're laid out ask layout. Returns whether any image
+    /// changed.
+    pub fn select_image_sources(&mut self, use_layout: bool, image_cache_task: &ImageCacheTask)
+                                -> bool {
+        let (root, window) = match self.frame {
+            Some(ref frame) => (frame.document.with_base(|doc| doc.root), frame.window),
+            None => return false,
+        };
+        let device_pixel_ratio = window.metrics.device_pixel_ratio;
+        let viewport_width = window.metrics.window_size.width as f32 / device_pixel_ratio;
+
+        let mut changed = false;
+        for node in root.traverse_preorder() {
+            if !node.is_image_element() {
+                loop
+            }
+            let (has_srcset, depends_on_layout) = do node.with_imm_image_element |image_element| {
+                (!image_element.srcset.is_empty(), image_element.depends_on_layout())
+            };
+            if !has_srcset {
+                loop
+            }
+
+            let laid_out_width = if use_layout && depends_on_layout {
+                match self.query_layout(|chan| ContentBoxQuery(node, chan)) {
+                    Ok(ContentBoxResponse(rect)) if rect.size.width.to_f32() > 0.0 => {
+                        Some(rect.size.width.to_f32())
+                    }
+                    // Not laid out, e.g. because it's `display: none`, or laid out with no width
+                    // before anything loaded.
+                    _ => None,
+                }
+            } else {
+                None
+            };
+
+            do node.with_mut_image_element |image_element| {
+                let source = image_element.select_source(viewport_width,
+                                                         device_pixel_ratio,
+                                                         laid_out_width);
+                if source != image_element.image {
+                    debug!("script: image source is now %?", source);
+                    for url in source.iter() {
+                        image_cache_task.send(Prefetch(url.clone()));
+                    }
+                    image_element.image = source;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Runs a script that the parser reached in the page's document, and returns the text the
+    /// script wrote into the document with `document.write`. Without a document, e.g. while
+    /// fuzzing the parser, the script isn't run.
+    pub fn run_parser_script(&mut self, source: ~[u8], url: &Url) -> ~str {
+        let document = match self.frame {
+            Some(ref frame) => frame.document,
+            None => return ~"",
+        };
+
+        let inserted_text = @mut ~"";
+        do document.with_mut_base |doc| {
+            doc.script_inserted_text = Some(inserted_text);
+        }
+        let js_info = self.js_info.get_ref();
+        let _ = js_info.js_context.evaluate_script(js_info.js_compartment.global_obj,
+                                                   source,
+                                                   url.to_str(),
+                                                   1);
+        do document.with_mut_base |doc| {
+            doc.script_inserted_text = None;
+        }
+        self.notify_mutation_observers();
+        (*inserted_text).clone()
+    }
+
+    /// Delivers the mutation records queued while script ran. This is our microtask checkpoint,
+    /// reached at the end of each task and after each script the parser runs.
+    pub fn notify_mutation_observers(&self) {
+        for frame in self.frame.iter() {
+            frame.window.notify_mutation_observers();
+        }
+    }
+
     /// Reflows the entire document.
     ///
     /// FIXME: This should basically never be used.
@@ -351,6 +613,8 @@ pub struct ScriptTask {
     image_cache_task: ImageCacheTask,
     /// A handle to the resource task.
     resource_task: ResourceTask,
+    /// A channel to the profiler, for the tasks of canvases to report to.
+    profiler_chan: ProfilerChan,
 
     /// The port on which the script task receives messages (load URL, exit, etc.)
     port: Port<ScriptMsg>,
@@ -365,6 +629,13 @@ pub struct ScriptTask {
 
     /// The JavaScript runtime.
     js_runtime: js::rust::rt,
+
+    /// How long to wait before handling each message, in milliseconds, to reproduce how pages
+    /// behave on slow machines.
+    message_delay: Option<uint>,
+
+    /// The user agent string given to the windows of the pages in this task.
+    user_agent: ~str,
 }
 
 /// Returns the relevant page from the associated JS Context.
@@ -384,7 +655,10 @@ impl ScriptTask {
                constellation_chan: ConstellationChan,
                resource_task: ResourceTask,
                img_cache_task: ImageCacheTask,
-               initial_size: Future<Size2D<uint>>)
+               profiler_chan: ProfilerChan,
+               initial_size: Future<Size2D<uint>>,
+               message_delay: Option<uint>,
+               user_agent: ~str)
                -> @mut ScriptTask {
         let js_runtime = js::rust::rt();
 
@@ -393,6 +667,7 @@ impl ScriptTask {
 
             image_cache_task: img_cache_task,
             resource_task: resource_task,
+            profiler_chan: profiler_chan,
 
             port: port,
             chan: chan,
@@ -400,6 +675,9 @@ impl ScriptTask {
             compositor: compositor,
 
             js_runtime: js_runtime,
+
+            message_delay: message_delay,
+            user_agent: user_agent,
         };
 
         script_task.page_tree.page.initialize_js_info(script_task.js_runtime.cx());
@@ -422,10 +700,14 @@ impl ScriptTask {
                                             constellation_chan: ConstellationChan,
                                             resource_task: ResourceTask,
                                             image_cache_task: ImageCacheTask,
-                                            initial_size: Future<Size2D<uint>>) {
+                                            profiler_chan: ProfilerChan,
+                                            initial_size: Future<Size2D<uint>>,
+                                            message_delay: Option<uint>,
+                                            user_agent: ~str) {
         let compositor = Cell::new(compositor);
         let port = Cell::new(port);
         let initial_size = Cell::new(initial_size);
+        let user_agent = Cell::new(user_agent);
         // FIXME: rust#6399
         let mut the_task = task();
         the_task.sched_mode(SingleThreaded);
@@ -438,21 +720,52 @@ impl ScriptTask {
                                               constellation_chan.clone(),
                                               resource_task.clone(),
                                               image_cache_task.clone(),
-                                              initial_size.take());
+                                              profiler_chan.clone(),
+                                              initial_size.take(),
+                                              message_delay,
+                                              user_agent.take());
             script_task.start();
         }
     }
 
     /// Handles an incoming control message.
     fn handle_msg(&mut self) -> bool {
-        match self.port.recv() {
+        let msg = self.port.recv();
+        for &delay in self.message_delay.iter() {
+            let timer = Timer::new().unwrap();
+            timer.sleep(delay as u64);
+        }
+
+        match msg {
             // TODO(tkuehn) need to handle auxiliary layouts for iframes
             AttachLayoutMsg(new_layout_info) => self.handle_new_layout(new_layout_info),
-            LoadMsg(id, url) => self.load(id, url),
+            LoadMsg(id, load_data) => self.load(id, load_data),
             ExecuteMsg(id, url) => self.handle_execute_msg(id, url),
             SendEventMsg(id, event) => self.handle_event(id, event),
             FireTimerMsg(id, timer_data) => self.handle_fire_timer_msg(id, timer_data),
+            TickAnimationFrameMsg(id) => self.handle_tick_animation_frame_msg(id),
+            XHRProgressMsg(id, xhr_id, generation, progress) => {
+                self.handle_xhr_progress_msg(id, xhr_id, generation, progress)
+            }
+            WebSocketEventMsg(id, socket_id, event) => {
+                self.handle_websocket_event_msg(id, socket_id, event)
+            }
+            MediaEventMsg(id, media_id, event) => {
+                self.handle_media_event_msg(id, media_id, event)
+            }
+            WorkerPostMessageMsg(id, worker_id, data) => {
+                self.handle_worker_post_message_msg(id, worker_id, data)
+            }
+            WorkerErrorMsg(id, worker_id, message) => {
+                self.handle_worker_error_msg(id, worker_id, message)
+            }
             NavigateMsg(direction) => self.handle_navigate_msg(direction),
+            TriggerLoadMsg(id, load_data) => self.handle_trigger_load_msg(id, load_data),
+            PopStateMsg(id, index, url) => self.handle_pop_state_msg(id, index, url),
+            PostMessageMsg(id, source_id, origin, target_origin, data) => {
+                self.handle_post_message_msg(id, source_id, origin, target_origin, data)
+            }
+            DevtoolsMsg(id, msg) => self.handle_devtools_msg(id, msg),
             ReflowCompleteMsg(id) => self.handle_reflow_complete_msg(id),
             ResizeInactiveMsg(id, new_size) => self.handle_resize_inactive_msg(id, new_size),
             ExitMsg => {
@@ -460,9 +773,24 @@ impl ScriptTask {
                 return false
             }
         }
+        for page in self.page_tree.iter() {
+            page.notify_mutation_observers();
+        }
+        self.flush_damage();
         true
     }
 
+    /// Sends layout a single reflow for each page that changed during this turn, however many
+    /// changes script made to it.
+    fn flush_damage(&mut self) {
+        for page in self.page_tree.iter() {
+            if page.needs_display() {
+                page.damage(ReflowDocumentDamage);
+                page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor);
+            }
+        }
+    }
+
     fn handle_new_layout(&mut self, new_layout_info: NewLayoutInfo) {
         debug!("Script: new layout: %?", new_layout_info);
         let NewLayoutInfo {
@@ -525,17 +853,177 @@ impl ScriptTask {
 
         }
         // We don't know what the script changed, so for now we will do a total redisplay.
-        page.reflow_all(ReflowForDisplay, self.chan.clone(), self.compositor);
+        page.damage_all();
+    }
+
+    /// Handles an animation frame tick from the compositor. All callbacks registered for this
+    /// frame run before a single reflow, so that animations don't flush layout per callback.
+    fn handle_tick_animation_frame_msg(&mut self, id: PipelineId) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received animation frame tick for
+            a pipeline ID not associated with this script task. This is a bug.").page;
+        let window = match page.frame {
+            Some(ref frame) => frame.window,
+            None => return,
+        };
+
+        // The window hears about being resized, and whatever scrolled about that, before the
+        // callbacks run.
+        let resized = window.fire_resize_event();
+        let scrolled = window.fire_scroll_events();
+        let callbacks = window.take_animation_frame_callbacks();
+        if callbacks.is_empty() {
+            if resized || scrolled {
+                page.damage_all();
+            }
+            return;
+        }
+
+        let timestamp = RUST_DOUBLE_TO_JSVAL(precise_time_ns() as f64 / 1000000.0);
+        let js_info = page.js_info.get_ref();
+        for &(_, callback) in callbacks.iter() {
+            let rval = JSVAL_NULL;
+            unsafe {
+                JS_CallFunctionValue(js_info.js_context.ptr,
+                                     js_info.js_compartment.global_obj.ptr,
+                                     callback,
+                                     1,
+                                     &timestamp,
+                                     &rval);
+            }
+        }
+
+        // As with timers, we don't know what the callbacks changed.
+        page.damage_all();
+    }
+
+    /// Handles progress on an asynchronous `XMLHttpRequest`.
+    fn handle_xhr_progress_msg(&mut self,
+                               id: PipelineId,
+                               xhr_id: XHRId,
+                               generation: uint,
+                               progress: ProgressMsg) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received XHR progress for a
+            pipeline ID not associated with this script task. This is a bug.").page;
+        let xhr = match page.frame {
+            Some(ref frame) => frame.window.find_xhr(xhr_id),
+            None => None,
+        };
+        let xhr = match xhr {
+            Some(xhr) => xhr,
+            None => return,
+        };
+
+        let events = xhr.process_progress(generation, progress);
+        if !events.is_empty() {
+            xhr.dispatch_events(events);
+            // As with timers, we don't know what the handlers changed.
+            page.damage_all();
+        }
+    }
+
+    /// Handles what happened to the connection of a `WebSocket`.
+    fn handle_websocket_event_msg(&mut self,
+                                  id: PipelineId,
+                                  socket_id: WebSocketId,
+                                  event: WebSocketEvent) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received a WebSocket event for a
+            pipeline ID not associated with this script task. This is a bug.").page;
+        let socket = match page.frame {
+            Some(ref frame) => frame.window.find_websocket(socket_id),
+            None => None,
+        };
+        let socket = match socket {
+            Some(socket) => socket,
+            None => return,
+        };
+
+        let events = socket.process_event(event);
+        if !events.is_empty() {
+            socket.dispatch_events(events);
+            page.damage_all();
+        }
+    }
+
+    /// Handles what the task playing the source of a media element found out or did.
+    fn handle_media_event_msg(&mut self, id: PipelineId, media_id: MediaId, event: MediaEvent) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received a media event for a
+            pipeline ID not associated with this script task. This is a bug.").page;
+        // Nothing is listening any more if the element loaded another source since.
+        let element = match page.frame {
+            Some(ref frame) => frame.window.find_media(media_id),
+            None => None,
+        };
+        match element {
+            Some(element) => {
+                htmlmediaelement::process_event(element, event);
+                page.damage_all();
+            }
+            None => {}
+        }
+    }
+
+    /// Handles a message a worker posted to its `Worker` object.
+    fn handle_worker_post_message_msg(&mut self,
+                                      id: PipelineId,
+                                      worker_id: WorkerId,
+                                      data: StructuredCloneData) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received a worker message for a
+            pipeline ID not associated with this script task. This is a bug.").page;
+        let worker = match page.frame {
+            Some(ref frame) => frame.window.find_worker(worker_id),
+            None => None,
+        };
+        for &worker in worker.iter() {
+            worker.receive_message(data.clone());
+            page.damage_all();
+        }
+    }
+
+    /// Handles a worker whose script failed.
+    fn handle_worker_error_msg(&mut self, id: PipelineId, worker_id: WorkerId, message: ~str) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received a worker error for a
+            pipeline ID not associated with this script task. This is a bug.").page;
+        let worker = match page.frame {
+            Some(ref frame) => frame.window.find_worker(worker_id),
+            None => None,
+        };
+        for &worker in worker.iter() {
+            worker.report_error(message.clone());
+            page.damage_all();
+        }
     }
 
     /// Handles a notification that reflow completed.
     fn handle_reflow_complete_msg(&mut self, pipeline_id: PipelineId) {
         debug!("Script: Reflow complete for %?", pipeline_id);
-        self.page_tree.find(pipeline_id).expect("ScriptTask: received a load
+        let page = self.page_tree.find(pipeline_id).expect("ScriptTask: received a load
             message for a layout channel that is not associated with this script task. This
-            is a bug.").page.layout_join_port = None;
+            is a bug.").page;
+        page.layout_join_port = None;
         self.constellation_chan.send(RendererReadyMsg(pipeline_id));
         self.compositor.set_ready_state(FinishedLoading);
+        self.update_title(page);
+    }
+
+    /// Tells the compositor the title of the page's document, if it changed since it was last
+    /// told.
+    fn update_title(&self, page: @mut Page) {
+        let title = match page.frame {
+            Some(ref frame) => {
+                do frame.document.with_base |doc| {
+                    match doc.doctype {
+                        // SVG documents have no `<title>` we know how to find yet.
+                        SVG => None,
+                        _ => Some(doc.Title().to_str()),
+                    }
+                }
+            }
+            None => None,
+        };
+        if title.is_some() && title != page.title {
+            self.compositor.set_title(page.id, title.get_ref().clone());
+            page.title = title;
+        }
     }
 
     /// Handles a navigate forward or backward message.
@@ -544,6 +1032,49 @@ impl ScriptTask {
         self.constellation_chan.send(constellation_msg::NavigateMsg(direction));
     }
 
+    /// Asks the constellation to load a page in place of the one in the given pipeline.
+    fn handle_trigger_load_msg(&mut self, id: PipelineId, load_data: LoadData) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received a load request for a
+            pipeline that is not associated with a page in the page tree. This is a bug.").page;
+        self.constellation_chan.send(LoadUrlMsg(id, load_data, from_value(page.window_size.get())));
+    }
+
+    /// Handles the user moving to another of a page's session history entries.
+    fn handle_pop_state_msg(&mut self, id: PipelineId, index: uint, url: Url) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received a pop state message for
+            a pipeline that is not associated with a page in the page tree. This is a bug.").page;
+        for frame in page.frame.iter() {
+            frame.window.pop_state(index, url.clone());
+        }
+    }
+
+    /// Fires `message` at the window of the given pipeline.
+    fn handle_post_message_msg(&mut self, id: PipelineId, source_id: PipelineId, origin: ~str,
+                               target_origin: Option<~str>, data: StructuredCloneData) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received a posted message for a
+            pipeline that is not associated with a page in the page tree. This is a bug.").page;
+        for frame in page.frame.iter() {
+            frame.window.receive_message(source_id, origin.clone(), target_origin.clone(),
+                                         data.clone());
+        }
+    }
+
+    /// Answers a request of the developer tools. Requests about pages that have gone away are
+    /// dropped, which the developer tools see as the channel for the answer closing.
+    fn handle_devtools_msg(&mut self, id: PipelineId, msg: DevtoolScriptControlMsg) {
+        let page = match self.page_tree.find(id) {
+            Some(page_tree) => page_tree.page,
+            None => return,
+        };
+        match msg {
+            GetDocumentMsg(reply) => reply.send(devtools::document_info(page)),
+            GetComputedStyleMsg(node_id, reply) => {
+                reply.send(devtools::computed_style(page, node_id))
+            }
+            EvaluateJSMsg(source, reply) => reply.send(devtools::evaluate(page, source)),
+        }
+    }
+
     /// Window was resized, but this script was not active, so don't reflow yet
     fn handle_resize_inactive_msg(&mut self, id: PipelineId, new_size: Size2D<uint>) {
         let page = self.page_tree.find(id).expect("Received resize message for PipelineId not associated
@@ -568,7 +1099,8 @@ impl ScriptTask {
 
     /// The entry point to document loading. Defines bindings, sets up the window and document
     /// objects, parses HTML and CSS, and kicks off initial layout.
-    fn load(&mut self, pipeline_id: PipelineId, url: Url) {
+    fn load(&mut self, pipeline_id: PipelineId, load_data: LoadData) {
+        let url = load_data.url.clone();
         debug!("ScriptTask: loading %? on page %?", url, pipeline_id);
 
         let page = self.page_tree.find(pipeline_id).expect("ScriptTask: received a load
@@ -577,7 +1109,8 @@ impl ScriptTask {
         let last_loaded_url = replace(&mut page.url, None);
         for loaded in last_loaded_url.iter() {
             let (ref loaded, needs_reflow) = *loaded;
-            if *loaded == url {
+            // Submitting a form to the page it's on loads the page again.
+            if *loaded == url && load_data.data.is_none() {
                 page.url = Some((loaded.clone(), false));
                 if needs_reflow {
                     page.reflow_all(ReflowForDisplay, self.chan.clone(), self.compositor);
@@ -598,19 +1131,11 @@ impl ScriptTask {
         }
 
         self.compositor.set_ready_state(Loading);
-        // Parse HTML.
-        //
-        // Note: We can parse the next document in parallel with any previous documents.
-        let html_parsing_result = hubbub_html_parser::parse_html(page.js_info.get_ref().js_compartment.cx.ptr,
-                                                                 url.clone(),
-                                                                 self.resource_task.clone(),
-                                                                 self.image_cache_task.clone(),
-                                                                 page.next_subpage_id.clone(),
-                                                                 self.constellation_chan.clone());
-
-        let HtmlParserResult {root, discovery_port} = html_parsing_result;
 
-        // Create the window and document objects.
+        // Create the window and document objects. Scripts run while the document is parsed, so
+        // they have to exist first.
+        let cx = page.js_info.get_ref().js_compartment.cx.ptr;
+        let root = hubbub_html_parser::create_document_root(cx);
         let window = {
             // Need an extra block here due to Rust #6248
             //
@@ -619,7 +1144,9 @@ impl ScriptTask {
             // pointer.  We think it's safe here because the main task will hold onto the box,
             // and because the current refcounting implementation of @ doesn't move.
             let page = &mut *page;
-            Window::new(page, self.chan.clone(), self.compositor)
+            Window::new(page, self.chan.clone(), self.compositor, self.constellation_chan.clone(),
+                        self.resource_task.clone(), self.image_cache_task.clone(),
+                        self.profiler_chan.clone(), self.user_agent.clone())
         };
         let document = HTMLDocument::new(root, Some(window));
 
@@ -634,6 +1161,25 @@ impl ScriptTask {
             window: window,
         });
         page.url = Some((url.clone(), true));
+        page.touches = ~[];
+        page.pressing_scrollbar = false;
+        page.scrollbar_drag = None;
+
+        // Define debug functions.
+        page.js_info.get_ref().js_compartment.define_functions(debug_fns);
+
+        // Parse HTML.
+        //
+        // Note: We can parse the next document in parallel with any previous documents.
+        let html_parsing_result = hubbub_html_parser::parse_html(cx,
+                                                                 root,
+                                                                 load_data,
+                                                                 self.resource_task.clone(),
+                                                                 self.image_cache_task.clone(),
+                                                                 page.next_subpage_id.clone(),
+                                                                 self.constellation_chan.clone());
+
+        let HtmlParserResult {discovery_port} = html_parsing_result;
 
         // Send style sheets over to layout.
         //
@@ -647,7 +1193,10 @@ impl ScriptTask {
                     assert!(js_scripts.is_none());
                     js_scripts = Some(scripts);
                 }
-                Some(HtmlDiscoveredStyle(sheet)) => {
+                Some(HtmlDiscoveredStyle(sheet, href)) => {
+                    do document.with_mut_base |document| {
+                        document.add_stylesheet(sheet.clone(), href.clone());
+                    }
                     page.layout_chan.send(AddStylesheetMsg(sheet));
                 }
                 Some(HtmlDiscoveredIFrame((iframe_url, subpage_id, size_future))) => {
@@ -666,6 +1215,16 @@ impl ScriptTask {
         let js_scripts = js_scripts.take_unwrap();
         debug!("js_scripts: %?", js_scripts);
 
+        page.select_image_sources(false, &self.image_cache_task);
+
+        // Leave out the alternate style sheets, and any others not in the preferred set.
+        update_stylesheet_sets(root, &page.layout_chan);
+
+        // Record the visit, and have the links to pages in the history match `:visited`.
+        self.resource_task.send(AddVisit(url.clone()));
+        let base_url = page.frame.get_ref().document.with_base(|doc| doc.base_url());
+        htmlanchorelement::mark_visited_links(root, base_url, &self.resource_task);
+
         // Perform the initial reflow.
         page.damage = Some(DocumentDamage {
             root: root,
@@ -674,11 +1233,20 @@ impl ScriptTask {
         page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor);
         page.url = Some((url, false));
 
-        // Define debug functions.
-        let js_info = page.js_info.get_ref();
-        js_info.js_compartment.define_functions(debug_fns);
+        // FIXME: The document should scroll to the fragment of its URL once it's laid out, but
+        // the compositor doesn't know the size of the page yet, so it can't scroll.
 
-        // Evaluate every script in the document.
+        // Now that the images are laid out, images without `sizes` can be picked for the width
+        // they ended up.
+        if page.select_image_sources(true, &self.image_cache_task) {
+            page.damage_all();
+        }
+
+        // Media sources are resolved against the document's URL, which is only known now.
+        page.load_media();
+
+        // Evaluate the `async` and `defer` scripts, which were left for after parsing.
+        let js_info = page.js_info.get_ref();
         for bytes in js_scripts.iter() {
             let _ = js_info.js_context.evaluate_script(js_info.js_compartment.global_obj,
                                                        bytes.clone(),
@@ -699,11 +1267,31 @@ impl ScriptTask {
             ResizeEvent(new_width, new_height) => {
                 debug!("script got resize event: %u, %u", new_width, new_height);
 
-                page.window_size = from_value(Size2D(new_width, new_height));
+                let new_size = Size2D(new_width, new_height);
+                let resized = page.window_size.get() != new_size;
+                page.window_size = from_value(new_size);
 
                 if page.frame.is_some() {
-                    page.damage(ReflowDocumentDamage);
-                    page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor)
+                    // Layout needs the new device pixel ratio too, which changes when the page
+                    // is zoomed.
+                    let window = page.frame.get_ref().window;
+                    let old_ratio = window.metrics.device_pixel_ratio;
+                    window.update_metrics();
+
+                    // Iframes are told their size each time their parent is laid out, which
+                    // mostly leaves it as it was.
+                    if resized || window.metrics.device_pixel_ratio != old_ratio {
+                        page.damage(ReflowDocumentDamage);
+                        page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor);
+
+                        window.evaluate_media_query_lists();
+                        if page.select_image_sources(true, &self.image_cache_task) {
+                            page.damage_all();
+                        }
+                    }
+                    if resized {
+                        window.queue_resize_event();
+                    }
                 }
             }
 
@@ -720,55 +1308,518 @@ impl ScriptTask {
             ClickEvent(_button, point) => {
                 debug!("ClickEvent: clicked at %?", point);
 
-                let root = do page.frame.expect("root frame is None").document.with_base |doc| {
-                    doc.root
-                };
-                let (port, chan) = comm::stream();
-                match page.query_layout(HitTestQuery(root, point, chan), port) {
-                    Ok(node) => match node {
-                        HitTestResponse(node) => {
-                            debug!("clicked on %s", node.debug_str());
-                            let mut node = node;
-                            // traverse node generations until a node that is an element is found
-                            while !node.is_element() {
-                                match node.parent_node() {
-                                    Some(parent) => {
-                                        node = parent;
-                                    }
-                                    None => break
-                                }
-                            }
-                            if node.is_element() {
-                                do node.with_imm_element |element| {
-                                    if "a" == element.tag_name {
-                                        self.load_url_from_element(page, element)
-                                    }
-                                }
+                // Clicking a scrollbar only scrolls.
+                if page.pressing_scrollbar {
+                    return
+                }
+
+                for &node in self.element_at_point(page, point).iter() {
+                    debug!("clicked on %s", node.debug_str());
+                    // Clicking an element that can have focus, or anything in one, focuses
+                    // it, and clicking anything else takes focus away.
+                    let host = editing::editing_host(node);
+                    let document = page.frame.get_ref().document;
+                    focus::set_focus(document, focus::focusable_ancestor(node));
+                    for &host in host.iter() {
+                        self.place_caret(page, host, point);
+                    }
+
+                    // Clicking a select opens its list of options.
+                    if node.is_select_element() {
+                        self.show_select_popup(page, node);
+                    }
+
+                    // Clicking a checkbox or radio button checks it, and clicking
+                    // a submit button submits its form.
+                    if node.is_input_element() {
+                        do node.with_mut_input_element |input| {
+                            input.activate(node)
+                        }
+                        if node.with_imm_input_element(|input| {
+                                input.is_submit_button()
+                            }) {
+                            for &form in htmlformelement::form_owner(node).iter() {
+                                htmlformelement::submit(form, Some(node), true)
                             }
                         }
-                    },
-                    Err(()) => {
-                        debug!(fmt!("layout query error"));
                     }
+
+                    // Clicking a link, or anything in one, follows it.
+                    for &link in htmlanchorelement::link_for(node).iter() {
+                        htmlanchorelement::activate(link, page.frame.get_ref().window)
+                    }
+                }
+            }
+            // Pressing the mouse button on text starts selecting from there, and pressing it
+            // anywhere else clears the selection. What it's pressed on is `:active` until it's
+            // released.
+            MouseDownEvent(_button, point) => {
+                if page.frame.is_some() && self.press_scrollbar(page, point) {
+                    page.pressing_scrollbar = true;
+                    return
+                }
+                if page.frame.is_some() {
+                    let target = self.element_at_point(page, point);
+                    do page.frame.get_ref().document.with_mut_base |doc| {
+                        doc.set_active(target)
+                    }
+
+                    let anchor = self.selection_point(page, point);
+                    page.selecting = anchor.is_some();
+                    do page.frame.get_ref().document.with_mut_base |doc| {
+                        doc.set_selection(anchor.map(|anchor| (anchor.clone(), anchor.clone())))
+                    }
+                }
+            }
+            MouseMoveEvent(point) => {
+                if page.scrollbar_drag.is_some() {
+                    self.drag_scrollbar(page, point);
+                    return
+                }
+                if page.frame.is_some() {
+                    let target = self.element_at_point(page, point);
+                    do page.frame.get_ref().document.with_mut_base |doc| {
+                        doc.set_hover(target)
+                    }
+                }
+                if page.selecting && page.frame.is_some() {
+                    let document = page.frame.get_ref().document;
+                    let selection = document.with_base(|doc| doc.selection.clone());
+                    match (selection, self.selection_point(page, point)) {
+                        (Some((anchor, _)), Some(focus)) => {
+                            document.with_mut_base(|doc| doc.set_selection(Some((anchor, focus))))
+                        }
+                        // Dragging off text leaves the selection as it was.
+                        _ => {}
+                    }
+                }
+            }
+            MouseUpEvent(*) => {
+                page.selecting = false;
+                page.pressing_scrollbar = false;
+                page.scrollbar_drag = None;
+                for frame in page.frame.iter() {
+                    frame.document.with_mut_base(|doc| doc.set_active(None));
+                }
+            }
+
+            TouchStartEvent(id, point) => self.handle_touch_event(page, "touchstart", id, point),
+            TouchMoveEvent(id, point) => self.handle_touch_event(page, "touchmove", id, point),
+            TouchEndEvent(id, point) => self.handle_touch_event(page, "touchend", id, point),
+            TouchCancelEvent(id, point) => {
+                self.handle_touch_event(page, "touchcancel", id, point)
+            }
+
+            KeyEvent(key) => {
+                debug!("script got key event: %?", key);
+
+                let focused = match page.frame {
+                    Some(ref frame) => frame.document.with_base(|doc| doc.focused),
+                    None => None,
+                };
+
+                // Tab and Shift+Tab move focus through the document.
+                if key == TabKey || key == ShiftTabKey {
+                    for frame in page.frame.iter() {
+                        let root = frame.document.with_base(|doc| doc.root);
+                        let next = focus::next_in_navigation_order(root, focused, key == TabKey);
+                        focus::set_focus(frame.document, next);
+                    }
+                    return
+                }
+
+                let handled = self.handle_focused_key(page, key);
+
+                // Backspace goes back when it doesn't edit text, and enter in a text field
+                // submits its form.
+                match focused {
+                    _ if handled => {}
+                    _ if key == BackspaceKey => self.handle_navigate_msg(constellation_msg::Back),
+                    Some(node) if key == EnterKey && node.is_input_element() => {
+                        htmlformelement::implicitly_submit(node)
+                    }
+                    _ => {}
+                }
+            }
+
+            CopyEvent => {
+                debug!("script got copy event");
+
+                for frame in page.frame.iter() {
+                    frame.document.with_base(|doc| doc.copy());
+                }
+            }
+
+            // Pasting types the text into the focused field.
+            PasteEvent(text) => {
+                debug!("script got paste event");
+
+                for character in text.iter() {
+                    match character {
+                        '\r' => {}
+                        '\n' => {
+                            self.handle_focused_key(page, EnterKey);
+                        }
+                        _ => {
+                            self.handle_focused_key(page, CharacterKey(character));
+                        }
+                    }
+                }
+            }
+
+            SelectOptionEvent(index) => {
+                debug!("script got select option event: %?", index);
+
+                let focused = match page.frame {
+                    Some(ref frame) => frame.document.with_base(|doc| doc.focused),
+                    None => None,
+                };
+                match (focused, index) {
+                    (Some(node), Some(_)) if node.is_select_element() => {
+                        htmlselectelement::select_index(node, index)
+                    }
+                    // The popup was dismissed, or focus moved on while it was open.
+                    _ => {}
+                }
+            }
+
+            ScrollEvent(offset) => {
+                debug!("script got scroll event: %?", offset);
+
+                // Scrolls script asked for are already recorded, and their events queued.
+                if page.scroll_offset != offset {
+                    for frame in page.frame.iter() {
+                        frame.window.queue_scroll_event(None);
+                    }
+                }
+                page.scroll_offset = offset;
+
+                // Layout lays out contents skipped by `content-visibility` once they're within a
+                // viewport of the viewport, so it only needs to hear about scrolling every half a
+                // viewport or so.
+                let window_size = page.window_size.get();
+                let moved = page.scroll_offset - page.reflow_scroll_offset;
+                if page.frame.is_some() &&
+                        (moved.x.abs() * 2.0 >= window_size.width as f32 ||
+                         moved.y.abs() * 2.0 >= window_size.height as f32) {
+                    page.damage(ReflowDocumentDamage);
+                    page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor)
+                }
+            }
+        }
+    }
+
+    /// Has the focused element act on the key the user pressed, if it wants it. Returns whether
+    /// it did.
+    fn handle_focused_key(&self, page: @mut Page, key: Key) -> bool {
+        let focused = match page.frame {
+            Some(ref frame) => frame.document.with_base(|doc| doc.focused),
+            None => None,
+        };
+        match focused {
+            Some(node) if node.is_input_element() => {
+                node.with_mut_input_element(|input| input.handle_key(key))
+            }
+            Some(node) if node.is_textarea_element() => {
+                node.with_mut_textarea_element(|textarea| textarea.handle_key(node, key))
+            }
+            Some(node) if editing::editing_host(node) == Some(node) => {
+                let document = page.frame.get_ref().document;
+                let caret = document.with_base(|doc| doc.caret);
+                match editing::handle_key(node, caret, key) {
+                    Some(caret) => {
+                        document.with_mut_base(|doc| doc.set_caret(Some(caret)));
+                        true
+                    }
+                    None => false,
                 }
             }
-            MouseDownEvent(*) => {}
-            MouseUpEvent(*) => {}
+            // The arrow keys change the option of a closed select.
+            Some(node) if node.is_select_element() && (key == UpKey || key == DownKey) => {
+                htmlselectelement::step_selection(node, if key == UpKey { -1 } else { 1 });
+                true
+            }
+            _ => false,
         }
     }
 
-    fn load_url_from_element(&self, page: @mut Page, element: &Element) {
-        // if the node's element is "a," load url from href attr
-        let attr = element.get_attr("href");
-        for href in attr.iter() {
-            debug!("ScriptTask: clicked on link to %s", *href);
-            let current_url = do page.url.map |&(ref url, _)| {
-                url.clone()
+    /// Asks layout for the text node under `point`, and how many of the characters laid out for
+    /// it come before the point.
+    fn text_at_point(&self, page: @mut Page, point: Point2D<f32>)
+                     -> Option<(AbstractNode<ScriptView>, uint)> {
+        let root = page.frame.get_ref().document.with_base(|doc| doc.root);
+        match page.query_layout(|chan| CaretPositionQuery(root, point, chan)) {
+            Ok(CaretPositionResponse(text, offset)) => {
+                // FIXME: Isolate this transmutation into a single "bridge" module.
+                let text: AbstractNode<ScriptView> = unsafe {
+                    transmute(text)
+                };
+                Some((text, offset))
+            }
+            Err(()) => None,
+        }
+    }
+
+    /// The element under `point`, or the element holding the text there.
+    fn element_at_point(&self, page: @mut Page, point: Point2D<f32>)
+                        -> Option<AbstractNode<ScriptView>> {
+        let root = page.frame.get_ref().document.with_base(|doc| doc.root);
+        match page.query_layout(|chan| HitTestQuery(root, point, chan)) {
+            Ok(HitTestResponse(node)) => {
+                // FIXME: Isolate this transmutation into a single "bridge" module.
+                let mut node: AbstractNode<ScriptView> = unsafe {
+                    transmute(node)
+                };
+                while !node.is_element() {
+                    match node.parent_node() {
+                        Some(parent) => node = parent,
+                        None => return None,
+                    }
+                }
+                Some(node)
+            }
+            Err(()) => {
+                debug!("script: layout couldn't hit test %?", point);
+                None
+            }
+        }
+    }
+
+    /// The geometry of `element`, if it scrolls its overflow.
+    fn scroll_area(&self, page: @mut Page, element: AbstractNode<ScriptView>)
+                   -> Option<ScrollArea> {
+        match page.query_layout(|chan| ScrollAreaQuery(element, chan)) {
+            Ok(area) => Some(area),
+            Err(()) => None,
+        }
+    }
+
+    /// Presses the mouse button at `point`, if it's on the scrollbar of an element that scrolls
+    /// its overflow: pressing the thumb starts dragging it, and pressing the track scrolls the
+    /// element by the length of its view towards the point. Returns whether the point was on a
+    /// scrollbar.
+    fn press_scrollbar(&self, page: @mut Page, point: Point2D<f32>) -> bool {
+        let mut current = self.element_at_point(page, point);
+        while current.is_some() {
+            let element = current.unwrap();
+            current = element.parent_node();
+            let area = match self.scroll_area(page, element) {
+                Some(area) => area,
+                None => loop,
             };
-            debug!("ScriptTask: current url is %?", current_url);
-            let url = make_url(href.to_owned(), current_url);
-            self.constellation_chan.send(LoadUrlMsg(page.id, url, from_value(page.window_size.get())));
+            let (vertical, position) = match area.scrollbar_at(point) {
+                Some(hit) => hit,
+                None => loop,
+            };
+            let offset = do element.with_imm_element |element| {
+                area.clamp_offset(element.scroll_offset)
+            };
+            let (scrollbar, scrolled) = if vertical {
+                (area.vertical(), offset.y)
+            } else {
+                (area.horizontal(), offset.x)
+            };
+            match scrollbar.part_at(position, scrolled) {
+                Thumb => {
+                    let (start, _) = scrollbar.thumb(scrolled);
+                    page.scrollbar_drag = Some((element, vertical, position - start));
+                }
+                part => {
+                    let new_offset = scrollbar.offset_for_track_click(part, scrolled);
+                    scroll_along(element, offset, vertical, new_offset);
+                }
+            }
+            return true
         }
+        false
+    }
+
+    /// Moves the scrollbar thumb being dragged to follow the mouse to `point`.
+    fn drag_scrollbar(&self, page: @mut Page, point: Point2D<f32>) {
+        let (element, vertical, grab) = match page.scrollbar_drag {
+            Some(drag) => drag,
+            None => return,
+        };
+        let area = match self.scroll_area(page, element) {
+            Some(area) => area,
+            None => return,
+        };
+        let offset = element.with_imm_element(|element| area.clamp_offset(element.scroll_offset));
+        let new_offset = if vertical {
+            let position = point.y - area.view.origin.y.to_f32();
+            area.vertical().offset_for_thumb_at(position - grab)
+        } else {
+            let position = point.x - area.view.origin.x.to_f32();
+            area.horizontal().offset_for_thumb_at(position - grab)
+        };
+        scroll_along(element, offset, vertical, new_offset);
+    }
+
+    /// Fires a touch event of the given type for the finger `id`, now at `point`, at the element
+    /// it first touched. The compositor holds off scrolling for the touch until it hears back
+    /// whether a `touchstart` or `touchmove` was canceled.
+    fn handle_touch_event(&mut self, page: @mut Page, type_: &str, id: uint,
+                          point: Point2D<f32>) {
+        let allowed = self.fire_touch_event(page, type_, id, point);
+        if type_ == "touchstart" || type_ == "touchmove" {
+            self.compositor.touch_event_processed(if allowed {
+                TouchDefaultAllowed
+            } else {
+                TouchDefaultPrevented
+            });
+        }
+    }
+
+    /// Keeps track of the fingers on the page, and fires a touch event about the finger `id`.
+    /// Returns false if a listener canceled it.
+    fn fire_touch_event(&mut self, page: @mut Page, type_: &str, id: uint,
+                        point: Point2D<f32>) -> bool {
+        if page.frame.is_none() {
+            return true
+        }
+        let index = page.touches.iter().position(|&(touch_id, _, _)| touch_id == id);
+        let target = match (type_, index) {
+            ("touchstart", _) => {
+                // A finger that touches no element touches the root.
+                let target = match self.element_at_point(page, point) {
+                    Some(target) => target,
+                    None => page.frame.get_ref().document.with_base(|doc| doc.root),
+                };
+                for &index in index.iter() {
+                    page.touches.remove(index);
+                }
+                page.touches.push((id, target, point));
+                target
+            }
+            (_, Some(index)) => {
+                let (_, target, _) = page.touches[index];
+                if type_ == "touchmove" {
+                    page.touches[index] = (id, target, point);
+                } else {
+                    page.touches.remove(index);
+                }
+                target
+            }
+            // The page didn't see the finger touch down, as when it loaded after.
+            (_, None) => return true,
+        };
+
+        let window = page.frame.get_ref().window;
+        let cx = page.js_info.get_ref().js_compartment.cx.ptr;
+        let scope = window.wrapper.get_wrapper();
+        let scroll_offset = page.scroll_offset;
+        let new_touch: &fn(uint, AbstractNode<ScriptView>, Point2D<f32>) -> @mut Touch =
+                |id, target, point| {
+            let client_point = point - scroll_offset;
+            Touch::new(id as i32, target, Point2D(client_point.x as i32, client_point.y as i32),
+                       Point2D(point.x as i32, point.y as i32), cx, scope)
+        };
+
+        let touches: ~[@mut Touch] = do page.touches.iter().map |&(id, target, point)| {
+            new_touch(id, target, point)
+        }.collect();
+        let target_touches = touches.iter().filter(|touch| touch.target == target)
+                                           .map(|&touch| touch)
+                                           .collect();
+        // A lifted finger is no longer among the touches.
+        let changed_touch = match touches.iter().find(|touch| touch.identifier == id as i32) {
+            Some(&touch) => touch,
+            None => new_touch(id, target, point),
+        };
+
+        let event = @mut TouchEvent::new(&str(type_.to_owned()),
+                                         type_ != "touchcancel",
+                                         None,
+                                         TouchList::new(touches, cx, scope),
+                                         TouchList::new(target_touches, cx, scope),
+                                         TouchList::new(~[changed_touch], cx, scope));
+        event.parent.parent.trusted = true;
+        touchevent::dispatch(event, target, window)
+    }
+
+    /// The place in the text under `point` that a selection dragged there starts or ends at.
+    fn selection_point(&self, page: @mut Page, point: Point2D<f32>) -> Option<BoundaryPoint> {
+        do self.text_at_point(page, point).map_move |(text, offset)| {
+            let data = text.with_imm_text(|text| text.parent.data.to_str());
+            BoundaryPoint {
+                node: text,
+                offset: uncompressed_offset(data, offset),
+            }
+        }
+    }
+
+    /// Puts the editing caret in `host` where the user clicked it, at `point`.
+    fn place_caret(&self, page: @mut Page, host: AbstractNode<ScriptView>, point: Point2D<f32>) {
+        let hit = self.text_at_point(page, point);
+        let caret = editing::caret_for_click(host, hit);
+        do page.frame.get_ref().document.with_mut_base |doc| {
+            doc.set_caret(caret)
+        }
+    }
+
+    /// Asks the compositor to show the options of `select` in a popup list below it.
+    fn show_select_popup(&self, page: @mut Page, select: AbstractNode<ScriptView>) {
+        let bounds = match page.query_layout(|chan| ContentBoxQuery(select, chan)) {
+            Ok(ContentBoxResponse(rect)) => {
+                Rect(Point2D(rect.origin.x.to_f32(), rect.origin.y.to_f32()),
+                     Size2D(rect.size.width.to_f32(), rect.size.height.to_f32()))
+            }
+            Err(()) => {
+                debug!("script: select isn't laid out, so it has no popup");
+                return
+            }
+        };
+        let options = htmlselectelement::options(select);
+        let popup = SelectPopup {
+            bounds: bounds,
+            options: options.iter().map(|&option| htmloptionelement::label(option)).collect(),
+            selected: htmlselectelement::selected_index(select),
+        };
+        self.compositor.show_select_popup(page.id, popup);
     }
 }
 
+
+/// Scrolls `element` along one axis to `offset`, leaving it scrolled by `current` along the other.
+fn scroll_along(element: AbstractNode<ScriptView>, current: Point2D<f32>, vertical: bool,
+                offset: f32) {
+    let new_offset = if vertical {
+        Point2D(current.x, offset)
+    } else {
+        Point2D(offset, current.y)
+    };
+    element.with_mut_element(|element| element.set_scroll_offset(new_offset));
+}
+
+/// Snapshots the elements under `root` that layout needs new snapshots of: those whose attributes
+/// or form control state changed since the last reflow, those in subtrees inserted since, and those
+/// layout hasn't given layout data yet. Only subtrees marked as having one of them are looked
+/// through, so the rest of the document isn't walked.
+fn snapshot_elements(root: AbstractNode<ScriptView>)
+                     -> ~[(AbstractNode<ScriptView>, ElementSnapshot)] {
+    let mut snapshots = ~[];
+    collect_snapshots(root, false, &mut snapshots);
+    snapshots
+}
+
+fn collect_snapshots(node: AbstractNode<ScriptView>,
+                     all: bool,
+                     snapshots: &mut ~[(AbstractNode<ScriptView>, ElementSnapshot)]) {
+    let (dirty, subtree) = node.take_snapshot_dirtiness();
+    let all = all || subtree;
+    if node.is_element() {
+        let unseen = unsafe { !node.unsafe_has_layout_data() };
+        let attrs_changed = node.with_imm_element(|element| element.attrs_changed);
+        // The state of a form control can change without its attributes changing, as can the
+        // nodes under it that it shows, like the options of a select.
+        let control_changed = dirty && element::is_form_control(node);
+        if all || unseen || attrs_changed || control_changed {
+            snapshots.push((node, element::snapshot(node)));
+        }
+    }
+    if all || dirty {
+        for child in node.children() {
+            collect_snapshots(child, all, snapshots);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The task that runs the script of a dedicated worker. Each worker gets a task, JS runtime and
+//! compartment of its own, and talks to the script task of the document that started it only by
+//! messages: `WorkerMsg`s on the way in, and `ScriptMsg`s on the way out.
+//!
+//! FIXME: Workers can't make `XMLHttpRequest`s yet, since the bindings find the global object of
+//! a constructor through the page of the context, which workers don't have.
+
+use dom::bindings::codegen::{DedicatedWorkerGlobalScopeBinding, EventBinding};
+use dom::bindings::codegen::MessageEventBinding;
+use dom::bindings::utils::{CacheableWrapper, initialize_global};
+use dom::dedicatedworkerglobalscope::DedicatedWorkerGlobalScope;
+use dom::window::TimerData;
+use dom::worker::WorkerId;
+use html::hubbub_html_parser::load_script;
+use script_task::{ScriptChan, WorkerErrorMsg};
+
+use js::JSPROP_ENUMERATE;
+use js::JSVAL_NULL;
+use js::global::{global_class, debug_fns};
+use js::glue::*;
+use js::jsapi::JS_CallFunctionValue;
+use js::rust::{Compartment, Cx};
+use js;
+use servo_msg::constellation_msg::{PipelineId, StructuredCloneData};
+use servo_net::resource_task::ResourceTask;
+use extra::url::Url;
+
+use std::cell::Cell;
+use std::comm;
+use std::comm::{Port, SharedChan};
+use std::ptr;
+use std::ptr::null;
+use std::task::{SingleThreaded, task};
+
+/// Messages to a worker task.
+pub enum WorkerMsg {
+    /// Delivers a message the worker's owner posted to it.
+    PostMessageToWorkerMsg(StructuredCloneData),
+    /// Fires the timeout with the given handle, unless it has been cleared since.
+    FireWorkerTimerMsg(i32, ~TimerData),
+    /// Stops the worker straight away.
+    TerminateMsg,
+}
+
+/// Encapsulates a channel to a worker task.
+#[deriving(Clone)]
+pub struct WorkerChan {
+    chan: SharedChan<WorkerMsg>,
+}
+
+impl WorkerChan {
+    pub fn new(chan: Chan<WorkerMsg>) -> WorkerChan {
+        WorkerChan {
+            chan: SharedChan::new(chan)
+        }
+    }
+
+    pub fn send(&self, msg: WorkerMsg) {
+        self.chan.send(msg);
+    }
+}
+
+pub struct WorkerTask {
+    port: Port<WorkerMsg>,
+    /// The JavaScript runtime, which has to outlive the context and compartment.
+    js_runtime: js::rust::rt,
+    js_context: @Cx,
+    js_compartment: @mut Compartment,
+    /// What the worker's script sees as `self`.
+    global: @mut DedicatedWorkerGlobalScope,
+}
+
+impl WorkerTask {
+    /// Starts a worker running the script at `url`, for the `Worker` object with the id
+    /// `worker_id` in the given pipeline of the script task `owner`. Returns the channel to
+    /// the worker.
+    pub fn create(url: Url,
+                  owner: ScriptChan,
+                  pipeline_id: PipelineId,
+                  worker_id: WorkerId,
+                  resource_task: ResourceTask)
+                  -> WorkerChan {
+        let (port, chan) = comm::stream();
+        let chan = WorkerChan::new(chan);
+        let worker_chan = Cell::new(chan.clone());
+        let port = Cell::new(port);
+        let url = Cell::new(url);
+        let owner = Cell::new(owner);
+        let pipeline_id = Cell::new(pipeline_id);
+        let resource_task = Cell::new(resource_task);
+        let start: ~fn() = || {
+            let url = url.take();
+            let owner = owner.take();
+            let pipeline_id = pipeline_id.take();
+            match load_script(&url, &resource_task.take()) {
+                Some(script) => {
+                    let worker_task = WorkerTask::new(port.take(), worker_chan.take(), owner,
+                                                      pipeline_id, worker_id);
+                    worker_task.start(&url, script);
+                }
+                None => {
+                    let message = fmt!("failed to load %s", url.to_str());
+                    owner.send(WorkerErrorMsg(pipeline_id, worker_id, message));
+                }
+            }
+        };
+
+        // A worker that fails doesn't take the document that started it down with it.
+        let mut the_task = task();
+        the_task.sched_mode(SingleThreaded);
+        the_task.unlinked();
+        the_task.spawn(start);
+        chan
+    }
+
+    fn new(port: Port<WorkerMsg>,
+           chan: WorkerChan,
+           owner: ScriptChan,
+           pipeline_id: PipelineId,
+           worker_id: WorkerId)
+           -> @mut WorkerTask {
+        let js_runtime = js::rust::rt();
+        let js_context = js_runtime.cx();
+        js_context.set_default_options_and_version();
+        js_context.set_logging_error_reporter();
+        let compartment = match js_context.new_compartment(global_class) {
+            Ok(c) => c,
+            Err(()) => fail!("Failed to create a compartment"),
+        };
+        define_worker_bindings(compartment);
+
+        let global = DedicatedWorkerGlobalScope::new(compartment, chan, owner, pipeline_id,
+                                                     worker_id);
+        unsafe {
+            let cache = ptr::to_unsafe_ptr(global.get_wrappercache());
+            compartment.define_property(~"self",
+                                        RUST_OBJECT_TO_JSVAL((*cache).wrapper),
+                                        GetJSClassHookStubPointer(PROPERTY_STUB) as *u8,
+                                        GetJSClassHookStubPointer(STRICT_PROPERTY_STUB) as *u8,
+                                        JSPROP_ENUMERATE);
+        }
+        compartment.define_functions(debug_fns);
+
+        @mut WorkerTask {
+            port: port,
+            js_runtime: js_runtime,
+            js_context: js_context,
+            js_compartment: compartment,
+            global: global,
+        }
+    }
+
+    /// Runs the worker's script, then handles messages until the worker is terminated or closes
+    /// itself.
+    fn start(&mut self, url: &Url, script: ~[u8]) {
+        let result = self.js_context.evaluate_script(self.js_compartment.global_obj,
+                                                     script,
+                                                     url.to_str(),
+                                                     1);
+        if result.is_err() {
+            self.global.report_error(fmt!("error running %s", url.to_str()));
+        }
+
+        while !self.global.closing && self.handle_msg() {
+            // Go on...
+        }
+    }
+
+    /// Handles a message, returning false once the worker has been terminated.
+    fn handle_msg(&mut self) -> bool {
+        match self.port.recv() {
+            PostMessageToWorkerMsg(data) => self.global.receive_message(data),
+            FireWorkerTimerMsg(handle, timer_data) => {
+                self.handle_fire_timer_msg(handle, timer_data)
+            }
+            TerminateMsg => return false,
+        }
+        true
+    }
+
+    /// Handles a timeout that fired, unless it has been cleared.
+    fn handle_fire_timer_msg(&mut self, handle: i32, timer_data: ~TimerData) {
+        if !self.global.take_timer(handle) {
+            return;
+        }
+        unsafe {
+            let this_value = if timer_data.args.len() > 0 {
+                RUST_JSVAL_TO_OBJECT(timer_data.args[0])
+            } else {
+                self.js_compartment.global_obj.ptr
+            };
+
+            // TODO: Support extra arguments. This requires passing a `*JSVal` array as `argv`.
+            let rval = JSVAL_NULL;
+            JS_CallFunctionValue(self.js_context.ptr,
+                                 this_value,
+                                 timer_data.funval,
+                                 0,
+                                 null(),
+                                 &rval);
+        }
+    }
+}
+
+/// Defines the interfaces that make sense without a document in a worker's compartment.
+fn define_worker_bindings(compartment: @mut Compartment) {
+    initialize_global(compartment.global_obj.ptr);
+    let mut unused = false;
+    assert!(DedicatedWorkerGlobalScopeBinding::DefineDOMInterface(compartment.cx.ptr,
+                                                                  compartment.global_obj.ptr,
+                                                                  &mut unused));
+    assert!(EventBinding::DefineDOMInterface(compartment.cx.ptr,
+                                             compartment.global_obj.ptr,
+                                             &mut unused));
+    assert!(MessageEventBinding::DefineDOMInterface(compartment.cx.ptr,
+                                                    compartment.global_obj.ptr,
+                                                    &mut unused));
+}
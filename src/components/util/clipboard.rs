@@ -0,0 +1,48 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The clipboard, as far as copying and pasting plain text goes. The windowing system usually
+//! owns the system clipboard, so each platform's window implements `Clipboard`.
+
+/// Somewhere to copy plain text to and paste it from.
+pub trait Clipboard {
+    /// Returns the text on the clipboard, if it holds any.
+    fn get_text(&self) -> Option<~str>;
+    /// Replaces what's on the clipboard with `text`.
+    fn set_text(&mut self, text: &str);
+}
+
+/// A clipboard of Servo's own, for platforms whose windowing system doesn't have one. Text copied
+/// to it can only be pasted within Servo.
+pub struct LocalClipboard {
+    priv text: Option<~str>,
+}
+
+impl LocalClipboard {
+    pub fn new() -> LocalClipboard {
+        LocalClipboard {
+            text: None,
+        }
+    }
+}
+
+impl Clipboard for LocalClipboard {
+    fn get_text(&self) -> Option<~str> {
+        self.text.clone()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.text = Some(text.to_owned());
+    }
+}
+
+#[test]
+fn test_local_clipboard() {
+    let mut clipboard = LocalClipboard::new();
+    assert!(clipboard.get_text() == None);
+    clipboard.set_text("copied");
+    assert!(clipboard.get_text() == Some(~"copied"));
+    clipboard.set_text("");
+    assert!(clipboard.get_text() == Some(~""));
+}
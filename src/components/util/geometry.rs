@@ -0,0 +1,182 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Lengths, points, sizes and rects tagged with the coordinate space they're in.
+//!
+//! There are three spaces between layout and the screen:
+//!
+//! * `CssPx`: CSS pixels, which layout works in. An `Au` is a sixtieth of one.
+//! * `PagePx`: pixels of the layers the compositor scrolls. A CSS pixel is as many page pixels as
+//!   the page zoom.
+//! * `DevicePx`: pixels on the screen. A page pixel is as many device pixels as the pinch zoom
+//!   times the device pixel ratio.
+//!
+//! Going from one space to another takes a `ScaleFactor`, so that a value can't end up in the
+//! wrong space by accident.
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+
+/// CSS pixels.
+#[deriving(Clone, Eq)]
+pub struct CssPx;
+
+/// Pixels of a page's layers.
+#[deriving(Clone, Eq)]
+pub struct PagePx;
+
+/// Pixels on the screen.
+#[deriving(Clone, Eq)]
+pub struct DevicePx;
+
+/// A length in the space `Unit`.
+#[deriving(Clone, Eq)]
+pub struct Length<Unit, T> {
+    value: T,
+}
+
+pub fn Length<Unit, T>(value: T) -> Length<Unit, T> {
+    Length {
+        value: value,
+    }
+}
+
+/// A point in the space `Unit`.
+#[deriving(Clone, Eq)]
+pub struct TypedPoint2D<Unit, T> {
+    point: Point2D<T>,
+}
+
+pub fn TypedPoint2D<Unit, T>(x: T, y: T) -> TypedPoint2D<Unit, T> {
+    TypedPoint2D {
+        point: Point2D(x, y),
+    }
+}
+
+/// A size in the space `Unit`.
+#[deriving(Clone, Eq)]
+pub struct TypedSize2D<Unit, T> {
+    size: Size2D<T>,
+}
+
+pub fn TypedSize2D<Unit, T>(width: T, height: T) -> TypedSize2D<Unit, T> {
+    TypedSize2D {
+        size: Size2D(width, height),
+    }
+}
+
+/// A rect in the space `Unit`.
+#[deriving(Clone, Eq)]
+pub struct TypedRect<Unit, T> {
+    rect: Rect<T>,
+}
+
+pub fn TypedRect<Unit, T>(origin: TypedPoint2D<Unit, T>, size: TypedSize2D<Unit, T>)
+                          -> TypedRect<Unit, T> {
+    TypedRect {
+        rect: Rect(origin.point, size.size),
+    }
+}
+
+impl<Unit: Clone, T: Clone> TypedRect<Unit, T> {
+    pub fn origin(&self) -> TypedPoint2D<Unit, T> {
+        TypedPoint2D {
+            point: self.rect.origin.clone(),
+        }
+    }
+
+    pub fn size(&self) -> TypedSize2D<Unit, T> {
+        TypedSize2D {
+            size: self.rect.size.clone(),
+        }
+    }
+}
+
+impl<Unit> TypedSize2D<Unit, uint> {
+    pub fn to_f32(&self) -> TypedSize2D<Unit, f32> {
+        TypedSize2D(self.size.width as f32, self.size.height as f32)
+    }
+}
+
+impl<Unit> TypedRect<Unit, f32> {
+    /// The smallest rect of whole pixels that covers this one.
+    pub fn round_out(&self) -> TypedRect<Unit, uint> {
+        let x = self.rect.origin.x.floor();
+        let y = self.rect.origin.y.floor();
+        let width = (self.rect.origin.x + self.rect.size.width).ceil() - x;
+        let height = (self.rect.origin.y + self.rect.size.height).ceil() - y;
+        TypedRect(TypedPoint2D(x as uint, y as uint), TypedSize2D(width as uint, height as uint))
+    }
+}
+
+/// How many `Dst` units there are to one `Src` unit.
+#[deriving(Clone, Eq)]
+pub struct ScaleFactor<Src, Dst> {
+    factor: f32,
+}
+
+pub fn ScaleFactor<Src, Dst>(factor: f32) -> ScaleFactor<Src, Dst> {
+    ScaleFactor {
+        factor: factor,
+    }
+}
+
+impl<Src, Dst> ScaleFactor<Src, Dst> {
+    pub fn get(&self) -> f32 {
+        self.factor
+    }
+
+    /// The factor for going the other way.
+    pub fn inv(&self) -> ScaleFactor<Dst, Src> {
+        ScaleFactor(1.0 / self.factor)
+    }
+
+    /// Scales by this factor, then by `other`.
+    pub fn then<Next>(&self, other: ScaleFactor<Dst, Next>) -> ScaleFactor<Src, Next> {
+        ScaleFactor(self.factor * other.factor)
+    }
+
+    pub fn approx_eq(&self, other: &ScaleFactor<Src, Dst>) -> bool {
+        self.factor.approx_eq(&other.factor)
+    }
+
+    pub fn transform_length(&self, length: Length<Src, f32>) -> Length<Dst, f32> {
+        Length(length.value * self.factor)
+    }
+
+    pub fn transform_point(&self, point: &TypedPoint2D<Src, f32>) -> TypedPoint2D<Dst, f32> {
+        TypedPoint2D(point.point.x * self.factor, point.point.y * self.factor)
+    }
+
+    pub fn transform_size(&self, size: &TypedSize2D<Src, f32>) -> TypedSize2D<Dst, f32> {
+        TypedSize2D(size.size.width * self.factor, size.size.height * self.factor)
+    }
+
+    pub fn transform_rect(&self, rect: &TypedRect<Src, f32>) -> TypedRect<Dst, f32> {
+        TypedRect(TypedPoint2D(rect.rect.origin.x * self.factor, rect.rect.origin.y * self.factor),
+                  TypedSize2D(rect.rect.size.width * self.factor,
+                              rect.rect.size.height * self.factor))
+    }
+}
+
+#[test]
+fn test_scale_factor() {
+    let zoom: ScaleFactor<PagePx, DevicePx> = ScaleFactor(2.0);
+    let page_rect: TypedRect<PagePx, f32> = TypedRect(TypedPoint2D(10.0, 5.0),
+                                                      TypedSize2D(100.0, 50.5));
+    let device_rect = zoom.transform_rect(&page_rect);
+    assert!(device_rect.rect == Rect(Point2D(20.0, 10.0), Size2D(200.0, 101.0)));
+    assert!(zoom.inv().transform_rect(&device_rect) == page_rect);
+
+    let dpr: ScaleFactor<DevicePx, DevicePx> = ScaleFactor(1.5);
+    assert!(zoom.then(dpr).get() == 3.0);
+}
+
+#[test]
+fn test_round_out() {
+    let rect: TypedRect<DevicePx, f32> = TypedRect(TypedPoint2D(0.5, 1.0),
+                                                   TypedSize2D(10.0, 2.5));
+    assert!(rect.round_out().rect == Rect(Point2D(0u, 1u), Size2D(11u, 3u)));
+}
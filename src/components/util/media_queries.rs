@@ -0,0 +1,888 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS media queries, and the `@import` and `@media` rules of style sheets.
+//!
+//! rust-css matches style sheets for the screen and knows nothing about media features, so the
+//! `@media` rules are picked out of a style sheet's text before it's parsed. Whenever the device
+//! changes, the rules of those that apply to it are put back where they were and the style sheet
+//! is parsed again. Imported style sheets are loaded first and go through the same, with the
+//! media list of their `@import` rule on top.
+
+use extra::url::Url;
+use geom::size::Size2D;
+use std::ascii::StrAsciiExt;
+use std::from_str::FromStr;
+
+/// The initial value of `font-size`, which `em` in media queries is relative to, in CSS pixels.
+static INITIAL_FONT_SIZE: f32 = 16.0;
+
+#[deriving(Clone, Eq)]
+pub enum MediaType {
+    AllMedia,
+    ScreenMedia,
+    PrintMedia,
+    /// A media type we never render for, like `tv`, or one that isn't known at all.
+    OtherMedia,
+}
+
+impl MediaType {
+    fn parse(name: &str) -> MediaType {
+        match name {
+            "all" => AllMedia,
+            "screen" => ScreenMedia,
+            "print" => PrintMedia,
+            _ => OtherMedia,
+        }
+    }
+}
+
+/// What style is being computed for.
+#[deriving(Clone, Eq)]
+pub struct Device {
+    media_type: MediaType,
+    /// The size of the viewport, in CSS pixels.
+    viewport_size: Size2D<f32>,
+    /// How many device pixels there are to a CSS pixel.
+    device_pixel_ratio: f32,
+}
+
+impl Device {
+    pub fn screen(viewport_size: Size2D<f32>, device_pixel_ratio: f32) -> Device {
+        Device {
+            media_type: ScreenMedia,
+            viewport_size: viewport_size,
+            device_pixel_ratio: device_pixel_ratio,
+        }
+    }
+}
+
+/// How the value of a media feature compares to the value in an expression: whether the feature
+/// was prefixed with `min-` or `max-`, or not at all.
+#[deriving(Clone, Eq)]
+pub enum Range {
+    AtLeast,
+    AtMost,
+    Equal,
+}
+
+impl Range {
+    fn compare(&self, actual: f32, value: f32) -> bool {
+        match *self {
+            AtLeast => actual >= value,
+            AtMost => actual <= value,
+            Equal => actual == value,
+        }
+    }
+}
+
+#[deriving(Clone, Eq)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+/// One parenthesized expression of a media query. An expression without a value matches if the
+/// feature isn't zero, or for `orientation`, always.
+#[deriving(Clone, Eq)]
+pub enum Expression {
+    /// The width of the viewport, in CSS pixels.
+    WidthFeature(Range, Option<f32>),
+    /// The height of the viewport, in CSS pixels.
+    HeightFeature(Range, Option<f32>),
+    DevicePixelRatioFeature(Range, Option<f32>),
+    OrientationFeature(Option<Orientation>),
+}
+
+impl Expression {
+    /// Parses the text between the parentheses of an expression, in lower case.
+    fn parse(text: &str) -> Option<Expression> {
+        let mut parts = text.splitn_iter(':', 1);
+        let name = match parts.next() {
+            Some(name) => name.trim(),
+            None => return None,
+        };
+        let value = match parts.next() {
+            Some(value) if value.trim().is_empty() => return None,
+            Some(value) => Some(value.trim()),
+            None => None,
+        };
+
+        // Pages still use the prefixed names of `device-pixel-ratio`, which go
+        // `-webkit-min-device-pixel-ratio` and `min--moz-device-pixel-ratio`.
+        let name = if name.starts_with("-webkit-") { name.slice_from(8) } else { name };
+        let (range, feature) = if name.starts_with("min-") {
+            (AtLeast, name.slice_from(4))
+        } else if name.starts_with("max-") {
+            (AtMost, name.slice_from(4))
+        } else {
+            (Equal, name)
+        };
+        let feature = if feature.starts_with("-moz-") { feature.slice_from(5) } else { feature };
+        if range != Equal && value.is_none() {
+            return None;
+        }
+
+        match feature {
+            "width" => parse_value(value, parse_length).map(|&v| WidthFeature(range, v)),
+            "height" => parse_value(value, parse_length).map(|&v| HeightFeature(range, v)),
+            "device-pixel-ratio" => {
+                parse_value(value, parse_number).map(|&v| DevicePixelRatioFeature(range, v))
+            }
+            "orientation" if range == Equal => {
+                match value {
+                    None => Some(OrientationFeature(None)),
+                    Some("portrait") => Some(OrientationFeature(Some(Portrait))),
+                    Some("landscape") => Some(OrientationFeature(Some(Landscape))),
+                    Some(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn evaluate(&self, device: &Device) -> bool {
+        let size = device.viewport_size;
+        let compare = |range: Range, actual: f32, value: Option<f32>| {
+            match value {
+                Some(value) => range.compare(actual, value),
+                None => actual != 0.0,
+            }
+        };
+        match *self {
+            WidthFeature(range, value) => compare(range, size.width, value),
+            HeightFeature(range, value) => compare(range, size.height, value),
+            DevicePixelRatioFeature(range, value) => {
+                compare(range, device.device_pixel_ratio, value)
+            }
+            OrientationFeature(value) => {
+                let orientation = if size.height >= size.width { Portrait } else { Landscape };
+                value.map_default(true, |&value| value == orientation)
+            }
+        }
+    }
+}
+
+/// Parses the value of an expression with `parse`. Returns `None` if there's a value and it's
+/// invalid.
+fn parse_value(value: Option<&str>, parse: &fn(&str) -> Option<f32>) -> Option<Option<f32>> {
+    match value {
+        None => Some(None),
+        Some(value) => {
+            match parse(value) {
+                Some(value) => Some(Some(value)),
+                None => None,
+            }
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Option<f32> {
+    let number: Option<f32> = FromStr::from_str(text);
+    match number {
+        Some(number) if number >= 0.0 => Some(number),
+        _ => None,
+    }
+}
+
+/// Parses a length, in CSS pixels.
+fn parse_length(text: &str) -> Option<f32> {
+    let split = text.find(|c: char| c.is_alphabetic()).unwrap_or(text.len());
+    let number = match parse_number(text.slice_to(split)) {
+        Some(number) => number,
+        None => return None,
+    };
+    let pixels_per_unit: f32 = match text.slice_from(split) {
+        "px" => 1.0,
+        "" if number == 0.0 => 1.0,
+        "em" | "rem" => INITIAL_FONT_SIZE,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        _ => return None,
+    };
+    Some(number * pixels_per_unit)
+}
+
+enum Token {
+    Word(~str),
+    /// The text between a pair of parentheses.
+    Parenthesized(~str),
+}
+
+/// Splits a media query into words and parenthesized expressions, or returns `None` if a
+/// parenthesis isn't closed.
+fn tokenize(text: &str) -> Option<~[Token]> {
+    let mut tokens = ~[];
+    let mut rest = text.trim_left();
+    while !rest.is_empty() {
+        if rest.starts_with("(") {
+            let end = match rest.find(')') {
+                Some(end) => end,
+                None => return None,
+            };
+            tokens.push(Parenthesized(rest.slice(1, end).to_owned()));
+            rest = rest.slice_from(end + 1);
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || c == '(').unwrap_or(rest.len());
+            tokens.push(Word(rest.slice_to(end).to_owned()));
+            rest = rest.slice_from(end);
+        }
+        rest = rest.trim_left();
+    }
+    Some(tokens)
+}
+
+fn is_word(tokens: &[Token], index: uint, word: &str) -> bool {
+    index < tokens.len() && match tokens[index] {
+        Word(ref token) => token.as_slice() == word,
+        Parenthesized(_) => false,
+    }
+}
+
+/// A media query, like `screen and (min-width: 600px)`.
+#[deriving(Clone, Eq)]
+pub struct MediaQuery {
+    /// Whether the query began with `not`.
+    negated: bool,
+    media_type: MediaType,
+    expressions: ~[Expression],
+}
+
+impl MediaQuery {
+    /// `not all`, which is what a malformed query is treated as.
+    fn never() -> MediaQuery {
+        MediaQuery {
+            negated: true,
+            media_type: AllMedia,
+            expressions: ~[],
+        }
+    }
+
+    /// Parses a single media query. Returns `None` if it's malformed.
+    pub fn parse(text: &str) -> Option<MediaQuery> {
+        let text = text.to_ascii_lower();
+        let tokens = match tokenize(text.as_slice()) {
+            Some(tokens) => tokens,
+            None => return None,
+        };
+
+        let negated = is_word(tokens, 0, "not");
+        let mut index = if negated || is_word(tokens, 0, "only") { 1 } else { 0 };
+        let mut query = MediaQuery {
+            negated: negated,
+            media_type: AllMedia,
+            expressions: ~[],
+        };
+
+        // A media type can be left out if the query starts with an expression.
+        if index >= tokens.len() {
+            return None;
+        }
+        match tokens[index] {
+            Word(ref name) => {
+                match name.as_slice() {
+                    "and" | "not" | "only" => return None,
+                    name => query.media_type = MediaType::parse(name),
+                }
+                index += 1;
+                if index == tokens.len() {
+                    return Some(query);
+                }
+                if !is_word(tokens, index, "and") {
+                    return None;
+                }
+                index += 1;
+            }
+            Parenthesized(_) if index == 0 => {}
+            Parenthesized(_) => return None,
+        }
+
+        loop {
+            if index >= tokens.len() {
+                return None;
+            }
+            match tokens[index] {
+                Parenthesized(ref text) => {
+                    match Expression::parse(text.as_slice()) {
+                        Some(expression) => query.expressions.push(expression),
+                        None => return None,
+                    }
+                }
+                Word(_) => return None,
+            }
+            index += 1;
+            if index == tokens.len() {
+                return Some(query);
+            }
+            if !is_word(tokens, index, "and") {
+                return None;
+            }
+            index += 1;
+        }
+    }
+
+    pub fn evaluate(&self, device: &Device) -> bool {
+        let type_matches = match self.media_type {
+            AllMedia => true,
+            OtherMedia => false,
+            media_type => media_type == device.media_type,
+        };
+        let matches = type_matches && self.expressions.iter().all(|e| e.evaluate(device));
+        matches != self.negated
+    }
+}
+
+/// A comma-separated list of media queries, like the value of a `media` attribute. It matches if
+/// any of its queries do, or if it's empty.
+#[deriving(Clone, Eq)]
+pub struct MediaQueryList {
+    queries: ~[MediaQuery],
+}
+
+impl MediaQueryList {
+    /// Parses a list of media queries. Malformed queries never match, but don't stop the others
+    /// in the list from matching.
+    pub fn parse(text: &str) -> MediaQueryList {
+        if text.trim().is_empty() {
+            return MediaQueryList {
+                queries: ~[],
+            };
+        }
+        MediaQueryList {
+            queries: text.split_iter(',').map(|query| {
+                MediaQuery::parse(query).unwrap_or(MediaQuery::never())
+            }).collect(),
+        }
+    }
+
+    pub fn evaluate(&self, device: &Device) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|query| query.evaluate(device))
+    }
+}
+
+/// A run of rules from a style sheet, with the media lists of the `@import` and `@media` rules
+/// it came from inside. It applies if all of those match.
+#[deriving(Clone)]
+pub struct StylesheetPart {
+    /// The URL of the style sheet the rules are in, which URLs in them are relative to.
+    url: Url,
+    media: ~[MediaQueryList],
+    text: ~str,
+}
+
+impl StylesheetPart {
+    fn applies_to(&self, device: &Device) -> bool {
+        self.media.iter().all(|media| media.evaluate(device))
+    }
+}
+
+/// The text of a style sheet and those it imports, split up at their `@media` rules.
+#[deriving(Clone)]
+pub struct StylesheetSource {
+    url: Url,
+    parts: ~[StylesheetPart],
+    /// The text of the style sheet's own rules, after its `@import` rules.
+    text: ~str,
+    /// How many of the parts come from the style sheets it imports.
+    imported_parts: uint,
+    /// The media lists the whole style sheet has been restricted to.
+    media: ~[MediaQueryList],
+}
+
+impl StylesheetSource {
+    pub fn new(url: Url, text: &str) -> StylesheetSource {
+        StylesheetSource::with_imports(url, ~[], text)
+    }
+
+    /// Makes a style sheet whose `@import` rules were taken off its text by `take_imports`, out
+    /// of the style sheets they import and the media lists of the rules. The imported rules
+    /// come first in the cascade, in the order they were imported.
+    pub fn with_imports(url: Url, imports: ~[(StylesheetSource, MediaQueryList)], text: &str)
+                        -> StylesheetSource {
+        let mut parts = ~[];
+        for (imported, media) in imports.move_iter() {
+            let mut imported = imported;
+            imported.restrict_to(&media);
+            parts.push_all_move(imported.parts);
+        }
+        let imported_parts = parts.len();
+        parts.push_all_move(split_media_rules(&url, text));
+        StylesheetSource {
+            url: url,
+            parts: parts,
+            text: text.to_owned(),
+            imported_parts: imported_parts,
+            media: ~[],
+        }
+    }
+
+    /// Makes all of the style sheet apply only where `media` matches, like the `media` attribute
+    /// of the element it came from does.
+    pub fn restrict_to(&mut self, media: &MediaQueryList) {
+        if media.queries.is_empty() {
+            return;
+        }
+        for part in self.parts.mut_iter() {
+            part.media.push(media.clone());
+        }
+        self.media.push(media.clone());
+    }
+
+    /// Replaces the style sheet's own rules with `text`, keeping the style sheets it imports and
+    /// the media lists it has been restricted to.
+    pub fn set_text(&mut self, text: &str) {
+        self.parts.truncate(self.imported_parts);
+        for part in split_media_rules(&self.url, text).move_iter() {
+            let mut part = part;
+            part.media.push_all(self.media);
+            self.parts.push(part);
+        }
+        self.text = text.to_owned();
+    }
+
+    /// The text of the rules that apply to `device`, with the `@import` and `@media` rules taken
+    /// out. There's a style sheet for each run of rules from the same URL, with that URL.
+    pub fn sheets_for(&self, device: &Device) -> ~[(Url, ~str)] {
+        let mut sheets: ~[(Url, ~str)] = ~[];
+        for part in self.parts.iter() {
+            if !part.applies_to(device) {
+                loop;
+            }
+            let same_url = match sheets.last_opt() {
+                Some(&(ref url, _)) => *url == part.url,
+                None => false,
+            };
+            if same_url {
+                let last = sheets.len() - 1;
+                match sheets[last] {
+                    (_, ref mut text) => {
+                        text.push_char('\n');
+                        text.push_str(part.text.as_slice());
+                    }
+                }
+            } else {
+                sheets.push((part.url.clone(), part.text.clone()));
+            }
+        }
+        sheets
+    }
+
+    /// Whether each of the rules with media lists applies to `device`, in order. `sheets_for`
+    /// gives the same style sheets for two devices as long as this is the same for them.
+    pub fn media_matches(&self, device: &Device) -> ~[bool] {
+        self.parts.iter().filter_map(|part| {
+            if part.media.is_empty() {
+                None
+            } else {
+                Some(part.applies_to(device))
+            }
+        }).collect()
+    }
+}
+
+/// An `@import` rule.
+pub struct Import {
+    /// The URL of the style sheet to import, as it was written.
+    href: ~str,
+    media: MediaQueryList,
+}
+
+/// Takes the `@charset` and `@import` rules off the start of the text of a style sheet, and
+/// returns the imports and the rest of the text. An `@import` after any other rule is invalid,
+/// and is left for the parser to drop.
+pub fn take_imports(text: &str) -> (~[Import], ~str) {
+    let bytes = text.as_bytes();
+    let mut imports = ~[];
+    let mut position = skip_whitespace_and_comments(bytes, 0);
+    loop {
+        let is_charset = is_at_rule(bytes, position, "charset");
+        if !is_charset && !is_at_rule(bytes, position, "import") {
+            break;
+        }
+        let end = find_outside_blocks(bytes, position, [';' as u8]);
+        if !is_charset {
+            match parse_import(text.slice(position + 7, end)) {
+                Some(import) => imports.push(import),
+                None => {}
+            }
+        }
+        position = if end < bytes.len() { end + 1 } else { end };
+        position = skip_whitespace_and_comments(bytes, position);
+    }
+    (imports, text.slice_from(position).to_owned())
+}
+
+/// Splits the text of a style sheet into its top-level rules, trimmed. A rule runs to the end of
+/// its block, or to a `;` before any block for at-rules like `@import`.
+pub fn split_rules(text: &str) -> ~[~str] {
+    let bytes = text.as_bytes();
+    let mut rules = ~[];
+    let mut position = skip_whitespace_and_comments(bytes, 0);
+    while position < bytes.len() {
+        let mut end = find_outside_blocks(bytes, position, ['{' as u8, ';' as u8]);
+        if end < bytes.len() && bytes[end] == '{' as u8 {
+            // A block that isn't closed runs to the end of the style sheet.
+            end = find_outside_blocks(bytes, end + 1, ['}' as u8]);
+        }
+        end = if end < bytes.len() { end + 1 } else { end };
+        rules.push(text.slice(position, end).trim().to_owned());
+        position = skip_whitespace_and_comments(bytes, end);
+    }
+    rules
+}
+
+/// Parses what follows `@import`: a URL or a string, and a list of media queries.
+fn parse_import(prelude: &str) -> Option<Import> {
+    let prelude = prelude.trim();
+    let is_url = prelude.len() >= 4 && prelude.is_char_boundary(4) &&
+        prelude.slice_to(4).to_ascii_lower() == ~"url(";
+    let (href, rest) = if is_url {
+        let end = match prelude.find(')') {
+            Some(end) => end,
+            None => return None,
+        };
+        let href = prelude.slice(4, end).trim();
+        let href = if href.starts_with("\"") || href.starts_with("'") {
+            if href.len() < 2 || href.char_at(href.len() - 1) != href.char_at(0) {
+                return None;
+            }
+            href.slice(1, href.len() - 1)
+        } else {
+            href
+        };
+        (href, prelude.slice_from(end + 1))
+    } else if prelude.starts_with("\"") || prelude.starts_with("'") {
+        let quote = prelude.char_at(0);
+        let end = match prelude.slice_from(1).find(quote) {
+            Some(end) => end + 1,
+            None => return None,
+        };
+        (prelude.slice(1, end), prelude.slice_from(end + 1))
+    } else {
+        return None;
+    };
+    Some(Import {
+        href: href.to_owned(),
+        media: MediaQueryList::parse(rest),
+    })
+}
+
+/// Skips whitespace, comments and the `<!--` and `-->` that can hide a style sheet from very
+/// old browsers.
+fn skip_whitespace_and_comments(bytes: &[u8], from: uint) -> uint {
+    let mut index = from;
+    loop {
+        let rest = bytes.slice_from(index);
+        if !rest.is_empty() && (rest[0] as char).is_whitespace() {
+            index += 1;
+        } else if rest.starts_with(bytes!("<!--")) {
+            index += 4;
+        } else if rest.starts_with(bytes!("-->")) {
+            index += 3;
+        } else if rest.starts_with(bytes!("/*")) {
+            index += 2;
+            while index < bytes.len() && !bytes.slice_from(index).starts_with(bytes!("*/")) {
+                index += 1;
+            }
+            index = if index < bytes.len() { index + 2 } else { index };
+        } else {
+            return index;
+        }
+    }
+}
+
+/// Finds the first of `targets` at or after `from` that isn't in a string, a comment or a block,
+/// or returns the length of `bytes` if there isn't one.
+fn find_outside_blocks(bytes: &[u8], from: uint, targets: &[u8]) -> uint {
+    let mut index = from;
+    let mut depth = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if depth == 0 && targets.contains(&byte) {
+            return index;
+        }
+        if byte == '"' as u8 || byte == '\'' as u8 {
+            // Strings end at the matching quote, or at the end of the line.
+            index += 1;
+            while index < bytes.len() && bytes[index] != byte && bytes[index] != '\n' as u8 {
+                if bytes[index] == '\\' as u8 {
+                    index += 1;
+                }
+                index += 1;
+            }
+        } else if byte == '/' as u8 && index + 1 < bytes.len() && bytes[index + 1] == '*' as u8 {
+            index += 2;
+            while index + 1 < bytes.len() &&
+                    !(bytes[index] == '*' as u8 && bytes[index + 1] == '/' as u8) {
+                index += 1;
+            }
+            index += 1;
+        } else if byte == '{' as u8 {
+            depth += 1;
+        } else if byte == '}' as u8 && depth > 0 {
+            depth -= 1;
+        }
+        index += 1;
+    }
+    bytes.len()
+}
+
+/// Whether there's an at-rule with the given name, in lower case, at `index`.
+fn is_at_rule(bytes: &[u8], index: uint, name: &str) -> bool {
+    let end = index + 1 + name.len();
+    if end > bytes.len() || bytes[index] != '@' as u8 {
+        return false;
+    }
+    let keyword = bytes.slice(index + 1, end);
+    let keyword_matches = keyword.iter().zip(name.as_bytes().iter()).all(|(&byte, &lower)| {
+        byte == lower || byte == lower - 32
+    });
+    keyword_matches && (end == bytes.len() || !(bytes[end] == '-' as u8 ||
+                                                bytes[end] == '_' as u8 ||
+                                                bytes[end] >= 0x80 ||
+                                                (bytes[end] as char).is_alphanumeric()))
+}
+
+/// Splits the text of a style sheet at its `@media` rules. Only those at the top level are split
+/// out; an `@media` rule without a block is left alone for the parser to drop.
+fn split_media_rules(url: &Url, text: &str) -> ~[StylesheetPart] {
+    let bytes = text.as_bytes();
+    let mut parts = ~[];
+    let mut start = 0;
+    let mut position = 0;
+    loop {
+        let at = find_outside_blocks(bytes, position, ['@' as u8]);
+        if at == bytes.len() {
+            break;
+        }
+        if !is_at_rule(bytes, at, "media") {
+            position = at + 1;
+            loop;
+        }
+        let open = find_outside_blocks(bytes, at, ['{' as u8, ';' as u8]);
+        if open == bytes.len() || bytes[open] == ';' as u8 {
+            position = open;
+            loop;
+        }
+        // A block that isn't closed runs to the end of the style sheet.
+        let close = find_outside_blocks(bytes, open + 1, ['}' as u8]);
+
+        if at > start {
+            parts.push(StylesheetPart {
+                url: url.clone(),
+                media: ~[],
+                text: text.slice(start, at).to_owned(),
+            });
+        }
+        parts.push(StylesheetPart {
+            url: url.clone(),
+            media: ~[MediaQueryList::parse(text.slice(at + 6, open))],
+            text: text.slice(open + 1, close).to_owned(),
+        });
+        position = if close < bytes.len() { close + 1 } else { close };
+        start = position;
+    }
+    if start < bytes.len() {
+        parts.push(StylesheetPart {
+            url: url.clone(),
+            media: ~[],
+            text: text.slice_from(start).to_owned(),
+        });
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extra::url::Url;
+    use geom::size::Size2D;
+    use std::from_str::FromStr;
+
+    fn device(width: f32, height: f32) -> Device {
+        Device::screen(Size2D(width, height), 1.0)
+    }
+
+    fn matches(text: &str, device: &Device) -> bool {
+        MediaQueryList::parse(text).evaluate(device)
+    }
+
+    #[test]
+    fn media_types() {
+        let screen = device(800.0, 600.0);
+        assert!(matches("", &screen));
+        assert!(matches("screen", &screen));
+        assert!(matches("all", &screen));
+        assert!(matches("print, SCREEN", &screen));
+        assert!(matches("only screen", &screen));
+        assert!(matches("not print", &screen));
+        assert!(!matches("print", &screen));
+        assert!(!matches("tv", &screen));
+        assert!(!matches("not screen", &screen));
+    }
+
+    #[test]
+    fn sizes() {
+        let screen = device(800.0, 600.0);
+        assert!(matches("screen and (min-width: 600px)", &screen));
+        assert!(matches("(max-width: 800px)", &screen));
+        assert!(matches("(min-width: 50em) and (max-height: 40em)", &screen));
+        assert!(matches("(width: 800px) and (height: 600px)", &screen));
+        assert!(matches("(width)", &screen));
+        assert!(!matches("screen and (min-width: 801px)", &screen));
+        assert!(!matches("(max-height: 599px)", &screen));
+        assert!(!matches("not screen and (min-width: 600px)", &screen));
+        assert!(matches("not screen and (min-width: 900px)", &screen));
+    }
+
+    #[test]
+    fn orientation_and_ratio() {
+        let landscape = device(800.0, 600.0);
+        let portrait = device(600.0, 800.0);
+        assert!(matches("(orientation: landscape)", &landscape));
+        assert!(matches("(orientation: portrait)", &portrait));
+        assert!(!matches("(orientation: portrait)", &landscape));
+        assert!(matches("(orientation)", &portrait));
+
+        let hidpi = Device::screen(Size2D(800.0f32, 600.0f32), 2.0);
+        assert!(matches("(min-device-pixel-ratio: 1.5)", &hidpi));
+        assert!(matches("(-webkit-min-device-pixel-ratio: 2)", &hidpi));
+        assert!(matches("(min--moz-device-pixel-ratio: 2)", &hidpi));
+        assert!(!matches("(min-device-pixel-ratio: 1.5)", &landscape));
+    }
+
+    #[test]
+    fn malformed() {
+        let screen = device(800.0, 600.0);
+        assert!(MediaQuery::parse("screen and").is_none());
+        assert!(MediaQuery::parse("screen (min-width: 1px)").is_none());
+        assert!(MediaQuery::parse("(min-width)").is_none());
+        assert!(MediaQuery::parse("(min-width: 10)").is_none());
+        assert!(MediaQuery::parse("(min-width: 10px").is_none());
+        assert!(MediaQuery::parse("(colour: red)").is_none());
+        assert!(MediaQuery::parse("only (width)").is_none());
+        // A malformed query doesn't stop the rest of the list from matching.
+        assert!(!matches("screen and", &screen));
+        assert!(matches("screen and, screen", &screen));
+    }
+
+    #[test]
+    fn splitting() {
+        let url: Url = FromStr::from_str("http://example.com/style.css").unwrap();
+        let source = StylesheetSource::new(url, "a { color: red }\n\
+            @media screen and (min-width: 600px) { p { color: blue } div { margin: 0 } }\n\
+            b { content: \"@media print {\" }\n\
+            @MEDIA print { p { color: black } }\n\
+            i { color: green }");
+
+        let wide = device(800.0, 600.0);
+        let narrow = device(400.0, 600.0);
+        assert!(source.media_matches(&wide) == ~[true, false]);
+        assert!(source.media_matches(&narrow) == ~[false, false]);
+
+        let sheets = source.sheets_for(&wide);
+        assert!(sheets.len() == 1);
+        let (_, ref text) = sheets[0];
+        assert!(text.contains("color: red"));
+        assert!(text.contains(" p { color: blue } div { margin: 0 } "));
+        assert!(text.contains("b { content: \"@media print {\" }"));
+        assert!(!text.contains("black"));
+        assert!(!text.contains("@media"));
+        assert!(text.contains("color: green"));
+        let narrow_sheets = source.sheets_for(&narrow);
+        let (_, ref text) = narrow_sheets[0];
+        assert!(!text.contains("blue"));
+    }
+
+    #[test]
+    fn imports() {
+        let (imports, rest) = take_imports("@charset \"utf-8\";\n\
+            /* First */ @import url(\"a.css\");\n\
+            @IMPORT 'b.css' screen and (min-width: 600px);\n\
+            @import url(c.css) print, tv;\n\
+            @import nothing;\n\
+            p { color: red }\n\
+            @import \"late.css\";");
+        assert!(imports.len() == 3);
+        assert!(imports[0].href == ~"a.css");
+        assert!(imports[0].media.queries.is_empty());
+        assert!(imports[1].href == ~"b.css");
+        assert!(imports[1].media.evaluate(&device(800.0, 600.0)));
+        assert!(!imports[1].media.evaluate(&device(400.0, 600.0)));
+        assert!(imports[2].href == ~"c.css");
+        assert!(!imports[2].media.evaluate(&device(800.0, 600.0)));
+        assert!(rest.starts_with("p { color: red }"));
+        assert!(rest.contains("late.css"));
+    }
+
+    #[test]
+    fn imported_parts() {
+        let a: Url = FromStr::from_str("http://example.com/a.css").unwrap();
+        let b: Url = FromStr::from_str("http://example.com/b.css").unwrap();
+        let page: Url = FromStr::from_str("http://example.com/page.css").unwrap();
+        let imported_a = StylesheetSource::new(a.clone(), "a { color: red }");
+        let imported_b = StylesheetSource::new(b.clone(), "b { color: red } \
+            @media (orientation: portrait) { i { color: red } }");
+        let source = StylesheetSource::with_imports(page.clone(),
+            ~[(imported_a, MediaQueryList::parse("")),
+              (imported_b, MediaQueryList::parse("(min-width: 600px)"))],
+            "p { color: blue }");
+
+        let wide = device(800.0, 600.0);
+        assert!(source.media_matches(&wide) == ~[true, false]);
+        let sheets = source.sheets_for(&wide);
+        assert!(sheets.len() == 3);
+        let urls: ~[Url] = sheets.iter().map(|&(ref url, _)| url.clone()).collect();
+        assert!(urls == ~[a, b, page]);
+        let (_, ref text) = sheets[1];
+        assert!(text.contains("b { color: red }"));
+        assert!(!text.contains("i { color: red }"));
+
+        // Neither of the parts of b.css apply to a narrow device.
+        let narrow = device(400.0, 800.0);
+        assert!(source.media_matches(&narrow) == ~[false, false]);
+        assert!(source.sheets_for(&narrow).len() == 2);
+    }
+    }
+
+    #[test]
+    fn rules() {
+        let rules = split_rules("/* Comment */ @charset \"utf-8\";\n\
+            p { content: \"}\" }\n\
+            @media print { p { color: black } i { color: red } }\n\
+            <!-- b{color:red}\n\
+            i { color: green");
+        assert!(rules == ~[~"@charset \"utf-8\";",
+                           ~"p { content: \"}\" }",
+                           ~"@media print { p { color: black } i { color: red } }",
+                           ~"b{color:red}",
+                           ~"i { color: green"]);
+        assert!(split_rules("  /* Nothing */ ").is_empty());
+    }
+
+    #[test]
+    fn replacing_text() {
+        let a: Url = FromStr::from_str("http://example.com/a.css").unwrap();
+        let page: Url = FromStr::from_str("http://example.com/page.css").unwrap();
+        let imported = StylesheetSource::new(a.clone(), "a { color: red }");
+        let mut source = StylesheetSource::with_imports(page.clone(),
+            ~[(imported, MediaQueryList::parse(""))], "p { color: blue }");
+        source.restrict_to(&MediaQueryList::parse("(min-width: 600px)"));
+        source.set_text("i { color: green } @media print { b { color: black } }");
+        assert!(source.text == ~"i { color: green } @media print { b { color: black } }");
+
+        let wide = device(800.0, 600.0);
+        let sheets = source.sheets_for(&wide);
+        let urls: ~[Url] = sheets.iter().map(|&(ref url, _)| url.clone()).collect();
+        assert!(urls == ~[a, page]);
+        let (_, ref text) = sheets[1];
+        assert!(text.contains("color: green"));
+        assert!(!text.contains("blue"));
+        assert!(!text.contains("black"));
+        assert!(source.sheets_for(&device(400.0, 600.0)).is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The memory profiler: a task that asks each part of the engine that registered as a reporter
+//! how much it's holding on to, and prints the answers side by side.
+
+use std::cell::Cell;
+use std::comm::{Chan, Port, SharedChan, stream};
+
+#[deriving(Clone)]
+pub struct MemoryProfilerChan {
+    chan: SharedChan<MemoryProfilerMsg>,
+}
+
+impl MemoryProfilerChan {
+    pub fn new(chan: Chan<MemoryProfilerMsg>) -> MemoryProfilerChan {
+        MemoryProfilerChan {
+            chan: SharedChan::new(chan),
+        }
+    }
+    pub fn send(&self, msg: MemoryProfilerMsg) {
+        self.chan.send(msg);
+    }
+}
+
+/// What a measurement counts.
+#[deriving(Clone, Eq)]
+pub enum ReportUnit {
+    Bytes,
+    /// Things, like nodes or cache entries, whose size isn't known.
+    Items,
+}
+
+/// One measurement of a reporter, named like `image-cache/decoded`.
+pub struct MemoryReport {
+    path: ~str,
+    amount: uint,
+    unit: ReportUnit,
+}
+
+/// Asks a reporter for its measurements, which it sends on the given channel. A reporter is
+/// usually a task, which this forwards the request to as one of its own messages.
+pub type MemoryReporter = ~fn(Chan<~[MemoryReport]>);
+
+pub enum MemoryProfilerMsg {
+    /// Adds a reporter under a name, replacing any other registered under that name.
+    RegisterReporterMsg(~str, MemoryReporter),
+    /// Removes the reporter with the given name, as when its task exits.
+    UnregisterReporterMsg(~str),
+    /// Collects reports from all the reporters and prints them.
+    PrintMsg,
+}
+
+pub struct MemoryProfiler {
+    port: Port<MemoryProfilerMsg>,
+    reporters: ~[(~str, MemoryReporter)],
+}
+
+impl MemoryProfiler {
+    pub fn create(port: Port<MemoryProfilerMsg>) {
+        let port = Cell::new(port);
+        do spawn {
+            let mut profiler = MemoryProfiler::new(port.take());
+            profiler.start();
+        }
+    }
+
+    pub fn new(port: Port<MemoryProfilerMsg>) -> MemoryProfiler {
+        MemoryProfiler {
+            port: port,
+            reporters: ~[],
+        }
+    }
+
+    pub fn start(&mut self) {
+        loop {
+            match self.port.try_recv() {
+                Some(msg) => self.handle_msg(msg),
+                None => break
+            }
+        }
+    }
+
+    fn handle_msg(&mut self, msg: MemoryProfilerMsg) {
+        match msg {
+            RegisterReporterMsg(name, reporter) => {
+                self.unregister(name.as_slice());
+                self.reporters.push((name, reporter));
+            }
+            UnregisterReporterMsg(name) => self.unregister(name.as_slice()),
+            PrintMsg => self.print_reports(),
+        }
+    }
+
+    fn unregister(&mut self, name: &str) {
+        match self.reporters.iter().position(|&(ref reporter_name, _)| {
+            reporter_name.as_slice() == name
+        }) {
+            Some(index) => {
+                self.reporters.remove(index);
+            }
+            None => {}
+        }
+    }
+
+    fn print_reports(&mut self) {
+        println(fmt!("%-50s %15s", "_reporter_", "_amount_"));
+        // A reporter whose task has gone away drops the channel without answering.
+        let mut gone = ~[];
+        for &(ref name, ref reporter) in self.reporters.iter() {
+            let (port, chan) = stream();
+            (*reporter)(chan);
+            match port.try_recv() {
+                Some(reports) => {
+                    for report in reports.iter() {
+                        let amount = match report.unit {
+                            Bytes => fmt!("%.2f MB", (report.amount as float) / 1048576f),
+                            Items => fmt!("%u", report.amount),
+                        };
+                        println(fmt!("%-50s %15s", fmt!("%s/%s", *name, report.path), amount));
+                    }
+                }
+                None => gone.push(name.clone()),
+            }
+        }
+        for name in gone.iter() {
+            self.unregister(name.as_slice());
+        }
+        println("");
+    }
+}
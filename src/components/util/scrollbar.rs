@@ -0,0 +1,149 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The geometry of scrollbars, shared by the compositor, which draws the window's, and layout and
+//! script, which draw and drive those of boxes that scroll their overflow. Lengths are in px, and
+//! offsets are how far the content has scrolled from its start.
+
+/// How thick scrollbars are.
+pub static SCROLLBAR_WIDTH: f32 = 12.0;
+/// How short the thumb can get, so that it stays easy to grab however long the content is.
+static MIN_THUMB_LENGTH: f32 = 20.0;
+
+/// The parts of a scrollbar the mouse can be pressed on.
+#[deriving(Clone, Eq)]
+pub enum ScrollbarPart {
+    /// The part of the track before the thumb, which scrolls back a page when clicked.
+    TrackBefore,
+    Thumb,
+    /// The part of the track after the thumb, which scrolls on a page when clicked.
+    TrackAfter,
+}
+
+/// A scrollbar along one axis.
+#[deriving(Clone, Eq)]
+pub struct Scrollbar {
+    /// How long the track the thumb moves along is.
+    track_length: f32,
+    /// How much of the content can be seen at once.
+    view_length: f32,
+    /// How long the content is.
+    content_length: f32,
+}
+
+impl Scrollbar {
+    pub fn new(track_length: f32, view_length: f32, content_length: f32) -> Scrollbar {
+        Scrollbar {
+            track_length: track_length,
+            view_length: view_length,
+            content_length: content_length,
+        }
+    }
+
+    /// Whether there's more content than fits in the view, so that there's something to scroll.
+    pub fn is_needed(&self) -> bool {
+        self.content_length > self.view_length
+    }
+
+    /// How far the content can scroll.
+    pub fn max_offset(&self) -> f32 {
+        (self.content_length - self.view_length).max(&0.0)
+    }
+
+    /// Keeps `offset` within the content.
+    pub fn clamp_offset(&self, offset: f32) -> f32 {
+        offset.max(&0.0).min(&self.max_offset())
+    }
+
+    /// Where the thumb starts along the track, and how long it is, with the content scrolled by
+    /// `offset`. The thumb is as much of the track as the view is of the content.
+    pub fn thumb(&self, offset: f32) -> (f32, f32) {
+        if !self.is_needed() {
+            return (0.0, self.track_length)
+        }
+        let length = (self.track_length * self.view_length / self.content_length)
+            .max(&MIN_THUMB_LENGTH.min(&self.track_length));
+        let travel = self.track_length - length;
+        (travel * self.clamp_offset(offset) / self.max_offset(), length)
+    }
+
+    /// The part of the scrollbar at `position` along the track, with the content scrolled by
+    /// `offset`.
+    pub fn part_at(&self, position: f32, offset: f32) -> ScrollbarPart {
+        let (start, length) = self.thumb(offset);
+        if position < start {
+            TrackBefore
+        } else if position < start + length {
+            Thumb
+        } else {
+            TrackAfter
+        }
+    }
+
+    /// How far to scroll the content for the thumb to start at `thumb_start` along the track, as
+    /// when the user drags it there.
+    pub fn offset_for_thumb_at(&self, thumb_start: f32) -> f32 {
+        let (_, length) = self.thumb(0.0);
+        let travel = self.track_length - length;
+        if travel <= 0.0 {
+            return 0.0
+        }
+        self.clamp_offset(thumb_start * self.max_offset() / travel)
+    }
+
+    /// Where clicking the track at `part` scrolls the content by `offset` to: a view's length
+    /// back or on.
+    pub fn offset_for_track_click(&self, part: ScrollbarPart, offset: f32) -> f32 {
+        match part {
+            TrackBefore => self.clamp_offset(offset - self.view_length),
+            TrackAfter => self.clamp_offset(offset + self.view_length),
+            Thumb => offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumb_is_proportional() {
+        let scrollbar = Scrollbar::new(100.0, 100.0, 400.0);
+        assert!(scrollbar.is_needed());
+        assert!(scrollbar.thumb(0.0) == (0.0, 25.0));
+        assert!(scrollbar.thumb(300.0) == (75.0, 25.0));
+        // Offsets past the content are taken as its end.
+        assert!(scrollbar.thumb(1000.0) == (75.0, 25.0));
+
+        let short = Scrollbar::new(100.0, 100.0, 50.0);
+        assert!(!short.is_needed());
+        assert!(short.thumb(0.0) == (0.0, 100.0));
+    }
+
+    #[test]
+    fn thumb_has_a_minimum_length() {
+        let scrollbar = Scrollbar::new(100.0, 100.0, 100000.0);
+        let (_, length) = scrollbar.thumb(0.0);
+        assert!(length == 20.0);
+    }
+
+    #[test]
+    fn parts() {
+        let scrollbar = Scrollbar::new(100.0, 100.0, 400.0);
+        assert!(scrollbar.part_at(10.0, 0.0) == Thumb);
+        assert!(scrollbar.part_at(50.0, 0.0) == TrackAfter);
+        assert!(scrollbar.part_at(10.0, 300.0) == TrackBefore);
+    }
+
+    #[test]
+    fn dragging_and_clicking() {
+        let scrollbar = Scrollbar::new(100.0, 100.0, 400.0);
+        assert!(scrollbar.offset_for_thumb_at(75.0) == 300.0);
+        assert!(scrollbar.offset_for_thumb_at(-10.0) == 0.0);
+        assert!(scrollbar.offset_for_thumb_at(500.0) == 300.0);
+        assert!(scrollbar.offset_for_track_click(TrackAfter, 0.0) == 100.0);
+        assert!(scrollbar.offset_for_track_click(TrackAfter, 250.0) == 300.0);
+        assert!(scrollbar.offset_for_track_click(TrackBefore, 50.0) == 0.0);
+    }
+}
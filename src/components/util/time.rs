@@ -34,7 +34,9 @@ pub enum ProfilerCategory {
     LayoutAuxInitCategory,
     LayoutSelectorMatchCategory,
     LayoutTreeBuilderCategory,
-    LayoutMainCategory,
+    LayoutBubbleWidthsCategory,
+    LayoutAssignWidthsCategory,
+    LayoutAssignHeightsCategory,
     LayoutShapingCategory,
     LayoutDispListBuildCategory,
     GfxRegenAvailableFontsCategory,
@@ -45,20 +47,31 @@ pub enum ProfilerCategory {
     NUM_BUCKETS,
 }
 // FIXME(#5873) this should be initialized by a NUM_BUCKETS cast,
-static BUCKETS: uint = 13;
+static BUCKETS: uint = 15;
 type ProfilerBuckets = [(ProfilerCategory, ~[float]), ..BUCKETS];
 
 pub enum ProfilerMsg {
     // Normal message used for reporting time
     TimeMsg(ProfilerCategory, float),
+    // Message used to report how long laying out the page at a URL took
+    UrlTimeMsg(~str, float),
     // Message used to force print the profiling metrics
     PrintMsg,
 }
 
+// how many times the page at a URL was laid out, and how long that took altogether
+struct UrlTotal {
+    url: ~str,
+    count: uint,
+    total: float,
+}
+
 // back end of the profiler that handles data aggregation and performance metrics
 pub struct Profiler {
     port: Port<ProfilerMsg>,
     buckets: ProfilerBuckets,
+    // total layout time for each URL, in the order the URLs were first laid out
+    url_totals: ~[UrlTotal],
     last_msg: Option<ProfilerMsg>,
 }
 
@@ -78,7 +91,9 @@ impl ProfilerCategory {
             (LayoutAuxInitCategory, ~[]),
             (LayoutSelectorMatchCategory, ~[]),
             (LayoutTreeBuilderCategory, ~[]),
-            (LayoutMainCategory, ~[]),
+            (LayoutBubbleWidthsCategory, ~[]),
+            (LayoutAssignWidthsCategory, ~[]),
+            (LayoutAssignHeightsCategory, ~[]),
             (LayoutShapingCategory, ~[]),
             (LayoutDispListBuildCategory, ~[]),
             (GfxRegenAvailableFontsCategory, ~[]),
@@ -105,7 +120,9 @@ impl ProfilerCategory {
     pub fn format(self) -> ~str {
         let padding = match self {
             LayoutAuxInitCategory | LayoutSelectorMatchCategory | LayoutTreeBuilderCategory |
-            LayoutMainCategory | LayoutDispListBuildCategory | LayoutShapingCategory=> " - ",
+            LayoutBubbleWidthsCategory | LayoutAssignWidthsCategory |
+            LayoutAssignHeightsCategory | LayoutDispListBuildCategory |
+            LayoutShapingCategory => " - ",
             _ => ""
         };
         fmt!("%s%?", padding, self)
@@ -125,6 +142,7 @@ impl Profiler {
         Profiler {
             port: port,
             buckets: ProfilerCategory::empty_buckets(),
+            url_totals: ~[],
             last_msg: None,
         }
     }
@@ -145,9 +163,25 @@ impl Profiler {
                 //TODO(tkuehn): would be nice to have tuple.second_mut()
                 (_, ref mut data) => data.push(t),
             },
+            UrlTimeMsg(ref url, t) => {
+                match self.url_totals.mut_iter().find(|total| total.url == *url) {
+                    Some(total) => {
+                        total.count += 1;
+                        total.total += t;
+                    }
+                    None => self.url_totals.push(UrlTotal {
+                        url: url.clone(),
+                        count: 1,
+                        total: t,
+                    }),
+                }
+            }
             PrintMsg => match self.last_msg {
                 // only print if more data has arrived since the last printout
-                Some(TimeMsg(*)) => self.print_buckets(),
+                Some(TimeMsg(*)) | Some(UrlTimeMsg(*)) => {
+                    self.print_buckets();
+                    self.print_url_totals();
+                }
                 _ => {}
             },
         };
@@ -176,6 +210,19 @@ impl Profiler {
         }
         println("");
     }
+
+    fn print_url_totals(&self) {
+        if self.url_totals.is_empty() {
+            return
+        }
+        println(fmt!("%15s %15s %15s  %s", "_layouts_", "_total (ms)_", "_mean (ms)_", "_url_"));
+        for total in self.url_totals.iter() {
+            println(fmt!("%15u %15.4f %15.4f  %s",
+                         total.count, total.total, total.total / (total.count as float),
+                         total.url));
+        }
+        println("");
+    }
 }
 
 
@@ -191,6 +238,21 @@ pub fn profile<T>(category: ProfilerCategory,
     return val;
 }
 
+// like `profile`, also counting the time towards the page at `url`
+pub fn profile_url<T>(category: ProfilerCategory,
+                      url: ~str,
+                      profiler_chan: ProfilerChan,
+                      callback: &fn() -> T)
+                      -> T {
+    let start_time = precise_time_ns();
+    let val = callback();
+    let end_time = precise_time_ns();
+    let ms = ((end_time - start_time) as float / 1000000f);
+    profiler_chan.send(TimeMsg(category, ms));
+    profiler_chan.send(UrlTimeMsg(url, ms));
+    return val;
+}
+
 pub fn time<T>(msg: &str, callback: &fn() -> T) -> T{
     let start_time = precise_time_ns();
     let val = callback();
@@ -114,6 +114,12 @@ pub trait TreeNodeRef<Node>: Clone {
     fn set_next_sibling(node: &mut Node, new_next_sibling: Option<Self>);
 
 
+    /// Called once `new_child` has been inserted among this node's children. Does nothing by
+    /// default.
+    fn child_inserted(&self, _new_child: &Self) {
+    }
+
+
     // The tree utilities, operating on refs mostly.
 
     /// Returns true if this node is disconnected from the tree or has no children.
@@ -148,6 +154,39 @@ pub trait TreeNodeRef<Node>: Clone {
                 set!(new_child_node, set_parent_node, Some((*self).clone()));
             }
         }
+        self.child_inserted(&new_child);
+    }
+
+    /// Adds a new child to this node's list of children, right before `before`.
+    ///
+    /// Fails unless `new_child` is disconnected from the tree and `before` is a child of this
+    /// node. (FIXME: The latter is not yet checked.)
+    fn insert_before(&self, new_child: Self, before: Self) {
+        do self.with_mut_base |this_node| {
+            do new_child.with_mut_base |new_child_node| {
+                assert!((get!(new_child_node, parent_node)).is_none());
+                assert!((get!(new_child_node, prev_sibling)).is_none());
+                assert!((get!(new_child_node, next_sibling)).is_none());
+
+                do before.with_mut_base |before_node| {
+                    match get!(before_node, prev_sibling) {
+                        None => set!(this_node, set_first_child, Some(new_child.clone())),
+                        Some(prev_sibling) => {
+                            do prev_sibling.with_mut_base |prev_sibling_node| {
+                                set!(prev_sibling_node, set_next_sibling, Some(new_child.clone()));
+                                set!(new_child_node, set_prev_sibling, Some(prev_sibling.clone()));
+                            }
+                        }
+                    }
+
+                    set!(before_node, set_prev_sibling, Some(new_child.clone()));
+                    set!(new_child_node, set_next_sibling, Some(before.clone()));
+                }
+
+                set!(new_child_node, set_parent_node, Some((*self).clone()));
+            }
+        }
+        self.child_inserted(&new_child);
     }
 
     /// Removes the given child from this node's list of children.
@@ -30,7 +30,12 @@ pub fn make_url(str_url: ~str, current_url: Option<Url>) -> Url {
         } else {
             let current_url = current_url.unwrap();
             debug!("make_url: current_url: %?", current_url);
-            if str_url.starts_with("//") {
+            if str_url.starts_with("#") {
+                // A fragment alone points into the current page.
+                let mut url = current_url.clone();
+                url.fragment = Some(str_url.slice_from(1).to_owned());
+                return url;
+            } else if str_url.starts_with("//") {
                 current_url.scheme + ":" + str_url
             } else if current_url.path.is_empty() ||
                       str_url.starts_with("/") {
@@ -58,6 +63,56 @@ pub fn make_url(str_url: ~str, current_url: Option<Url>) -> Url {
     url::from_str(str_url).unwrap()
 }
 
+/// Whether navigating from `current_url` to `url` stays on the same document, scrolling to the
+/// fragment `url` names: the two only differ in their fragments, and `url` has one.
+pub fn is_fragment_navigation(current_url: &Url, url: &Url) -> bool {
+    let mut without_fragment = url.clone();
+    without_fragment.fragment = current_url.fragment.clone();
+    url.fragment.is_some() && without_fragment == *current_url
+}
+
+/// The scheme, host and port of a URL, which decide what a document may access: documents may
+/// only read each other, and what they load, when their origins are the same.
+///
+/// FIXME: Ports aren't defaulted, so `http://example.com` and `http://example.com:80` are taken to
+/// be different origins. Pages from files all have the same origin, `file://`.
+#[deriving(Clone, Eq)]
+pub struct Origin {
+    scheme: ~str,
+    host: ~str,
+    port: Option<~str>,
+}
+
+impl Origin {
+    pub fn new(url: &Url) -> Origin {
+        Origin {
+            scheme: url.scheme.clone(),
+            host: url.host.clone(),
+            port: url.port.clone(),
+        }
+    }
+
+    /// Whether `url` is of this origin.
+    pub fn is_same_origin(&self, url: &Url) -> bool {
+        *self == Origin::new(url)
+    }
+}
+
+/// Serializes the origin as `postMessage` and the `origin` of message events use it.
+impl ToStr for Origin {
+    fn to_str(&self) -> ~str {
+        match self.port {
+            Some(ref port) => fmt!("%s://%s:%s", self.scheme, self.host, *port),
+            None => fmt!("%s://%s", self.scheme, self.host),
+        }
+    }
+}
+
+/// Whether the two URLs are of the same origin.
+pub fn is_same_origin(a: &Url, b: &Url) -> bool {
+    Origin::new(a).is_same_origin(b)
+}
+
 mod make_url_tests {
 
     #[test]
@@ -113,6 +168,45 @@ mod make_url_tests {
         assert!(new_url.path == ~"/snarf/crumpet.html");
     }
 
+    #[test]
+    fn should_create_url_based_on_old_url_with_fragment() {
+        let old_str = ~"http://example.com/snarf/index.html?a=b#top";
+        let old_url = make_url(old_str, None);
+        let new_str = ~"#crumpet";
+        let new_url = make_url(new_str, Some(old_url));
+        assert!(new_url.host == ~"example.com");
+        assert!(new_url.path == ~"/snarf/index.html");
+        assert!(new_url.query == ~[(~"a", ~"b")]);
+        assert!(new_url.fragment == Some(~"crumpet"));
+    }
+
+    #[test]
+    fn should_only_navigate_to_fragment_within_same_document() {
+        let current_url = make_url(~"http://example.com/index.html", None);
+        let fragment_url = make_url(~"#crumpet", Some(current_url.clone()));
+        let other_url = make_url(~"other.html#crumpet", Some(current_url.clone()));
+        assert!(is_fragment_navigation(&current_url, &fragment_url));
+        assert!(!is_fragment_navigation(&current_url, &other_url));
+        assert!(!is_fragment_navigation(&current_url, &current_url));
+    }
+
+    #[test]
+    fn should_serialize_origin_without_path() {
+        let url = make_url(~"http://example.com:8000/snarf/index.html?a=b#top", None);
+        assert!(Origin::new(&url).to_str() == ~"http://example.com:8000");
+        let url = make_url(~"https://example.com/", None);
+        assert!(Origin::new(&url).to_str() == ~"https://example.com");
+    }
+
+    #[test]
+    fn should_compare_scheme_host_and_port_of_origins() {
+        let url = make_url(~"http://example.com/index.html", None);
+        assert!(is_same_origin(&url, &make_url(~"http://example.com/snarf/crumpet.html?a=b", None)));
+        assert!(!is_same_origin(&url, &make_url(~"https://example.com/index.html", None)));
+        assert!(!is_same_origin(&url, &make_url(~"http://example.org/index.html", None)));
+        assert!(!is_same_origin(&url, &make_url(~"http://example.com:8000/index.html", None)));
+    }
+
 }
 
 pub type UrlMap<T> = @mut HashMap<Url, T>;
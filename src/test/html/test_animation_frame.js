@@ -0,0 +1,17 @@
+var frames = 0;
+var start = null;
+
+function step(timestamp) {
+  if (start === null)
+    start = timestamp;
+  frames++;
+  if (frames == 60) {
+    window.alert("60 animation frames in " + (timestamp - start) + "ms");
+    return;
+  }
+  window.requestAnimationFrame(step);
+}
+
+var cancelled = window.requestAnimationFrame(function() { window.alert("cancelled frame ran"); });
+window.cancelAnimationFrame(cancelled);
+window.requestAnimationFrame(step);
\ No newline at end of file
@@ -0,0 +1,8 @@
+window.alert("initial cookies: '" + document.cookie + "'");
+document.cookie = "a=1";
+document.cookie = "b=2; Max-Age=3600";
+window.alert("after setting a and b: '" + document.cookie + "'");
+document.cookie = "a=; Max-Age=0";
+window.alert("after expiring a: '" + document.cookie + "'");
+document.cookie = "c=3; HttpOnly";
+window.alert("HttpOnly cookies can't be set from script: '" + document.cookie + "'");
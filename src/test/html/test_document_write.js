@@ -0,0 +1,2 @@
+document.write("<p>Written by an external script, after " +
+               document.getElementById("written").tagName + ".</p>");
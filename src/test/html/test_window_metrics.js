@@ -0,0 +1,14 @@
+function report() {
+  window.alert("inner: " + window.innerWidth + "x" + window.innerHeight);
+  window.alert("outer: " + window.outerWidth + "x" + window.outerHeight);
+  window.alert("screen: " + window.screen.width + "x" + window.screen.height +
+               " (available " + window.screen.availWidth + "x" + window.screen.availHeight + ")");
+  window.alert("color depth: " + window.screen.colorDepth);
+  window.alert("device pixel ratio: " + window.devicePixelRatio);
+}
+
+report();
+window.addEventListener("resize", function(event) {
+  window.alert("resize event: " + event.type);
+  report();
+});
\ No newline at end of file
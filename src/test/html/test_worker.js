@@ -0,0 +1,6 @@
+self.onmessage = function(event) {
+    var count = event.data.count;
+    self.setTimeout(function() {
+        self.postMessage({ count: count, doubled: count * 2 });
+    }, 100);
+};
@@ -0,0 +1,24 @@
+var sync = new XMLHttpRequest();
+sync.open("GET", "test_xhr.html", false);
+sync.send();
+window.alert("sync: readyState " + sync.readyState + ", status " + sync.status + ", " + sync.responseText.length + " chars");
+
+var xhr = new XMLHttpRequest();
+xhr.onreadystatechange = function() {
+  window.alert("readyState " + xhr.readyState);
+  if (xhr.readyState == XMLHttpRequest.DONE)
+    window.alert("async: status " + xhr.status + " " + xhr.statusText + ": " + xhr.responseText);
+};
+xhr.open("GET", "test_xhr.html");
+xhr.setRequestHeader("X-Servo-Test", "1");
+xhr.send();
+
+var progress = new XMLHttpRequest();
+progress.onprogress = function(e) {
+  window.alert("progress: " + e.loaded + " of " + (e.lengthComputable ? e.total : "unknown") + " bytes");
+};
+progress.addEventListener("loadend", function(e) {
+  window.alert("loadend after " + e.loaded + " bytes");
+});
+progress.open("GET", "lipsum.html");
+progress.send();